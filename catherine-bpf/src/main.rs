@@ -0,0 +1,717 @@
+//! A small `bpftool`-like utility for inspecting and managing the BPF
+//! programs, maps, and links loaded into the kernel.
+
+use std::ffi::CString;
+use std::fs;
+use std::io::Error;
+use std::os::fd::AsFd as _;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::ptr;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use clap::ValueEnum;
+
+use libbpf_rs::btf::fmt::ValueFormatter;
+use libbpf_rs::disasm::Disassembler;
+use libbpf_rs::libbpf_sys;
+use libbpf_rs::query::LinkInfo;
+use libbpf_rs::query::LinkInfoIter;
+use libbpf_rs::query::LinkTypeInfo;
+use libbpf_rs::query::MapInfo;
+use libbpf_rs::query::MapInfoIter;
+use libbpf_rs::query::ProgInfoIter;
+use libbpf_rs::query::ProgInfoQueryOptions;
+use libbpf_rs::query::ProgramInfo;
+use libbpf_rs::Btf;
+use libbpf_rs::MapCore as _;
+use libbpf_rs::MapFlags;
+use libbpf_rs::MapHandle;
+use libbpf_rs::Program;
+use libbpf_rs::Xdp;
+use libbpf_rs::XdpFlags;
+
+mod scaffold;
+
+
+/// Inspect and manage BPF programs, maps, and links.
+#[derive(Debug, Parser)]
+#[command(name = "catherine-bpf", version)]
+struct Args {
+    /// Emit JSON instead of human readable output.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Inspect loaded programs.
+    #[command(subcommand)]
+    Prog(ProgCommand),
+    /// Inspect loaded maps.
+    #[command(subcommand)]
+    Map(MapCommand),
+    /// Inspect links.
+    #[command(subcommand)]
+    Link(LinkCommand),
+    /// Pin a program, map, or link to a path in the BPF file system.
+    Pin {
+        /// The kind of object to pin.
+        #[arg(value_enum)]
+        kind: ObjectKind,
+        /// The ID of the object to pin.
+        id: u32,
+        /// The path to pin the object at.
+        path: PathBuf,
+    },
+    /// Remove a pinned object from the BPF file system.
+    Unpin {
+        /// The path the object is pinned at.
+        path: PathBuf,
+    },
+    /// Attach and detach XDP programs.
+    #[command(subcommand)]
+    Xdp(XdpCommand),
+    /// Generate a new project with a BPF program and a user space
+    /// counterpart consuming its events.
+    New {
+        /// The name of the project, in snake case.
+        name: String,
+        /// The directory to create the project in; defaults to the name.
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ProgCommand {
+    /// List all loaded programs.
+    List,
+    /// Show details about the program with the given ID.
+    Show { id: u32 },
+    /// Disassemble the translated instructions of the program with the
+    /// given ID.
+    Dump { id: u32 },
+}
+
+#[derive(Debug, Subcommand)]
+enum MapCommand {
+    /// List all loaded maps.
+    List,
+    /// Show details about the map with the given ID.
+    Show { id: u32 },
+    /// Dump the contents of the map with the given ID, rendered based on
+    /// its BTF type information if available.
+    Dump { id: u32 },
+}
+
+#[derive(Debug, Subcommand)]
+enum LinkCommand {
+    /// List all links.
+    List,
+    /// Show details about the link with the given ID.
+    Show { id: u32 },
+}
+
+#[derive(Debug, Subcommand)]
+enum XdpCommand {
+    /// Attach a loaded XDP program to a network interface.
+    Attach {
+        /// The interface to attach to.
+        iface: String,
+        /// The ID of the program to attach.
+        #[arg(long, required_unless_present = "pinned", conflicts_with = "pinned")]
+        id: Option<u32>,
+        /// The path the program to attach is pinned at.
+        #[arg(long)]
+        pinned: Option<PathBuf>,
+        /// The mode to attach the program in.
+        #[arg(long, value_enum, default_value_t = Mode::Auto)]
+        mode: Mode,
+        /// Replace a program that is already attached.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Detach the XDP program from a network interface.
+    Detach {
+        /// The interface to detach from.
+        iface: String,
+        /// The mode the program was attached in.
+        #[arg(long, value_enum, default_value_t = Mode::Auto)]
+        mode: Mode,
+    },
+}
+
+/// The kind of a BPF object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ObjectKind {
+    Prog,
+    Map,
+    Link,
+}
+
+/// The mode to attach an XDP program in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Use the driver's native support, falling back to generic mode if
+    /// it is unavailable
+    Auto,
+    /// Run the program in the driver, before socket buffers are allocated
+    Native,
+    /// Run the program on socket buffers, supported by all drivers
+    Generic,
+    /// Run the program on the network device itself
+    Offload,
+}
+
+impl Mode {
+    fn flags(&self) -> XdpFlags {
+        match self {
+            Self::Auto => XdpFlags::NONE,
+            Self::Native => XdpFlags::DRV_MODE,
+            Self::Generic => XdpFlags::SKB_MODE,
+            Self::Offload => XdpFlags::HW_MODE,
+        }
+    }
+}
+
+
+fn print_json<T>(value: &T) -> Result<()>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let json = serde_json::to_string_pretty(value).context("failed to serialize output")?;
+    println!("{json}");
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn ifindex(iface: &str) -> Result<i32> {
+    let name = CString::new(iface).context("interface name contains NUL byte")?;
+    // SAFETY: `name` is a valid NUL terminated string.
+    let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if ifindex == 0 {
+        return Err(Error::last_os_error()).with_context(|| format!("unknown interface `{iface}`"));
+    }
+    Ok(ifindex as i32)
+}
+
+fn path_to_cstring(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("path `{}` contains NUL byte", path.display()))
+}
+
+/// Convert the return value of a libbpf function returning a file
+/// descriptor into an [`OwnedFd`].
+fn owned_fd(ret: i32) -> Result<OwnedFd, Error> {
+    if ret < 0 {
+        return Err(Error::from_raw_os_error(-ret));
+    }
+    // SAFETY: The file descriptor was just handed to us by libbpf.
+    Ok(unsafe { OwnedFd::from_raw_fd(ret) })
+}
+
+
+fn prog_list(json: bool) -> Result<()> {
+    let progs = ProgInfoIter::default().collect::<Vec<_>>();
+    if json {
+        return print_json(&progs);
+    }
+
+    println!(
+        "{:>6}  {:<16}  {:<24}  {:>12}  {:>14}",
+        "ID", "NAME", "TYPE", "RUNS", "RUNTIME_NS"
+    );
+    for prog in progs {
+        let ty = format!("{:?}", prog.ty);
+        println!(
+            "{:>6}  {:<16}  {ty:<24}  {:>12}  {:>14}",
+            prog.id,
+            prog.name.to_string_lossy(),
+            prog.run_cnt,
+            prog.run_time_ns,
+        );
+    }
+    Ok(())
+}
+
+fn find_prog(id: u32) -> Result<ProgramInfo> {
+    let opts = ProgInfoQueryOptions::default()
+        .include_map_ids(true)
+        .include_xlated_prog_insns(true)
+        .include_jited_prog_insns(true);
+    ProgInfoIter::with_query_opts(opts)
+        .find(|prog| prog.id == id)
+        .with_context(|| format!("no program with ID {id} found"))
+}
+
+fn prog_show(id: u32, json: bool) -> Result<()> {
+    let prog = find_prog(id)?;
+    if json {
+        return print_json(&prog);
+    }
+
+    println!("id:          {}", prog.id);
+    println!("name:        {}", prog.name.to_string_lossy());
+    println!("type:        {:?}", prog.ty);
+    println!("tag:         {}", hex(&prog.tag.0));
+    println!("gpl:         {}", prog.gpl_compatible);
+    println!("loaded at:   {}s after boot", prog.load_time.as_secs());
+    println!("uid:         {}", prog.created_by_uid);
+    println!("xlated:      {} bytes", prog.xlated_prog_insns.len());
+    println!("jited:       {} bytes", prog.jited_prog_insns.len());
+    println!("btf id:      {}", prog.btf_id);
+    if prog.ifindex != 0 {
+        println!("offloaded:   ifindex {}", prog.ifindex);
+    }
+    let map_ids = prog.map_ids.iter().map(u32::to_string).collect::<Vec<_>>();
+    println!("map ids:     {}", map_ids.join(", "));
+    println!("runs:        {}", prog.run_cnt);
+    println!("runtime:     {} ns", prog.run_time_ns);
+    println!("rec. misses: {}", prog.recursion_misses);
+    Ok(())
+}
+
+fn prog_dump(id: u32, json: bool) -> Result<()> {
+    let prog = find_prog(id)?;
+    if prog.xlated_prog_insns.is_empty() {
+        bail!("no instructions available for program {id}; insufficient privileges?");
+    }
+
+    let mut disasm = Disassembler::new();
+    disasm.resolve_maps();
+    // Kernel symbols are only used for nicer call targets; helper names
+    // are resolved via BTF regardless.
+    let _result = disasm.resolve_kernel_symbols();
+    let text = disasm.disassemble_xlated(&prog.xlated_prog_insns)?;
+    if json {
+        return print_json(&text.lines().collect::<Vec<_>>());
+    }
+    print!("{text}");
+    Ok(())
+}
+
+
+fn map_list(json: bool) -> Result<()> {
+    let maps = MapInfoIter::default().collect::<Vec<_>>();
+    if json {
+        return print_json(&maps);
+    }
+
+    println!(
+        "{:>6}  {:<16}  {:<20}  {:>6}  {:>6}  {:>10}",
+        "ID", "NAME", "TYPE", "KEY", "VALUE", "ENTRIES"
+    );
+    for map in maps {
+        let ty = format!("{:?}", map.ty);
+        println!(
+            "{:>6}  {:<16}  {ty:<20}  {:>6}  {:>6}  {:>10}",
+            map.id,
+            map.name.to_string_lossy(),
+            map.key_size,
+            map.value_size,
+            map.max_entries,
+        );
+    }
+    Ok(())
+}
+
+fn find_map(id: u32) -> Result<MapInfo> {
+    MapInfoIter::default()
+        .find(|map| map.id == id)
+        .with_context(|| format!("no map with ID {id} found"))
+}
+
+fn map_show(id: u32, json: bool) -> Result<()> {
+    let map = find_map(id)?;
+    if json {
+        return print_json(&map);
+    }
+
+    println!("id:          {}", map.id);
+    println!("name:        {}", map.name.to_string_lossy());
+    println!("type:        {:?}", map.ty);
+    println!("key size:    {}", map.key_size);
+    println!("value size:  {}", map.value_size);
+    println!("max entries: {}", map.max_entries);
+    println!("flags:       {:#x}", map.map_flags);
+    println!("btf id:      {}", map.btf_id);
+    if map.ifindex != 0 {
+        println!("offloaded:   ifindex {}", map.ifindex);
+    }
+    Ok(())
+}
+
+fn map_dump(id: u32, json: bool) -> Result<()> {
+    let map = MapHandle::from_map_id(id).with_context(|| format!("failed to open map {id}"))?;
+    if json {
+        let entries = map
+            .dump_json()
+            .with_context(|| format!("failed to dump map {id}"))?;
+        return print_json(&entries);
+    }
+
+    let info = map.info()?.info;
+    let btf = if info.btf_id != 0 {
+        Some(Btf::from_btf_id(info.btf_id).context("failed to load map BTF")?)
+    } else {
+        None
+    };
+    let formatter = btf.as_ref().map(ValueFormatter::new);
+    let render = |type_id: u32, data: &[u8]| -> Result<String> {
+        match &formatter {
+            Some(formatter) if type_id != 0 => Ok(formatter.format(type_id.into(), data)?),
+            _ => Ok(hex(data)),
+        }
+    };
+
+    let mut count = 0;
+    for key in map.keys() {
+        let values = if map.map_type().is_percpu() {
+            map.lookup_percpu(&key, MapFlags::ANY)?
+        } else {
+            map.lookup(&key, MapFlags::ANY)?.map(|value| vec![value])
+        };
+        // The entry may have been removed since we retrieved its key.
+        let Some(values) = values else { continue };
+
+        println!("key: {}", render(info.btf_key_type_id, &key)?);
+        if let [value] = values.as_slice() {
+            println!("value: {}", render(info.btf_value_type_id, value)?);
+        } else {
+            for (cpu, value) in values.iter().enumerate() {
+                println!(
+                    "value (CPU {cpu}): {}",
+                    render(info.btf_value_type_id, value)?
+                );
+            }
+        }
+        count += 1;
+    }
+    println!("{count} entries");
+    Ok(())
+}
+
+
+fn link_type(link: &LinkInfo) -> String {
+    match &link.info {
+        LinkTypeInfo::RawTracepoint(info) => format!("raw_tracepoint ({})", info.name),
+        LinkTypeInfo::Tracing(info) => format!("tracing ({:?})", info.attach_type),
+        LinkTypeInfo::Cgroup(info) => {
+            format!("cgroup ({:?}, cgroup {})", info.attach_type, info.cgroup_id)
+        }
+        LinkTypeInfo::Iter => "iter".to_string(),
+        LinkTypeInfo::NetNs(info) => format!("netns ({:?}, inode {})", info.attach_type, info.ino),
+        LinkTypeInfo::Unknown => "unknown".to_string(),
+    }
+}
+
+fn link_list(json: bool) -> Result<()> {
+    let links = LinkInfoIter::default().collect::<Vec<_>>();
+    if json {
+        return print_json(&links);
+    }
+
+    println!("{:>6}  {:>8}  TYPE", "ID", "PROG_ID");
+    for link in links {
+        println!("{:>6}  {:>8}  {}", link.id, link.prog_id, link_type(&link));
+    }
+    Ok(())
+}
+
+fn link_show(id: u32, json: bool) -> Result<()> {
+    let link = LinkInfoIter::default()
+        .find(|link| link.id == id)
+        .with_context(|| format!("no link with ID {id} found"))?;
+    if json {
+        return print_json(&link);
+    }
+
+    println!("id:          {}", link.id);
+    println!("prog id:     {}", link.prog_id);
+    println!("type:        {}", link_type(&link));
+    Ok(())
+}
+
+
+fn pin(kind: ObjectKind, id: u32, path: &Path) -> Result<()> {
+    let fd = match kind {
+        ObjectKind::Map => {
+            let mut map = MapHandle::from_map_id(id)?;
+            return map
+                .pin(path)
+                .with_context(|| format!("failed to pin map {id} at `{}`", path.display()))
+        }
+        ObjectKind::Prog => Program::fd_from_id(id)?,
+        // SAFETY: `bpf_link_get_fd_by_id` is always safe to call.
+        ObjectKind::Link => owned_fd(unsafe { libbpf_sys::bpf_link_get_fd_by_id(id) })
+            .with_context(|| format!("failed to open link {id}"))?,
+    };
+
+    let path_c = path_to_cstring(path)?;
+    // SAFETY: The file descriptor is valid and `path_c` is a valid C
+    //         string.
+    let ret = unsafe { libbpf_sys::bpf_obj_pin(fd.as_raw_fd(), path_c.as_ptr()) };
+    if ret < 0 {
+        return Err(Error::from_raw_os_error(-ret))
+            .with_context(|| format!("failed to pin {kind:?} {id} at `{}`", path.display()));
+    }
+    Ok(())
+}
+
+fn unpin(path: &Path) -> Result<()> {
+    // Make sure that we only ever remove BPF objects.
+    let path_c = path_to_cstring(path)?;
+    // SAFETY: `path_c` is a valid C string.
+    let _fd = owned_fd(unsafe { libbpf_sys::bpf_obj_get(path_c.as_ptr()) })
+        .with_context(|| format!("`{}` is not a pinned BPF object", path.display()))?;
+    fs::remove_file(path).with_context(|| format!("failed to unpin `{}`", path.display()))
+}
+
+
+/// Check whether `err` reports a lack of support for the requested
+/// operation.
+fn is_unsupported(err: &libbpf_rs::Error) -> bool {
+    err.kind() == libbpf_rs::ErrorKind::Unsupported || err.raw_os_error() == Some(libc::EOPNOTSUPP)
+}
+
+fn xdp_attach(
+    iface: &str,
+    id: Option<u32>,
+    pinned: Option<&Path>,
+    mode: Mode,
+    force: bool,
+) -> Result<()> {
+    let ifindex = ifindex(iface)?;
+    let fd = match (id, pinned) {
+        (Some(id), _) => Program::fd_from_id(id)?,
+        (None, Some(path)) => Program::fd_from_pinned_path(path)?,
+        (None, None) => bail!("either a program ID or a pinned path is required"),
+    };
+    let xdp = Xdp::new(fd.as_fd());
+    let base = if force {
+        XdpFlags::NONE
+    } else {
+        XdpFlags::UPDATE_IF_NOEXIST
+    };
+
+    let attach = |mode: Mode| xdp.attach(ifindex, base | mode.flags());
+    let result = match mode {
+        // Only fall back if the driver lacks native support; other
+        // errors (e.g., an already attached program) would equally
+        // affect generic mode or deserve attention.
+        Mode::Auto => attach(Mode::Native).or_else(|err| {
+            if !is_unsupported(&err) {
+                return Err(err)
+            }
+            eprintln!("native mode unavailable on {iface} ({err}); using generic mode");
+            attach(Mode::Generic)
+        }),
+        mode => attach(mode),
+    };
+    result.with_context(|| format!("failed to attach XDP program to {iface}"))
+}
+
+fn xdp_detach(iface: &str, mode: Mode) -> Result<()> {
+    let ifindex = ifindex(iface)?;
+    // SAFETY: Passing no options is always valid.
+    let ret = unsafe { libbpf_sys::bpf_xdp_detach(ifindex, mode.flags().bits(), ptr::null()) };
+    if ret < 0 {
+        return Err(Error::from_raw_os_error(-ret))
+            .with_context(|| format!("failed to detach XDP program from {iface}"));
+    }
+    Ok(())
+}
+
+
+fn new(name: &str, path: Option<&Path>, json: bool) -> Result<()> {
+    let dir = path.unwrap_or_else(|| Path::new(name));
+    let files = scaffold::generate(name, dir)?;
+    if json {
+        return print_json(&files)
+    }
+
+    for file in &files {
+        println!("created `{}`", file.display());
+    }
+    println!("build and run with `cargo build` and `sudo target/debug/{name}`");
+    Ok(())
+}
+
+
+fn main() -> Result<()> {
+    let Args { json, command } = Args::parse();
+
+    match command {
+        Command::Prog(ProgCommand::List) => prog_list(json),
+        Command::Prog(ProgCommand::Show { id }) => prog_show(id, json),
+        Command::Prog(ProgCommand::Dump { id }) => prog_dump(id, json),
+        Command::Map(MapCommand::List) => map_list(json),
+        Command::Map(MapCommand::Show { id }) => map_show(id, json),
+        Command::Map(MapCommand::Dump { id }) => map_dump(id, json),
+        Command::Link(LinkCommand::List) => link_list(json),
+        Command::Link(LinkCommand::Show { id }) => link_show(id, json),
+        Command::Pin { kind, id, path } => pin(kind, id, &path),
+        Command::Unpin { path } => unpin(&path),
+        Command::Xdp(XdpCommand::Attach {
+            iface,
+            id,
+            pinned,
+            mode,
+            force,
+        }) => xdp_attach(&iface, id, pinned.as_deref(), mode, force),
+        Command::Xdp(XdpCommand::Detach { iface, mode }) => xdp_detach(&iface, mode),
+        Command::New { name, path } => new(&name, path.as_deref(), json),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::CommandFactory as _;
+
+
+    /// Check that the command line interface is consistent.
+    #[test]
+    fn verify_cli() {
+        let () = Args::command().debug_assert();
+    }
+
+    /// Check that the global `--json` flag is accepted after subcommands.
+    #[test]
+    fn parse_json_flag() {
+        let args = Args::try_parse_from(["catherine-bpf", "prog", "show", "42", "--json"]).unwrap();
+        assert!(args.json);
+        assert!(matches!(
+            args.command,
+            Command::Prog(ProgCommand::Show { id: 42 })
+        ));
+
+        let args = Args::try_parse_from(["catherine-bpf", "map", "list"]).unwrap();
+        assert!(!args.json);
+        assert!(matches!(args.command, Command::Map(MapCommand::List)));
+    }
+
+    /// Check that pinning requires a valid object kind.
+    #[test]
+    fn parse_pin() {
+        let args = Args::try_parse_from(["catherine-bpf", "pin", "link", "7", "/sys/fs/bpf/link"])
+            .unwrap();
+        match args.command {
+            Command::Pin { kind, id, path } => {
+                assert_eq!(kind, ObjectKind::Link);
+                assert_eq!(id, 7);
+                assert_eq!(path, Path::new("/sys/fs/bpf/link"));
+            }
+            command => panic!("unexpected command: {command:?}"),
+        }
+
+        let _err = Args::try_parse_from(["catherine-bpf", "pin", "btf", "7", "/sys/fs/bpf/btf"])
+            .unwrap_err();
+    }
+
+    /// Check that XDP attachment requires exactly one program source.
+    #[test]
+    fn parse_xdp_attach() {
+        let args =
+            Args::try_parse_from(["catherine-bpf", "xdp", "attach", "eth0", "--id", "3"]).unwrap();
+        match args.command {
+            Command::Xdp(XdpCommand::Attach {
+                iface,
+                id,
+                pinned,
+                mode,
+                force,
+            }) => {
+                assert_eq!(iface, "eth0");
+                assert_eq!(id, Some(3));
+                assert_eq!(pinned, None);
+                assert_eq!(mode, Mode::Auto);
+                assert!(!force);
+            }
+            command => panic!("unexpected command: {command:?}"),
+        }
+
+        let args = Args::try_parse_from([
+            "catherine-bpf",
+            "xdp",
+            "attach",
+            "eth0",
+            "--pinned",
+            "/sys/fs/bpf/xdp",
+            "--mode",
+            "generic",
+            "--force",
+        ])
+        .unwrap();
+        match args.command {
+            Command::Xdp(XdpCommand::Attach {
+                id,
+                pinned,
+                mode,
+                force,
+                ..
+            }) => {
+                assert_eq!(id, None);
+                assert_eq!(pinned.as_deref(), Some(Path::new("/sys/fs/bpf/xdp")));
+                assert_eq!(mode, Mode::Generic);
+                assert!(force);
+            }
+            command => panic!("unexpected command: {command:?}"),
+        }
+
+        let _err = Args::try_parse_from(["catherine-bpf", "xdp", "attach", "eth0"]).unwrap_err();
+        let _err = Args::try_parse_from([
+            "catherine-bpf",
+            "xdp",
+            "attach",
+            "eth0",
+            "--id",
+            "3",
+            "--pinned",
+            "/sys/fs/bpf/xdp",
+        ])
+        .unwrap_err();
+        let _err = Args::try_parse_from([
+            "catherine-bpf",
+            "xdp",
+            "attach",
+            "eth0",
+            "--id",
+            "3",
+            "--mode",
+            "turbo",
+        ])
+        .unwrap_err();
+    }
+
+    /// Check that only errors reporting a lack of support trigger the
+    /// fallback to generic XDP mode.
+    #[test]
+    fn unsupported_errors() {
+        assert!(is_unsupported(&libbpf_rs::Error::from_raw_os_error(
+            libc::EOPNOTSUPP
+        )));
+        assert!(!is_unsupported(&libbpf_rs::Error::from_raw_os_error(
+            libc::EBUSY
+        )));
+        assert!(!is_unsupported(&libbpf_rs::Error::from_raw_os_error(
+            libc::EPERM
+        )));
+    }
+}
@@ -0,0 +1,174 @@
+//! Generation of new projects built on top of `libbpf-rs`.
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+
+/// The files making up a new project, as pairs of the path relative to
+/// the project root and the template used for generating the contents.
+const TEMPLATES: &[(&str, &str)] = &[
+    ("Cargo.toml", include_str!("../templates/Cargo.toml.in")),
+    (".gitignore", include_str!("../templates/gitignore.in")),
+    ("build.rs", include_str!("../templates/build.rs.in")),
+    ("src/main.rs", include_str!("../templates/main.rs.in")),
+    (
+        "src/bpf/{{name}}.bpf.c",
+        include_str!("../templates/prog.bpf.c.in"),
+    ),
+];
+
+
+/// Check that `name` can serve as crate name, BPF object name, and Rust
+/// module name all at once.
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.split('_').all(|part| {
+            !part.is_empty()
+                && part
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+        });
+    if !valid {
+        bail!(
+            "invalid project name `{name}`: expected lower case letters and digits, \
+             optionally separated by single underscores"
+        );
+    }
+    Ok(())
+}
+
+/// Convert a snake case `name` into the camel case form that
+/// `libbpf-cargo` uses for naming skeleton types.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let (first, rest) = part.split_at(1);
+            first.to_ascii_uppercase() + rest
+        })
+        .collect()
+}
+
+/// Escape `s` for use in a basic TOML string.
+fn toml_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Substitute all placeholders in `template`.
+fn render(template: &str, name: &str) -> String {
+    // Generated projects depend on the crates of the workspace this tool
+    // was built from, as it may contain changes not released yet.
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap_or(Path::new(env!("CARGO_MANIFEST_DIR")));
+
+    template
+        .replace("{{name}}", name)
+        .replace("{{Name}}", &camel_case(name))
+        .replace("{{root}}", &toml_escape(&root.to_string_lossy()))
+}
+
+/// Generate a new project called `name` in directory `dir`, returning
+/// the paths of all files created.
+///
+/// The directory must not exist yet.
+pub fn generate(name: &str, dir: &Path) -> Result<Vec<PathBuf>> {
+    validate_name(name)?;
+
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory `{}`", parent.display()))?;
+    }
+    fs::create_dir(dir)
+        .with_context(|| format!("failed to create project directory `{}`", dir.display()))?;
+
+    let mut files = Vec::with_capacity(TEMPLATES.len());
+    for (path, template) in TEMPLATES {
+        let path = dir.join(render(path, name));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory `{}`", parent.display()))?;
+        }
+        fs::write(&path, render(template, name))
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+        files.push(path);
+    }
+    Ok(files)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that we accept and reject the expected project names.
+    #[test]
+    fn name_validation() {
+        for name in ["execwatch", "exec_watch", "tcp4_stats"] {
+            assert!(validate_name(name).is_ok(), "{name}");
+        }
+        for name in ["", "Exec", "exec-watch", "4stats", "exec__watch", "exec_"] {
+            assert!(validate_name(name).is_err(), "{name}");
+        }
+    }
+
+    /// Check that placeholders are substituted as expected.
+    #[test]
+    fn template_substitution() {
+        assert_eq!(camel_case("exec_watch"), "ExecWatch");
+        assert_eq!(
+            render("mod {{name}}; {{Name}}SkelBuilder", "exec_watch"),
+            "mod exec_watch; ExecWatchSkelBuilder"
+        );
+        assert_eq!(
+            render("src/bpf/{{name}}.bpf.c", "execwatch"),
+            "src/bpf/execwatch.bpf.c"
+        );
+
+        for (path, template) in TEMPLATES {
+            let path = render(path, "exec_watch");
+            let contents = render(template, "exec_watch");
+            assert!(!path.contains("{{"), "{path}");
+            assert!(!contents.contains("{{"), "{path}:\n{contents}");
+        }
+    }
+
+    /// Check that generated projects depend on the crates of this
+    /// workspace.
+    #[test]
+    fn workspace_dependencies() {
+        let (_path, template) = TEMPLATES
+            .iter()
+            .find(|(path, _template)| *path == "Cargo.toml")
+            .unwrap();
+        let manifest = render(template, "execwatch");
+
+        for krate in ["libbpf-rs", "libbpf-cargo"] {
+            let line = manifest
+                .lines()
+                .find(|line| line.starts_with(&format!("{krate} = ")))
+                .unwrap();
+            let path = line
+                .split_once("path = \"")
+                .and_then(|(_, rest)| rest.split_once('"'))
+                .map(|(path, _)| path)
+                .unwrap_or_else(|| panic!("no path dependency: {line}"));
+            assert!(
+                Path::new(path).join("Cargo.toml").exists(),
+                "{path} is not a crate"
+            );
+        }
+    }
+
+    /// Check that we escape strings for use in TOML.
+    #[test]
+    fn toml_escaping() {
+        assert_eq!(toml_escape("/home/user/src"), "/home/user/src");
+        assert_eq!(toml_escape(r#"C:\src\"x""#), r#"C:\\src\\\"x\""#);
+    }
+}
@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+#define TASK_COMM_LEN 16
+// The maximum number of bytes of the argument list reported; must be a
+// power of two.
+#define ARGS_SIZE 1024
+
+char _license[] SEC("license") = "GPL";
+
+const volatile uid_t targ_uid = -1;
+
+struct event {
+    __u64 cgroup_id;
+    __u32 pid;
+    __u32 ppid;
+    __u32 uid;
+    // The number of valid bytes in `args`
+    __u32 args_size;
+    // Whether the argument list was truncated to fit into `args`
+    __u8 args_truncated;
+    u8 comm[TASK_COMM_LEN];
+    // The NUL separated arguments, as laid out in the process's memory
+    u8 args[ARGS_SIZE];
+};
+
+// Dummy instance to get skeleton to generate definition for `struct event`
+struct event _event = {0};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    __uint(max_entries, 1024 * 1024);
+} events SEC(".maps");
+
+SEC("tp/sched/sched_process_exec")
+int handle_exec(struct trace_event_raw_sched_process_exec *ctx)
+{
+    struct task_struct *task = (struct task_struct *)bpf_get_current_task();
+    unsigned long arg_start, arg_end, len;
+    uid_t uid = bpf_get_current_uid_gid();
+    struct event *e;
+
+    if (targ_uid != (uid_t)-1 && targ_uid != uid)
+        return 0;
+
+    e = bpf_ringbuf_reserve(&events, sizeof(*e), 0);
+    if (!e)
+        return 0;
+
+    e->cgroup_id = bpf_get_current_cgroup_id();
+    e->pid = bpf_get_current_pid_tgid() >> 32;
+    e->ppid = BPF_CORE_READ(task, real_parent, tgid);
+    e->uid = uid;
+    bpf_get_current_comm(e->comm, sizeof(e->comm));
+
+    // At this point the task already runs the new program, so its memory
+    // map describes the argument list passed to `execve`.
+    arg_start = BPF_CORE_READ(task, mm, arg_start);
+    arg_end = BPF_CORE_READ(task, mm, arg_end);
+    len = arg_end - arg_start;
+    e->args_truncated = len > ARGS_SIZE;
+    if (len > ARGS_SIZE)
+        len = ARGS_SIZE;
+    if (bpf_probe_read_user(e->args, len, (void *)arg_start))
+        len = 0;
+    e->args_size = len;
+
+    bpf_ringbuf_submit(e, 0);
+    return 0;
+}
@@ -0,0 +1,207 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fs;
+use std::mem::MaybeUninit;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+use serde::Serialize;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::Skel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::ErrorKind;
+use libbpf_rs::RingBufferBuilder;
+use plain::Plain;
+
+mod execsnoop {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/execsnoop.skel.rs"
+    ));
+}
+use execsnoop::*;
+
+unsafe impl Plain for types::event {}
+
+/// Trace process executions, enriched with cgroup and container
+/// information
+#[derive(Debug, Parser)]
+struct Command {
+    /// Only trace executions by this user ID
+    #[arg(short, long)]
+    uid: Option<u32>,
+
+    /// Only report executions inside of containers
+    #[arg(short, long)]
+    containers: bool,
+
+    /// Print events as JSON objects, one per line
+    #[arg(short, long)]
+    json: bool,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// An exec event, enriched with information gathered in user space.
+#[derive(Debug, Serialize)]
+struct Exec {
+    pid: u32,
+    ppid: u32,
+    uid: u32,
+    comm: String,
+    args: Vec<String>,
+    args_truncated: bool,
+    cgroup: Option<String>,
+    container_id: Option<String>,
+}
+
+/// Resolves cgroup IDs to paths, caching the results.
+#[derive(Debug, Default)]
+struct CgroupCache {
+    paths: HashMap<u64, String>,
+}
+
+impl CgroupCache {
+    /// Look up the path of the cgroup with the given ID, which `pid` is a
+    /// member of.
+    ///
+    /// The path is read from procfs, which fails if the process exited in
+    /// the meantime. Failures are not cached, so that later processes in
+    /// the same cgroup can still provide the path.
+    fn path(&mut self, cgroup_id: u64, pid: u32) -> Option<&str> {
+        if !self.paths.contains_key(&cgroup_id) {
+            let path = read_cgroup_path(pid)?;
+            let _prev = self.paths.insert(cgroup_id, path);
+        }
+        self.paths.get(&cgroup_id).map(String::as_str)
+    }
+}
+
+/// Read the cgroup v2 path of the process with the given PID.
+fn read_cgroup_path(pid: u32) -> Option<String> {
+    let content = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    // On the unified hierarchy the only entry has the form `0::<path>`.
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(str::to_string)
+}
+
+/// Extract a container ID from a cgroup path.
+///
+/// Container runtimes name cgroups after the 64 character hexadecimal ID
+/// of the container, e.g., `/system.slice/docker-<id>.scope` for Docker
+/// using the systemd cgroup driver, `/docker/<id>` without it, or
+/// `.../cri-containerd-<id>.scope` for containerd.
+fn container_id(cgroup: &str) -> Option<&str> {
+    cgroup.rsplit('/').find_map(|component| {
+        let name = component.strip_suffix(".scope").unwrap_or(component);
+        let id = name.rsplit_once('-').map_or(name, |(_, id)| id);
+        (id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())).then_some(id)
+    })
+}
+
+fn c_str(bytes: &[u8]) -> Cow<'_, str> {
+    match CStr::from_bytes_until_nul(bytes) {
+        Ok(s) => s.to_string_lossy(),
+        Err(_) => String::from_utf8_lossy(bytes),
+    }
+}
+
+fn handle_event(cgroups: &mut CgroupCache, opts: &Command, data: &[u8]) -> i32 {
+    let mut event = types::event::default();
+    plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+
+    let size = (event.args_size as usize).min(event.args.len());
+    let args = event.args[..size]
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    let cgroup = cgroups.path(event.cgroup_id, event.pid);
+    let container_id = cgroup.and_then(container_id).map(str::to_string);
+    if opts.containers && container_id.is_none() {
+        return 0;
+    }
+
+    let exec = Exec {
+        pid: event.pid,
+        ppid: event.ppid,
+        uid: event.uid,
+        comm: c_str(&event.comm).into_owned(),
+        args,
+        args_truncated: event.args_truncated != 0,
+        cgroup: cgroup.map(str::to_string),
+        container_id,
+    };
+
+    if opts.json {
+        let json = serde_json::to_string(&exec).expect("failed to serialize event");
+        println!("{json}");
+    } else {
+        println!(
+            "{:<7} {:<7} {:<7} {:16} {:12} {}{}",
+            exec.pid,
+            exec.ppid,
+            exec.uid,
+            exec.comm,
+            // Abbreviate the ID the way container runtimes usually do.
+            exec.container_id.as_deref().map_or("-", |id| &id[..12]),
+            exec.args.join(" "),
+            if exec.args_truncated { " ..." } else { "" },
+        );
+    }
+    0
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut builder = ExecsnoopSkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let open = builder.open(&mut open_object)?;
+    if let Some(uid) = opts.uid {
+        open.maps.rodata_data.targ_uid = uid;
+    }
+    let mut skel = open.load()?;
+    skel.attach()?;
+
+    let json = opts.json;
+    let mut cgroups = CgroupCache::default();
+    let mut builder = RingBufferBuilder::new();
+    builder.add(&skel.maps.events, move |data: &[u8]| {
+        handle_event(&mut cgroups, &opts, data)
+    })?;
+    let ringbuf = builder.build()?;
+
+    if !json {
+        println!(
+            "{:<7} {:<7} {:<7} {:16} {:12} ARGS",
+            "PID", "PPID", "UID", "COMM", "CONTAINER"
+        );
+    }
+    while running.load(Ordering::SeqCst) {
+        match ringbuf.poll(Duration::from_millis(100)) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
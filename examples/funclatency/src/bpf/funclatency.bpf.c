@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+// Latencies of 2^(MAX_SLOTS - 1) units and above share the last slot
+#define MAX_SLOTS 32
+
+char _license[] SEC("license") = "GPL";
+
+const volatile pid_t targ_tgid = 0;
+// The number of nanoseconds per unit of the histogram
+const volatile __u64 unit_ns = 1000;
+
+// Maps thread IDs to the time they entered the function
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 10240);
+    __type(key, __u32);
+    __type(value, __u64);
+} starts SEC(".maps");
+
+// Maps log2 slots of latencies to the number of calls in them
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, MAX_SLOTS);
+    __type(key, __u32);
+    __type(value, __u64);
+} hist SEC(".maps");
+
+static __always_inline __u32 log2l(__u64 v)
+{
+    __u32 slot = 0;
+
+    while (v > 1 && slot < 64) {
+        v >>= 1;
+        slot++;
+    }
+    return slot;
+}
+
+SEC("uprobe")
+int BPF_UPROBE(func_entry)
+{
+    __u64 id = bpf_get_current_pid_tgid();
+    __u32 tid = id;
+    __u64 ts;
+
+    if (targ_tgid && targ_tgid != id >> 32)
+        return 0;
+
+    ts = bpf_ktime_get_ns();
+    bpf_map_update_elem(&starts, &tid, &ts, BPF_ANY);
+    return 0;
+}
+
+SEC("uretprobe")
+int BPF_URETPROBE(func_exit)
+{
+    __u32 tid = bpf_get_current_pid_tgid();
+    __u64 zero = 0, *count, *start;
+    __u32 slot;
+
+    start = bpf_map_lookup_elem(&starts, &tid);
+    if (!start)
+        return 0;
+
+    slot = log2l((bpf_ktime_get_ns() - *start) / unit_ns);
+    bpf_map_delete_elem(&starts, &tid);
+    if (slot >= MAX_SLOTS)
+        slot = MAX_SLOTS - 1;
+
+    count = bpf_map_lookup_elem(&hist, &slot);
+    if (!count) {
+        bpf_map_update_elem(&hist, &slot, &zero, BPF_NOEXIST);
+        count = bpf_map_lookup_elem(&hist, &slot);
+        if (!count)
+            return 0;
+    }
+    __sync_fetch_and_add(count, 1);
+    return 0;
+}
@@ -0,0 +1,196 @@
+use std::mem::MaybeUninit;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Parser;
+use clap::ValueEnum;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::MapCore;
+use libbpf_rs::MapFlags;
+use libbpf_rs::UprobeOpts;
+
+mod funclatency {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/funclatency.skel.rs"
+    ));
+}
+use funclatency::*;
+
+/// The number of slots of the `hist` map.
+const MAX_SLOTS: u32 = 32;
+
+/// Time user space functions, printing latencies as a histogram
+#[derive(Debug, Parser)]
+struct Command {
+    /// The function to trace
+    #[arg(short, long)]
+    func: String,
+
+    /// The binary or shared library containing the function; defaults to
+    /// the executable of the process traced
+    #[arg(short, long)]
+    binary: Option<PathBuf>,
+
+    /// Only trace the process with this PID
+    #[arg(short, long)]
+    pid: Option<i32>,
+
+    /// The unit to report latencies in
+    #[arg(short, long, value_enum, default_value_t = Unit::Usecs)]
+    unit: Unit,
+
+    /// Print and reset the histogram at this interval in seconds, instead
+    /// of only once on exit
+    #[arg(short, long)]
+    interval: Option<u64>,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Unit {
+    Nsecs,
+    Usecs,
+    Msecs,
+}
+
+impl Unit {
+    fn as_ns(&self) -> u64 {
+        match self {
+            Self::Nsecs => 1,
+            Self::Usecs => 1_000,
+            Self::Msecs => 1_000_000,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Nsecs => "nsecs",
+            Self::Usecs => "usecs",
+            Self::Msecs => "msecs",
+        }
+    }
+}
+
+/// Print a histogram of power-of-two buckets, where `vals[i]` contains the
+/// number of values in `[2^i, 2^(i+1))`, with the first bucket also
+/// counting zero.
+fn print_log2_hist(vals: &[u64], unit: &str) {
+    const WIDTH: u64 = 40;
+
+    let Some(last) = vals.iter().rposition(|val| *val != 0) else {
+        println!("(no calls recorded)");
+        return;
+    };
+    let max = vals.iter().copied().max().unwrap_or(0);
+
+    println!("{unit:>23} : count    distribution");
+    for (slot, val) in vals.iter().enumerate().take(last + 1) {
+        let low = if slot == 0 { 0 } else { 1u64 << slot };
+        let high = (1u64 << (slot + 1)) - 1;
+        let stars = (val * WIDTH / max) as usize;
+        println!(
+            "{low:>10} -> {high:<10}: {val:<8} |{:<width$}|",
+            "*".repeat(stars),
+            width = WIDTH as usize,
+        );
+    }
+}
+
+/// Read the histogram from the `hist` map, resetting it if requested.
+fn read_hist(hist: &impl MapCore, reset: bool) -> Result<Vec<u64>> {
+    let mut vals = vec![0; MAX_SLOTS as usize];
+    for (slot, val) in vals.iter_mut().enumerate() {
+        let key = (slot as u32).to_ne_bytes();
+        if let Some(value) = hist
+            .lookup(&key, MapFlags::ANY)
+            .context("failed to read histogram")?
+        {
+            *val = u64::from_ne_bytes(value.try_into().unwrap());
+            if reset {
+                let _result = hist.delete(&key);
+            }
+        }
+    }
+    Ok(vals)
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let binary = match (&opts.binary, opts.pid) {
+        (Some(binary), _) => binary.clone(),
+        (None, Some(pid)) => PathBuf::from(format!("/proc/{pid}/exe")),
+        (None, None) => bail!("either --binary or --pid has to be provided"),
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut builder = FunclatencySkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let open = builder.open(&mut open_object)?;
+    open.maps.rodata_data.targ_tgid = opts.pid.unwrap_or(0);
+    open.maps.rodata_data.unit_ns = opts.unit.as_ns();
+    let skel = open.load()?;
+
+    // libbpf resolves the function name to an offset using the binary's
+    // symbol tables.
+    let pid = opts.pid.unwrap_or(-1);
+    let mut links = Vec::new();
+    for (prog, retprobe) in [
+        (&skel.progs.func_entry, false),
+        (&skel.progs.func_exit, true),
+    ] {
+        let opts = UprobeOpts {
+            retprobe,
+            func_name: opts.func.clone(),
+            ..Default::default()
+        };
+        let link = prog
+            .attach_uprobe_with_opts(pid, &binary, 0, opts)
+            .with_context(|| format!("failed to attach to `{}`", binary.display()))?;
+        links.push(link);
+    }
+    println!(
+        "Tracing {} in {}... Hit Ctrl-C to end.",
+        opts.func,
+        binary.display()
+    );
+
+    let mut elapsed = Duration::ZERO;
+    while running.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(100));
+        elapsed += Duration::from_millis(100);
+        if let Some(interval) = opts.interval {
+            if elapsed >= Duration::from_secs(interval) {
+                let vals = read_hist(&skel.maps.hist, true)?;
+                print_log2_hist(&vals, opts.unit.name());
+                println!();
+                elapsed = Duration::ZERO;
+            }
+        }
+    }
+
+    println!();
+    let vals = read_hist(&skel.maps.hist, false)?;
+    print_log2_hist(&vals, opts.unit.name());
+    Ok(())
+}
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include "opensnoop.h"
+
+const volatile pid_t targ_tgid = 0;
+const volatile bool failed_only = false;
+
+// Dummy instance to get skeleton to generate definition for `struct event`
+struct event _event = {0};
+
+// The arguments of an `openat` call in progress
+struct args {
+	const char *fname;
+	int flags;
+};
+
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, 10240);
+	__type(key, u32);
+	__type(value, struct args);
+} start SEC(".maps");
+
+struct {
+	__uint(type, BPF_MAP_TYPE_RINGBUF);
+	__uint(max_entries, 256 * 1024);
+} events SEC(".maps");
+
+SEC("tracepoint/syscalls/sys_enter_openat")
+int tracepoint__syscalls__sys_enter_openat(struct trace_event_raw_sys_enter *ctx)
+{
+	u64 id = bpf_get_current_pid_tgid();
+	u32 tgid = id >> 32;
+	u32 tid = id;
+	struct args args = {};
+
+	if (targ_tgid && targ_tgid != tgid)
+		return 0;
+
+	/* openat(int dfd, const char *filename, int flags, umode_t mode) */
+	args.fname = (const char *)ctx->args[1];
+	args.flags = (int)ctx->args[2];
+	bpf_map_update_elem(&start, &tid, &args, BPF_ANY);
+	return 0;
+}
+
+SEC("tracepoint/syscalls/sys_exit_openat")
+int tracepoint__syscalls__sys_exit_openat(struct trace_event_raw_sys_exit *ctx)
+{
+	u64 id = bpf_get_current_pid_tgid();
+	u32 tid = id;
+	struct event *e;
+	struct args *ap;
+	int ret;
+
+	ap = bpf_map_lookup_elem(&start, &tid);
+	if (!ap)
+		return 0;
+
+	ret = ctx->ret;
+	if (failed_only && ret >= 0)
+		goto cleanup;
+
+	e = bpf_ringbuf_reserve(&events, sizeof(*e), 0);
+	if (!e)
+		goto cleanup;
+
+	e->pid = id >> 32;
+	e->uid = bpf_get_current_uid_gid();
+	e->ret = ret;
+	e->flags = ap->flags;
+	bpf_get_current_comm(e->comm, sizeof(e->comm));
+	bpf_probe_read_user_str(e->fname, sizeof(e->fname), ap->fname);
+	bpf_ringbuf_submit(e, 0);
+
+cleanup:
+	bpf_map_delete_elem(&start, &tid);
+	return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,17 @@
+/* SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause) */
+#ifndef __OPENSNOOP_H
+#define __OPENSNOOP_H
+
+#define TASK_COMM_LEN 16
+#define NAME_MAX 255
+
+struct event {
+	__u32 pid;
+	__u32 uid;
+	int ret;
+	int flags;
+	u8 comm[TASK_COMM_LEN];
+	u8 fname[NAME_MAX];
+};
+
+#endif /* __OPENSNOOP_H */
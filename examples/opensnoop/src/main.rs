@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause)
+
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::Skel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::ErrorKind;
+use libbpf_rs::RingBufferBuilder;
+use plain::Plain;
+
+mod opensnoop {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/opensnoop.skel.rs"
+    ));
+}
+
+use opensnoop::*;
+
+/// Trace `openat` system calls
+#[derive(Debug, Parser)]
+struct Command {
+    /// Only trace the process with this PID
+    #[arg(short, long)]
+    pid: Option<i32>,
+    /// Only trace calls that failed
+    #[arg(short = 'x', long)]
+    failed: bool,
+    /// Verbose debug output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+unsafe impl Plain for opensnoop::types::event {}
+
+/// Convert a NUL terminated C string stored in a fixed size array, such
+/// as a task's `comm`, into a string.
+///
+/// The complete array is used if it is not NUL terminated.
+fn c_str(bytes: &[u8]) -> Cow<'_, str> {
+    match CStr::from_bytes_until_nul(bytes) {
+        Ok(s) => s.to_string_lossy(),
+        Err(_) => String::from_utf8_lossy(bytes),
+    }
+}
+
+fn handle_event(data: &[u8]) -> i32 {
+    let mut event = opensnoop::types::event::default();
+    plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+
+    let (fd, err) = if event.ret >= 0 {
+        (event.ret, 0)
+    } else {
+        (-1, -event.ret)
+    };
+
+    println!(
+        "{:<7} {:<7} {:16} {:>4} {:>3} {:08o} {}",
+        event.pid,
+        event.uid,
+        c_str(&event.comm),
+        fd,
+        err,
+        event.flags,
+        c_str(&event.fname),
+    );
+    0
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut skel_builder = OpensnoopSkelBuilder::default();
+    if opts.verbose {
+        skel_builder.obj_builder.debug(true);
+    }
+
+    let mut open_object = MaybeUninit::uninit();
+    let open_skel = skel_builder.open(&mut open_object)?;
+
+    // Write arguments into prog
+    open_skel.maps.rodata_data.targ_tgid = opts.pid.unwrap_or(0);
+    open_skel.maps.rodata_data.failed_only = opts.failed;
+
+    // Begin tracing
+    let mut skel = open_skel.load()?;
+    skel.attach()?;
+
+    let mut builder = RingBufferBuilder::new();
+    builder.add(&skel.maps.events, handle_event)?;
+    let ringbuf = builder.build()?;
+
+    println!(
+        "{:<7} {:<7} {:16} {:>4} {:>3} {:8} PATH",
+        "PID", "UID", "COMM", "FD", "ERR", "FLAGS"
+    );
+    while running.load(Ordering::SeqCst) {
+        match ringbuf.poll(Duration::from_millis(100)) {
+            Ok(()) => (),
+            // The poll got interrupted by the signal terminating us.
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
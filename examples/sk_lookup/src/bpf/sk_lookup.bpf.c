@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_helpers.h>
+
+#define IPPROTO_TCP 6
+
+char _license[] SEC("license") = "GPL";
+
+// The range of local ports, in host byte order, to steer
+const volatile __u16 port_min = 0;
+const volatile __u16 port_max = 0;
+
+// Holds the listening socket connections get steered to
+struct {
+    __uint(type, BPF_MAP_TYPE_SOCKMAP);
+    __uint(max_entries, 1);
+    __type(key, __u32);
+    __type(value, __u64);
+} target SEC(".maps");
+
+SEC("sk_lookup")
+int steer_port_range(struct bpf_sk_lookup *ctx)
+{
+    const __u32 zero = 0;
+    struct bpf_sock *sk;
+    long err;
+
+    if (ctx->protocol != IPPROTO_TCP)
+        return SK_PASS;
+    if (ctx->local_port < port_min || ctx->local_port > port_max)
+        return SK_PASS;
+
+    sk = bpf_map_lookup_elem(&target, &zero);
+    // Fall back to the regular socket lookup if no socket got inserted.
+    if (!sk)
+        return SK_PASS;
+
+    err = bpf_sk_assign(ctx, sk, 0);
+    bpf_sk_release(sk);
+    return err ? SK_DROP : SK_PASS;
+}
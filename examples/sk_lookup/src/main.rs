@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::ErrorKind as IoErrorKind;
+use std::io::Write as _;
+use std::mem::MaybeUninit;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::os::unix::io::AsFd as _;
+use std::os::unix::io::AsRawFd as _;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Parser;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::MapCore;
+use libbpf_rs::MapFlags;
+
+mod sk_lookup {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/sk_lookup.skel.rs"
+    ));
+}
+use sk_lookup::*;
+
+/// Steer TCP connections to a range of ports to a single listening socket
+#[derive(Debug, Parser)]
+struct Command {
+    /// The range of ports to steer, as `<first>-<last>`
+    #[arg(short, long, default_value = "7000-7999")]
+    ports: PortRange,
+
+    /// The address to listen on; TCP connections to any port in the range
+    /// get steered to the listening socket, regardless of their destination
+    /// address
+    #[arg(short, long, default_value = "127.0.0.1:6999")]
+    listen: SocketAddr,
+
+    /// The network namespace to attach to
+    #[arg(short, long, default_value = "/proc/self/ns/net")]
+    netns: PathBuf,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// An inclusive range of ports.
+#[derive(Clone, Copy, Debug)]
+struct PortRange {
+    first: u16,
+    last: u16,
+}
+
+impl FromStr for PortRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (first, last) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow!("expected `<first>-<last>`"))?;
+        let range = Self {
+            first: first.parse().context("invalid first port")?,
+            last: last.parse().context("invalid last port")?,
+        };
+        ensure!(range.first <= range.last, "port range is empty");
+        Ok(range)
+    }
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut builder = SkLookupSkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let open = builder.open(&mut open_object)?;
+    open.maps.rodata_data.port_min = opts.ports.first;
+    open.maps.rodata_data.port_max = opts.ports.last;
+    let skel = open.load()?;
+
+    // Insert the listening socket into the SOCKMAP, from where the program
+    // picks it up to assign it to incoming connections. The map only holds
+    // a reference to the socket, so the listener has to outlive the link.
+    let listener = TcpListener::bind(opts.listen)
+        .with_context(|| format!("failed to listen on {}", opts.listen))?;
+    let () = listener.set_nonblocking(true)?;
+    let key = 0u32.to_ne_bytes();
+    let fd = u64::try_from(listener.as_raw_fd())?.to_ne_bytes();
+    let () = skel
+        .maps
+        .target
+        .update(&key, &fd, MapFlags::ANY)
+        .context("failed to insert socket into SOCKMAP")?;
+
+    let netns = File::open(&opts.netns)
+        .with_context(|| format!("failed to open `{}`", opts.netns.display()))?;
+    let _link = skel
+        .progs
+        .steer_port_range
+        .attach_netns(netns.as_fd())
+        .context("failed to attach to network namespace")?;
+
+    println!(
+        "Steering connections to ports {}-{} to {}",
+        opts.ports.first, opts.ports.last, opts.listen
+    );
+
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((mut stream, peer)) => {
+                // The local address of the connection is the one the peer
+                // connected to, not the one the listener is bound to.
+                let local = stream.local_addr()?;
+                println!("{peer} -> {local}");
+                let _result = writeln!(stream, "Hello from port {}!", local.port());
+            }
+            Err(err) if err.kind() == IoErrorKind::WouldBlock => {
+                sleep(Duration::from_millis(100));
+            }
+            Err(err) if err.kind() == IoErrorKind::Interrupted => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+char _license[] SEC("license") = "GPL";
+
+// Whether to list all threads instead of only thread group leaders
+const volatile bool threads = false;
+
+// Kernel 5.14 changed the state field to __state
+struct task_struct___pre_5_14 {
+    long int state;
+};
+
+static __always_inline long task_state(struct task_struct *task)
+{
+    struct task_struct___pre_5_14 *t = (void *)task;
+
+    if (bpf_core_field_exists(t->state))
+        return BPF_CORE_READ(t, state);
+    return BPF_CORE_READ(task, __state);
+}
+
+SEC("iter/task")
+int dump_task(struct bpf_iter__task *ctx)
+{
+    struct seq_file *seq = ctx->meta->seq;
+    struct task_struct *task = ctx->task;
+
+    // The program is invoked one last time with a NULL task once the
+    // iteration is complete
+    if (!task)
+        return 0;
+
+    if (!threads && task->pid != task->tgid)
+        return 0;
+
+    // One line per task: pid, tid, ppid, state, comm
+    BPF_SEQ_PRINTF(seq, "%d %d %d %ld %s\n", task->tgid, task->pid,
+                   task->real_parent->tgid, task_state(task), task->comm);
+    return 0;
+}
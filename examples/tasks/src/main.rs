@@ -0,0 +1,116 @@
+use std::io::BufRead as _;
+use std::mem::MaybeUninit;
+
+use anyhow::anyhow;
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Parser;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::Iter;
+
+mod tasks {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/tasks.skel.rs"
+    ));
+}
+use tasks::*;
+
+/// List tasks using a BPF task iterator
+#[derive(Debug, Parser)]
+struct Command {
+    /// List all threads instead of only processes
+    #[arg(short = 'T', long)]
+    threads: bool,
+
+    /// Only list tasks whose name contains this string
+    #[arg(short, long)]
+    comm: Option<String>,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// A task as reported by the `dump_task` program.
+#[derive(Debug)]
+struct Task<'line> {
+    pid: i32,
+    tid: i32,
+    ppid: i32,
+    state: i64,
+    comm: &'line str,
+}
+
+impl<'line> Task<'line> {
+    fn parse(line: &'line str) -> Result<Self> {
+        let invalid = || anyhow!("invalid task line `{line}`");
+        // The name comes last, as it may contain spaces.
+        let mut fields = line.splitn(5, ' ');
+        let mut next = || fields.next().ok_or_else(invalid);
+        Ok(Self {
+            pid: next()?.parse()?,
+            tid: next()?.parse()?,
+            ppid: next()?.parse()?,
+            state: next()?.parse()?,
+            comm: next()?,
+        })
+    }
+
+    /// Convert the task's state into the letter used by `ps`.
+    fn state_char(&self) -> char {
+        // TASK_IDLE is TASK_UNINTERRUPTIBLE | TASK_NOLOAD.
+        if self.state & 0x402 == 0x402 {
+            return 'I';
+        }
+        match self.state {
+            0 => 'R',
+            s if s & 0x1 != 0 => 'S',
+            s if s & 0x2 != 0 => 'D',
+            s if s & 0x4 != 0 => 'T',
+            s if s & 0x8 != 0 => 't',
+            s if s & 0x10 != 0 => 'X',
+            s if s & 0x20 != 0 => 'Z',
+            _ => '?',
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let mut builder = TasksSkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let open = builder.open(&mut open_object)?;
+    open.maps.rodata_data.threads = opts.threads;
+    let skel = open.load()?;
+
+    // Each read from an iterator created from the link runs the program
+    // over all tasks, yielding the output it wrote to the seq_file.
+    let link = skel.progs.dump_task.attach()?;
+    let iter = Iter::new(&link).context("failed to create iterator")?;
+
+    println!("{:>7} {:>7} {:>7} {:1} COMM", "PID", "TID", "PPID", "S");
+    for line in iter.lines() {
+        let line = line.context("failed to read from iterator")?;
+        let task = Task::parse(&line)?;
+        if let Some(comm) = &opts.comm {
+            if !task.comm.contains(comm.as_str()) {
+                continue;
+            }
+        }
+        println!(
+            "{:>7} {:>7} {:>7} {:1} {}",
+            task.pid,
+            task.tid,
+            task.ppid,
+            task.state_char(),
+            task.comm
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_helpers.h>
+
+#define TC_ACT_OK 0
+#define TC_ACT_SHOT 2
+
+#define NSEC_PER_SEC 1000000000ULL
+
+char _license[] SEC("license") = "GPL";
+
+// The token bucket configuration of a cgroup
+struct limit {
+    // The rate at which tokens are added, in bytes per second
+    __u64 rate;
+    // The maximum number of tokens, in bytes
+    __u64 burst;
+};
+
+// The state of a cgroup's token bucket
+struct bucket {
+    struct bpf_spin_lock lock;
+    __u64 tokens;
+    __u64 last_ns;
+    __u64 passed;
+    __u64 dropped;
+};
+
+// Maps cgroup IDs to their limits; updated by user space while running
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 1024);
+    __type(key, __u64);
+    __type(value, struct limit);
+} limits SEC(".maps");
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 1024);
+    __type(key, __u64);
+    __type(value, struct bucket);
+} buckets SEC(".maps");
+
+SEC("tc")
+int rate_limit(struct __sk_buff *skb)
+{
+    __u64 cgroup_id = bpf_skb_cgroup_id(skb);
+    __u64 now = bpf_ktime_get_ns();
+    struct bucket *bucket;
+    struct limit *limit;
+    __u64 elapsed;
+    bool pass;
+
+    limit = bpf_map_lookup_elem(&limits, &cgroup_id);
+    if (!limit)
+        return TC_ACT_OK;
+
+    bucket = bpf_map_lookup_elem(&buckets, &cgroup_id);
+    if (!bucket) {
+        struct bucket init = {
+            .tokens = limit->burst,
+            .last_ns = now,
+        };
+
+        bpf_map_update_elem(&buckets, &cgroup_id, &init, BPF_NOEXIST);
+        bucket = bpf_map_lookup_elem(&buckets, &cgroup_id);
+        if (!bucket)
+            return TC_ACT_OK;
+    }
+
+    bpf_spin_lock(&bucket->lock);
+    elapsed = now - bucket->last_ns;
+    // Refill the bucket, taking care not to overflow for long idle periods
+    if (elapsed >= NSEC_PER_SEC)
+        bucket->tokens = limit->burst;
+    else
+        bucket->tokens += elapsed * limit->rate / NSEC_PER_SEC;
+    if (bucket->tokens > limit->burst)
+        bucket->tokens = limit->burst;
+    bucket->last_ns = now;
+
+    pass = bucket->tokens >= skb->len;
+    if (pass) {
+        bucket->tokens -= skb->len;
+        bucket->passed += skb->len;
+    } else {
+        bucket->dropped += skb->len;
+    }
+    bpf_spin_unlock(&bucket->lock);
+
+    return pass ? TC_ACT_OK : TC_ACT_SHOT;
+}
@@ -0,0 +1,290 @@
+use std::ffi::CString;
+use std::fs;
+use std::io::Error;
+use std::mem::MaybeUninit;
+use std::os::fd::AsFd as _;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Args;
+use clap::Parser;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::MapCore;
+use libbpf_rs::MapFlags;
+use libbpf_rs::MapHandle;
+use libbpf_rs::TcHookBuilder;
+use libbpf_rs::TC_EGRESS;
+
+mod tc_rate_limit {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/tc_rate_limit.skel.rs"
+    ));
+}
+use tc_rate_limit::*;
+
+/// The directory the `limits` map is pinned in while running.
+const PIN_DIR: &str = "/sys/fs/bpf/tc_rate_limit";
+
+/// Limit the egress bandwidth of cgroups
+#[derive(Debug, Parser)]
+enum Command {
+    /// Attach the rate limiter to an interface until interrupted
+    Run(RunArgs),
+    /// Set the limit of a cgroup in the running instance
+    Set(LimitArgs),
+    /// Remove the limit of a cgroup in the running instance
+    Unset(CgroupArgs),
+}
+
+#[derive(Debug, Args)]
+struct RunArgs {
+    /// The network interface to limit egress traffic on
+    #[arg(short, long)]
+    iface: String,
+
+    /// Limit a cgroup right away, as `<cgroup path>=<rate>[:<burst>]`; may
+    /// be repeated
+    #[arg(short, long = "limit")]
+    limits: Vec<CgroupLimit>,
+
+    /// The interval in seconds at which to print statistics
+    #[arg(long, default_value_t = 1)]
+    interval: u64,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Debug, Args)]
+struct CgroupArgs {
+    /// The path of the cgroup
+    cgroup: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct LimitArgs {
+    #[command(flatten)]
+    cgroup: CgroupArgs,
+
+    /// The rate in bytes per second; may carry a `k`, `m`, or `g` suffix
+    #[arg(value_parser = parse_bytes)]
+    rate: u64,
+
+    /// The maximum burst in bytes; defaults to the rate
+    #[arg(short, long, value_parser = parse_bytes)]
+    burst: Option<u64>,
+}
+
+/// The token bucket configuration of a cgroup, matching `struct limit`.
+#[derive(Clone, Copy, Debug)]
+struct Limit {
+    rate: u64,
+    burst: u64,
+}
+
+impl Limit {
+    fn new(rate: u64, burst: Option<u64>) -> Self {
+        Self {
+            rate,
+            burst: burst.unwrap_or(rate),
+        }
+    }
+
+    fn to_value(self) -> [u8; 16] {
+        let mut value = [0; 16];
+        value[..8].copy_from_slice(&self.rate.to_ne_bytes());
+        value[8..].copy_from_slice(&self.burst.to_ne_bytes());
+        value
+    }
+}
+
+/// A limit for a cgroup as provided on the command line.
+#[derive(Clone, Debug)]
+struct CgroupLimit {
+    cgroup: PathBuf,
+    limit: Limit,
+}
+
+impl FromStr for CgroupLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (cgroup, limit) = s
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow!("expected `<cgroup path>=<rate>[:<burst>]`"))?;
+        let (rate, burst) = match limit.split_once(':') {
+            Some((rate, burst)) => (rate, Some(parse_bytes(burst)?)),
+            None => (limit, None),
+        };
+        Ok(Self {
+            cgroup: PathBuf::from(cgroup),
+            limit: Limit::new(parse_bytes(rate)?, burst),
+        })
+    }
+}
+
+/// Parse a number of bytes with an optional decimal unit suffix.
+fn parse_bytes(s: &str) -> Result<u64> {
+    let (num, factor) = match s.char_indices().last() {
+        Some((idx, 'k' | 'K')) => (&s[..idx], 1_000),
+        Some((idx, 'm' | 'M')) => (&s[..idx], 1_000_000),
+        Some((idx, 'g' | 'G')) => (&s[..idx], 1_000_000_000),
+        _ => (s, 1),
+    };
+    let num = num
+        .parse::<u64>()
+        .with_context(|| format!("invalid number of bytes `{s}`"))?;
+    num.checked_mul(factor)
+        .ok_or_else(|| anyhow!("number of bytes `{s}` is too large"))
+}
+
+/// Retrieve the ID of the cgroup at `path`, which is the inode number of
+/// its directory.
+fn cgroup_id(path: &Path) -> Result<u64> {
+    let metadata =
+        fs::metadata(path).with_context(|| format!("failed to access `{}`", path.display()))?;
+    Ok(metadata.ino())
+}
+
+fn set_limit(limits: &impl MapCore, cgroup: &Path, limit: Limit) -> Result<()> {
+    let id = cgroup_id(cgroup)?;
+    limits
+        .update(&id.to_ne_bytes(), &limit.to_value(), MapFlags::ANY)
+        .with_context(|| format!("failed to set limit of `{}`", cgroup.display()))
+}
+
+fn open_limits() -> Result<MapHandle> {
+    let path = Path::new(PIN_DIR).join("limits");
+    MapHandle::from_pinned_path(&path).with_context(|| {
+        format!(
+            "failed to open `{}`; is tc_rate_limit running?",
+            path.display()
+        )
+    })
+}
+
+fn print_stats(buckets: &impl MapCore) -> Result<()> {
+    println!(
+        "{:<12} {:>16} {:>16}",
+        "CGROUP ID", "PASSED BYTES", "DROPPED BYTES"
+    );
+    for key in buckets.keys() {
+        // Read the bucket atomically with respect to the BPF program.
+        let Some(value) = buckets
+            .lookup(&key, MapFlags::LOCK)
+            .context("failed to read bucket")?
+        else {
+            continue;
+        };
+        let read_u64 =
+            |offset: usize| u64::from_ne_bytes(value[offset..offset + 8].try_into().unwrap());
+        let id = u64::from_ne_bytes(key[..8].try_into().unwrap());
+        // The lock and its padding take up the first eight bytes, followed
+        // by `tokens`, `last_ns`, `passed`, and `dropped`.
+        println!("{id:<12} {:>16} {:>16}", read_u64(24), read_u64(32));
+    }
+    println!();
+    Ok(())
+}
+
+fn run(args: RunArgs) -> Result<()> {
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut builder = TcRateLimitSkelBuilder::default();
+    if args.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let open = builder.open(&mut open_object)?;
+    let mut skel = open.load()?;
+
+    for CgroupLimit { cgroup, limit } in &args.limits {
+        let () = set_limit(&skel.maps.limits, cgroup, *limit)?;
+    }
+
+    let () = fs::create_dir(PIN_DIR)
+        .with_context(|| format!("failed to create `{PIN_DIR}`; is another instance running?"))?;
+    let pin_path = Path::new(PIN_DIR).join("limits");
+    let result = skel.maps.limits.pin(&pin_path).context("failed to pin map");
+    if let Err(err) = result {
+        let _result = fs::remove_dir(PIN_DIR);
+        return Err(err);
+    }
+
+    let result = attach_and_wait(&skel, &args, &running);
+
+    let () = skel
+        .maps
+        .limits
+        .unpin(&pin_path)
+        .context("failed to unpin map")?;
+    let () = fs::remove_dir(PIN_DIR).with_context(|| format!("failed to remove `{PIN_DIR}`"))?;
+    result
+}
+
+fn attach_and_wait(skel: &TcRateLimitSkel<'_>, args: &RunArgs, running: &AtomicBool) -> Result<()> {
+    let name = CString::new(args.iface.as_str()).context("interface name contains NUL byte")?;
+    // SAFETY: `name` is a valid NUL terminated string.
+    let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if ifindex == 0 {
+        return Err(Error::last_os_error())
+            .with_context(|| format!("unknown interface `{}`", args.iface));
+    }
+
+    let mut egress = TcHookBuilder::new(skel.progs.rate_limit.as_fd())
+        .ifindex(ifindex as i32)
+        .replace(true)
+        .handle(1)
+        .priority(1)
+        .hook(TC_EGRESS);
+    let _hook = egress.create().context("failed to create clsact qdisc")?;
+    let _hook = egress.attach().context("failed to attach egress hook")?;
+    println!("Limiting egress traffic on {}", args.iface);
+
+    let interval = Duration::from_secs(args.interval);
+    let mut elapsed = Duration::ZERO;
+    while running.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(100));
+        elapsed += Duration::from_millis(100);
+        if elapsed >= interval {
+            let () = print_stats(&skel.maps.buckets)?;
+            elapsed = Duration::ZERO;
+        }
+    }
+
+    egress.detach().context("failed to detach egress hook")
+}
+
+fn main() -> Result<()> {
+    match Command::parse() {
+        Command::Run(args) => run(args),
+        Command::Set(LimitArgs {
+            cgroup,
+            rate,
+            burst,
+        }) => set_limit(&open_limits()?, &cgroup.cgroup, Limit::new(rate, burst)),
+        Command::Unset(CgroupArgs { cgroup }) => {
+            let id = cgroup_id(&cgroup)?;
+            open_limits()?
+                .delete(&id.to_ne_bytes())
+                .with_context(|| format!("`{}` is not limited", cgroup.display()))
+        }
+    }
+}
@@ -10,73 +10,334 @@
 #define IP_OFFSET 0x1FFF
 #define ETH_HLEN 14
 #define ETH_P_IP 0x0800
+#define ETH_P_IPV6 0x86DD
+#define IPV6_HLEN 40
 
-#define TCP_OPTION_CODE 253
-#define TCP_OPTION_MAGIC 0xEB9F
+#define TCP_FLAG_SYN 0x02
+
+#define AF_INET 2
+#define AF_INET6 10
+
+// The space available for TCP options
+#define MAX_OPTION_LEN 40
+// Options consist of at least kind and length
+#define MAX_PAYLOAD_LEN (MAX_OPTION_LEN - 2)
+// Experimental option kinds, which carry an experiment identifier (ExID)
+// as defined by RFC 6994
+#define TCP_OPTION_EXP1 253
+#define TCP_OPTION_EXP2 254
 
 char _license[] SEC("license") = "GPL";
 
-const volatile __u32 targ_ip = 0;
-const volatile __u32 data_such_as_trace_id = 0;
+// IPv4 addresses are stored as IPv4-mapped IPv6 addresses
+struct lpm_key {
+    __u32 prefixlen;
+    __u32 addr[4];
+};
+
+// Connections from or to addresses in any of these networks get tagged
+struct {
+    __uint(type, BPF_MAP_TYPE_LPM_TRIE);
+    __uint(max_entries, 256);
+    __type(key, struct lpm_key);
+    __type(value, __u8);
+    __uint(map_flags, BPF_F_NO_PREALLOC);
+} allowlist SEC(".maps");
+
+// Connections to services listening on ports with a non-zero entry get
+// tagged; a non-zero entry 0 enables all ports
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 65536);
+    __type(key, __u32);
+    __type(value, __u8);
+} ports SEC(".maps");
+// Only tag connections of sockets created by the processes or in the
+// cgroups listed below
+const volatile bool filter_tasks = false;
+
+// Processes (by PID) whose connections get tagged
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 1024);
+    __type(key, __u32);
+    __type(value, __u8);
+} pids SEC(".maps");
+
+// Cgroups (by ID) whose processes' connections get tagged
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 1024);
+    __type(key, __u64);
+    __type(value, __u8);
+} cgroup_ids SEC(".maps");
+
+// Sockets created by any of the above; inherited by sockets accepted on
+// a tagged listening socket
+struct {
+    __uint(type, BPF_MAP_TYPE_SK_STORAGE);
+    __uint(map_flags, BPF_F_NO_PREALLOC | BPF_F_CLONE);
+    __type(key, int);
+    __type(value, __u8);
+} tagged_socks SEC(".maps");
+
+// The option to inject, kept in a map so that it can be changed while
+// running
+struct config {
+    __u16 option_exid;
+    __u8 option_kind;
+    __u8 payload_len;
+    __u8 payload[MAX_PAYLOAD_LEN];
+};
+
+// Dummy instance to get skeleton to generate definition for `struct config`
+struct config _config = {0};
 
-struct __attribute__((packed)) tcp_option
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, u32);
+    __type(value, struct config);
+} config SEC(".maps");
+
+static inline struct config *get_config(void)
 {
-    u8 kind;
-    u8 length;
-    u16 magic;
-    u32 data;
+    u32 zero = 0;
+
+    return bpf_map_lookup_elem(&config, &zero);
+}
+
+struct event {
+    __u32 saddr[4];
+    __u32 daddr[4];
+    __u16 sport;
+    __u16 dport;
+    __u8 family;
+    __u8 tcp_flags;
+    __u8 payload_len;
+    __u8 payload[MAX_PAYLOAD_LEN];
+};
+
+// Dummy instance to get skeleton to generate definition for `struct event`
+struct event _event = {0};
+
+// Connections observed to carry our option are reported here
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    __uint(max_entries, 256 * 1024);
+} events SEC(".maps");
+
+struct stats_key {
+    // IPv4 addresses are stored as IPv4-mapped IPv6 addresses
+    __u32 addr[4];
+    __u32 port;
 };
 
+struct stats_value {
+    __u64 syns;
+    __u64 bytes;
+    __u64 packets;
+};
+
+// Statistics about tagged packets, per destination
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_HASH);
+    __uint(max_entries, 4096);
+    __type(key, struct stats_key);
+    __type(value, struct stats_value);
+} stats SEC(".maps");
+
+static inline bool has_exid(u8 kind)
+{
+    return kind == TCP_OPTION_EXP1 || kind == TCP_OPTION_EXP2;
+}
+
+static inline u32 tcp_option_len(const struct config *cfg)
+{
+    return 2 + (has_exid(cfg->option_kind) ? 2 : 0) + cfg->payload_len;
+}
+
 static void reserve_space_for_tcp_option(struct bpf_sock_ops *skops)
 {
-    u32 need_space = skops->skb_len + sizeof(struct tcp_option);
+    struct config *cfg = get_config();
+    if (!cfg)
+        return;
+
+    u32 len = tcp_option_len(cfg);
+    u32 need_space = skops->skb_len + len;
     if (need_space > skops->mss_cache)
         return;
 
     bpf_printk("Sufficient space available to store a TCP option, total space: %u, required space: %u", skops->mss_cache, need_space);
-    bpf_reserve_hdr_opt(skops, sizeof(struct tcp_option), 0);
+    bpf_reserve_hdr_opt(skops, len, 0);
+}
+
+static inline void count_tagged_packet(struct bpf_sock_ops *skops)
+{
+    struct stats_key key = {
+        .port = bpf_ntohl(skops->remote_port),
+    };
+    struct stats_value zero = {}, *value;
+
+    if (skops->family == AF_INET) {
+        key.addr[2] = bpf_htonl(0xffff);
+        key.addr[3] = skops->remote_ip4;
+    } else {
+        key.addr[0] = skops->remote_ip6[0];
+        key.addr[1] = skops->remote_ip6[1];
+        key.addr[2] = skops->remote_ip6[2];
+        key.addr[3] = skops->remote_ip6[3];
+    }
+
+    value = bpf_map_lookup_elem(&stats, &key);
+    if (!value) {
+        bpf_map_update_elem(&stats, &key, &zero, BPF_NOEXIST);
+        value = bpf_map_lookup_elem(&stats, &key);
+        if (!value)
+            return;
+    }
+
+    // The map is per-CPU, so there is no need for atomic operations
+    if (skops->skb_tcp_flags & TCP_FLAG_SYN)
+        value->syns++;
+    value->bytes += skops->skb_len;
+    value->packets++;
 }
 
 static inline void store_tcp_option_header(struct bpf_sock_ops *skops)
 {
-    struct tcp_option tcp_option;
-    struct tcphdr *th = skops->skb_data;
+    u8 tcp_option[MAX_OPTION_LEN] = {};
+    struct config *cfg = get_config();
+    u32 len, off = 2;
+    u32 i;
+
+    if (!cfg)
+        return;
 
-    if (skops->skb_len + sizeof(struct tcp_option) > skops->mss_cache)
+    len = tcp_option_len(cfg);
+    if (len > MAX_OPTION_LEN || skops->skb_len + len > skops->mss_cache)
         return;
 
-    tcp_option.kind = TCP_OPTION_CODE;
-    tcp_option.length = sizeof(struct tcp_option);
-    tcp_option.magic = bpf_htons(TCP_OPTION_MAGIC);
-    tcp_option.data = data_such_as_trace_id;
-    bpf_store_hdr_opt(skops, &tcp_option, sizeof(tcp_option), 0);
+    tcp_option[0] = cfg->option_kind;
+    tcp_option[1] = len;
+    if (has_exid(cfg->option_kind)) {
+        tcp_option[2] = cfg->option_exid >> 8;
+        tcp_option[3] = cfg->option_exid & 0xff;
+        off += 2;
+    }
+    for (i = 0; i < MAX_PAYLOAD_LEN && off + i < MAX_OPTION_LEN; i++) {
+        if (i >= cfg->payload_len)
+            break;
+        tcp_option[off + i] = cfg->payload[i];
+    }
+    if (bpf_store_hdr_opt(skops, tcp_option, len, 0))
+        return;
     bpf_printk("Stored a TCP option in TCP Flag: %u", skops->skb_tcp_flags);
+    count_tagged_packet(skops);
+}
+
+static inline bool ip4_matches(u32 ip)
+{
+    struct lpm_key key = {
+        .prefixlen = 128,
+        .addr = {0, 0, bpf_htonl(0xffff), ip},
+    };
+
+    return bpf_map_lookup_elem(&allowlist, &key) != NULL;
+}
+
+static inline bool ip6_matches(const u32 ip[4])
+{
+    struct lpm_key key = {
+        .prefixlen = 128,
+        .addr = {ip[0], ip[1], ip[2], ip[3]},
+    };
+
+    return bpf_map_lookup_elem(&allowlist, &key) != NULL;
+}
+
+static inline bool sock_ops_matches(struct bpf_sock_ops *skops)
+{
+    if (skops->family == AF_INET)
+        return ip4_matches(skops->local_ip4) || ip4_matches(skops->remote_ip4);
+
+    if (skops->family == AF_INET6) {
+        u32 l_ip[4] = {skops->local_ip6[0], skops->local_ip6[1], skops->local_ip6[2], skops->local_ip6[3]};
+        u32 r_ip[4] = {skops->remote_ip6[0], skops->remote_ip6[1], skops->remote_ip6[2], skops->remote_ip6[3]};
+
+        return ip6_matches(l_ip) || ip6_matches(r_ip);
+    }
+    return false;
+}
+
+static inline bool port_matches(u32 port)
+{
+    u32 any = 0;
+    u8 *allowed;
+
+    allowed = bpf_map_lookup_elem(&ports, &any);
+    if (allowed && *allowed)
+        return true;
+
+    allowed = bpf_map_lookup_elem(&ports, &port);
+    return allowed && *allowed;
+}
+
+SEC("cgroup/sock_create")
+int sock_create_tag_tasks(struct bpf_sock *sk)
+{
+    u32 pid = bpf_get_current_pid_tgid() >> 32;
+    u64 cgroup_id = bpf_get_current_cgroup_id();
+    u8 *tagged;
+
+    if (!filter_tasks)
+        return 1;
+
+    if (!bpf_map_lookup_elem(&pids, &pid) && !bpf_map_lookup_elem(&cgroup_ids, &cgroup_id))
+        return 1;
+
+    tagged = bpf_sk_storage_get(&tagged_socks, sk, 0, BPF_SK_STORAGE_GET_F_CREATE);
+    if (tagged)
+        *tagged = 1;
+    return 1;
+}
+
+static inline bool task_matches(struct bpf_sock_ops *skops)
+{
+    struct bpf_sock *sk;
+
+    if (!filter_tasks)
+        return true;
+
+    sk = skops->sk;
+    if (!sk)
+        return false;
+
+    return bpf_sk_storage_get(&tagged_socks, sk, 0, 0) != NULL;
 }
 
 SEC("sockops")
 int sockops_write_tcp_options(struct bpf_sock_ops *skops)
 {
-    u32 l_ip, r_ip;
-    l_ip = skops->local_ip4;
-    r_ip = skops->remote_ip4;
-
     // Check if the IP addresses match the target IP
-    if (r_ip != targ_ip && l_ip != targ_ip) {
+    if (!sock_ops_matches(skops)) {
         return 1;
     }
     switch (skops->op)
     {
     // When creating a connection to another host
     case BPF_SOCK_OPS_TCP_CONNECT_CB:
-        bpf_sock_ops_cb_flags_set(skops, skops->bpf_sock_ops_cb_flags | BPF_SOCK_OPS_WRITE_HDR_OPT_CB_FLAG);
-        break;
     // When accepting a connection from another host
     case BPF_SOCK_OPS_ACTIVE_ESTABLISHED_CB:
-        bpf_sock_ops_cb_flags_set(skops, skops->bpf_sock_ops_cb_flags | BPF_SOCK_OPS_WRITE_HDR_OPT_CB_FLAG);
+        // The service is the one we connect to
+        if (port_matches(bpf_ntohl(skops->remote_port)) && task_matches(skops))
+            bpf_sock_ops_cb_flags_set(skops, skops->bpf_sock_ops_cb_flags | BPF_SOCK_OPS_WRITE_HDR_OPT_CB_FLAG);
         break;
     // When the socket is established
     case BPF_SOCK_OPS_PASSIVE_ESTABLISHED_CB:
-        bpf_sock_ops_cb_flags_set(skops, skops->bpf_sock_ops_cb_flags | BPF_SOCK_OPS_WRITE_HDR_OPT_CB_FLAG);
+        // The service is our own
+        if (port_matches(skops->local_port) && task_matches(skops))
+            bpf_sock_ops_cb_flags_set(skops, skops->bpf_sock_ops_cb_flags | BPF_SOCK_OPS_WRITE_HDR_OPT_CB_FLAG);
         break;
     // When reserving space for TCP options header
     case BPF_SOCK_OPS_HDR_OPT_LEN_CB:
@@ -118,35 +379,58 @@ int socket_handler(struct __sk_buff *skb) {
     u8 hdr_len;
     u32 tcp_hdr_start = 0;
     u32 ip_proto = 0;
-    u32 l_ip, r_ip;
+    bool matches;
+    bool found = false;
+    struct event event = {};
     bpf_skb_load_bytes(skb, 12, &proto, 2);
     proto = __bpf_ntohs(proto);
-    if (proto != ETH_P_IP)
-        return 0;
-    
-    if (ip_is_fragment(skb, nhoff))
-        return 0;
-
-    bpf_skb_load_bytes(skb, ETH_HLEN, &hdr_len, sizeof(hdr_len));
-    hdr_len &= 0x0f;
-    hdr_len *= 4;
 
-
-    bpf_skb_load_bytes(skb, nhoff + offsetof(struct iphdr, protocol), &ip_proto, 1);
+    if (proto == ETH_P_IP) {
+        if (ip_is_fragment(skb, nhoff))
+            return 0;
+
+        bpf_skb_load_bytes(skb, ETH_HLEN, &hdr_len, sizeof(hdr_len));
+        hdr_len &= 0x0f;
+        hdr_len *= 4;
+
+        bpf_skb_load_bytes(skb, nhoff + offsetof(struct iphdr, protocol), &ip_proto, 1);
+        bpf_skb_load_bytes(skb, nhoff + offsetof(struct iphdr, saddr), &event.saddr[0], 4);
+        bpf_skb_load_bytes(skb, nhoff + offsetof(struct iphdr, daddr), &event.daddr[0], 4);
+        event.family = AF_INET;
+        matches = ip4_matches(event.saddr[0]) || ip4_matches(event.daddr[0]);
+    } else if (proto == ETH_P_IPV6) {
+        // Extension headers are not supported; TCP has to follow the fixed
+        // header directly
+        hdr_len = IPV6_HLEN;
+        bpf_skb_load_bytes(skb, nhoff + offsetof(struct ipv6hdr, nexthdr), &ip_proto, 1);
+        bpf_skb_load_bytes(skb, nhoff + offsetof(struct ipv6hdr, saddr), event.saddr, sizeof(event.saddr));
+        bpf_skb_load_bytes(skb, nhoff + offsetof(struct ipv6hdr, daddr), event.daddr, sizeof(event.daddr));
+        event.family = AF_INET6;
+        matches = ip6_matches(event.saddr) || ip6_matches(event.daddr);
+    } else {
+        return 0;
+    }
 
     if (ip_proto != IPPROTO_TCP)
     {
         return 0;
     }
 
-    bpf_skb_load_bytes(skb, nhoff + offsetof(struct iphdr, saddr), &l_ip, 4);
-    bpf_skb_load_bytes(skb, nhoff + offsetof(struct iphdr, daddr), &r_ip, 4);
+    struct config *cfg = get_config();
+    if (!cfg)
+        return 0;
 
-    if (r_ip == targ_ip || l_ip == targ_ip) {
+    if (matches) {
 
         tcp_hdr_start = nhoff + hdr_len;
         u8 tcp_flag;
         bpf_skb_load_bytes(skb, tcp_hdr_start + offsetof(struct __tcphdr, ack_seq) + 5, &tcp_flag, sizeof(tcp_flag));
+        event.tcp_flags = tcp_flag;
+
+        bpf_skb_load_bytes(skb, tcp_hdr_start + offsetof(struct __tcphdr, source), &event.sport, sizeof(event.sport));
+        bpf_skb_load_bytes(skb, tcp_hdr_start + offsetof(struct __tcphdr, dest), &event.dport, sizeof(event.dport));
+        event.sport = __bpf_ntohs(event.sport);
+        event.dport = __bpf_ntohs(event.dport);
     
         u16 tcp_data_offset;
         bpf_skb_load_bytes(skb, tcp_hdr_start + offsetof(struct __tcphdr, ack_seq) + 4, &tcp_data_offset, sizeof(tcp_data_offset));
@@ -168,19 +452,37 @@ int socket_handler(struct __sk_buff *skb) {
                 goto END;
             }
 
-            if (kind == TCP_OPTION_CODE) {
+            if (kind == cfg->option_kind) {
+                u32 data_start = option_start + 2;
                 u16 magic;
                 u32 data;
-                
-                // Load magic number from TCP option header
-                bpf_skb_load_bytes(skb, option_start + 2, &magic, sizeof(magic));
-                magic = __bpf_ntohs(magic);
-                bpf_printk("####=> Socket TCP option magic: 0x%x", magic);
-
-                if (magic == TCP_OPTION_MAGIC) {
-                    // Load data from TCP option header
-                    bpf_skb_load_bytes(skb, option_start + 4, &data, sizeof(data));
-                    bpf_printk("####=> Socket TCP option data: %u", data);
+
+                if (has_exid(kind)) {
+                    // Load magic number from TCP option header
+                    bpf_skb_load_bytes(skb, data_start, &magic, sizeof(magic));
+                    magic = __bpf_ntohs(magic);
+                    bpf_printk("####=> Socket TCP option magic: 0x%x", magic);
+                    data_start += 2;
+                } else {
+                    magic = cfg->option_exid;
+                }
+
+                if (magic == cfg->option_exid && length >= data_start - option_start + sizeof(data)) {
+                    // Load the beginning of the payload from TCP option header
+                    bpf_skb_load_bytes(skb, data_start, &data, sizeof(data));
+                    bpf_printk("####=> Socket TCP option data: %u", __bpf_ntohl(data));
+                }
+
+                if (magic == cfg->option_exid) {
+                    u32 len = length - (data_start - option_start);
+
+                    // Report the complete payload to user space
+                    if (len > 0 && len <= MAX_PAYLOAD_LEN) {
+                        bpf_skb_load_bytes(skb, data_start, event.payload, len);
+                        event.payload_len = len;
+                        bpf_ringbuf_output(&events, &event, sizeof(event), 0);
+                        found = true;
+                    }
                 }
             }
 
@@ -192,5 +494,6 @@ int socket_handler(struct __sk_buff *skb) {
         }
 
     }
-    return skb->len;
+    // Only packets carrying our option are passed on to the socket
+    return found ? skb->len : 0;
 }
@@ -0,0 +1,183 @@
+//! Support for running as a service, either detached from the terminal
+//! or supervised by systemd.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Error;
+use std::io::Read as _;
+use std::io::Write as _;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::FromRawFd as _;
+use std::os::linux::net::SocketAddrExt as _;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::net::SocketAddr;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+/// Send a status update, such as `READY=1`, to the service manager.
+///
+/// This is a no-op unless running as a systemd service of
+/// `Type=notify`, which is indicated by the `NOTIFY_SOCKET` environment
+/// variable.
+pub fn sd_notify(state: &str) -> Result<()> {
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+
+    let addr = notify_addr(path)?;
+    let socket = UnixDatagram::unbound().context("failed to create notification socket")?;
+    let _sent = socket
+        .send_to_addr(state.as_bytes(), &addr)
+        .context("failed to notify service manager")?;
+    Ok(())
+}
+
+fn notify_addr(path: OsString) -> Result<SocketAddr> {
+    let bytes = path.as_bytes();
+    // A leading `@` denotes a socket in the abstract namespace.
+    let addr = if let Some(name) = bytes.strip_prefix(b"@") {
+        SocketAddr::from_abstract_name(name)
+    } else {
+        SocketAddr::from_pathname(&path)
+    };
+    addr.with_context(|| format!("invalid NOTIFY_SOCKET `{}`", path.to_string_lossy()))
+}
+
+/// A process detached from its parent by [`Daemon::fork`], which has not
+/// yet signaled readiness.
+#[derive(Debug)]
+pub struct Daemon {
+    /// The write end of a pipe the waiting parent reads from.
+    ready: File,
+}
+
+impl Daemon {
+    /// Fork into the background and start a new session.
+    ///
+    /// The parent process stays around until the child calls
+    /// [`Daemon::ready`], so that errors encountered while setting up are
+    /// still reported on the terminal, and exits with a status reflecting
+    /// whether that happened. This function must be called before any
+    /// threads are spawned.
+    pub fn fork() -> Result<Self> {
+        let mut fds = [-1; 2];
+        // SAFETY: `fds` is valid for writes of two file descriptors.
+        let rc = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+        if rc < 0 {
+            return Err(Error::last_os_error()).context("failed to create pipe");
+        }
+        // SAFETY: `pipe2` succeeded and we own both file descriptors.
+        let (mut reader, writer) =
+            unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+        // SAFETY: No other threads are running, so the child is in a
+        //         consistent state.
+        let pid = unsafe { libc::fork() };
+        match pid {
+            -1 => Err(Error::last_os_error()).context("failed to fork"),
+            0 => {
+                drop(reader);
+                // SAFETY: `setsid` is always safe to call.
+                if unsafe { libc::setsid() } < 0 {
+                    return Err(Error::last_os_error()).context("failed to create session");
+                }
+                Ok(Self { ready: writer })
+            }
+            _ => {
+                drop(writer);
+                let mut buf = [0];
+                // If the child exits before signaling readiness, the read
+                // hits EOF.
+                let status = match reader.read(&mut buf) {
+                    Ok(1) => 0,
+                    _ => 1,
+                };
+                process::exit(status)
+            }
+        }
+    }
+
+    /// Signal readiness to the waiting parent, detaching from the
+    /// terminal.
+    ///
+    /// Standard input is connected to `/dev/null`, while standard output
+    /// and error are redirected to `log_file`, if provided, or discarded
+    /// otherwise.
+    pub fn ready(mut self, log_file: Option<&Path>) -> Result<()> {
+        let null = File::open("/dev/null").context("failed to open `/dev/null`")?;
+        let log = match log_file {
+            Some(path) => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("failed to open log file `{}`", path.display()))?,
+            None => OpenOptions::new()
+                .write(true)
+                .open("/dev/null")
+                .context("failed to open `/dev/null`")?,
+        };
+
+        for (src, dst) in [
+            (null.as_raw_fd(), libc::STDIN_FILENO),
+            (log.as_raw_fd(), libc::STDOUT_FILENO),
+            (log.as_raw_fd(), libc::STDERR_FILENO),
+        ] {
+            // SAFETY: Both file descriptors are valid.
+            if unsafe { libc::dup2(src, dst) } < 0 {
+                return Err(Error::last_os_error()).context("failed to redirect standard streams");
+            }
+        }
+
+        let () = env::set_current_dir("/").context("failed to change to root directory")?;
+        let () = self
+            .ready
+            .write_all(&[1])
+            .context("failed to signal readiness")?;
+        Ok(())
+    }
+}
+
+/// A file containing the ID of the running process, which is removed
+/// when dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Create the PID file at `path`, refusing to overwrite the file of a
+    /// running instance.
+    pub fn create(path: &Path) -> Result<Self> {
+        // Resolve the path up front, as the working directory may change.
+        let path = env::current_dir()?.join(path);
+        if let Ok(content) = fs::read_to_string(&path) {
+            if let Ok(pid) = content.trim().parse::<libc::pid_t>() {
+                // SAFETY: `kill` with signal 0 only checks for existence.
+                if unsafe { libc::kill(pid, 0) } == 0 {
+                    bail!("`{}` refers to running process {pid}", path.display());
+                }
+            }
+        }
+
+        let () = fs::write(&path, format!("{}\n", process::id()))
+            .with_context(|| format!("failed to write PID file `{}`", path.display()))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            eprintln!("failed to remove PID file `{}`: {err}", self.path.display());
+        }
+    }
+}
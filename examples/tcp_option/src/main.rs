@@ -1,34 +1,71 @@
+use std::env;
+use std::ffi::CString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write as _;
+use std::mem;
 use std::mem::MaybeUninit;
+use std::net::IpAddr;
 use std::net::Ipv4Addr;
-use std::os::fd::AsFd;
-use std::os::fd::IntoRawFd;
-use std::os::unix::fs::OpenOptionsExt;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::os::fd::AsFd as _;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::IntoRawFd as _;
+use std::os::fd::OwnedFd;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 
+use libc::bind;
+use libc::if_nametoindex;
+use libc::recv;
+use libc::sockaddr;
+use libc::sockaddr_ll;
 use libc::socket;
+use libc::socklen_t;
+use libc::AF_INET;
 use libc::AF_PACKET;
+use libc::MSG_TRUNC;
 use libc::SOCK_CLOEXEC;
 use libc::SOCK_NONBLOCK;
 use libc::SOCK_RAW;
 
-use libc::c_int;
-use libc::c_void;
-use libc::socklen_t;
-use std::mem::size_of_val;
-
-use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context as _;
 use anyhow::Result;
 use clap::Parser;
-use std::fs::OpenOptions;
+use clap::ValueEnum;
 use std::io::Error;
 use std::result::Result::Ok;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
-use std::thread;
 
+use libbpf_rs::bump_memlock_rlimit;
 use libbpf_rs::skel::OpenSkel;
 use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::ErrorKind;
+use libbpf_rs::MapCore;
+use libbpf_rs::MapFlags;
+use libbpf_rs::MapHandle;
+use libbpf_rs::MapMut;
+use libbpf_rs::ProgramMut;
+use libbpf_rs::RingBufferBuilder;
+use plain::Plain;
+use serde_json::json;
+
+mod daemon;
 
 mod tcp_option {
     include!(concat!(
@@ -38,50 +75,626 @@ mod tcp_option {
 }
 use tcp_option::*;
 
-const SOL_SOCKET: c_int = 1;
-const SO_ATTACH_BPF: c_int = 50;
+use daemon::sd_notify;
+use daemon::Daemon;
+use daemon::PidFile;
+
+unsafe impl Plain for types::event {}
+unsafe impl Plain for types::config {}
+
 const ETH_P_ALL: u16 = 0x0003;
 
+/// The space available for TCP options.
+const MAX_OPTION_LEN: usize = 40;
+/// The experimental TCP option kinds, as defined by RFC 6994.
+const TCP_OPTION_EXP1: u8 = 253;
+const TCP_OPTION_EXP2: u8 = 254;
+
 #[derive(Debug, Parser)]
 struct Command {
-    #[arg(short, long)]
-    ip: Option<Ipv4Addr>,
+    /// Only tag connections from or to addresses in this IPv4 or IPv6
+    /// network (e.g., `10.0.0.0/8` or `fd00::1`); may be repeated. All
+    /// connections are tagged if not provided
+    #[arg(short, long = "cidr")]
+    cidrs: Vec<Cidr>,
+
+    /// Only tag connections to services listening on this port; may be
+    /// repeated. Connections to all ports are tagged if neither this nor
+    /// `--port-range` is provided
+    #[arg(short, long = "port")]
+    ports: Vec<u16>,
+
+    /// Only tag connections to services listening on a port in this
+    /// inclusive range (e.g., `8000-8080`); may be repeated
+    #[arg(long = "port-range")]
+    port_ranges: Vec<PortRange>,
+
+    /// The cgroup to tag connections of, including those of its
+    /// descendants; may be repeated
+    #[arg(short, long = "cgroup", default_value = "/sys/fs/cgroup")]
+    cgroups: Vec<PathBuf>,
 
+    /// Only tag connections of sockets created by the process with this
+    /// PID; may be repeated
+    #[arg(long = "pid")]
+    pids: Vec<u32>,
+
+    /// Only tag connections of sockets created by processes in the cgroup
+    /// with this ID (the inode number of its directory); may be repeated
+    #[arg(long = "cgroup-id")]
+    cgroup_ids: Vec<u64>,
+
+    /// The trace ID to carry as payload, as a 32 bit big endian integer
     #[arg(short, long, default_value_t = 42)]
     trace_id: u32,
 
+    /// The payload to carry in hex (e.g., `deadbeef`), instead of the
+    /// trace ID
+    #[arg(long, conflicts_with = "trace_id")]
+    payload: Option<Payload>,
+
+    /// The kind of the injected TCP option. The experimental kinds 253 and
+    /// 254 carry an experiment identifier (ExID) in front of the payload
+    #[arg(short, long, default_value_t = TCP_OPTION_EXP1)]
+    kind: u8,
+
+    /// The experiment identifier to use for experimental option kinds, in
+    /// hex
+    #[arg(long, default_value = "eb9f", value_parser = parse_exid)]
+    exid: u16,
+
+    /// Only observe packets on this network interface, instead of all
+    #[arg(long)]
+    iface: Option<String>,
+
+    /// Write packets observed to carry the option to this file, in the
+    /// pcap format
+    #[arg(long, value_name = "FILE")]
+    pcap: Option<PathBuf>,
+
+    /// Fork into the background once set up, detaching from the terminal
+    #[arg(long)]
+    daemon: bool,
+
+    /// Write the process ID to this file while running
+    #[arg(long, value_name = "FILE")]
+    pid_file: Option<PathBuf>,
+
+    /// Append output to this file when running in the background, instead
+    /// of discarding it
+    #[arg(long, value_name = "FILE", requires = "daemon")]
+    log_file: Option<PathBuf>,
+
+    /// The bpffs directory to pin the maps used for reconfiguration in
+    #[arg(long, value_name = "DIR", default_value = "/sys/fs/bpf/tcp_option")]
+    pin_dir: PathBuf,
+
+    /// Update the option to inject and the networks to tag connections of
+    /// in the running instance using `--pin-dir`, instead of loading the
+    /// programs. Options not provided take their defaults
+    #[arg(long, conflicts_with_all = ["daemon", "pid_file", "pcap"])]
+    reconfigure: bool,
+
+    /// Print statistics about tagged packets every this many seconds, in
+    /// addition to on exit
+    #[arg(long, value_name = "SECONDS")]
+    stats_interval: Option<u64>,
+
+    /// The format to report observed options in
+    #[arg(short, long, value_enum, default_value_t = Output::Text)]
+    output: Output,
+
     #[arg(short, long)]
     verbose: bool,
 }
 
-fn bump_memlock_rlimit() -> Result<()> {
-    let rlimit = libc::rlimit {
-        rlim_cur: 128 << 20,
-        rlim_max: 128 << 20,
+/// An IPv4 or IPv6 network in CIDR notation.
+#[derive(Clone, Copy, Debug)]
+struct Cidr {
+    /// The network's address, with IPv4 addresses being IPv4-mapped.
+    addr: Ipv6Addr,
+    /// The prefix length, relative to `addr`.
+    prefix_len: u32,
+}
+
+impl Cidr {
+    /// The network matching all addresses.
+    const ANY: Self = Self {
+        addr: Ipv6Addr::UNSPECIFIED,
+        prefix_len: 0,
     };
 
-    if unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlimit) } != 0 {
-        bail!("Failed to increase rlimit");
+    /// Convert the network into a key of the `allowlist` LPM trie.
+    fn to_key(self) -> [u8; 20] {
+        let mut key = [0; 20];
+        key[..4].copy_from_slice(&self.prefix_len.to_ne_bytes());
+        key[4..].copy_from_slice(&self.addr.octets());
+        key
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.addr.to_ipv4_mapped() {
+            Some(addr) if self.prefix_len >= 96 => write!(f, "{addr}/{}", self.prefix_len - 96),
+            _ => write!(f, "{}/{}", self.addr, self.prefix_len),
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr = IpAddr::from_str(addr).with_context(|| format!("invalid address `{addr}`"))?;
+        let (addr, offset, max_len) = match addr {
+            IpAddr::V4(addr) => (addr.to_ipv6_mapped(), 96, 32),
+            IpAddr::V6(addr) => (addr, 0, 128),
+        };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .with_context(|| format!("invalid prefix length `{prefix_len}`"))?,
+            None => max_len,
+        };
+        ensure!(
+            prefix_len <= max_len,
+            "prefix length {prefix_len} exceeds {max_len}"
+        );
+
+        Ok(Self {
+            addr,
+            prefix_len: offset + prefix_len,
+        })
+    }
+}
+
+/// The format observed options are reported in.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Output {
+    /// Human readable text.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// The payload of the injected TCP option.
+#[derive(Clone, Debug)]
+struct Payload(Vec<u8>);
+
+impl FromStr for Payload {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        ensure!(s.len() % 2 == 0, "hex payload `{s}` has odd length");
+
+        let payload = (0..s.len())
+            .step_by(2)
+            .map(|idx| {
+                let byte = s.get(idx..idx + 2).unwrap_or_default();
+                u8::from_str_radix(byte, 16).with_context(|| format!("invalid hex byte `{byte}`"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self(payload))
+    }
+}
+
+fn parse_exid(s: &str) -> Result<u16> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    u16::from_str_radix(s, 16).with_context(|| format!("invalid experiment identifier `{s}`"))
+}
+
+/// An inclusive range of ports.
+#[derive(Clone, Copy, Debug)]
+struct PortRange {
+    start: u16,
+    end: u16,
+}
+
+impl FromStr for PortRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .with_context(|| format!("port range `{s}` is not of the form <start>-<end>"))?;
+        let start = start
+            .parse()
+            .with_context(|| format!("invalid port `{start}`"))?;
+        let end = end
+            .parse()
+            .with_context(|| format!("invalid port `{end}`"))?;
+        ensure!(start <= end, "port range `{s}` is empty");
+
+        Ok(Self { start, end })
+    }
+}
+
+/// A writer of packets to a file in the pcap format.
+struct PcapWriter {
+    writer: BufWriter<File>,
+}
+
+impl PcapWriter {
+    /// The maximum number of bytes captured per packet.
+    const SNAPLEN: u32 = 65535;
+    /// The link type of Ethernet packets.
+    const LINKTYPE_ETHERNET: u32 = 1;
+
+    fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("failed to create `{}`", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        // The magic number written in native byte order tells readers the
+        // byte order used by all other fields.
+        let () = writer.write_all(&0xa1b2c3d4u32.to_ne_bytes())?;
+        let () = writer.write_all(&2u16.to_ne_bytes())?;
+        let () = writer.write_all(&4u16.to_ne_bytes())?;
+        // Time zone offset and timestamp accuracy.
+        let () = writer.write_all(&0i32.to_ne_bytes())?;
+        let () = writer.write_all(&0u32.to_ne_bytes())?;
+        let () = writer.write_all(&Self::SNAPLEN.to_ne_bytes())?;
+        let () = writer.write_all(&Self::LINKTYPE_ETHERNET.to_ne_bytes())?;
+        Ok(Self { writer })
+    }
+
+    fn write_packet(&mut self, data: &[u8], orig_len: usize) -> Result<()> {
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let () = self
+            .writer
+            .write_all(&(time.as_secs() as u32).to_ne_bytes())?;
+        let () = self.writer.write_all(&time.subsec_micros().to_ne_bytes())?;
+        let () = self.writer.write_all(&(data.len() as u32).to_ne_bytes())?;
+        let () = self.writer.write_all(&(orig_len as u32).to_ne_bytes())?;
+        let () = self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context("failed to flush pcap file")
+    }
+}
+
+/// Write all packets queued on the packet `socket` to `pcap`.
+fn capture_packets(socket: BorrowedFd<'_>, pcap: &mut PcapWriter) -> Result<()> {
+    let mut buf = vec![0u8; PcapWriter::SNAPLEN as usize];
+    loop {
+        // With `MSG_TRUNC` the original length of truncated packets is
+        // reported.
+        // SAFETY: `buf` is valid for writes of `buf.len()` bytes.
+        let rc = unsafe {
+            recv(
+                socket.as_raw_fd(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+                MSG_TRUNC,
+            )
+        };
+        if rc < 0 {
+            let err = Error::last_os_error();
+            match err.kind() {
+                io::ErrorKind::WouldBlock => break Ok(()),
+                io::ErrorKind::Interrupted => continue,
+                _ => break Err(err).context("failed to receive packet"),
+            }
+        }
+
+        let orig_len = rc as usize;
+        let () = pcap.write_packet(&buf[..orig_len.min(buf.len())], orig_len)?;
+    }
+}
+
+/// Create the configuration of the option to inject.
+fn make_config(opts: &Command) -> Result<types::config> {
+    let payload = match &opts.payload {
+        Some(Payload(payload)) => payload.clone(),
+        None => opts.trace_id.to_be_bytes().to_vec(),
+    };
+    let header_len = if matches!(opts.kind, TCP_OPTION_EXP1 | TCP_OPTION_EXP2) {
+        4
+    } else {
+        2
+    };
+    ensure!(
+        header_len + payload.len() <= MAX_OPTION_LEN,
+        "payload of {} bytes exceeds the TCP option space of {MAX_OPTION_LEN} bytes",
+        payload.len(),
+    );
+
+    let mut config = types::config {
+        option_exid: opts.exid,
+        option_kind: opts.kind,
+        payload_len: payload.len() as u8,
+        payload: [0; 38],
+    };
+    config.payload[..payload.len()].copy_from_slice(&payload);
+    Ok(config)
+}
+
+/// Replace the networks in the `allowlist` map with `cidrs`.
+fn update_allowlist(allowlist: &impl MapCore, cidrs: &[Cidr]) -> Result<()> {
+    let cidrs: &[Cidr] = if cidrs.is_empty() {
+        &[Cidr::ANY]
+    } else {
+        cidrs
+    };
+    let keys = cidrs.iter().map(|cidr| cidr.to_key()).collect::<Vec<_>>();
+
+    // Add new entries before removing stale ones, so that connections
+    // matching both the old and the new networks are tagged throughout.
+    let stale = allowlist
+        .keys()
+        .filter(|key| !keys.iter().any(|new| new[..] == key[..]))
+        .collect::<Vec<_>>();
+    for (cidr, key) in cidrs.iter().zip(&keys) {
+        let () = allowlist
+            .update(key, &[1], MapFlags::ANY)
+            .with_context(|| format!("failed to add {cidr} to allowlist"))?;
+    }
+    for key in stale {
+        let () = allowlist
+            .delete(&key)
+            .context("failed to remove network from allowlist")?;
+    }
+    Ok(())
+}
+
+/// Update the configuration of a running instance via its pinned maps.
+fn reconfigure(opts: &Command) -> Result<()> {
+    let config = make_config(opts)?;
+    let path = opts.pin_dir.join("config");
+    let map = MapHandle::from_pinned_path(&path).with_context(|| {
+        format!(
+            "failed to open `{}`; is tcp_option running?",
+            path.display()
+        )
+    })?;
+    // SAFETY: `config` is a plain C struct without padding.
+    let value = unsafe { plain::as_bytes(&config) };
+    let () = map
+        .update(&0u32.to_ne_bytes(), value, MapFlags::ANY)
+        .context("failed to update configuration")?;
+
+    let path = opts.pin_dir.join("allowlist");
+    let map = MapHandle::from_pinned_path(&path)
+        .with_context(|| format!("failed to open `{}`", path.display()))?;
+    let () = update_allowlist(&map, &opts.cidrs)?;
+    eprintln!("Reconfigured tcp_option");
+    Ok(())
+}
+
+/// A directory in bpffs with pinned maps, which is removed when dropped.
+struct PinDir {
+    path: PathBuf,
+}
+
+impl PinDir {
+    fn create(path: &Path) -> Result<Self> {
+        // Resolve the path up front, as the working directory may change.
+        let path = env::current_dir()?.join(path);
+        let () = fs::create_dir(&path).with_context(|| {
+            format!(
+                "failed to create `{}`; is another instance running?",
+                path.display()
+            )
+        })?;
+        Ok(Self { path })
+    }
+
+    fn pin(&self, map: &mut MapMut<'_>, name: &str) -> Result<()> {
+        map.pin(self.path.join(name))
+            .with_context(|| format!("failed to pin map `{name}`"))
+    }
+}
+
+impl Drop for PinDir {
+    fn drop(&mut self) {
+        // Unpinning a map is a matter of removing its file.
+        if let Err(err) = fs::remove_dir_all(&self.path) {
+            eprintln!("failed to remove `{}`: {err}", self.path.display());
+        }
+    }
+}
+
+/// A socket filter attached to a socket, which is detached when dropped.
+struct SocketFilter<'prog, 'obj> {
+    prog: &'prog ProgramMut<'obj>,
+    socket: Option<OwnedFd>,
+}
+
+impl<'prog, 'obj> SocketFilter<'prog, 'obj> {
+    fn attach(prog: &'prog ProgramMut<'obj>, socket: OwnedFd) -> Result<Self> {
+        let () = prog
+            .attach_socket(socket.as_fd())
+            .context("failed to attach socket filter")?;
+        Ok(Self {
+            prog,
+            socket: Some(socket),
+        })
+    }
+
+    /// Retrieve the socket the filter is attached to.
+    fn socket(&self) -> BorrowedFd<'_> {
+        self.socket.as_ref().unwrap().as_fd()
+    }
+
+    /// Detach the filter and close the socket, reporting errors.
+    fn detach(mut self) -> Result<()> {
+        let socket = self.socket.take().unwrap();
+        let () = self
+            .prog
+            .detach_socket(socket.as_fd())
+            .context("failed to detach socket filter")?;
+        // SAFETY: The file descriptor is owned and not used afterwards.
+        let rc = unsafe { libc::close(socket.into_raw_fd()) };
+        if rc != 0 {
+            return Err(Error::last_os_error()).context("failed to close packet socket");
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SocketFilter<'_, '_> {
+    fn drop(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            let _result = self.prog.detach_socket(socket.as_fd());
+        }
+    }
+}
+
+/// Print a summary of the per-destination statistics about tagged packets.
+fn print_stats(stats: &impl MapCore) -> Result<()> {
+    let mut rows = Vec::new();
+    for key in stats.keys() {
+        // The entry may have been removed in the meantime.
+        let Some(values) = stats.lookup_percpu(&key, MapFlags::ANY)? else {
+            continue;
+        };
+
+        // `struct stats_value` consists of `syns`, `bytes`, and `packets`.
+        let mut sums = [0u64; 3];
+        for value in values {
+            for (sum, counter) in sums.iter_mut().zip(value.chunks_exact(8)) {
+                *sum += u64::from_ne_bytes(counter.try_into().unwrap());
+            }
+        }
+
+        let addr = Ipv6Addr::from(<[u8; 16]>::try_from(&key[..16]).unwrap());
+        let addr = addr
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(addr));
+        let port = u32::from_ne_bytes(key[16..20].try_into().unwrap());
+        rows.push((SocketAddr::new(addr, port as u16), sums));
     }
+    rows.sort_by_key(|(dst, _sums)| *dst);
 
+    eprintln!(
+        "{:<47} {:>10} {:>10} {:>12}",
+        "DESTINATION", "SYNS", "PACKETS", "BYTES"
+    );
+    for (dst, [syns, bytes, packets]) in rows {
+        eprintln!(
+            "{:<47} {syns:>10} {packets:>10} {bytes:>12}",
+            dst.to_string()
+        );
+    }
     Ok(())
 }
 
-fn open_fd() -> Result<i32> {
-    unsafe {
+fn event_addr(family: u8, addr: [u32; 4]) -> IpAddr {
+    if i32::from(family) == AF_INET {
+        IpAddr::V4(Ipv4Addr::from(addr[0].to_ne_bytes()))
+    } else {
+        let mut octets = [0; 16];
+        for (dst, src) in octets.chunks_exact_mut(4).zip(addr) {
+            dst.copy_from_slice(&src.to_ne_bytes());
+        }
+        IpAddr::V6(Ipv6Addr::from(octets))
+    }
+}
+
+fn handle_event(output: Output, data: &[u8]) -> i32 {
+    let mut event = types::event::default();
+    plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+
+    let src = event_addr(event.family, event.saddr);
+    let dst = event_addr(event.family, event.daddr);
+    let len = usize::from(event.payload_len).min(event.payload.len());
+    let payload = &event.payload[..len];
+    let payload_hex = payload
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let flags = event.tcp_flags;
+
+    match output {
+        Output::Text => {
+            let src = SocketAddr::new(src, event.sport);
+            let dst = SocketAddr::new(dst, event.dport);
+            println!("{src} -> {dst} flags: 0x{flags:02x} payload: {payload_hex}");
+        }
+        Output::Json => {
+            let timestamp = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            // Payloads of four bytes are assumed to be trace IDs.
+            let trace_id = <[u8; 4]>::try_from(payload).ok().map(u32::from_be_bytes);
+            let event = json!({
+                "timestamp": timestamp,
+                "src_addr": src,
+                "src_port": event.sport,
+                "dst_addr": dst,
+                "dst_port": event.dport,
+                "protocol": "tcp",
+                "tcp_flags": flags,
+                "payload": payload_hex,
+                "trace_id": trace_id,
+            });
+            println!("{event}");
+        }
+    }
+    0
+}
+
+fn open_fd(iface: Option<&str>) -> Result<OwnedFd> {
+    let fd = unsafe {
         match socket(
             AF_PACKET,
             SOCK_RAW | SOCK_NONBLOCK | SOCK_CLOEXEC,
             ETH_P_ALL.to_be() as i32,
         ) {
-            -1 => Err(Error::last_os_error().into()),
-            fd => Ok(fd),
+            -1 => return Err(Error::last_os_error().into()),
+            fd => OwnedFd::from_raw_fd(fd),
+        }
+    };
+
+    if let Some(iface) = iface {
+        let name = CString::new(iface).context("interface name contains NUL byte")?;
+        let ifindex = unsafe { if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(Error::last_os_error())
+                .with_context(|| format!("failed to find interface `{iface}`"));
+        }
+
+        // SAFETY: `sockaddr_ll` is a plain C struct for which all zeroes is
+        //         a valid bit pattern.
+        let mut addr = unsafe { mem::zeroed::<sockaddr_ll>() };
+        addr.sll_family = AF_PACKET as u16;
+        addr.sll_protocol = ETH_P_ALL.to_be();
+        addr.sll_ifindex = ifindex as i32;
+        let rc = unsafe {
+            bind(
+                fd.as_raw_fd(),
+                &addr as *const sockaddr_ll as *const sockaddr,
+                mem::size_of::<sockaddr_ll>() as socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(Error::last_os_error())
+                .with_context(|| format!("failed to bind packet socket to `{iface}`"));
         }
     }
+    Ok(fd)
 }
 
 fn main() -> Result<()> {
     let opts = Command::parse();
+    if opts.reconfigure {
+        return reconfigure(&opts);
+    }
+
+    // Forking has to happen before any threads get spawned.
+    let daemon = opts.daemon.then(Daemon::fork).transpose()?;
     bump_memlock_rlimit()?;
 
     let running = Arc::new(AtomicBool::new(true));
@@ -90,59 +703,152 @@ fn main() -> Result<()> {
         r.store(false, Ordering::SeqCst);
     })?;
 
-    let ip = if let Some(ip) = opts.ip {
-        ip
-    } else {
-        Ipv4Addr::new(0, 0, 0, 0)
-    };
-
     let mut builder = TcpOptionSkelBuilder::default();
     if opts.verbose {
         builder.obj_builder.debug(true);
     }
     let mut open_object = MaybeUninit::uninit();
-    let open = builder.open(&mut open_object)?;
-
-    open.maps.rodata_data.targ_ip = u32::from_be_bytes(ip.octets()).to_be();
-    open.maps.rodata_data.data_such_as_trace_id = opts.trace_id;
-
-    let skel = open.load()?;
-
-    let cgroup_fd = OpenOptions::new()
-        .read(true)
-        .custom_flags(libc::O_DIRECTORY)
-        .open("/sys/fs/cgroup")
-        .unwrap()
-        .into_raw_fd();
-
-    let _kprobe = skel
-        .progs
-        .sockops_write_tcp_options
-        .attach_cgroup(cgroup_fd)
-        .unwrap();
-
-    let target_socket_fd = open_fd()?;
-    let prog_fd = skel.progs.socket_handler.as_fd();
-    match unsafe {
-        libc::setsockopt(
-            target_socket_fd as c_int,
-            SOL_SOCKET,
-            SO_ATTACH_BPF,
-            &prog_fd as *const _ as *const c_void,
-            size_of_val(&prog_fd) as socklen_t,
-        )
-    } {
-        0 => {
-            println!("BPF Attached Successfully!");
+    let mut open = builder.open(&mut open_object)?;
+
+    let config = make_config(&opts)?;
+    let rodata = &mut open.maps.rodata_data;
+    let filter_tasks = !opts.pids.is_empty() || !opts.cgroup_ids.is_empty();
+    rodata.filter_tasks = filter_tasks;
+    open.progs.sock_create_tag_tasks.set_autoload(filter_tasks);
+
+    let mut skel = open.load()?;
+
+    // SAFETY: `config` is a plain C struct without padding.
+    let value = unsafe { plain::as_bytes(&config) };
+    let () = skel
+        .maps
+        .config
+        .update(&0u32.to_ne_bytes(), value, MapFlags::ANY)
+        .context("failed to set configuration")?;
+
+    for pid in &opts.pids {
+        let () = skel
+            .maps
+            .pids
+            .update(&pid.to_ne_bytes(), &[1], MapFlags::ANY)
+            .with_context(|| format!("failed to add PID {pid}"))?;
+    }
+    for cgroup_id in &opts.cgroup_ids {
+        let () = skel
+            .maps
+            .cgroup_ids
+            .update(&cgroup_id.to_ne_bytes(), &[1], MapFlags::ANY)
+            .with_context(|| format!("failed to add cgroup ID {cgroup_id}"))?;
+    }
+
+    let () = update_allowlist(&skel.maps.allowlist, &opts.cidrs)?;
+
+    let pin_dir = PinDir::create(&opts.pin_dir)?;
+    let () = pin_dir.pin(&mut skel.maps.config, "config")?;
+    let () = pin_dir.pin(&mut skel.maps.allowlist, "allowlist")?;
+
+    let mut links = Vec::new();
+    for cgroup in &opts.cgroups {
+        let mut progs = vec![&skel.progs.sockops_write_tcp_options];
+        if filter_tasks {
+            progs.push(&skel.progs.sock_create_tag_tasks);
         }
-        _ => {
-            println!("Failed to Attach BPF, Reason: ");
-            return Err(Error::last_os_error().into());
+
+        for prog in progs {
+            let link = prog
+                .attach_cgroup_path(cgroup)
+                .with_context(|| format!("failed to attach to cgroup `{}`", cgroup.display()))?;
+            links.push((cgroup, link));
         }
-    };
+    }
+
+    let mut ranges = opts
+        .ports
+        .iter()
+        .map(|port| PortRange {
+            start: *port,
+            end: *port,
+        })
+        .chain(opts.port_ranges)
+        .collect::<Vec<_>>();
+    if ranges.is_empty() {
+        // Entry 0 enables all ports.
+        ranges.push(PortRange { start: 0, end: 0 });
+    }
+    for range in ranges {
+        let keys = (u32::from(range.start)..=u32::from(range.end))
+            .flat_map(u32::to_ne_bytes)
+            .collect::<Vec<_>>();
+        let count = u32::from(range.end - range.start) + 1;
+        let () = skel
+            .maps
+            .ports
+            .update_batch(
+                &keys,
+                &vec![1; count as usize],
+                count,
+                MapFlags::ANY,
+                MapFlags::ANY,
+            )
+            .with_context(|| format!("failed to enable ports {}-{}", range.start, range.end))?;
+    }
+
+    let filter = SocketFilter::attach(&skel.progs.socket_handler, open_fd(opts.iface.as_deref())?)?;
+    let mut builder = RingBufferBuilder::new();
+    let output = opts.output;
+    let _builder = builder.add(&skel.maps.events, move |data: &[u8]| {
+        handle_event(output, data)
+    })?;
+    let events = builder.build()?;
+    let mut pcap = opts.pcap.as_deref().map(PcapWriter::create).transpose()?;
+    let _pid_file = opts.pid_file.as_deref().map(PidFile::create).transpose()?;
+    eprintln!("BPF Attached Successfully!");
+
+    if let Some(daemon) = daemon {
+        let () = daemon.ready(opts.log_file.as_deref())?;
+    }
+    let () = sd_notify("READY=1")?;
 
+    let mut last_stats = Instant::now();
     while running.load(Ordering::SeqCst) {
-        thread::sleep(Duration::new(1, 0));
+        match events.poll(Duration::from_millis(100)) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err).context("failed to poll events"),
+        }
+
+        if let Some(pcap) = &mut pcap {
+            let () = capture_packets(filter.socket(), pcap)?;
+        }
+
+        if let Some(interval) = opts.stats_interval {
+            if last_stats.elapsed() >= Duration::from_secs(interval) {
+                let () = print_stats(&skel.maps.stats)?;
+                last_stats = Instant::now();
+            }
+        }
+    }
+    let () = sd_notify("STOPPING=1")?;
+    let () = print_stats(&skel.maps.stats)?;
+    if let Some(pcap) = &mut pcap {
+        let () = pcap.flush()?;
+    }
+
+    let mut failed = false;
+    if let Err(err) = filter.detach() {
+        eprintln!("{err:#}");
+        failed = true;
+    }
+    for (cgroup, link) in links {
+        if let Err(err) = link.detach() {
+            eprintln!(
+                "failed to detach from cgroup `{}`: {err:#}",
+                cgroup.display()
+            );
+            failed = true;
+        }
     }
+    ensure!(!failed, "failed to tear down BPF programs");
+    eprintln!("BPF Detached Successfully!");
     Ok(())
 }
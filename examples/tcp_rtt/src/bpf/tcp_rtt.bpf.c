@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_endian.h>
+
+#define AF_INET 2
+
+char _license[] SEC("license") = "GPL";
+
+enum event_kind {
+    EVENT_RTT = 0,
+    EVENT_RETRANS = 1,
+    EVENT_CLOSE = 2,
+};
+
+struct event {
+    // IPv4 addresses are stored as IPv4-mapped IPv6 addresses
+    __u32 saddr[4];
+    __u32 daddr[4];
+    __u16 sport;
+    __u16 dport;
+    __u32 kind;
+    // The smoothed RTT in microseconds
+    __u32 srtt_us;
+    // The minimum RTT observed in microseconds
+    __u32 rtt_min_us;
+    __u32 total_retrans;
+    __u64 bytes_acked;
+};
+
+// Dummy instance to get skeleton to generate definition for `struct event`
+struct event _event = {0};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    __uint(max_entries, 256 * 1024);
+} events SEC(".maps");
+
+static void fill_addrs(struct event *e, struct bpf_sock_ops *skops)
+{
+    if (skops->family == AF_INET) {
+        e->saddr[2] = bpf_htonl(0xffff);
+        e->saddr[3] = skops->local_ip4;
+        e->daddr[2] = bpf_htonl(0xffff);
+        e->daddr[3] = skops->remote_ip4;
+    } else {
+        e->saddr[0] = skops->local_ip6[0];
+        e->saddr[1] = skops->local_ip6[1];
+        e->saddr[2] = skops->local_ip6[2];
+        e->saddr[3] = skops->local_ip6[3];
+        e->daddr[0] = skops->remote_ip6[0];
+        e->daddr[1] = skops->remote_ip6[1];
+        e->daddr[2] = skops->remote_ip6[2];
+        e->daddr[3] = skops->remote_ip6[3];
+    }
+    e->sport = skops->local_port;
+    e->dport = bpf_ntohl(skops->remote_port);
+}
+
+static void report(struct bpf_sock_ops *skops, enum event_kind kind)
+{
+    struct event *e;
+
+    e = bpf_ringbuf_reserve(&events, sizeof(*e), 0);
+    if (!e)
+        return;
+
+    __builtin_memset(e, 0, sizeof(*e));
+    fill_addrs(e, skops);
+    e->kind = kind;
+    // The kernel keeps the smoothed RTT scaled by eight
+    e->srtt_us = skops->srtt_us >> 3;
+    e->rtt_min_us = skops->rtt_min;
+    e->total_retrans = skops->total_retrans;
+    e->bytes_acked = skops->bytes_acked;
+    bpf_ringbuf_submit(e, 0);
+}
+
+SEC("sockops")
+int sockops_rtt(struct bpf_sock_ops *skops)
+{
+    switch (skops->op) {
+    case BPF_SOCK_OPS_ACTIVE_ESTABLISHED_CB:
+    case BPF_SOCK_OPS_PASSIVE_ESTABLISHED_CB:
+        // Callbacks beyond connection establishment have to be requested
+        // per socket
+        bpf_sock_ops_cb_flags_set(skops, skops->bpf_sock_ops_cb_flags |
+                                         BPF_SOCK_OPS_RTT_CB_FLAG |
+                                         BPF_SOCK_OPS_RETRANS_CB_FLAG |
+                                         BPF_SOCK_OPS_STATE_CB_FLAG);
+        break;
+    case BPF_SOCK_OPS_RTT_CB:
+        report(skops, EVENT_RTT);
+        break;
+    case BPF_SOCK_OPS_RETRANS_CB:
+        report(skops, EVENT_RETRANS);
+        break;
+    case BPF_SOCK_OPS_STATE_CB:
+        // args[1] holds the new state
+        if (skops->args[1] == BPF_TCP_CLOSE)
+            report(skops, EVENT_CLOSE);
+        break;
+    }
+    return 1;
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::mem::MaybeUninit;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Parser;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::ErrorKind;
+use libbpf_rs::RingBufferBuilder;
+use plain::Plain;
+
+mod tcp_rtt {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/tcp_rtt.skel.rs"
+    ));
+}
+use tcp_rtt::*;
+
+unsafe impl Plain for types::event {}
+
+// Values of `enum event_kind`.
+const EVENT_RTT: u32 = 0;
+const EVENT_RETRANS: u32 = 1;
+const EVENT_CLOSE: u32 = 2;
+
+/// Monitor the round trip time and retransmissions of TCP connections
+#[derive(Debug, Parser)]
+struct Command {
+    /// The cgroup to monitor connections of, including those of its
+    /// descendants
+    #[arg(short, long, default_value = "/sys/fs/cgroup")]
+    cgroup: PathBuf,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// Statistics of a connection aggregated from RTT events.
+#[derive(Debug, Default)]
+struct ConnStats {
+    samples: u64,
+    srtt_sum_us: u64,
+}
+
+fn event_addr(addr: [u32; 4], port: u16) -> SocketAddr {
+    let mut octets = [0; 16];
+    for (chunk, word) in octets.chunks_exact_mut(4).zip(addr) {
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    let addr = Ipv6Addr::from(octets);
+    match addr.to_ipv4_mapped() {
+        Some(addr) => SocketAddr::new(addr.into(), port),
+        None => SocketAddr::new(addr.into(), port),
+    }
+}
+
+fn handle_event(conns: &mut HashMap<(SocketAddr, SocketAddr), ConnStats>, data: &[u8]) -> i32 {
+    let mut event = types::event::default();
+    plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+
+    let src = event_addr(event.saddr, event.sport);
+    let dst = event_addr(event.daddr, event.dport);
+    match event.kind {
+        EVENT_RTT => {
+            let stats = conns.entry((src, dst)).or_default();
+            stats.samples += 1;
+            stats.srtt_sum_us += u64::from(event.srtt_us);
+        }
+        EVENT_RETRANS => {
+            println!(
+                "{:<8} {:<47} {:<47} srtt={}us retrans={}",
+                "RETRANS", src, dst, event.srtt_us, event.total_retrans
+            );
+        }
+        EVENT_CLOSE => {
+            let stats = conns.remove(&(src, dst)).unwrap_or_default();
+            let avg_us = stats.srtt_sum_us.checked_div(stats.samples).unwrap_or(0);
+            println!(
+                "{:<8} {:<47} {:<47} samples={} avg_srtt={avg_us}us min_rtt={}us acked={}B retrans={}",
+                "CLOSE",
+                src,
+                dst,
+                stats.samples,
+                event.rtt_min_us,
+                event.bytes_acked,
+                event.total_retrans
+            );
+        }
+        kind => eprintln!("unknown event kind {kind}"),
+    }
+    0
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut builder = TcpRttSkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let open = builder.open(&mut open_object)?;
+    let skel = open.load()?;
+
+    let _link = skel
+        .progs
+        .sockops_rtt
+        .attach_cgroup_path(&opts.cgroup)
+        .with_context(|| format!("failed to attach to cgroup `{}`", opts.cgroup.display()))?;
+
+    let mut conns = HashMap::new();
+    let mut builder = RingBufferBuilder::new();
+    builder.add(&skel.maps.events, move |data: &[u8]| {
+        handle_event(&mut conns, data)
+    })?;
+    let ringbuf = builder.build()?;
+
+    println!("{:<8} {:<47} {:<47} STATS", "EVENT", "LOCAL", "REMOTE");
+    while running.load(Ordering::SeqCst) {
+        match ringbuf.poll(Duration::from_millis(100)) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
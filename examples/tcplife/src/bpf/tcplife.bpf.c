@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_endian.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+#define AF_INET 2
+#define TASK_COMM_LEN 16
+
+char _license[] SEC("license") = "GPL";
+
+const volatile pid_t targ_tgid = 0;
+
+struct event {
+    __u64 duration_ns;
+    __u64 bytes_received;
+    __u64 bytes_acked;
+    __u32 pid;
+    __u32 saddr;
+    __u32 daddr;
+    __u16 sport;
+    __u16 dport;
+    u8 comm[TASK_COMM_LEN];
+};
+
+// Dummy instance to get skeleton to generate definition for `struct event`
+struct event _event = {0};
+
+// Information about the task initiating a connection
+struct start {
+    __u64 ts;
+    __u32 pid;
+    u8 comm[TASK_COMM_LEN];
+};
+
+// Maps sockets to the start of their connection
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 10240);
+    __type(key, struct sock *);
+    __type(value, struct start);
+} starts SEC(".maps");
+
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    __uint(max_entries, 256 * 1024);
+} events SEC(".maps");
+
+static __always_inline int handle_connect(struct sock *sk)
+{
+    __u32 pid = bpf_get_current_pid_tgid() >> 32;
+    struct start start = {};
+
+    if (targ_tgid && targ_tgid != pid)
+        return 0;
+
+    start.ts = bpf_ktime_get_ns();
+    start.pid = pid;
+    bpf_get_current_comm(start.comm, sizeof(start.comm));
+    bpf_map_update_elem(&starts, &sk, &start, BPF_ANY);
+    return 0;
+}
+
+static __always_inline int handle_close(struct sock *sk)
+{
+    struct tcp_sock *tp = (struct tcp_sock *)sk;
+    struct start *start;
+    struct event *e;
+
+    start = bpf_map_lookup_elem(&starts, &sk);
+    if (!start)
+        return 0;
+
+    if (BPF_CORE_READ(sk, __sk_common.skc_family) != AF_INET)
+        goto cleanup;
+
+    e = bpf_ringbuf_reserve(&events, sizeof(*e), 0);
+    if (!e)
+        goto cleanup;
+
+    e->duration_ns = bpf_ktime_get_ns() - start->ts;
+    e->bytes_received = BPF_CORE_READ(tp, bytes_received);
+    e->bytes_acked = BPF_CORE_READ(tp, bytes_acked);
+    e->pid = start->pid;
+    e->saddr = BPF_CORE_READ(sk, __sk_common.skc_rcv_saddr);
+    e->daddr = BPF_CORE_READ(sk, __sk_common.skc_daddr);
+    e->sport = BPF_CORE_READ(sk, __sk_common.skc_num);
+    e->dport = bpf_ntohs(BPF_CORE_READ(sk, __sk_common.skc_dport));
+    __builtin_memcpy(e->comm, start->comm, sizeof(e->comm));
+    bpf_ringbuf_submit(e, 0);
+
+cleanup:
+    bpf_map_delete_elem(&starts, &sk);
+    return 0;
+}
+
+// Both flavors of programs are contained in the object; user space decides
+// which ones to load, based on the running kernel's support for fentry.
+
+SEC("fentry/tcp_v4_connect")
+int BPF_PROG(fentry_tcp_v4_connect, struct sock *sk)
+{
+    return handle_connect(sk);
+}
+
+SEC("fentry/tcp_close")
+int BPF_PROG(fentry_tcp_close, struct sock *sk)
+{
+    return handle_close(sk);
+}
+
+SEC("kprobe/tcp_v4_connect")
+int BPF_KPROBE(kprobe_tcp_v4_connect, struct sock *sk)
+{
+    return handle_connect(sk);
+}
+
+SEC("kprobe/tcp_close")
+int BPF_KPROBE(kprobe_tcp_close, struct sock *sk)
+{
+    return handle_close(sk);
+}
@@ -0,0 +1,163 @@
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::mem;
+use std::mem::MaybeUninit;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::ErrorKind;
+use libbpf_rs::RingBufferBuilder;
+use plain::Plain;
+
+mod tcplife {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/tcplife.skel.rs"
+    ));
+}
+use tcplife::*;
+
+unsafe impl Plain for types::event {}
+
+/// Log the lifetime of outgoing TCP connections
+#[derive(Debug, Parser)]
+struct Command {
+    /// Only trace connections initiated by the process with this PID
+    #[arg(short, long)]
+    pid: Option<i32>,
+
+    /// Use kprobes even if the kernel supports fentry
+    #[arg(long)]
+    kprobes: bool,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// Retrieve the major and minor version of the running kernel.
+fn kernel_version() -> Option<(u32, u32)> {
+    // SAFETY: `utsname` is a plain C struct for which all zeroes is a valid
+    //         bit pattern.
+    let mut uts = unsafe { mem::zeroed::<libc::utsname>() };
+    // SAFETY: `uts` is valid for writes.
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return None;
+    }
+    // SAFETY: `uname` NUL terminates the release string.
+    let release = unsafe { CStr::from_ptr(uts.release.as_ptr()) }
+        .to_str()
+        .ok()?;
+    let mut parts = release.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Check whether fentry programs can be attached on the running kernel.
+///
+/// fentry programs were introduced with Linux 5.5 and rely on the kernel's
+/// BTF to be available.
+fn fentry_supported() -> bool {
+    matches!(kernel_version(), Some(version) if version >= (5, 5))
+        && Path::new("/sys/kernel/btf/vmlinux").exists()
+}
+
+fn c_str(bytes: &[u8]) -> Cow<'_, str> {
+    match CStr::from_bytes_until_nul(bytes) {
+        Ok(s) => s.to_string_lossy(),
+        Err(_) => String::from_utf8_lossy(bytes),
+    }
+}
+
+fn handle_event(data: &[u8]) -> i32 {
+    let mut event = types::event::default();
+    plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+
+    // Addresses are in network byte order.
+    let src = Ipv4Addr::from(event.saddr.to_ne_bytes());
+    let dst = Ipv4Addr::from(event.daddr.to_ne_bytes());
+    println!(
+        "{:<7} {:16} {:>21} {:>21} {:>10} {:>10} {:>10.3}",
+        event.pid,
+        c_str(&event.comm),
+        format!("{src}:{}", event.sport),
+        format!("{dst}:{}", event.dport),
+        event.bytes_acked / 1024,
+        event.bytes_received / 1024,
+        event.duration_ns as f64 / 1_000_000.0,
+    );
+    0
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut builder = TcplifeSkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let mut open = builder.open(&mut open_object)?;
+    open.maps.rodata_data.targ_tgid = opts.pid.unwrap_or(0);
+
+    // Only load one flavor of programs, as fentry programs would fail to
+    // load on kernels lacking support.
+    let fentry = !opts.kprobes && fentry_supported();
+    open.progs.fentry_tcp_v4_connect.set_autoload(fentry);
+    open.progs.fentry_tcp_close.set_autoload(fentry);
+    open.progs.kprobe_tcp_v4_connect.set_autoload(!fentry);
+    open.progs.kprobe_tcp_close.set_autoload(!fentry);
+    let skel = open.load()?;
+
+    let progs = if fentry {
+        [
+            &skel.progs.fentry_tcp_v4_connect,
+            &skel.progs.fentry_tcp_close,
+        ]
+    } else {
+        [
+            &skel.progs.kprobe_tcp_v4_connect,
+            &skel.progs.kprobe_tcp_close,
+        ]
+    };
+    let _links = progs
+        .into_iter()
+        .map(|prog| prog.attach())
+        .collect::<Result<Vec<_>, _>>()?;
+    eprintln!(
+        "Tracing TCP connections using {}",
+        if fentry { "fentry" } else { "kprobes" }
+    );
+
+    let mut builder = RingBufferBuilder::new();
+    builder.add(&skel.maps.events, handle_event)?;
+    let ringbuf = builder.build()?;
+
+    println!(
+        "{:<7} {:16} {:>21} {:>21} {:>10} {:>10} {:>10}",
+        "PID", "COMM", "LOCAL", "REMOTE", "TX_KB", "RX_KB", "MS"
+    );
+    while running.load(Ordering::SeqCst) {
+        match ringbuf.poll(Duration::from_millis(100)) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
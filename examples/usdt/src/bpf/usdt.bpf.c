@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_helpers.h>
+#include <bpf/usdt.bpf.h>
+
+#define TASK_COMM_LEN 16
+// The maximum number of arguments reported per probe hit
+#define MAX_ARGS 6
+
+char _license[] SEC("license") = "GPL";
+
+struct event {
+    __u32 pid;
+    __u32 arg_cnt;
+    __u64 cookie;
+    __s64 args[MAX_ARGS];
+    u8 comm[TASK_COMM_LEN];
+};
+
+// Dummy instance to get skeleton to generate definition for `struct event`
+struct event _event = {0};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    __uint(max_entries, 256 * 1024);
+} events SEC(".maps");
+
+SEC("usdt")
+int usdt_probe(struct pt_regs *ctx)
+{
+    struct event *e;
+    long arg;
+    int cnt, i;
+
+    e = bpf_ringbuf_reserve(&events, sizeof(*e), 0);
+    if (!e)
+        return 0;
+
+    e->pid = bpf_get_current_pid_tgid() >> 32;
+    // The cookie identifies the probe the program was attached to
+    e->cookie = bpf_usdt_cookie(ctx);
+    bpf_get_current_comm(e->comm, sizeof(e->comm));
+
+    // libbpf decodes the argument specification of the probe, so arguments
+    // are fetched uniformly irrespective of whether they reside in
+    // registers, on the stack, or in memory
+    cnt = bpf_usdt_arg_cnt(ctx);
+    e->arg_cnt = cnt < 0 ? 0 : cnt;
+    for (i = 0; i < MAX_ARGS && i < cnt; i++) {
+        if (bpf_usdt_arg(ctx, i, &arg))
+            break;
+        e->args[i] = arg;
+    }
+
+    bpf_ringbuf_submit(e, 0);
+    return 0;
+}
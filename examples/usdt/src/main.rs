@@ -0,0 +1,156 @@
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Parser;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::ErrorKind;
+use libbpf_rs::RingBufferBuilder;
+use libbpf_rs::UsdtOpts;
+use plain::Plain;
+
+mod usdt {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/bpf/usdt.skel.rs"));
+}
+use usdt::*;
+
+unsafe impl Plain for types::event {}
+
+/// Trace USDT probes, printing their arguments
+#[derive(Debug, Parser)]
+struct Command {
+    /// The binary or shared library containing the probes; libraries are
+    /// searched for in the default library paths
+    #[arg(short, long, default_value = "libc.so.6")]
+    binary: PathBuf,
+
+    /// The probe to attach to, as `<provider>:<name>`; may be repeated
+    #[arg(short = 'P', long = "probe", default_value = "libc:memory_sbrk_more")]
+    probes: Vec<Probe>,
+
+    /// Only trace the process with this PID
+    #[arg(short, long)]
+    pid: Option<i32>,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// A USDT probe, identified by its provider and name.
+#[derive(Clone, Debug)]
+struct Probe {
+    provider: String,
+    name: String,
+}
+
+impl FromStr for Probe {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (provider, name) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected `<provider>:<name>`"))?;
+        Ok(Self {
+            provider: provider.to_string(),
+            name: name.to_string(),
+        })
+    }
+}
+
+fn c_str(bytes: &[u8]) -> Cow<'_, str> {
+    match CStr::from_bytes_until_nul(bytes) {
+        Ok(s) => s.to_string_lossy(),
+        Err(_) => String::from_utf8_lossy(bytes),
+    }
+}
+
+fn handle_event(probes: &[Probe], data: &[u8]) -> i32 {
+    let mut event = types::event::default();
+    plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+
+    let probe = &probes[event.cookie as usize];
+    let cnt = (event.arg_cnt as usize).min(event.args.len());
+    let args = event.args[..cnt]
+        .iter()
+        .map(|arg| format!("{arg:#x}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    println!(
+        "{:<7} {:16} {}:{}({args})",
+        event.pid,
+        c_str(&event.comm),
+        probe.provider,
+        probe.name,
+    );
+    0
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut builder = UsdtSkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let open = builder.open(&mut open_object)?;
+    let skel = open.load()?;
+
+    // Probes guarded by a semaphore are only enabled while the semaphore is
+    // non-zero; libbpf takes care of adjusting it while attached.
+    let pid = opts.pid.unwrap_or(-1);
+    let mut links = Vec::new();
+    for (idx, probe) in opts.probes.iter().enumerate() {
+        let usdt_opts = UsdtOpts {
+            cookie: idx as u64,
+            ..Default::default()
+        };
+        let link = skel
+            .progs
+            .usdt_probe
+            .attach_usdt_with_opts(pid, &opts.binary, &probe.provider, &probe.name, usdt_opts)
+            .with_context(|| {
+                format!(
+                    "failed to attach to {}:{} in `{}`",
+                    probe.provider,
+                    probe.name,
+                    opts.binary.display()
+                )
+            })?;
+        links.push(link);
+    }
+
+    let probes = opts.probes.clone();
+    let mut builder = RingBufferBuilder::new();
+    builder.add(&skel.maps.events, move |data: &[u8]| {
+        handle_event(&probes, data)
+    })?;
+    let ringbuf = builder.build()?;
+
+    println!("{:<7} {:16} PROBE(ARGS)", "PID", "COMM");
+    while running.load(Ordering::SeqCst) {
+        match ringbuf.poll(Duration::from_millis(100)) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
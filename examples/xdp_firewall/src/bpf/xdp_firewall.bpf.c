@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_endian.h>
+
+#define ETH_P_IP 0x0800
+#define ETH_P_IPV6 0x86DD
+
+#define MAX_RULES 1024
+
+char _license[] SEC("license") = "GPL";
+
+// IPv4 addresses are stored as IPv4-mapped IPv6 addresses, so that a single
+// trie serves both address families
+struct lpm_key {
+    __u32 prefixlen;
+    __u32 addr[4];
+};
+
+// Maps denied source networks to the index of their rule
+struct {
+    __uint(type, BPF_MAP_TYPE_LPM_TRIE);
+    __uint(max_entries, MAX_RULES);
+    __uint(map_flags, BPF_F_NO_PREALLOC);
+    __type(key, struct lpm_key);
+    __type(value, __u32);
+} denylist SEC(".maps");
+
+// The number of packets dropped per rule
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(max_entries, MAX_RULES);
+    __type(key, __u32);
+    __type(value, __u64);
+} drops SEC(".maps");
+
+SEC("xdp")
+int xdp_firewall(struct xdp_md *ctx)
+{
+    void *data_end = (void *)(long)ctx->data_end;
+    void *data = (void *)(long)ctx->data;
+    struct ethhdr *eth = data;
+    struct lpm_key key = {
+        .prefixlen = 128,
+    };
+    __u64 *count;
+    __u32 *rule;
+
+    if ((void *)(eth + 1) > data_end)
+        return XDP_PASS;
+
+    if (eth->h_proto == bpf_htons(ETH_P_IP)) {
+        struct iphdr *iph = (void *)(eth + 1);
+
+        if ((void *)(iph + 1) > data_end)
+            return XDP_PASS;
+
+        key.addr[2] = bpf_htonl(0xffff);
+        key.addr[3] = iph->saddr;
+    } else if (eth->h_proto == bpf_htons(ETH_P_IPV6)) {
+        struct ipv6hdr *ip6h = (void *)(eth + 1);
+
+        if ((void *)(ip6h + 1) > data_end)
+            return XDP_PASS;
+
+        __builtin_memcpy(key.addr, &ip6h->saddr, sizeof(key.addr));
+    } else {
+        return XDP_PASS;
+    }
+
+    rule = bpf_map_lookup_elem(&denylist, &key);
+    if (!rule)
+        return XDP_PASS;
+
+    // The map is per-CPU, so there is no need for atomic operations
+    count = bpf_map_lookup_elem(&drops, rule);
+    if (count)
+        (*count)++;
+    return XDP_DROP;
+}
@@ -0,0 +1,280 @@
+use std::ffi::CString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::io::Error;
+use std::mem::MaybeUninit;
+use std::net::IpAddr;
+use std::net::Ipv6Addr;
+use std::os::fd::AsFd as _;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::ensure;
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Parser;
+use clap::ValueEnum;
+
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+use libbpf_rs::MapCore;
+use libbpf_rs::MapFlags;
+use libbpf_rs::Xdp;
+use libbpf_rs::XdpFlags;
+
+mod xdp_firewall {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/xdp_firewall.skel.rs"
+    ));
+}
+use xdp_firewall::*;
+
+/// The capacity of the `denylist` map.
+const MAX_RULES: usize = 1024;
+
+#[derive(Debug, Parser)]
+struct Command {
+    /// The network interface to attach to; may be repeated
+    #[arg(short, long = "iface", required = true)]
+    ifaces: Vec<String>,
+
+    /// Drop packets from addresses in this IPv4 or IPv6 network (e.g.,
+    /// `10.0.0.0/8` or `fd00::1`); may be repeated
+    #[arg(short, long = "deny")]
+    denied: Vec<Cidr>,
+
+    /// The mode to attach the program in
+    #[arg(short, long, value_enum, default_value_t = Mode::Auto)]
+    mode: Mode,
+
+    /// The interval in seconds at which to print drop counters
+    #[arg(long, default_value_t = 1)]
+    interval: u64,
+
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// The mode to attach an XDP program in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Use the driver's native support, falling back to generic mode if
+    /// it is unavailable
+    Auto,
+    /// Run the program in the driver, before socket buffers are allocated
+    Native,
+    /// Run the program on socket buffers, supported by all drivers
+    Generic,
+}
+
+/// An IPv4 or IPv6 network in CIDR notation.
+#[derive(Clone, Copy, Debug)]
+struct Cidr {
+    /// The network's address, with IPv4 addresses being IPv4-mapped.
+    addr: Ipv6Addr,
+    /// The prefix length, relative to `addr`.
+    prefix_len: u32,
+}
+
+impl Cidr {
+    /// Convert the network into a key of the `denylist` LPM trie.
+    fn to_key(self) -> [u8; 20] {
+        let mut key = [0; 20];
+        key[..4].copy_from_slice(&self.prefix_len.to_ne_bytes());
+        key[4..].copy_from_slice(&self.addr.octets());
+        key
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.addr.to_ipv4_mapped() {
+            Some(addr) if self.prefix_len >= 96 => write!(f, "{addr}/{}", self.prefix_len - 96),
+            _ => write!(f, "{}/{}", self.addr, self.prefix_len),
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr = IpAddr::from_str(addr).with_context(|| format!("invalid address `{addr}`"))?;
+        let (addr, offset, max_len) = match addr {
+            IpAddr::V4(addr) => (addr.to_ipv6_mapped(), 96, 32),
+            IpAddr::V6(addr) => (addr, 0, 128),
+        };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .with_context(|| format!("invalid prefix length `{prefix_len}`"))?,
+            None => max_len,
+        };
+        ensure!(
+            prefix_len <= max_len,
+            "prefix length {prefix_len} exceeds {max_len}"
+        );
+
+        Ok(Self {
+            addr,
+            prefix_len: offset + prefix_len,
+        })
+    }
+}
+
+/// An XDP program attached to a network interface.
+struct Attachment {
+    iface: String,
+    ifindex: i32,
+    flags: XdpFlags,
+}
+
+/// The XDP attachments made so far, which get detached once dropped, so
+/// that the program does not stay attached when bailing out early.
+struct Attachments<'xdp, 'fd> {
+    xdp: &'xdp Xdp<'fd>,
+    attached: Vec<Attachment>,
+}
+
+impl Attachments<'_, '_> {
+    /// Detach from all interfaces, reporting whether that succeeded for
+    /// every one of them.
+    fn detach(&mut self) -> bool {
+        let mut success = true;
+        for Attachment {
+            iface,
+            ifindex,
+            flags,
+        } in self.attached.drain(..)
+        {
+            if let Err(err) = self.xdp.detach(ifindex, flags) {
+                eprintln!("failed to detach from {iface}: {err}");
+                success = false;
+            }
+        }
+        success
+    }
+}
+
+impl Drop for Attachments<'_, '_> {
+    fn drop(&mut self) {
+        let _success = self.detach();
+    }
+}
+
+fn ifindex(iface: &str) -> Result<i32> {
+    let name = CString::new(iface).context("interface name contains NUL byte")?;
+    // SAFETY: `name` is a valid NUL terminated string.
+    let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if ifindex == 0 {
+        return Err(Error::last_os_error()).with_context(|| format!("unknown interface `{iface}`"));
+    }
+    Ok(ifindex as i32)
+}
+
+/// Attach `xdp` to `iface` in the requested `mode`.
+fn attach(xdp: &Xdp<'_>, iface: &str, mode: Mode) -> Result<Attachment> {
+    let ifindex = ifindex(iface)?;
+    // Refuse to replace programs attached by somebody else.
+    let attach = |flags: XdpFlags| {
+        xdp.attach(ifindex, flags | XdpFlags::UPDATE_IF_NOEXIST)
+            .map(|()| Attachment {
+                iface: iface.to_string(),
+                ifindex,
+                flags,
+            })
+    };
+
+    let result = match mode {
+        Mode::Native => attach(XdpFlags::DRV_MODE),
+        Mode::Generic => attach(XdpFlags::SKB_MODE),
+        Mode::Auto => attach(XdpFlags::DRV_MODE).or_else(|err| {
+            eprintln!("native mode unavailable on {iface} ({err}); using generic mode");
+            attach(XdpFlags::SKB_MODE)
+        }),
+    };
+    result.with_context(|| format!("failed to attach to {iface}"))
+}
+
+fn print_drops(drops: &impl MapCore, denied: &[Cidr]) -> Result<()> {
+    println!("{:<45} {:>12}", "RULE", "DROPS");
+    for (idx, cidr) in denied.iter().enumerate() {
+        let count = drops
+            .lookup_percpu_sum::<u64>(&(idx as u32).to_ne_bytes(), MapFlags::ANY)
+            .context("failed to read drop counter")?
+            .unwrap_or(0);
+        println!("{:<45} {count:>12}", cidr.to_string());
+    }
+    println!();
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+    ensure!(
+        opts.denied.len() <= MAX_RULES,
+        "at most {MAX_RULES} rules are supported"
+    );
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut builder = XdpFirewallSkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open_object = MaybeUninit::uninit();
+    let open = builder.open(&mut open_object)?;
+    let skel = open.load()?;
+
+    for (idx, cidr) in opts.denied.iter().enumerate() {
+        let () = skel
+            .maps
+            .denylist
+            .update(&cidr.to_key(), &(idx as u32).to_ne_bytes(), MapFlags::ANY)
+            .with_context(|| format!("failed to add {cidr} to denylist"))?;
+    }
+
+    let xdp = Xdp::new(skel.progs.xdp_firewall.as_fd());
+    let mut attachments = Attachments {
+        xdp: &xdp,
+        attached: Vec::new(),
+    };
+    for iface in &opts.ifaces {
+        let attachment = attach(&xdp, iface, opts.mode)?;
+        let mode = if attachment.flags == XdpFlags::DRV_MODE {
+            "native"
+        } else {
+            "generic"
+        };
+        println!("Attached to {iface} in {mode} mode");
+        attachments.attached.push(attachment);
+    }
+
+    let interval = Duration::from_secs(opts.interval);
+    let mut elapsed = Duration::ZERO;
+    while running.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(100));
+        elapsed += Duration::from_millis(100);
+        if elapsed >= interval {
+            let () = print_drops(&skel.maps.drops, &opts.denied)?;
+            elapsed = Duration::ZERO;
+        }
+    }
+
+    ensure!(attachments.detach(), "failed to detach XDP program");
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Benchmarks of map lookups in a tight loop, as performed when
+//! periodically scraping metrics from maps.
+//!
+//! Creating maps requires `CAP_BPF`, so run with:
+//! ```sh
+//! $ sudo cargo bench --bench map
+//! ```
+
+use std::hint::black_box;
+use std::mem::size_of;
+use std::time::Duration;
+use std::time::Instant;
+
+use libbpf_rs::MapCore as _;
+use libbpf_rs::MapFlags;
+use libbpf_rs::MapHandle;
+use libbpf_rs::MapType;
+
+
+/// The number of entries in the benchmarked map.
+const ENTRIES: u32 = 1024;
+/// The number of passes over all entries per measurement.
+const PASSES: u32 = 1000;
+
+
+/// Measure the time `f` takes for looking up all entries `PASSES` times,
+/// reporting the time per lookup.
+fn measure<F>(name: &str, mut f: F) -> Duration
+where
+    F: FnMut(&[u8; 4]),
+{
+    let start = Instant::now();
+    for _ in 0..PASSES {
+        for key in 0..ENTRIES {
+            let () = f(&key.to_ne_bytes());
+        }
+    }
+    let elapsed = start.elapsed() / (PASSES * ENTRIES);
+    println!("{name:>12}: {elapsed:?} per lookup");
+    elapsed
+}
+
+
+fn main() {
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = match MapHandle::create(MapType::Hash, Some("bench"), 4, 8, ENTRIES, &opts) {
+        Ok(map) => map,
+        Err(err) => {
+            eprintln!("skipping map benchmarks: failed to create map: {err}");
+            return
+        }
+    };
+    for key in 0..ENTRIES {
+        let () = map
+            .update(
+                &key.to_ne_bytes(),
+                &u64::from(key).to_ne_bytes(),
+                MapFlags::ANY,
+            )
+            .unwrap();
+    }
+
+    let lookup = measure("lookup", |key| {
+        let value = map.lookup(key, MapFlags::ANY).unwrap();
+        let _value = black_box(value);
+    });
+
+    let mut value = [0; 8];
+    let lookup_into = measure("lookup_into", |key| {
+        let found = map.lookup_into(key, &mut value, MapFlags::ANY).unwrap();
+        let _found = black_box(found);
+    });
+
+    println!(
+        "{:>12}: {:.2}x",
+        "speedup",
+        lookup.as_secs_f64() / lookup_into.as_secs_f64()
+    );
+}
@@ -0,0 +1,16 @@
+//! Fuzz opening BPF objects from memory with validation enabled, checking
+//! that whatever passes validation can safely be handed to libbpf.
+//!
+//! Seeding the corpus with the test objects in `tests/bin` helps the
+//! fuzzer reach beyond the ELF header checks.
+
+#![no_main]
+
+use libbpf_rs::ObjectBuilder;
+use libbpf_rs::ObjectLimits;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut builder = ObjectBuilder::default();
+    let _result = builder.validate(ObjectLimits::default()).open_memory(data);
+});
@@ -0,0 +1,12 @@
+//! Fuzz the validation of BPF object files, which must never panic,
+//! regardless of input.
+
+#![no_main]
+
+use libbpf_rs::validate_object;
+use libbpf_rs::ObjectLimits;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _result = validate_object(data, &ObjectLimits::default());
+});
@@ -0,0 +1,394 @@
+//! Support for BPF arenas, i.e., memory shared between BPF programs and
+//! userspace.
+//!
+//! A `BPF_MAP_TYPE_ARENA` map is a sparse region of up to 4 GiB, allocated
+//! page by page, either by BPF programs via `bpf_arena_alloc_pages` or by
+//! userspace touching a page. Userspace maps the arena into its address
+//! space, at which point pointers stored in the arena by BPF programs and
+//! userspace alike refer to the same data, allowing for data structures
+//! such as lists and trees to be shared.
+//!
+//! [`create`] creates an arena outside of any object, while arenas
+//! declared in an object are mapped by libbpf when loading it. [`Arena`]
+//! provides access to either.
+//!
+//! ```no_run
+//! # fn example(map: &libbpf_rs::Map<'_>) -> libbpf_rs::Result<()> {
+//! use libbpf_rs::arena::Arena;
+//!
+//! let arena = Arena::from_map(map)?;
+//! let () = arena.write(0, 42u64)?;
+//! assert_eq!(arena.read::<u64>(0)?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::OsStr;
+use std::io;
+use std::marker::PhantomData;
+use std::mem::align_of;
+use std::mem::size_of;
+use std::mem::MaybeUninit;
+use std::os::fd::AsFd as _;
+use std::os::fd::AsRawFd as _;
+use std::ptr::NonNull;
+use std::sync::atomic::AtomicU64;
+
+use crate::AsRawLibbpf as _;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Map;
+use crate::MapCore;
+use crate::MapHandle;
+use crate::MapInfo;
+use crate::MapType;
+use crate::Result;
+
+
+/// Options for creating an arena with [`create`].
+#[derive(Clone, Debug, Default)]
+pub struct ArenaOpts {
+    /// The address in userspace to map the arena at, or zero to let the
+    /// kernel pick one when the arena is first mapped.
+    ///
+    /// Pointers stored in the arena are only meaningful in processes that
+    /// mapped it at the same address.
+    pub user_addr: u64,
+    /// Whether to raise `SIGSEGV` when userspace accesses a page that has
+    /// not been allocated, instead of allocating it on demand.
+    pub segv_on_fault: bool,
+    /// Whether to disable the conversion of pointers from the BPF to the
+    /// userspace representation when BPF programs store them, for arenas
+    /// only used for storing plain data.
+    pub no_user_conv: bool,
+    /// The struct is non-exhaustive and open to extension.
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl From<&ArenaOpts> for libbpf_sys::bpf_map_create_opts {
+    fn from(opts: &ArenaOpts) -> Self {
+        let ArenaOpts {
+            user_addr,
+            segv_on_fault,
+            no_user_conv,
+            _non_exhaustive,
+        } = opts;
+
+        let mut map_flags = libbpf_sys::BPF_F_MMAPABLE;
+        if *segv_on_fault {
+            map_flags |= libbpf_sys::BPF_F_SEGV_ON_FAULT;
+        }
+        if *no_user_conv {
+            map_flags |= libbpf_sys::BPF_F_NO_USER_CONV;
+        }
+
+        libbpf_sys::bpf_map_create_opts {
+            sz: size_of::<Self>() as _,
+            map_flags,
+            map_extra: *user_addr,
+            ..Default::default()
+        }
+    }
+}
+
+
+/// Create an arena spanning `pages` pages.
+///
+/// The returned map can be mapped into the process with
+/// [`Arena::from_handle`] and be shared with BPF programs, e.g., via
+/// [`OpenMapMut::reuse_fd`][crate::OpenMapMut::reuse_fd].
+pub fn create<T>(name: Option<T>, pages: u32, opts: &ArenaOpts) -> Result<MapHandle>
+where
+    T: AsRef<OsStr>,
+{
+    let opts = libbpf_sys::bpf_map_create_opts::from(opts);
+    MapHandle::create(MapType::Arena, name, 0, 0, pages, &opts)
+}
+
+
+/// Plain old data types that can be read from and written to an
+/// [`Arena`].
+///
+/// # Safety
+/// Implementors must be valid for any bit pattern and must not contain
+/// padding.
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(
+            // SAFETY: Primitive integers are valid for any bit pattern.
+            unsafe impl Pod for $ty {}
+        )*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+// SAFETY: Arrays of plain old data are plain old data themselves.
+unsafe impl<T, const N: usize> Pod for [T; N] where T: Pod {}
+
+
+/// A view of an arena mapped into the process.
+///
+/// As BPF programs may access the arena concurrently, all accesses are
+/// performed through raw pointers; it is up to the program and userspace
+/// to coordinate, e.g., by means of atomic operations (see
+/// [`Arena::atomic_u64`]).
+#[derive(Debug)]
+pub struct Arena<'map> {
+    ptr: NonNull<u8>,
+    len: usize,
+    /// Whether we own the mapping, as opposed to libbpf.
+    owned: bool,
+    /// Whether accessing unallocated pages raises `SIGSEGV`.
+    segv_on_fault: bool,
+    _map: PhantomData<&'map ()>,
+}
+
+impl<'map> Arena<'map> {
+    fn check_type<M>(map: &M) -> Result<()>
+    where
+        M: MapCore,
+    {
+        if map.map_type() != MapType::Arena {
+            return Err(Error::with_invalid_data(format!(
+                "map `{}` is of type {:?}; expected {:?}",
+                map.name().to_string_lossy(),
+                map.map_type(),
+                MapType::Arena,
+            )))
+        }
+        Ok(())
+    }
+
+    /// Access the arena of a loaded object, which libbpf mapped into the
+    /// process while loading it.
+    pub fn from_map(map: &'map Map<'_>) -> Result<Self> {
+        let () = Self::check_type(map)?;
+
+        let mut len = 0u64;
+        // SAFETY: The map pointer is valid and libbpf reports the
+        //         mapping of arenas via their initial value.
+        let ptr = unsafe {
+            libbpf_sys::bpf_map__initial_value(
+                map.as_libbpf_object().as_ptr(),
+                &mut len as *mut _ as _,
+            )
+        };
+        let ptr = NonNull::new(ptr.cast()).ok_or_else(|| {
+            Error::with_invalid_data(format!(
+                "arena `{}` is not mapped",
+                map.name().to_string_lossy()
+            ))
+        })?;
+
+        // SAFETY: The map pointer is valid.
+        let flags = unsafe { libbpf_sys::bpf_map__map_flags(map.as_libbpf_object().as_ptr()) };
+
+        let slf = Self {
+            ptr,
+            len: len as usize,
+            owned: false,
+            segv_on_fault: flags & libbpf_sys::BPF_F_SEGV_ON_FAULT != 0,
+            _map: PhantomData,
+        };
+        Ok(slf)
+    }
+
+    /// Map the arena referred to by `map` into the process.
+    ///
+    /// An arena can only be mapped once and, if it was created with a
+    /// [user address][ArenaOpts::user_addr], only at said address.
+    pub fn from_handle(map: &'map MapHandle) -> Result<Self> {
+        let () = Self::check_type(map)?;
+
+        let info = MapInfo::new(map.as_fd())?;
+        // SAFETY: `sysconf` is always safe to call.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let len = info.info.max_entries as usize * page_size;
+        let addr = info.info.map_extra as *mut libc::c_void;
+
+        // SAFETY: All arguments are valid and we check for errors.
+        let ptr = unsafe {
+            libc::mmap(
+                addr,
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                map.as_fd().as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error()
+                .with_context(|| format!("failed to map arena `{}`", map.name().to_string_lossy())))
+        }
+
+        let slf = Self {
+            // SAFETY: `mmap` succeeded and hence did not return NULL.
+            ptr: unsafe { NonNull::new_unchecked(ptr.cast()) },
+            len,
+            owned: true,
+            segv_on_fault: info.info.map_flags & libbpf_sys::BPF_F_SEGV_ON_FAULT != 0,
+            _map: PhantomData,
+        };
+        Ok(slf)
+    }
+
+    /// Retrieve the size of the arena in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the arena is empty, i.e., spans no pages.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Retrieve the address the arena is mapped at.
+    ///
+    /// Pointers stored in the arena are relative to this address.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Convert a pointer stored in the arena into an offset from its
+    /// start, if it points into the arena.
+    pub fn offset_of(&self, addr: u64) -> Option<usize> {
+        let offset = usize::try_from(addr.checked_sub(self.ptr.as_ptr() as u64)?).ok()?;
+        (offset < self.len).then_some(offset)
+    }
+
+    /// Retrieve a pointer to the `T` at `offset`, checking that it lies
+    /// within the arena and, if `align` is set, that it is suitably
+    /// aligned.
+    fn ptr<T>(&self, offset: usize, align: bool) -> Result<*mut T> {
+        let end = offset.checked_add(size_of::<T>());
+        if end.map_or(true, |end| end > self.len) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "access of {} bytes at offset {offset:#x} exceeds arena of {:#x} bytes",
+                    size_of::<T>(),
+                    self.len
+                ),
+            ))
+        }
+
+        // SAFETY: The offset is within the mapping.
+        let ptr = unsafe { self.ptr.as_ptr().add(offset) }.cast::<T>();
+        if align && ptr as usize % align_of::<T>() != 0 {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("offset {offset:#x} is not suitably aligned"),
+            ))
+        }
+        Ok(ptr)
+    }
+
+    /// Retrieve a pointer to the `T` at `offset` for an access through a
+    /// safe interface, which is only possible if it cannot fault.
+    fn safe_ptr<T>(&self, offset: usize, align: bool) -> Result<*mut T> {
+        if self.segv_on_fault {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                "arena raises SIGSEGV on access to unallocated pages; use `Arena::as_ptr` instead",
+            ))
+        }
+        self.ptr::<T>(offset, align)
+    }
+
+    /// Read the value at `offset`.
+    ///
+    /// Accessing a page that has not been allocated allocates it. As that
+    /// is not the case for arenas created with
+    /// [`ArenaOpts::segv_on_fault`], these are rejected.
+    ///
+    /// BPF programs may modify the value concurrently, so it is read
+    /// using volatile accesses and may be torn.
+    pub fn read<T>(&self, offset: usize) -> Result<T>
+    where
+        T: Pod,
+    {
+        let ptr = self.safe_ptr::<T>(offset, false)?.cast::<u8>();
+        let mut value = MaybeUninit::<T>::uninit();
+        let out = value.as_mut_ptr().cast::<u8>();
+        for i in 0..size_of::<T>() {
+            // SAFETY: The value is within the mapping and all of its pages
+            //         get allocated on access.
+            let () = unsafe { out.add(i).write(ptr.add(i).read_volatile()) };
+        }
+        // SAFETY: We initialized all bytes and `T` is valid for any bit
+        //         pattern.
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Write `value` at `offset`.
+    ///
+    /// Arenas created with [`ArenaOpts::segv_on_fault`] are rejected, as
+    /// for [`Arena::read`]. The value is written using volatile accesses.
+    pub fn write<T>(&self, offset: usize, value: T) -> Result<()>
+    where
+        T: Pod,
+    {
+        let ptr = self.safe_ptr::<T>(offset, false)?.cast::<u8>();
+        let value = (&value as *const T).cast::<u8>();
+        for i in 0..size_of::<T>() {
+            // SAFETY: The value is within the mapping, which is writable
+            //         and all of its pages get allocated on access.
+            let () = unsafe { ptr.add(i).write_volatile(value.add(i).read()) };
+        }
+        Ok(())
+    }
+
+    /// Access the naturally aligned 64 bit value at `offset` atomically,
+    /// e.g., for counters shared with BPF programs using
+    /// `__sync_fetch_and_add`.
+    ///
+    /// Arenas created with [`ArenaOpts::segv_on_fault`] are rejected, as
+    /// for [`Arena::read`].
+    pub fn atomic_u64(&self, offset: usize) -> Result<&AtomicU64> {
+        let ptr = self.safe_ptr::<AtomicU64>(offset, true)?;
+        // SAFETY: The pointer is within the mapping, which lives as long
+        //         as we do, and suitably aligned.
+        Ok(unsafe { &*ptr })
+    }
+}
+
+impl Drop for Arena<'_> {
+    fn drop(&mut self) {
+        if self.owned {
+            // SAFETY: We created the mapping with the given size and it is
+            //         no longer referenced.
+            let _ret = unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that `ArenaOpts` are converted to the expected map creation
+    /// options.
+    #[test]
+    fn arena_opts_conversion() {
+        let opts = ArenaOpts {
+            user_addr: 0x1000_0000,
+            segv_on_fault: true,
+            ..Default::default()
+        };
+        let opts = libbpf_sys::bpf_map_create_opts::from(&opts);
+        assert_eq!(
+            opts.map_flags,
+            libbpf_sys::BPF_F_MMAPABLE | libbpf_sys::BPF_F_SEGV_ON_FAULT
+        );
+        assert_eq!(opts.map_extra, 0x1000_0000);
+    }
+}
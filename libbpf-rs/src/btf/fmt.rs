@@ -0,0 +1,484 @@
+//! Human readable rendering of raw data, guided by BTF type information.
+//!
+//! A [`ValueFormatter`] renders the bytes of, say, a map value according
+//! to their BTF type: structs and unions with the names of their members,
+//! enums by the name of the matching enumerator, and character arrays as
+//! strings. The output is kept on a single line, making it suitable for
+//! log output.
+//!
+//! Members and typedefs can be annotated via `btf_decl_tag` to render
+//! addresses in their conventional notation:
+//!
+//! ```c
+//! struct flow {
+//!     __u32 saddr __attribute__((btf_decl_tag("ipv4")));
+//!     __u8 daddr[16] __attribute__((btf_decl_tag("ipv6")));
+//!     __u8 mac[6] __attribute__((btf_decl_tag("mac")));
+//! };
+//! ```
+//!
+//! IPv4 and IPv6 addresses are expected in network byte order, as
+//! commonly found in packet headers.
+//!
+//! ```no_run
+//! # fn example(btf: &libbpf_rs::Btf<'_>, value: &[u8]) -> libbpf_rs::Result<()> {
+//! use libbpf_rs::btf::fmt::ValueFormatter;
+//! use libbpf_rs::btf::types::Struct;
+//!
+//! let ty = btf.type_by_name::<Struct<'_>>("flow").unwrap();
+//! let formatter = ValueFormatter::new(btf);
+//! // E.g., `{saddr: 10.0.0.1, daddr: ::1, mac: 02:00:00:00:00:01}`
+//! println!("{}", formatter.format(ty.type_id(), value)?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt as _;
+
+use super::types;
+use super::types::Composite;
+use super::types::IntEncoding;
+use super::types::MemberAttr;
+use super::Btf;
+use super::BtfKind;
+use super::BtfType;
+use super::HasSize as _;
+use super::ReferencesType as _;
+use super::TypeId;
+use crate::AsRawLibbpf as _;
+use crate::Error;
+use crate::Result;
+
+
+/// Retrieve the slice of `data` covering `range`, failing if `data` is
+/// too short.
+pub(super) fn slice(data: &[u8], range: Range<usize>) -> Result<&[u8]> {
+    let len = data.len();
+    data.get(range.clone()).ok_or_else(|| {
+        Error::with_invalid_data(format!(
+            "data of {len} bytes too short to access range {range:?}"
+        ))
+    })
+}
+
+pub(super) fn type_size(ty: &BtfType<'_>) -> Result<usize> {
+    // SAFETY: The BTF pointer is valid and `btf__resolve_size` does not
+    //         require anything else.
+    let size = unsafe {
+        libbpf_sys::btf__resolve_size(ty.source.as_libbpf_object().as_ptr(), ty.type_id().into())
+    };
+    usize::try_from(size).map_err(|_| {
+        Error::with_invalid_data(format!(
+            "failed to resolve size of BTF type {}",
+            ty.type_id()
+        ))
+    })
+}
+
+/// Read `bits` bits starting at bit offset `bit_offset` of `data` as an
+/// unsigned integer, using native bit ordering.
+pub(super) fn read_bits(data: &[u8], bit_offset: usize, bits: usize) -> Result<u128> {
+    if bits == 0 || bits > 128 {
+        return Err(Error::with_invalid_data(format!(
+            "unsupported integer width of {bits} bits"
+        )))
+    }
+
+    let start = bit_offset / 8;
+    let shift = bit_offset % 8;
+    let len = (shift + bits + 7) / 8;
+    if len > 16 {
+        return Err(Error::with_invalid_data(format!(
+            "unaligned integer of {bits} bits at bit offset {bit_offset} is not supported"
+        )))
+    }
+    let bytes = slice(data, start..start + len)?;
+
+    let mut buf = [0; 16];
+    let raw = if cfg!(target_endian = "little") {
+        let () = buf[..len].copy_from_slice(bytes);
+        u128::from_le_bytes(buf) >> shift
+    } else {
+        let () = buf[16 - len..].copy_from_slice(bytes);
+        u128::from_be_bytes(buf) >> (len * 8 - shift - bits)
+    };
+
+    let mask = if bits == 128 {
+        u128::MAX
+    } else {
+        (1 << bits) - 1
+    };
+    Ok(raw & mask)
+}
+
+/// Sign extend the `bits` wide integer `raw`.
+pub(super) fn sign_extend(raw: u128, bits: usize) -> i128 {
+    ((raw << (128 - bits)) as i128) >> (128 - bits)
+}
+
+/// Check whether the provided type is a character type, as used for
+/// strings.
+pub(super) fn is_char(ty: &BtfType<'_>) -> bool {
+    let ty = ty.skip_mods_and_typedefs();
+    match types::Int::try_from(ty) {
+        Ok(int) => {
+            int.bits == 8
+                && (matches!(int.encoding, IntEncoding::Char)
+                    || int.name().is_some_and(|name| name == "char"))
+        }
+        Err(..) => false,
+    }
+}
+
+fn lookup<'btf>(btf: &'btf Btf<'btf>, type_id: TypeId) -> Result<BtfType<'btf>> {
+    btf.type_by_id::<BtfType<'_>>(type_id)
+        .ok_or_else(|| Error::with_invalid_data(format!("BTF type {type_id} not found")))
+}
+
+
+/// An annotation attached to a member or typedef via `btf_decl_tag`,
+/// changing how values are rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Annotation {
+    /// An IPv4 address in network byte order.
+    Ipv4,
+    /// An IPv6 address.
+    Ipv6,
+    /// A MAC address.
+    Mac,
+}
+
+impl Annotation {
+    fn from_tag(tag: &[u8]) -> Option<Self> {
+        match tag {
+            b"ipv4" => Some(Self::Ipv4),
+            b"ipv6" => Some(Self::Ipv6),
+            b"mac" => Some(Self::Mac),
+            _ => None,
+        }
+    }
+
+    /// Render `data` as annotated, if it is of the expected size.
+    fn render(&self, data: &[u8], out: &mut String) -> bool {
+        // Writing to a `String` never fails.
+        match self {
+            Self::Ipv4 => {
+                let Ok(addr) = <[u8; 4]>::try_from(data) else {
+                    return false
+                };
+                let _result = write!(out, "{}", Ipv4Addr::from(addr));
+            }
+            Self::Ipv6 => {
+                let Ok(addr) = <[u8; 16]>::try_from(data) else {
+                    return false
+                };
+                let _result = write!(out, "{}", Ipv6Addr::from(addr));
+            }
+            Self::Mac => {
+                if data.len() != 6 {
+                    return false
+                }
+                let mac = data
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect::<Vec<_>>();
+                let () = out.push_str(&mac.join(":"));
+            }
+        }
+        true
+    }
+}
+
+
+/// A formatter rendering raw data as human readable text, based on BTF
+/// type information.
+///
+/// See the [module documentation][self] for details.
+#[derive(Debug)]
+pub struct ValueFormatter<'btf> {
+    btf: &'btf Btf<'btf>,
+    /// Annotations, keyed by the annotated type and, for members, the
+    /// member index.
+    annotations: HashMap<(TypeId, Option<u32>), Annotation>,
+}
+
+impl<'btf> ValueFormatter<'btf> {
+    /// Create a formatter for types described by `btf`.
+    pub fn new(btf: &'btf Btf<'btf>) -> Self {
+        let annotations = btf
+            .type_by_kind::<types::DeclTag<'_>>()
+            .filter_map(|tag| {
+                let annotation = Annotation::from_tag(tag.name()?.as_bytes())?;
+                let key = (tag.referenced_type_id(), tag.component_index());
+                Some((key, annotation))
+            })
+            .collect();
+
+        Self { btf, annotations }
+    }
+
+    /// Render `data`, interpreted as a value of the type with the given
+    /// ID.
+    pub fn format(&self, type_id: TypeId, data: &[u8]) -> Result<String> {
+        let ty = lookup(self.btf, type_id)?;
+        let mut out = String::new();
+        let () = self.render_type(ty, data, &mut out)?;
+        Ok(out)
+    }
+
+    /// Find the annotation of `ty` or of any typedef it refers to.
+    fn annotation(&self, ty: BtfType<'btf>) -> Option<Annotation> {
+        let mut ty = Some(ty);
+        while let Some(t) = ty {
+            if let Some(annotation) = self.annotations.get(&(t.type_id(), None)) {
+                return Some(*annotation)
+            }
+            if !matches!(
+                t.kind(),
+                BtfKind::Typedef
+                    | BtfKind::Const
+                    | BtfKind::Volatile
+                    | BtfKind::Restrict
+                    | BtfKind::TypeTag
+            ) {
+                break
+            }
+            ty = t.next_type();
+        }
+        None
+    }
+
+    fn render_int(raw: u128, bits: usize, signed: bool, out: &mut String) {
+        let _result = if signed {
+            write!(out, "{}", sign_extend(raw, bits))
+        } else {
+            write!(out, "{raw}")
+        };
+    }
+
+    fn render_composite(&self, ty: BtfType<'btf>, data: &[u8], out: &mut String) -> Result<()> {
+        let composite = Composite::try_from(ty).unwrap();
+        let () = out.push('{');
+        for (idx, member) in composite.iter().enumerate() {
+            if idx > 0 {
+                let () = out.push_str(", ");
+            }
+            if let Some(name) = member.name {
+                let _result = write!(out, "{}: ", name.to_string_lossy());
+            }
+
+            let member_ty = lookup(self.btf, member.ty)?;
+            match member.attr {
+                MemberAttr::Normal { offset } => {
+                    let start = offset as usize / 8;
+                    let size = type_size(&member_ty)?;
+                    let data = slice(data, start..start + size)?;
+                    let annotation = self
+                        .annotations
+                        .get(&(ty.type_id(), Some(idx as u32)))
+                        .copied()
+                        .or_else(|| self.annotation(member_ty));
+                    if !annotation.is_some_and(|annotation| annotation.render(data, out)) {
+                        let () = self.render_type(member_ty, data, out)?;
+                    }
+                }
+                MemberAttr::BitField { size, offset } => {
+                    let raw = read_bits(data, offset as usize, size.into())?;
+                    let signed = types::Int::try_from(member_ty.skip_mods_and_typedefs())
+                        .is_ok_and(|int| matches!(int.encoding, IntEncoding::Signed));
+                    let () = Self::render_int(raw, size.into(), signed, out);
+                }
+            }
+        }
+        let () = out.push('}');
+        Ok(())
+    }
+
+    fn render_type(&self, ty: BtfType<'btf>, data: &[u8], out: &mut String) -> Result<()> {
+        if let Some(annotation) = self.annotation(ty) {
+            if annotation.render(data, out) {
+                return Ok(())
+            }
+        }
+
+        let ty = ty.skip_mods_and_typedefs();
+        match ty.kind() {
+            BtfKind::Int => {
+                let int = types::Int::try_from(ty).unwrap();
+                let bits = usize::from(int.bits);
+                let raw = read_bits(slice(data, 0..int.size())?, int.offset.into(), bits)?;
+                match int.encoding {
+                    IntEncoding::Bool => {
+                        let _result = write!(out, "{}", raw != 0);
+                    }
+                    IntEncoding::Signed => Self::render_int(raw, bits, true, out),
+                    IntEncoding::None | IntEncoding::Char => {
+                        Self::render_int(raw, bits, false, out)
+                    }
+                }
+            }
+            BtfKind::Float => {
+                let float = types::Float::try_from(ty).unwrap();
+                let bytes = slice(data, 0..float.size())?;
+                let _result = match bytes.len() {
+                    4 => write!(out, "{}", f32::from_ne_bytes(bytes.try_into().unwrap())),
+                    8 => write!(out, "{}", f64::from_ne_bytes(bytes.try_into().unwrap())),
+                    _ => write!(out, "{bytes:02x?}"),
+                };
+            }
+            BtfKind::Ptr => {
+                let size = self.btf.ptr_size()?.get();
+                let raw = read_bits(slice(data, 0..size)?, 0, size * 8)?;
+                let _result = write!(out, "{raw:#x}");
+            }
+            BtfKind::Enum => {
+                let enum_ = types::Enum::try_from(ty).unwrap();
+                let bits = enum_.size() * 8;
+                let raw = read_bits(slice(data, 0..enum_.size())?, 0, bits)?;
+                // Enumerators are stored as 32 bit values; compare based on
+                // the lower 32 bits to be agnostic to the enum's signedness.
+                match enum_
+                    .iter()
+                    .find(|member| member.value as u32 == raw as u32)
+                    .and_then(|member| member.name)
+                {
+                    Some(name) => out.push_str(&name.to_string_lossy()),
+                    None => Self::render_int(raw, bits, false, out),
+                }
+            }
+            BtfKind::Enum64 => {
+                let enum_ = types::Enum64::try_from(ty).unwrap();
+                let bits = enum_.size() * 8;
+                let raw = read_bits(slice(data, 0..enum_.size())?, 0, bits)?;
+                match enum_
+                    .iter()
+                    .find(|member| u128::from(member.value) == raw)
+                    .and_then(|member| member.name)
+                {
+                    Some(name) => out.push_str(&name.to_string_lossy()),
+                    None => Self::render_int(raw, bits, false, out),
+                }
+            }
+            BtfKind::Array => {
+                let array = types::Array::try_from(ty).unwrap();
+                let elem = array.contained_type();
+                let elem_size = type_size(&elem)?;
+                let bytes = slice(data, 0..elem_size * array.capacity())?;
+
+                if is_char(&elem) {
+                    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                    let _result = write!(out, "{:?}", String::from_utf8_lossy(&bytes[..end]));
+                } else {
+                    let () = out.push('[');
+                    if elem_size != 0 {
+                        for (idx, chunk) in bytes.chunks_exact(elem_size).enumerate() {
+                            if idx > 0 {
+                                let () = out.push_str(", ");
+                            }
+                            let () = self.render_type(elem, chunk, out)?;
+                        }
+                    }
+                    let () = out.push(']');
+                }
+            }
+            BtfKind::Struct | BtfKind::Union => self.render_composite(ty, data, out)?,
+            BtfKind::Var => {
+                let var = types::Var::try_from(ty).unwrap();
+                self.render_type(var.referenced_type(), data, out)?
+            }
+            BtfKind::DataSec => {
+                let datasec = types::DataSec::try_from(ty).unwrap();
+                let () = out.push('{');
+                for (idx, info) in datasec.iter().enumerate() {
+                    if idx > 0 {
+                        let () = out.push_str(", ");
+                    }
+                    let start = info.offset as usize;
+                    let bytes = slice(data, start..start + info.size)?;
+                    let var = lookup(self.btf, info.ty)?;
+                    if let Some(name) = var.name() {
+                        let _result = write!(out, "{}: ", name.to_string_lossy());
+                    }
+                    let () = self.render_type(var, bytes, out)?;
+                }
+                let () = out.push('}');
+            }
+            BtfKind::Void
+            | BtfKind::Fwd
+            | BtfKind::Func
+            | BtfKind::FuncProto
+            | BtfKind::DeclTag
+            | BtfKind::Volatile
+            | BtfKind::Const
+            | BtfKind::Restrict
+            | BtfKind::Typedef
+            | BtfKind::TypeTag => {
+                let _result = write!(out, "{data:02x?}");
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that we can extract integers and bit fields from raw data.
+    #[test]
+    fn bit_reading() {
+        let data = 0x1234_5678_u32.to_ne_bytes();
+        assert_eq!(read_bits(&data, 0, 32).unwrap(), 0x1234_5678);
+        assert_eq!(read_bits(&data, 0, 8).unwrap(), 0x78);
+        assert_eq!(read_bits(&data, 4, 4).unwrap(), 0x7);
+        assert_eq!(read_bits(&data, 12, 8).unwrap(), 0x45);
+        assert!(read_bits(&data, 24, 16).is_err());
+        assert!(read_bits(&data, 0, 0).is_err());
+    }
+
+    /// Check that annotated values are rendered in their conventional
+    /// notation.
+    #[test]
+    fn annotation_rendering() {
+        let render = |annotation: Annotation, data: &[u8]| {
+            let mut out = String::new();
+            annotation.render(data, &mut out).then_some(out)
+        };
+
+        assert_eq!(
+            render(Annotation::Ipv4, &[10, 0, 0, 1]).as_deref(),
+            Some("10.0.0.1")
+        );
+        let mut ipv6 = [0; 16];
+        ipv6[15] = 1;
+        assert_eq!(render(Annotation::Ipv6, &ipv6).as_deref(), Some("::1"));
+        assert_eq!(
+            render(Annotation::Mac, &[2, 0, 0, 0xab, 0xcd, 1]).as_deref(),
+            Some("02:00:00:ab:cd:01")
+        );
+        assert_eq!(render(Annotation::Ipv4, &[1, 2, 3]), None);
+        assert_eq!(Annotation::from_tag(b"mac"), Some(Annotation::Mac));
+        assert_eq!(Annotation::from_tag(b"kptr"), None);
+    }
+
+    /// Check that we can render a kernel type using its BTF.
+    #[test]
+    fn vmlinux_type_formatting() {
+        let btf = Btf::from_vmlinux().unwrap();
+        let ty = btf.type_by_name::<types::Struct<'_>>("list_head").unwrap();
+        let formatter = ValueFormatter::new(&btf);
+        let data = [0x11; 16];
+        let value = formatter.format(ty.type_id(), &data).unwrap();
+        assert_eq!(
+            value,
+            "{next: 0x1111111111111111, prev: 0x1111111111111111}"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Rendering of raw data as structured JSON, guided by BTF type
+//! information.
+
+use std::fmt::Write as _;
+
+use serde_json::Map;
+use serde_json::Number;
+use serde_json::Value;
+
+use super::fmt::is_char;
+use super::fmt::read_bits;
+use super::fmt::sign_extend;
+use super::fmt::slice;
+use super::fmt::type_size;
+use super::types;
+use super::types::Composite;
+use super::types::IntEncoding;
+use super::types::MemberAttr;
+use super::Btf;
+use super::BtfKind;
+use super::BtfType;
+use super::HasSize as _;
+use super::ReferencesType as _;
+use super::TypeId;
+use crate::Error;
+use crate::Result;
+
+
+/// Render raw bytes as a hexadecimal string, for use when no type
+/// information is available.
+pub(crate) fn render_bytes(data: &[u8]) -> Value {
+    let mut hex = String::with_capacity(data.len() * 2);
+    for byte in data {
+        // Writing to a `String` never fails.
+        let _result = write!(hex, "{byte:02x}");
+    }
+    Value::String(hex)
+}
+
+/// Render `data`, interpreted as a value of the type with the given ID.
+pub(crate) fn render(btf: &Btf<'_>, type_id: TypeId, data: &[u8]) -> Result<Value> {
+    let ty = btf
+        .type_by_id::<BtfType<'_>>(type_id)
+        .ok_or_else(|| Error::with_invalid_data(format!("BTF type {type_id} not found")))?;
+    render_type(ty, data)
+}
+
+/// Render an integer of the given width, sign extending it if requested.
+fn render_int(raw: u128, bits: usize, signed: bool) -> Value {
+    if signed {
+        let value = sign_extend(raw, bits);
+        i64::try_from(value)
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(value.to_string()))
+    } else {
+        u64::try_from(raw)
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(raw.to_string()))
+    }
+}
+
+fn render_composite(ty: BtfType<'_>, data: &[u8]) -> Result<Value> {
+    let composite = Composite::try_from(ty).unwrap();
+    let mut object = Map::new();
+    for member in composite.iter() {
+        let ty = ty
+            .source
+            .type_by_id::<BtfType<'_>>(member.ty)
+            .ok_or_else(|| Error::with_invalid_data(format!("BTF type {} not found", member.ty)))?;
+
+        let value = match member.attr {
+            MemberAttr::Normal { offset } => {
+                let start = offset as usize / 8;
+                let size = type_size(&ty)?;
+                render_type(ty, slice(data, start..start + size)?)?
+            }
+            MemberAttr::BitField { size, offset } => {
+                let raw = read_bits(data, offset as usize, size.into())?;
+                let signed = types::Int::try_from(ty.skip_mods_and_typedefs())
+                    .is_ok_and(|int| matches!(int.encoding, IntEncoding::Signed));
+                render_int(raw, size.into(), signed)
+            }
+        };
+
+        match (member.name, value) {
+            (Some(name), value) => {
+                let _prev = object.insert(name.to_string_lossy().into_owned(), value);
+            }
+            // Members of anonymous structs and unions are accessed as if
+            // they were members of the enclosing type, so flatten them.
+            (None, Value::Object(members)) => {
+                let () = object.extend(members);
+            }
+            (None, value) => {
+                let _prev = object.insert(String::new(), value);
+            }
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+fn render_type(ty: BtfType<'_>, data: &[u8]) -> Result<Value> {
+    let ty = ty.skip_mods_and_typedefs();
+    let value = match ty.kind() {
+        BtfKind::Int => {
+            let int = types::Int::try_from(ty).unwrap();
+            let bits = usize::from(int.bits);
+            let raw = read_bits(slice(data, 0..int.size())?, int.offset.into(), bits)?;
+            match int.encoding {
+                IntEncoding::Bool => Value::Bool(raw != 0),
+                IntEncoding::Signed => render_int(raw, bits, true),
+                IntEncoding::None | IntEncoding::Char => render_int(raw, bits, false),
+            }
+        }
+        BtfKind::Float => {
+            let float = types::Float::try_from(ty).unwrap();
+            let bytes = slice(data, 0..float.size())?;
+            let value = match bytes.len() {
+                4 => f64::from(f32::from_ne_bytes(bytes.try_into().unwrap())),
+                8 => f64::from_ne_bytes(bytes.try_into().unwrap()),
+                _ => return Ok(render_bytes(bytes)),
+            };
+            // NaN and infinity cannot be represented in JSON.
+            Number::from_f64(value)
+                .map(Value::Number)
+                .unwrap_or_else(|| Value::String(value.to_string()))
+        }
+        BtfKind::Ptr => {
+            let size = ty.source.ptr_size()?.get();
+            let raw = read_bits(slice(data, 0..size)?, 0, size * 8)?;
+            render_int(raw, size * 8, false)
+        }
+        BtfKind::Enum => {
+            let enum_ = types::Enum::try_from(ty).unwrap();
+            let bits = enum_.size() * 8;
+            let raw = read_bits(slice(data, 0..enum_.size())?, 0, bits)?;
+            // Enumerators are stored as 32 bit values; compare based on
+            // the lower 32 bits to be agnostic to the enum's signedness.
+            enum_
+                .iter()
+                .find(|member| member.value as u32 == raw as u32)
+                .and_then(|member| member.name)
+                .map(|name| Value::String(name.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| render_int(raw, bits, false))
+        }
+        BtfKind::Enum64 => {
+            let enum_ = types::Enum64::try_from(ty).unwrap();
+            let bits = enum_.size() * 8;
+            let raw = read_bits(slice(data, 0..enum_.size())?, 0, bits)?;
+            enum_
+                .iter()
+                .find(|member| u128::from(member.value) == raw)
+                .and_then(|member| member.name)
+                .map(|name| Value::String(name.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| render_int(raw, bits, false))
+        }
+        BtfKind::Array => {
+            let array = types::Array::try_from(ty).unwrap();
+            let elem = array.contained_type();
+            let elem_size = type_size(&elem)?;
+            let bytes = slice(data, 0..elem_size * array.capacity())?;
+
+            if is_char(&elem) {
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                Value::String(String::from_utf8_lossy(&bytes[..end]).into_owned())
+            } else if elem_size == 0 {
+                Value::Array(Vec::new())
+            } else {
+                bytes
+                    .chunks_exact(elem_size)
+                    .map(|chunk| render_type(elem, chunk))
+                    .collect::<Result<Vec<_>>>()
+                    .map(Value::Array)?
+            }
+        }
+        BtfKind::Struct | BtfKind::Union => render_composite(ty, data)?,
+        BtfKind::Var => {
+            let var = types::Var::try_from(ty).unwrap();
+            render_type(var.referenced_type(), data)?
+        }
+        BtfKind::DataSec => {
+            let datasec = types::DataSec::try_from(ty).unwrap();
+            let mut object = Map::new();
+            for info in datasec.iter() {
+                let start = info.offset as usize;
+                let bytes = slice(data, start..start + info.size)?;
+                let var = ty
+                    .source
+                    .type_by_id::<BtfType<'_>>(info.ty)
+                    .ok_or_else(|| {
+                        Error::with_invalid_data(format!("BTF type {} not found", info.ty))
+                    })?;
+                let name = var
+                    .name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let _prev = object.insert(name, render_type(var, bytes)?);
+            }
+            Value::Object(object)
+        }
+        BtfKind::Void
+        | BtfKind::Fwd
+        | BtfKind::Func
+        | BtfKind::FuncProto
+        | BtfKind::DeclTag
+        | BtfKind::Volatile
+        | BtfKind::Const
+        | BtfKind::Restrict
+        | BtfKind::Typedef
+        | BtfKind::TypeTag => render_bytes(data),
+    };
+    Ok(value)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that integers are rendered with the correct signedness.
+    #[test]
+    fn int_rendering() {
+        assert_eq!(render_int(0xff, 8, false), Value::from(255));
+        assert_eq!(render_int(0xff, 8, true), Value::from(-1));
+        assert_eq!(render_int(0x7f, 8, true), Value::from(127));
+        assert_eq!(
+            render_int(u128::MAX, 128, false),
+            Value::String(u128::MAX.to_string())
+        );
+        assert_eq!(render_bytes(&[0xde, 0xad, 0x01]), Value::from("dead01"));
+    }
+
+    /// Check that we can render a kernel type using its BTF.
+    #[test]
+    fn vmlinux_type_rendering() {
+        let btf = Btf::from_vmlinux().unwrap();
+        let ty = btf.type_by_name::<types::Struct<'_>>("list_head").unwrap();
+        let data = [0x11; 16];
+        let value = render(&btf, ty.type_id(), &data).unwrap();
+        let object = value.as_object().unwrap();
+        assert_eq!(object.len(), 2);
+        assert_eq!(object["next"], Value::from(0x1111_1111_1111_1111_u64));
+        assert_eq!(object["prev"], Value::from(0x1111_1111_1111_1111_u64));
+    }
+}
@@ -12,12 +12,14 @@
 //! [`Btf::type_by_kind`]). If you want to get a type independently of the kind, just make sure `K`
 //! binds to [`BtfType`].
 
+pub mod fmt;
+#[cfg(feature = "serde")]
+pub(crate) mod json;
 pub mod types;
 
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::ffi::OsStr;
-use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -141,7 +143,7 @@ impl From<TypeId> for u32 {
 }
 
 impl Display for TypeId {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(f, "{}", self.0)
     }
 }
@@ -211,7 +213,13 @@ impl Btf<'static> {
             )
         })?;
 
-        let ptr = unsafe { libbpf_sys::btf__load_from_kernel_by_id(info.btf_id) };
+        Self::from_btf_id(info.btf_id)
+    }
+
+    /// Load the btf information with the given BTF object id from the
+    /// kernel.
+    pub fn from_btf_id(btf_id: u32) -> Result<Self> {
+        let ptr = unsafe { libbpf_sys::btf__load_from_kernel_by_id(btf_id) };
         let ptr = validate_bpf_ret(ptr).context("failed to load BTF from kernel")?;
 
         Ok(Self {
@@ -468,7 +476,7 @@ pub struct BtfType<'btf> {
 }
 
 impl Debug for BtfType<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         f.debug_struct("BtfType")
             .field("type_id", &self.type_id)
             .field("name", &self.name())
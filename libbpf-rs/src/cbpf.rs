@@ -0,0 +1,462 @@
+//! Construction and attachment of classic BPF (cBPF) socket filters.
+//!
+//! Classic BPF is the original, much more limited, instruction set used by
+//! `SO_ATTACH_FILTER` socket filters and tools like `tcpdump`. It does not
+//! require loading a program into the kernel via the `bpf` system call and
+//! is available to unprivileged users, which makes it a good fit for simple
+//! packet-capture style filtering.
+//!
+//! ```no_run
+//! # fn example(socket: std::os::fd::BorrowedFd<'_>) -> libbpf_rs::Result<()> {
+//! use libbpf_rs::cbpf::FilterBuilder;
+//! use libbpf_rs::cbpf::LoadSize;
+//!
+//! // Accept IPv4 (ethertype 0x0800) frames on a packet socket, drop
+//! // everything else.
+//! let filter = FilterBuilder::new()
+//!     .load_abs(LoadSize::Half, 12)
+//!     .jump_eq(0x0800, 0, 1)
+//!     .ret(u32::MAX)
+//!     .ret(0)
+//!     .build()?;
+//! let () = filter.attach(socket)?;
+//! let () = libbpf_rs::cbpf::lock_filter(socket)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::os::fd::BorrowedFd;
+
+use crate::util;
+use crate::Error;
+use crate::Result;
+
+
+/// The maximum number of instructions a classic BPF filter may consist of.
+pub const BPF_MAXINSNS: usize = 4096;
+
+// Instruction classes.
+/// Load into the accumulator.
+pub const BPF_LD: u16 = 0x00;
+/// Load into the index register.
+pub const BPF_LDX: u16 = 0x01;
+/// Store the accumulator into scratch memory.
+pub const BPF_ST: u16 = 0x02;
+/// Store the index register into scratch memory.
+pub const BPF_STX: u16 = 0x03;
+/// Arithmetic and logic operation on the accumulator.
+pub const BPF_ALU: u16 = 0x04;
+/// Jump.
+pub const BPF_JMP: u16 = 0x05;
+/// Return from the filter.
+pub const BPF_RET: u16 = 0x06;
+/// Miscellaneous register transfer.
+pub const BPF_MISC: u16 = 0x07;
+
+// Load sizes.
+/// Load a 32 bit word.
+pub const BPF_W: u16 = 0x00;
+/// Load a 16 bit half word.
+pub const BPF_H: u16 = 0x08;
+/// Load a byte.
+pub const BPF_B: u16 = 0x10;
+
+// Load modes.
+/// Load the immediate `k`.
+pub const BPF_IMM: u16 = 0x00;
+/// Load from the packet at absolute offset `k`.
+pub const BPF_ABS: u16 = 0x20;
+/// Load from the packet at offset `X + k`.
+pub const BPF_IND: u16 = 0x40;
+/// Load from scratch memory slot `k`.
+pub const BPF_MEM: u16 = 0x60;
+/// Load the packet length.
+pub const BPF_LEN: u16 = 0x80;
+/// Load `4 * (packet[k] & 0xf)`, e.g., an IPv4 header length.
+pub const BPF_MSH: u16 = 0xa0;
+
+// ALU operations.
+/// Addition.
+pub const BPF_ADD: u16 = 0x00;
+/// Subtraction.
+pub const BPF_SUB: u16 = 0x10;
+/// Multiplication.
+pub const BPF_MUL: u16 = 0x20;
+/// Division.
+pub const BPF_DIV: u16 = 0x30;
+/// Bitwise or.
+pub const BPF_OR: u16 = 0x40;
+/// Bitwise and.
+pub const BPF_AND: u16 = 0x50;
+/// Left shift.
+pub const BPF_LSH: u16 = 0x60;
+/// Right shift.
+pub const BPF_RSH: u16 = 0x70;
+/// Negation.
+pub const BPF_NEG: u16 = 0x80;
+/// Modulo.
+pub const BPF_MOD: u16 = 0x90;
+/// Bitwise exclusive or.
+pub const BPF_XOR: u16 = 0xa0;
+
+// Jump operations.
+/// Unconditional jump by `k`.
+pub const BPF_JA: u16 = 0x00;
+/// Jump if equal.
+pub const BPF_JEQ: u16 = 0x10;
+/// Jump if greater than.
+pub const BPF_JGT: u16 = 0x20;
+/// Jump if greater than or equal.
+pub const BPF_JGE: u16 = 0x30;
+/// Jump if any of the bits are set.
+pub const BPF_JSET: u16 = 0x40;
+
+// Operand sources.
+/// Use the constant `k` as operand.
+pub const BPF_K: u16 = 0x00;
+/// Use the index register as operand.
+pub const BPF_X: u16 = 0x08;
+/// Use the accumulator as return value.
+pub const BPF_A: u16 = 0x10;
+
+// Miscellaneous operations.
+/// Copy the accumulator into the index register.
+pub const BPF_TAX: u16 = 0x00;
+/// Copy the index register into the accumulator.
+pub const BPF_TXA: u16 = 0x80;
+
+
+/// A single classic BPF instruction, laid out like the kernel's
+/// `struct sock_filter`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Instruction {
+    /// The opcode, a combination of the `BPF_*` constants of this module.
+    pub code: u16,
+    /// The relative jump offset if the condition is true.
+    pub jt: u8,
+    /// The relative jump offset if the condition is false.
+    pub jf: u8,
+    /// The generic operand, e.g., an offset or an immediate.
+    pub k: u32,
+}
+
+impl Instruction {
+    /// Create a non-jump instruction, equivalent to the `BPF_STMT` macro.
+    pub const fn stmt(code: u16, k: u32) -> Self {
+        Self {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    /// Create a jump instruction, equivalent to the `BPF_JUMP` macro.
+    pub const fn jump(code: u16, k: u32, jt: u8, jf: u8) -> Self {
+        Self { code, jt, jf, k }
+    }
+
+    /// The class of the instruction, e.g., [`BPF_JMP`].
+    #[inline]
+    pub const fn class(&self) -> u16 {
+        self.code & 0x07
+    }
+}
+
+
+/// The size of a packet load.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LoadSize {
+    /// Load a single byte.
+    Byte,
+    /// Load a 16 bit half word, in network byte order.
+    Half,
+    /// Load a 32 bit word, in network byte order.
+    Word,
+}
+
+impl LoadSize {
+    const fn to_code(self) -> u16 {
+        match self {
+            Self::Byte => BPF_B,
+            Self::Half => BPF_H,
+            Self::Word => BPF_W,
+        }
+    }
+}
+
+
+/// A builder for classic BPF [`Filter`]s.
+///
+/// Jump offsets are relative to the instruction following the jump, as is
+/// the case for the underlying instructions.
+#[derive(Clone, Debug, Default)]
+pub struct FilterBuilder {
+    insns: Vec<Instruction>,
+}
+
+impl FilterBuilder {
+    /// Create a new, empty `FilterBuilder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an arbitrary instruction.
+    pub fn insn(&mut self, insn: Instruction) -> &mut Self {
+        self.insns.push(insn);
+        self
+    }
+
+    /// Append a non-jump instruction. See [`Instruction::stmt`].
+    pub fn stmt(&mut self, code: u16, k: u32) -> &mut Self {
+        self.insn(Instruction::stmt(code, k))
+    }
+
+    /// Append a jump instruction. See [`Instruction::jump`].
+    pub fn jump(&mut self, code: u16, k: u32, jt: u8, jf: u8) -> &mut Self {
+        self.insn(Instruction::jump(code, k, jt, jf))
+    }
+
+    /// Load `size` bytes at absolute packet offset `offset` into the
+    /// accumulator.
+    pub fn load_abs(&mut self, size: LoadSize, offset: u32) -> &mut Self {
+        self.stmt(BPF_LD | size.to_code() | BPF_ABS, offset)
+    }
+
+    /// Load `size` bytes at packet offset `X + offset` into the
+    /// accumulator.
+    pub fn load_ind(&mut self, size: LoadSize, offset: u32) -> &mut Self {
+        self.stmt(BPF_LD | size.to_code() | BPF_IND, offset)
+    }
+
+    /// Load the packet length into the accumulator.
+    pub fn load_len(&mut self) -> &mut Self {
+        self.stmt(BPF_LD | BPF_W | BPF_LEN, 0)
+    }
+
+    /// Load the IPv4 header length of the header starting at packet offset
+    /// `offset` into the index register.
+    pub fn load_ip_hdr_len(&mut self, offset: u32) -> &mut Self {
+        self.stmt(BPF_LDX | BPF_B | BPF_MSH, offset)
+    }
+
+    /// Bitwise and the accumulator with `k`.
+    pub fn and(&mut self, k: u32) -> &mut Self {
+        self.stmt(BPF_ALU | BPF_AND | BPF_K, k)
+    }
+
+    /// Jump by `jt` instructions if the accumulator equals `k` and by `jf`
+    /// otherwise.
+    pub fn jump_eq(&mut self, k: u32, jt: u8, jf: u8) -> &mut Self {
+        self.jump(BPF_JMP | BPF_JEQ | BPF_K, k, jt, jf)
+    }
+
+    /// Jump by `jt` instructions if the accumulator is greater than `k` and
+    /// by `jf` otherwise.
+    pub fn jump_gt(&mut self, k: u32, jt: u8, jf: u8) -> &mut Self {
+        self.jump(BPF_JMP | BPF_JGT | BPF_K, k, jt, jf)
+    }
+
+    /// Jump by `jt` instructions if the accumulator is greater than or
+    /// equal to `k` and by `jf` otherwise.
+    pub fn jump_ge(&mut self, k: u32, jt: u8, jf: u8) -> &mut Self {
+        self.jump(BPF_JMP | BPF_JGE | BPF_K, k, jt, jf)
+    }
+
+    /// Jump by `jt` instructions if any of the bits in `k` are set in the
+    /// accumulator and by `jf` otherwise.
+    pub fn jump_set(&mut self, k: u32, jt: u8, jf: u8) -> &mut Self {
+        self.jump(BPF_JMP | BPF_JSET | BPF_K, k, jt, jf)
+    }
+
+    /// Return from the filter, accepting up to `len` bytes of the packet.
+    /// A `len` of zero drops the packet.
+    pub fn ret(&mut self, len: u32) -> &mut Self {
+        self.stmt(BPF_RET | BPF_K, len)
+    }
+
+    /// Return from the filter, accepting as many bytes of the packet as the
+    /// accumulator holds.
+    pub fn ret_a(&mut self) -> &mut Self {
+        self.stmt(BPF_RET | BPF_A, 0)
+    }
+
+    /// Validate the instructions and create the [`Filter`].
+    pub fn build(&self) -> Result<Filter> {
+        Filter::new(self.insns.clone())
+    }
+}
+
+
+/// A validated classic BPF socket filter program.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Filter {
+    insns: Vec<Instruction>,
+}
+
+impl Filter {
+    /// Create a `Filter` from raw instructions.
+    ///
+    /// The instructions are checked for the structural constraints the
+    /// kernel enforces: the program has to be non-empty, consist of at most
+    /// [`BPF_MAXINSNS`] instructions, end in a return, and all jumps have
+    /// to stay within the program.
+    pub fn new(insns: Vec<Instruction>) -> Result<Self> {
+        if insns.is_empty() || insns.len() > BPF_MAXINSNS {
+            return Err(Error::with_invalid_data(format!(
+                "cBPF filter has invalid length {}; must be between 1 and {BPF_MAXINSNS}",
+                insns.len()
+            )));
+        }
+
+        let last = insns.len() - 1;
+        for (idx, insn) in insns.iter().enumerate() {
+            if insn.class() != BPF_JMP {
+                continue
+            }
+
+            let next = idx + 1;
+            let targets = if insn.code & 0xf0 == BPF_JA {
+                let target = next.saturating_add(insn.k as usize);
+                [target, target]
+            } else {
+                [next + usize::from(insn.jt), next + usize::from(insn.jf)]
+            };
+            if targets.iter().any(|target| *target > last) {
+                return Err(Error::with_invalid_data(format!(
+                    "cBPF instruction {idx} jumps out of bounds"
+                )));
+            }
+        }
+
+        if insns[last].class() != BPF_RET {
+            return Err(Error::with_invalid_data(
+                "cBPF filter does not end with a return instruction",
+            ));
+        }
+        Ok(Self { insns })
+    }
+
+    /// Retrieve the filter's instructions.
+    #[inline]
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.insns
+    }
+
+    /// Attach the filter to `socket` via `SO_ATTACH_FILTER`, replacing any
+    /// previously attached filter.
+    pub fn attach(&self, socket: BorrowedFd<'_>) -> Result<()> {
+        let prog = libc::sock_fprog {
+            // The length was checked on construction.
+            len: self.insns.len() as _,
+            // The kernel does not modify the instructions.
+            filter: self.insns.as_ptr() as *mut libc::sock_filter,
+        };
+        util::setsockopt(socket, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, &prog)
+    }
+}
+
+
+/// Detach the classic (or extended) BPF filter attached to `socket`.
+pub fn detach_filter(socket: BorrowedFd<'_>) -> Result<()> {
+    let unused: libc::c_int = 0;
+    util::setsockopt(socket, libc::SOL_SOCKET, libc::SO_DETACH_FILTER, &unused)
+}
+
+/// Lock the filter attached to `socket` via `SO_LOCK_FILTER`, preventing
+/// it from being replaced or detached, e.g., before handing the socket to
+/// less privileged code.
+pub fn lock_filter(socket: BorrowedFd<'_>) -> Result<()> {
+    let enable: libc::c_int = 1;
+    util::setsockopt(socket, libc::SOL_SOCKET, libc::SO_LOCK_FILTER, &enable)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+    use std::mem::size_of;
+    use std::net::UdpSocket;
+    use std::os::fd::AsFd as _;
+    use std::time::Duration;
+
+    use crate::ErrorKind;
+
+
+    /// Check that `Instruction` has the same layout as the kernel's
+    /// `sock_filter`.
+    #[test]
+    fn instruction_layout() {
+        assert_eq!(size_of::<Instruction>(), size_of::<libc::sock_filter>());
+        assert_eq!(size_of::<Instruction>(), 8);
+    }
+
+    /// Check that we reject structurally invalid filters.
+    #[test]
+    fn filter_validation() {
+        let err = FilterBuilder::new().build().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = FilterBuilder::new().load_len().build().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = FilterBuilder::new()
+            .load_len()
+            .jump_eq(1, 0, 2)
+            .ret(0)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let filter = FilterBuilder::new()
+            .load_len()
+            .jump_eq(1, 0, 1)
+            .ret(0)
+            .ret_a()
+            .build()
+            .unwrap();
+        assert_eq!(filter.instructions().len(), 4);
+        assert_eq!(
+            filter.instructions()[1],
+            Instruction::jump(BPF_JMP | BPF_JEQ | BPF_K, 1, 0, 1)
+        );
+    }
+
+    /// Check that an attached filter takes effect and that it can no longer
+    /// be detached once locked.
+    #[test]
+    fn filter_attach() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let () = socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let drop_all = FilterBuilder::new().ret(0).build().unwrap();
+        let () = drop_all.attach(socket.as_fd()).unwrap();
+        let _cnt = sender
+            .send_to(b"dropped", socket.local_addr().unwrap())
+            .unwrap();
+        let mut buf = [0; 16];
+        let err = socket.recv(&mut buf).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+
+        let () = detach_filter(socket.as_fd()).unwrap();
+        let _cnt = sender
+            .send_to(b"accepted", socket.local_addr().unwrap())
+            .unwrap();
+        let cnt = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..cnt], b"accepted");
+
+        let () = drop_all.attach(socket.as_fd()).unwrap();
+        let () = lock_filter(socket.as_fd()).unwrap();
+        let err = detach_filter(socket.as_fd()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    }
+}
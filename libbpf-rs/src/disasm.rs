@@ -0,0 +1,569 @@
+//! Disassembly of eBPF instructions into human readable text.
+//!
+//! The output follows the conventions of `bpftool prog dump xlated`, with
+//! one instruction per line, prefixed by its index and opcode:
+//!
+//! ```text
+//!    0: (b7) r0 = 0
+//!    1: (18) r1 = map[id:12] (counts)
+//!    3: (85) call bpf_map_lookup_elem#1
+//!    4: (95) exit
+//! ```
+//!
+//! Both the instructions of programs as passed to the kernel (e.g.,
+//! [`Program::insns`][crate::Program::insns] or those assembled with
+//! [`InsnBuilder`][crate::insn::InsnBuilder]) as well as the translated
+//! instructions the kernel reports for loaded programs (see
+//! [`ProgramInfo::xlated_prog_insns`][crate::query::ProgramInfo::xlated_prog_insns])
+//! can be disassembled.
+//!
+//! ```no_run
+//! # fn example(prog: &libbpf_rs::query::ProgramInfo) -> libbpf_rs::Result<()> {
+//! use libbpf_rs::disasm::Disassembler;
+//!
+//! let mut disasm = Disassembler::new();
+//! disasm.resolve_maps();
+//! disasm.resolve_kernel_symbols()?;
+//! print!("{}", disasm.disassemble_xlated(&prog.xlated_prog_insns)?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::mem::size_of;
+
+use libbpf_sys::bpf_insn;
+
+use crate::btf::types;
+use crate::btf::BtfType;
+use crate::insn::BPF_ALU;
+use crate::insn::BPF_ALU64;
+use crate::insn::BPF_CALL;
+use crate::insn::BPF_EXIT;
+use crate::insn::BPF_IMM;
+use crate::insn::BPF_JA;
+use crate::insn::BPF_JMP;
+use crate::insn::BPF_JMP32;
+use crate::insn::BPF_LD;
+use crate::insn::BPF_LDX;
+use crate::insn::BPF_MEM;
+use crate::insn::BPF_PSEUDO_MAP_FD;
+use crate::insn::BPF_ST;
+use crate::insn::BPF_STX;
+use crate::insn::BPF_X;
+use crate::query::MapInfoIter;
+use crate::symbolize::Ksyms;
+use crate::Btf;
+use crate::Error;
+use crate::Result;
+
+
+// Load and store modes not used by the instruction builder.
+const BPF_ABS: u8 = 0x20;
+const BPF_IND: u8 = 0x40;
+const BPF_MEMSX: u8 = 0x80;
+const BPF_ATOMIC: u8 = 0xc0;
+
+// Operations not used by the instruction builder.
+const BPF_NEG: u8 = 0x80;
+const BPF_END: u8 = 0xd0;
+const BPF_MOV: u8 = 0xb0;
+const BPF_DIV: u8 = 0x30;
+const BPF_MOD: u8 = 0x90;
+
+// Atomic operations, as encoded in the immediate.
+const BPF_FETCH: i32 = 0x01;
+const BPF_XCHG: i32 = 0xe0 | BPF_FETCH;
+const BPF_CMPXCHG: i32 = 0xf0 | BPF_FETCH;
+
+// `src_reg` values of 64 bit immediate loads and calls.
+const BPF_PSEUDO_MAP_VALUE: u8 = 2;
+const BPF_PSEUDO_BTF_ID: u8 = 3;
+const BPF_PSEUDO_FUNC: u8 = 4;
+const BPF_PSEUDO_MAP_IDX: u8 = 5;
+const BPF_PSEUDO_MAP_IDX_VALUE: u8 = 6;
+const BPF_PSEUDO_CALL: u8 = 1;
+const BPF_PSEUDO_KFUNC_CALL: u8 = 2;
+
+
+/// Retrieve the C type name for the size encoded in a load or store.
+fn size_name(code: u8, signed: bool) -> &'static str {
+    match (code & 0x18, signed) {
+        (0x00, false) => "u32",
+        (0x08, false) => "u16",
+        (0x10, false) => "u8",
+        (0x18, false) => "u64",
+        (0x00, true) => "s32",
+        (0x08, true) => "s16",
+        (0x10, true) => "s8",
+        (_, true) => "s64",
+        // All possible sizes are covered above.
+        (_, false) => unreachable!(),
+    }
+}
+
+/// Retrieve the operator of an arithmetic operation.
+fn alu_op(op: u8, off: i16) -> Option<&'static str> {
+    let op = match (op, off) {
+        (0x00, _) => "+=",
+        (0x10, _) => "-=",
+        (0x20, _) => "*=",
+        (BPF_DIV, 0) => "/=",
+        (BPF_DIV, _) => "s/=",
+        (0x40, _) => "|=",
+        (0x50, _) => "&=",
+        (0x60, _) => "<<=",
+        (0x70, _) => ">>=",
+        (BPF_MOD, 0) => "%=",
+        (BPF_MOD, _) => "s%=",
+        (0xa0, _) => "^=",
+        (BPF_MOV, _) => "=",
+        (0xc0, _) => "s>>=",
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Retrieve the operator of a conditional jump.
+fn jmp_op(op: u8) -> Option<&'static str> {
+    let op = match op {
+        0x10 => "==",
+        0x20 => ">",
+        0x30 => ">=",
+        0x40 => "&",
+        0x50 => "!=",
+        0x60 => "s>",
+        0x70 => "s>=",
+        0xa0 => "<",
+        0xb0 => "<=",
+        0xc0 => "s<",
+        0xd0 => "s<=",
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Retrieve the name of an atomic operation.
+fn atomic_op(op: i32) -> Option<(&'static str, &'static str)> {
+    let op = match op & !BPF_FETCH {
+        0x00 => ("+=", "add"),
+        0x40 => ("|=", "or"),
+        0x50 => ("&=", "and"),
+        0xa0 => ("^=", "xor"),
+        _ => return None,
+    };
+    Some(op)
+}
+
+
+/// A disassembler for eBPF instructions.
+///
+/// By default, helper and kfunc calls are resolved based on the kernel's
+/// BTF, if available. The names of maps referenced by translated
+/// instructions and of the kernel functions they call can be resolved
+/// additionally, see [`Disassembler::resolve_maps`] and
+/// [`Disassembler::resolve_kernel_symbols`].
+///
+/// A disassembler created via [`Default`] does not resolve any names.
+#[derive(Debug, Default)]
+pub struct Disassembler {
+    /// The kernel's BTF, for resolving kfuncs and BTF IDs.
+    vmlinux: Option<Btf<'static>>,
+    /// The names of helpers, keyed by their ID.
+    helpers: HashMap<i32, String>,
+    /// The names of maps, keyed by their ID.
+    maps: HashMap<u32, String>,
+    /// Kernel symbols along with the address of `__bpf_call_base`, for
+    /// resolving calls in translated instructions.
+    ksyms: Option<(Ksyms, u64)>,
+}
+
+impl Disassembler {
+    /// Create a new disassembler, resolving helpers and kfuncs based on
+    /// the kernel's BTF, if available.
+    pub fn new() -> Self {
+        let vmlinux = Btf::from_vmlinux().ok();
+        let helpers = vmlinux
+            .as_ref()
+            .and_then(|btf| btf.type_by_name::<types::Enum<'_>>("bpf_func_id"))
+            .map(|func_ids| {
+                func_ids
+                    .iter()
+                    .filter_map(|member| {
+                        let name = member.name?.to_str()?.strip_prefix("BPF_FUNC_")?;
+                        Some((member.value, format!("bpf_{name}")))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            vmlinux,
+            helpers,
+            ..Default::default()
+        }
+    }
+
+    /// Resolve the names of the maps referenced by translated
+    /// instructions, by looking up all currently loaded maps.
+    pub fn resolve_maps(&mut self) -> &mut Self {
+        self.maps = MapInfoIter::default()
+            .map(|map| (map.id, map.name.to_string_lossy().into_owned()))
+            .collect();
+        self
+    }
+
+    /// Resolve the kernel functions called by translated instructions
+    /// (which no longer refer to helpers by ID) via `/proc/kallsyms`.
+    ///
+    /// This requires kernel addresses to be visible to the process (see
+    /// `kernel.kptr_restrict`).
+    pub fn resolve_kernel_symbols(&mut self) -> Result<&mut Self> {
+        let ksyms = Ksyms::load()?;
+        let base = ksyms
+            .addr_of("__bpf_call_base")
+            .ok_or_else(|| Error::with_invalid_data("kernel symbol `__bpf_call_base` not found"))?;
+        self.ksyms = Some((ksyms, base));
+        Ok(self)
+    }
+
+    /// Disassemble instructions as passed to the kernel, which refer to
+    /// maps by file descriptor and to helpers by ID.
+    pub fn disassemble(&self, insns: &[bpf_insn]) -> String {
+        self.render(insns, false)
+    }
+
+    /// Disassemble translated instructions, as reported by the kernel
+    /// for loaded programs, which refer to maps by ID.
+    pub fn disassemble_xlated(&self, xlated: &[u8]) -> Result<String> {
+        let size = size_of::<bpf_insn>();
+        if xlated.len() % size != 0 {
+            return Err(Error::with_invalid_data(format!(
+                "instruction buffer of {} bytes is not a multiple of the instruction size",
+                xlated.len()
+            )))
+        }
+
+        let insns = xlated
+            .chunks_exact(size)
+            // SAFETY: Each chunk is exactly one instruction in size and
+            //         any bit pattern is a valid `bpf_insn`.
+            .map(|chunk| unsafe { chunk.as_ptr().cast::<bpf_insn>().read_unaligned() })
+            .collect::<Vec<_>>();
+        Ok(self.render(&insns, true))
+    }
+
+    fn render(&self, insns: &[bpf_insn], xlated: bool) -> String {
+        let mut out = String::new();
+        let mut idx = 0;
+        while let Some(insn) = insns.get(idx) {
+            let next = insns.get(idx + 1);
+            let text = self.insn(insn, next, xlated);
+            // Writing to a `String` never fails.
+            let _result = writeln!(out, "{idx:>4}: ({:02x}) {text}", insn.code);
+            idx += if insn.code == BPF_LD | 0x18 | BPF_IMM {
+                2
+            } else {
+                1
+            };
+        }
+        out
+    }
+
+    fn map_ref(&self, kind: &str, id: i32, xlated: bool) -> String {
+        match self.maps.get(&(id as u32)) {
+            Some(name) if xlated && kind == "id" => format!("map[id:{id}] ({name})"),
+            _ => format!("map[{kind}:{id}]"),
+        }
+    }
+
+    fn call(&self, insn: &bpf_insn, xlated: bool) -> String {
+        match insn.src_reg() {
+            BPF_PSEUDO_CALL => format!("call pc{:+}", insn.imm),
+            BPF_PSEUDO_KFUNC_CALL => {
+                let name = self
+                    .vmlinux
+                    .as_ref()
+                    .filter(|_| insn.off == 0)
+                    .and_then(|btf| btf.type_by_id::<BtfType<'_>>((insn.imm as u32).into()))
+                    .and_then(|ty| ty.name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "kernel-function".to_string());
+                format!("call {name}#{}", insn.imm)
+            }
+            _ => {
+                let name = if xlated {
+                    self.ksyms.as_ref().and_then(|(ksyms, base)| {
+                        let addr = base.wrapping_add(insn.imm as i64 as u64);
+                        ksyms
+                            .find(addr)
+                            .filter(|sym| sym.addr == addr)
+                            .map(|sym| sym.name.clone())
+                    })
+                } else {
+                    None
+                };
+                let name = name
+                    .or_else(|| self.helpers.get(&insn.imm).cloned())
+                    .unwrap_or_else(|| "unknown".to_string());
+                format!("call {name}#{}", insn.imm)
+            }
+        }
+    }
+
+    fn ld_imm64(&self, insn: &bpf_insn, next: Option<&bpf_insn>, xlated: bool) -> String {
+        let Some(next) = next else {
+            return "(incomplete ld_imm64)".to_string()
+        };
+        let dst = insn.dst_reg();
+        let imm = u64::from(insn.imm as u32) | (u64::from(next.imm as u32) << 32);
+        let map_kind = if xlated { "id" } else { "fd" };
+
+        let value = match insn.src_reg() {
+            0 => format!("{imm:#x} ll"),
+            BPF_PSEUDO_MAP_FD => self.map_ref(map_kind, insn.imm, xlated),
+            BPF_PSEUDO_MAP_VALUE => {
+                format!(
+                    "{}[0]+{}",
+                    self.map_ref(map_kind, insn.imm, xlated),
+                    next.imm
+                )
+            }
+            BPF_PSEUDO_MAP_IDX => self.map_ref("idx", insn.imm, xlated),
+            BPF_PSEUDO_MAP_IDX_VALUE => {
+                format!("{}[0]+{}", self.map_ref("idx", insn.imm, xlated), next.imm)
+            }
+            BPF_PSEUDO_BTF_ID => {
+                let name = self
+                    .vmlinux
+                    .as_ref()
+                    .and_then(|btf| btf.type_by_id::<BtfType<'_>>((insn.imm as u32).into()))
+                    .and_then(|ty| ty.name())
+                    .map(|name| format!(" ({})", name.to_string_lossy()))
+                    .unwrap_or_default();
+                format!("btf_id:{}{name}", insn.imm)
+            }
+            BPF_PSEUDO_FUNC => format!("subprog[pc{:+}]", insn.imm),
+            src => format!("{imm:#x} ll (unknown pseudo source {src})"),
+        };
+        format!("r{dst} = {value}")
+    }
+
+    fn alu(insn: &bpf_insn) -> String {
+        let reg = if insn.code & 0x07 == BPF_ALU64 {
+            'r'
+        } else {
+            'w'
+        };
+        let dst = insn.dst_reg();
+        let src = if insn.code & BPF_X != 0 {
+            format!("{reg}{}", insn.src_reg())
+        } else {
+            insn.imm.to_string()
+        };
+
+        match insn.code & 0xf0 {
+            BPF_NEG => format!("{reg}{dst} = -{reg}{dst}"),
+            BPF_END => {
+                let kind = if insn.code & 0x07 == BPF_ALU64 {
+                    "bswap"
+                } else if insn.code & BPF_X != 0 {
+                    "be"
+                } else {
+                    "le"
+                };
+                format!("{reg}{dst} = {kind}{} {reg}{dst}", insn.imm)
+            }
+            BPF_MOV if insn.code & BPF_X != 0 && insn.off != 0 => {
+                format!("{reg}{dst} = (s{}){reg}{}", insn.off, insn.src_reg())
+            }
+            op => match alu_op(op, insn.off) {
+                Some(op) => format!("{reg}{dst} {op} {src}"),
+                None => format!("(invalid ALU operation {op:#04x})"),
+            },
+        }
+    }
+
+    fn jmp(&self, insn: &bpf_insn, xlated: bool) -> String {
+        let jmp32 = insn.code & 0x07 == BPF_JMP32;
+        match insn.code & 0xf0 {
+            BPF_JA if jmp32 => format!("gotol pc{:+}", insn.imm),
+            BPF_JA => format!("goto pc{:+}", insn.off),
+            BPF_CALL => self.call(insn, xlated),
+            BPF_EXIT => "exit".to_string(),
+            op => {
+                let reg = if jmp32 { 'w' } else { 'r' };
+                let src = if insn.code & BPF_X != 0 {
+                    format!("{reg}{}", insn.src_reg())
+                } else {
+                    format!("{:#x}", insn.imm)
+                };
+                match jmp_op(op) {
+                    Some(op) => format!(
+                        "if {reg}{} {op} {src} goto pc{:+}",
+                        insn.dst_reg(),
+                        insn.off
+                    ),
+                    None => format!("(invalid jump operation {op:#04x})"),
+                }
+            }
+        }
+    }
+
+    fn atomic(insn: &bpf_insn) -> String {
+        let dw = insn.code & 0x18 == 0x18;
+        let (reg, prefix) = if dw {
+            ('r', "atomic64")
+        } else {
+            ('w', "atomic")
+        };
+        let mem = format!(
+            "({} *)(r{} {:+})",
+            size_name(insn.code, false),
+            insn.dst_reg(),
+            insn.off
+        );
+        let src = insn.src_reg();
+
+        match insn.imm {
+            BPF_XCHG => format!("{reg}{src} = {prefix}_xchg({mem}, {reg}{src})"),
+            BPF_CMPXCHG => format!("{reg}0 = {prefix}_cmpxchg({mem}, {reg}0, {reg}{src})"),
+            op => match atomic_op(op) {
+                Some((_, name)) if op & BPF_FETCH != 0 => {
+                    format!("{reg}{src} = {prefix}_fetch_{name}({mem}, {reg}{src})")
+                }
+                Some((sym, _)) => format!("lock *{mem} {sym} {reg}{src}"),
+                None => format!("(invalid atomic operation {op:#04x})"),
+            },
+        }
+    }
+
+    fn insn(&self, insn: &bpf_insn, next: Option<&bpf_insn>, xlated: bool) -> String {
+        let code = insn.code;
+        let mode = code & 0xe0;
+        match code & 0x07 {
+            BPF_ALU | BPF_ALU64 => Self::alu(insn),
+            BPF_JMP | BPF_JMP32 => self.jmp(insn, xlated),
+            BPF_LDX if matches!(mode, BPF_MEM | BPF_MEMSX) => format!(
+                "r{} = *({} *)(r{} {:+})",
+                insn.dst_reg(),
+                size_name(code, mode == BPF_MEMSX),
+                insn.src_reg(),
+                insn.off
+            ),
+            BPF_ST if mode == BPF_MEM => format!(
+                "*({} *)(r{} {:+}) = {}",
+                size_name(code, false),
+                insn.dst_reg(),
+                insn.off,
+                insn.imm
+            ),
+            BPF_STX if mode == BPF_MEM => format!(
+                "*({} *)(r{} {:+}) = r{}",
+                size_name(code, false),
+                insn.dst_reg(),
+                insn.off,
+                insn.src_reg()
+            ),
+            BPF_STX if mode == BPF_ATOMIC => Self::atomic(insn),
+            BPF_LD if code == BPF_LD | 0x18 | BPF_IMM => self.ld_imm64(insn, next, xlated),
+            BPF_LD if mode == BPF_ABS => {
+                format!("r0 = *({} *)skb[{}]", size_name(code, false), insn.imm)
+            }
+            BPF_LD if mode == BPF_IND => format!(
+                "r0 = *({} *)skb[r{} + {}]",
+                size_name(code, false),
+                insn.src_reg(),
+                insn.imm
+            ),
+            _ => format!("(invalid opcode {code:#04x})"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::slice;
+
+    use crate::insn::AluOp;
+    use crate::insn::InsnBuilder;
+    use crate::insn::JmpOp;
+    use crate::insn::Reg;
+    use crate::insn::Size;
+
+
+    /// Check that instructions are disassembled as expected.
+    #[test]
+    fn disassembly() {
+        let insns = InsnBuilder::new()
+            .mov64_imm(Reg::R0, 0)
+            .alu32_reg(AluOp::Add, Reg::R1, Reg::R2)
+            .ld_imm64(Reg::R2, 0x1_0000_0002)
+            .ldx_mem(Size::W, Reg::R3, Reg::R1, -4)
+            .stx_mem(Size::DW, Reg::R10, Reg::R3, -8)
+            .st_mem(Size::B, Reg::R10, -1, 7)
+            .jmp_imm(JmpOp::Jne, Reg::R3, 7, 1)
+            .ja(-2)
+            .exit()
+            .build();
+
+        let disasm = Disassembler::default();
+        let text = disasm.disassemble(&insns);
+        let expected = "   0: (b7) r0 = 0
+   1: (0c) w1 += w2
+   2: (18) r2 = 0x100000002 ll
+   4: (61) r3 = *(u32 *)(r1 -4)
+   5: (7b) *(u64 *)(r10 -8) = r3
+   6: (72) *(u8 *)(r10 -1) = 7
+   7: (55) if r3 != 0x7 goto pc+1
+   8: (05) goto pc-2
+   9: (95) exit
+";
+        assert_eq!(text, expected);
+    }
+
+    /// Check that map references and calls are rendered as expected.
+    #[test]
+    fn map_and_call_disassembly() {
+        let mut ld_map = InsnBuilder::new().ld_imm64(Reg::R1, 12).build();
+        let () = ld_map[0].set_src_reg(BPF_PSEUDO_MAP_FD);
+        let mut disasm = Disassembler::default();
+        let _prev = disasm.helpers.insert(1, "bpf_map_lookup_elem".to_string());
+        let _prev = disasm.maps.insert(12, "counts".to_string());
+
+        let insn = disasm.ld_imm64(&ld_map[0], Some(&ld_map[1]), true);
+        assert_eq!(insn, "r1 = map[id:12] (counts)");
+        let insn = disasm.ld_imm64(&ld_map[0], Some(&ld_map[1]), false);
+        assert_eq!(insn, "r1 = map[fd:12]");
+
+        let insns = InsnBuilder::new()
+            .call(libbpf_sys::BPF_FUNC_map_lookup_elem)
+            .build();
+        assert_eq!(
+            disasm.disassemble(&insns),
+            "   0: (85) call bpf_map_lookup_elem#1\n"
+        );
+    }
+
+    /// Check that translated instructions are decoded from raw bytes.
+    #[test]
+    fn xlated_decoding() {
+        let insns = InsnBuilder::new().mov64_imm(Reg::R0, 1).exit().build();
+        let bytes = insns
+            .iter()
+            .flat_map(|insn| {
+                // SAFETY: `bpf_insn` is a plain old data type of eight bytes.
+                unsafe { slice::from_raw_parts((insn as *const bpf_insn).cast::<u8>(), 8) }.to_vec()
+            })
+            .collect::<Vec<_>>();
+
+        let disasm = Disassembler::default();
+        let text = disasm.disassemble_xlated(&bytes).unwrap();
+        assert_eq!(text, "   0: (b7) r0 = 1\n   1: (95) exit\n");
+        assert!(disasm.disassemble_xlated(&bytes[..5]).is_err());
+    }
+}
@@ -2,6 +2,8 @@ use std::borrow::Borrow;
 use std::borrow::Cow;
 use std::error;
 use std::error::Error as _;
+use std::ffi::CStr;
+use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -92,10 +94,47 @@ impl IntoCowStr for String {
     }
 }
 
+/// Details about an entity that could not be found by name.
+struct NameNotFound {
+    /// The kind of entity looked up, e.g., "map".
+    what: &'static str,
+    /// The name that was looked up.
+    name: Box<str>,
+    /// The names of all entities of the same kind that are available.
+    available: Vec<String>,
+}
+
+impl Display for NameNotFound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let Self {
+            what,
+            name,
+            available,
+        } = self;
+
+        let () = write!(f, "{what} `{name}` not found")?;
+        if available.is_empty() {
+            write!(f, "; no {what}s available")
+        } else {
+            let () = write!(f, "; available {what}s: ")?;
+            for (i, available) in available.iter().enumerate() {
+                let sep = if i == 0 { "" } else { ", " };
+                let () = write!(f, "{sep}`{available}`")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+
 // TODO: We may want to support optionally storing a backtrace in
 //       terminal variants.
 enum ErrorImpl {
     Io(io::Error),
+    /// A `libbpf` specific error code, as per `enum libbpf_errno`.
+    Libbpf(i32),
+    /// A lookup of an entity by name failed.
+    NameNotFound(Box<NameNotFound>),
     // Unfortunately, if we just had a single `Context` variant that
     // contains a `Cow`, this inner `Cow` would cause an overall enum
     // size increase by a machine word, because currently `rustc`
@@ -130,12 +169,40 @@ impl ErrorImpl {
                 io::ErrorKind::OutOfMemory => ErrorKind::OutOfMemory,
                 _ => ErrorKind::Other,
             },
+            Self::Libbpf(code) => match *code as libbpf_sys::libbpf_errno {
+                libbpf_sys::LIBBPF_ERRNO__VERIFY => ErrorKind::Verifier,
+                libbpf_sys::LIBBPF_ERRNO__LIBELF
+                | libbpf_sys::LIBBPF_ERRNO__FORMAT
+                | libbpf_sys::LIBBPF_ERRNO__ENDIAN
+                | libbpf_sys::LIBBPF_ERRNO__RELOC
+                | libbpf_sys::LIBBPF_ERRNO__INVSEQ
+                | libbpf_sys::LIBBPF_ERRNO__NLPARSE => ErrorKind::InvalidData,
+                libbpf_sys::LIBBPF_ERRNO__PROG2BIG | libbpf_sys::LIBBPF_ERRNO__WRNGPID => {
+                    ErrorKind::InvalidInput
+                }
+                libbpf_sys::LIBBPF_ERRNO__KVERSION
+                | libbpf_sys::LIBBPF_ERRNO__KVER
+                | libbpf_sys::LIBBPF_ERRNO__PROGTYPE => ErrorKind::Unsupported,
+                _ => ErrorKind::Other,
+            },
+            Self::NameNotFound(..) => ErrorKind::NotFound,
             Self::ContextOwned { source, .. } | Self::ContextStatic { source, .. } => {
                 source.deref().kind()
             }
         }
     }
 
+    fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Self::Io(error) => error.raw_os_error(),
+            Self::Libbpf(code) => Some(*code),
+            Self::NameNotFound(..) => None,
+            Self::ContextOwned { source, .. } | Self::ContextStatic { source, .. } => {
+                source.deref().raw_os_error()
+            }
+        }
+    }
+
     #[cfg(test)]
     fn is_owned(&self) -> Option<bool> {
         match self {
@@ -158,6 +225,14 @@ impl Debug for ErrorImpl {
                     dbg = f.debug_tuple(stringify!(Io));
                     dbg.field(io)
                 }
+                Self::Libbpf(code) => {
+                    dbg = f.debug_tuple(stringify!(Libbpf));
+                    dbg.field(code)
+                }
+                Self::NameNotFound(not_found) => {
+                    dbg = f.debug_tuple(stringify!(NameNotFound));
+                    dbg.field(&not_found.to_string())
+                }
                 Self::ContextOwned { context, .. } => {
                     dbg = f.debug_tuple(stringify!(ContextOwned));
                     dbg.field(context)
@@ -171,6 +246,8 @@ impl Debug for ErrorImpl {
         } else {
             let () = match self {
                 Self::Io(error) => write!(f, "Error: {error}")?,
+                Self::Libbpf(..) => write!(f, "Error: {self}")?,
+                Self::NameNotFound(not_found) => write!(f, "Error: {not_found}")?,
                 Self::ContextOwned { context, .. } => write!(f, "Error: {context}")?,
                 Self::ContextStatic { context, .. } => write!(f, "Error: {context}")?,
             };
@@ -193,6 +270,17 @@ impl Display for ErrorImpl {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         let () = match self {
             Self::Io(error) => Display::fmt(error, f)?,
+            Self::Libbpf(code) => {
+                let mut buf = [0; 128];
+                // SAFETY: `buf` is valid for writes of `buf.len()` bytes and
+                //         `libbpf_strerror` always NUL terminates its output.
+                let _ret =
+                    unsafe { libbpf_sys::libbpf_strerror(*code, buf.as_mut_ptr(), buf.len() as _) };
+                // SAFETY: `buf` is guaranteed to be NUL terminated.
+                let msg = unsafe { CStr::from_ptr(buf.as_ptr()) };
+                write!(f, "{} (libbpf error {code})", msg.to_string_lossy())?
+            }
+            Self::NameNotFound(not_found) => Display::fmt(not_found, f)?,
             Self::ContextOwned { context, .. } => Display::fmt(context, f)?,
             Self::ContextStatic { context, .. } => Display::fmt(context, f)?,
         };
@@ -212,6 +300,7 @@ impl error::Error for ErrorImpl {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Self::Io(error) => error.source(),
+            Self::Libbpf(..) | Self::NameNotFound(..) => None,
             Self::ContextOwned { source, .. } | Self::ContextStatic { source, .. } => Some(source),
         }
     }
@@ -255,6 +344,8 @@ pub enum ErrorKind {
     /// An operation could not be completed, because it failed
     /// to allocate enough memory.
     OutOfMemory,
+    /// A BPF program was rejected by the kernel verifier.
+    Verifier,
     /// A custom error that does not fall under any other I/O error
     /// kind.
     Other,
@@ -327,6 +418,9 @@ pub struct Error {
 impl Error {
     /// Create an [`Error`] from an OS error code (typically `errno`).
     ///
+    /// `libbpf` specific error codes (as per `enum libbpf_errno`) are
+    /// recognized as well and described using `libbpf_strerror`.
+    ///
     /// # Notes
     /// An OS error code should always be positive.
     #[inline]
@@ -335,7 +429,15 @@ impl Error {
             code > 0,
             "OS error code should be positive integer; got: {code}"
         );
-        Self::from(io::Error::from_raw_os_error(code))
+
+        let libbpf_errnos = libbpf_sys::__LIBBPF_ERRNO__START..libbpf_sys::__LIBBPF_ERRNO__END;
+        if libbpf_errnos.contains(&(code as libbpf_sys::libbpf_errno)) {
+            Self {
+                error: Box::new(ErrorImpl::Libbpf(code)),
+            }
+        } else {
+            Self::from(io::Error::from_raw_os_error(code))
+        }
     }
 
     #[inline]
@@ -346,6 +448,24 @@ impl Error {
         Self::from(io::Error::new(kind, error.to_string()))
     }
 
+    /// Create an [`Error`] reporting that no `what` (e.g., "map") named
+    /// `name` exists, listing the names of those that are `available`.
+    #[inline]
+    pub(crate) fn with_name_not_found(
+        what: &'static str,
+        name: &OsStr,
+        available: Vec<String>,
+    ) -> Self {
+        let not_found = NameNotFound {
+            what,
+            name: name.to_string_lossy().into(),
+            available,
+        };
+        Self {
+            error: Box::new(ErrorImpl::NameNotFound(Box::new(not_found))),
+        }
+    }
+
     #[inline]
     pub(crate) fn with_invalid_data<E>(error: E) -> Self
     where
@@ -361,6 +481,16 @@ impl Error {
         self.error.kind()
     }
 
+    /// Retrieve the OS error code (typically `errno`) at the root of
+    /// this error's chain, if any.
+    ///
+    /// The returned code is positive and may be a `libbpf` specific
+    /// error code.
+    #[inline]
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.error.raw_os_error()
+    }
+
     /// Layer the provided context on top of this `Error`, creating a
     /// new one in the process.
     fn layer_context(self, context: Cow<'static, Str>) -> Self {
@@ -610,4 +740,69 @@ Caused by:
         assert_eq!(format!("{err:?}"), expected);
         assert_ne!(format!("{err:#?}"), "");
     }
+
+    /// Check that OS error codes are preserved throughout the error
+    /// chain.
+    #[test]
+    fn error_raw_os_error() {
+        let err = Error::from_raw_os_error(libc::ENOENT);
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+
+        let err = err.context("some context");
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+
+        let err = Error::with_invalid_data("some invalid data");
+        assert_eq!(err.raw_os_error(), None);
+    }
+
+    /// Check that `libbpf` specific error codes are classified and
+    /// described correctly.
+    #[test]
+    fn libbpf_error() {
+        let code = libbpf_sys::LIBBPF_ERRNO__VERIFY as i32;
+        let err = Error::from_raw_os_error(code);
+        assert_eq!(err.kind(), ErrorKind::Verifier);
+        assert_eq!(err.raw_os_error(), Some(code));
+        assert_eq!(
+            format!("{err}"),
+            "Kernel verifier blocks program loading (libbpf error 4007)"
+        );
+        assert_eq!(format!("{err:#?}"), "Libbpf(\n    4007,\n)");
+
+        let err = err.context("failed to load object");
+        assert_eq!(err.kind(), ErrorKind::Verifier);
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to load object: Kernel verifier blocks program loading (libbpf error 4007)"
+        );
+
+        let err = Error::from_raw_os_error(libbpf_sys::LIBBPF_ERRNO__PROGTYPE as i32);
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    /// Check that errors about entities not found by name are formatted
+    /// as expected.
+    #[test]
+    fn name_not_found_error() {
+        let available = vec!["events".to_string(), "counts".to_string()];
+        let err = Error::with_name_not_found("map", OsStr::new("event"), available);
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.raw_os_error(), None);
+        assert_eq!(
+            format!("{err}"),
+            "map `event` not found; available maps: `events`, `counts`"
+        );
+        assert_eq!(
+            format!("{err:?}"),
+            "Error: map `event` not found; available maps: `events`, `counts`"
+        );
+
+        let err = Error::with_name_not_found("program", OsStr::new("handler"), Vec::new());
+        assert_eq!(
+            format!("{err}"),
+            "program `handler` not found; no programs available"
+        );
+    }
 }
@@ -0,0 +1,299 @@
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::os::unix::prelude::BorrowedFd;
+use std::time::Duration;
+
+use crate::Error;
+use crate::MapCore;
+use crate::MapType;
+use crate::OpenMapMut;
+use crate::PerfBuffer;
+use crate::PerfBufferBuilder;
+use crate::Result;
+use crate::RingBuffer;
+use crate::RingBufferBuilder;
+
+// Callbacks are required to be `Send`, because they are owned by the
+// `EventReader`, which itself may be sent to a different thread.
+type SampleCb<'cb> = Box<dyn FnMut(&[u8]) + Send + 'cb>;
+type LostCb<'cb> = Box<dyn FnMut(u64) + Send + 'cb>;
+
+
+/// The kind of map an [`EventReader`] transfers events through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventBackend {
+    /// A `BPF_MAP_TYPE_RINGBUF` map, available as of Linux 5.8.
+    RingBuf,
+    /// A `BPF_MAP_TYPE_PERF_EVENT_ARRAY` map.
+    PerfBuf,
+}
+
+impl EventBackend {
+    /// Determine the preferred backend supported by the running kernel.
+    pub fn detect() -> Result<Self> {
+        if MapType::RingBuf.is_supported()? {
+            Ok(Self::RingBuf)
+        } else {
+            Ok(Self::PerfBuf)
+        }
+    }
+
+    fn map_type(&self) -> MapType {
+        match self {
+            Self::RingBuf => MapType::RingBuf,
+            Self::PerfBuf => MapType::PerfEventArray,
+        }
+    }
+}
+
+
+/// Builds [`EventReader`] instances.
+pub struct EventReaderBuilder<'a, 'cb, M>
+where
+    M: MapCore,
+{
+    map: &'a M,
+    pages: usize,
+    sample_cb: Option<SampleCb<'cb>>,
+    lost_cb: Option<LostCb<'cb>>,
+}
+
+impl<'a, M> EventReaderBuilder<'a, '_, M>
+where
+    M: MapCore,
+{
+    /// Create a new `EventReaderBuilder` for the provided map, which has
+    /// to be a `ringbuf` or `perf buffer`, typically as set up by
+    /// [`EventReader::prepare`].
+    pub fn new(map: &'a M) -> Self {
+        Self {
+            map,
+            pages: 64,
+            sample_cb: None,
+            lost_cb: None,
+        }
+    }
+}
+
+impl<'a, 'cb: 'a, M> EventReaderBuilder<'a, 'cb, M>
+where
+    M: MapCore,
+{
+    /// Callback to run when an event is received.
+    ///
+    /// The callback provides a raw byte slice. Note that samples read from
+    /// a `perf buffer` may include trailing padding.
+    pub fn sample_cb<F>(self, cb: F) -> Self
+    where
+        F: FnMut(&[u8]) + Send + 'cb,
+    {
+        Self {
+            sample_cb: Some(Box::new(cb)),
+            ..self
+        }
+    }
+
+    /// Callback to run when events got lost, receiving the number of lost
+    /// events.
+    ///
+    /// Only a `perf buffer` reports lost events. Producers writing to a
+    /// `ringbuf` learn about a full buffer when trying to submit instead.
+    pub fn lost_cb<F>(self, cb: F) -> Self
+    where
+        F: FnMut(u64) + Send + 'cb,
+    {
+        Self {
+            lost_cb: Some(Box::new(cb)),
+            ..self
+        }
+    }
+
+    /// The number of pages to size each per-CPU buffer of a `perf buffer`
+    /// with.
+    ///
+    /// The size of a `ringbuf` is defined by its map instead.
+    pub fn pages(self, pages: usize) -> Self {
+        Self { pages, ..self }
+    }
+
+    /// Build the `EventReader` object as configured.
+    pub fn build(self) -> Result<EventReader<'cb>> {
+        let Self {
+            map,
+            pages,
+            sample_cb,
+            lost_cb,
+        } = self;
+        let mut sample_cb = sample_cb.unwrap_or_else(|| Box::new(|_data| ()));
+
+        let inner = match map.map_type() {
+            MapType::RingBuf => {
+                let mut builder = RingBufferBuilder::new();
+                let _builder = builder.add(map, move |data| {
+                    let () = sample_cb(data);
+                    0
+                })?;
+                Inner::RingBuf(builder.build()?)
+            }
+            MapType::PerfEventArray => {
+                let builder = PerfBufferBuilder::new(map)
+                    .pages(pages)
+                    .sample_cb(move |_cpu: i32, data: &[u8]| sample_cb(data));
+                let builder = match lost_cb {
+                    Some(mut lost_cb) => {
+                        builder.lost_cb(move |_cpu: i32, count: u64| lost_cb(count))
+                    }
+                    None => builder,
+                };
+                Inner::PerfBuf(builder.build()?)
+            }
+            ty => {
+                return Err(Error::with_invalid_data(format!(
+                    "map `{}` is of type {ty:?}; expected RingBuf or PerfEventArray",
+                    map.name().to_string_lossy(),
+                )))
+            }
+        };
+        Ok(EventReader { inner })
+    }
+}
+
+impl<M> Debug for EventReaderBuilder<'_, '_, M>
+where
+    M: MapCore,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let Self {
+            map,
+            pages,
+            sample_cb,
+            lost_cb,
+        } = self;
+        f.debug_struct("EventReaderBuilder")
+            .field("map", map)
+            .field("pages", pages)
+            .field("sample_cb", &sample_cb.as_ref().map(|cb| &cb as *const _))
+            .field("lost_cb", &lost_cb.as_ref().map(|cb| &cb as *const _))
+            .finish()
+    }
+}
+
+
+#[derive(Debug)]
+enum Inner<'cb> {
+    RingBuf(RingBuffer<'cb>),
+    PerfBuf(PerfBuffer<'cb>),
+}
+
+
+/// A reader of events submitted by BPF programs, transparently using a
+/// `ringbuf` where supported and falling back to a `perf buffer` on older
+/// kernels.
+///
+/// The map transferring events is declared as `BPF_MAP_TYPE_RINGBUF` in
+/// the BPF object and converted by [`EventReader::prepare`] before the
+/// object is loaded, if necessary. BPF programs have to use the helper
+/// matching the map's type, which can be decided at load time using
+/// CO-RE:
+/// ```c
+/// if (bpf_core_enum_value_exists(enum bpf_func_id, BPF_FUNC_ringbuf_output))
+///     bpf_ringbuf_output(&events, &event, sizeof(event), 0);
+/// else
+///     bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
+/// ```
+///
+/// An `EventReader` is [`Send`], but not [`Sync`]:
+/// ```compile_fail
+/// fn test<T: Sync>() {}
+/// test::<libbpf_rs::EventReader<'_>>();
+/// ```
+#[derive(Debug)]
+pub struct EventReader<'cb> {
+    inner: Inner<'cb>,
+}
+
+impl EventReader<'_> {
+    /// Configure `map` to use the best backend supported by the running
+    /// kernel, returning the backend chosen.
+    pub fn prepare(map: &mut OpenMapMut<'_>) -> Result<EventBackend> {
+        let backend = EventBackend::detect()?;
+        let () = Self::prepare_for(map, backend)?;
+        Ok(backend)
+    }
+
+    /// Configure `map` to use the provided backend.
+    pub fn prepare_for(map: &mut OpenMapMut<'_>, backend: EventBackend) -> Result<()> {
+        if map.map_type() == backend.map_type() {
+            return Ok(())
+        }
+
+        match backend {
+            EventBackend::RingBuf => Err(Error::with_invalid_data(format!(
+                "map `{}` is of type {:?}; only RingBuf maps can be converted",
+                map.name().to_string_lossy(),
+                map.map_type(),
+            ))),
+            EventBackend::PerfBuf => {
+                let () = map.set_type(MapType::PerfEventArray)?;
+                let () = map.set_key_size(4)?;
+                let () = map.set_value_size(4)?;
+                // libbpf sizes the map to the number of possible CPUs.
+                let () = map.set_max_entries(0)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Retrieve the backend events are read from.
+    pub fn backend(&self) -> EventBackend {
+        match self.inner {
+            Inner::RingBuf(..) => EventBackend::RingBuf,
+            Inner::PerfBuf(..) => EventBackend::PerfBuf,
+        }
+    }
+
+    /// Poll for events, calling the registered callback for each one,
+    /// until either no more events are available or `timeout` is reached.
+    pub fn poll(&self, timeout: Duration) -> Result<()> {
+        match &self.inner {
+            Inner::RingBuf(ringbuf) => ringbuf.poll(timeout),
+            Inner::PerfBuf(perfbuf) => perfbuf.poll(timeout),
+        }
+    }
+
+    /// Greedily consume all available events, calling the registered
+    /// callback for each one.
+    pub fn consume(&self) -> Result<()> {
+        match &self.inner {
+            Inner::RingBuf(ringbuf) => ringbuf.consume(),
+            Inner::PerfBuf(perfbuf) => perfbuf.consume(),
+        }
+    }
+
+    /// Get an fd that can be used to sleep until events are available.
+    pub fn epoll_fd(&self) -> BorrowedFd<'_> {
+        match &self.inner {
+            Inner::RingBuf(ringbuf) => ringbuf.epoll_fd(),
+            Inner::PerfBuf(perfbuf) => perfbuf.epoll_fd(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Check that `EventReader` is `Send`.
+    #[test]
+    fn event_reader_is_send() {
+        fn test<T>()
+        where
+            T: Send,
+        {
+        }
+
+        test::<EventReader<'_>>();
+    }
+}
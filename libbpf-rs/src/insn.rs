@@ -0,0 +1,358 @@
+//! Programmatic construction of eBPF instructions.
+//!
+//! This module allows for assembling small BPF programs, e.g., a trivial
+//! socket filter or `SK_REUSEPORT` selector, without having to compile C
+//! code. The resulting instructions can be loaded with
+//! [`Program::load_from_insns`][crate::Program::load_from_insns].
+//!
+//! ```no_run
+//! # fn example() -> libbpf_rs::Result<()> {
+//! use libbpf_rs::insn::InsnBuilder;
+//! use libbpf_rs::insn::Reg;
+//! use libbpf_rs::Program;
+//! use libbpf_rs::ProgramType;
+//!
+//! // A socket filter dropping all packets.
+//! let insns = InsnBuilder::new().mov64_imm(Reg::R0, 0).exit().build();
+//! let fd = Program::load_from_insns(&insns, ProgramType::SocketFilter, "GPL")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::os::fd::AsRawFd as _;
+use std::os::fd::BorrowedFd;
+
+use libbpf_sys::bpf_func_id;
+use libbpf_sys::bpf_insn;
+
+
+// Instruction classes.
+pub(crate) const BPF_LD: u8 = 0x00;
+pub(crate) const BPF_LDX: u8 = 0x01;
+pub(crate) const BPF_ST: u8 = 0x02;
+pub(crate) const BPF_STX: u8 = 0x03;
+pub(crate) const BPF_ALU: u8 = 0x04;
+pub(crate) const BPF_JMP: u8 = 0x05;
+pub(crate) const BPF_JMP32: u8 = 0x06;
+pub(crate) const BPF_ALU64: u8 = 0x07;
+
+// Operand sources.
+pub(crate) const BPF_K: u8 = 0x00;
+pub(crate) const BPF_X: u8 = 0x08;
+
+// Load and store modes.
+pub(crate) const BPF_IMM: u8 = 0x00;
+pub(crate) const BPF_MEM: u8 = 0x60;
+
+// Special operations.
+pub(crate) const BPF_JA: u8 = 0x00;
+pub(crate) const BPF_CALL: u8 = 0x80;
+pub(crate) const BPF_EXIT: u8 = 0x90;
+
+/// The `src_reg` value marking a 64 bit immediate load as referring to a
+/// map file descriptor.
+pub(crate) const BPF_PSEUDO_MAP_FD: u8 = 1;
+
+
+/// An eBPF register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Reg {
+    /// Return value of helper calls and the program itself.
+    R0 = 0,
+    /// First argument; holds the program's context on entry.
+    R1,
+    /// Second argument.
+    R2,
+    /// Third argument.
+    R3,
+    /// Fourth argument.
+    R4,
+    /// Fifth argument.
+    R5,
+    /// Callee saved register.
+    R6,
+    /// Callee saved register.
+    R7,
+    /// Callee saved register.
+    R8,
+    /// Callee saved register.
+    R9,
+    /// Read-only frame pointer.
+    R10,
+}
+
+
+/// An arithmetic or logic operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum AluOp {
+    /// `dst += src`
+    Add = 0x00,
+    /// `dst -= src`
+    Sub = 0x10,
+    /// `dst *= src`
+    Mul = 0x20,
+    /// `dst /= src`
+    Div = 0x30,
+    /// `dst |= src`
+    Or = 0x40,
+    /// `dst &= src`
+    And = 0x50,
+    /// `dst <<= src`
+    Lsh = 0x60,
+    /// `dst >>= src` (logical)
+    Rsh = 0x70,
+    /// `dst = -dst`
+    Neg = 0x80,
+    /// `dst %= src`
+    Mod = 0x90,
+    /// `dst ^= src`
+    Xor = 0xa0,
+    /// `dst = src`
+    Mov = 0xb0,
+    /// `dst >>= src` (arithmetic)
+    Arsh = 0xc0,
+}
+
+
+/// A conditional jump operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum JmpOp {
+    /// Jump if `dst == src`.
+    Jeq = 0x10,
+    /// Jump if `dst > src` (unsigned).
+    Jgt = 0x20,
+    /// Jump if `dst >= src` (unsigned).
+    Jge = 0x30,
+    /// Jump if `dst & src != 0`.
+    Jset = 0x40,
+    /// Jump if `dst != src`.
+    Jne = 0x50,
+    /// Jump if `dst > src` (signed).
+    Jsgt = 0x60,
+    /// Jump if `dst >= src` (signed).
+    Jsge = 0x70,
+    /// Jump if `dst < src` (unsigned).
+    Jlt = 0xa0,
+    /// Jump if `dst <= src` (unsigned).
+    Jle = 0xb0,
+    /// Jump if `dst < src` (signed).
+    Jslt = 0xc0,
+    /// Jump if `dst <= src` (signed).
+    Jsle = 0xd0,
+}
+
+
+/// The size of a memory access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Size {
+    /// 32 bit word.
+    W = 0x00,
+    /// 16 bit half word.
+    H = 0x08,
+    /// Byte.
+    B = 0x10,
+    /// 64 bit double word.
+    DW = 0x18,
+}
+
+
+/// Create a raw instruction from its individual parts.
+pub fn raw(code: u8, dst: Reg, src: Reg, off: i16, imm: i32) -> bpf_insn {
+    raw_regs(code, dst as u8, src as u8, off, imm)
+}
+
+fn raw_regs(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> bpf_insn {
+    let mut insn = bpf_insn {
+        code,
+        off,
+        imm,
+        ..Default::default()
+    };
+    let () = insn.set_dst_reg(dst);
+    let () = insn.set_src_reg(src);
+    insn
+}
+
+
+/// A builder for sequences of eBPF instructions.
+///
+/// Jump offsets are counted in instructions, relative to the instruction
+/// following the jump. Note that 64 bit immediate loads occupy two
+/// instructions.
+#[derive(Clone, Debug, Default)]
+pub struct InsnBuilder {
+    insns: Vec<bpf_insn>,
+}
+
+impl InsnBuilder {
+    /// Create a new, empty `InsnBuilder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a raw instruction.
+    pub fn insn(&mut self, insn: bpf_insn) -> &mut Self {
+        self.insns.push(insn);
+        self
+    }
+
+    /// `dst = imm`
+    pub fn mov64_imm(&mut self, dst: Reg, imm: i32) -> &mut Self {
+        self.alu64_imm(AluOp::Mov, dst, imm)
+    }
+
+    /// `dst = src`
+    pub fn mov64_reg(&mut self, dst: Reg, src: Reg) -> &mut Self {
+        self.alu64_reg(AluOp::Mov, dst, src)
+    }
+
+    /// `dst = dst <op> imm`, operating on 64 bit values.
+    pub fn alu64_imm(&mut self, op: AluOp, dst: Reg, imm: i32) -> &mut Self {
+        self.insn(raw(BPF_ALU64 | op as u8 | BPF_K, dst, Reg::R0, 0, imm))
+    }
+
+    /// `dst = dst <op> src`, operating on 64 bit values.
+    pub fn alu64_reg(&mut self, op: AluOp, dst: Reg, src: Reg) -> &mut Self {
+        self.insn(raw(BPF_ALU64 | op as u8 | BPF_X, dst, src, 0, 0))
+    }
+
+    /// `dst = dst <op> imm`, operating on the lower 32 bits and zeroing
+    /// the upper ones.
+    pub fn alu32_imm(&mut self, op: AluOp, dst: Reg, imm: i32) -> &mut Self {
+        self.insn(raw(BPF_ALU | op as u8 | BPF_K, dst, Reg::R0, 0, imm))
+    }
+
+    /// `dst = dst <op> src`, operating on the lower 32 bits and zeroing
+    /// the upper ones.
+    pub fn alu32_reg(&mut self, op: AluOp, dst: Reg, src: Reg) -> &mut Self {
+        self.insn(raw(BPF_ALU | op as u8 | BPF_X, dst, src, 0, 0))
+    }
+
+    /// `dst = imm`, for a full 64 bit immediate.
+    pub fn ld_imm64(&mut self, dst: Reg, imm: u64) -> &mut Self {
+        self.ld_imm64_raw(dst, 0, imm)
+    }
+
+    /// `dst = map`, loading a reference to the map referred to by `fd`, as
+    /// required for passing it to helpers such as `bpf_map_lookup_elem`.
+    ///
+    /// The file descriptor has to stay open until the program is loaded.
+    pub fn ld_map_fd(&mut self, dst: Reg, fd: BorrowedFd<'_>) -> &mut Self {
+        self.ld_imm64_raw(dst, BPF_PSEUDO_MAP_FD, fd.as_raw_fd() as u64)
+    }
+
+    fn ld_imm64_raw(&mut self, dst: Reg, src: u8, imm: u64) -> &mut Self {
+        let code = BPF_LD | Size::DW as u8 | BPF_IMM;
+        let lo = raw_regs(code, dst as u8, src, 0, imm as i32);
+        let hi = raw_regs(0, 0, 0, 0, (imm >> 32) as i32);
+        self.insn(lo).insn(hi)
+    }
+
+    /// `dst = *(size *)(src + off)`
+    pub fn ldx_mem(&mut self, size: Size, dst: Reg, src: Reg, off: i16) -> &mut Self {
+        self.insn(raw(BPF_LDX | size as u8 | BPF_MEM, dst, src, off, 0))
+    }
+
+    /// `*(size *)(dst + off) = src`
+    pub fn stx_mem(&mut self, size: Size, dst: Reg, src: Reg, off: i16) -> &mut Self {
+        self.insn(raw(BPF_STX | size as u8 | BPF_MEM, dst, src, off, 0))
+    }
+
+    /// `*(size *)(dst + off) = imm`
+    pub fn st_mem(&mut self, size: Size, dst: Reg, off: i16, imm: i32) -> &mut Self {
+        self.insn(raw(BPF_ST | size as u8 | BPF_MEM, dst, Reg::R0, off, imm))
+    }
+
+    /// `if (dst <op> imm) goto pc + off`, comparing 64 bit values.
+    pub fn jmp_imm(&mut self, op: JmpOp, dst: Reg, imm: i32, off: i16) -> &mut Self {
+        self.insn(raw(BPF_JMP | op as u8 | BPF_K, dst, Reg::R0, off, imm))
+    }
+
+    /// `if (dst <op> src) goto pc + off`, comparing 64 bit values.
+    pub fn jmp_reg(&mut self, op: JmpOp, dst: Reg, src: Reg, off: i16) -> &mut Self {
+        self.insn(raw(BPF_JMP | op as u8 | BPF_X, dst, src, off, 0))
+    }
+
+    /// `if (dst <op> imm) goto pc + off`, comparing the lower 32 bits.
+    pub fn jmp32_imm(&mut self, op: JmpOp, dst: Reg, imm: i32, off: i16) -> &mut Self {
+        self.insn(raw(BPF_JMP32 | op as u8 | BPF_K, dst, Reg::R0, off, imm))
+    }
+
+    /// `if (dst <op> src) goto pc + off`, comparing the lower 32 bits.
+    pub fn jmp32_reg(&mut self, op: JmpOp, dst: Reg, src: Reg, off: i16) -> &mut Self {
+        self.insn(raw(BPF_JMP32 | op as u8 | BPF_X, dst, src, off, 0))
+    }
+
+    /// `goto pc + off`
+    pub fn ja(&mut self, off: i16) -> &mut Self {
+        self.insn(raw(BPF_JMP | BPF_JA, Reg::R0, Reg::R0, off, 0))
+    }
+
+    /// Call the BPF helper `helper`, e.g.,
+    /// [`BPF_FUNC_map_lookup_elem`][libbpf_sys::BPF_FUNC_map_lookup_elem].
+    ///
+    /// Arguments are passed in `R1` to `R5` and the result is returned in
+    /// `R0`.
+    pub fn call(&mut self, helper: bpf_func_id) -> &mut Self {
+        self.insn(raw(BPF_JMP | BPF_CALL, Reg::R0, Reg::R0, 0, helper as i32))
+    }
+
+    /// Exit the program, returning `R0`.
+    pub fn exit(&mut self) -> &mut Self {
+        self.insn(raw(BPF_JMP | BPF_EXIT, Reg::R0, Reg::R0, 0, 0))
+    }
+
+    /// Retrieve the instructions assembled so far.
+    #[inline]
+    pub fn insns(&self) -> &[bpf_insn] {
+        &self.insns
+    }
+
+    /// Retrieve the assembled instructions.
+    pub fn build(&self) -> Vec<bpf_insn> {
+        self.insns.clone()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that instructions are encoded as expected.
+    #[test]
+    fn insn_encoding() {
+        let insns = InsnBuilder::new()
+            .mov64_imm(Reg::R0, 1)
+            .ld_imm64(Reg::R2, 0x1_0000_0002)
+            .ldx_mem(Size::W, Reg::R3, Reg::R1, -4)
+            .jmp_imm(JmpOp::Jeq, Reg::R3, 7, 1)
+            .exit()
+            .build();
+        assert_eq!(insns.len(), 6);
+
+        assert_eq!(insns[0].code, 0xb7);
+        assert_eq!(insns[0].dst_reg(), 0);
+        assert_eq!(insns[0].imm, 1);
+
+        assert_eq!(insns[1].code, 0x18);
+        assert_eq!(insns[1].dst_reg(), 2);
+        assert_eq!(insns[1].imm, 2);
+        assert_eq!(insns[2].code, 0);
+        assert_eq!(insns[2].imm, 1);
+
+        assert_eq!(insns[3].code, 0x61);
+        assert_eq!(insns[3].dst_reg(), 3);
+        assert_eq!(insns[3].src_reg(), 1);
+        assert_eq!(insns[3].off, -4);
+
+        assert_eq!(insns[4].code, 0x15);
+        assert_eq!(insns[4].off, 1);
+        assert_eq!(insns[5].code, 0x95);
+    }
+}
@@ -1,6 +1,9 @@
 use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
 use std::os::fd::AsFd;
 use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
 use std::os::fd::FromRawFd;
 use std::os::fd::OwnedFd;
 
@@ -8,19 +11,133 @@ use crate::Error;
 use crate::Link;
 use crate::Result;
 
+
+/// The order in which a cgroup iterator visits cgroups.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CgroupIterOrder {
+    /// Visit the given cgroup only.
+    #[default]
+    SelfOnly,
+    /// Visit the given cgroup and its descendants, parents before their
+    /// children.
+    DescendantsPre,
+    /// Visit the given cgroup and its descendants, children before their
+    /// parents.
+    DescendantsPost,
+    /// Visit the given cgroup and its ancestors, up to the root.
+    Ancestors,
+}
+
+impl CgroupIterOrder {
+    fn to_raw(self) -> libbpf_sys::bpf_cgroup_iter_order {
+        match self {
+            Self::SelfOnly => libbpf_sys::BPF_CGROUP_ITER_SELF_ONLY,
+            Self::DescendantsPre => libbpf_sys::BPF_CGROUP_ITER_DESCENDANTS_PRE,
+            Self::DescendantsPost => libbpf_sys::BPF_CGROUP_ITER_DESCENDANTS_POST,
+            Self::Ancestors => libbpf_sys::BPF_CGROUP_ITER_ANCESTORS_UP,
+        }
+    }
+}
+
+
+/// The kernel objects a [BPF
+/// iterator](https://www.kernel.org/doc/html/latest/bpf/bpf_iterators.html)
+/// program iterates over, as provided to
+/// [`ProgramMut::attach_iter_target`][crate::ProgramMut::attach_iter_target].
+///
+/// Which targets are supported depends on the iterator type, e.g.,
+/// `SEC("iter/task")` programs support all task related targets, while
+/// `SEC("iter/bpf_map_elem")` ones require [`IterTarget::Map`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum IterTarget<'fd> {
+    /// Iterate over all objects of the iterator's type, e.g., all tasks or
+    /// all TCP sockets.
+    All,
+    /// Iterate over the elements of the map referred to by the file
+    /// descriptor.
+    Map(BorrowedFd<'fd>),
+    /// Iterate over the thread with the given ID only.
+    Thread(u32),
+    /// Iterate over the threads of the process with the given ID.
+    Process(u32),
+    /// Iterate over the threads of the process referred to by the pidfd.
+    PidFd(BorrowedFd<'fd>),
+    /// Iterate over cgroups, starting at the cgroup referred to by the
+    /// file descriptor, e.g., of an opened cgroup v2 directory.
+    CgroupFd {
+        /// The file descriptor of the cgroup to start at.
+        fd: BorrowedFd<'fd>,
+        /// The order in which to visit cgroups.
+        order: CgroupIterOrder,
+    },
+    /// Iterate over cgroups, starting at the cgroup with the given ID,
+    /// i.e., the inode number of its cgroup v2 directory.
+    CgroupId {
+        /// The ID of the cgroup to start at.
+        id: u64,
+        /// The order in which to visit cgroups.
+        order: CgroupIterOrder,
+    },
+}
+
+impl IterTarget<'_> {
+    /// Convert the target into the kernel's link info representation, if
+    /// it is parameterized.
+    pub(crate) fn link_info(&self) -> Option<libbpf_sys::bpf_iter_link_info> {
+        let mut info = libbpf_sys::bpf_iter_link_info::default();
+        match self {
+            Self::All => return None,
+            Self::Map(fd) => info.map.map_fd = fd.as_raw_fd() as _,
+            Self::Thread(tid) => info.task.tid = *tid,
+            Self::Process(pid) => info.task.pid = *pid,
+            Self::PidFd(fd) => info.task.pid_fd = fd.as_raw_fd() as _,
+            Self::CgroupFd { fd, order } => {
+                info.cgroup.order = order.to_raw();
+                info.cgroup.cgroup_fd = fd.as_raw_fd() as _;
+            }
+            Self::CgroupId { id, order } => {
+                info.cgroup.order = order.to_raw();
+                info.cgroup.cgroup_id = *id;
+            }
+        }
+        Some(info)
+    }
+}
+
+
+/// The unbuffered output of an iterator.
+#[derive(Debug)]
+struct IterFd(OwnedFd);
+
+impl io::Read for IterFd {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read =
+            unsafe { libc::read(self.0.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len()) };
+        if bytes_read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(bytes_read as usize)
+    }
+}
+
 /// Represents a bpf iterator for reading kernel data structures. This requires
 /// Linux 5.8.
 ///
-/// This implements [`std::io::Read`] for reading bytes from the iterator.
-/// Methods require working with raw bytes. You may find libraries such as
+/// This implements [`std::io::Read`] as well as [`std::io::BufRead`] for
+/// reading bytes from the iterator, e.g., line by line for iterators
+/// emitting text via `bpf_seq_printf`. Methods require working with raw
+/// bytes. You may find libraries such as
 /// [`plain`](https://crates.io/crates/plain) helpful.
 #[derive(Debug)]
 pub struct Iter {
-    fd: OwnedFd,
+    reader: BufReader<IterFd>,
 }
 
 impl Iter {
     /// Create a new `Iter` wrapping the provided `Link`.
+    ///
+    /// Each `Iter` created from a link runs the iterator anew.
     pub fn new(link: &Link) -> Result<Self> {
         let link_fd = link.as_fd().as_raw_fd();
         let fd = unsafe { libbpf_sys::bpf_iter_create(link_fd) };
@@ -28,18 +145,29 @@ impl Iter {
             return Err(Error::from(io::Error::last_os_error()));
         }
         Ok(Self {
-            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            reader: BufReader::new(IterFd(unsafe { OwnedFd::from_raw_fd(fd) })),
         })
     }
 }
 
 impl io::Read for Iter {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let bytes_read =
-            unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len()) };
-        if bytes_read < 0 {
-            return Err(io::Error::last_os_error());
-        }
-        Ok(bytes_read as usize)
+        self.reader.read(buf)
+    }
+}
+
+impl BufRead for Iter {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt)
+    }
+}
+
+impl AsFd for Iter {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.reader.get_ref().0.as_fd()
     }
 }
@@ -78,22 +78,47 @@
 )]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+pub mod arena;
 pub mod btf;
+pub mod cbpf;
+pub mod disasm;
 mod error;
+mod event_reader;
+pub mod insn;
 mod iter;
 mod link;
 mod linker;
+pub mod local_storage;
 mod map;
+mod memlock;
 mod object;
+pub mod offload;
+pub mod perf;
 mod perf_buffer;
 mod print;
+mod privilege;
+pub mod profiler;
 mod program;
+mod progress;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
 pub mod query;
+mod restricted_map;
+pub mod reuseport;
 mod ringbuf;
 mod skeleton;
+mod snapshot;
+pub mod stats;
+pub mod symbolize;
 mod tc;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod token;
+mod trace;
 mod user_ringbuf;
 mod util;
+mod validate;
+mod watch;
 mod xdp;
 
 pub use libbpf_sys;
@@ -105,11 +130,20 @@ pub use crate::error::Error;
 pub use crate::error::ErrorExt;
 pub use crate::error::ErrorKind;
 pub use crate::error::Result;
+pub use crate::event_reader::EventBackend;
+pub use crate::event_reader::EventReader;
+pub use crate::event_reader::EventReaderBuilder;
+pub use crate::iter::CgroupIterOrder;
 pub use crate::iter::Iter;
+pub use crate::iter::IterTarget;
 pub use crate::link::Link;
+pub use crate::link::LinkDropPolicy;
+pub use crate::link::Links;
 pub use crate::linker::Linker;
 pub use crate::map::Map;
 pub use crate::map::MapCore;
+pub use crate::map::MapDrainIter;
+pub use crate::map::MapEntryIter;
 pub use crate::map::MapFlags;
 pub use crate::map::MapHandle;
 pub use crate::map::MapImpl;
@@ -120,6 +154,11 @@ pub use crate::map::MapType;
 pub use crate::map::OpenMap;
 pub use crate::map::OpenMapImpl;
 pub use crate::map::OpenMapMut;
+pub use crate::map::PercpuCounter;
+pub use crate::memlock::bump_memlock_rlimit;
+pub use crate::memlock::memlock_accounting;
+pub use crate::memlock::set_memlock_rlimit;
+pub use crate::memlock::MemlockAccounting;
 pub use crate::object::AsRawLibbpf;
 pub use crate::object::MapIter;
 pub use crate::object::Object;
@@ -128,11 +167,20 @@ pub use crate::object::OpenObject;
 pub use crate::object::ProgIter;
 pub use crate::perf_buffer::PerfBuffer;
 pub use crate::perf_buffer::PerfBufferBuilder;
+pub use crate::print::capture_print;
 pub use crate::print::get_print;
+#[cfg(feature = "log")]
+pub use crate::print::print_to_log;
+#[cfg(feature = "tracing")]
+pub use crate::print::print_to_tracing;
 pub use crate::print::set_print;
 pub use crate::print::PrintCallback;
 pub use crate::print::PrintLevel;
+pub use crate::program::AttachOrder;
+pub use crate::program::AttachTarget;
+pub use crate::program::BenchmarkStats;
 pub use crate::program::Input as ProgramInput;
+pub use crate::program::NetkitOpts;
 pub use crate::program::OpenProgram;
 pub use crate::program::OpenProgramImpl;
 pub use crate::program::OpenProgramMut;
@@ -142,11 +190,19 @@ pub use crate::program::ProgramAttachType;
 pub use crate::program::ProgramImpl;
 pub use crate::program::ProgramMut;
 pub use crate::program::ProgramType;
+pub use crate::program::TestRunContext;
 pub use crate::program::TracepointOpts;
 pub use crate::program::UprobeOpts;
 pub use crate::program::UsdtOpts;
+pub use crate::program::VerifierStats;
+pub use crate::progress::LoadItem;
+pub use crate::progress::LoadOutcome;
+pub use crate::progress::LoadProgress;
+pub use crate::restricted_map::ReadOnlyMap;
+pub use crate::restricted_map::WriteOnlyMap;
 pub use crate::ringbuf::RingBuffer;
 pub use crate::ringbuf::RingBufferBuilder;
+pub use crate::snapshot::MapSnapshot;
 pub use crate::tc::TcAttachPoint;
 pub use crate::tc::TcHook;
 pub use crate::tc::TcHookBuilder;
@@ -157,10 +213,19 @@ pub use crate::tc::TC_H_INGRESS;
 pub use crate::tc::TC_H_MIN_EGRESS;
 pub use crate::tc::TC_H_MIN_INGRESS;
 pub use crate::tc::TC_INGRESS;
+pub use crate::token::BpfToken;
 pub use crate::user_ringbuf::UserRingBuffer;
 pub use crate::user_ringbuf::UserRingBufferSample;
+pub use crate::util::cpu_numa_node;
 pub use crate::util::num_possible_cpus;
+pub use crate::util::online_cpus;
+pub use crate::validate::validate_object;
+pub use crate::validate::ObjectLimits;
+pub use crate::watch::MapUpdate;
+pub use crate::watch::MapWatcher;
 pub use crate::xdp::Xdp;
+pub use crate::xdp::XdpDeviceFeatures;
+pub use crate::xdp::XdpFeatures;
 pub use crate::xdp::XdpFlags;
 
 
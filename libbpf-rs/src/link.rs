@@ -1,25 +1,59 @@
+use std::ffi::OsStr;
+use std::ffi::OsString;
 use std::fmt::Debug;
 use std::os::unix::io::AsFd;
 use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::OwnedFd;
 use std::path::Path;
 use std::path::PathBuf;
 use std::ptr::NonNull;
+use std::vec::IntoIter;
 
 use crate::util;
 use crate::util::validate_bpf_ret;
 use crate::AsRawLibbpf;
 use crate::ErrorExt as _;
+use crate::Iter;
+use crate::Map;
 use crate::Program;
 use crate::Result;
 
+/// The action to take when a [`Link`] is dropped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum LinkDropPolicy {
+    /// Detach the underlying program, unless something else is holding a
+    /// reference to the link. This is the default.
+    #[default]
+    Detach,
+    /// Leave the underlying program attached, even after the process
+    /// exits (if supported by the link type).
+    ///
+    /// This is equivalent to calling [`Link::disconnect`] before dropping
+    /// the link.
+    KeepAttached,
+    /// [Pin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
+    /// the link to the provided bpffs path and leave the program attached,
+    /// e.g., to hand it off to a different process.
+    ///
+    /// If the link is already pinned to the given path, it is left as is.
+    /// If pinning fails, the program is detached, as with
+    /// [`LinkDropPolicy::Detach`].
+    PinAt(PathBuf),
+}
+
 /// Represents an attached [`Program`].
 ///
 /// This struct is used to model ownership. The underlying program will be detached
-/// when this object is dropped if nothing else is holding a reference count.
+/// when this object is dropped if nothing else is holding a reference count. This
+/// behavior can be adjusted by means of [`Link::set_drop_policy`].
+///
+/// A `Link` is [`Send`] and [`Sync`] and can be shared between threads by
+/// wrapping it in an [`Arc`][std::sync::Arc].
 #[derive(Debug)]
 #[must_use = "not using this `Link` will detach the underlying program immediately"]
 pub struct Link {
     ptr: NonNull<libbpf_sys::bpf_link>,
+    drop_policy: LinkDropPolicy,
 }
 
 impl Link {
@@ -29,7 +63,20 @@ impl Link {
     ///
     /// `ptr` must point to a correctly initialized [`libbpf_sys::bpf_link`].
     pub(crate) unsafe fn new(ptr: NonNull<libbpf_sys::bpf_link>) -> Self {
-        Link { ptr }
+        Link {
+            ptr,
+            drop_policy: LinkDropPolicy::default(),
+        }
+    }
+
+    /// Run the BPF iterator this link attaches, returning an [`Iter`]
+    /// for reading its output.
+    ///
+    /// The link has to be the result of attaching an iterator program,
+    /// e.g., via
+    /// [`ProgramMut::attach_iter_target`][crate::ProgramMut::attach_iter_target].
+    pub fn iter_create(&self) -> Result<Iter> {
+        Iter::new(self)
     }
 
     /// Create link from BPF FS file.
@@ -58,6 +105,22 @@ impl Link {
         util::parse_ret(ret)
     }
 
+    /// Replace the struct_ops map backing this link with `map`, atomically
+    /// switching the registered implementation (e.g., a TCP congestion
+    /// control algorithm) over to the one described by `map`.
+    ///
+    /// The link must have been created by
+    /// [`MapMut::attach_struct_ops`][crate::MapMut::attach_struct_ops] for
+    /// a map defined in a `SEC(".struct_ops.link")` section and `map` has to
+    /// be of the same struct_ops type. Depending on the kernel subsystem
+    /// further constraints may apply, e.g., TCP congestion control
+    /// algorithms have to keep their name.
+    pub fn update_map(&mut self, map: &Map<'_>) -> Result<()> {
+        let map_ptr = map.as_libbpf_object().as_ptr();
+        let ret = unsafe { libbpf_sys::bpf_link__update_map(self.ptr.as_ptr(), map_ptr) };
+        util::parse_ret(ret)
+    }
+
     /// Release "ownership" of underlying BPF resource (typically, a BPF program
     /// attached to some BPF hook, e.g., tracepoint, kprobe, etc). Disconnected
     /// links, when destructed through bpf_link__destroy() call won't attempt to
@@ -103,6 +166,27 @@ impl Link {
         Some(PathBuf::from(path.as_str()))
     }
 
+    /// Set the action to take when this `Link` is dropped.
+    pub fn set_drop_policy(&mut self, policy: LinkDropPolicy) {
+        self.drop_policy = policy;
+    }
+
+    /// Retrieve the action to take when this `Link` is dropped.
+    pub fn drop_policy(&self) -> &LinkDropPolicy {
+        &self.drop_policy
+    }
+
+    /// Duplicate the link's file descriptor.
+    ///
+    /// The returned descriptor refers to the same kernel link and keeps
+    /// the attachment in place even after this `Link` got dropped, until
+    /// it is closed as well or the link is detached explicitly.
+    pub fn try_clone_fd(&self) -> Result<OwnedFd> {
+        self.as_fd()
+            .try_clone_to_owned()
+            .context("failed to duplicate link file descriptor")
+    }
+
     /// Detach the link.
     pub fn detach(&self) -> Result<()> {
         let ret = unsafe { libbpf_sys::bpf_link__detach(self.ptr.as_ptr()) };
@@ -122,6 +206,11 @@ impl AsRawLibbpf for Link {
 // SAFETY: `bpf_link` objects can safely be sent to a different thread.
 unsafe impl Send for Link {}
 
+// SAFETY: All methods accessible through a shared reference merely read
+//         immutable state of the `bpf_link` or invoke system calls on its
+//         file descriptor, both of which can happen concurrently.
+unsafe impl Sync for Link {}
+
 impl AsFd for Link {
     #[inline]
     fn as_fd(&self) -> BorrowedFd<'_> {
@@ -135,6 +224,112 @@ impl AsFd for Link {
 
 impl Drop for Link {
     fn drop(&mut self) {
+        let keep_attached = match &self.drop_policy {
+            LinkDropPolicy::Detach => false,
+            LinkDropPolicy::KeepAttached => true,
+            LinkDropPolicy::PinAt(path) => {
+                if self.pin_path().as_deref() == Some(path.as_path()) {
+                    true
+                } else {
+                    let path = path.clone();
+                    self.pin(path).is_ok()
+                }
+            }
+        };
+
+        if keep_attached {
+            let () = self.disconnect();
+        }
+
         let _ = unsafe { libbpf_sys::bpf_link__destroy(self.ptr.as_ptr()) };
     }
 }
+
+/// A set of [`Link`]s, each associated with the name of the program it
+/// attaches, as created by [`Object::attach_all`][crate::Object::attach_all].
+///
+/// Dropping the bundle drops all contained links, which are treated
+/// according to their [drop policy][Link::set_drop_policy].
+#[derive(Debug, Default)]
+#[must_use = "not using these `Links` will detach the underlying programs immediately"]
+pub struct Links {
+    links: Vec<(OsString, Link)>,
+}
+
+impl Links {
+    /// Add `link` for the program named `name`.
+    pub(crate) fn push(&mut self, name: OsString, link: Link) {
+        let () = self.links.push((name, link));
+    }
+
+    /// Retrieve the link attaching the program with the given name.
+    pub fn get<N: AsRef<OsStr>>(&self, name: N) -> Option<&Link> {
+        self.links
+            .iter()
+            .find(|(prog, _link)| prog == name.as_ref())
+            .map(|(_prog, link)| link)
+    }
+
+    /// Retrieve the link attaching the program with the given name.
+    pub fn get_mut<N: AsRef<OsStr>>(&mut self, name: N) -> Option<&mut Link> {
+        self.links
+            .iter_mut()
+            .find(|(prog, _link)| prog == name.as_ref())
+            .map(|(_prog, link)| link)
+    }
+
+    /// Remove the link attaching the program with the given name from the
+    /// bundle, handing over ownership to the caller.
+    pub fn take<N: AsRef<OsStr>>(&mut self, name: N) -> Option<Link> {
+        let idx = self
+            .links
+            .iter()
+            .position(|(prog, _link)| prog == name.as_ref())?;
+        let (_prog, link) = self.links.remove(idx);
+        Some(link)
+    }
+
+    /// Retrieve an iterator over the contained links, along with the names
+    /// of the programs they attach, in attachment order.
+    pub fn iter(&self) -> impl Iterator<Item = (&OsStr, &Link)> {
+        self.links
+            .iter()
+            .map(|(prog, link)| (prog.as_os_str(), link))
+    }
+
+    /// Retrieve the number of contained links.
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Check whether the bundle contains no links.
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+}
+
+impl IntoIterator for Links {
+    type Item = (OsString, Link);
+    type IntoIter = IntoIter<(OsString, Link)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.links.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Check that `Link` is `Send` and `Sync`.
+    #[test]
+    fn link_is_send_and_sync() {
+        fn test<T>()
+        where
+            T: Send + Sync,
+        {
+        }
+
+        test::<Link>();
+    }
+}
@@ -0,0 +1,260 @@
+//! Userspace access to local storage maps.
+//!
+//! Task, socket, inode, and cgroup local storage maps
+//! (`BPF_MAP_TYPE_TASK_STORAGE`, `BPF_MAP_TYPE_SK_STORAGE`,
+//! `BPF_MAP_TYPE_INODE_STORAGE`, and `BPF_MAP_TYPE_CGRP_STORAGE`) attach a
+//! value to a kernel object, e.g., for keeping per-task state in LSM
+//! programs. BPF programs key them by a pointer to the object, while
+//! userspace refers to the object by a file descriptor: a pidfd (see
+//! [`pidfd_open`]) for tasks, a socket for sockets, any open file for
+//! inodes, and an opened cgroup v2 directory for cgroups. [`LocalStorage`]
+//! wraps such a map, allowing for seeding and inspecting the stored
+//! values.
+//!
+//! The legacy `BPF_MAP_TYPE_CGROUP_STORAGE` and
+//! `BPF_MAP_TYPE_PERCPU_CGROUP_STORAGE` maps are keyed by the ID of a
+//! cgroup (see [`cgroup_id`]) and, optionally, the attach type of the
+//! program using them, and are wrapped by [`CgroupStorage`].
+//!
+//! ```no_run
+//! # fn example(map: libbpf_rs::Map<'_>) -> libbpf_rs::Result<()> {
+//! use std::os::fd::AsFd as _;
+//!
+//! use libbpf_rs::local_storage::pidfd_open;
+//! use libbpf_rs::local_storage::LocalStorage;
+//! use libbpf_rs::MapFlags;
+//!
+//! let storage = LocalStorage::new(map)?;
+//! let pidfd = pidfd_open(std::process::id())?;
+//! let () = storage.insert(pidfd.as_fd(), &1u64.to_ne_bytes(), MapFlags::ANY)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::io;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::Path;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapCore;
+use crate::MapFlags;
+use crate::MapType;
+use crate::ProgramAttachType;
+use crate::Result;
+
+
+/// A typed wrapper around a task, socket, inode, or cgroup local storage
+/// map, mapping the kernel objects referred to by file descriptors to
+/// values.
+#[derive(Debug)]
+pub struct LocalStorage<M> {
+    map: M,
+}
+
+impl<M> LocalStorage<M>
+where
+    M: MapCore,
+{
+    /// Wrap `map`, which has to be of type [`MapType::TaskStorage`],
+    /// [`MapType::SkStorage`], [`MapType::InodeStorage`], or
+    /// [`MapType::CgrpStorage`].
+    pub fn new(map: M) -> Result<Self> {
+        if !matches!(
+            map.map_type(),
+            MapType::TaskStorage
+                | MapType::SkStorage
+                | MapType::InodeStorage
+                | MapType::CgrpStorage
+        ) {
+            return Err(Error::with_invalid_data(format!(
+                "map `{}` is of type {:?}; expected a local storage map",
+                map.name().to_string_lossy(),
+                map.map_type(),
+            )));
+        }
+        Ok(Self { map })
+    }
+
+    fn key(owner: BorrowedFd<'_>) -> [u8; 4] {
+        owner.as_raw_fd().to_ne_bytes()
+    }
+
+    /// Retrieve the value stored for the object referred to by `owner`,
+    /// if any.
+    pub fn get(&self, owner: BorrowedFd<'_>) -> Result<Option<Vec<u8>>> {
+        self.map.lookup(&Self::key(owner), MapFlags::ANY)
+    }
+
+    /// Store `value` for the object referred to by `owner`.
+    ///
+    /// `flags` may contain [`MapFlags::NO_EXIST`] or [`MapFlags::EXIST`]
+    /// to only create or only update a value, respectively, as well as
+    /// [`MapFlags::LOCK`] for values containing a `bpf_spin_lock`.
+    pub fn insert(&self, owner: BorrowedFd<'_>, value: &[u8], flags: MapFlags) -> Result<()> {
+        self.map.update(&Self::key(owner), value, flags)
+    }
+
+    /// Remove the value stored for the object referred to by `owner`.
+    pub fn remove(&self, owner: BorrowedFd<'_>) -> Result<()> {
+        self.map.delete(&Self::key(owner))
+    }
+
+    /// Retrieve the wrapped map.
+    #[inline]
+    pub fn map(&self) -> &M {
+        &self.map
+    }
+
+    /// Unwrap the map.
+    #[inline]
+    pub fn into_inner(self) -> M {
+        self.map
+    }
+}
+
+
+/// A typed wrapper around a legacy cgroup storage map, i.e., one of type
+/// [`MapType::CgroupStorage`] or [`MapType::PercpuCgroupStorage`].
+///
+/// Entries of such maps are created by the kernel when a program using
+/// the map gets attached to a cgroup, so they can only be read and
+/// updated, not inserted or removed.
+#[derive(Debug)]
+pub struct CgroupStorage<M> {
+    map: M,
+}
+
+impl<M> CgroupStorage<M>
+where
+    M: MapCore,
+{
+    /// Wrap `map`, which has to be of type [`MapType::CgroupStorage`] or
+    /// [`MapType::PercpuCgroupStorage`].
+    pub fn new(map: M) -> Result<Self> {
+        if !matches!(
+            map.map_type(),
+            MapType::CgroupStorage | MapType::PercpuCgroupStorage
+        ) {
+            return Err(Error::with_invalid_data(format!(
+                "map `{}` is of type {:?}; expected a cgroup storage map",
+                map.name().to_string_lossy(),
+                map.map_type(),
+            )));
+        }
+        Ok(Self { map })
+    }
+
+    /// Build the key of the entry for the given cgroup and attach type.
+    ///
+    /// Maps with a key size of eight bytes are shared between attach
+    /// types and keyed by the cgroup ID only; all others are keyed by a
+    /// `struct bpf_cgroup_storage_key`.
+    fn key(&self, cgroup_id: u64, attach_type: ProgramAttachType) -> Vec<u8> {
+        let mut key = vec![0; self.map.key_size() as usize];
+        if let Some(id) = key.get_mut(..8) {
+            let () = id.copy_from_slice(&cgroup_id.to_ne_bytes());
+        }
+        if let Some(ty) = key.get_mut(8..12) {
+            let () = ty.copy_from_slice(&(attach_type as u32).to_ne_bytes());
+        }
+        key
+    }
+
+    /// Retrieve the value stored for the cgroup with the given ID and the
+    /// given attach type.
+    ///
+    /// For per-cpu maps, the values of all possible CPUs are reported, as
+    /// by [`MapCore::lookup_percpu`].
+    pub fn get(
+        &self,
+        cgroup_id: u64,
+        attach_type: ProgramAttachType,
+    ) -> Result<Option<Vec<Vec<u8>>>> {
+        let key = self.key(cgroup_id, attach_type);
+        if self.map.map_type().is_percpu() {
+            self.map.lookup_percpu(&key, MapFlags::ANY)
+        } else {
+            let value = self.map.lookup(&key, MapFlags::ANY)?;
+            Ok(value.map(|value| vec![value]))
+        }
+    }
+
+    /// Update the value stored for the cgroup with the given ID and the
+    /// given attach type.
+    ///
+    /// `values` has to contain a single value for maps of type
+    /// [`MapType::CgroupStorage`] and one value per possible CPU for maps
+    /// of type [`MapType::PercpuCgroupStorage`].
+    pub fn set(
+        &self,
+        cgroup_id: u64,
+        attach_type: ProgramAttachType,
+        values: &[Vec<u8>],
+        flags: MapFlags,
+    ) -> Result<()> {
+        let key = self.key(cgroup_id, attach_type);
+        if self.map.map_type().is_percpu() {
+            self.map.update_percpu(&key, values, flags)
+        } else {
+            let [value] = values else {
+                return Err(Error::with_io_error(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "expected a single value for map `{}`, got {}",
+                        self.map.name().to_string_lossy(),
+                        values.len()
+                    ),
+                ))
+            };
+            self.map.update(&key, value, flags)
+        }
+    }
+
+    /// Retrieve the wrapped map.
+    #[inline]
+    pub fn map(&self) -> &M {
+        &self.map
+    }
+
+    /// Unwrap the map.
+    #[inline]
+    pub fn into_inner(self) -> M {
+        self.map
+    }
+}
+
+
+/// Retrieve the ID of the cgroup with the given cgroup v2 directory, as
+/// used for keying cgroup storage maps.
+pub fn cgroup_id<P>(path: P) -> Result<u64>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let metadata = fs::metadata(path)
+        .with_context(|| format!("failed to retrieve metadata of `{}`", path.display()))?;
+    Ok(metadata.ino())
+}
+
+
+/// Open a pidfd referring to the process with the given ID, e.g., for
+/// accessing its entries in a task local storage map.
+pub fn pidfd_open(pid: u32) -> Result<OwnedFd> {
+    // SAFETY: `pidfd_open` is always safe to call.
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error()
+            .with_context(|| format!("failed to open pidfd for process {pid}")))
+    }
+
+    // SAFETY: The system call succeeded and returned a file descriptor
+    //         that we now own.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+    Ok(fd)
+}
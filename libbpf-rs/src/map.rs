@@ -22,18 +22,33 @@ use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
 use std::slice::from_raw_parts;
+use std::sync::atomic;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::SyncSender;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::thread;
 
 use bitflags::bitflags;
 use libbpf_sys::bpf_map_info;
 use libbpf_sys::bpf_obj_get_info_by_fd;
 
+#[cfg(feature = "serde")]
+use crate::btf::json;
+use crate::trace;
 use crate::util;
 use crate::util::parse_ret_i32;
 use crate::util::validate_bpf_ret;
 use crate::AsRawLibbpf;
+#[cfg(feature = "serde")]
+use crate::Btf;
 use crate::Error;
 use crate::ErrorExt as _;
 use crate::Link;
+use crate::MapSnapshot;
 use crate::Mut;
 use crate::Result;
 
@@ -85,6 +100,57 @@ impl<'obj> OpenMap<'obj> {
         MapType::from(ty)
     }
 
+    /// Retrieve the size of the map's keys in bytes.
+    pub fn key_size(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_map__key_size(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the size of the map's values in bytes.
+    pub fn value_size(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_map__value_size(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the maximum number of entries the map can have.
+    pub fn max_entries(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_map__max_entries(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the flags the map will be created with.
+    pub fn map_flags(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_map__map_flags(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the NUMA node the map will be allocated on.
+    pub fn numa_node(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_map__numa_node(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the index of the network device the map is bound to, or
+    /// zero if it is not bound to any.
+    pub fn ifindex(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_map__ifindex(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the map type specific extra value, e.g., the number of
+    /// hash functions used by a bloom filter.
+    pub fn map_extra(&self) -> u64 {
+        unsafe { libbpf_sys::bpf_map__map_extra(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the path the map will be pinned at, if any.
+    ///
+    /// For maps declared with `__uint(pinning, LIBBPF_PIN_BY_NAME)` the
+    /// path is derived from the map's name and the object's
+    /// [pin root path][crate::ObjectBuilder::pin_root_path].
+    pub fn pin_path(&self) -> Option<&OsStr> {
+        let path_ptr = unsafe { libbpf_sys::bpf_map__pin_path(self.ptr.as_ptr()) };
+        if path_ptr.is_null() {
+            return None
+        }
+        let path_c_str = unsafe { CStr::from_ptr(path_ptr) };
+        Some(OsStr::from_bytes(path_c_str.to_bytes()))
+    }
+
     fn initial_value_raw(&self) -> (*mut u8, usize) {
         let mut size = 0u64;
         let ptr = unsafe {
@@ -127,12 +193,21 @@ impl<'obj> OpenMapMut<'obj> {
 
     /// Bind map to a particular network device.
     ///
-    /// Used for offloading maps to hardware.
-    pub fn set_map_ifindex(&mut self, idx: u32) {
-        unsafe { libbpf_sys::bpf_map__set_ifindex(self.ptr.as_ptr(), idx) };
+    /// Used for offloading maps to hardware. Only array and hash maps can
+    /// be offloaded, and they can only be used by programs offloaded to
+    /// the same device. See
+    /// [`offload::is_map_type_supported`][crate::offload::is_map_type_supported]
+    /// for probing for support.
+    pub fn set_map_ifindex(&mut self, idx: u32) -> Result<()> {
+        let ret = unsafe { libbpf_sys::bpf_map__set_ifindex(self.ptr.as_ptr(), idx) };
+        util::parse_ret(ret)
     }
 
     /// Set the initial value of the map.
+    ///
+    /// `data` has to be exactly as large as the map's current initial
+    /// value. Use [`OpenMapMut::set_value_size`] to resize the map first,
+    /// if necessary.
     pub fn set_initial_value(&mut self, data: &[u8]) -> Result<()> {
         let ret = unsafe {
             libbpf_sys::bpf_map__set_initial_value(
@@ -164,6 +239,10 @@ impl<'obj> OpenMapMut<'obj> {
     }
 
     /// Set the maximum number of entries this map can have.
+    ///
+    /// This allows for sizing maps based on properties of the system the
+    /// program is loaded on, e.g., the number of CPUs or the amount of
+    /// memory available.
     pub fn set_max_entries(&mut self, count: u32) -> Result<()> {
         let ret = unsafe { libbpf_sys::bpf_map__set_max_entries(self.ptr.as_ptr(), count) };
         util::parse_ret(ret)
@@ -175,15 +254,18 @@ impl<'obj> OpenMapMut<'obj> {
         util::parse_ret(ret)
     }
 
-    // TODO: Document member.
-    #[allow(missing_docs)]
+    /// Set the NUMA node the map should be allocated on.
+    ///
+    /// The kernel only honors the node if the map is created with the
+    /// `BPF_F_NUMA_NODE` flag, which this method sets as well.
     pub fn set_numa_node(&mut self, numa_node: u32) -> Result<()> {
         let ret = unsafe { libbpf_sys::bpf_map__set_numa_node(self.ptr.as_ptr(), numa_node) };
-        util::parse_ret(ret)
+        let () = util::parse_ret(ret)?;
+        self.set_map_flags(self.map_flags() | libbpf_sys::BPF_F_NUMA_NODE)
     }
 
-    // TODO: Document member.
-    #[allow(missing_docs)]
+    /// Set the map used as a template for the inner maps of this
+    /// map-in-map.
     pub fn set_inner_map_fd(&mut self, inner_map_fd: BorrowedFd<'_>) -> Result<()> {
         let ret = unsafe {
             libbpf_sys::bpf_map__set_inner_map_fd(self.ptr.as_ptr(), inner_map_fd.as_raw_fd())
@@ -191,8 +273,8 @@ impl<'obj> OpenMapMut<'obj> {
         util::parse_ret(ret)
     }
 
-    // TODO: Document member.
-    #[allow(missing_docs)]
+    /// Set the map type specific extra value, e.g., the number of hash
+    /// functions used by a bloom filter.
     pub fn set_map_extra(&mut self, map_extra: u64) -> Result<()> {
         let ret = unsafe { libbpf_sys::bpf_map__set_map_extra(self.ptr.as_ptr(), map_extra) };
         util::parse_ret(ret)
@@ -206,7 +288,9 @@ impl<'obj> OpenMapMut<'obj> {
 
     /// Set where the map should be pinned.
     ///
-    /// Note this does not actually create the pin.
+    /// Note this does not actually create the pin. Rather, when the object
+    /// is loaded, a map already pinned at `path` is reused if compatible,
+    /// and the newly created map gets pinned there otherwise.
     pub fn set_pin_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
         let path_c = util::path_to_cstring(path)?;
         let path_ptr = path_c.as_ptr();
@@ -215,6 +299,16 @@ impl<'obj> OpenMapMut<'obj> {
         util::parse_ret(ret)
     }
 
+    /// Opt the map out of pinning.
+    ///
+    /// This overrides `__uint(pinning, LIBBPF_PIN_BY_NAME)` as well as a
+    /// path set via [`OpenMapMut::set_pin_path`], causing the map to
+    /// neither reuse an existing pin nor to be pinned on load.
+    pub fn clear_pin_path(&mut self) -> Result<()> {
+        let ret = unsafe { libbpf_sys::bpf_map__set_pin_path(self.ptr.as_ptr(), ptr::null()) };
+        util::parse_ret(ret)
+    }
+
     /// Reuse an fd for a BPF map
     pub fn reuse_fd(&mut self, fd: BorrowedFd<'_>) -> Result<()> {
         let ret = unsafe { libbpf_sys::bpf_map__reuse_fd(self.ptr.as_ptr(), fd.as_raw_fd()) };
@@ -270,7 +364,9 @@ where
     M: MapCore + ?Sized,
 {
     let val_size = map.value_size() as usize;
-    util::roundup(val_size, 8)
+    let aligned_val_size = util::roundup(val_size, 8);
+    debug_assert!(aligned_val_size >= val_size && aligned_val_size % 8 == 0);
+    aligned_val_size
 }
 
 /// Returns the size of the buffer needed for a lookup/update of a per-cpu map.
@@ -283,6 +379,54 @@ where
     Ok(ncpu * aligned_val_size)
 }
 
+/// Check that a buffer of `provided` bytes has the `expected` size for
+/// being used as `what` with `map`, reporting a descriptive error if not.
+fn check_size<M>(map: &M, what: &str, provided: usize, expected: usize) -> Result<()>
+where
+    M: MapCore + ?Sized,
+{
+    if provided != expected {
+        return Err(Error::with_invalid_data(format!(
+            "{what} for map `{}` has size {provided}, expected {expected}",
+            map.name().to_string_lossy(),
+        )))
+    }
+    Ok(())
+}
+
+/// Check that `key` is as large as the keys of `map`.
+fn check_key_size<M>(map: &M, key: &[u8]) -> Result<()>
+where
+    M: MapCore + ?Sized,
+{
+    check_size(map, "key", key.len(), map.key_size() as usize)
+}
+
+/// Check that `value` is large enough to hold the values of all CPUs of
+/// the per-cpu `map`.
+///
+/// A common mistake is not accounting for each value being padded to a
+/// multiple of eight bytes, which is called out explicitly.
+pub(crate) fn check_percpu_buffer_size<M>(map: &M, value: &[u8]) -> Result<()>
+where
+    M: MapCore + ?Sized,
+{
+    let expected = percpu_buffer_size(map)?;
+    if value.len() != expected {
+        let ncpu = crate::num_possible_cpus()?;
+        let unpadded = ncpu * map.value_size() as usize;
+        if value.len() == unpadded {
+            return Err(Error::with_invalid_data(format!(
+                "value buffer for per-cpu map `{}` has size {unpadded}, expected {expected}: \
+                 the value of each of the {ncpu} possible CPUs has to be padded to {} bytes",
+                map.name().to_string_lossy(),
+                percpu_aligned_value_size(map),
+            )))
+        }
+    }
+    check_size(map, "value buffer", value.len(), expected)
+}
+
 /// Apply a key check and return a null pointer in case of dealing with queue/stack/bloom-filter
 /// map, before passing the key to the bpf functions that support the map of type
 /// queue/stack/bloom-filter.
@@ -298,58 +442,171 @@ where
     key.as_ptr() as *const c_void
 }
 
-/// Internal function to return a value from a map into a buffer of the given size.
-fn lookup_raw<M>(map: &M, key: &[u8], flags: MapFlags, out_size: usize) -> Result<Option<Vec<u8>>>
+/// A read-only memory mapping of the values of a `BPF_F_MMAPABLE` array
+/// map.
+#[derive(Debug)]
+struct ArrayMmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl ArrayMmap {
+    /// Map the values of the given map, if it is a memory mappable array.
+    fn new(
+        fd: BorrowedFd<'_>,
+        map_type: MapType,
+        map_flags: u32,
+        value_size: u32,
+        max_entries: u32,
+    ) -> Option<Self> {
+        if map_type != MapType::Array || map_flags & libbpf_sys::BPF_F_MMAPABLE == 0 {
+            return None
+        }
+
+        let len = util::roundup(value_size as usize, 8).checked_mul(max_entries as usize)?;
+        if len == 0 {
+            return None
+        }
+
+        // SAFETY: All arguments are valid and we check for errors.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None
+        }
+
+        Some(Self {
+            ptr: NonNull::new(ptr.cast())?,
+            len,
+        })
+    }
+
+    fn as_ptr(&self) -> NonNull<[u8]> {
+        NonNull::slice_from_raw_parts(self.ptr, self.len)
+    }
+}
+
+impl Drop for ArrayMmap {
+    fn drop(&mut self) {
+        // SAFETY: We created the mapping with the given size and it is no
+        //         longer referenced.
+        let _ret = unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+// SAFETY: The mapping is read-only and may be accessed from any thread.
+unsafe impl Send for ArrayMmap {}
+// SAFETY: The mapping is read-only and may be accessed from any thread.
+unsafe impl Sync for ArrayMmap {}
+
+/// Look up the value for `key` in the memory mapped `values` of an array
+/// map, copying it to `out`. Returns `None` if the lookup has to be
+/// performed using the `bpf` system call instead.
+fn lookup_mmap(
+    values: NonNull<[u8]>,
+    value_size: usize,
+    key: &[u8],
+    flags: MapFlags,
+    out: *mut c_void,
+) -> Option<bool> {
+    // Lookups honoring the value's spin lock require kernel support.
+    if flags.contains(MapFlags::LOCK) {
+        return None
+    }
+
+    let index = u32::from_ne_bytes(key.try_into().ok()?) as usize;
+    let offset = index.checked_mul(util::roundup(value_size, 8))?;
+    // Just as the system call, report out-of-bounds indices as not
+    // found.
+    match offset.checked_add(value_size) {
+        Some(end) if end <= values.len() => (),
+        _ => return Some(false),
+    }
+
+    // Order the copy after all preceding memory accesses, as the
+    // system call would.
+    let () = atomic::fence(Ordering::SeqCst);
+    let value = values.as_ptr().cast::<u8>();
+    let out = out.cast::<u8>();
+    for i in 0..value_size {
+        // SAFETY: We checked that the value is within the mapping and the
+        //         caller guarantees that `out` can hold a value. BPF
+        //         programs may modify the value concurrently, so we read
+        //         it using volatile accesses.
+        let () = unsafe { out.add(i).write(value.add(offset + i).read_volatile()) };
+    }
+    Some(true)
+}
+
+/// Internal function to look up a value from a map into the buffer `out`,
+/// which has to be large enough to hold it. Returns `false` if the map has
+/// no entry for `key`.
+fn lookup_raw_into<M>(map: &M, key: &[u8], flags: MapFlags, out: *mut c_void) -> Result<bool>
 where
     M: MapCore + ?Sized,
 {
-    if key.len() != map.key_size() as usize {
-        return Err(Error::with_invalid_data(format!(
-            "key_size {} != {}",
-            key.len(),
-            map.key_size()
-        )));
-    };
+    let () = check_key_size(map, key)?;
 
-    let mut out: Vec<u8> = Vec::with_capacity(out_size);
+    if let Some(values) = map.direct_ptr() {
+        if let Some(found) = lookup_mmap(values, map.value_size() as usize, key, flags, out) {
+            return Ok(found)
+        }
+    }
 
     let ret = unsafe {
         libbpf_sys::bpf_map_lookup_elem_flags(
             map.as_fd().as_raw_fd(),
             map_key(map, key),
-            out.as_mut_ptr() as *mut c_void,
+            out,
             flags.bits(),
         )
     };
 
     if ret == 0 {
-        unsafe {
-            out.set_len(out_size);
-        }
-        Ok(Some(out))
+        Ok(true)
     } else {
         let err = io::Error::last_os_error();
         if err.kind() == io::ErrorKind::NotFound {
-            Ok(None)
+            Ok(false)
         } else {
             Err(Error::from(err))
         }
     }
 }
 
+/// Internal function to return a value from a map into a buffer of the given size.
+fn lookup_raw<M>(map: &M, key: &[u8], flags: MapFlags, out_size: usize) -> Result<Option<Vec<u8>>>
+where
+    M: MapCore + ?Sized,
+{
+    let mut out: Vec<u8> = Vec::with_capacity(out_size);
+    let found = lookup_raw_into(map, key, flags, out.as_mut_ptr() as *mut c_void)?;
+    if found {
+        // SAFETY: The kernel initialized `out_size` bytes.
+        unsafe {
+            out.set_len(out_size);
+        }
+        Ok(Some(out))
+    } else {
+        Ok(None)
+    }
+}
+
 /// Internal function to update a map. This does not check the length of the
 /// supplied value.
-fn update_raw<M>(map: &M, key: &[u8], value: &[u8], flags: MapFlags) -> Result<()>
+pub(crate) fn update_raw<M>(map: &M, key: &[u8], value: &[u8], flags: MapFlags) -> Result<()>
 where
     M: MapCore + ?Sized,
 {
-    if key.len() != map.key_size() as usize {
-        return Err(Error::with_invalid_data(format!(
-            "key_size {} != {}",
-            key.len(),
-            map.key_size()
-        )));
-    };
+    let () = check_key_size(map, key)?;
 
     let ret = unsafe {
         libbpf_sys::bpf_map_update_elem(
@@ -371,8 +628,48 @@ mod private {
 
     impl<T> Sealed for MapImpl<'_, T> {}
     impl Sealed for MapHandle {}
+
+    pub trait Counter: Copy + Default {
+        fn from_ne_slice(bytes: &[u8]) -> Self;
+        fn add_wrapping(self, other: Self) -> Self;
+    }
+}
+
+/// The size of the stack buffer used by [`MapCore::lookup_percpu_sum`],
+/// sufficient for 512 CPUs.
+const PERCPU_SUM_STACK_SIZE: usize = 4096;
+
+/// An integer type that the per-CPU values of a map can be summed up as,
+/// by means of [`MapCore::lookup_percpu_sum`].
+///
+/// This trait is sealed and implemented for all fixed size integer types
+/// up to 64 bits.
+pub trait PercpuCounter: private::Counter {}
+
+macro_rules! impl_percpu_counter {
+    ($($ty:ty),*) => {
+        $(
+            impl private::Counter for $ty {
+                #[inline]
+                fn from_ne_slice(bytes: &[u8]) -> Self {
+                    let mut buf = [0; mem::size_of::<$ty>()];
+                    let () = buf.copy_from_slice(bytes);
+                    <$ty>::from_ne_bytes(buf)
+                }
+
+                #[inline]
+                fn add_wrapping(self, other: Self) -> Self {
+                    self.wrapping_add(other)
+                }
+            }
+
+            impl PercpuCounter for $ty {}
+        )*
+    };
 }
 
+impl_percpu_counter!(u8, u16, u32, u64, i8, i16, i32, i64);
+
 /// A trait representing core functionality common to fully initialized maps.
 pub trait MapCore: Debug + AsFd + private::Sealed {
     /// Retrieve the map's name.
@@ -387,6 +684,19 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
     /// Retrieve the size of the map's values.
     fn value_size(&self) -> u32;
 
+    /// Retrieve a pointer to the values of a `BPF_F_MMAPABLE` array map as
+    /// they are mapped into memory, or `None` for other maps.
+    ///
+    /// Value `i` starts at offset `i * value_size` rounded up to a multiple
+    /// of eight bytes. The memory must not be written to and stays valid
+    /// for as long as `self` (or the object the map belongs to) does. As
+    /// BPF programs may modify it at any time, it must only be read using
+    /// volatile or atomic accesses. For maps backing global variables the
+    /// mapping libbpf established when loading the object is used; other
+    /// maps are mapped on first use. [`Self::lookup()`] reads values from
+    /// the mapping, bypassing the `bpf` system call.
+    fn direct_ptr(&self) -> Option<NonNull<[u8]>>;
+
     /// Fetch extra map information
     #[inline]
     fn info(&self) -> Result<MapInfo> {
@@ -399,7 +709,72 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
     /// iteration can skip keys, restart from the beginning, or duplicate keys. In other words,
     /// iteration becomes unpredictable.
     fn keys(&self) -> MapKeyIter<'_> {
-        MapKeyIter::new(self.as_fd(), self.key_size())
+        MapKeyIter::new(
+            self.as_fd(),
+            self.map_type(),
+            self.key_size(),
+            self.value_size(),
+        )
+    }
+
+    /// Returns an iterator over the entries in this map, as pairs of key and
+    /// value.
+    ///
+    /// Values of per-cpu maps contain the value for each possible CPU, each
+    /// padded to a multiple of eight bytes, as for
+    /// [`Self::lookup_percpu()`].
+    ///
+    /// The same caveats regarding concurrent modifications as for
+    /// [`Self::keys()`] apply.
+    fn iter(&self) -> Result<MapEntryIter<'_>> {
+        MapEntryIter::new(self)
+    }
+
+    /// Dump the entries of this map on up to `num_workers` background
+    /// threads, delivering them through the returned channel as pairs of
+    /// key and value.
+    ///
+    /// Entries are retrieved in batches by a dedicated thread, while the
+    /// workers split the batches into individual entries. As the kernel
+    /// does not define the position a batch continues from, the batches
+    /// themselves cannot be retrieved in parallel. Maps not supporting
+    /// batch lookups, or maps on kernels lacking support for them, are
+    /// dumped entry by entry by a single thread. Entries arrive in no
+    /// particular order. Errors are reported through the channel as well,
+    /// after which the dump stops. All threads exit once done or once the
+    /// receiver got dropped.
+    ///
+    /// Values are formatted as for [`Self::iter()`] and the same caveats
+    /// regarding concurrent modifications apply.
+    fn par_dump(&self, num_workers: usize) -> Result<Receiver<Result<(Vec<u8>, Vec<u8>)>>> {
+        par_dump(self, num_workers)
+    }
+
+    /// Capture the entries of this map, along with metadata describing
+    /// it, in a [`MapSnapshot`].
+    ///
+    /// The snapshot can be serialized, e.g., to persist counters or
+    /// allowlists across reboots, and later be written back into a
+    /// compatible map using [`Self::restore()`]. Maps referencing kernel
+    /// objects by file descriptor (e.g., program arrays or socket maps)
+    /// cannot be snapshotted.
+    ///
+    /// The same caveats regarding concurrent modifications as for
+    /// [`Self::iter()`] apply.
+    fn snapshot(&self) -> Result<MapSnapshot> {
+        MapSnapshot::capture(self)
+    }
+
+    /// Write the entries captured in `snapshot` into this map, using
+    /// `flags` for each update.
+    ///
+    /// The map has to be of the same type and have the same key and value
+    /// sizes as the one the snapshot was taken of. Snapshots of per-cpu
+    /// maps can only be restored on systems with the same number of
+    /// possible CPUs. Entries present in the map but not in the snapshot
+    /// are left untouched.
+    fn restore(&self, snapshot: &MapSnapshot, flags: MapFlags) -> Result<()> {
+        snapshot.restore_into(self, flags)
     }
 
     /// Returns map value as `Vec` of `u8`.
@@ -410,15 +785,15 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
     /// must be used.
     /// If the map is of type bloom_filter the function [`Self::lookup_bloom_filter()`] must be used
     fn lookup(&self, key: &[u8], flags: MapFlags) -> Result<Option<Vec<u8>>> {
-        if self.map_type().is_bloom_filter() {
+        let map_type = self.map_type();
+        if map_type.is_bloom_filter() {
             return Err(Error::with_invalid_data(
                 "lookup_bloom_filter() must be used for bloom filter maps",
             ));
         }
-        if self.map_type().is_percpu() {
+        if map_type.is_percpu() {
             return Err(Error::with_invalid_data(format!(
-                "lookup_percpu() must be used for per-cpu maps (type of the map is {:?})",
-                self.map_type(),
+                "lookup_percpu() must be used for per-cpu maps (type of the map is {map_type:?})",
             )));
         }
 
@@ -426,15 +801,94 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
         lookup_raw(self, key, flags, out_size)
     }
 
+    /// Looks up the value for `key`, storing it in `value` instead of
+    /// allocating a new buffer. Returns `false` if the map has no entry
+    /// for `key`, in which case `value` is left untouched.
+    ///
+    /// This is meant for hot paths, such as periodically scraping a set of
+    /// maps. `key` must have exactly [`Self::key_size()`] elements. `value`
+    /// must have exactly [`Self::value_size()`] elements or, for per-cpu
+    /// maps, hold the value for each possible CPU, each padded to a
+    /// multiple of eight bytes.
+    fn lookup_into(&self, key: &[u8], value: &mut [u8], flags: MapFlags) -> Result<bool> {
+        let map_type = self.map_type();
+        if map_type.is_bloom_filter() {
+            return Err(Error::with_invalid_data(
+                "lookup_bloom_filter() must be used for bloom filter maps",
+            ));
+        }
+
+        if map_type.is_percpu() {
+            let () = check_percpu_buffer_size(self, value)?;
+        } else {
+            let value_size = self.value_size() as usize;
+            let () = check_size(self, "value buffer", value.len(), value_size)?;
+        }
+
+        lookup_raw_into(self, key, flags, value.as_mut_ptr() as *mut c_void)
+    }
+
+    /// Returns the sum of the per-cpu values for `key`, as is common for
+    /// maps holding statistics counters.
+    ///
+    /// The map's value size has to match the size of `T`. Sums wrap around
+    /// on overflow. Unlike [`Self::lookup_percpu()`] this function does not
+    /// allocate, unless the system has more than 512 possible CPUs.
+    fn lookup_percpu_sum<T>(&self, key: &[u8], flags: MapFlags) -> Result<Option<T>>
+    where
+        Self: Sized,
+        T: PercpuCounter,
+    {
+        if !self.map_type().is_percpu() {
+            return Err(Error::with_invalid_data(format!(
+                "lookup_percpu_sum() must be used for per-cpu maps (type of the map is {:?})",
+                self.map_type(),
+            )));
+        }
+
+        let val_size = self.value_size() as usize;
+        if val_size != mem::size_of::<T>() {
+            return Err(Error::with_invalid_data(format!(
+                "value size of map `{}` is {val_size}, but counter type has size {}",
+                self.name().to_string_lossy(),
+                mem::size_of::<T>()
+            )));
+        }
+
+        let aligned_val_size = percpu_aligned_value_size(self);
+        let buf_size = percpu_buffer_size(self)?;
+        let mut stack_buf = [0u8; PERCPU_SUM_STACK_SIZE];
+        let mut heap_buf;
+        let buf = if buf_size <= stack_buf.len() {
+            &mut stack_buf[..buf_size]
+        } else {
+            heap_buf = vec![0u8; buf_size];
+            &mut heap_buf[..]
+        };
+
+        if !lookup_raw_into(self, key, flags, buf.as_mut_ptr() as *mut c_void)? {
+            return Ok(None)
+        }
+
+        let sum = buf
+            .chunks_exact(aligned_val_size)
+            .map(|chunk| T::from_ne_slice(&chunk[..val_size]))
+            .fold(T::default(), T::add_wrapping);
+        Ok(Some(sum))
+    }
+
     /// Returns if the given value is likely present in bloom_filter as `bool`.
     ///
     /// `value` must have exactly [`Self::value_size()`] elements.
     fn lookup_bloom_filter(&self, value: &[u8]) -> Result<bool> {
+        let () = check_size(self, "value", value.len(), self.value_size() as usize)?;
+
+        // SAFETY: The kernel only reads the value for bloom filter maps.
         let ret = unsafe {
             libbpf_sys::bpf_map_lookup_elem(
                 self.as_fd().as_raw_fd(),
                 ptr::null(),
-                value.to_vec().as_mut_ptr() as *mut c_void,
+                value.as_ptr() as *mut c_void,
             )
         };
 
@@ -477,17 +931,72 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
         }
     }
 
+    /// Dump the map's contents as JSON.
+    ///
+    /// The result is an array of objects with `key` and `value` members.
+    /// Keys and values are rendered as structured JSON based on the
+    /// map's BTF type information, if available, and as hexadecimal
+    /// strings otherwise. Values of per-CPU maps are reported as arrays
+    /// with one element per CPU.
+    ///
+    /// Maps that do not support iteration over their keys (e.g., queues
+    /// and ring buffers) cannot be dumped.
+    #[cfg(feature = "serde")]
+    fn dump_json(&self) -> Result<serde_json::Value> {
+        use serde_json::Value;
+
+        if !self.map_type().supports_key_iteration() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                format!("maps of type {:?} cannot be dumped", self.map_type()),
+            ))
+        }
+
+        let info = self.info()?.info;
+        let btf = if info.btf_id != 0 {
+            Some(Btf::from_btf_id(info.btf_id).context("failed to load map BTF")?)
+        } else {
+            None
+        };
+        let render = |type_id: u32, data: &[u8]| match &btf {
+            Some(btf) if type_id != 0 => json::render(btf, type_id.into(), data),
+            _ => Ok(json::render_bytes(data)),
+        };
+
+        let mut entries = Vec::new();
+        for key in self.keys() {
+            let value = if self.map_type().is_percpu() {
+                // The entry may have been removed since we retrieved its
+                // key, in which case we just skip it.
+                let Some(values) = self.lookup_percpu(&key, MapFlags::ANY)? else {
+                    continue
+                };
+                values
+                    .iter()
+                    .map(|value| render(info.btf_value_type_id, value))
+                    .collect::<Result<Vec<_>>>()
+                    .map(Value::Array)?
+            } else {
+                let Some(value) = self.lookup(&key, MapFlags::ANY)? else {
+                    continue
+                };
+                render(info.btf_value_type_id, &value)?
+            };
+
+            let entry = serde_json::json!({
+                "key": render(info.btf_key_type_id, &key)?,
+                "value": value,
+            });
+            let () = entries.push(entry);
+        }
+        Ok(Value::Array(entries))
+    }
+
     /// Deletes an element from the map.
     ///
     /// `key` must have exactly [`Self::key_size()`] elements.
     fn delete(&self, key: &[u8]) -> Result<()> {
-        if key.len() != self.key_size() as usize {
-            return Err(Error::with_invalid_data(format!(
-                "key_size {} != {}",
-                key.len(),
-                self.key_size()
-            )));
-        };
+        let () = check_key_size(self, key)?;
 
         let ret = unsafe {
             libbpf_sys::bpf_map_delete_elem(self.as_fd().as_raw_fd(), key.as_ptr() as *const c_void)
@@ -505,14 +1014,12 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
         elem_flags: MapFlags,
         flags: MapFlags,
     ) -> Result<()> {
-        if keys.len() as u32 / count != self.key_size() || (keys.len() as u32) % count != 0 {
-            return Err(Error::with_invalid_data(format!(
-                "batch key_size {} != {} * {}",
-                keys.len(),
-                self.key_size(),
-                count
-            )));
-        };
+        let () = check_size(
+            self,
+            "batch keys",
+            keys.len(),
+            self.key_size() as usize * count as usize,
+        )?;
 
         #[allow(clippy::needless_update)]
         let opts = libbpf_sys::bpf_map_batch_opts {
@@ -535,6 +1042,53 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
         util::parse_ret(ret)
     }
 
+    /// Removes the entries of the map, returning an iterator over them as
+    /// pairs of key and value.
+    ///
+    /// Entries are retrieved and deleted in batches of up to `count`
+    /// entries via `BPF_MAP_LOOKUP_AND_DELETE_BATCH`, as the iterator
+    /// advances. Unlike separate lookups and deletions this guarantees
+    /// that no update to an entry gets lost in between, making it suitable
+    /// for draining maps that are continuously being filled, e.g., by
+    /// consuming pending events. Entries added while the map is being
+    /// drained may or may not be reported.
+    ///
+    /// Values are formatted as for [`Self::iter()`]. Only hash maps
+    /// support this operation.
+    fn lookup_and_delete_batch(
+        &self,
+        count: u32,
+        elem_flags: MapFlags,
+        flags: MapFlags,
+    ) -> Result<MapDrainIter<'_>> {
+        let map_type = self.map_type();
+        if !matches!(
+            map_type,
+            MapType::Hash | MapType::PercpuHash | MapType::LruHash | MapType::LruPercpuHash
+        ) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                format!("maps of type {map_type:?} do not support batched lookup and delete"),
+            ))
+        }
+
+        let value_size = if map_type.is_percpu() {
+            percpu_buffer_size(self)?
+        } else {
+            self.value_size() as usize
+        };
+        let key_size = self.key_size() as usize;
+        let cursor = BatchCursor::new(self.as_fd(), key_size, value_size).deleting(
+            count as usize,
+            elem_flags,
+            flags,
+        );
+        Ok(MapDrainIter {
+            cursor,
+            failed: false,
+        })
+    }
+
     /// Same as [`Self::lookup()`] except this also deletes the key from the map.
     ///
     /// Note that this operation is currently only implemented in the kernel for [`MapType::Queue`]
@@ -542,13 +1096,7 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
     ///
     /// `key` must have exactly [`Self::key_size()`] elements.
     fn lookup_and_delete(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        if key.len() != self.key_size() as usize {
-            return Err(Error::with_invalid_data(format!(
-                "key_size {} != {}",
-                key.len(),
-                self.key_size()
-            )));
-        };
+        let () = check_key_size(self, key)?;
 
         let mut out: Vec<u8> = Vec::with_capacity(self.value_size() as usize);
 
@@ -589,13 +1137,7 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
             )));
         }
 
-        if value.len() != self.value_size() as usize {
-            return Err(Error::with_invalid_data(format!(
-                "value_size {} != {}",
-                value.len(),
-                self.value_size()
-            )));
-        };
+        let () = check_size(self, "value", value.len(), self.value_size() as usize)?;
 
         update_raw(self, key, value, flags)
     }
@@ -612,23 +1154,19 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
         elem_flags: MapFlags,
         flags: MapFlags,
     ) -> Result<()> {
-        if keys.len() as u32 / count != self.key_size() || (keys.len() as u32) % count != 0 {
-            return Err(Error::with_invalid_data(format!(
-                "batch key_size {} != {} * {}",
-                keys.len(),
-                self.key_size(),
-                count
-            )));
-        };
+        let () = check_size(
+            self,
+            "batch keys",
+            keys.len(),
+            self.key_size() as usize * count as usize,
+        )?;
 
-        if values.len() as u32 / count != self.value_size() || (values.len() as u32) % count != 0 {
-            return Err(Error::with_invalid_data(format!(
-                "batch value_size {} != {} * {}",
-                values.len(),
-                self.value_size(),
-                count
-            )));
-        }
+        let () = check_size(
+            self,
+            "batch values",
+            values.len(),
+            self.value_size() as usize * count as usize,
+        )?;
 
         #[allow(clippy::needless_update)]
         let opts = libbpf_sys::bpf_map_batch_opts {
@@ -668,29 +1206,23 @@ pub trait MapCore: Debug + AsFd + private::Sealed {
             )));
         }
 
-        if values.len() != crate::num_possible_cpus()? {
+        let ncpu = crate::num_possible_cpus()?;
+        if values.len() != ncpu {
             return Err(Error::with_invalid_data(format!(
-                "number of values {} != number of cpus {}",
+                "number of values for per-cpu map `{}` is {}, expected one per possible CPU ({ncpu})",
+                self.name().to_string_lossy(),
                 values.len(),
-                crate::num_possible_cpus()?
             )));
         };
 
         let val_size = self.value_size() as usize;
         let aligned_val_size = percpu_aligned_value_size(self);
-        let buf_size = percpu_buffer_size(self)?;
+        let buf_size = ncpu * aligned_val_size;
 
         let mut value_buf = vec![0; buf_size];
 
         for (i, val) in values.iter().enumerate() {
-            if val.len() != val_size {
-                return Err(Error::with_invalid_data(format!(
-                    "value size for cpu {} is {} != {}",
-                    i,
-                    val.len(),
-                    val_size
-                )));
-            }
+            let () = check_size(self, &format!("value of CPU {i}"), val.len(), val_size)?;
 
             value_buf[(i * aligned_val_size)..(i * aligned_val_size + val_size)]
                 .copy_from_slice(val);
@@ -712,6 +1244,8 @@ pub type MapMut<'obj> = MapImpl<'obj, Mut>;
 #[derive(Debug)]
 pub struct MapImpl<'obj, T = ()> {
     ptr: NonNull<libbpf_sys::bpf_map>,
+    /// The lazily established memory mapping of the map's values.
+    mmap: OnceLock<Option<ArrayMmap>>,
     _phantom: PhantomData<&'obj T>,
 }
 
@@ -728,6 +1262,7 @@ impl<'obj> Map<'obj> {
 
         Self {
             ptr,
+            mmap: OnceLock::new(),
             _phantom: PhantomData,
         }
     }
@@ -745,6 +1280,7 @@ impl<'obj> Map<'obj> {
     pub unsafe fn from_map_without_fd(ptr: NonNull<libbpf_sys::bpf_map>) -> Self {
         Self {
             ptr,
+            mmap: OnceLock::new(),
             _phantom: PhantomData,
         }
     }
@@ -766,6 +1302,42 @@ impl<'obj> Map<'obj> {
     }
 }
 
+impl<T> MapImpl<'_, T> {
+    /// Retrieve the NUMA node the map was allocated on, if it is bound to
+    /// a specific one (see [`OpenMapMut::set_numa_node`]).
+    pub fn numa_node(&self) -> Option<u32> {
+        let flags = unsafe { libbpf_sys::bpf_map__map_flags(self.ptr.as_ptr()) };
+        (flags & libbpf_sys::BPF_F_NUMA_NODE != 0)
+            .then(|| unsafe { libbpf_sys::bpf_map__numa_node(self.ptr.as_ptr()) })
+    }
+
+    /// Retrieve the memory mapping of the values of a `BPF_F_MMAPABLE`
+    /// array map that libbpf established itself, if any.
+    ///
+    /// libbpf maps the values of the maps backing global variables when
+    /// loading the object and keeps them mapped until the object is
+    /// closed, so there is no need for a mapping of our own.
+    fn libbpf_mmap(&self) -> Option<NonNull<[u8]>> {
+        if map_fd(self.ptr).is_none() {
+            return None
+        }
+
+        // SAFETY: We ensured `ptr` is valid during construction.
+        let ty = unsafe { libbpf_sys::bpf_map__type(self.ptr.as_ptr()) };
+        let flags = unsafe { libbpf_sys::bpf_map__map_flags(self.ptr.as_ptr()) };
+        if MapType::from(ty) != MapType::Array || flags & libbpf_sys::BPF_F_MMAPABLE == 0 {
+            return None
+        }
+
+        let mut size = 0u64;
+        let ptr = unsafe {
+            libbpf_sys::bpf_map__initial_value(self.ptr.as_ptr(), &mut size as *mut _ as _)
+        };
+        let ptr = NonNull::new(ptr.cast::<u8>())?;
+        Some(NonNull::slice_from_raw_parts(ptr, size as usize))
+    }
+}
+
 impl<'obj> MapMut<'obj> {
     /// Create a [`MapMut`] from a [`libbpf_sys::bpf_map`].
     pub fn new_mut(map: &'obj mut libbpf_sys::bpf_map) -> Self {
@@ -779,6 +1351,7 @@ impl<'obj> MapMut<'obj> {
 
         Self {
             ptr,
+            mmap: OnceLock::new(),
             _phantom: PhantomData,
         }
     }
@@ -802,20 +1375,31 @@ impl<'obj> MapMut<'obj> {
         util::parse_ret(ret)
     }
 
-    /// Attach a struct ops map
+    /// Attach a struct ops map, registering the implementation it describes
+    /// (e.g., a TCP congestion control algorithm or a `sched_ext` scheduler)
+    /// with the kernel.
+    ///
+    /// The implementation stays registered for as long as the returned
+    /// [`Link`] exists; use [`Link::detach`] or drop it to unregister. For
+    /// maps defined in a `SEC(".struct_ops.link")` section the link can
+    /// furthermore be used to switch to a different implementation via
+    /// [`Link::update_map`].
     pub fn attach_struct_ops(&mut self) -> Result<Link> {
-        if self.map_type() != MapType::StructOps {
-            return Err(Error::with_invalid_data(format!(
-                "Invalid map type ({:?}) for attach_struct_ops()",
-                self.map_type(),
-            )));
-        }
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_struct_ops", &name, || {
+            if self.map_type() != MapType::StructOps {
+                return Err(Error::with_invalid_data(format!(
+                    "Invalid map type ({:?}) for attach_struct_ops()",
+                    self.map_type(),
+                )));
+            }
 
-        let ptr = unsafe { libbpf_sys::bpf_map__attach_struct_ops(self.ptr.as_ptr()) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach struct_ops")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+            let ptr = unsafe { libbpf_sys::bpf_map__attach_struct_ops(self.ptr.as_ptr()) };
+            let ptr = validate_bpf_ret(ptr).context("failed to attach struct_ops")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 }
 
@@ -868,6 +1452,26 @@ where
     fn value_size(&self) -> u32 {
         unsafe { libbpf_sys::bpf_map__value_size(self.ptr.as_ptr()) }
     }
+
+    fn direct_ptr(&self) -> Option<NonNull<[u8]>> {
+        if let Some(values) = self.libbpf_mmap() {
+            return Some(values)
+        }
+
+        self.mmap
+            .get_or_init(|| {
+                let fd = map_fd(self.ptr)?;
+                // SAFETY: `fd` is guaranteed to be valid for the lifetime
+                //         of the object.
+                let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+                // SAFETY: We ensured `ptr` is valid during construction.
+                let flags = unsafe { libbpf_sys::bpf_map__map_flags(self.ptr.as_ptr()) };
+                let max_entries = unsafe { libbpf_sys::bpf_map__max_entries(self.ptr.as_ptr()) };
+                ArrayMmap::new(fd, self.map_type(), flags, self.value_size(), max_entries)
+            })
+            .as_ref()
+            .map(ArrayMmap::as_ptr)
+    }
 }
 
 impl AsRawLibbpf for Map<'_> {
@@ -894,6 +1498,10 @@ impl AsRawLibbpf for Map<'_> {
 ///
 /// Some methods require working with raw bytes. You may find libraries such as
 /// [`plain`](https://crates.io/crates/plain) helpful.
+///
+/// A `MapHandle` merely wraps a file descriptor and is [`Send`] and [`Sync`].
+/// As such, it can be shared between threads by wrapping it in an
+/// [`Arc`][std::sync::Arc].
 #[derive(Debug)]
 pub struct MapHandle {
     fd: OwnedFd,
@@ -901,10 +1509,18 @@ pub struct MapHandle {
     ty: MapType,
     key_size: u32,
     value_size: u32,
+    /// The NUMA node the map was allocated on, if known.
+    numa_node: Option<u32>,
+    /// The lazily established memory mapping of the map's values.
+    mmap: OnceLock<Option<ArrayMmap>>,
 }
 
 impl MapHandle {
     /// Create a bpf map whose data is not managed by libbpf.
+    ///
+    /// To allocate the map on a specific NUMA node, set `opts.numa_node`
+    /// and include `BPF_F_NUMA_NODE` in `opts.map_flags`; without the
+    /// flag, the kernel ignores the node.
     pub fn create<T: AsRef<OsStr>>(
         map_type: MapType,
         name: Option<T>,
@@ -918,36 +1534,41 @@ impl MapHandle {
             // The old version kernel don't support specifying map name.
             None => OsString::new(),
         };
-        let name_c_str = CString::new(name.as_bytes()).map_err(|_| {
-            Error::with_invalid_data(format!("invalid name `{name:?}`: has NUL bytes"))
-        })?;
-        let name_c_ptr = if name.is_empty() {
-            ptr::null()
-        } else {
-            name_c_str.as_bytes_with_nul().as_ptr()
-        };
-
-        let fd = unsafe {
-            libbpf_sys::bpf_map_create(
-                map_type.into(),
-                name_c_ptr.cast(),
+        trace::instrument("create_map", &name.to_string_lossy(), || {
+            let name_c_str = CString::new(name.as_bytes()).map_err(|_| {
+                Error::with_invalid_data(format!("invalid name `{name:?}`: has NUL bytes"))
+            })?;
+            let name_c_ptr = if name.is_empty() {
+                ptr::null()
+            } else {
+                name_c_str.as_bytes_with_nul().as_ptr()
+            };
+
+            let fd = unsafe {
+                libbpf_sys::bpf_map_create(
+                    map_type.into(),
+                    name_c_ptr.cast(),
+                    key_size,
+                    value_size,
+                    max_entries,
+                    opts,
+                )
+            };
+            let () = util::parse_ret(fd)?;
+
+            Ok(Self {
+                // SAFETY: A file descriptor coming from the `bpf_map_create`
+                //         function is always suitable for ownership and can be
+                //         cleaned up with close.
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+                name: name.clone(),
+                ty: map_type,
                 key_size,
                 value_size,
-                max_entries,
-                opts,
-            )
-        };
-        let () = util::parse_ret(fd)?;
-
-        Ok(Self {
-            // SAFETY: A file descriptor coming from the `bpf_map_create`
-            //         function is always suitable for ownership and can be
-            //         cleaned up with close.
-            fd: unsafe { OwnedFd::from_raw_fd(fd) },
-            name,
-            ty: map_type,
-            key_size,
-            value_size,
+                numa_node: (opts.map_flags & libbpf_sys::BPF_F_NUMA_NODE != 0)
+                    .then_some(opts.numa_node),
+                mmap: OnceLock::new(),
+            })
         })
     }
 
@@ -990,7 +1611,7 @@ impl MapHandle {
         .and_then(Self::from_fd)
     }
 
-    fn from_fd(fd: OwnedFd) -> Result<Self> {
+    pub(crate) fn from_fd(fd: OwnedFd) -> Result<Self> {
         let info = MapInfo::new(fd.as_fd())?;
         Ok(Self {
             fd,
@@ -998,9 +1619,41 @@ impl MapHandle {
             ty: info.map_type(),
             key_size: info.info.key_size,
             value_size: info.info.value_size,
+            numa_node: None,
+            mmap: OnceLock::new(),
         })
     }
 
+    /// Create a new `MapHandle` referring to the same map, by duplicating
+    /// the underlying file descriptor.
+    ///
+    /// Either handle keeps the map alive on its own and may be moved to
+    /// a different thread or component.
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self {
+            fd: self
+                .fd
+                .try_clone()
+                .context("failed to duplicate map file descriptor")?,
+            name: self.name.clone(),
+            ty: self.ty,
+            key_size: self.key_size,
+            value_size: self.value_size,
+            numa_node: self.numa_node,
+            mmap: OnceLock::new(),
+        })
+    }
+
+    /// Retrieve the NUMA node the map was allocated on.
+    ///
+    /// The kernel does not report the node of existing maps, so it is only
+    /// known for maps created through this handle or derived from a
+    /// loaded [`Map`]; `None` is returned otherwise, as well as for maps
+    /// not bound to a specific node.
+    pub fn numa_node(&self) -> Option<u32> {
+        self.numa_node
+    }
+
     /// Freeze the map as read-only from user space.
     ///
     /// Entries from a frozen map can no longer be updated or deleted with the
@@ -1050,6 +1703,26 @@ impl MapCore for MapHandle {
     fn value_size(&self) -> u32 {
         self.value_size
     }
+
+    fn direct_ptr(&self) -> Option<NonNull<[u8]>> {
+        self.mmap
+            .get_or_init(|| {
+                // Avoid retrieving map information where it is not needed.
+                if self.ty != MapType::Array {
+                    return None
+                }
+                let info = MapInfo::new(self.as_fd()).ok()?;
+                ArrayMmap::new(
+                    self.as_fd(),
+                    self.ty,
+                    info.info.map_flags,
+                    self.value_size,
+                    info.info.max_entries,
+                )
+            })
+            .as_ref()
+            .map(ArrayMmap::as_ptr)
+    }
 }
 
 impl AsFd for MapHandle {
@@ -1059,6 +1732,13 @@ impl AsFd for MapHandle {
     }
 }
 
+impl From<MapHandle> for OwnedFd {
+    #[inline]
+    fn from(handle: MapHandle) -> Self {
+        handle.fd
+    }
+}
+
 impl<T> TryFrom<&MapImpl<'_, T>> for MapHandle
 where
     T: Debug,
@@ -1075,6 +1755,8 @@ where
             ty: other.map_type(),
             key_size: other.key_size(),
             value_size: other.value_size(),
+            numa_node: other.numa_node(),
+            mmap: OnceLock::new(),
         })
     }
 }
@@ -1083,16 +1765,7 @@ impl TryFrom<&MapHandle> for MapHandle {
     type Error = Error;
 
     fn try_from(other: &MapHandle) -> Result<Self> {
-        Ok(Self {
-            fd: other
-                .as_fd()
-                .try_clone_to_owned()
-                .context("failed to duplicate map file descriptor")?,
-            name: other.name().to_os_string(),
-            ty: other.map_type(),
-            key_size: other.key_size(),
-            value_size: other.value_size(),
-        })
+        other.try_clone()
     }
 }
 
@@ -1116,6 +1789,7 @@ bitflags! {
 #[non_exhaustive]
 #[repr(u32)]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document members.
 #[allow(missing_docs)]
 pub enum MapType {
@@ -1151,6 +1825,8 @@ pub enum MapType {
     TaskStorage,
     BloomFilter,
     UserRingBuf,
+    CgrpStorage,
+    Arena,
     /// We choose to specify our own "unknown" type here b/c it's really up to the kernel
     /// to decide if it wants to reject the map. If it accepts it, it just means whoever
     /// using this library is a bit out of date.
@@ -1159,6 +1835,7 @@ pub enum MapType {
 
 impl MapType {
     /// Returns if the map is of one of the per-cpu types.
+    #[inline]
     pub fn is_percpu(&self) -> bool {
         matches!(
             self,
@@ -1175,7 +1852,41 @@ impl MapType {
         matches!(self, MapType::Queue | MapType::Stack | MapType::BloomFilter)
     }
 
+    /// Returns whether the keys of a map of this type can be iterated
+    /// over (by means of [`MapCore::keys`]).
+    pub(crate) fn supports_key_iteration(&self) -> bool {
+        !matches!(
+            self,
+            MapType::Queue
+                | MapType::Stack
+                | MapType::RingBuf
+                | MapType::UserRingBuf
+                | MapType::BloomFilter
+                | MapType::StructOps
+                | MapType::SkStorage
+                | MapType::InodeStorage
+                | MapType::TaskStorage
+                | MapType::CgrpStorage
+                | MapType::Arena
+        )
+    }
+
+    /// Returns whether entries of maps of this type can be retrieved in
+    /// batches (by means of `BPF_MAP_LOOKUP_BATCH`).
+    pub(crate) fn supports_batch_lookup(&self) -> bool {
+        matches!(
+            self,
+            MapType::Hash
+                | MapType::PercpuHash
+                | MapType::LruHash
+                | MapType::LruPercpuHash
+                | MapType::Array
+                | MapType::PercpuArray
+        )
+    }
+
     /// Returns if the map is of bloom filter type
+    #[inline]
     pub fn is_bloom_filter(&self) -> bool {
         MapType::BloomFilter.eq(self)
     }
@@ -1195,6 +1906,7 @@ impl MapType {
 }
 
 impl From<u32> for MapType {
+    #[inline]
     fn from(value: u32) -> Self {
         use MapType::*;
 
@@ -1231,6 +1943,8 @@ impl From<u32> for MapType {
             x if x == TaskStorage as u32 => TaskStorage,
             x if x == BloomFilter as u32 => BloomFilter,
             x if x == UserRingBuf as u32 => UserRingBuf,
+            x if x == CgrpStorage as u32 => CgrpStorage,
+            x if x == Arena as u32 => Arena,
             _ => Unknown,
         }
     }
@@ -1242,28 +1956,166 @@ impl From<MapType> for u32 {
     }
 }
 
-/// An iterator over the keys of a BPF map.
+/// The number of entries to retrieve at once with `BPF_MAP_LOOKUP_BATCH`.
+const LOOKUP_BATCH_SIZE: usize = 256;
+
+/// Determine the size of the buffer holding the opaque position to
+/// continue a batch lookup from.
+///
+/// libbpf requires it to hold a key, but to be at least four bytes wide
+/// for hash maps, irrespective of the key size.
+fn batch_token_size(key_size: usize) -> usize {
+    key_size.max(4)
+}
+
+/// A cursor retrieving map entries in batches via `BPF_MAP_LOOKUP_BATCH`.
 #[derive(Debug)]
-pub struct MapKeyIter<'map> {
+struct BatchCursor<'map> {
+    map_fd: BorrowedFd<'map>,
+    key_size: usize,
+    value_size: usize,
+    /// The opaque position to continue the next batch from, as
+    /// reported by the kernel.
+    token: Vec<u8>,
+    keys: Vec<u8>,
+    values: Vec<u8>,
+    /// The number of entries to request per batch.
+    batch_size: usize,
+    /// The number of entries in the current batch.
+    count: usize,
+    /// The index of the next entry of the current batch to report.
+    pos: usize,
+    /// Whether we have retrieved at least one batch.
+    started: bool,
+    /// Whether the current batch is the last one.
+    exhausted: bool,
+    /// Whether to delete retrieved entries, along with the flags to use
+    /// for `BPF_MAP_LOOKUP_AND_DELETE_BATCH`.
+    delete: Option<(MapFlags, MapFlags)>,
+}
+
+impl<'map> BatchCursor<'map> {
+    fn new(map_fd: BorrowedFd<'map>, key_size: usize, value_size: usize) -> Self {
+        Self {
+            map_fd,
+            key_size,
+            value_size,
+            token: vec![0; batch_token_size(key_size)],
+            keys: Vec::new(),
+            values: Vec::new(),
+            batch_size: LOOKUP_BATCH_SIZE,
+            count: 0,
+            pos: 0,
+            started: false,
+            exhausted: false,
+            delete: None,
+        }
+    }
+
+    /// Delete entries as they are retrieved, requesting `batch_size`
+    /// entries at a time.
+    fn deleting(mut self, batch_size: usize, elem_flags: MapFlags, flags: MapFlags) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.delete = Some((elem_flags, flags));
+        self
+    }
+
+    fn fetch(&mut self) -> Result<()> {
+        loop {
+            let () = self.keys.resize(self.batch_size * self.key_size, 0);
+            let () = self.values.resize(self.batch_size * self.value_size, 0);
+            let mut count = self.batch_size as u32;
+            let (elem_flags, flags) = self
+                .delete
+                .unwrap_or((MapFlags::empty(), MapFlags::empty()));
+            let opts = libbpf_sys::bpf_map_batch_opts {
+                sz: mem::size_of::<libbpf_sys::bpf_map_batch_opts>() as _,
+                elem_flags: elem_flags.bits(),
+                flags: flags.bits(),
+                ..Default::default()
+            };
+            let in_batch = if self.started {
+                self.token.as_mut_ptr() as *mut c_void
+            } else {
+                ptr::null_mut()
+            };
+            let batch_fn = if self.delete.is_some() {
+                libbpf_sys::bpf_map_lookup_and_delete_batch
+            } else {
+                libbpf_sys::bpf_map_lookup_batch
+            };
+
+            let ret = unsafe {
+                batch_fn(
+                    self.map_fd.as_raw_fd(),
+                    in_batch,
+                    self.token.as_mut_ptr() as *mut c_void,
+                    self.keys.as_mut_ptr() as *mut c_void,
+                    self.values.as_mut_ptr() as *mut c_void,
+                    &mut count,
+                    &opts,
+                )
+            };
+            match -ret {
+                0 => (),
+                // The final batch, which may still contain entries.
+                libc::ENOENT => self.exhausted = true,
+                // A hash bucket holds more entries than requested.
+                libc::ENOSPC if count == 0 => {
+                    self.batch_size *= 2;
+                    continue
+                }
+                err => return Err(Error::from_raw_os_error(err)),
+            }
+
+            self.count = count as usize;
+            self.pos = 0;
+            self.started = true;
+            break Ok(())
+        }
+    }
+
+    /// Advance to the next entry, returning its index in the current batch.
+    fn next_entry(&mut self) -> Result<Option<usize>> {
+        while self.pos >= self.count {
+            if self.exhausted {
+                return Ok(None)
+            }
+            let () = self.fetch()?;
+        }
+
+        let idx = self.pos;
+        self.pos += 1;
+        Ok(Some(idx))
+    }
+
+    fn key(&self, idx: usize) -> &[u8] {
+        &self.keys[idx * self.key_size..][..self.key_size]
+    }
+
+    fn value(&self, idx: usize) -> &[u8] {
+        &self.values[idx * self.value_size..][..self.value_size]
+    }
+}
+
+/// A cursor retrieving map keys one by one via `BPF_MAP_GET_NEXT_KEY`.
+#[derive(Debug)]
+struct NextKeyCursor<'map> {
     map_fd: BorrowedFd<'map>,
     prev: Option<Vec<u8>>,
     next: Vec<u8>,
 }
 
-impl<'map> MapKeyIter<'map> {
-    fn new(map_fd: BorrowedFd<'map>, key_size: u32) -> Self {
+impl<'map> NextKeyCursor<'map> {
+    fn new(map_fd: BorrowedFd<'map>, key_size: usize) -> Self {
         Self {
             map_fd,
             prev: None,
-            next: vec![0; key_size as usize],
+            next: vec![0; key_size],
         }
     }
-}
-
-impl Iterator for MapKeyIter<'_> {
-    type Item = Vec<u8>;
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_key(&mut self) -> Result<Option<Vec<u8>>> {
         let prev = self.prev.as_ref().map_or(ptr::null(), |p| p.as_ptr());
 
         let ret = unsafe {
@@ -1273,15 +2125,370 @@ impl Iterator for MapKeyIter<'_> {
                 self.next.as_mut_ptr() as _,
             )
         };
-        if ret != 0 {
-            None
+        // The kernel signals the end of the map with `ENOENT`.
+        if ret == -libc::ENOENT {
+            return Ok(None)
+        }
+        let () = util::parse_ret(ret)?;
+
+        match &mut self.prev {
+            Some(prev) => prev.copy_from_slice(&self.next),
+            None => self.prev = Some(self.next.clone()),
+        }
+        Ok(Some(self.next.clone()))
+    }
+}
+
+/// The mechanism used for iterating over the entries of a map.
+#[derive(Debug)]
+enum MapCursor<'map> {
+    Batch(BatchCursor<'map>),
+    NextKey(NextKeyCursor<'map>),
+}
+
+impl<'map> MapCursor<'map> {
+    fn new(map_fd: BorrowedFd<'map>, map_type: MapType, key_size: u32, value_size: u32) -> Self {
+        let key_size = key_size as usize;
+        let value_size = if map_type.is_percpu() {
+            crate::num_possible_cpus()
+                .ok()
+                .map(|ncpu| ncpu * util::roundup(value_size as usize, 8))
         } else {
-            self.prev = Some(self.next.clone());
-            Some(self.next.clone())
+            Some(value_size as usize)
+        };
+
+        match value_size {
+            Some(value_size) if map_type.supports_batch_lookup() => {
+                Self::Batch(BatchCursor::new(map_fd, key_size, value_size))
+            }
+            _ => Self::NextKey(NextKeyCursor::new(map_fd, key_size)),
+        }
+    }
+
+    /// Advance the cursor, returning the next key along with its value, if
+    /// requested via `with_value` and retrieved as well.
+    fn next(&mut self, with_value: bool) -> Result<Option<(Vec<u8>, Option<Vec<u8>>)>> {
+        let batch = match self {
+            Self::Batch(batch) => batch,
+            Self::NextKey(cursor) => {
+                let key = cursor.next_key()?;
+                return Ok(key.map(|key| (key, None)))
+            }
+        };
+
+        match batch.next_entry() {
+            Ok(entry) => Ok(entry.map(|idx| {
+                let key = batch.key(idx).to_vec();
+                let value = with_value.then(|| batch.value(idx).to_vec());
+                (key, value)
+            })),
+            // The kernel may not support batch lookups for the map. Fall
+            // back to iterating key by key, unless we already reported
+            // entries.
+            Err(_) if !batch.started => {
+                let cursor = NextKeyCursor::new(batch.map_fd, batch.key_size);
+                *self = Self::NextKey(cursor);
+                self.next(with_value)
+            }
+            Err(err) => Err(err),
         }
     }
 }
 
+/// An iterator over the keys of a BPF map.
+///
+/// Where supported, keys are retrieved from the kernel in batches.
+/// Iteration ends early if retrieving keys fails; use
+/// [`MapKeyIter::try_next`] to learn about such failures.
+#[derive(Debug)]
+pub struct MapKeyIter<'map> {
+    cursor: MapCursor<'map>,
+}
+
+impl<'map> MapKeyIter<'map> {
+    pub(crate) fn new(
+        map_fd: BorrowedFd<'map>,
+        map_type: MapType,
+        key_size: u32,
+        value_size: u32,
+    ) -> Self {
+        Self {
+            cursor: MapCursor::new(map_fd, map_type, key_size, value_size),
+        }
+    }
+
+    /// Retrieve the next key, reporting failures to do so.
+    ///
+    /// Iteration should stop after an error.
+    pub fn try_next(&mut self) -> Result<Option<Vec<u8>>> {
+        let entry = self.cursor.next(false)?;
+        Ok(entry.map(|(key, _value)| key))
+    }
+}
+
+impl Iterator for MapKeyIter<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().ok().flatten()
+    }
+}
+
+/// An iterator over the entries of a BPF map, as returned by
+/// [`MapCore::iter`].
+///
+/// Where supported, entries are retrieved from the kernel in batches.
+/// Iteration ends early if retrieving entries fails; use
+/// [`MapEntryIter::try_next`] to learn about such failures.
+#[derive(Debug)]
+pub struct MapEntryIter<'map> {
+    map_fd: BorrowedFd<'map>,
+    value_size: usize,
+    cursor: MapCursor<'map>,
+}
+
+impl<'map> MapEntryIter<'map> {
+    fn new<M>(map: &'map M) -> Result<Self>
+    where
+        M: MapCore + ?Sized,
+    {
+        let value_size = if map.map_type().is_percpu() {
+            percpu_buffer_size(map)?
+        } else {
+            map.value_size() as usize
+        };
+
+        let map_fd = map.as_fd();
+        let cursor = MapCursor::new(map_fd, map.map_type(), map.key_size(), map.value_size());
+        Ok(Self {
+            map_fd,
+            value_size,
+            cursor,
+        })
+    }
+}
+
+impl MapEntryIter<'_> {
+    /// Retrieve the next entry, reporting failures to do so.
+    ///
+    /// Iteration should stop after an error.
+    pub fn try_next(&mut self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        loop {
+            let Some((key, value)) = self.cursor.next(true)? else {
+                break Ok(None)
+            };
+            if let Some(value) = value {
+                break Ok(Some((key, value)))
+            }
+
+            let mut value = vec![0; self.value_size];
+            let ret = unsafe {
+                libbpf_sys::bpf_map_lookup_elem(
+                    self.map_fd.as_raw_fd(),
+                    key.as_ptr() as *const c_void,
+                    value.as_mut_ptr() as *mut c_void,
+                )
+            };
+            // The entry may have been removed since we retrieved its key,
+            // in which case we just skip it.
+            if ret == -libc::ENOENT {
+                continue
+            }
+            let () = util::parse_ret(ret)?;
+            break Ok(Some((key, value)))
+        }
+    }
+}
+
+impl Iterator for MapEntryIter<'_> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().ok().flatten()
+    }
+}
+
+/// An iterator removing the entries of a BPF map, as returned by
+/// [`MapCore::lookup_and_delete_batch`].
+///
+/// Entries are retrieved and deleted in batches. Once a batch got
+/// retrieved, its entries are reported even if retrieving a later batch
+/// fails.
+#[derive(Debug)]
+pub struct MapDrainIter<'map> {
+    cursor: BatchCursor<'map>,
+    failed: bool,
+}
+
+impl Iterator for MapDrainIter<'_> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None
+        }
+
+        match self.cursor.next_entry() {
+            Ok(entry) => entry.map(|idx| {
+                let key = self.cursor.key(idx).to_vec();
+                let value = self.cursor.value(idx).to_vec();
+                Ok((key, value))
+            }),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// An entry as reported by [`MapCore::par_dump`].
+type DumpEntry = Result<(Vec<u8>, Vec<u8>)>;
+
+/// A batch of entries retrieved on behalf of [`MapCore::par_dump`], yet
+/// to be split into individual entries.
+#[derive(Debug)]
+struct DumpBatch {
+    keys: Vec<u8>,
+    values: Vec<u8>,
+    count: usize,
+}
+
+/// Retrieve the entries of the map referenced by `map_fd` batch by batch,
+/// handing each batch to `batches`.
+fn fetch_batches(
+    map_fd: BorrowedFd<'_>,
+    key_size: usize,
+    value_size: usize,
+    batches: &SyncSender<DumpBatch>,
+) -> Result<()> {
+    let mut cursor = BatchCursor::new(map_fd, key_size, value_size);
+    while !cursor.exhausted {
+        let () = cursor.fetch()?;
+        let batch = DumpBatch {
+            keys: mem::take(&mut cursor.keys),
+            values: mem::take(&mut cursor.values),
+            count: cursor.count,
+        };
+        if batches.send(batch).is_err() {
+            // All workers are gone, because the receiver got dropped.
+            break
+        }
+    }
+    Ok(())
+}
+
+/// Split the batches received via `batches` into entries, sending them
+/// via `tx`.
+fn split_batches(
+    batches: &Mutex<Receiver<DumpBatch>>,
+    key_size: usize,
+    value_size: usize,
+    tx: &SyncSender<DumpEntry>,
+) {
+    loop {
+        // The lock is released before splitting the batch, so that other
+        // workers can pick up the following ones in the meantime.
+        let batch = batches.lock().unwrap().recv();
+        let Ok(batch) = batch else { break };
+
+        for idx in 0..batch.count {
+            let key = batch.keys[idx * key_size..][..key_size].to_vec();
+            let value = batch.values[idx * value_size..][..value_size].to_vec();
+            if tx.send(Ok((key, value))).is_err() {
+                // The receiver is gone and so there is no point in
+                // continuing.
+                return
+            }
+        }
+    }
+}
+
+fn par_dump<M>(map: &M, num_workers: usize) -> Result<Receiver<DumpEntry>>
+where
+    M: MapCore + ?Sized,
+{
+    if num_workers == 0 {
+        return Err(Error::with_io_error(
+            io::ErrorKind::InvalidInput,
+            "at least one worker is required to dump a map",
+        ));
+    }
+
+    let map_type = map.map_type();
+    let key_size = map.key_size() as usize;
+    let value_size = if map_type.is_percpu() {
+        percpu_buffer_size(map)?
+    } else {
+        map.value_size() as usize
+    };
+    // Check that the kernel actually supports batch lookups for the map.
+    let batched = map_type.supports_batch_lookup()
+        && BatchCursor::new(map.as_fd(), key_size, value_size)
+            .fetch()
+            .is_ok();
+
+    let (tx, rx) = mpsc::sync_channel(LOOKUP_BATCH_SIZE * num_workers);
+    let dup_fd = || {
+        map.as_fd()
+            .try_clone_to_owned()
+            .context("failed to duplicate map file descriptor")
+    };
+
+    if !batched {
+        let handle = MapHandle::from_fd(dup_fd()?)?;
+        let _thread = thread::Builder::new()
+            .name("bpf-map-dump".to_string())
+            .spawn(move || {
+                let mut iter = match handle.iter() {
+                    Ok(iter) => iter,
+                    Err(err) => {
+                        let _result = tx.send(Err(err));
+                        return
+                    }
+                };
+                loop {
+                    let entry = match iter.try_next() {
+                        Ok(Some(entry)) => Ok(entry),
+                        Ok(None) => break,
+                        Err(err) => Err(err),
+                    };
+                    let failed = entry.is_err();
+                    if tx.send(entry).is_err() || failed {
+                        break
+                    }
+                }
+            })
+            .context("failed to spawn map dump thread")?;
+        return Ok(rx)
+    }
+
+    // The format of the position the kernel continues a batch lookup
+    // from is not defined, so batches have to be retrieved one after the
+    // other. Only splitting them into entries is done in parallel.
+    let (batch_tx, batch_rx) = mpsc::sync_channel(num_workers);
+    let batch_rx = Arc::new(Mutex::new(batch_rx));
+    for idx in 0..num_workers {
+        let batch_rx = batch_rx.clone();
+        let tx = tx.clone();
+        let _thread = thread::Builder::new()
+            .name(format!("bpf-map-dump-{idx}"))
+            .spawn(move || split_batches(&batch_rx, key_size, value_size, &tx))
+            .context("failed to spawn map dump thread")?;
+    }
+
+    let fd = dup_fd()?;
+    let _thread = thread::Builder::new()
+        .name("bpf-map-dump".to_string())
+        .spawn(move || {
+            if let Err(err) = fetch_batches(fd.as_fd(), key_size, value_size, &batch_tx) {
+                let _result = tx.send(Err(err));
+            }
+        })
+        .context("failed to spawn map dump thread")?;
+    Ok(rx)
+}
+
 /// A convenience wrapper for [`bpf_map_info`][libbpf_sys::bpf_map_info]. It
 /// provides the ability to retrieve the details of a certain map.
 #[derive(Debug)]
@@ -1377,10 +2584,24 @@ mod tests {
             TaskStorage,
             BloomFilter,
             UserRingBuf,
+            CgrpStorage,
+            Arena,
             Unknown,
         ] {
             // check if discriminants match after a roundtrip conversion
             assert_eq!(discriminant(&t), discriminant(&MapType::from(t as u32)));
         }
     }
+
+    /// Check that `MapHandle` is `Send` and `Sync`.
+    #[test]
+    fn map_handle_is_send_and_sync() {
+        fn test<T>()
+        where
+            T: Send + Sync,
+        {
+        }
+
+        test::<MapHandle>();
+    }
 }
@@ -0,0 +1,85 @@
+use std::io;
+
+use crate::util;
+use crate::ErrorExt as _;
+use crate::ProgramType;
+use crate::Result;
+
+/// The mechanism the kernel uses to account for memory consumed by BPF
+/// maps and programs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemlockAccounting {
+    /// Memory is charged against `RLIMIT_MEMLOCK` of the process.
+    ///
+    /// This is the behavior of kernels prior to 5.11.
+    Rlimit,
+    /// Memory is charged to the memory cgroup of the process and
+    /// `RLIMIT_MEMLOCK` is ignored.
+    Memcg,
+}
+
+/// Detect how the running kernel accounts for memory used by BPF
+/// objects.
+///
+/// memcg based accounting was introduced in Linux 5.11, in the same
+/// release as the `bpf_ktime_get_coarse_ns` helper. Similar to what
+/// `libbpf` does internally, we infer the accounting mode from the
+/// availability of said helper.
+///
+/// Make sure the process has required set of CAP_* permissions (or runs as
+/// root) when performing the check.
+pub fn memlock_accounting() -> Result<MemlockAccounting> {
+    let supported = ProgramType::SocketFilter
+        .is_helper_supported(libbpf_sys::BPF_FUNC_ktime_get_coarse_ns)
+        .context("failed to probe for memcg based BPF memory accounting")?;
+
+    if supported {
+        Ok(MemlockAccounting::Memcg)
+    } else {
+        Ok(MemlockAccounting::Rlimit)
+    }
+}
+
+/// Lift `RLIMIT_MEMLOCK` of the current process, if the kernel still
+/// accounts BPF memory against it.
+///
+/// On kernels using memcg based accounting the limit is left untouched,
+/// because it has no bearing on BPF object creation. The detected
+/// accounting mode is returned.
+pub fn bump_memlock_rlimit() -> Result<MemlockAccounting> {
+    let accounting = memlock_accounting()?;
+    if accounting == MemlockAccounting::Rlimit {
+        let rlimit = libc::rlimit {
+            rlim_cur: libc::RLIM_INFINITY,
+            rlim_max: libc::RLIM_INFINITY,
+        };
+
+        // SAFETY: `rlimit` is a valid pointer to an initialized object.
+        let rc = unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlimit) };
+        if rc != 0 {
+            let err = io::Error::last_os_error().context("failed to bump RLIMIT_MEMLOCK");
+            return Err(err);
+        }
+    }
+    Ok(accounting)
+}
+
+/// Set the value `RLIMIT_MEMLOCK` is raised to by libbpf, if it finds
+/// that the kernel still accounts BPF memory against it.
+///
+/// A value of `0` disables the implicit bump altogether, which is useful
+/// for applications managing the limit themselves (e.g., by means of
+/// [`bump_memlock_rlimit`]).
+///
+/// libbpf 1.x always operates with all of its former "strict mode"
+/// behaviors (`LIBBPF_STRICT_ALL`) enabled and no longer supports opting
+/// out of any of them; this bump is the only one that remains
+/// configurable.
+///
+/// The setting has to be made before any BPF object is created; libbpf
+/// rejects it with `EBUSY` otherwise.
+pub fn set_memlock_rlimit(bytes: usize) -> Result<()> {
+    // SAFETY: `libbpf_set_memlock_rlim` is always safe to call.
+    let ret = unsafe { libbpf_sys::libbpf_set_memlock_rlim(bytes as _) };
+    util::parse_ret(ret).context("failed to set libbpf RLIMIT_MEMLOCK value")
+}
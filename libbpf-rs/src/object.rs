@@ -1,22 +1,46 @@
 use core::ffi::c_void;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ffi::c_char;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::io;
 use std::mem;
+use std::os::fd::AsFd as _;
+use std::os::fd::AsRawFd as _;
 use std::os::unix::ffi::OsStrExt as _;
 use std::path::Path;
+use std::path::PathBuf;
 use std::ptr;
 use std::ptr::addr_of;
 use std::ptr::NonNull;
+use std::slice;
+use std::sync::Mutex;
 
 use crate::map::map_fd;
+use crate::offload;
+use crate::print::observe_print;
+use crate::privilege;
+use crate::privilege::Capability;
+use crate::program::has_auto_attach_target;
+use crate::progress::Tracker;
 use crate::set_print;
+use crate::trace;
 use crate::util;
 use crate::util::validate_bpf_ret;
+use crate::validate_object;
 use crate::Btf;
+use crate::Error;
 use crate::ErrorExt as _;
+use crate::Links;
+use crate::LoadItem;
+use crate::LoadProgress;
 use crate::Map;
+use crate::MapCore as _;
 use crate::MapMut;
+use crate::ObjectLimits;
 use crate::OpenMap;
 use crate::OpenMapMut;
 use crate::OpenProgram;
@@ -25,6 +49,7 @@ use crate::PrintLevel;
 use crate::Program;
 use crate::ProgramMut;
 use crate::Result;
+use crate::VerifierStats;
 
 
 /// An iterator over the maps in a BPF object.
@@ -101,11 +126,125 @@ pub trait AsRawLibbpf {
     fn as_libbpf_object(&self) -> NonNull<Self::LibbpfType>;
 }
 
+/// The minimum size of a kernel log buffer the kernel accepts.
+const KERNEL_LOG_SIZE_MIN: usize = 128;
+/// The maximum size of a verifier log buffer the kernel accepts.
+const VERIFIER_LOG_SIZE_MAX: usize = (u32::MAX >> 2) as usize;
+/// The size of the buffer the verifier log of a program that failed to
+/// load is first retrieved with.
+const VERIFIER_LOG_SIZE_INITIAL: usize = 1 << 20;
+/// The log level at which the kernel reports nothing but statistics about
+/// the verification of a program.
+const BPF_LOG_STATS: u32 = 4;
+/// The size of the buffer statistics about the verification of a program
+/// are retrieved with.
+const STATS_LOG_SIZE: usize = 64 << 10;
+
+/// Retrieve the contents of a kernel log buffer, which the kernel NUL
+/// terminates.
+fn kernel_log_str(buf: &[u8]) -> Cow<'_, str> {
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len])
+}
+
+/// Use `buf` as the verifier log buffer of `prog`.
+fn set_log_buf(prog: &mut OpenProgramMut<'_>, buf: &mut [u8]) -> Result<()> {
+    let ret = unsafe {
+        libbpf_sys::bpf_program__set_log_buf(
+            prog.as_libbpf_object().as_ptr(),
+            buf.as_mut_ptr().cast(),
+            buf.len() as libbpf_sys::size_t,
+        )
+    };
+    util::parse_ret(ret)
+}
+
+/// Apply the configuration of the maps and programs of `from` to those of
+/// `to`, another copy of the same object, as far as libbpf allows for
+/// retrieving it.
+fn copy_settings(from: &OpenObject, to: &mut OpenObject) -> Result<()> {
+    for (map, mut copy) in from.maps().zip(to.maps_mut()) {
+        let () = copy.set_type(map.map_type())?;
+        let () = copy.set_key_size(map.key_size())?;
+        if copy.value_size() != map.value_size() {
+            let () = copy.set_value_size(map.value_size())?;
+        }
+        let () = copy.set_max_entries(map.max_entries())?;
+        if map.map_flags() & libbpf_sys::BPF_F_NUMA_NODE != 0 {
+            let () = copy.set_numa_node(map.numa_node())?;
+        }
+        let () = copy.set_map_flags(map.map_flags())?;
+        let () = copy.set_map_extra(map.map_extra())?;
+        let () = copy.set_map_ifindex(map.ifindex())?;
+        let autocreate =
+            unsafe { libbpf_sys::bpf_map__autocreate(map.as_libbpf_object().as_ptr()) };
+        let () = copy.set_autocreate(autocreate)?;
+        // The copy must neither reuse nor create pins.
+        let () = copy.clear_pin_path()?;
+        if let Some(value) = map.initial_value() {
+            if copy.initial_value() != Some(value) {
+                // libbpf refuses to set the value of the `.kconfig` map,
+                // which it populates itself.
+                let _result = copy.set_initial_value(value);
+            }
+        }
+    }
+
+    for (prog, mut copy) in from.progs().zip(to.progs_mut()) {
+        let () = copy.set_prog_type(prog.prog_type())?;
+        let () = copy.set_attach_type(prog.attach_type())?;
+        let () = copy.set_flags(prog.flags());
+        let () = copy.set_autoload(prog.autoload());
+        let () = copy.set_log_level(prog.log_level())?;
+    }
+    Ok(())
+}
+
+/// Find the item called `name`, reporting the names of all items of the
+/// given kind if there is none.
+fn find_by_name<T, I, F>(what: &'static str, name: &OsStr, items: I, item_name: F) -> Result<T>
+where
+    I: Iterator<Item = T>,
+    F: Fn(&T) -> &OsStr,
+{
+    let mut available = Vec::new();
+    for item in items {
+        let item_name = item_name(&item);
+        if item_name == name {
+            return Ok(item)
+        }
+        let () = available.push(item_name.to_string_lossy().into_owned());
+    }
+    Err(Error::with_name_not_found(what, name, available))
+}
+
+/// Store `value` in `slot`, returning a pointer to its contents for use in
+/// [`libbpf_sys::bpf_object_open_opts`].
+///
+/// The contents of a `CString` live on the heap, so the pointer stays valid
+/// until `slot` is modified again, even if its owner gets moved.
+fn store_cstring(slot: &mut Option<CString>, value: CString) -> *const c_char {
+    slot.insert(value).as_ptr()
+}
+
 /// Builder for creating an [`OpenObject`]. Typically the entry point into libbpf-rs.
+///
+/// Strings are converted and validated once, when set, and the resulting
+/// open options are reused for every object opened with the builder.
 #[derive(Debug)]
 pub struct ObjectBuilder {
+    // The string options referenced by `opts`, which must only be set
+    // via `store_cstring`.
     name: Option<CString>,
     pin_root_path: Option<CString>,
+    kconfig: Option<CString>,
+    btf_custom_path: Option<CString>,
+    bpf_token_path: Option<CString>,
+    /// The size of the kernel log buffer to allocate for each opened
+    /// object, or zero if none.
+    kernel_log_size: usize,
+    /// The limits to validate objects opened from memory against, if any.
+    limits: Option<ObjectLimits>,
 
     opts: libbpf_sys::bpf_object_open_opts,
 }
@@ -127,16 +266,51 @@ impl Default for ObjectBuilder {
         Self {
             name: None,
             pin_root_path: None,
+            kconfig: None,
+            btf_custom_path: None,
+            bpf_token_path: None,
+            kernel_log_size: 0,
+            limits: None,
             opts,
         }
     }
 }
 
+impl Clone for ObjectBuilder {
+    fn clone(&self) -> Self {
+        let mut builder = Self {
+            kernel_log_size: self.kernel_log_size,
+            limits: self.limits.clone(),
+            opts: self.opts,
+            ..Self::default()
+        };
+        // The copied options still reference our strings and have to be
+        // pointed at the clone's own copies instead.
+        if let Some(name) = &self.name {
+            builder.opts.object_name = store_cstring(&mut builder.name, name.clone());
+        }
+        if let Some(path) = &self.pin_root_path {
+            builder.opts.pin_root_path = store_cstring(&mut builder.pin_root_path, path.clone());
+        }
+        if let Some(kconfig) = &self.kconfig {
+            builder.opts.kconfig = store_cstring(&mut builder.kconfig, kconfig.clone());
+        }
+        if let Some(path) = &self.btf_custom_path {
+            builder.opts.btf_custom_path =
+                store_cstring(&mut builder.btf_custom_path, path.clone());
+        }
+        if let Some(path) = &self.bpf_token_path {
+            builder.opts.bpf_token_path = store_cstring(&mut builder.bpf_token_path, path.clone());
+        }
+        builder
+    }
+}
+
 impl ObjectBuilder {
     /// Override the generated name that would have been inferred from the constructor.
     pub fn name<T: AsRef<str>>(&mut self, name: T) -> Result<&mut Self> {
-        self.name = Some(util::str_to_cstring(name.as_ref())?);
-        self.opts.object_name = self.name.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+        let name = util::str_to_cstring(name.as_ref())?;
+        self.opts.object_name = store_cstring(&mut self.name, name);
         Ok(self)
     }
 
@@ -144,11 +318,86 @@ impl ObjectBuilder {
     ///
     /// By default, this is NULL which bpf translates to /sys/fs/bpf
     pub fn pin_root_path<T: AsRef<Path>>(&mut self, path: T) -> Result<&mut Self> {
-        self.pin_root_path = Some(util::path_to_cstring(path)?);
-        self.opts.pin_root_path = self
-            .pin_root_path
-            .as_ref()
-            .map_or(ptr::null(), |p| p.as_ptr());
+        let path = util::path_to_cstring(path)?;
+        self.opts.pin_root_path = store_cstring(&mut self.pin_root_path, path);
+        Ok(self)
+    }
+
+    /// Provide the contents of the kernel configuration to use for
+    /// resolving `__kconfig` externs, in the format of
+    /// `/proc/config.gz` (e.g., `CONFIG_BPF_SYSCALL=y`).
+    ///
+    /// The provided values take precedence over those of the running
+    /// kernel, which libbpf reads otherwise.
+    pub fn kconfig<T: AsRef<str>>(&mut self, kconfig: T) -> Result<&mut Self> {
+        let kconfig = util::str_to_cstring(kconfig.as_ref())?;
+        self.opts.kconfig = store_cstring(&mut self.kconfig, kconfig);
+        Ok(self)
+    }
+
+    /// Set the path of a BTF file to use for CO-RE relocations, instead
+    /// of the running kernel's (`/sys/kernel/btf/vmlinux`).
+    ///
+    /// This is useful for kernels not exposing their BTF, for which it
+    /// has been generated separately.
+    pub fn btf_custom_path<T: AsRef<Path>>(&mut self, path: T) -> Result<&mut Self> {
+        let path = util::path_to_cstring(path)?;
+        self.opts.btf_custom_path = store_cstring(&mut self.btf_custom_path, path);
+        Ok(self)
+    }
+
+    /// Set the log level the kernel uses when loading the object's BTF
+    /// and those programs without a [log level of their
+    /// own][OpenProgramMut::set_log_level].
+    ///
+    /// Unless [captured][Self::kernel_log_size], the log is emitted via
+    /// libbpf's [print callback][set_print].
+    pub fn kernel_log_level(&mut self, level: u32) -> &mut Self {
+        self.opts.kernel_log_level = level;
+        self
+    }
+
+    /// Capture up to `size` bytes of the log the kernel produces while
+    /// loading objects opened with this builder, for retrieval via
+    /// [`Object::kernel_log`].
+    ///
+    /// A buffer is allocated for each object opened with
+    /// [`open_file`][Self::open_file] and [`open_memory`][Self::open_memory]
+    /// and owned by it; options retrieved via
+    /// [`as_libbpf_object`][AsRawLibbpf::as_libbpf_object] don't
+    /// reference one. libbpf reuses the buffer for every BTF and program
+    /// load, so it holds the log of the last one. If loading fails, the
+    /// log becomes part of the error. Unless a [log
+    /// level][Self::kernel_log_level] is set the kernel only produces a
+    /// log on failure. A size of zero disables capturing; otherwise the
+    /// kernel requires a size of at least 128 bytes and libbpf at most
+    /// [`u32::MAX`].
+    pub fn kernel_log_size(&mut self, size: usize) -> Result<&mut Self> {
+        if size != 0 && !(KERNEL_LOG_SIZE_MIN..=u32::MAX as usize).contains(&size) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "kernel log size {size} is not in range {KERNEL_LOG_SIZE_MIN}..={}",
+                    u32::MAX
+                ),
+            ))
+        }
+        self.kernel_log_size = size;
+        Ok(self)
+    }
+
+    /// Set the path of a BPF file system instance from which to derive a
+    /// BPF token.
+    ///
+    /// The token is used for all BPF operations performed on behalf of the
+    /// object (map creation, program loading, BTF upload, feature probing),
+    /// allowing for loading in a user namespace to which BPF permissions
+    /// were delegated via a bpffs mount. Setting an empty path disables
+    /// implicit token creation, which otherwise is attempted using the
+    /// `LIBBPF_BPF_TOKEN_PATH` environment variable or `/sys/fs/bpf`.
+    pub fn bpf_token_path<T: AsRef<Path>>(&mut self, path: T) -> Result<&mut Self> {
+        let path = util::path_to_cstring(path)?;
+        self.opts.bpf_token_path = store_cstring(&mut self.bpf_token_path, path);
         Ok(self)
     }
 
@@ -171,34 +420,88 @@ impl ObjectBuilder {
         self
     }
 
-    /// Open an object using the provided path on the file system.
-    pub fn open_file<P: AsRef<Path>>(&mut self, path: P) -> Result<OpenObject> {
-        let path = path.as_ref();
-        let path_c = util::path_to_cstring(path)?;
-        let path_ptr = path_c.as_ptr();
-        let opts_ptr = self.as_libbpf_object().as_ptr();
+    /// Validate objects opened from memory against `limits` (see
+    /// [`validate_object`]) before handing them to libbpf.
+    ///
+    /// Validation is meant for objects from untrusted sources, which it
+    /// rejects with a precise error if they are malformed, instead of
+    /// exposing libbpf's parser to them.
+    pub fn validate(&mut self, limits: ObjectLimits) -> &mut Self {
+        self.limits = Some(limits);
+        self
+    }
 
-        let ptr = unsafe { libbpf_sys::bpf_object__open_file(path_ptr, opts_ptr) };
-        let ptr = validate_bpf_ret(ptr)
-            .with_context(|| format!("failed to open object from `{}`", path.display()))?;
+    /// Open an object from `source`, along with a kernel log buffer if
+    /// one was requested.
+    fn open_source(&self, source: &ObjectSource) -> Result<OpenObject> {
+        let mut opts = self.opts;
+        let mut kernel_log = None;
+        if self.kernel_log_size > 0 {
+            let log = kernel_log.insert(vec![0; self.kernel_log_size].into_boxed_slice());
+            opts.kernel_log_buf = log.as_mut_ptr().cast();
+            opts.kernel_log_size = log.len() as _;
+        }
+
+        let ptr = match source {
+            ObjectSource::File(path) => {
+                let path_c = util::path_to_cstring(path)?;
+                let path_ptr = path_c.as_ptr();
+                let ptr = unsafe { libbpf_sys::bpf_object__open_file(path_ptr, &opts) };
+                validate_bpf_ret(ptr)
+                    .with_context(|| format!("failed to open object from `{}`", path.display()))?
+            }
+            ObjectSource::Memory(mem) => {
+                let ptr = unsafe {
+                    libbpf_sys::bpf_object__open_mem(
+                        mem.as_ptr() as *const c_void,
+                        mem.len() as libbpf_sys::size_t,
+                        &opts,
+                    )
+                };
+                validate_bpf_ret(ptr).context("failed to open object from memory")?
+            }
+        };
 
         let obj = unsafe { OpenObject::from_ptr(ptr) };
+        if kernel_log.is_some() {
+            let () = with_state(obj.ptr, |state| state.kernel_log = kernel_log);
+        }
         Ok(obj)
     }
 
+    /// Open an object from `source`, remembering where it came from for
+    /// retrieving the verifier log should loading it fail.
+    fn open_origin(&self, source: ObjectSource) -> Result<OpenObject> {
+        let obj = self.open_source(&source)?;
+        let mut builder = self.clone();
+        builder.kernel_log_size = 0;
+        let origin = ObjectOrigin { builder, source };
+        let () = with_state(obj.ptr, |state| state.origin = Some(origin));
+        Ok(obj)
+    }
+
+    /// Open an object using the provided path on the file system.
+    pub fn open_file<P: AsRef<Path>>(&mut self, path: P) -> Result<OpenObject> {
+        let path = path.as_ref();
+        trace::instrument("open", &path.display(), || {
+            self.open_origin(ObjectSource::File(path.to_path_buf()))
+        })
+    }
+
     /// Open an object from memory.
+    ///
+    /// If [validation][Self::validate] is enabled, the object is
+    /// validated first. The object's contents are copied, to be able to
+    /// retrieve the verifier log should loading it fail (see
+    /// [`OpenObject::load`]).
     pub fn open_memory(&mut self, mem: &[u8]) -> Result<OpenObject> {
-        let opts_ptr = self.as_libbpf_object().as_ptr();
-        let ptr = unsafe {
-            libbpf_sys::bpf_object__open_mem(
-                mem.as_ptr() as *const c_void,
-                mem.len() as libbpf_sys::size_t,
-                opts_ptr,
-            )
-        };
-        let ptr = validate_bpf_ret(ptr).context("failed to open object from memory")?;
-        let obj = unsafe { OpenObject::from_ptr(ptr) };
-        Ok(obj)
+        trace::instrument("open", &"<memory>", || {
+            if let Some(limits) = &self.limits {
+                let () = validate_object(mem, limits).context("object failed validation")?;
+            }
+
+            self.open_origin(ObjectSource::Memory(mem.into()))
+        })
     }
 }
 
@@ -213,9 +516,65 @@ impl AsRawLibbpf for ObjectBuilder {
 }
 
 
+/// The source an object was opened from.
+#[derive(Debug)]
+enum ObjectSource {
+    File(PathBuf),
+    Memory(Box<[u8]>),
+}
+
+/// The means to open another copy of an object.
+#[derive(Debug)]
+struct ObjectOrigin {
+    builder: ObjectBuilder,
+    source: ObjectSource,
+}
+
+// SAFETY: The options of the builder only reference strings owned by the
+//         builder itself.
+unsafe impl Send for ObjectOrigin {}
+
+/// State associated with a `bpf_object`.
+///
+/// [`OpenObject`] and [`Object`] are transparent wrappers around the
+/// object's pointer, as generated skeletons rely on, so state is kept
+/// here, keyed by the object's address, until the object is closed.
+#[derive(Debug, Default)]
+struct ObjectState {
+    /// The buffer libbpf writes the kernel log to, if one was requested
+    /// via [`ObjectBuilder::kernel_log_size`].
+    kernel_log: Option<Box<[u8]>>,
+    /// Where the object was opened from, if known and not yet loaded.
+    origin: Option<ObjectOrigin>,
+    /// The verifier log buffers set on the object's programs.
+    log_bufs: Vec<Box<[u8]>>,
+    /// Paths of pinned maps that were reused during load.
+    reused_pins: Vec<OsString>,
+}
+
+static OBJECT_STATES: Mutex<BTreeMap<usize, ObjectState>> = Mutex::new(BTreeMap::new());
+
+/// Run `f` on the state of the object at `ptr`.
+fn with_state<F, R>(ptr: NonNull<libbpf_sys::bpf_object>, f: F) -> R
+where
+    F: FnOnce(&mut ObjectState) -> R,
+{
+    let mut states = OBJECT_STATES.lock().unwrap();
+    f(states.entry(ptr.as_ptr() as usize).or_default())
+}
+
+/// Remove the state of the object at `ptr`.
+fn take_state(ptr: NonNull<libbpf_sys::bpf_object>) -> ObjectState {
+    let mut states = OBJECT_STATES.lock().unwrap();
+    states.remove(&(ptr.as_ptr() as usize)).unwrap_or_default()
+}
+
+
 /// Represents an opened (but not yet loaded) BPF object file.
 ///
 /// Use this object to access [`OpenMap`]s and [`OpenProgram`]s.
+///
+/// An `OpenObject` is [`Send`], but not [`Sync`].
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct OpenObject {
@@ -238,16 +597,30 @@ impl OpenObject {
     }
 
     /// Takes underlying `libbpf_sys::bpf_object` pointer.
+    ///
+    /// A kernel log buffer the object references is leaked, as the
+    /// object may still write to it.
     pub fn take_ptr(mut self) -> NonNull<libbpf_sys::bpf_object> {
         let ptr = {
             let Self { ptr } = &mut self;
             *ptr
         };
+        let ObjectState { kernel_log, .. } = take_state(ptr);
+        let () = mem::forget(kernel_log);
         // avoid double free of self.ptr
         mem::forget(self);
         ptr
     }
 
+    /// Convert the object, which got loaded, into an [`Object`], keeping
+    /// its state.
+    fn into_loaded(self) -> Object {
+        let ptr = self.ptr;
+        mem::forget(self);
+        // SAFETY: The object got loaded.
+        unsafe { Object::from_ptr(ptr) }
+    }
+
     /// Retrieve the object's name.
     pub fn name(&self) -> Option<&OsStr> {
         // SAFETY: We ensured `ptr` is valid during construction.
@@ -285,14 +658,220 @@ impl OpenObject {
             .map(|mut ptr| unsafe { OpenProgramMut::new_mut(ptr.as_mut()) })
     }
 
+    /// Retrieve the BPF map with the given name.
+    ///
+    /// If no such map exists, the returned error is of kind
+    /// [`NotFound`][crate::ErrorKind::NotFound] and lists the names of
+    /// all maps in the object.
+    pub fn map<N: AsRef<OsStr>>(&self, name: N) -> Result<OpenMap<'_>> {
+        find_by_name("map", name.as_ref(), self.maps(), |map| map.name())
+    }
+
+    /// Retrieve the BPF map with the given name.
+    ///
+    /// See [`OpenObject::map`] for details.
+    pub fn map_mut<N: AsRef<OsStr>>(&mut self, name: N) -> Result<OpenMapMut<'_>> {
+        find_by_name("map", name.as_ref(), self.maps_mut(), |map| map.name())
+    }
+
+    /// Retrieve the BPF program with the given name.
+    ///
+    /// If no such program exists, the returned error is of kind
+    /// [`NotFound`][crate::ErrorKind::NotFound] and lists the names of
+    /// all programs in the object.
+    pub fn prog<N: AsRef<OsStr>>(&self, name: N) -> Result<OpenProgram<'_>> {
+        find_by_name("program", name.as_ref(), self.progs(), |prog| prog.name())
+    }
+
+    /// Retrieve the BPF program with the given name.
+    ///
+    /// See [`OpenObject::prog`] for details.
+    pub fn prog_mut<N: AsRef<OsStr>>(&mut self, name: N) -> Result<OpenProgramMut<'_>> {
+        find_by_name("program", name.as_ref(), self.progs_mut(), |prog| {
+            prog.name()
+        })
+    }
+
     /// Load the maps and programs contained in this BPF object into the system.
+    ///
+    /// If a program fails to load, the verifier log is included in the
+    /// returned error. As libbpf loads an object only once, the log is
+    /// retrieved by loading another copy of the object, opened the same
+    /// way and with the configuration of its maps and programs carried
+    /// over, into a buffer of our own. Should the log not fit, the
+    /// buffer is doubled in size and the copy loaded again, until the
+    /// complete log fits or the kernel's limit is reached. This is only
+    /// possible for objects opened via [`ObjectBuilder`].
+    ///
+    /// For programs with a [log level][OpenProgramMut::set_log_level] of
+    /// exactly `4` (`BPF_LOG_STATS`), the statistics contained in the log
+    /// of a successful load are made available via
+    /// [`Object::verifier_stats`].
+    ///
+    /// Failures to load an object containing maps bound to a network
+    /// device for [offload][crate::offload] are annotated with the
+    /// device in question.
     pub fn load(self) -> Result<Object> {
-        let ret = unsafe { libbpf_sys::bpf_object__load(self.ptr.as_ptr()) };
-        let () = util::parse_ret(ret)?;
+        self.load_impl(None)
+    }
+
+    /// Load the object like [`load`][Self::load], reporting the creation
+    /// of each map and the loading of each program to `progress` as it
+    /// happens.
+    ///
+    /// Progress is derived from the messages libbpf emits. libbpf does
+    /// not report the successful load of a program without a [log
+    /// level][OpenProgramMut::set_log_level], so such a program is
+    /// considered loaded once libbpf reports on a later program, or once
+    /// the object as a whole loaded successfully, without having
+    /// reported a failure or a skip for it before.
+    ///
+    /// If the load fails for a reason not attributable to a specific
+    /// map or program, the last [`Started`][LoadProgress::Started] event
+    /// is not followed by a corresponding
+    /// [`Finished`][LoadProgress::Finished] one. A panic raised by
+    /// `progress` is resumed once libbpf is done.
+    pub fn load_with_progress<F>(self, mut progress: F) -> Result<Object>
+    where
+        F: FnMut(&LoadProgress<'_>),
+    {
+        self.load_impl(Some(&mut progress))
+    }
 
-        let obj = unsafe { Object::from_ptr(self.take_ptr()) };
+    /// Retrieve the complete verifier log of the program that caused
+    /// loading the object to fail, by loading another copy of it.
+    fn verifier_log(&self) -> Option<String> {
+        let origin = with_state(self.ptr, |state| state.origin.take())?;
+        let mut size = VERIFIER_LOG_SIZE_INITIAL;
+        loop {
+            let mut log = vec![0; size].into_boxed_slice();
+            let mut copy = origin.builder.open_source(&origin.source).ok()?;
+            let () = copy_settings(self, &mut copy).ok()?;
+            for mut prog in copy.progs_mut() {
+                let () = set_log_buf(&mut prog, &mut log).ok()?;
+            }
+            let ret = unsafe { libbpf_sys::bpf_object__load(copy.ptr.as_ptr()) };
+            let () = drop(copy);
+            if ret == 0 {
+                return None
+            }
 
-        Ok(obj)
+            // The kernel fills the buffer completely if the log got
+            // truncated.
+            let len = log.iter().position(|b| *b == 0).unwrap_or(log.len());
+            if len + 1 < log.len() || size == VERIFIER_LOG_SIZE_MAX {
+                let log = kernel_log_str(&log);
+                return (!log.is_empty()).then(|| log.into_owned())
+            }
+            size = size.saturating_mul(2).min(VERIFIER_LOG_SIZE_MAX);
+        }
+    }
+
+    fn load_impl(mut self, progress: Option<&mut dyn FnMut(&LoadProgress<'_>)>) -> Result<Object> {
+        let name = self
+            .name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        trace::instrument("load", &name, || {
+            let mut tracker = None;
+            if let Some(progress) = progress {
+                // libbpf creates all maps before loading any programs.
+                let maps = self
+                    .maps()
+                    .map(|map| (LoadItem::Map, map.name().to_os_string()));
+                let progs = self
+                    .progs()
+                    .map(|prog| (LoadItem::Program, prog.name().to_os_string()));
+                tracker = Some(Tracker::new(maps.chain(progs), progress));
+            }
+
+            // Programs logging nothing but statistics get a buffer to log
+            // to, from which `Object::verifier_stats` reports them.
+            let mut log_bufs = Vec::new();
+            for mut prog in self.progs_mut() {
+                let mut size = 0;
+                let buf = unsafe {
+                    libbpf_sys::bpf_program__log_buf(prog.as_libbpf_object().as_ptr(), &mut size)
+                };
+                if prog.log_level() != BPF_LOG_STATS || !prog.autoload() || !buf.is_null() {
+                    continue
+                }
+
+                let mut buf = vec![0; STATS_LOG_SIZE].into_boxed_slice();
+                let () = set_log_buf(&mut prog, &mut buf)?;
+                let () = log_bufs.push(buf);
+            }
+            let () = with_state(self.ptr, |state| state.log_bufs = log_bufs);
+
+            // Maps get pinned during load, so remember which pins exist
+            // beforehand.
+            let pins = self
+                .maps()
+                .filter_map(|map| map.pin_path().map(OsStr::to_os_string))
+                .filter(|path| Path::new(path).exists())
+                .collect::<Vec<_>>();
+
+            let load = || unsafe { libbpf_sys::bpf_object__load(self.ptr.as_ptr()) };
+            let ret = match &mut tracker {
+                Some(tracker) => {
+                    let () = tracker.start_next();
+                    observe_print(&mut |level, msg: &str| tracker.message(level, msg), load)
+                }
+                None => load(),
+            };
+            if let Some(tracker) = tracker {
+                let () = tracker.conclude(ret == 0);
+            }
+            let () = util::parse_ret(ret).map_err(|err| {
+                let err = match self.verifier_log() {
+                    Some(log) => err.context(format!("verifier log:\n{log}")),
+                    None => err,
+                };
+                let kernel_log = with_state(self.ptr, |state| {
+                    state
+                        .kernel_log
+                        .as_deref()
+                        .map(|log| kernel_log_str(log).into_owned())
+                });
+                let err = match kernel_log {
+                    Some(log) if !log.is_empty() => err.context(format!("kernel log:\n{log}")),
+                    _ => err,
+                };
+
+                let offloaded = self
+                    .maps()
+                    .filter(|map| map.ifindex() != 0)
+                    .map(|map| (map.name().to_string_lossy().into_owned(), map.ifindex()))
+                    .collect::<Vec<_>>();
+                let err = offload::diagnose(err, &offloaded);
+
+                // Creating maps requires `CAP_BPF`, irrespective of which
+                // programs are contained in the object.
+                let mut required = vec![Capability::Bpf];
+                for prog in self.progs() {
+                    for cap in privilege::prog_type_capabilities(prog.prog_type()) {
+                        if !required.contains(cap) {
+                            let () = required.push(*cap);
+                        }
+                    }
+                }
+                privilege::diagnose(err, &required)
+            })?;
+
+            let obj = self.into_loaded();
+            let reused_pins = obj
+                .maps()
+                .filter(|map| map.is_pinned())
+                .filter_map(|map| map.get_pin_path().map(OsStr::to_os_string))
+                .filter(|path| pins.contains(path))
+                .collect::<Vec<_>>();
+            let () = with_state(obj.ptr, |state| {
+                state.origin = None;
+                state.reused_pins = reused_pins;
+            });
+            Ok(obj)
+        })
     }
 }
 
@@ -305,8 +884,13 @@ impl AsRawLibbpf for OpenObject {
     }
 }
 
+// SAFETY: `bpf_object` objects can safely be sent to a different thread.
+unsafe impl Send for OpenObject {}
+
 impl Drop for OpenObject {
     fn drop(&mut self) {
+        // The buffers the object references must outlive it.
+        let _state = take_state(self.ptr);
         // `self.ptr` may be null if `load()` was called. This is ok: libbpf noops
         unsafe {
             libbpf_sys::bpf_object__close(self.ptr.as_ptr());
@@ -323,12 +907,27 @@ impl Drop for OpenObject {
 ///
 /// Note that this is an explanation of the motivation -- Rust's lifetime system should already be
 /// enforcing this invariant.
+///
+/// An `Object` is [`Send`], but not [`Sync`], because mutable access to its
+/// programs can be gained through a shared reference
+/// (see [`Object::progs_mut`]):
+/// ```compile_fail
+/// fn test<T: Sync>() {}
+/// test::<libbpf_rs::Object>();
+/// ```
+///
+/// Neither are the [`Map`]s and [`Program`]s borrowed from it. Use
+/// [`MapHandle`][crate::MapHandle], which is [`Send`] and [`Sync`], for
+/// sharing access to a map between threads.
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct Object {
     ptr: NonNull<libbpf_sys::bpf_object>,
 }
 
+// Generated skeletons convert an `OpenObject` into an `Object` in place.
+const _: () = assert!(mem::size_of::<OpenObject>() == mem::size_of::<Object>());
+
 impl Object {
     /// Takes ownership from pointer.
     ///
@@ -385,6 +984,159 @@ impl Object {
         ProgIter::new(unsafe { self.ptr.as_ref() })
             .map(|mut ptr| unsafe { ProgramMut::new_mut(ptr.as_mut()) })
     }
+
+    /// Retrieve the BPF map with the given name.
+    ///
+    /// If no such map exists, the returned error is of kind
+    /// [`NotFound`][crate::ErrorKind::NotFound] and lists the names of
+    /// all maps in the object. A common cause is the compiler renaming
+    /// global data sections (e.g., `.bss` becoming `<object>.bss`).
+    pub fn map<N: AsRef<OsStr>>(&self, name: N) -> Result<Map<'_>> {
+        find_by_name("map", name.as_ref(), self.maps(), |map| map.name())
+    }
+
+    /// Retrieve the BPF map with the given name.
+    ///
+    /// See [`Object::map`] for details.
+    pub fn map_mut<N: AsRef<OsStr>>(&mut self, name: N) -> Result<MapMut<'_>> {
+        find_by_name("map", name.as_ref(), self.maps_mut(), |map| map.name())
+    }
+
+    /// Retrieve the BPF program with the given name.
+    ///
+    /// If no such program exists, the returned error is of kind
+    /// [`NotFound`][crate::ErrorKind::NotFound] and lists the names of
+    /// all programs in the object.
+    pub fn prog<N: AsRef<OsStr>>(&self, name: N) -> Result<Program<'_>> {
+        find_by_name("program", name.as_ref(), self.progs(), |prog| prog.name())
+    }
+
+    /// Retrieve the BPF program with the given name.
+    ///
+    /// See [`Object::prog`] for details.
+    pub fn prog_mut<N: AsRef<OsStr>>(&self, name: N) -> Result<ProgramMut<'_>> {
+        find_by_name("program", name.as_ref(), self.progs_mut(), |prog| {
+            prog.name()
+        })
+    }
+
+    /// Attach all programs of the object that can be attached
+    /// automatically, based on their section.
+    ///
+    /// Programs that were not loaded (see
+    /// [`OpenProgramMut::set_autoload`]) or that opted out of being
+    /// attached automatically (see [`OpenProgramMut::set_autoattach`]) are
+    /// skipped, as are programs whose section does not provide enough
+    /// information for attaching them (e.g., a plain `SEC("uprobe")` or
+    /// `SEC("xdp")`).
+    ///
+    /// If attaching any program fails, all links created up to this point
+    /// are destroyed again and the error is reported.
+    pub fn attach_all(&self) -> Result<Links> {
+        let mut links = Links::default();
+        for prog in self.progs_mut() {
+            if !prog.autoload() || !prog.autoattach() {
+                continue
+            }
+            let attachable = prog
+                .section()
+                .to_str()
+                .map_or(false, has_auto_attach_target);
+            if !attachable {
+                continue
+            }
+
+            let link = prog.attach().with_context(|| {
+                format!(
+                    "failed to attach program `{}`",
+                    prog.name().to_string_lossy()
+                )
+            })?;
+            // Dropping `links` on error detaches the programs attached so
+            // far.
+            let () = links.push(prog.name().to_os_string(), link);
+        }
+        Ok(links)
+    }
+
+    /// Check whether the map with the given name reused a map pinned
+    /// previously, as opposed to having been created (and possibly pinned)
+    /// when the object was loaded.
+    ///
+    /// Maps are reused if they have a [pin path][OpenMap::pin_path] set at
+    /// which a compatible map is pinned already, e.g., because they are
+    /// declared with `__uint(pinning, LIBBPF_PIN_BY_NAME)` and the object
+    /// got loaded before.
+    pub fn map_pin_reused<N: AsRef<OsStr>>(&self, name: N) -> Result<bool> {
+        let map = self.map(name)?;
+        let reused = map.get_pin_path().is_some_and(|path| {
+            with_state(self.ptr, |state| {
+                state.reused_pins.iter().any(|pin| pin == path)
+            })
+        });
+        Ok(reused)
+    }
+
+    /// Retrieve the log the kernel produced while loading the object,
+    /// if capturing it was requested via
+    /// [`ObjectBuilder::kernel_log_size`].
+    pub fn kernel_log(&self) -> Option<Cow<'_, str>> {
+        with_state(self.ptr, |state| {
+            state
+                .kernel_log
+                .as_deref()
+                .map(|log| Cow::Owned(kernel_log_str(log).into_owned()))
+        })
+    }
+
+    /// Retrieve statistics about the verification of the program with the
+    /// given name.
+    ///
+    /// The complete set of statistics is only available for programs
+    /// loaded with a [log level][OpenProgramMut::set_log_level] of exactly
+    /// `4` (`BPF_LOG_STATS`). For other programs only the number of
+    /// processed instructions is reported, as far as supported by the
+    /// kernel (5.16 or later).
+    pub fn verifier_stats<N: AsRef<OsStr>>(&self, name: N) -> Result<VerifierStats> {
+        let prog = self.prog(name)?;
+        if prog.log_level() == BPF_LOG_STATS {
+            let mut size = 0;
+            let buf = unsafe {
+                libbpf_sys::bpf_program__log_buf(prog.as_libbpf_object().as_ptr(), &mut size)
+            };
+            if !buf.is_null() {
+                // SAFETY: The buffer stays valid for as long as the object
+                //         exists and the kernel is done writing to it.
+                let buf = unsafe { slice::from_raw_parts(buf.cast::<u8>(), size as usize) };
+                if let Some(stats) = VerifierStats::from_log(&kernel_log_str(buf)) {
+                    return Ok(stats)
+                }
+            }
+        }
+
+        let mut info = libbpf_sys::bpf_prog_info::default();
+        let mut len = mem::size_of::<libbpf_sys::bpf_prog_info>() as u32;
+        // SAFETY: `info` is valid for writes of `len` bytes.
+        let ret = unsafe {
+            libbpf_sys::bpf_obj_get_info_by_fd(
+                prog.as_fd().as_raw_fd(),
+                (&mut info as *mut libbpf_sys::bpf_prog_info).cast::<c_void>(),
+                &mut len,
+            )
+        };
+        let () = util::parse_ret(ret).with_context(|| {
+            format!(
+                "failed to retrieve information about program `{}`",
+                prog.name().to_string_lossy()
+            )
+        })?;
+
+        let stats = VerifierStats {
+            processed_insns: (info.verified_insns != 0).then_some(info.verified_insns),
+            ..Default::default()
+        };
+        Ok(stats)
+    }
 }
 
 impl AsRawLibbpf for Object {
@@ -396,10 +1148,97 @@ impl AsRawLibbpf for Object {
     }
 }
 
+// SAFETY: `bpf_object` objects can safely be sent to a different thread.
+unsafe impl Send for Object {}
+
 impl Drop for Object {
     fn drop(&mut self) {
+        // The buffers the object references must outlive it.
+        let _state = take_state(self.ptr);
         unsafe {
             libbpf_sys::bpf_object__close(self.ptr.as_ptr());
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ErrorKind;
+
+    /// Check that kernel log buffers are read up to the terminating NUL.
+    #[test]
+    fn kernel_log_reading() {
+        assert_eq!(kernel_log_str(b"func#0 @0\n\0stale"), "func#0 @0\n");
+        assert_eq!(kernel_log_str(b"\0\0\0"), "");
+        assert_eq!(kernel_log_str(b"unterminated"), "unterminated");
+    }
+
+    /// Check that a cloned `ObjectBuilder` references its own strings.
+    #[test]
+    fn object_builder_clone() {
+        let mut builder = ObjectBuilder::default();
+        let _builder = builder
+            .name("cloned")
+            .unwrap()
+            .pin_root_path("/sys/fs/bpf/test")
+            .unwrap()
+            .kconfig("CONFIG_BPF_SYSCALL=y")
+            .unwrap()
+            .kernel_log_size(4096)
+            .unwrap();
+
+        let clone = builder.clone();
+        let () = drop(builder);
+
+        let name = unsafe { CStr::from_ptr(clone.opts.object_name) };
+        assert_eq!(name.to_str().unwrap(), "cloned");
+        let path = unsafe { CStr::from_ptr(clone.opts.pin_root_path) };
+        assert_eq!(path.to_str().unwrap(), "/sys/fs/bpf/test");
+        let kconfig = unsafe { CStr::from_ptr(clone.opts.kconfig) };
+        assert_eq!(kconfig.to_str().unwrap(), "CONFIG_BPF_SYSCALL=y");
+        assert!(clone.opts.btf_custom_path.is_null());
+        assert!(clone.opts.bpf_token_path.is_null());
+        // Kernel log buffers are only allocated when opening an object.
+        assert!(clone.opts.kernel_log_buf.is_null());
+        assert_eq!(clone.kernel_log_size, 4096);
+        assert_eq!(
+            clone.opts.object_name,
+            clone.name.as_ref().unwrap().as_ptr()
+        );
+    }
+
+    /// Check that kernel log sizes the kernel or libbpf cannot handle
+    /// are rejected.
+    #[test]
+    fn object_builder_kernel_log_size() {
+        let mut builder = ObjectBuilder::default();
+        let _builder = builder.kernel_log_size(0).unwrap();
+        let _builder = builder.kernel_log_size(128).unwrap();
+        let _builder = builder.kernel_log_size(u32::MAX as usize).unwrap();
+
+        let err = builder.kernel_log_size(127).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        #[cfg(target_pointer_width = "64")]
+        {
+            let err = builder.kernel_log_size(u32::MAX as usize + 1).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+        // A rejected size leaves the previous one in place.
+        assert_eq!(builder.kernel_log_size, u32::MAX as usize);
+    }
+
+    /// Check that `OpenObject` and `Object` are `Send`.
+    #[test]
+    fn object_is_send() {
+        fn test<T>()
+        where
+            T: Send,
+        {
+        }
+
+        test::<OpenObject>();
+        test::<Object>();
+    }
+}
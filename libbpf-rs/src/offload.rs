@@ -0,0 +1,214 @@
+//! Support for offloading programs and maps to network devices.
+//!
+//! Some SmartNICs, e.g., Netronome's Agilio cards driven by `nfp`, are
+//! capable of running XDP and tc classifier programs, along with the
+//! array and hash maps they use, on the device itself. Programs and maps
+//! are bound to a device before loading, via
+//! [`OpenProgramMut::set_ifindex`][crate::OpenProgramMut::set_ifindex]
+//! and [`OpenMapMut::set_map_ifindex`][crate::OpenMapMut::set_map_ifindex].
+//! Offloaded XDP programs then have to be attached in hardware mode, i.e.,
+//! via [`Xdp::attach`][crate::Xdp::attach] with
+//! [`XdpFlags::HW_MODE`][crate::XdpFlags::HW_MODE].
+//!
+//! Whether offload works depends on the device, its driver, and its
+//! firmware, and the kernel reports a lack of support with rather
+//! generic errors. The functions in here allow for probing for support
+//! up front.
+//!
+//! ```no_run
+//! # fn example(obj: &mut libbpf_rs::OpenObject, ifindex: u32) -> libbpf_rs::Result<()> {
+//! use libbpf_rs::offload;
+//! use libbpf_rs::ProgramType;
+//!
+//! if offload::is_prog_type_supported(ifindex, ProgramType::Xdp)? {
+//!     let () = obj.prog_mut("xdp_filter")?.set_ifindex(ifindex);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::CStr;
+use std::io;
+use std::mem::size_of;
+use std::ptr;
+
+use crate::insn::InsnBuilder;
+use crate::insn::Reg;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::ErrorKind;
+use crate::MapType;
+use crate::ProgramType;
+use crate::Result;
+
+
+/// Retrieve the name of the network device with the given index, if it
+/// exists.
+pub(crate) fn device_name(ifindex: u32) -> Option<String> {
+    let mut buf = [0 as libc::c_char; libc::IF_NAMESIZE];
+    // SAFETY: The buffer is `IF_NAMESIZE` bytes large, as required.
+    let name = unsafe { libc::if_indextoname(ifindex, buf.as_mut_ptr()) };
+    if name.is_null() {
+        return None
+    }
+    // SAFETY: `if_indextoname` succeeded and stored a NUL terminated
+    //         string in the buffer.
+    let name = unsafe { CStr::from_ptr(name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Check that a network device with the given index exists.
+///
+/// The kernel reports missing devices with `EINVAL`, just like missing
+/// offload support, so we have to tell both apart ourselves.
+fn check_device(ifindex: u32) -> Result<()> {
+    if device_name(ifindex).is_none() {
+        return Err(Error::with_io_error(
+            io::ErrorKind::NotFound,
+            format!("no network device with index {ifindex} exists"),
+        ))
+    }
+    Ok(())
+}
+
+/// Interpret the result of creating a probe object bound to a device.
+fn probe_result(ret: i32) -> Result<bool> {
+    if ret >= 0 {
+        // SAFETY: A non-negative return value is a file descriptor we own.
+        let _ret = unsafe { libc::close(ret) };
+        Ok(true)
+    } else if ret == -libc::EINVAL || ret == -libc::EOPNOTSUPP {
+        Ok(false)
+    } else {
+        Err(Error::from_raw_os_error(-ret))
+    }
+}
+
+/// Check whether programs of type `prog_type` can be offloaded to the
+/// network device with index `ifindex`.
+///
+/// Only XDP and tc classifier programs are eligible for offload at all.
+/// The check loads a trivial program bound to the device, which requires
+/// `CAP_BPF` and `CAP_NET_ADMIN`.
+pub fn is_prog_type_supported(ifindex: u32, prog_type: ProgramType) -> Result<bool> {
+    let () = check_device(ifindex)?;
+    if !matches!(prog_type, ProgramType::Xdp | ProgramType::SchedCls) {
+        return Ok(false)
+    }
+
+    let insns = InsnBuilder::new().mov64_imm(Reg::R0, 0).exit().build();
+    let mut opts = libbpf_sys::bpf_prog_load_opts {
+        sz: size_of::<libbpf_sys::bpf_prog_load_opts>() as _,
+        prog_ifindex: ifindex,
+        ..Default::default()
+    };
+    // SAFETY: All pointers are valid for the duration of the call.
+    let ret = unsafe {
+        libbpf_sys::bpf_prog_load(
+            prog_type as u32,
+            ptr::null(),
+            b"GPL\0".as_ptr().cast(),
+            insns.as_ptr(),
+            insns.len() as _,
+            &mut opts,
+        )
+    };
+    probe_result(ret).with_context(|| {
+        format!("failed to probe offload of {prog_type:?} programs to device {ifindex}")
+    })
+}
+
+/// Check whether maps of type `map_type` can be offloaded to the network
+/// device with index `ifindex`.
+///
+/// Only array and hash maps are eligible for offload at all. The check
+/// creates a single entry map bound to the device, which requires
+/// `CAP_BPF` and `CAP_NET_ADMIN`.
+pub fn is_map_type_supported(ifindex: u32, map_type: MapType) -> Result<bool> {
+    let () = check_device(ifindex)?;
+    if !matches!(map_type, MapType::Array | MapType::Hash) {
+        return Ok(false)
+    }
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as _,
+        map_ifindex: ifindex,
+        ..Default::default()
+    };
+    // SAFETY: The options are valid for the duration of the call.
+    let ret = unsafe {
+        libbpf_sys::bpf_map_create(
+            map_type as u32,
+            ptr::null(),
+            size_of::<u32>() as _,
+            size_of::<u32>() as _,
+            1,
+            &opts,
+        )
+    };
+    probe_result(ret).with_context(|| {
+        format!("failed to probe offload of {map_type:?} maps to device {ifindex}")
+    })
+}
+
+/// Enrich an error from loading an object with the devices that the
+/// given maps, as `(name, ifindex)` pairs, were bound to.
+///
+/// The kernel reports the failure to offload with `EINVAL` or, if the
+/// driver rejected the object, commonly with `EOPNOTSUPP`. Errors of
+/// any other kind are passed through unchanged.
+pub(crate) fn diagnose(err: Error, offloaded: &[(String, u32)]) -> Error {
+    if offloaded.is_empty()
+        || !matches!(err.kind(), ErrorKind::InvalidInput | ErrorKind::Unsupported)
+    {
+        return err;
+    }
+
+    let maps = offloaded
+        .iter()
+        .map(|(name, ifindex)| {
+            let device = device_name(*ifindex).unwrap_or_else(|| "<unknown>".to_string());
+            format!("`{name}` on device `{device}` (ifindex {ifindex})")
+        })
+        .collect::<Vec<_>>();
+    err.context(format!(
+        "object contains maps bound for offload ({}); the device may not support offload or rejected the object",
+        maps.join(", ")
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that only errors plausibly caused by offload get diagnosed.
+    #[test]
+    fn offload_error_diagnosis() {
+        let offloaded = [("counts".to_string(), u32::MAX)];
+
+        let err = Error::from_raw_os_error(libc::ENOENT);
+        let err = diagnose(err, &offloaded);
+        assert_eq!(err.to_string(), "No such file or directory (os error 2)");
+
+        let err = Error::from_raw_os_error(libc::EINVAL);
+        let err = diagnose(err, &[]);
+        assert_eq!(err.to_string(), "Invalid argument (os error 22)");
+
+        let err = Error::from_raw_os_error(libc::EINVAL);
+        let err = diagnose(err, &offloaded);
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        let msg = err.to_string();
+        assert!(msg.contains("`counts` on device `<unknown>`"), "{msg}");
+    }
+
+    /// Check that probing a non-existent device fails.
+    #[test]
+    fn probe_missing_device() {
+        let err = is_prog_type_supported(u32::MAX, ProgramType::Xdp).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        let err = is_map_type_supported(u32::MAX, MapType::Array).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,612 @@
+//! Opening of perf events, e.g., for attaching BPF programs to them.
+//!
+//! This module wraps the
+//! [`perf_event_open`](https://man7.org/linux/man-pages/man2/perf_event_open.2.html)
+//! system call. The resulting [`PerfEvent`]s can be read as counters or,
+//! if configured for sampling, be used with
+//! [`ProgramMut::attach_perf_event`][crate::ProgramMut::attach_perf_event]
+//! to run a `SEC("perf_event")` program for every sample.
+//!
+//! ```no_run
+//! # fn example(prog: &libbpf_rs::ProgramMut<'_>) -> libbpf_rs::Result<()> {
+//! use libbpf_rs::perf::PerfEventBuilder;
+//! use libbpf_rs::perf::SoftwareEvent;
+//!
+//! let event = PerfEventBuilder::new(SoftwareEvent::CpuClock)
+//!     .sample_freq(99)
+//!     .pid(-1)
+//!     .cpu(0)
+//!     .open()?;
+//! let _link = prog.attach_perf_event(event.into())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::io;
+use std::mem::size_of;
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::path::Path;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_TYPE_SOFTWARE: u32 = 1;
+const PERF_TYPE_TRACEPOINT: u32 = 2;
+
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+
+/// Apply an `ioctl` to all events of a group, when issued on the leader.
+const PERF_IOC_FLAG_GROUP: libc::c_ulong = 1 << 0;
+
+/// Read the counts of all events of a group at once, via the leader.
+const PERF_FORMAT_GROUP: u64 = 1 << 3;
+
+// Bits of `perf_event_attr`'s flags bit field.
+const ATTR_DISABLED: u64 = 1 << 0;
+const ATTR_INHERIT: u64 = 1 << 1;
+const ATTR_EXCLUDE_USER: u64 = 1 << 4;
+const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+const ATTR_EXCLUDE_HV: u64 = 1 << 6;
+const ATTR_FREQ: u64 = 1 << 10;
+
+
+/// Mirror of the kernel's `struct perf_event_attr`, as of
+/// `PERF_ATTR_SIZE_VER7` (128 bytes), not all of whose members we make
+/// use of.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    /// `sample_period` or `sample_freq`, depending on `ATTR_FREQ`.
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    /// `wakeup_events` or `wakeup_watermark`.
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+    config2: u64,
+    branch_sample_type: u64,
+    sample_regs_user: u64,
+    sample_stack_user: u32,
+    clockid: i32,
+    sample_regs_intr: u64,
+    aux_watermark: u32,
+    sample_max_stack: u16,
+    _reserved_2: u16,
+    aux_sample_size: u32,
+    _reserved_3: u32,
+    sig_data: u64,
+}
+
+
+/// A generalized hardware event, counted by the CPU's performance
+/// monitoring unit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HardwareEvent {
+    /// Total CPU cycles.
+    CpuCycles,
+    /// Retired instructions.
+    Instructions,
+    /// Cache accesses, usually of the last level cache.
+    CacheReferences,
+    /// Cache misses, usually of the last level cache.
+    CacheMisses,
+    /// Retired branch instructions.
+    BranchInstructions,
+    /// Mispredicted branch instructions.
+    BranchMisses,
+    /// Bus cycles.
+    BusCycles,
+    /// Stalled cycles during issue.
+    StalledCyclesFrontend,
+    /// Stalled cycles during retirement.
+    StalledCyclesBackend,
+    /// CPU cycles, not affected by CPU frequency scaling.
+    RefCpuCycles,
+}
+
+impl HardwareEvent {
+    fn config(self) -> u64 {
+        match self {
+            Self::CpuCycles => 0,
+            Self::Instructions => 1,
+            Self::CacheReferences => 2,
+            Self::CacheMisses => 3,
+            Self::BranchInstructions => 4,
+            Self::BranchMisses => 5,
+            Self::BusCycles => 6,
+            Self::StalledCyclesFrontend => 7,
+            Self::StalledCyclesBackend => 8,
+            Self::RefCpuCycles => 9,
+        }
+    }
+}
+
+/// A software event, provided by the kernel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SoftwareEvent {
+    /// A high resolution per-CPU timer.
+    CpuClock,
+    /// A clock counting the time the monitored task is running.
+    TaskClock,
+    /// Page faults.
+    PageFaults,
+    /// Context switches.
+    ContextSwitches,
+    /// Migrations of the monitored task to a different CPU.
+    CpuMigrations,
+    /// Minor page faults.
+    PageFaultsMin,
+    /// Major page faults.
+    PageFaultsMaj,
+    /// Alignment faults.
+    AlignmentFaults,
+    /// Emulation faults.
+    EmulationFaults,
+    /// An event that never counts.
+    Dummy,
+    /// An event for emitting data from BPF programs via
+    /// `bpf_perf_event_output`.
+    BpfOutput,
+}
+
+impl SoftwareEvent {
+    fn config(self) -> u64 {
+        match self {
+            Self::CpuClock => 0,
+            Self::TaskClock => 1,
+            Self::PageFaults => 2,
+            Self::ContextSwitches => 3,
+            Self::CpuMigrations => 4,
+            Self::PageFaultsMin => 5,
+            Self::PageFaultsMaj => 6,
+            Self::AlignmentFaults => 7,
+            Self::EmulationFaults => 8,
+            Self::Dummy => 9,
+            Self::BpfOutput => 10,
+        }
+    }
+}
+
+/// The kind of a perf event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// A generalized hardware event.
+    Hardware(HardwareEvent),
+    /// A software event.
+    Software(SoftwareEvent),
+    /// A tracepoint, identified by its ID; see
+    /// [`EventKind::tracepoint`].
+    Tracepoint(u64),
+    /// An event of a PMU specific type, e.g., as listed in
+    /// `/sys/bus/event_source/devices/<pmu>/type`, with a PMU specific
+    /// configuration.
+    Raw {
+        /// The event type.
+        type_: u32,
+        /// The type specific configuration.
+        config: u64,
+    },
+}
+
+impl EventKind {
+    /// Look up the tracepoint `name` of category `category`, e.g.,
+    /// `sched` and `sched_switch`, in tracefs.
+    pub fn tracepoint(category: &str, name: &str) -> Result<Self> {
+        let path = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"]
+            .iter()
+            .map(|tracefs| format!("{tracefs}/events/{category}/{name}/id"))
+            .find(|path| Path::new(path).exists())
+            .ok_or_else(|| {
+                Error::with_io_error(
+                    io::ErrorKind::NotFound,
+                    format!("tracepoint `{category}:{name}` not found"),
+                )
+            })?;
+        let id = fs::read_to_string(&path).with_context(|| format!("failed to read `{path}`"))?;
+        let id = id
+            .trim()
+            .parse()
+            .map_err(|_| Error::with_invalid_data(format!("invalid tracepoint ID in `{path}`")))?;
+        Ok(Self::Tracepoint(id))
+    }
+
+    fn type_config(self) -> (u32, u64) {
+        match self {
+            Self::Hardware(event) => (PERF_TYPE_HARDWARE, event.config()),
+            Self::Software(event) => (PERF_TYPE_SOFTWARE, event.config()),
+            Self::Tracepoint(id) => (PERF_TYPE_TRACEPOINT, id),
+            Self::Raw { type_, config } => (type_, config),
+        }
+    }
+}
+
+impl From<HardwareEvent> for EventKind {
+    fn from(event: HardwareEvent) -> Self {
+        Self::Hardware(event)
+    }
+}
+
+impl From<SoftwareEvent> for EventKind {
+    fn from(event: SoftwareEvent) -> Self {
+        Self::Software(event)
+    }
+}
+
+
+/// Builds [`PerfEvent`] instances.
+///
+/// By default, events count (rather than sample) for the calling process
+/// on any CPU, and are enabled right away.
+#[derive(Clone, Debug)]
+pub struct PerfEventBuilder {
+    attr: PerfEventAttr,
+    pid: i32,
+    cpu: i32,
+}
+
+impl PerfEventBuilder {
+    /// Create a new `PerfEventBuilder` for events of the given kind.
+    pub fn new<K>(kind: K) -> Self
+    where
+        K: Into<EventKind>,
+    {
+        let (type_, config) = kind.into().type_config();
+        let attr = PerfEventAttr {
+            type_,
+            size: size_of::<PerfEventAttr>() as u32,
+            config,
+            ..Default::default()
+        };
+
+        Self {
+            attr,
+            pid: 0,
+            cpu: -1,
+        }
+    }
+
+    fn set_flag(&mut self, flag: u64, value: bool) -> &mut Self {
+        if value {
+            self.attr.flags |= flag;
+        } else {
+            self.attr.flags &= !flag;
+        }
+        self
+    }
+
+    /// Sample every `period` occurrences of the event.
+    pub fn sample_period(&mut self, period: u64) -> &mut Self {
+        self.attr.sample_period = period;
+        self.set_flag(ATTR_FREQ, false)
+    }
+
+    /// Sample at a frequency of `freq` Hz, adjusting the sampling period
+    /// dynamically.
+    pub fn sample_freq(&mut self, freq: u64) -> &mut Self {
+        self.attr.sample_period = freq;
+        self.set_flag(ATTR_FREQ, true)
+    }
+
+    /// Only monitor the process (or thread) with the given ID.
+    ///
+    /// `0` refers to the calling process, `-1` to all processes, which
+    /// requires a specific [CPU][PerfEventBuilder::cpu].
+    pub fn pid(&mut self, pid: i32) -> &mut Self {
+        self.pid = pid;
+        self
+    }
+
+    /// Only monitor the CPU with the given ID, `-1` referring to any CPU.
+    pub fn cpu(&mut self, cpu: i32) -> &mut Self {
+        self.cpu = cpu;
+        self
+    }
+
+    /// Whether to monitor children created after the event was opened as
+    /// well.
+    pub fn inherit(&mut self, inherit: bool) -> &mut Self {
+        self.set_flag(ATTR_INHERIT, inherit)
+    }
+
+    /// Whether to exclude events happening in user space.
+    pub fn exclude_user(&mut self, exclude: bool) -> &mut Self {
+        self.set_flag(ATTR_EXCLUDE_USER, exclude)
+    }
+
+    /// Whether to exclude events happening in the kernel.
+    pub fn exclude_kernel(&mut self, exclude: bool) -> &mut Self {
+        self.set_flag(ATTR_EXCLUDE_KERNEL, exclude)
+    }
+
+    /// Whether to exclude events happening in the hypervisor.
+    pub fn exclude_hv(&mut self, exclude: bool) -> &mut Self {
+        self.set_flag(ATTR_EXCLUDE_HV, exclude)
+    }
+
+    /// Whether to open the event disabled, to be enabled later on via
+    /// [`PerfEvent::enable`].
+    pub fn disabled(&mut self, disabled: bool) -> &mut Self {
+        self.set_flag(ATTR_DISABLED, disabled)
+    }
+
+    /// Wake up readers of the event's ring buffer every `events` samples.
+    pub fn wakeup_events(&mut self, events: u32) -> &mut Self {
+        self.attr.wakeup_events = events;
+        self
+    }
+
+    /// Open the perf event as configured.
+    pub fn open(&self) -> Result<PerfEvent> {
+        self.open_impl(&self.attr, None)
+    }
+
+    fn open_impl(&self, attr: &PerfEventAttr, group: Option<&PerfEvent>) -> Result<PerfEvent> {
+        let group_fd = group.map_or(-1, |leader| leader.fd.as_raw_fd());
+        // SAFETY: `perf_event_open` is always safe to call with a valid
+        //         `perf_event_attr`, which we own.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                attr as *const PerfEventAttr,
+                self.pid,
+                self.cpu,
+                group_fd,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().with_context(|| {
+                format!(
+                    "failed to open perf event (type {}, config {:#x}) for pid {} on cpu {}",
+                    self.attr.type_, self.attr.config, self.pid, self.cpu
+                )
+            }))
+        }
+
+        // SAFETY: The system call succeeded and returned a file
+        //         descriptor that we now own.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+        Ok(PerfEvent { fd })
+    }
+}
+
+
+/// An open perf event.
+///
+/// The event is closed when the object is dropped. To attach a program
+/// via [`ProgramMut::attach_perf_event`][crate::ProgramMut::attach_perf_event],
+/// convert the event into an [`OwnedFd`], ownership of which is passed
+/// on to the resulting link.
+#[derive(Debug)]
+pub struct PerfEvent {
+    fd: OwnedFd,
+}
+
+impl PerfEvent {
+    fn ioctl(&self, request: libc::c_ulong, op: &str) -> Result<()> {
+        self.ioctl_with_flags(request, 0, op)
+    }
+
+    fn ioctl_with_flags(
+        &self,
+        request: libc::c_ulong,
+        flags: libc::c_ulong,
+        op: &str,
+    ) -> Result<()> {
+        // SAFETY: The file descriptor is valid and the request takes no
+        //         argument other than flags.
+        let rc = unsafe { libc::ioctl(self.fd.as_raw_fd(), request as _, flags) };
+        if rc < 0 {
+            let err = io::Error::last_os_error();
+            return Err(err.with_context(|| format!("failed to {op} perf event")))
+        }
+        Ok(())
+    }
+
+    /// Enable the event.
+    pub fn enable(&self) -> Result<()> {
+        self.ioctl(PERF_EVENT_IOC_ENABLE, "enable")
+    }
+
+    /// Disable the event.
+    pub fn disable(&self) -> Result<()> {
+        self.ioctl(PERF_EVENT_IOC_DISABLE, "disable")
+    }
+
+    /// Reset the event's count to zero.
+    pub fn reset(&self) -> Result<()> {
+        self.ioctl(PERF_EVENT_IOC_RESET, "reset")
+    }
+
+    /// Read the event's current count.
+    ///
+    /// This fails for the [leader][PerfEventGroup::leader] of a group,
+    /// whose count is read via [`PerfEventGroup::counts`].
+    pub fn count(&self) -> Result<u64> {
+        let mut count = 0u64;
+        // SAFETY: The file descriptor is valid and the buffer is large
+        //         enough for the count, which is all the kernel reports
+        //         with the default read format.
+        let rc = unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                (&mut count as *mut u64).cast(),
+                size_of::<u64>(),
+            )
+        };
+        if rc != size_of::<u64>() as isize {
+            return Err(io::Error::last_os_error().context("failed to read perf event count"))
+        }
+        Ok(count)
+    }
+}
+
+impl AsFd for PerfEvent {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl From<PerfEvent> for OwnedFd {
+    fn from(event: PerfEvent) -> Self {
+        event.fd
+    }
+}
+
+
+/// A group of perf events, scheduled onto the PMU together and read
+/// coherently.
+///
+/// All events in a group count over exactly the same period of time,
+/// which makes it possible to relate their counts, e.g., to compute
+/// instructions per cycle. The group consists of a leader, which is
+/// opened when creating the group, and its siblings, which get added
+/// subsequently and have to monitor the same process and CPU.
+///
+/// A sampling leader can be used with
+/// [`ProgramMut::attach_perf_event`][crate::ProgramMut::attach_perf_event]
+/// to run a program whenever it overflows. As the resulting link owns
+/// the file descriptor it is provided with while the group keeps the
+/// leader open, provide it with a file descriptor of its own, as
+/// returned by [`BorrowedFd::try_clone_to_owned`]. Siblings can be
+/// stored in a `BPF_MAP_TYPE_PERF_EVENT_ARRAY` map for the program to
+/// read them via `bpf_perf_event_read_value`.
+///
+/// ```no_run
+/// # fn example() -> libbpf_rs::Result<()> {
+/// use libbpf_rs::perf::HardwareEvent;
+/// use libbpf_rs::perf::PerfEventBuilder;
+/// use libbpf_rs::perf::PerfEventGroup;
+///
+/// let mut group =
+///     PerfEventGroup::new(PerfEventBuilder::new(HardwareEvent::CpuCycles).disabled(true))?;
+/// let _idx = group.add(&PerfEventBuilder::new(HardwareEvent::Instructions))?;
+/// let () = group.enable()?;
+/// // ...
+/// let () = group.disable()?;
+/// let counts = group.counts()?;
+/// println!("IPC: {:.2}", counts[1] as f64 / counts[0] as f64);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PerfEventGroup {
+    // Siblings are declared first so that they get closed before the
+    // leader when the group is dropped.
+    siblings: Vec<PerfEvent>,
+    leader: PerfEvent,
+}
+
+impl PerfEventGroup {
+    /// Create a new group, opening its leader as configured by `leader`.
+    ///
+    /// Siblings only count while the leader is enabled, so the leader
+    /// is usually opened [disabled][PerfEventBuilder::disabled] and
+    /// enabled once all siblings got added.
+    pub fn new(leader: &PerfEventBuilder) -> Result<Self> {
+        let attr = PerfEventAttr {
+            read_format: leader.attr.read_format | PERF_FORMAT_GROUP,
+            ..leader.attr
+        };
+        let leader = leader.open_impl(&attr, None)?;
+        let slf = Self {
+            siblings: Vec::new(),
+            leader,
+        };
+        Ok(slf)
+    }
+
+    /// Open an event as configured by `sibling` and add it to the group,
+    /// returning its index in the counts reported by
+    /// [`PerfEventGroup::counts`].
+    pub fn add(&mut self, sibling: &PerfEventBuilder) -> Result<usize> {
+        let event = sibling
+            .open_impl(&sibling.attr, Some(&self.leader))
+            .context("failed to add perf event to group")?;
+        let () = self.siblings.push(event);
+        Ok(self.siblings.len())
+    }
+
+    /// Retrieve the group's leader.
+    ///
+    /// The leader is opened with a read format reporting the counts of
+    /// the whole group, so [`PerfEvent::count`] fails on it; use
+    /// [`PerfEventGroup::counts`] to read its count instead.
+    pub fn leader(&self) -> &PerfEvent {
+        &self.leader
+    }
+
+    /// Retrieve the sibling with the given index, as returned by
+    /// [`PerfEventGroup::add`].
+    pub fn sibling(&self, idx: usize) -> Option<&PerfEvent> {
+        idx.checked_sub(1).and_then(|idx| self.siblings.get(idx))
+    }
+
+    /// Retrieve the number of events in the group, including the leader.
+    fn len(&self) -> usize {
+        self.siblings.len() + 1
+    }
+
+    /// Enable all events of the group.
+    pub fn enable(&self) -> Result<()> {
+        self.leader
+            .ioctl_with_flags(PERF_EVENT_IOC_ENABLE, PERF_IOC_FLAG_GROUP, "enable")
+    }
+
+    /// Disable all events of the group.
+    pub fn disable(&self) -> Result<()> {
+        self.leader
+            .ioctl_with_flags(PERF_EVENT_IOC_DISABLE, PERF_IOC_FLAG_GROUP, "disable")
+    }
+
+    /// Reset the counts of all events of the group to zero.
+    pub fn reset(&self) -> Result<()> {
+        self.leader
+            .ioctl_with_flags(PERF_EVENT_IOC_RESET, PERF_IOC_FLAG_GROUP, "reset")
+    }
+
+    /// Read the current counts of all events of the group at once.
+    ///
+    /// The count of the leader comes first, followed by those of the
+    /// siblings in the order they were added in.
+    pub fn counts(&self) -> Result<Vec<u64>> {
+        // The kernel reports the number of events followed by their
+        // counts.
+        let mut buf = vec![0u64; self.len() + 1];
+        let size = buf.len() * size_of::<u64>();
+        // SAFETY: The file descriptor is valid and the buffer is large
+        //         enough for the group's read format.
+        let rc = unsafe { libc::read(self.leader.fd.as_raw_fd(), buf.as_mut_ptr().cast(), size) };
+        if rc < 0 {
+            return Err(io::Error::last_os_error().context("failed to read perf event group"))
+        }
+        if rc != size as isize || buf[0] != self.len() as u64 {
+            return Err(Error::with_invalid_data(format!(
+                "perf event group read returned unexpected data ({rc} bytes for {} events)",
+                buf[0]
+            )))
+        }
+        let _nr = buf.remove(0);
+        Ok(buf)
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
 use std::os::unix::prelude::AsRawFd;
+use std::os::unix::prelude::BorrowedFd;
 use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
@@ -20,11 +21,14 @@ use crate::Result;
 // Workaround for `trait_alias`
 // (https://doc.rust-lang.org/unstable-book/language-features/trait-alias.html)
 // not being available yet. This is just a custom trait plus a blanket implementation.
-pub trait SampleCb: FnMut(i32, &[u8]) {}
-impl<T> SampleCb for T where T: FnMut(i32, &[u8]) {}
+//
+// Callbacks are required to be `Send`, because they are owned by the
+// `PerfBuffer`, which itself may be sent to a different thread.
+pub trait SampleCb: FnMut(i32, &[u8]) + Send {}
+impl<T> SampleCb for T where T: FnMut(i32, &[u8]) + Send {}
 
-pub trait LostCb: FnMut(i32, u64) {}
-impl<T> LostCb for T where T: FnMut(i32, u64) {}
+pub trait LostCb: FnMut(i32, u64) + Send {}
+impl<T> LostCb for T where T: FnMut(i32, u64) + Send {}
 
 struct CbStruct<'b> {
     sample_cb: Option<Box<dyn SampleCb + 'b>>,
@@ -194,6 +198,13 @@ where
 
 /// Represents a special kind of [`MapCore`]. Typically used to transfer data between
 /// [`Program`][crate::Program]s and userspace.
+///
+/// A `PerfBuffer` is [`Send`], but not [`Sync`], as it must not be polled
+/// from multiple threads concurrently:
+/// ```compile_fail
+/// fn test<T: Sync>() {}
+/// test::<libbpf_rs::PerfBuffer<'_>>();
+/// ```
 #[derive(Debug)]
 pub struct PerfBuffer<'b> {
     ptr: NonNull<libbpf_sys::perf_buffer>,
@@ -204,8 +215,11 @@ pub struct PerfBuffer<'b> {
 // TODO: Document methods.
 #[allow(missing_docs)]
 impl PerfBuffer<'_> {
-    pub fn epoll_fd(&self) -> i32 {
-        unsafe { libbpf_sys::perf_buffer__epoll_fd(self.ptr.as_ptr()) }
+    pub fn epoll_fd(&self) -> BorrowedFd<'_> {
+        let fd = unsafe { libbpf_sys::perf_buffer__epoll_fd(self.ptr.as_ptr()) };
+        // SAFETY: The epoll file descriptor is owned by the `perf_buffer`
+        //         and stays valid for as long as it is alive.
+        unsafe { BorrowedFd::borrow_raw(fd) }
     }
 
     pub fn poll(&self, timeout: Duration) -> Result<()> {
@@ -250,7 +264,10 @@ impl AsRawLibbpf for PerfBuffer<'_> {
     }
 }
 
-// SAFETY: `perf_buffer` objects can safely be polled from any thread.
+// SAFETY: `perf_buffer` objects can safely be polled from any thread
+//         and all callbacks are `Send`. Note that we intentionally do
+//         not implement `Sync`: concurrent polling of the same
+//         `perf_buffer` is not supported by libbpf.
 unsafe impl Send for PerfBuffer<'_> {}
 
 impl Drop for PerfBuffer<'_> {
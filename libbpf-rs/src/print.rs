@@ -1,10 +1,14 @@
+use std::cell::Cell;
+use std::cell::RefCell;
 use std::ffi::c_char;
 use std::ffi::c_int;
 use std::ffi::c_void;
 use std::io;
 use std::io::Write;
 use std::mem;
+use std::ptr::NonNull;
 use std::sync::Mutex;
+use std::sync::Once;
 
 use crate::util::LazyLock;
 
@@ -53,6 +57,21 @@ fn default_callback(_lvl: PrintLevel, msg: String) {
 static PRINT_CB: LazyLock<Mutex<Option<(PrintLevel, PrintCallback)>>> =
     LazyLock::new(|| Mutex::new(Some((PrintLevel::Info, default_callback))));
 
+thread_local! {
+    /// Messages captured on the current thread by an active
+    /// [`capture_print`] invocation, if any.
+    static CAPTURED: RefCell<Option<Vec<(PrintLevel, String)>>> = const { RefCell::new(None) };
+    /// The observer of an active [`observe_print`] invocation on the
+    /// current thread, if any.
+    static OBSERVER: Cell<Option<NonNull<Observer<'static>>>> = const { Cell::new(None) };
+}
+
+/// A function receiving libbpf messages as they are emitted.
+pub(crate) type Observer<'a> = dyn FnMut(PrintLevel, &str) + 'a;
+
+/// Ensures that [`outer_print_cb`] is installed.
+static INSTALL: Once = Once::new();
+
 extern "C" fn outer_print_cb(
     level: libbpf_sys::libbpf_print_level,
     fmtstr: *const c_char,
@@ -63,18 +82,51 @@ extern "C" fn outer_print_cb(
     va_list: *mut c_void,
 ) -> c_int {
     let level = level.into();
-    if let Some((min_level, func)) = { *PRINT_CB.lock().unwrap() } {
-        if level <= min_level {
-            let msg = match unsafe { vsprintf::vsprintf(fmtstr, va_list) } {
-                Ok(s) => s,
-                Err(e) => format!("Failed to parse libbpf output: {e}"),
-            };
+    let callback = { *PRINT_CB.lock().unwrap() }.filter(|(min_level, _)| level <= *min_level);
+    let capturing = CAPTURED.with(|captured| captured.borrow().is_some());
+    // The observer is taken out while it runs, so that messages emitted
+    // by whatever it does are not reported to it recursively.
+    let observer = OBSERVER.with(Cell::take);
+
+    if callback.is_some() || capturing || observer.is_some() {
+        let msg = match unsafe { vsprintf::vsprintf(fmtstr, va_list) } {
+            Ok(s) => s,
+            Err(e) => format!("Failed to parse libbpf output: {e}"),
+        };
+
+        if capturing {
+            let () = CAPTURED.with(|captured| {
+                if let Some(messages) = captured.borrow_mut().as_mut() {
+                    let () = messages.push((level, msg.clone()));
+                }
+            });
+        }
+
+        if let Some(mut observer) = observer {
+            // SAFETY: `observe_print` keeps the observer alive while it is
+            //         installed and, having taken it out, we hold the only
+            //         reference to it.
+            let () = unsafe { observer.as_mut() }(level, &msg);
+            let () = OBSERVER.with(|cell| cell.set(Some(observer)));
+        }
+
+        if let Some((_, func)) = callback {
             func(level, msg);
         }
     }
     0 // return value is ignored by libbpf
 }
 
+/// Install [`outer_print_cb`] as libbpf's print function.
+fn install_outer_print_cb() {
+    // # Safety
+    // outer_print_cb has the same function signature as libbpf_print_fn_t
+    #[allow(clippy::missing_transmute_annotations)]
+    let real_cb: libbpf_sys::libbpf_print_fn_t =
+        unsafe { Some(mem::transmute(outer_print_cb as *const ())) };
+    unsafe { libbpf_sys::libbpf_set_print(real_cb) };
+}
+
 /// Set a callback to receive log messages from libbpf, instead of printing them to stderr.
 ///
 /// # Arguments
@@ -102,6 +154,10 @@ extern "C" fn outer_print_cb(
 /// set_print(Some((PrintLevel::Debug, print_to_log)));
 /// ```
 ///
+/// With the `log` feature enabled, [`print_to_log`] provides the same
+/// functionality out of the box (and [`print_to_tracing`] does likewise
+/// for the `tracing` crate, with the `tracing` feature enabled).
+///
 /// To disable printing completely:
 ///
 /// ```
@@ -121,14 +177,11 @@ extern "C" fn outer_print_cb(
 pub fn set_print(
     mut callback: Option<(PrintLevel, PrintCallback)>,
 ) -> Option<(PrintLevel, PrintCallback)> {
-    // # Safety
-    // outer_print_cb has the same function signature as libbpf_print_fn_t
-    #[allow(clippy::missing_transmute_annotations)]
-    let real_cb: libbpf_sys::libbpf_print_fn_t =
-        unsafe { Some(mem::transmute(outer_print_cb as *const ())) };
-    let real_cb: libbpf_sys::libbpf_print_fn_t = callback.as_ref().and(real_cb);
     mem::swap(&mut callback, &mut *PRINT_CB.lock().unwrap());
-    unsafe { libbpf_sys::libbpf_set_print(real_cb) };
+    // Our print function stays installed even when printing is disabled,
+    // so that `capture_print` keeps working. It is a no-op if there is
+    // nobody interested in the message.
+    let () = install_outer_print_cb();
     callback
 }
 
@@ -149,3 +202,127 @@ pub fn set_print(
 pub fn get_print() -> Option<(PrintLevel, PrintCallback)> {
     *PRINT_CB.lock().unwrap()
 }
+
+/// Run `f`, capturing all messages libbpf emits on the calling thread in
+/// the meantime.
+///
+/// Messages are captured at all levels, irrespective of the callback
+/// configured via [`set_print`], which continues to receive them as
+/// usual. Because libbpf reports messages on the thread performing an
+/// operation, this can be used to retrieve the log associated with a
+/// specific failed load.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libbpf_rs::capture_print;
+/// use libbpf_rs::ObjectBuilder;
+///
+/// let (result, messages) = capture_print(|| ObjectBuilder::default().open_file("prog.bpf.o"));
+/// if result.is_err() {
+///     for (level, msg) in messages {
+///         eprint!("{level:?}: {msg}");
+///     }
+/// }
+/// ```
+pub fn capture_print<F, R>(f: F) -> (R, Vec<(PrintLevel, String)>)
+where
+    F: FnOnce() -> R,
+{
+    /// A guard restoring the previous capture state, even on panic.
+    struct Restore(Option<Vec<(PrintLevel, String)>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            let _captured = CAPTURED.with(|captured| captured.replace(prev));
+        }
+    }
+
+    // libbpf uses its own print function until `set_print` is called for
+    // the first time. Make sure ours is in place.
+    let () = INSTALL.call_once(install_outer_print_cb);
+
+    let prev = CAPTURED.with(|captured| captured.replace(Some(Vec::new())));
+    let restore = Restore(prev);
+    let result = f();
+    let messages = CAPTURED
+        .with(|captured| captured.borrow_mut().take())
+        .unwrap_or_default();
+    let () = drop(restore);
+    (result, messages)
+}
+
+/// Run `f`, reporting all messages libbpf emits on the calling thread in
+/// the meantime to `observer`, as they are emitted.
+///
+/// Like with [`capture_print`], messages are reported at all levels.
+pub(crate) fn observe_print<F, R>(observer: &mut Observer<'_>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    /// A guard restoring the previous observer, even on panic.
+    struct Restore(Option<NonNull<Observer<'static>>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let () = OBSERVER.with(|cell| cell.set(self.0));
+        }
+    }
+
+    let () = INSTALL.call_once(install_outer_print_cb);
+
+    // SAFETY: Only the lifetime is erased. `Restore` uninstalls the
+    //         observer before the borrow ends, even on panic.
+    let observer = unsafe {
+        mem::transmute::<NonNull<Observer<'_>>, NonNull<Observer<'static>>>(NonNull::from(observer))
+    };
+    let prev = OBSERVER.with(|cell| cell.replace(Some(observer)));
+    let _restore = Restore(prev);
+    f()
+}
+
+/// A [`PrintCallback`] forwarding libbpf messages to the `log` crate.
+///
+/// Messages are logged with the `libbpf` target, using the log level
+/// corresponding to the [`PrintLevel`].
+///
+/// ```
+/// use libbpf_rs::print_to_log;
+/// use libbpf_rs::set_print;
+/// use libbpf_rs::PrintLevel;
+///
+/// set_print(Some((PrintLevel::Debug, print_to_log)));
+/// ```
+#[cfg(feature = "log")]
+pub fn print_to_log(level: PrintLevel, msg: String) {
+    let level = match level {
+        PrintLevel::Debug => log::Level::Debug,
+        PrintLevel::Info => log::Level::Info,
+        PrintLevel::Warn => log::Level::Warn,
+    };
+    log::log!(target: "libbpf", level, "{}", msg.trim_end());
+}
+
+/// A [`PrintCallback`] forwarding libbpf messages to the `tracing`
+/// crate.
+///
+/// Events are emitted with the `libbpf` target, using the level
+/// corresponding to the [`PrintLevel`].
+///
+/// ```
+/// use libbpf_rs::print_to_tracing;
+/// use libbpf_rs::set_print;
+/// use libbpf_rs::PrintLevel;
+///
+/// set_print(Some((PrintLevel::Debug, print_to_tracing)));
+/// ```
+#[cfg(feature = "tracing")]
+pub fn print_to_tracing(level: PrintLevel, msg: String) {
+    let msg = msg.trim_end();
+    match level {
+        PrintLevel::Debug => tracing::debug!(target: "libbpf", "{msg}"),
+        PrintLevel::Info => tracing::info!(target: "libbpf", "{msg}"),
+        PrintLevel::Warn => tracing::warn!(target: "libbpf", "{msg}"),
+    }
+}
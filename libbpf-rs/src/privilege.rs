@@ -0,0 +1,311 @@
+//! Diagnostics for permission errors reported by the kernel.
+//!
+//! `EPERM` and `EACCES` are notoriously unhelpful when it comes to BPF:
+//! they may be caused by a missing capability, by a system wide sysctl
+//! restricting unprivileged BPF, or by a Linux Security Module vetoing
+//! the operation. The functionality in here attempts to narrow down the
+//! likely cause and attach it to the error as context.
+
+use std::fs;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::ErrorKind;
+use crate::ProgramType;
+
+
+/// A Linux capability relevant to BPF operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    /// `CAP_NET_ADMIN`, required for networking program types.
+    NetAdmin,
+    /// `CAP_SYS_ADMIN`, which subsumes all other BPF related capabilities.
+    SysAdmin,
+    /// `CAP_PERFMON`, required for tracing program types.
+    Perfmon,
+    /// `CAP_BPF`, required for most BPF operations.
+    Bpf,
+}
+
+impl Capability {
+    /// All capabilities, in the order they are reported in.
+    pub(crate) const ALL: [Self; 4] = [Self::NetAdmin, Self::SysAdmin, Self::Perfmon, Self::Bpf];
+
+    /// The capability's bit in the capability sets reported by the kernel.
+    fn bit(&self) -> u32 {
+        match self {
+            Self::NetAdmin => 12,
+            Self::SysAdmin => 21,
+            Self::Perfmon => 38,
+            Self::Bpf => 39,
+        }
+    }
+
+    /// The capability's mask in the capability sets reported by the
+    /// kernel.
+    pub(crate) fn mask(&self) -> u64 {
+        1 << self.bit()
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::NetAdmin => "CAP_NET_ADMIN",
+            Self::SysAdmin => "CAP_SYS_ADMIN",
+            Self::Perfmon => "CAP_PERFMON",
+            Self::Bpf => "CAP_BPF",
+        }
+    }
+
+    /// Check whether the capability is effectively granted by the provided
+    /// effective capability set.
+    ///
+    /// Mirroring the kernel's `bpf_capable` and `perfmon_capable`,
+    /// `CAP_SYS_ADMIN` implies `CAP_BPF` and `CAP_PERFMON`, but no other
+    /// capabilities.
+    pub(crate) fn is_granted(&self, effective: u64) -> bool {
+        let has = |cap: Self| effective & cap.mask() != 0;
+        match self {
+            Self::Bpf | Self::Perfmon => has(*self) || has(Self::SysAdmin),
+            Self::NetAdmin | Self::SysAdmin => has(*self),
+        }
+    }
+}
+
+
+/// Retrieve the set of capabilities required for loading and attaching a
+/// program of the provided type.
+pub(crate) fn prog_type_capabilities(prog_type: ProgramType) -> &'static [Capability] {
+    match prog_type {
+        ProgramType::Kprobe
+        | ProgramType::Tracepoint
+        | ProgramType::PerfEvent
+        | ProgramType::RawTracepoint
+        | ProgramType::RawTracepointWritable
+        | ProgramType::Tracing
+        | ProgramType::StructOps
+        | ProgramType::Lsm => &[Capability::Bpf, Capability::Perfmon],
+        ProgramType::SchedCls
+        | ProgramType::SchedAct
+        | ProgramType::Xdp
+        | ProgramType::CgroupSkb
+        | ProgramType::CgroupSock
+        | ProgramType::CgroupDevice
+        | ProgramType::CgroupSockAddr
+        | ProgramType::CgroupSysctl
+        | ProgramType::CgroupSockopt
+        | ProgramType::LwtIn
+        | ProgramType::LwtOut
+        | ProgramType::LwtXmit
+        | ProgramType::LwtSeg6local
+        | ProgramType::SockOps
+        | ProgramType::SkSkb
+        | ProgramType::SkMsg
+        | ProgramType::SkLookup
+        | ProgramType::FlowDissector => &[Capability::Bpf, Capability::NetAdmin],
+        ProgramType::Ext => &[Capability::Bpf, Capability::Perfmon, Capability::NetAdmin],
+        _ => &[Capability::Bpf],
+    }
+}
+
+/// Parse the effective capability set (`CapEff`) from the contents of
+/// `/proc/<pid>/status`.
+fn parse_effective_caps(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+}
+
+/// Retrieve the effective capability set of the current process.
+pub(crate) fn effective_caps() -> Option<u64> {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .as_deref()
+        .and_then(parse_effective_caps)
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+}
+
+/// The parts of the system's configuration relevant to diagnosing
+/// permission errors.
+struct Environment {
+    /// The effective capability set of the current process.
+    effective: Option<u64>,
+    /// The value of the `kernel.unprivileged_bpf_disabled` sysctl.
+    unprivileged_bpf_disabled: Option<String>,
+    /// The list of active Linux Security Modules.
+    lsms: Option<String>,
+}
+
+impl Environment {
+    /// Gather the configuration of the current process and system.
+    fn current() -> Self {
+        Self {
+            effective: effective_caps(),
+            unprivileged_bpf_disabled: read_trimmed("/proc/sys/kernel/unprivileged_bpf_disabled"),
+            lsms: read_trimmed("/sys/kernel/security/lsm"),
+        }
+    }
+}
+
+/// Produce a human readable diagnosis of why an operation requiring the
+/// provided capabilities may have been denied in the given environment.
+fn diagnosis(required: &[Capability], env: &Environment) -> String {
+    let mut parts = Vec::new();
+
+    let missing = match env.effective {
+        Some(effective) => {
+            let missing = required
+                .iter()
+                .filter(|cap| !cap.is_granted(effective))
+                .map(|cap| match cap {
+                    // See `Capability::is_granted`.
+                    Capability::Bpf | Capability::Perfmon => {
+                        format!("{} (or CAP_SYS_ADMIN)", cap.name())
+                    }
+                    Capability::NetAdmin | Capability::SysAdmin => cap.name().to_string(),
+                })
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                parts.push(format!("process is likely missing {}", missing.join(", ")));
+            }
+            Some(missing)
+        }
+        None => {
+            parts.push("failed to determine effective capabilities".to_string());
+            None
+        }
+    };
+
+    if let Some(disabled) = &env.unprivileged_bpf_disabled {
+        parts.push(format!("kernel.unprivileged_bpf_disabled = {disabled}"));
+    }
+
+    let lsms = env.lsms.as_deref();
+    if missing.is_some_and(|missing| missing.is_empty()) {
+        // All required capabilities seem to be present, so the denial most
+        // likely stems from an LSM (or a seccomp filter).
+        let lsms = lsms.unwrap_or("unknown");
+        parts.push(format!(
+            "required capabilities are present; denial likely originates from an LSM (active: {lsms})"
+        ));
+    } else if let Some(lsms) = lsms {
+        parts.push(format!("active LSMs: {lsms}"));
+    }
+
+    parts.join("; ")
+}
+
+/// Enrich a permission error with a diagnosis of its likely cause.
+///
+/// Errors of any other kind are passed through unchanged.
+pub(crate) fn diagnose(err: Error, required: &[Capability]) -> Error {
+    if err.kind() != ErrorKind::PermissionDenied {
+        return err;
+    }
+    err.context(diagnosis(required, &Environment::current()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+
+
+    /// Check that we can parse the effective capability set from a
+    /// `/proc/<pid>/status` file.
+    #[test]
+    fn effective_caps_parsing() {
+        let status = "Name:\tcat\nCapInh:\t0000000000000000\nCapPrm:\t000001ffffffffff\nCapEff:\t000001ffffffffff\n";
+        let effective = parse_effective_caps(status).unwrap();
+        assert!(Capability::Bpf.is_granted(effective));
+        assert!(Capability::Perfmon.is_granted(effective));
+        assert!(Capability::NetAdmin.is_granted(effective));
+
+        let status = "CapEff:\t0000000000001000\n";
+        let effective = parse_effective_caps(status).unwrap();
+        assert!(Capability::NetAdmin.is_granted(effective));
+        assert!(!Capability::Bpf.is_granted(effective));
+
+        // CAP_SYS_ADMIN implies CAP_BPF and CAP_PERFMON only.
+        let status = "CapEff:\t0000000000200000\n";
+        let effective = parse_effective_caps(status).unwrap();
+        assert!(Capability::Bpf.is_granted(effective));
+        assert!(Capability::Perfmon.is_granted(effective));
+        assert!(!Capability::NetAdmin.is_granted(effective));
+
+        assert_eq!(parse_effective_caps("Name:\tcat\n"), None);
+    }
+
+    /// Check that only permission errors get diagnosed.
+    #[test]
+    fn permission_error_diagnosis() {
+        let err = Error::from(io::Error::from_raw_os_error(libc::ENOENT));
+        let err = diagnose(err, &[Capability::Bpf]);
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "No such file or directory (os error 2)");
+
+        let err = Error::from(io::Error::from_raw_os_error(libc::EPERM));
+        let err = diagnose(err, &[Capability::Bpf]);
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_ne!(err.to_string(), "Operation not permitted (os error 1)");
+    }
+
+    /// Check that `CAP_SYS_ADMIN` is only suggested for capabilities it
+    /// implies.
+    #[test]
+    fn net_admin_diagnosis() {
+        let env = Environment {
+            // CAP_SYS_ADMIN only.
+            effective: Some(0x200000),
+            unprivileged_bpf_disabled: None,
+            lsms: None,
+        };
+        let diagnosis = diagnosis(&[Capability::Bpf, Capability::NetAdmin], &env);
+        assert_eq!(diagnosis, "process is likely missing CAP_NET_ADMIN");
+        assert!(!diagnosis.contains("CAP_SYS_ADMIN"), "{diagnosis}");
+    }
+
+    /// Check that the diagnosis names missing capabilities and the
+    /// relevant system configuration.
+    #[test]
+    fn diagnosis_text() {
+        let env = Environment {
+            // CAP_NET_ADMIN only.
+            effective: Some(0x1000),
+            unprivileged_bpf_disabled: Some("2".to_string()),
+            lsms: Some("lockdown,capability,bpf".to_string()),
+        };
+        assert_eq!(
+            diagnosis(&[Capability::Bpf, Capability::NetAdmin], &env),
+            "process is likely missing CAP_BPF (or CAP_SYS_ADMIN); kernel.unprivileged_bpf_disabled = 2; active LSMs: lockdown,capability,bpf"
+        );
+
+        let env = Environment {
+            // CAP_SYS_ADMIN only.
+            effective: Some(0x200000),
+            unprivileged_bpf_disabled: None,
+            lsms: None,
+        };
+        assert_eq!(
+            diagnosis(&[Capability::Bpf, Capability::Perfmon], &env),
+            "required capabilities are present; denial likely originates from an LSM (active: unknown)"
+        );
+
+        let env = Environment {
+            effective: None,
+            unprivileged_bpf_disabled: None,
+            lsms: Some("selinux".to_string()),
+        };
+        assert_eq!(
+            diagnosis(&[Capability::Bpf], &env),
+            "failed to determine effective capabilities; active LSMs: selinux"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! A building block for sampling CPU profilers.
+//!
+//! A [`Profiler`] opens a sampling perf event on each online CPU, attaches
+//! a user provided `SEC("perf_event")` program to them, and periodically
+//! drains the sample counts the program collected, reporting them along
+//! with symbolized stack traces. The program is expected to count samples
+//! in a hash map keyed by the following structure, with a `u64` count as
+//! value, and to capture stack traces via `bpf_get_stackid` into a
+//! [`StackTrace`][crate::MapType::StackTrace] map:
+//!
+//! ```c
+//! struct profile_key {
+//!     u32 pid;
+//!     s32 kernel_stack_id;
+//!     s32 user_stack_id;
+//!     char comm[16];
+//! };
+//! ```
+//!
+//! Negative stack IDs (i.e., errors reported by `bpf_get_stackid`)
+//! indicate that the respective stack trace is not available. A minimal
+//! program looks like this:
+//!
+//! ```c
+//! SEC("perf_event")
+//! int profile(void *ctx)
+//! {
+//!     struct profile_key key = {};
+//!     u64 one = 1, *count;
+//!
+//!     key.pid = bpf_get_current_pid_tgid() >> 32;
+//!     key.kernel_stack_id = bpf_get_stackid(ctx, &stacks, 0);
+//!     key.user_stack_id = bpf_get_stackid(ctx, &stacks, BPF_F_USER_STACK);
+//!     bpf_get_current_comm(&key.comm, sizeof(key.comm));
+//!
+//!     count = bpf_map_lookup_elem(&counts, &key);
+//!     if (count)
+//!         __sync_fetch_and_add(count, 1);
+//!     else
+//!         bpf_map_update_elem(&counts, &key, &one, BPF_NOEXIST);
+//!     return 0;
+//! }
+//! ```
+//!
+//! On the user space side, only a sink for the symbolized samples needs
+//! to be provided:
+//!
+//! ```no_run
+//! # fn example(obj: &libbpf_rs::Object) -> libbpf_rs::Result<()> {
+//! use std::ops::ControlFlow;
+//! use std::time::Duration;
+//!
+//! use libbpf_rs::profiler::Profiler;
+//!
+//! let mut profiler = Profiler::new(
+//!     &obj.prog_mut("profile")?,
+//!     &obj.map("counts")?,
+//!     &obj.map("stacks")?,
+//!     99,
+//! )?;
+//! profiler.run(Duration::from_secs(1), |samples| {
+//!     for sample in samples {
+//!         println!("{} ({}): {}", sample.comm, sample.pid, sample.count);
+//!     }
+//!     ControlFlow::Continue(())
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::CStr;
+use std::io;
+use std::mem::size_of;
+use std::ops::ControlFlow;
+use std::os::fd::AsFd as _;
+use std::thread;
+use std::time::Duration;
+
+use crate::perf::PerfEventBuilder;
+use crate::perf::SoftwareEvent;
+use crate::symbolize::Frame;
+use crate::symbolize::StackOrigin;
+use crate::symbolize::StackTraceMap;
+use crate::symbolize::Symbolizer;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Link;
+use crate::MapCore;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapType;
+use crate::ProgramMut;
+use crate::Result;
+
+
+/// The number of entries to retrieve per batch when draining counts.
+const DRAIN_BATCH_SIZE: u32 = 256;
+
+
+/// Mirror of the `struct profile_key` the BPF program uses to count
+/// samples.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+struct ProfileKey {
+    pid: u32,
+    kernel_stack_id: i32,
+    user_stack_id: i32,
+    comm: [u8; 16],
+}
+
+impl ProfileKey {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        debug_assert_eq!(bytes.len(), size_of::<Self>());
+        // SAFETY: The slice has the size of a `ProfileKey`, which is valid
+        //         for any bit pattern.
+        unsafe { bytes.as_ptr().cast::<Self>().read_unaligned() }
+    }
+}
+
+
+/// A symbolized sample count, as reported by [`Profiler::drain`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Sample {
+    /// The ID of the sampled process.
+    pub pid: u32,
+    /// The command name of the sampled thread.
+    pub comm: String,
+    /// The kernel stack trace, innermost frame first, if available.
+    pub kernel_stack: Option<Vec<Frame>>,
+    /// The user space stack trace, innermost frame first, if available.
+    pub user_stack: Option<Vec<Frame>>,
+    /// The number of times this combination of process and stack traces
+    /// was sampled since the last drain.
+    pub count: u64,
+}
+
+
+/// A sampling CPU profiler, driving a user provided BPF program.
+///
+/// See the [module documentation][self] for the contract the program has
+/// to adhere to. Sampling stops once the profiler is dropped.
+#[derive(Debug)]
+pub struct Profiler {
+    _links: Vec<Link>,
+    counts: MapHandle,
+    stacks: StackTraceMap,
+    symbolizer: Symbolizer,
+}
+
+impl Profiler {
+    /// Start profiling all processes at a frequency of `freq` Hz on each
+    /// online CPU, by attaching `prog` to a CPU clock event per CPU.
+    ///
+    /// `counts` is the hash map the program counts samples in and
+    /// `stacks` the stack trace map it captures stack traces into.
+    pub fn new<C, S>(prog: &ProgramMut<'_>, counts: &C, stacks: &S, freq: u64) -> Result<Self>
+    where
+        C: MapCore,
+        S: MapCore,
+    {
+        Self::with_event(
+            prog,
+            counts,
+            stacks,
+            PerfEventBuilder::new(SoftwareEvent::CpuClock)
+                .sample_freq(freq)
+                .pid(-1),
+        )
+    }
+
+    /// Start profiling, attaching `prog` to perf events configured by
+    /// `event`, opened on each online CPU.
+    ///
+    /// This allows for sampling based on events other than time, e.g.,
+    /// cache misses, or for profiling a single process only.
+    pub fn with_event<C, S>(
+        prog: &ProgramMut<'_>,
+        counts: &C,
+        stacks: &S,
+        event: &PerfEventBuilder,
+    ) -> Result<Self>
+    where
+        C: MapCore,
+        S: MapCore,
+    {
+        if counts.key_size() as usize != size_of::<ProfileKey>()
+            || counts.value_size() as usize != size_of::<u64>()
+            || !matches!(counts.map_type(), MapType::Hash | MapType::LruHash)
+        {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "map `{}` is not a hash map with `struct profile_key` keys ({} bytes) and `u64` values",
+                    counts.name().to_string_lossy(),
+                    size_of::<ProfileKey>(),
+                ),
+            ))
+        }
+
+        let stacks = StackTraceMap::new(stacks)?;
+        let fd = counts
+            .as_fd()
+            .try_clone_to_owned()
+            .context("failed to duplicate map file descriptor")?;
+        let counts = MapHandle::from_fd(fd)?;
+        let links = prog.attach_perf_event_all_cpus(event)?;
+
+        let slf = Self {
+            _links: links,
+            counts,
+            stacks,
+            symbolizer: Symbolizer::new(),
+        };
+        Ok(slf)
+    }
+
+    /// Retrieve the stack trace with the given ID, if available.
+    ///
+    /// Counts are already gone from the map at this point, so a stack
+    /// trace that cannot be symbolized (e.g., because the process exited
+    /// in the meantime) is reported by its raw addresses instead of
+    /// failing the drain as a whole.
+    fn stack(&mut self, id: i32, origin: StackOrigin) -> Option<Vec<Frame>> {
+        let id = u32::try_from(id).ok()?;
+        match self.stacks.frames(id, origin, &mut self.symbolizer) {
+            Ok(frames) => frames,
+            Err(_err) => self.stacks.raw_frames(id).ok().flatten(),
+        }
+    }
+
+    /// Remove all sample counts collected so far, reporting them along
+    /// with their symbolized stack traces.
+    ///
+    /// Stack traces that cannot be symbolized, e.g., those of processes
+    /// that exited before the drain, are reported with unsymbolized
+    /// frames, or not at all if they cannot be retrieved either.
+    ///
+    /// Afterwards the stack traces referenced by the drained counts are
+    /// removed from the stack trace map, to make room for new ones.
+    /// Samples sharing a stack trace with a drained count that arrive
+    /// while draining may consequently get reported without it.
+    pub fn drain(&mut self) -> Result<Vec<Sample>> {
+        let entries = self
+            .counts
+            .lookup_and_delete_batch(DRAIN_BATCH_SIZE, MapFlags::ANY, MapFlags::ANY)?
+            .collect::<Result<Vec<_>>>()
+            .context("failed to drain sample counts")?;
+
+        let mut stack_ids = Vec::new();
+        let mut samples = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let key = ProfileKey::from_bytes(&key);
+            let count = u64::from_ne_bytes(value.as_slice().try_into().unwrap());
+            let comm = CStr::from_bytes_until_nul(&key.comm)
+                .map(|comm| comm.to_string_lossy().into_owned())
+                .unwrap_or_else(|_| String::from_utf8_lossy(&key.comm).into_owned());
+
+            let sample = Sample {
+                pid: key.pid,
+                comm,
+                kernel_stack: self.stack(key.kernel_stack_id, StackOrigin::Kernel),
+                user_stack: self.stack(key.user_stack_id, StackOrigin::User(key.pid)),
+                count,
+            };
+            let () = samples.push(sample);
+            let () = stack_ids.extend(
+                [key.kernel_stack_id, key.user_stack_id]
+                    .into_iter()
+                    .filter_map(|id| u32::try_from(id).ok()),
+            );
+        }
+
+        let () = stack_ids.sort_unstable();
+        let () = stack_ids.dedup();
+        for id in stack_ids {
+            // The stack trace may be gone already if the kernel
+            // reused its slot.
+            let _result = self.stacks.remove(id);
+        }
+        Ok(samples)
+    }
+
+    /// Drain sample counts every `interval`, reporting them to `sink`
+    /// until it asks to stop.
+    ///
+    /// Kernel symbols and the symbols of processes are cached across
+    /// drains; call [`Profiler::symbolizer`] to evict stale data, e.g.,
+    /// of processes that exited.
+    pub fn run<F>(&mut self, interval: Duration, mut sink: F) -> Result<()>
+    where
+        F: FnMut(Vec<Sample>) -> ControlFlow<()>,
+    {
+        loop {
+            let () = thread::sleep(interval);
+            let samples = self.drain()?;
+            if sink(samples).is_break() {
+                break Ok(())
+            }
+        }
+    }
+
+    /// Retrieve the symbolizer used for symbolizing stack traces.
+    pub fn symbolizer(&mut self) -> &mut Symbolizer {
+        &mut self.symbolizer
+    }
+}
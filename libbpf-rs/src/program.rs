@@ -5,6 +5,8 @@
 use std::ffi::c_void;
 use std::ffi::CStr;
 use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
 use std::marker::PhantomData;
 use std::mem;
 use std::mem::size_of;
@@ -12,24 +14,34 @@ use std::mem::size_of_val;
 use std::mem::transmute;
 use std::ops::Deref;
 use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::fs::OpenOptionsExt as _;
 use std::os::unix::io::AsFd;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::io::BorrowedFd;
 use std::os::unix::io::FromRawFd;
+use std::os::unix::io::IntoRawFd as _;
 use std::os::unix::io::OwnedFd;
 use std::path::Path;
 use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
 use libbpf_sys::bpf_func_id;
 
+use crate::perf::PerfEventBuilder;
+use crate::privilege;
+use crate::trace;
 use crate::util;
 use crate::util::validate_bpf_ret;
 use crate::util::BpfObjectType;
 use crate::AsRawLibbpf;
 use crate::Error;
 use crate::ErrorExt as _;
+use crate::ErrorKind;
+use crate::IterTarget;
 use crate::Link;
 use crate::Mut;
 use crate::Result;
@@ -107,6 +119,170 @@ impl From<TracepointOpts> for libbpf_sys::bpf_tracepoint_opts {
     }
 }
 
+/// The position at which to insert a program into the list of programs
+/// attached to a hook supporting multiple ordered programs, such as
+/// netkit devices.
+#[derive(Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub enum AttachOrder<'fd> {
+    /// Insert the program after all programs attached already.
+    #[default]
+    Last,
+    /// Insert the program before all programs attached already.
+    First,
+    /// Insert the program right before the attached program referred to
+    /// by the file descriptor.
+    Before(BorrowedFd<'fd>),
+    /// Insert the program right after the attached program referred to by
+    /// the file descriptor.
+    After(BorrowedFd<'fd>),
+    /// Insert the program right before the attached program with the
+    /// given ID.
+    BeforeId(u32),
+    /// Insert the program right after the attached program with the given
+    /// ID.
+    AfterId(u32),
+}
+
+impl AttachOrder<'_> {
+    /// Convert the order into the flags, relative file descriptor, and
+    /// relative ID expected by the kernel.
+    fn to_raw(self) -> (u32, u32, u32) {
+        let before = libbpf_sys::BPF_F_BEFORE;
+        let after = libbpf_sys::BPF_F_AFTER;
+        let id = libbpf_sys::BPF_F_ID;
+
+        match self {
+            Self::Last => (after, 0, 0),
+            Self::First => (before, 0, 0),
+            Self::Before(fd) => (before, fd.as_raw_fd() as u32, 0),
+            Self::After(fd) => (after, fd.as_raw_fd() as u32, 0),
+            Self::BeforeId(prog_id) => (before | id, 0, prog_id),
+            Self::AfterId(prog_id) => (after | id, 0, prog_id),
+        }
+    }
+}
+
+/// Options to optionally be provided when attaching to a netkit device.
+#[derive(Clone, Debug, Default)]
+pub struct NetkitOpts<'fd> {
+    /// The position at which to insert the program.
+    pub order: AttachOrder<'fd>,
+    /// Only attach the program if the revision of the device's program
+    /// list matches, guarding against concurrent modifications. Zero
+    /// disables the check.
+    pub expected_revision: u64,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl From<NetkitOpts<'_>> for libbpf_sys::bpf_netkit_opts {
+    fn from(opts: NetkitOpts<'_>) -> Self {
+        let NetkitOpts {
+            order,
+            expected_revision,
+            _non_exhaustive,
+        } = opts;
+        let (flags, relative_fd, relative_id) = order.to_raw();
+
+        #[allow(clippy::needless_update)]
+        libbpf_sys::bpf_netkit_opts {
+            sz: size_of::<Self>() as _,
+            flags,
+            relative_fd,
+            relative_id,
+            expected_revision,
+            // bpf_netkit_opts might have padding fields on some platform
+            ..Default::default()
+        }
+    }
+}
+
+/// The kernel object to attach a program to, as provided to
+/// [`ProgramMut::attach_to`].
+///
+/// Which targets are valid depends on the type of the program.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum AttachTarget<'fd> {
+    /// Attach to the target described by the program's section, e.g.,
+    /// `SEC("kprobe/do_unlinkat")` or `SEC("tp/sched/sched_switch")`. This
+    /// is equivalent to [`ProgramMut::attach`].
+    Section,
+    /// Attach to the cgroup referred to by the file descriptor. Valid for
+    /// `cgroup/...` and `sockops` programs.
+    Cgroup(BorrowedFd<'fd>),
+    /// Attach to the network namespace referred to by the file descriptor.
+    /// Valid for `sk_lookup` and `flow_dissector` programs.
+    Netns(BorrowedFd<'fd>),
+    /// Attach to the network interface with the given index. Valid for
+    /// `xdp` programs.
+    Interface(i32),
+}
+
+/// The kind of target a program has to be attached to explicitly, because
+/// it can't be inferred from the program's section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RequiredTarget {
+    Cgroup,
+    Netns,
+    Interface,
+}
+
+impl RequiredTarget {
+    /// Determine the kind of target programs of the given type need.
+    fn of(prog_type: ProgramType) -> Option<Self> {
+        match prog_type {
+            ProgramType::CgroupSkb
+            | ProgramType::CgroupSock
+            | ProgramType::CgroupSockAddr
+            | ProgramType::CgroupSockopt
+            | ProgramType::CgroupSysctl
+            | ProgramType::CgroupDevice
+            | ProgramType::SockOps => Some(Self::Cgroup),
+            ProgramType::SkLookup | ProgramType::FlowDissector => Some(Self::Netns),
+            ProgramType::Xdp => Some(Self::Interface),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Cgroup => "cgroup",
+            Self::Netns => "network namespace",
+            Self::Interface => "network interface",
+        }
+    }
+}
+
+/// Check whether libbpf can attach a program with the given section
+/// automatically, i.e., whether the section maps to an attach mechanism
+/// and describes a complete target for it.
+///
+/// This mirrors the section definitions of libbpf that provide an
+/// attach function and the cases in which these decline to attach.
+pub(crate) fn has_auto_attach_target(section: &str) -> bool {
+    let (kind, target) = match section.split_once('/') {
+        Some((kind, target)) => (kind, Some(target)),
+        None => (section, None),
+    };
+
+    match kind {
+        // The target is part of the section and attaching is declined if
+        // it is missing.
+        "kprobe" | "kretprobe" | "kprobe.multi" | "kretprobe.multi" | "ksyscall"
+        | "kretsyscall" | "uprobe" | "uprobe.s" | "uretprobe" | "uretprobe.s" | "uprobe.multi"
+        | "uprobe.multi.s" | "uretprobe.multi" | "uretprobe.multi.s" | "usdt" | "usdt.s"
+        | "tracepoint" | "tp" | "raw_tracepoint" | "raw_tp" | "raw_tracepoint.w" | "raw_tp.w" => {
+            target.map_or(false, |target| !target.is_empty())
+        }
+        // The target is determined when loading the program.
+        "tp_btf" | "fentry" | "fentry.s" | "fexit" | "fexit.s" | "fmod_ret" | "fmod_ret.s"
+        | "freplace" | "lsm" | "lsm.s" | "iter" | "iter.s" => true,
+        _ => false,
+    }
+}
+
 
 /// An immutable parsed but not yet loaded BPF program.
 pub type OpenProgram<'obj> = OpenProgramImpl<'obj>;
@@ -139,6 +315,37 @@ impl<'obj> OpenProgram<'obj> {
         ProgramType::from(unsafe { libbpf_sys::bpf_program__type(self.ptr.as_ptr()) })
     }
 
+    /// Retrieve the attach type the program is expected to be attached
+    /// with.
+    pub fn attach_type(&self) -> ProgramAttachType {
+        ProgramAttachType::from(unsafe {
+            libbpf_sys::bpf_program__expected_attach_type(self.ptr.as_ptr())
+        })
+    }
+
+    /// Return `true` if the program is set to be loaded along with the
+    /// object, `false` otherwise.
+    pub fn autoload(&self) -> bool {
+        unsafe { libbpf_sys::bpf_program__autoload(self.ptr.as_ptr()) }
+    }
+
+    /// Return `true` if the program is set to be attached by
+    /// [`Object::attach_all`][crate::Object::attach_all] or skeleton
+    /// attachment, `false` otherwise.
+    pub fn autoattach(&self) -> bool {
+        unsafe { libbpf_sys::bpf_program__autoattach(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the verifier log level the program will be loaded with.
+    pub fn log_level(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_program__log_level(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the flags the program will be loaded with.
+    pub fn flags(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_program__flags(self.ptr.as_ptr()) }
+    }
+
     /// Retrieve the name of this `OpenProgram`.
     pub fn name(&self) -> &OsStr {
         let name_ptr = unsafe { libbpf_sys::bpf_program__name(self.ptr.as_ptr()) };
@@ -192,23 +399,35 @@ impl<'obj> OpenProgramMut<'obj> {
         }
     }
 
-    /// Set the program type.
-    pub fn set_prog_type(&mut self, prog_type: ProgramType) {
+    /// Set the program type, overriding the one inferred from the name of
+    /// the section the program resides in.
+    ///
+    /// Doing so also discards the section specific behavior libbpf
+    /// derived from the section name, such as the way the program gets
+    /// attached by [`ProgramMut::attach`]. Use
+    /// [`OpenProgramMut::set_attach_type`] to set the expected attach type
+    /// if the new program type requires one.
+    pub fn set_prog_type(&mut self, prog_type: ProgramType) -> Result<()> {
         let rc = unsafe { libbpf_sys::bpf_program__set_type(self.ptr.as_ptr(), prog_type as u32) };
-        debug_assert!(util::parse_ret(rc).is_ok(), "{rc}");
+        util::parse_ret(rc)
     }
 
-    /// Set the attachment type of the program.
-    pub fn set_attach_type(&mut self, attach_type: ProgramAttachType) {
+    /// Set the attach type the program is expected to be attached with,
+    /// overriding the one inferred from the name of its section.
+    pub fn set_attach_type(&mut self, attach_type: ProgramAttachType) -> Result<()> {
         let rc = unsafe {
             libbpf_sys::bpf_program__set_expected_attach_type(self.ptr.as_ptr(), attach_type as u32)
         };
-        debug_assert!(util::parse_ret(rc).is_ok(), "{rc}");
+        util::parse_ret(rc)
     }
 
     /// Bind the program to a particular network device.
     ///
     /// Currently only used for hardware offload and certain XDP features such like HW metadata.
+    /// Only XDP and tc classifier programs can be offloaded, and only to devices whose driver
+    /// supports it; see [`offload::is_prog_type_supported`][crate::offload::is_prog_type_supported]
+    /// for probing for support. Offloaded XDP programs have to be attached via
+    /// [`Xdp::attach`][crate::Xdp::attach] with [`XdpFlags::HW_MODE`][crate::XdpFlags::HW_MODE].
     pub fn set_ifindex(&mut self, idx: u32) {
         unsafe { libbpf_sys::bpf_program__set_ifindex(self.ptr.as_ptr(), idx) }
     }
@@ -220,10 +439,16 @@ impl<'obj> OpenProgramMut<'obj> {
     /// details.
     ///
     /// In general, a value of `0` disables logging while values `> 0` enables
-    /// it.
-    pub fn set_log_level(&mut self, log_level: u32) {
+    /// it. At a level of exactly `4` (`BPF_LOG_STATS`) the kernel reports
+    /// nothing but statistics about the verification, which are available
+    /// via [`Object::verifier_stats`][crate::Object::verifier_stats] after
+    /// loading.
+    ///
+    /// The level set here takes precedence over the one configured for the
+    /// object as a whole, allowing for verbose logs of individual programs.
+    pub fn set_log_level(&mut self, log_level: u32) -> Result<()> {
         let rc = unsafe { libbpf_sys::bpf_program__set_log_level(self.ptr.as_ptr(), log_level) };
-        debug_assert!(util::parse_ret(rc).is_ok(), "{rc}");
+        util::parse_ret(rc)
     }
 
     /// Set whether a bpf program should be automatically loaded by default
@@ -233,12 +458,33 @@ impl<'obj> OpenProgramMut<'obj> {
         debug_assert!(util::parse_ret(rc).is_ok(), "{rc}");
     }
 
-    #[allow(missing_docs)]
+    /// Set whether a bpf program should be attached automatically by
+    /// [`Object::attach_all`][crate::Object::attach_all] or skeleton
+    /// attachment. Programs are auto-attached by default.
+    pub fn set_autoattach(&mut self, autoattach: bool) {
+        unsafe { libbpf_sys::bpf_program__set_autoattach(self.ptr.as_ptr(), autoattach) }
+    }
+
+    /// Set the target to attach this program to.
+    ///
+    /// `attach_prog_fd` refers to the BPF program to attach to (e.g., for
+    /// `freplace` programs); `None` targets the kernel itself.
+    ///
+    /// Tracing programs (`fentry`/`fexit`) and extension programs
+    /// (`freplace`) can hook functions of an already loaded BPF program by
+    /// providing its file descriptor along with the name of the function
+    /// (e.g., a global subprogram or the program itself). The target has
+    /// to be set before loading, as the program is verified against it.
+    /// Once loaded, tracing programs are attached via
+    /// [`ProgramMut::attach_trace`] and extension programs via
+    /// [`ProgramMut::attach_trace`] or, to replace a function in a
+    /// different but compatible program, [`ProgramMut::attach_freplace`].
     pub fn set_attach_target(
         &mut self,
-        attach_prog_fd: i32,
+        attach_prog_fd: Option<BorrowedFd<'_>>,
         attach_func_name: Option<String>,
     ) -> Result<()> {
+        let attach_prog_fd = attach_prog_fd.map_or(0, |fd| fd.as_raw_fd());
         let ret = if let Some(name) = attach_func_name {
             // NB: we must hold onto a CString otherwise our pointer dangles
             let name_c = util::str_to_cstring(&name)?;
@@ -291,6 +537,7 @@ impl<T> AsRawLibbpf for OpenProgramImpl<'_, T> {
 #[non_exhaustive]
 #[repr(u32)]
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document variants.
 #[allow(missing_docs)]
 pub enum ProgramType {
@@ -406,6 +653,7 @@ impl From<u32> for ProgramType {
 #[non_exhaustive]
 #[repr(u32)]
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document variants.
 #[allow(missing_docs)]
 pub enum ProgramAttachType {
@@ -451,6 +699,20 @@ pub enum ProgramAttachType {
     SkReuseportSelect,
     SkReuseportSelectOrMigrate,
     PerfEvent,
+    TraceKprobeMulti,
+    LsmCgroup,
+    StructOps,
+    Netfilter,
+    TcxIngress,
+    TcxEgress,
+    TraceUprobeMulti,
+    CgroupUnixConnect,
+    CgroupUnixSendmsg,
+    CgroupUnixRecvmsg,
+    CgroupUnixGetpeername,
+    CgroupUnixGetsockname,
+    NetkitPrimary,
+    NetkitPeer,
     /// See [`MapType::Unknown`][crate::MapType::Unknown]
     Unknown = u32::MAX,
 }
@@ -502,11 +764,48 @@ impl From<u32> for ProgramAttachType {
             x if x == SkReuseportSelect as u32 => SkReuseportSelect,
             x if x == SkReuseportSelectOrMigrate as u32 => SkReuseportSelectOrMigrate,
             x if x == PerfEvent as u32 => PerfEvent,
+            x if x == TraceKprobeMulti as u32 => TraceKprobeMulti,
+            x if x == LsmCgroup as u32 => LsmCgroup,
+            x if x == StructOps as u32 => StructOps,
+            x if x == Netfilter as u32 => Netfilter,
+            x if x == TcxIngress as u32 => TcxIngress,
+            x if x == TcxEgress as u32 => TcxEgress,
+            x if x == TraceUprobeMulti as u32 => TraceUprobeMulti,
+            x if x == CgroupUnixConnect as u32 => CgroupUnixConnect,
+            x if x == CgroupUnixSendmsg as u32 => CgroupUnixSendmsg,
+            x if x == CgroupUnixRecvmsg as u32 => CgroupUnixRecvmsg,
+            x if x == CgroupUnixGetpeername as u32 => CgroupUnixGetpeername,
+            x if x == CgroupUnixGetsockname as u32 => CgroupUnixGetsockname,
+            x if x == NetkitPrimary as u32 => NetkitPrimary,
+            x if x == NetkitPeer as u32 => NetkitPeer,
             _ => Unknown,
         }
     }
 }
 
+/// A program type specific context structure, as accepted and produced
+/// by [`Program::test_run`].
+///
+/// # Safety
+/// Implementors must be plain old data types that are valid for any bit
+/// pattern.
+pub unsafe trait TestRunContext: Copy {}
+
+// SAFETY: `__sk_buff` is a plain C struct of integers.
+unsafe impl TestRunContext for libbpf_sys::__sk_buff {}
+// SAFETY: `xdp_md` is a plain C struct of integers.
+unsafe impl TestRunContext for libbpf_sys::xdp_md {}
+
+fn context_bytes<C>(context: &mut C) -> &mut [u8]
+where
+    C: TestRunContext,
+{
+    // SAFETY: `TestRunContext` types are valid for any bit pattern, so
+    //         exposing them as bytes and having the kernel write to
+    //         them is sound.
+    unsafe { slice::from_raw_parts_mut((context as *mut C).cast::<u8>(), size_of::<C>()) }
+}
+
 /// The input a program accepts.
 ///
 /// This type is mostly used in conjunction with the [`Program::test_run`]
@@ -526,12 +825,47 @@ pub struct Input<'dat> {
     /// The 'cpu' value passed to the kernel.
     pub cpu: u32,
     /// The 'flags' value passed to the kernel.
+    ///
+    /// E.g., [`libbpf_sys::BPF_F_TEST_XDP_LIVE_FRAMES`] makes an `XDP`
+    /// program process frames as if they were received on the interface
+    /// of the input context (the loopback device by default), actually
+    /// redirecting or transmitting them. Output context and data are not
+    /// supported in this mode.
     pub flags: u32,
+    /// The number of times to run the program, with `0` being treated
+    /// as `1`.
+    pub repeat: u32,
+    /// The number of frames to allocate and process at a time in `XDP`
+    /// live frame mode, with `0` selecting the kernel's default.
+    pub batch_size: u32,
     /// The struct is non-exhaustive and open to extension.
     #[doc(hidden)]
     pub _non_exhaustive: (),
 }
 
+impl<'dat> Input<'dat> {
+    /// Provide a program type specific structure, such as a
+    /// [`__sk_buff`][libbpf_sys::__sk_buff] or an
+    /// [`xdp_md`][libbpf_sys::xdp_md], as input context.
+    pub fn with_context_in<C>(mut self, context: &'dat mut C) -> Self
+    where
+        C: TestRunContext,
+    {
+        self.context_in = Some(context_bytes(context));
+        self
+    }
+
+    /// Provide a program type specific structure to receive the context
+    /// as modified by the program.
+    pub fn with_context_out<C>(mut self, context: &'dat mut C) -> Self
+    where
+        C: TestRunContext,
+    {
+        self.context_out = Some(context_bytes(context));
+        self
+    }
+}
+
 /// The output a program produces.
 ///
 /// This type is mostly used in conjunction with the [`Program::test_run`]
@@ -544,11 +878,123 @@ pub struct Output<'dat> {
     pub context: Option<&'dat mut [u8]>,
     /// Output data filled by the program.
     pub data: Option<&'dat mut [u8]>,
+    /// The average duration of a single run of the program, as measured
+    /// by the kernel.
+    pub duration: Duration,
+    /// The struct is non-exhaustive and open to extension.
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+/// The number of batches [`ProgramMut::benchmark`] runs a program in.
+const BENCHMARK_BATCHES: usize = 100;
+
+/// Run time statistics of a program, as gathered by
+/// [`ProgramMut::benchmark`].
+///
+/// All durations refer to a single run of the program. As the kernel only
+/// reports the average duration of the runs of a batch, the minimum,
+/// percentile, and maximum are computed across batch averages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BenchmarkStats {
+    /// The total number of times the program was run.
+    pub runs: u64,
+    /// The shortest average run time of a batch.
+    pub min: Duration,
+    /// The average run time across all batches.
+    pub avg: Duration,
+    /// The 99th percentile of batch average run times.
+    pub p99: Duration,
+    /// The longest average run time of a batch.
+    pub max: Duration,
     /// The struct is non-exhaustive and open to extension.
     #[doc(hidden)]
     pub _non_exhaustive: (),
 }
 
+impl BenchmarkStats {
+    /// Compute statistics from per-batch average run times of batches of
+    /// `repeat` runs each.
+    fn from_batches(batches: &mut [Duration], repeat: u32) -> Self {
+        debug_assert!(!batches.is_empty());
+        let () = batches.sort_unstable();
+        let total = batches.iter().sum::<Duration>();
+        // Nearest-rank percentile.
+        let p99 = (batches.len() * 99 + 99) / 100 - 1;
+
+        Self {
+            runs: batches.len() as u64 * u64::from(repeat),
+            min: batches[0],
+            avg: total / batches.len() as u32,
+            p99: batches[p99],
+            max: batches[batches.len() - 1],
+            _non_exhaustive: (),
+        }
+    }
+}
+
+/// Statistics about the verification of a program, as reported by the
+/// kernel.
+///
+/// See [`Object::verifier_stats`][crate::Object::verifier_stats].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifierStats {
+    /// The time the verifier took.
+    pub verification_time: Option<Duration>,
+    /// The stack depth of the program and each of its subprograms.
+    pub stack_depth: Option<Vec<u32>>,
+    /// The number of instructions the verifier processed.
+    pub processed_insns: Option<u32>,
+    /// The maximum number of states the verifier kept for a single
+    /// instruction.
+    pub max_states_per_insn: Option<u32>,
+    /// The total number of states the verifier created.
+    pub total_states: Option<u32>,
+    /// The maximum number of states the verifier kept at any one time.
+    pub peak_states: Option<u32>,
+    /// The struct is non-exhaustive and open to extension.
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl VerifierStats {
+    /// Extract the statistics contained in a verifier log, if any.
+    ///
+    /// The kernel reports them at the end of the log, e.g.:
+    /// ```text
+    /// verification time 20 usec
+    /// stack depth 8+0
+    /// processed 12 insns (limit 1000000) max_states_per_insn 0 total_states 1 peak_states 1 mark_read 1
+    /// ```
+    pub(crate) fn from_log(log: &str) -> Option<Self> {
+        let mut stats = Self::default();
+        for line in log.lines() {
+            if let Some(time) = line
+                .strip_prefix("verification time ")
+                .and_then(|time| time.strip_suffix(" usec"))
+            {
+                stats.verification_time = time.parse().ok().map(Duration::from_micros);
+            } else if let Some(depth) = line.strip_prefix("stack depth ") {
+                stats.stack_depth = depth.split('+').map(|d| d.parse().ok()).collect();
+            } else if let Some(processed) = line.strip_prefix("processed ") {
+                let mut words = processed.split_whitespace();
+                stats.processed_insns = words.next().and_then(|count| count.parse().ok());
+                while let Some(word) = words.next() {
+                    let value = match word {
+                        "max_states_per_insn" => &mut stats.max_states_per_insn,
+                        "total_states" => &mut stats.total_states,
+                        "peak_states" => &mut stats.peak_states,
+                        _ => continue,
+                    };
+                    *value = words.next().and_then(|count| count.parse().ok());
+                }
+            }
+        }
+
+        (stats != Self::default()).then_some(stats)
+    }
+}
+
 /// An immutable loaded BPF program.
 pub type Program<'obj> = ProgramImpl<'obj>;
 /// A mutable loaded BPF program.
@@ -604,6 +1050,24 @@ impl<'obj> Program<'obj> {
         ProgramType::from(unsafe { libbpf_sys::bpf_program__type(self.ptr.as_ptr()) })
     }
 
+    /// Duplicate the program's file descriptor.
+    ///
+    /// The returned descriptor refers to the same kernel program and keeps
+    /// it loaded independently of the [`Object`][crate::Object] this
+    /// `Program` belongs to, allowing it to be handed to other threads or
+    /// components.
+    pub fn try_clone_fd(&self) -> Result<OwnedFd> {
+        self.as_fd()
+            .try_clone_to_owned()
+            .context("failed to duplicate program file descriptor")
+    }
+
+    /// Enrich a permission error with a diagnosis based on the
+    /// capabilities this program's type requires.
+    fn diagnose(&self, err: Error) -> Error {
+        privilege::diagnose(err, privilege::prog_type_capabilities(self.prog_type()))
+    }
+
     #[deprecated = "renamed to Program::fd_from_id"]
     #[allow(missing_docs)]
     #[inline]
@@ -674,6 +1138,75 @@ impl<'obj> Program<'obj> {
         }
     }
 
+    /// Load a program consisting of the raw instructions `insns` into the
+    /// kernel, without going through a BPF object.
+    ///
+    /// This is mostly useful for tiny programs assembled with
+    /// [`InsnBuilder`][crate::insn::InsnBuilder]. The program is released
+    /// once the returned file descriptor and all links and attachments
+    /// referencing it are closed. If the kernel rejects the program, the
+    /// verifier log is included in the returned error.
+    pub fn load_from_insns(
+        insns: &[libbpf_sys::bpf_insn],
+        prog_type: ProgramType,
+        license: &str,
+    ) -> Result<OwnedFd> {
+        let license = util::str_to_cstring(license)?;
+        let mut log = vec![0u8; 64 * 1024];
+        let mut opts = libbpf_sys::bpf_prog_load_opts {
+            sz: size_of::<libbpf_sys::bpf_prog_load_opts>() as _,
+            // libbpf retries with a log level of one if loading fails.
+            log_buf: log.as_mut_ptr().cast(),
+            log_size: log.len() as _,
+            ..Default::default()
+        };
+
+        // SAFETY: All pointers are valid for the duration of the call and
+        //         `log` outlives `opts`.
+        let ret = unsafe {
+            libbpf_sys::bpf_prog_load(
+                prog_type as u32,
+                ptr::null(),
+                license.as_ptr(),
+                insns.as_ptr(),
+                insns.len() as _,
+                &mut opts,
+            )
+        };
+        let fd = util::parse_ret_i32(ret).with_context(|| {
+            let len = log.iter().position(|b| *b == 0).unwrap_or(log.len());
+            let log = String::from_utf8_lossy(&log[..len]);
+            let log = log.trim_end();
+            if log.is_empty() {
+                "failed to load program".to_string()
+            } else {
+                format!("failed to load program; verifier log:\n{log}")
+            }
+        })?;
+        // SAFETY: A file descriptor coming from `bpf_prog_load` is always
+        //         suitable for ownership.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Bind the map referred to by `map_fd` to this program, keeping it
+    /// alive for as long as the program is loaded.
+    ///
+    /// This is necessary for maps that the program only uses indirectly,
+    /// e.g., via a file descriptor array, and which would otherwise be
+    /// freed once the last file descriptor referring to them is closed.
+    pub fn bind_map(&self, map_fd: BorrowedFd<'_>) -> Result<()> {
+        Self::bind_map_to_fd(self.as_fd(), map_fd)
+    }
+
+    /// Bind the map referred to by `map_fd` to the program referred to by
+    /// `prog_fd`. See [`Program::bind_map`].
+    pub fn bind_map_to_fd(prog_fd: BorrowedFd<'_>, map_fd: BorrowedFd<'_>) -> Result<()> {
+        let ret = unsafe {
+            libbpf_sys::bpf_prog_bind_map(prog_fd.as_raw_fd(), map_fd.as_raw_fd(), ptr::null())
+        };
+        util::parse_ret(ret)
+    }
+
     /// Returns flags that have been set for the program.
     pub fn flags(&self) -> u32 {
         unsafe { libbpf_sys::bpf_program__flags(self.ptr.as_ptr()) }
@@ -691,6 +1224,12 @@ impl<'obj> Program<'obj> {
         unsafe { libbpf_sys::bpf_program__autoload(self.ptr.as_ptr()) }
     }
 
+    /// Return `true` if the bpf program is set to autoattach, `false`
+    /// otherwise.
+    pub fn autoattach(&self) -> bool {
+        unsafe { libbpf_sys::bpf_program__autoattach(self.ptr.as_ptr()) }
+    }
+
     /// Return the bpf program's log level.
     pub fn log_level(&self) -> u32 {
         unsafe { libbpf_sys::bpf_program__log_level(self.ptr.as_ptr()) }
@@ -743,31 +1282,144 @@ impl<'obj> ProgramMut<'obj> {
     }
 
     /// Auto-attach based on prog section
+    ///
+    /// This dispatches to the attach mechanism matching the program's
+    /// section, as `bpf_program__attach` does, e.g., `SEC("kprobe/<func>")`
+    /// attaches a kprobe and `SEC("tp/<category>/<name>")` a tracepoint.
+    /// Sections that don't describe a complete target (e.g., `SEC("xdp")`
+    /// or `SEC("cgroup/connect4")`) cause an error of kind
+    /// [`Unsupported`][crate::ErrorKind::Unsupported]; use
+    /// [`ProgramMut::attach_to`] to provide the target for those.
     pub fn attach(&self) -> Result<Link> {
-        let ptr = unsafe { libbpf_sys::bpf_program__attach(self.ptr.as_ptr()) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach BPF program")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach", &name, || {
+            let ptr = unsafe { libbpf_sys::bpf_program__attach(self.ptr.as_ptr()) };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .map_err(|err| match err.kind() {
+                    ErrorKind::Unsupported => err.context(format!(
+                        "section `{}` does not describe an attach target{}",
+                        self.section().to_string_lossy(),
+                        RequiredTarget::of(self.prog_type())
+                            .map(|target| {
+                                format!("; provide a {} via `attach_to`", target.describe())
+                            })
+                            .unwrap_or_default(),
+                    )),
+                    _ => err,
+                })
+                .context("failed to attach BPF program")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
+    }
+
+    /// Attach this program to `target`, dispatching to the attach
+    /// mechanism appropriate for the program's type.
+    ///
+    /// This allows for attaching programs of different types through a
+    /// single code path, e.g., in generic loaders. Targets not valid for the
+    /// program's type are rejected with an error of kind
+    /// [`InvalidInput`][crate::ErrorKind::InvalidInput].
+    pub fn attach_to(&self, target: AttachTarget<'_>) -> Result<Link> {
+        let prog_type = self.prog_type();
+        match (target, RequiredTarget::of(prog_type)) {
+            (AttachTarget::Section, _) => self.attach(),
+            (AttachTarget::Cgroup(fd), Some(RequiredTarget::Cgroup)) => self.attach_cgroup(fd),
+            (AttachTarget::Netns(fd), Some(RequiredTarget::Netns)) => self.attach_netns(fd),
+            (AttachTarget::Interface(ifindex), Some(RequiredTarget::Interface)) => {
+                self.attach_xdp(ifindex)
+            }
+            (target, _) => Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot attach program `{}` of type {prog_type:?} to {target:?}",
+                    self.name().to_string_lossy(),
+                ),
+            )),
+        }
     }
 
     /// Attach this program to a
     /// [cgroup](https://www.kernel.org/doc/html/latest/admin-guide/cgroup-v2.html).
-    pub fn attach_cgroup(&self, cgroup_fd: i32) -> Result<Link> {
-        let ptr = unsafe { libbpf_sys::bpf_program__attach_cgroup(self.ptr.as_ptr(), cgroup_fd) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach cgroup")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+    pub fn attach_cgroup(&self, cgroup_fd: BorrowedFd<'_>) -> Result<Link> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_cgroup", &name, || {
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_cgroup(self.ptr.as_ptr(), cgroup_fd.as_raw_fd())
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach cgroup")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
+    }
+
+    /// Attach this program to the
+    /// [cgroup](https://www.kernel.org/doc/html/latest/admin-guide/cgroup-v2.html)
+    /// at the provided path, e.g., `/sys/fs/cgroup/system.slice`.
+    ///
+    /// The cgroup directory is only kept open for the duration of the
+    /// call; the returned [`Link`] keeps the attachment alive on its own.
+    pub fn attach_cgroup_path<P: AsRef<Path>>(&self, path: P) -> Result<Link> {
+        let path = path.as_ref();
+        let cgroup = File::options()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY)
+            .open(path)
+            .with_context(|| format!("failed to open cgroup `{}`", path.display()))?;
+        self.attach_cgroup(cgroup.as_fd())
     }
 
     /// Attach this program to a [perf event](https://linux.die.net/man/2/perf_event_open).
-    pub fn attach_perf_event(&self, pfd: i32) -> Result<Link> {
-        let ptr = unsafe { libbpf_sys::bpf_program__attach_perf_event(self.ptr.as_ptr(), pfd) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach perf event")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+    ///
+    /// The returned [`Link`] takes ownership of `pfd` and closes it once
+    /// destroyed. [`PerfEvent`][crate::perf::PerfEvent] objects can be
+    /// converted into an [`OwnedFd`] for this purpose.
+    pub fn attach_perf_event(&self, pfd: OwnedFd) -> Result<Link> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_perf_event", &name, || {
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_perf_event(self.ptr.as_ptr(), pfd.as_raw_fd())
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach perf event")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            // The link closes the file descriptor once destroyed.
+            let _fd = pfd.into_raw_fd();
+            Ok(link)
+        })
+    }
+
+    /// Attach this program to a perf event on each online CPU, as
+    /// commonly done by profilers.
+    ///
+    /// One event is opened per online CPU, as configured by `event`, with
+    /// the CPU overridden. Use [`PerfEventBuilder::pid`] to select the
+    /// process to monitor, `-1` referring to all processes.
+    ///
+    /// Links are reported in the order of the CPUs they are attached on.
+    /// Should attaching on any CPU fail, all links created so far are
+    /// destroyed.
+    pub fn attach_perf_event_all_cpus(&self, event: &PerfEventBuilder) -> Result<Vec<Link>> {
+        let mut event = event.clone();
+        let mut links = Vec::new();
+        for cpu in util::online_cpus()? {
+            let pfd = event
+                .cpu(cpu as i32)
+                .open()
+                .with_context(|| format!("failed to open perf event on CPU {cpu}"))?;
+            let link = self
+                .attach_perf_event(pfd.into())
+                .with_context(|| format!("failed to attach to perf event on CPU {cpu}"))?;
+            let () = links.push(link);
+        }
+        Ok(links)
     }
 
     /// Attach this program to a [userspace
@@ -779,21 +1431,26 @@ impl<'obj> ProgramMut<'obj> {
         binary_path: T,
         func_offset: usize,
     ) -> Result<Link> {
-        let path = util::path_to_cstring(binary_path)?;
-        let path_ptr = path.as_ptr();
-        let ptr = unsafe {
-            libbpf_sys::bpf_program__attach_uprobe(
-                self.ptr.as_ptr(),
-                retprobe,
-                pid,
-                path_ptr,
-                func_offset as libbpf_sys::size_t,
-            )
-        };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach uprobe")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_uprobe", &name, || {
+            let path = util::path_to_cstring(binary_path)?;
+            let path_ptr = path.as_ptr();
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_uprobe(
+                    self.ptr.as_ptr(),
+                    retprobe,
+                    pid,
+                    path_ptr,
+                    func_offset as libbpf_sys::size_t,
+                )
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach uprobe")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Attach this program to a [userspace
@@ -806,72 +1463,142 @@ impl<'obj> ProgramMut<'obj> {
         func_offset: usize,
         opts: UprobeOpts,
     ) -> Result<Link> {
-        let path = util::path_to_cstring(binary_path)?;
-        let path_ptr = path.as_ptr();
-        let UprobeOpts {
-            ref_ctr_offset,
-            cookie,
-            retprobe,
-            func_name,
-            _non_exhaustive,
-        } = opts;
-
-        let func_name = util::str_to_cstring(&func_name)?;
-        let opts = libbpf_sys::bpf_uprobe_opts {
-            sz: size_of::<libbpf_sys::bpf_uprobe_opts>() as _,
-            ref_ctr_offset: ref_ctr_offset as libbpf_sys::size_t,
-            bpf_cookie: cookie,
-            retprobe,
-            func_name: func_name.as_ptr(),
-            ..Default::default()
-        };
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_uprobe", &name, || {
+            let path = util::path_to_cstring(binary_path)?;
+            let path_ptr = path.as_ptr();
+            let UprobeOpts {
+                ref_ctr_offset,
+                cookie,
+                retprobe,
+                func_name,
+                _non_exhaustive,
+            } = opts;
+
+            let func_name = util::str_to_cstring(&func_name)?;
+            let opts = libbpf_sys::bpf_uprobe_opts {
+                sz: size_of::<libbpf_sys::bpf_uprobe_opts>() as _,
+                ref_ctr_offset: ref_ctr_offset as libbpf_sys::size_t,
+                bpf_cookie: cookie,
+                retprobe,
+                func_name: func_name.as_ptr(),
+                ..Default::default()
+            };
 
-        let ptr = unsafe {
-            libbpf_sys::bpf_program__attach_uprobe_opts(
-                self.ptr.as_ptr(),
-                pid,
-                path_ptr,
-                func_offset as libbpf_sys::size_t,
-                &opts as *const _,
-            )
-        };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach uprobe")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_uprobe_opts(
+                    self.ptr.as_ptr(),
+                    pid,
+                    path_ptr,
+                    func_offset as libbpf_sys::size_t,
+                    &opts as *const _,
+                )
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach uprobe")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Attach this program to a [kernel
     /// probe](https://www.kernel.org/doc/html/latest/trace/kprobetrace.html).
+    ///
+    /// Functions of kernel modules can be referred to in the form
+    /// `<module>:<function>`, e.g., `nf_conntrack:nf_ct_delete`. If the
+    /// module is not loaded, an error of kind
+    /// [`NotFound`][crate::ErrorKind::NotFound] is reported; use
+    /// [`ProgramMut::attach_kprobe_wait`] to wait for it to get loaded.
     pub fn attach_kprobe<T: AsRef<str>>(&self, retprobe: bool, func_name: T) -> Result<Link> {
-        let func_name = util::str_to_cstring(func_name.as_ref())?;
-        let func_name_ptr = func_name.as_ptr();
-        let ptr = unsafe {
-            libbpf_sys::bpf_program__attach_kprobe(self.ptr.as_ptr(), retprobe, func_name_ptr)
-        };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach kprobe")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_kprobe", &name, || {
+            let func_name = func_name.as_ref();
+            let module = util::split_module_symbol(func_name).map(|(module, _func)| module);
+            if let Some(module) = module {
+                if !util::kernel_module_loaded(module) {
+                    return Err(Error::with_io_error(
+                        io::ErrorKind::NotFound,
+                        format!("kernel module `{module}` providing `{func_name}` is not loaded"),
+                    ))
+                }
+            }
+
+            let func_name_c = util::str_to_cstring(func_name)?;
+            let func_name_ptr = func_name_c.as_ptr();
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_kprobe(self.ptr.as_ptr(), retprobe, func_name_ptr)
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .map_err(|err| match module {
+                    Some(module) if err.kind() == ErrorKind::NotFound => err.context(format!(
+                        "function `{func_name}` not found in kernel module `{module}`"
+                    )),
+                    _ => err,
+                })
+                .context("failed to attach kprobe")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
+    }
+
+    /// Attach this program to a [kernel
+    /// probe](https://www.kernel.org/doc/html/latest/trace/kprobetrace.html),
+    /// waiting for up to `timeout` for the kernel module providing the
+    /// function to get loaded.
+    ///
+    /// For functions not referring to a module (see
+    /// [`ProgramMut::attach_kprobe`]) this is the same as attaching
+    /// right away. If the module does not get loaded in time, an error of
+    /// kind [`TimedOut`][crate::ErrorKind::TimedOut] is reported.
+    pub fn attach_kprobe_wait<T: AsRef<str>>(
+        &self,
+        retprobe: bool,
+        func_name: T,
+        timeout: Duration,
+    ) -> Result<Link> {
+        let func_name = func_name.as_ref();
+        if let Some((module, _func)) = util::split_module_symbol(func_name) {
+            let start = Instant::now();
+            while !util::kernel_module_loaded(module) {
+                let remaining = timeout.saturating_sub(start.elapsed());
+                if remaining.is_zero() {
+                    return Err(Error::with_io_error(
+                        io::ErrorKind::TimedOut,
+                        format!("timed out waiting for kernel module `{module}` to get loaded"),
+                    ))
+                }
+                let () = thread::sleep(remaining.min(Duration::from_millis(100)));
+            }
+        }
+        self.attach_kprobe(retprobe, func_name)
     }
 
     /// Attach this program to the specified syscall
     pub fn attach_ksyscall<T: AsRef<str>>(&self, retprobe: bool, syscall_name: T) -> Result<Link> {
-        let opts = libbpf_sys::bpf_ksyscall_opts {
-            sz: size_of::<libbpf_sys::bpf_ksyscall_opts>() as _,
-            retprobe,
-            ..Default::default()
-        };
-
-        let syscall_name = util::str_to_cstring(syscall_name.as_ref())?;
-        let syscall_name_ptr = syscall_name.as_ptr();
-        let ptr = unsafe {
-            libbpf_sys::bpf_program__attach_ksyscall(self.ptr.as_ptr(), syscall_name_ptr, &opts)
-        };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach ksyscall")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_ksyscall", &name, || {
+            let opts = libbpf_sys::bpf_ksyscall_opts {
+                sz: size_of::<libbpf_sys::bpf_ksyscall_opts>() as _,
+                retprobe,
+                ..Default::default()
+            };
+
+            let syscall_name = util::str_to_cstring(syscall_name.as_ref())?;
+            let syscall_name_ptr = syscall_name.as_ptr();
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_ksyscall(self.ptr.as_ptr(), syscall_name_ptr, &opts)
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach ksyscall")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     fn attach_tracepoint_impl(
@@ -880,35 +1607,40 @@ impl<'obj> ProgramMut<'obj> {
         tp_name: &str,
         tp_opts: Option<TracepointOpts>,
     ) -> Result<Link> {
-        let tp_category = util::str_to_cstring(tp_category)?;
-        let tp_category_ptr = tp_category.as_ptr();
-        let tp_name = util::str_to_cstring(tp_name)?;
-        let tp_name_ptr = tp_name.as_ptr();
-
-        let ptr = if let Some(tp_opts) = tp_opts {
-            let tp_opts = libbpf_sys::bpf_tracepoint_opts::from(tp_opts);
-            unsafe {
-                libbpf_sys::bpf_program__attach_tracepoint_opts(
-                    self.ptr.as_ptr(),
-                    tp_category_ptr,
-                    tp_name_ptr,
-                    &tp_opts as *const _,
-                )
-            }
-        } else {
-            unsafe {
-                libbpf_sys::bpf_program__attach_tracepoint(
-                    self.ptr.as_ptr(),
-                    tp_category_ptr,
-                    tp_name_ptr,
-                )
-            }
-        };
-
-        let ptr = validate_bpf_ret(ptr).context("failed to attach tracepoint")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_tracepoint", &name, || {
+            let tp_category = util::str_to_cstring(tp_category)?;
+            let tp_category_ptr = tp_category.as_ptr();
+            let tp_name = util::str_to_cstring(tp_name)?;
+            let tp_name_ptr = tp_name.as_ptr();
+
+            let ptr = if let Some(tp_opts) = tp_opts {
+                let tp_opts = libbpf_sys::bpf_tracepoint_opts::from(tp_opts);
+                unsafe {
+                    libbpf_sys::bpf_program__attach_tracepoint_opts(
+                        self.ptr.as_ptr(),
+                        tp_category_ptr,
+                        tp_name_ptr,
+                        &tp_opts as *const _,
+                    )
+                }
+            } else {
+                unsafe {
+                    libbpf_sys::bpf_program__attach_tracepoint(
+                        self.ptr.as_ptr(),
+                        tp_category_ptr,
+                        tp_name_ptr,
+                    )
+                }
+            };
+
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach tracepoint")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Attach this program to a [kernel
@@ -936,64 +1668,197 @@ impl<'obj> ProgramMut<'obj> {
     /// Attach this program to a [raw kernel
     /// tracepoint](https://lwn.net/Articles/748352/).
     pub fn attach_raw_tracepoint<T: AsRef<str>>(&self, tp_name: T) -> Result<Link> {
-        let tp_name = util::str_to_cstring(tp_name.as_ref())?;
-        let tp_name_ptr = tp_name.as_ptr();
-        let ptr = unsafe {
-            libbpf_sys::bpf_program__attach_raw_tracepoint(self.ptr.as_ptr(), tp_name_ptr)
-        };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach raw tracepoint")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_raw_tracepoint", &name, || {
+            let tp_name = util::str_to_cstring(tp_name.as_ref())?;
+            let tp_name_ptr = tp_name.as_ptr();
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_raw_tracepoint(self.ptr.as_ptr(), tp_name_ptr)
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach raw tracepoint")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Attach to an [LSM](https://en.wikipedia.org/wiki/Linux_Security_Modules) hook
     pub fn attach_lsm(&self) -> Result<Link> {
-        let ptr = unsafe { libbpf_sys::bpf_program__attach_lsm(self.ptr.as_ptr()) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach LSM")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_lsm", &name, || {
+            let ptr = unsafe { libbpf_sys::bpf_program__attach_lsm(self.ptr.as_ptr()) };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach LSM")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Attach to a [fentry/fexit kernel probe](https://lwn.net/Articles/801479/)
     pub fn attach_trace(&self) -> Result<Link> {
-        let ptr = unsafe { libbpf_sys::bpf_program__attach_trace(self.ptr.as_ptr()) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach fentry/fexit kernel probe")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_trace", &name, || {
+            let ptr = unsafe { libbpf_sys::bpf_program__attach_trace(self.ptr.as_ptr()) };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach fentry/fexit kernel probe")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
+    }
+
+    /// Attach this extension (`freplace`) program, replacing the function
+    /// `func_name` of the loaded BPF program referred to by `target_fd`.
+    ///
+    /// The program has to have been loaded against a target with a
+    /// compatible function signature (see
+    /// [`OpenProgramMut::set_attach_target`]), which may be a different
+    /// program than `target_fd`, allowing a single extension program to
+    /// replace functions in multiple programs. The replacement stays in
+    /// effect until the returned [`Link`] is destroyed.
+    pub fn attach_freplace(&self, target_fd: BorrowedFd<'_>, func_name: &str) -> Result<Link> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_freplace", &name, || {
+            let func_name = util::str_to_cstring(func_name)?;
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_freplace(
+                    self.ptr.as_ptr(),
+                    target_fd.as_raw_fd(),
+                    func_name.as_ptr(),
+                )
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach extension program")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Attach a verdict/parser to a [sockmap/sockhash](https://lwn.net/Articles/731133/)
-    pub fn attach_sockmap(&self, map_fd: i32) -> Result<()> {
-        let err = unsafe {
-            libbpf_sys::bpf_prog_attach(
-                self.as_fd().as_raw_fd(),
-                map_fd,
-                self.attach_type() as u32,
-                0,
+    pub fn attach_sockmap(&self, map_fd: BorrowedFd<'_>) -> Result<()> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_sockmap", &name, || {
+            let err = unsafe {
+                libbpf_sys::bpf_prog_attach(
+                    self.as_fd().as_raw_fd(),
+                    map_fd.as_raw_fd(),
+                    self.attach_type() as u32,
+                    0,
+                )
+            };
+            util::parse_ret(err).map_err(|err| self.diagnose(err))
+        })
+    }
+
+    /// Attach this program to `socket` via `SO_ATTACH_BPF`, replacing any
+    /// filter previously attached to it.
+    ///
+    /// This is meant for `SEC("socket")` programs, which act as filters for
+    /// the packets received on the socket.
+    pub fn attach_socket(&self, socket: BorrowedFd<'_>) -> Result<()> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_socket", &name, || {
+            let prog_fd = self.as_fd().as_raw_fd();
+            util::setsockopt(socket, libc::SOL_SOCKET, libc::SO_ATTACH_BPF, &prog_fd)
+                .map_err(|err| self.diagnose(err))
+        })
+    }
+
+    /// Detach the filter attached to `socket` via `SO_DETACH_BPF`.
+    ///
+    /// Note that a socket has at most one filter attached and the kernel
+    /// does not check that it is this program.
+    pub fn detach_socket(&self, socket: BorrowedFd<'_>) -> Result<()> {
+        let unused: libc::c_int = 0;
+        util::setsockopt(socket, libc::SOL_SOCKET, libc::SO_DETACH_BPF, &unused)
+    }
+
+    /// Attach this program to the `SO_REUSEPORT` group `socket` belongs to,
+    /// letting it select the socket handling incoming connections or
+    /// datagrams. Use [`reuseport::detach`][crate::reuseport::detach] to
+    /// detach it again.
+    ///
+    /// The socket has to have `SO_REUSEPORT` set. See the
+    /// [`reuseport`][crate::reuseport] module for details.
+    pub fn attach_reuseport(&self, socket: BorrowedFd<'_>) -> Result<()> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_reuseport", &name, || {
+            let prog_fd = self.as_fd().as_raw_fd();
+            util::setsockopt(
+                socket,
+                libc::SOL_SOCKET,
+                libc::SO_ATTACH_REUSEPORT_EBPF,
+                &prog_fd,
             )
-        };
-        util::parse_ret(err)
+            .map_err(|err| self.diagnose(err))
+        })
     }
 
     /// Attach this program to [XDP](https://lwn.net/Articles/825998/)
     pub fn attach_xdp(&self, ifindex: i32) -> Result<Link> {
-        let ptr = unsafe { libbpf_sys::bpf_program__attach_xdp(self.ptr.as_ptr(), ifindex) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach XDP program")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_xdp", &name, || {
+            let ptr = unsafe { libbpf_sys::bpf_program__attach_xdp(self.ptr.as_ptr(), ifindex) };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach XDP program")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
+    }
+
+    /// Attach this program to the [netkit](https://lwn.net/Articles/949960/)
+    /// device with the given interface index.
+    ///
+    /// Whether the program handles traffic of the primary device or its
+    /// peer is determined by the program's attach type, i.e., by it being
+    /// defined in a `SEC("netkit/primary")` or `SEC("netkit/peer")` section.
+    /// The program gets added after all programs attached already; use
+    /// [`ProgramMut::attach_netkit_with_opts`] to control its position.
+    pub fn attach_netkit(&self, ifindex: i32) -> Result<Link> {
+        self.attach_netkit_with_opts(ifindex, NetkitOpts::default())
+    }
+
+    /// Attach this program to the [netkit](https://lwn.net/Articles/949960/)
+    /// device with the given interface index, providing additional options.
+    pub fn attach_netkit_with_opts(&self, ifindex: i32, opts: NetkitOpts<'_>) -> Result<Link> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_netkit", &name, || {
+            let opts = libbpf_sys::bpf_netkit_opts::from(opts);
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_netkit(self.ptr.as_ptr(), ifindex, &opts)
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach netkit program")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Attach this program to [netns-based programs](https://lwn.net/Articles/819618/)
-    pub fn attach_netns(&self, netns_fd: i32) -> Result<Link> {
-        let ptr = unsafe { libbpf_sys::bpf_program__attach_netns(self.ptr.as_ptr(), netns_fd) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach network namespace program")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+    pub fn attach_netns(&self, netns_fd: BorrowedFd<'_>) -> Result<Link> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_netns", &name, || {
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_netns(self.ptr.as_ptr(), netns_fd.as_raw_fd())
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach network namespace program")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     fn attach_usdt_impl(
@@ -1004,32 +1869,37 @@ impl<'obj> ProgramMut<'obj> {
         usdt_name: &str,
         usdt_opts: Option<UsdtOpts>,
     ) -> Result<Link> {
-        let path = util::path_to_cstring(binary_path)?;
-        let path_ptr = path.as_ptr();
-        let usdt_provider = util::str_to_cstring(usdt_provider)?;
-        let usdt_provider_ptr = usdt_provider.as_ptr();
-        let usdt_name = util::str_to_cstring(usdt_name)?;
-        let usdt_name_ptr = usdt_name.as_ptr();
-        let usdt_opts = usdt_opts.map(libbpf_sys::bpf_usdt_opts::from);
-        let usdt_opts_ptr = usdt_opts
-            .as_ref()
-            .map(|opts| opts as *const _)
-            .unwrap_or_else(ptr::null);
-
-        let ptr = unsafe {
-            libbpf_sys::bpf_program__attach_usdt(
-                self.ptr.as_ptr(),
-                pid,
-                path_ptr,
-                usdt_provider_ptr,
-                usdt_name_ptr,
-                usdt_opts_ptr,
-            )
-        };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach USDT")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_usdt", &name, || {
+            let path = util::path_to_cstring(binary_path)?;
+            let path_ptr = path.as_ptr();
+            let usdt_provider = util::str_to_cstring(usdt_provider)?;
+            let usdt_provider_ptr = usdt_provider.as_ptr();
+            let usdt_name = util::str_to_cstring(usdt_name)?;
+            let usdt_name_ptr = usdt_name.as_ptr();
+            let usdt_opts = usdt_opts.map(libbpf_sys::bpf_usdt_opts::from);
+            let usdt_opts_ptr = usdt_opts
+                .as_ref()
+                .map(|opts| opts as *const _)
+                .unwrap_or_else(ptr::null);
+
+            let ptr = unsafe {
+                libbpf_sys::bpf_program__attach_usdt(
+                    self.ptr.as_ptr(),
+                    pid,
+                    path_ptr,
+                    usdt_provider_ptr,
+                    usdt_name_ptr,
+                    usdt_opts_ptr,
+                )
+            };
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach USDT")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Attach this program to a [USDT](https://lwn.net/Articles/753601/) probe
@@ -1075,25 +1945,40 @@ impl<'obj> ProgramMut<'obj> {
     /// [BPF Iterator](https://www.kernel.org/doc/html/latest/bpf/bpf_iterators.html).
     /// The entry point of the program must be defined with `SEC("iter")` or `SEC("iter.s")`.
     pub fn attach_iter(&self, map_fd: BorrowedFd<'_>) -> Result<Link> {
-        let mut linkinfo = libbpf_sys::bpf_iter_link_info::default();
-        linkinfo.map.map_fd = map_fd.as_raw_fd() as _;
-        let attach_opt = libbpf_sys::bpf_iter_attach_opts {
-            link_info: &mut linkinfo as *mut libbpf_sys::bpf_iter_link_info,
-            link_info_len: size_of::<libbpf_sys::bpf_iter_link_info>() as _,
-            sz: size_of::<libbpf_sys::bpf_iter_attach_opts>() as _,
-            ..Default::default()
-        };
-        let ptr = unsafe {
-            libbpf_sys::bpf_program__attach_iter(
-                self.ptr.as_ptr(),
-                &attach_opt as *const libbpf_sys::bpf_iter_attach_opts,
-            )
-        };
+        self.attach_iter_target(IterTarget::Map(map_fd))
+    }
 
-        let ptr = validate_bpf_ret(ptr).context("failed to attach iterator")?;
-        // SAFETY: the pointer came from libbpf and has been checked for errors.
-        let link = unsafe { Link::new(ptr) };
-        Ok(link)
+    /// Attach this program to a
+    /// [BPF Iterator](https://www.kernel.org/doc/html/latest/bpf/bpf_iterators.html),
+    /// iterating over the provided target.
+    ///
+    /// Use [`Link::iter_create`] to run the iterator and read its output.
+    pub fn attach_iter_target(&self, target: IterTarget<'_>) -> Result<Link> {
+        let name = self.name().to_string_lossy();
+        trace::instrument("attach_iter", &name, || {
+            // Unparameterized iterators are attached without options.
+            let mut linkinfo = target.link_info();
+            let attach_opt = linkinfo
+                .as_mut()
+                .map(|linkinfo| libbpf_sys::bpf_iter_attach_opts {
+                    link_info: linkinfo as *mut libbpf_sys::bpf_iter_link_info,
+                    link_info_len: size_of::<libbpf_sys::bpf_iter_link_info>() as _,
+                    sz: size_of::<libbpf_sys::bpf_iter_attach_opts>() as _,
+                    ..Default::default()
+                });
+            let attach_opt_ptr = attach_opt.as_ref().map_or(ptr::null(), |opts| {
+                opts as *const libbpf_sys::bpf_iter_attach_opts
+            });
+            let ptr =
+                unsafe { libbpf_sys::bpf_program__attach_iter(self.ptr.as_ptr(), attach_opt_ptr) };
+
+            let ptr = validate_bpf_ret(ptr)
+                .map_err(|err| self.diagnose(err))
+                .context("failed to attach iterator")?;
+            // SAFETY: the pointer came from libbpf and has been checked for errors.
+            let link = unsafe { Link::new(ptr) };
+            Ok(link)
+        })
     }
 
     /// Test run the program with the given input data.
@@ -1117,9 +2002,29 @@ impl<'obj> ProgramMut<'obj> {
             mut data_out,
             cpu,
             flags,
+            repeat,
+            batch_size,
             _non_exhaustive: (),
         } = input;
 
+        if flags & libbpf_sys::BPF_F_TEST_XDP_LIVE_FRAMES != 0 {
+            let prog_type = self.prog_type();
+            if !matches!(prog_type, ProgramType::Xdp) {
+                return Err(Error::with_io_error(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "live frame mode is only supported for XDP programs, not {prog_type:?}"
+                    ),
+                ))
+            }
+            if context_out.is_some() || data_out.is_some() {
+                return Err(Error::with_io_error(
+                    io::ErrorKind::InvalidInput,
+                    "live frame mode does not support output context or data",
+                ))
+            }
+        }
+
         let mut opts = unsafe { mem::zeroed::<libbpf_sys::bpf_test_run_opts>() };
         opts.sz = size_of_val(&opts) as _;
         opts.ctx_in = context_in
@@ -1143,6 +2048,8 @@ impl<'obj> ProgramMut<'obj> {
         opts.data_size_out = data_out.map(|data| data.len() as _).unwrap_or(0);
         opts.cpu = cpu;
         opts.flags = flags;
+        opts.repeat = repeat as _;
+        opts.batch_size = batch_size;
 
         let rc = unsafe { libbpf_sys::bpf_prog_test_run_opts(self.as_fd().as_raw_fd(), &mut opts) };
         let () = util::parse_ret(rc)?;
@@ -1150,10 +2057,40 @@ impl<'obj> ProgramMut<'obj> {
             return_value: opts.retval,
             context: unsafe { slice_from_array(opts.ctx_out.cast(), opts.ctx_size_out as _) },
             data: unsafe { slice_from_array(opts.data_out.cast(), opts.data_size_out as _) },
+            duration: Duration::from_nanos(opts.duration.into()),
             _non_exhaustive: (),
         };
         Ok(output)
     }
+
+    /// Measure the run time of the program by test running it with the
+    /// given input, to compare different implementations of a program.
+    ///
+    /// The program is run in a number of batches of `repeat` runs each
+    /// (see [`ProgramMut::test_run`]), overriding [`Input::repeat`]. The
+    /// kernel reports the average duration of a run per batch, from which
+    /// the returned statistics are derived. Outputs of the program are
+    /// discarded, except for being written to the provided buffers.
+    pub fn benchmark(&self, mut input: Input<'_>, repeat: u32) -> Result<BenchmarkStats> {
+        let repeat = repeat.max(1);
+        let mut batches = Vec::with_capacity(BENCHMARK_BATCHES);
+        for _ in 0..BENCHMARK_BATCHES {
+            let batch = Input {
+                context_in: input.context_in.as_deref_mut(),
+                context_out: input.context_out.as_deref_mut(),
+                data_in: input.data_in,
+                data_out: input.data_out.as_deref_mut(),
+                cpu: input.cpu,
+                flags: input.flags,
+                repeat,
+                batch_size: input.batch_size,
+                _non_exhaustive: (),
+            };
+            let output = self.test_run(batch)?;
+            let () = batches.push(output.duration);
+        }
+        Ok(BenchmarkStats::from_batches(&mut batches, repeat))
+    }
 }
 
 impl<'obj> Deref for ProgramMut<'obj> {
@@ -1188,6 +2125,46 @@ mod tests {
 
     use std::mem::discriminant;
 
+    /// Check that benchmark statistics are computed correctly.
+    #[test]
+    fn benchmark_stats() {
+        let mut batches = (1..=200)
+            .rev()
+            .map(Duration::from_nanos)
+            .collect::<Vec<_>>();
+        let stats = BenchmarkStats::from_batches(&mut batches, 10);
+        assert_eq!(stats.runs, 2000);
+        assert_eq!(stats.min, Duration::from_nanos(1));
+        assert_eq!(stats.avg, Duration::from_nanos(100));
+        assert_eq!(stats.p99, Duration::from_nanos(198));
+        assert_eq!(stats.max, Duration::from_nanos(200));
+
+        let mut batches = [Duration::from_nanos(7)];
+        let stats = BenchmarkStats::from_batches(&mut batches, 1);
+        assert_eq!(stats.p99, Duration::from_nanos(7));
+    }
+
+    /// Check that we correctly determine which sections can be attached
+    /// automatically.
+    #[test]
+    fn auto_attach_targets() {
+        assert!(has_auto_attach_target("kprobe/do_unlinkat"));
+        assert!(has_auto_attach_target("tp/sched/sched_switch"));
+        assert!(has_auto_attach_target("uprobe//bin/bash:readline"));
+        assert!(has_auto_attach_target("fentry/do_unlinkat"));
+        assert!(has_auto_attach_target("lsm.s/file_open"));
+        assert!(has_auto_attach_target("iter/task"));
+
+        assert!(!has_auto_attach_target("kprobe"));
+        assert!(!has_auto_attach_target("uprobe"));
+        assert!(!has_auto_attach_target("usdt"));
+        assert!(!has_auto_attach_target("raw_tp/"));
+        assert!(!has_auto_attach_target("xdp"));
+        assert!(!has_auto_attach_target("cgroup/connect4"));
+        assert!(!has_auto_attach_target("struct_ops/dummy"));
+        assert!(!has_auto_attach_target("perf_event"));
+    }
+
     #[test]
     fn program_type() {
         use ProgramType::*;
@@ -1279,6 +2256,20 @@ mod tests {
             SkReuseportSelect,
             SkReuseportSelectOrMigrate,
             PerfEvent,
+            TraceKprobeMulti,
+            LsmCgroup,
+            StructOps,
+            Netfilter,
+            TcxIngress,
+            TcxEgress,
+            TraceUprobeMulti,
+            CgroupUnixConnect,
+            CgroupUnixSendmsg,
+            CgroupUnixRecvmsg,
+            CgroupUnixGetpeername,
+            CgroupUnixGetsockname,
+            NetkitPrimary,
+            NetkitPeer,
             Unknown,
         ] {
             // check if discriminants match after a roundtrip conversion
@@ -1287,5 +2278,55 @@ mod tests {
                 discriminant(&ProgramAttachType::from(t as u32))
             );
         }
+
+        assert_eq!(NetkitPrimary as u32, libbpf_sys::BPF_NETKIT_PRIMARY);
+        assert_eq!(NetkitPeer as u32, libbpf_sys::BPF_NETKIT_PEER);
+    }
+
+    /// Check that attach orders are converted into the expected flags.
+    #[test]
+    fn attach_order_conversion() {
+        let before = libbpf_sys::BPF_F_BEFORE;
+        let after = libbpf_sys::BPF_F_AFTER;
+        let id = libbpf_sys::BPF_F_ID;
+
+        assert_eq!(AttachOrder::default().to_raw(), (after, 0, 0));
+        assert_eq!(AttachOrder::First.to_raw(), (before, 0, 0));
+        assert_eq!(AttachOrder::BeforeId(42).to_raw(), (before | id, 0, 42));
+        assert_eq!(AttachOrder::AfterId(7).to_raw(), (after | id, 0, 7));
+
+        let file = File::open("/dev/null").unwrap();
+        let fd = file.as_fd();
+        assert_eq!(
+            AttachOrder::Before(fd).to_raw(),
+            (before, fd.as_raw_fd() as u32, 0)
+        );
+    }
+
+    /// Check that we can extract verifier statistics from a verifier log.
+    #[test]
+    fn verifier_stats_parsing() {
+        let log = "func#0 @0\n\
+                   0: R1=ctx() R10=fp0\n\
+                   verification time 20 usec\n\
+                   stack depth 8+0\n\
+                   processed 12 insns (limit 1000000) max_states_per_insn 1 total_states 3 peak_states 2 mark_read 1\n";
+        let stats = VerifierStats::from_log(log).unwrap();
+        assert_eq!(stats.verification_time, Some(Duration::from_micros(20)));
+        assert_eq!(stats.stack_depth, Some(vec![8, 0]));
+        assert_eq!(stats.processed_insns, Some(12));
+        assert_eq!(stats.max_states_per_insn, Some(1));
+        assert_eq!(stats.total_states, Some(3));
+        assert_eq!(stats.peak_states, Some(2));
+
+        // Without `BPF_LOG_STATS` only the processed instructions are
+        // reported.
+        let log = "processed 2 insns (limit 1000000) max_states_per_insn 0 total_states 0 peak_states 0 mark_read 0\n";
+        let stats = VerifierStats::from_log(log).unwrap();
+        assert_eq!(stats.verification_time, None);
+        assert_eq!(stats.processed_insns, Some(2));
+        assert_eq!(stats.peak_states, Some(0));
+
+        assert_eq!(VerifierStats::from_log("0: R1=ctx() R10=fp0\n"), None);
     }
 }
@@ -0,0 +1,378 @@
+//! Reporting of progress while loading objects.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::PrintLevel;
+
+
+/// The kind of entity a [`LoadProgress`] event refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadItem {
+    /// A map being created.
+    Map,
+    /// A program being loaded.
+    Program,
+}
+
+/// The outcome of creating a map or loading a program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadOutcome {
+    /// The map was created or the program loaded.
+    Loaded,
+    /// libbpf skipped the map or program, because its automatic creation
+    /// or loading was disabled or because an existing map (e.g., a
+    /// pinned one) was reused.
+    Skipped,
+    /// Creating the map or loading the program failed, failing the load
+    /// as a whole.
+    Failed,
+}
+
+/// An event reported while loading an object with
+/// [`OpenObject::load_with_progress`][crate::OpenObject::load_with_progress].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadProgress<'name> {
+    /// Creation of a map or loading of a program is about to begin.
+    Started {
+        /// The kind of entity.
+        item: LoadItem,
+        /// The name of the map or program.
+        name: &'name OsStr,
+    },
+    /// Creation of a map or loading of a program finished.
+    Finished {
+        /// The kind of entity.
+        item: LoadItem,
+        /// The name of the map or program.
+        name: &'name OsStr,
+        /// The time passed since the corresponding
+        /// [`Started`][LoadProgress::Started] event.
+        elapsed: Duration,
+        /// The outcome.
+        outcome: LoadOutcome,
+    },
+}
+
+
+/// Determine the outcome of processing the map or program `name`
+/// reported by a libbpf message, if any.
+fn message_outcome(
+    item: LoadItem,
+    name: &str,
+    level: PrintLevel,
+    msg: &str,
+) -> Option<LoadOutcome> {
+    let kind = match item {
+        LoadItem::Map => "map",
+        LoadItem::Program => "prog",
+    };
+    let rest = msg
+        .strip_prefix("libbpf: ")?
+        .strip_prefix(kind)?
+        .strip_prefix(" '")?
+        .strip_prefix(name)?
+        .strip_prefix("': ")?;
+
+    let failed = level == PrintLevel::Warn
+        && (rest.starts_with("failed") || rest.starts_with("BPF program load failed"));
+    if failed {
+        return Some(LoadOutcome::Failed)
+    }
+
+    let outcome = match item {
+        LoadItem::Map if rest.starts_with("created successfully") => LoadOutcome::Loaded,
+        LoadItem::Map
+            if rest.starts_with("skipping creation")
+                || rest.starts_with("skipped auto-creating") =>
+        {
+            LoadOutcome::Skipped
+        }
+        // libbpf reports the log of a successful load at debug level
+        // and that of a failed one as a warning.
+        LoadItem::Program
+            if level == PrintLevel::Debug && rest.starts_with("-- BEGIN PROG LOAD LOG --") =>
+        {
+            LoadOutcome::Loaded
+        }
+        LoadItem::Program if rest.starts_with("skipped loading") => LoadOutcome::Skipped,
+        _ => return None,
+    };
+    Some(outcome)
+}
+
+
+/// Tracks the progress of loading an object based on the messages
+/// libbpf emits, reporting it to a callback.
+///
+/// libbpf creates maps and loads programs in the order it enumerates
+/// them in, but it does not report the successful load of a program
+/// unless the kernel produced a log for it. A message concerning a
+/// later map or program hence implies that all those before it were
+/// processed without a failure, i.e., that they got loaded.
+pub(crate) struct Tracker<'cb> {
+    /// The maps and programs yet to be processed, in order.
+    pending: VecDeque<(LoadItem, OsString)>,
+    /// When processing of the first pending map or program started, if
+    /// it was reported as started.
+    started: Option<Instant>,
+    callback: &'cb mut dyn FnMut(&LoadProgress<'_>),
+    /// A panic raised by the callback, to be resumed once libbpf is done.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl<'cb> Tracker<'cb> {
+    pub(crate) fn new<I>(items: I, callback: &'cb mut dyn FnMut(&LoadProgress<'_>)) -> Self
+    where
+        I: IntoIterator<Item = (LoadItem, OsString)>,
+    {
+        Self {
+            pending: items.into_iter().collect(),
+            started: None,
+            callback,
+            panic: None,
+        }
+    }
+
+    /// Report `event` to the callback, which is invoked from within a
+    /// libbpf callback and so must not unwind.
+    fn report(&mut self, event: &LoadProgress<'_>) {
+        if self.panic.is_some() {
+            return
+        }
+        let callback = &mut self.callback;
+        if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| callback(event))) {
+            self.panic = Some(panic);
+        }
+    }
+
+    /// Report the first pending map or program as started.
+    pub(crate) fn start_next(&mut self) {
+        if let Some((item, name)) = self.pending.front().cloned() {
+            let () = self.report(&LoadProgress::Started { item, name: &name });
+            self.started = Some(Instant::now());
+        }
+    }
+
+    /// Report the first pending map or program as finished with
+    /// `outcome`, reporting it as started first if necessary.
+    fn finish_next(&mut self, outcome: LoadOutcome) {
+        if self.started.is_none() {
+            let () = self.start_next();
+        }
+        if let Some((item, name)) = self.pending.pop_front() {
+            let elapsed = self.started.take().map(|t| t.elapsed()).unwrap_or_default();
+            let event = LoadProgress::Finished {
+                item,
+                name: &name,
+                elapsed,
+                outcome,
+            };
+            let () = self.report(&event);
+        }
+    }
+
+    /// Process a message emitted by libbpf.
+    pub(crate) fn message(&mut self, level: PrintLevel, msg: &str) {
+        let outcome = self
+            .pending
+            .iter()
+            .enumerate()
+            .find_map(|(idx, (item, name))| {
+                let name = name.to_str()?;
+                let outcome = message_outcome(*item, name, level, msg)?;
+                Some((idx, outcome))
+            });
+        if let Some((idx, outcome)) = outcome {
+            for _ in 0..idx {
+                let () = self.finish_next(LoadOutcome::Loaded);
+                let () = self.start_next();
+            }
+            let () = self.finish_next(outcome);
+            if outcome != LoadOutcome::Failed {
+                let () = self.start_next();
+            }
+        }
+    }
+
+    /// Conclude tracking once libbpf is done loading, resuming a panic
+    /// raised by the callback, if any.
+    ///
+    /// After a successful load, maps and programs libbpf did not report
+    /// a failure or skip for are reported as loaded.
+    pub(crate) fn conclude(mut self, success: bool) {
+        if success {
+            while !self.pending.is_empty() {
+                let () = self.finish_next(LoadOutcome::Loaded);
+            }
+        }
+        if let Some(panic) = self.panic.take() {
+            panic::resume_unwind(panic)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that libbpf messages are attributed to maps and programs.
+    #[test]
+    fn message_outcomes() {
+        let map = |level, msg| message_outcome(LoadItem::Map, "counts", level, msg);
+        assert_eq!(
+            map(
+                PrintLevel::Debug,
+                "libbpf: map 'counts': created successfully, fd=5\n"
+            ),
+            Some(LoadOutcome::Loaded)
+        );
+        assert_eq!(
+            map(
+                PrintLevel::Debug,
+                "libbpf: map 'counts': skipping creation (preset fd=7)\n"
+            ),
+            Some(LoadOutcome::Skipped)
+        );
+        assert_eq!(
+            map(
+                PrintLevel::Warn,
+                "libbpf: map 'counts': failed to create: Operation not permitted(-1)\n"
+            ),
+            Some(LoadOutcome::Failed)
+        );
+        assert_eq!(
+            map(
+                PrintLevel::Debug,
+                "libbpf: map 'counts_2': created successfully, fd=5\n"
+            ),
+            None
+        );
+        assert_eq!(
+            map(
+                PrintLevel::Debug,
+                "libbpf: prog 'counts': -- BEGIN PROG LOAD LOG --\n"
+            ),
+            None
+        );
+
+        let prog = |level, msg| message_outcome(LoadItem::Program, "handler", level, msg);
+        assert_eq!(
+            prog(PrintLevel::Debug, "libbpf: prog 'handler': -- BEGIN PROG LOAD LOG --\nprocessed 2 insns\n-- END PROG LOAD LOG --\n"),
+            Some(LoadOutcome::Loaded)
+        );
+        assert_eq!(
+            prog(
+                PrintLevel::Warn,
+                "libbpf: prog 'handler': BPF program load failed: Permission denied\n"
+            ),
+            Some(LoadOutcome::Failed)
+        );
+        assert_eq!(
+            prog(PrintLevel::Warn, "libbpf: prog 'handler': -- BEGIN PROG LOAD LOG --\ninvalid access\n-- END PROG LOAD LOG --\n"),
+            None
+        );
+        assert_eq!(
+            prog(
+                PrintLevel::Debug,
+                "libbpf: prog 'handler': skipped loading\n"
+            ),
+            Some(LoadOutcome::Skipped)
+        );
+    }
+
+    /// Check that the tracker reports events in the expected order.
+    #[test]
+    fn tracking() {
+        let mut events = Vec::new();
+        let mut callback = |event: &LoadProgress<'_>| {
+            let event = match event {
+                LoadProgress::Started { name, .. } => format!("start {}", name.to_string_lossy()),
+                LoadProgress::Finished { name, outcome, .. } => {
+                    format!("finish {} {outcome:?}", name.to_string_lossy())
+                }
+            };
+            let () = events.push(event);
+        };
+        let items = [
+            (LoadItem::Map, OsString::from("counts")),
+            (LoadItem::Program, OsString::from("handler")),
+            (LoadItem::Program, OsString::from("unused")),
+            (LoadItem::Program, OsString::from("last")),
+        ];
+        let mut tracker = Tracker::new(items, &mut callback);
+        let () = tracker.start_next();
+        let () = tracker.message(
+            PrintLevel::Debug,
+            "libbpf: map 'counts': created successfully, fd=5\n",
+        );
+        // libbpf does not report on programs loaded without a log, so a
+        // message concerning a later program concludes earlier ones.
+        let () = tracker.message(
+            PrintLevel::Debug,
+            "libbpf: prog 'unused': skipped loading\n",
+        );
+        // Programs libbpf did not report on are concluded as loaded.
+        let () = tracker.conclude(true);
+
+        assert_eq!(
+            events,
+            [
+                "start counts",
+                "finish counts Loaded",
+                "start handler",
+                "finish handler Loaded",
+                "start unused",
+                "finish unused Skipped",
+                "start last",
+                "finish last Loaded",
+            ]
+        );
+    }
+
+    /// Check that a failure concludes tracking without reporting
+    /// remaining programs as loaded.
+    #[test]
+    fn tracking_failure() {
+        let mut events = Vec::new();
+        let mut callback = |event: &LoadProgress<'_>| {
+            let event = match event {
+                LoadProgress::Started { name, .. } => format!("start {}", name.to_string_lossy()),
+                LoadProgress::Finished { name, outcome, .. } => {
+                    format!("finish {} {outcome:?}", name.to_string_lossy())
+                }
+            };
+            let () = events.push(event);
+        };
+        let items = [
+            (LoadItem::Program, OsString::from("first")),
+            (LoadItem::Program, OsString::from("broken")),
+            (LoadItem::Program, OsString::from("never")),
+        ];
+        let mut tracker = Tracker::new(items, &mut callback);
+        let () = tracker.start_next();
+        let () = tracker.message(
+            PrintLevel::Warn,
+            "libbpf: prog 'broken': BPF program load failed: Permission denied\n",
+        );
+        let () = tracker.conclude(false);
+
+        assert_eq!(
+            events,
+            [
+                "start first",
+                "finish first Loaded",
+                "start broken",
+                "finish broken Failed",
+            ]
+        );
+    }
+}
@@ -0,0 +1,590 @@
+//! Exposition of BPF map backed metrics in the Prometheus text format.
+//!
+//! Observability agents commonly aggregate data in BPF maps and export
+//! it to a monitoring system. An [`Exporter`] takes care of the latter
+//! part: maps are registered as counters, gauges, or histograms and
+//! rendered in the [Prometheus exposition
+//! format](https://prometheus.io/docs/instrumenting/exposition_formats/),
+//! either on demand ([`Exporter::render`]) or by means of a minimal HTTP
+//! server ([`Exporter::serve`]).
+//!
+//! Labels are derived from map keys, based on the map's BTF type
+//! information: each (scalar) member of a key struct becomes a label,
+//! with members of nested structs being joined by `_`. Scalar keys are
+//! reported using the `key` label.
+//!
+//! ```no_run
+//! # use libbpf_rs::Object;
+//! # fn example(obj: &Object) -> libbpf_rs::Result<()> {
+//! use libbpf_rs::prometheus::Exporter;
+//!
+//! let mut exporter = Exporter::new();
+//! let _ = exporter
+//!     .counter("tcp_retransmits_total", "TCP retransmits", &obj.map("retransmits")?)?
+//!     .histogram("runq_latency_us", "Run queue latency", &obj.map("runq_hist")?)?;
+//!
+//! exporter.serve("127.0.0.1:9435")
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::io::BufRead as _;
+use std::io::BufReader;
+use std::io::Write as _;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::os::fd::AsFd as _;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapCore;
+use crate::MapHandle;
+use crate::Result;
+
+
+/// The kind of a metric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricKind {
+    /// A monotonically increasing value.
+    ///
+    /// Map values are reported as they are.
+    Counter,
+    /// A value that can go up and down.
+    ///
+    /// Map values are reported as they are.
+    Gauge,
+    /// A histogram using power-of-two buckets, as commonly used by BPF
+    /// based tooling.
+    ///
+    /// Bucket `i` counts observations `v` with `2^i <= v < 2^(i+1)`
+    /// (bucket `0` also includes `0`). Buckets are provided either by
+    /// the map value, if it is an array of counts, or by the map key: a
+    /// scalar key or a key struct member named `slot` is interpreted as
+    /// the bucket index, and the value as the bucket's count.
+    ///
+    /// Because the sum of observations is unknown, no `_sum` sample is
+    /// reported.
+    Histogram,
+}
+
+impl MetricKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+            Self::Histogram => "histogram",
+        }
+    }
+}
+
+
+#[derive(Debug)]
+struct Metric {
+    name: String,
+    help: String,
+    kind: MetricKind,
+    map: MapHandle,
+}
+
+/// The maximum number of histogram buckets we accept.
+const MAX_SLOTS: usize = 128;
+/// The time after which a stalled client connection is closed.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The labels of a sample, sorted by name.
+type Labels = Vec<(String, String)>;
+
+
+/// Check whether `name` is a valid metric or label name.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Convert an arbitrary string into a valid label name.
+fn sanitize_label_name(name: &str) -> String {
+    let mut name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Escape a label value or help text as per the exposition format.
+fn escape(value: &str, escape_quotes: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if escape_quotes => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Flatten a (BTF rendered) key into a set of labels.
+fn collect_labels(key: &Value, prefix: &str, labels: &mut Labels) {
+    let name = || {
+        if prefix.is_empty() {
+            "key".to_string()
+        } else {
+            sanitize_label_name(prefix)
+        }
+    };
+
+    match key {
+        Value::Object(members) => {
+            for (member, value) in members {
+                let prefix = if prefix.is_empty() {
+                    member.clone()
+                } else {
+                    format!("{prefix}_{member}")
+                };
+                let () = collect_labels(value, &prefix, labels);
+            }
+        }
+        Value::String(s) => labels.push((name(), s.clone())),
+        Value::Number(n) => labels.push((name(), n.to_string())),
+        Value::Bool(b) => labels.push((name(), b.to_string())),
+        Value::Array(..) => labels.push((name(), key.to_string())),
+        Value::Null => (),
+    }
+}
+
+/// Interpret a string as the hexadecimal representation of a native
+/// endian unsigned integer of up to eight bytes, as rendered for values
+/// without type information.
+fn parse_hex(s: &str) -> Option<u64> {
+    if s.is_empty() || s.len() % 2 != 0 || s.len() > 16 {
+        return None
+    }
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().take(s.len() / 2).enumerate() {
+        let digits = s.get(i * 2..i * 2 + 2)?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None
+        }
+        *byte = u8::from_str_radix(digits, 16).ok()?;
+    }
+    let value = if cfg!(target_endian = "little") {
+        u64::from_le_bytes(bytes)
+    } else {
+        u64::from_be_bytes(bytes) >> (64 - s.len() * 4)
+    };
+    Some(value)
+}
+
+/// Interpret a value as a number.
+///
+/// Values without type information are rendered as hexadecimal strings
+/// of the in-memory representation and interpreted as native endian
+/// unsigned integers. Other numeric strings are decimal and only
+/// emitted for values not representable as JSON numbers (e.g., 128 bit
+/// integers exceeding the 64 bit range), which are longer than any
+/// hexadecimal rendering we interpret. Hence, the hexadecimal form is
+/// checked first.
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(f64::from(u8::from(*b))),
+        Value::String(s) => parse_hex(s)
+            .map(|value| value as f64)
+            .or_else(|| s.parse::<f64>().ok()),
+        Value::Array(..) | Value::Object(..) | Value::Null => None,
+    }
+}
+
+/// Interpret a value as a list of bucket counts.
+fn as_buckets(value: &Value) -> Option<Vec<f64>> {
+    match value {
+        Value::Array(values) => values.iter().map(as_number).collect(),
+        // A struct wrapping a single array of bucket counts.
+        Value::Object(members) if members.len() == 1 => {
+            members.values().next().and_then(as_buckets)
+        }
+        _ => None,
+    }
+}
+
+/// Add `b` to `a`, element wise.
+fn add_buckets(a: &mut Vec<f64>, b: &[f64]) {
+    if a.len() < b.len() {
+        let () = a.resize(b.len(), 0.0);
+    }
+    for (a, b) in a.iter_mut().zip(b) {
+        *a += b;
+    }
+}
+
+fn write_labels(out: &mut String, labels: &[(String, String)], extra: Option<(&str, &str)>) {
+    if labels.is_empty() && extra.is_none() {
+        return
+    }
+
+    let () = out.push('{');
+    let labels = labels
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .chain(extra);
+    for (i, (name, value)) in labels.enumerate() {
+        let sep = if i == 0 { "" } else { "," };
+        let _result = write!(out, "{sep}{name}=\"{}\"", escape(value, true));
+    }
+    let () = out.push('}');
+}
+
+
+/// An exporter of BPF map backed metrics in the Prometheus text format.
+///
+/// The exporter holds its own reference to each registered map and,
+/// hence, does not borrow from the [`Object`][crate::Object] the maps
+/// belong to.
+#[derive(Debug, Default)]
+pub struct Exporter {
+    metrics: Vec<Metric>,
+}
+
+impl Exporter {
+    /// Create a new `Exporter` without any metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a map as a metric of the given kind.
+    ///
+    /// `name` has to be a valid Prometheus metric name. Only maps
+    /// supporting iteration over their keys can be used.
+    pub fn add<M>(&mut self, kind: MetricKind, name: &str, help: &str, map: &M) -> Result<&mut Self>
+    where
+        M: MapCore,
+    {
+        if !is_valid_name(name) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("`{name}` is not a valid metric name"),
+            ))
+        }
+        if !map.map_type().supports_key_iteration() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "maps of type {:?} cannot be used as metrics",
+                    map.map_type()
+                ),
+            ))
+        }
+
+        let fd = map
+            .as_fd()
+            .try_clone_to_owned()
+            .context("failed to duplicate map file descriptor")?;
+        let map = MapHandle::from_fd(fd)?;
+        let metric = Metric {
+            name: name.to_string(),
+            help: help.to_string(),
+            kind,
+            map,
+        };
+        let () = self.metrics.push(metric);
+        Ok(self)
+    }
+
+    /// Register a map as a [counter][MetricKind::Counter].
+    pub fn counter<M>(&mut self, name: &str, help: &str, map: &M) -> Result<&mut Self>
+    where
+        M: MapCore,
+    {
+        self.add(MetricKind::Counter, name, help, map)
+    }
+
+    /// Register a map as a [gauge][MetricKind::Gauge].
+    pub fn gauge<M>(&mut self, name: &str, help: &str, map: &M) -> Result<&mut Self>
+    where
+        M: MapCore,
+    {
+        self.add(MetricKind::Gauge, name, help, map)
+    }
+
+    /// Register a map as a [histogram][MetricKind::Histogram].
+    pub fn histogram<M>(&mut self, name: &str, help: &str, map: &M) -> Result<&mut Self>
+    where
+        M: MapCore,
+    {
+        self.add(MetricKind::Histogram, name, help, map)
+    }
+
+    /// Read the map backing `metric`, returning its entries with the
+    /// values of per-CPU maps summed up.
+    fn read(metric: &Metric) -> Result<Vec<(Labels, Value)>> {
+        let dump = metric
+            .map
+            .dump_json()
+            .with_context(|| format!("failed to read map for metric `{}`", metric.name))?;
+        let percpu = metric.map.map_type().is_percpu();
+
+        let Value::Array(entries) = dump else {
+            return Ok(Vec::new())
+        };
+
+        let mut samples = Vec::with_capacity(entries.len());
+        for mut entry in entries {
+            let key = entry["key"].take();
+            let value = entry["value"].take();
+            let value = match value {
+                Value::Array(values) if percpu => {
+                    // Sum up per-CPU values (which may be bucket arrays
+                    // themselves).
+                    let mut sum = Vec::new();
+                    let mut scalar = true;
+                    for value in &values {
+                        if let Some(n) = as_number(value) {
+                            let () = add_buckets(&mut sum, &[n]);
+                        } else if let Some(buckets) = as_buckets(value) {
+                            scalar = false;
+                            let () = add_buckets(&mut sum, &buckets);
+                        }
+                    }
+                    if scalar {
+                        Value::from(sum.first().copied().unwrap_or_default())
+                    } else {
+                        Value::from(sum)
+                    }
+                }
+                value => value,
+            };
+
+            let mut labels = Labels::new();
+            let () = collect_labels(&key, "", &mut labels);
+            let () = labels.sort();
+            let () = samples.push((labels, value));
+        }
+        Ok(samples)
+    }
+
+    fn render_metric(metric: &Metric, out: &mut String) -> Result<()> {
+        let Metric {
+            name, help, kind, ..
+        } = metric;
+        let _result = writeln!(out, "# HELP {name} {}", escape(help, false));
+        let _result = writeln!(out, "# TYPE {name} {}", kind.as_str());
+
+        let samples = Self::read(metric)?;
+        match kind {
+            MetricKind::Counter | MetricKind::Gauge => {
+                for (labels, value) in samples {
+                    let Some(value) = as_number(&value) else {
+                        return Err(Error::with_invalid_data(format!(
+                            "value of metric `{name}` is not a number: {value}"
+                        )))
+                    };
+                    let () = out.push_str(name);
+                    let () = write_labels(out, &labels, None);
+                    let _result = writeln!(out, " {value}");
+                }
+            }
+            MetricKind::Histogram => {
+                let mut histograms = BTreeMap::<Labels, Vec<f64>>::new();
+                for (mut labels, value) in samples {
+                    let buckets = if let Some(buckets) = as_buckets(&value) {
+                        buckets
+                    } else {
+                        let slot = labels
+                            .iter()
+                            .position(|(name, _)| name == "slot" || name == "key")
+                            .map(|idx| labels.remove(idx).1)
+                            .and_then(|slot| slot.parse::<usize>().ok())
+                            .filter(|slot| *slot < MAX_SLOTS);
+                        match (slot, as_number(&value)) {
+                            (Some(slot), Some(count)) => {
+                                let mut buckets = vec![0.0; slot + 1];
+                                buckets[slot] = count;
+                                buckets
+                            }
+                            _ => {
+                                return Err(Error::with_invalid_data(format!(
+                                    "failed to determine buckets of histogram `{name}`"
+                                )))
+                            }
+                        }
+                    };
+                    let () = add_buckets(histograms.entry(labels).or_default(), &buckets);
+                }
+
+                for (labels, buckets) in histograms {
+                    let mut total = 0.0;
+                    for (slot, count) in buckets.iter().enumerate() {
+                        total += count;
+                        let le = 1u128
+                            .checked_shl(slot as u32 + 1)
+                            .map_or(u128::MAX, |le| le - 1);
+                        let _result = write!(out, "{name}_bucket");
+                        let () = write_labels(out, &labels, Some(("le", &le.to_string())));
+                        let _result = writeln!(out, " {total}");
+                    }
+                    let _result = write!(out, "{name}_bucket");
+                    let () = write_labels(out, &labels, Some(("le", "+Inf")));
+                    let _result = writeln!(out, " {total}");
+                    let _result = write!(out, "{name}_count");
+                    let () = write_labels(out, &labels, None);
+                    let _result = writeln!(out, " {total}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Render all registered metrics in the Prometheus text format.
+    pub fn render(&self) -> Result<String> {
+        let mut out = String::new();
+        for metric in &self.metrics {
+            let () = Self::render_metric(metric, &mut out)?;
+        }
+        Ok(out)
+    }
+
+    fn respond(&self, stream: TcpStream) -> io::Result<()> {
+        let () = stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        let () = stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut reader = BufReader::new(stream);
+        // Consume the request head; we serve metrics for every request.
+        let mut line = String::new();
+        loop {
+            let () = line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break
+            }
+        }
+
+        let mut stream = reader.into_inner();
+        let (status, body) = match self.render() {
+            Ok(body) => ("200 OK", body),
+            Err(err) => ("500 Internal Server Error", format!("{err:#}\n")),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// Serve the registered metrics via HTTP on the given address.
+    ///
+    /// Every request is answered with the rendered metrics, irrespective
+    /// of its method and path. Each connection is handled on a thread of
+    /// its own, so that a slow client does not hold up others, and is
+    /// closed if the client stalls for more than ten seconds. This
+    /// function only returns if the listening socket could not be created
+    /// or failed.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> Result<()> {
+        let listener = TcpListener::bind(addr).context("failed to bind metrics listener")?;
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let stream = stream.context("failed to accept metrics connection")?;
+                let _handle = scope.spawn(|| {
+                    // A misbehaving client should not take down the
+                    // exporter.
+                    let _result = self.respond(stream);
+                });
+            }
+            Ok(())
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+
+    /// Check that we validate and sanitize names as expected.
+    #[test]
+    fn name_handling() {
+        assert!(is_valid_name("tcp_retransmits_total"));
+        assert!(is_valid_name("bpf:latency"));
+        assert!(!is_valid_name("0abc"));
+        assert!(!is_valid_name("a-b"));
+        assert!(!is_valid_name(""));
+
+        assert_eq!(sanitize_label_name("comm"), "comm");
+        assert_eq!(sanitize_label_name("a.b"), "a_b");
+        assert_eq!(sanitize_label_name("0x"), "_0x");
+    }
+
+    /// Check that keys are flattened into labels.
+    #[test]
+    fn label_collection() {
+        let key = json!({
+            "pid": 42,
+            "comm": "sh\"ell",
+            "addr": { "port": 80 },
+        });
+        let mut labels = Labels::new();
+        let () = collect_labels(&key, "", &mut labels);
+        let () = labels.sort();
+        assert_eq!(
+            labels,
+            vec![
+                ("addr_port".to_string(), "80".to_string()),
+                ("comm".to_string(), "sh\"ell".to_string()),
+                ("pid".to_string(), "42".to_string()),
+            ]
+        );
+
+        let mut out = String::new();
+        let () = write_labels(&mut out, &labels, Some(("le", "1")));
+        assert_eq!(out, r#"{addr_port="80",comm="sh\"ell",pid="42",le="1"}"#);
+
+        let mut labels = Labels::new();
+        let () = collect_labels(&json!(7), "", &mut labels);
+        assert_eq!(labels, vec![("key".to_string(), "7".to_string())]);
+    }
+
+    /// Check that we can interpret values as numbers.
+    #[test]
+    fn number_interpretation() {
+        assert_eq!(as_number(&json!(3)), Some(3.0));
+        assert_eq!(as_number(&json!(true)), Some(1.0));
+        let hex = 0x1234_u32
+            .to_ne_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        assert_eq!(as_number(&Value::String(hex)), Some(f64::from(0x1234)));
+        // Decimal strings are only used for numbers exceeding 64 bits.
+        assert_eq!(
+            as_number(&Value::String(u128::MAX.to_string())),
+            Some(u128::MAX as f64)
+        );
+        assert_eq!(as_number(&json!("inf")), Some(f64::INFINITY));
+        assert_eq!(as_number(&json!("")), None);
+        assert_eq!(as_number(&json!({ "a": 1 })), None);
+
+        assert_eq!(as_buckets(&json!([1, 2, 3])), Some(vec![1.0, 2.0, 3.0]));
+        assert_eq!(
+            as_buckets(&json!({ "slots": [1, 0] })),
+            Some(vec![1.0, 0.0])
+        );
+        assert_eq!(as_buckets(&json!(1)), None);
+    }
+}
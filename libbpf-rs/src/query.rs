@@ -29,6 +29,51 @@ use crate::ProgramAttachType;
 use crate::ProgramType;
 use crate::Result;
 
+/// Serialization routines for types not supported by `serde` out of the
+/// box.
+#[cfg(feature = "serde")]
+mod ser {
+    use std::ffi::c_void;
+    use std::ffi::CString;
+
+    use serde::Serialize;
+    use serde::Serializer;
+
+
+    pub(super) fn cstring<S>(string: &CString, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&string.to_string_lossy())
+    }
+
+    pub(super) fn addrs<S>(addrs: &[*const c_void], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(addrs.iter().map(|addr| *addr as usize))
+    }
+
+    pub(super) fn func_info<S>(
+        func_info: &[libbpf_sys::bpf_func_info],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct FuncInfo {
+            insn_off: u32,
+            type_id: u32,
+        }
+
+        serializer.collect_seq(func_info.iter().map(|info| FuncInfo {
+            insn_off: info.insn_off,
+            type_id: info.type_id,
+        }))
+    }
+}
+
 macro_rules! gen_info_impl {
     // This magic here allows us to embed doc comments into macro expansions
     ($(#[$attr:meta])*
@@ -92,6 +137,7 @@ macro_rules! gen_info_impl {
 
 /// BTF Line information
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LineInfo {
     /// Offset of instruction in vector
     pub insn_off: u32,
@@ -119,14 +165,17 @@ impl From<&libbpf_sys::bpf_line_info> for LineInfo {
 
 /// Bpf identifier tag
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(C)]
 pub struct Tag(pub [u8; 8]);
 
 /// Information about a BPF program
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document members.
 #[allow(missing_docs)]
 pub struct ProgramInfo {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::cstring"))]
     pub name: CString,
     pub ty: ProgramType,
     pub tag: Tag,
@@ -141,12 +190,15 @@ pub struct ProgramInfo {
     pub gpl_compatible: bool,
     pub netns_dev: u64,
     pub netns_ino: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::addrs"))]
     pub jited_ksyms: Vec<*const c_void>,
     pub jited_func_lens: Vec<u32>,
     pub btf_id: u32,
     pub func_info_rec_size: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::func_info"))]
     pub func_info: Vec<libbpf_sys::bpf_func_info>,
     pub line_info: Vec<LineInfo>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::addrs"))]
     pub jited_line_info: Vec<*const c_void>,
     pub line_info_rec_size: u32,
     pub jited_line_info_rec_size: u32,
@@ -438,9 +490,11 @@ impl Iterator for ProgInfoIter {
 
 /// Information about a BPF map
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document members.
 #[allow(missing_docs)]
 pub struct MapInfo {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::cstring"))]
     pub name: CString,
     pub ty: MapType,
     pub id: u32,
@@ -493,8 +547,10 @@ gen_info_impl!(
 
 /// Information about BPF type format
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BtfInfo {
     /// The name associated with this btf information in the kernel
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::cstring"))]
     pub name: CString,
     /// The raw btf bytes from the kernel
     pub btf: Vec<u8>,
@@ -587,6 +643,7 @@ impl Iterator for BtfInfoIter {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document members.
 #[allow(missing_docs)]
 pub struct RawTracepointLinkInfo {
@@ -594,6 +651,7 @@ pub struct RawTracepointLinkInfo {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document members.
 #[allow(missing_docs)]
 pub struct TracingLinkInfo {
@@ -601,6 +659,7 @@ pub struct TracingLinkInfo {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document members.
 #[allow(missing_docs)]
 pub struct CgroupLinkInfo {
@@ -609,6 +668,7 @@ pub struct CgroupLinkInfo {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document members.
 #[allow(missing_docs)]
 pub struct NetNsLinkInfo {
@@ -617,6 +677,7 @@ pub struct NetNsLinkInfo {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document variants.
 #[allow(missing_docs)]
 pub enum LinkTypeInfo {
@@ -630,6 +691,7 @@ pub enum LinkTypeInfo {
 
 /// Information about a BPF link
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 // TODO: Document members.
 #[allow(missing_docs)]
 pub struct LinkInfo {
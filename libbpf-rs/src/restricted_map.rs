@@ -0,0 +1,345 @@
+//! Map handles restricted to either reading or writing.
+//!
+//! Maps created with `BPF_F_RDONLY` or `BPF_F_WRONLY`, as well as file
+//! descriptors opened with these flags, only permit the respective kind
+//! of access from userspace; everything else fails with `EPERM`. The
+//! types in this module reflect such restrictions in the type system,
+//! by only providing the operations that are permitted.
+//!
+//! The `BPF_F_RDONLY_PROG` and `BPF_F_WRONLY_PROG` flags, on the other
+//! hand, restrict access by BPF programs and are checked by the verifier.
+
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::mem::size_of;
+use std::os::fd::AsFd;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+
+use crate::util::parse_ret_i32;
+use crate::Error;
+use crate::MapCore;
+use crate::MapEntryIter;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapInfo;
+use crate::MapKeyIter;
+use crate::MapSnapshot;
+use crate::MapType;
+use crate::Result;
+
+
+/// Create a map with the given userspace access flag set.
+fn create<T>(
+    flag: u32,
+    map_type: MapType,
+    name: Option<T>,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    opts: &libbpf_sys::bpf_map_create_opts,
+) -> Result<MapHandle>
+where
+    T: AsRef<OsStr>,
+{
+    let mut opts = *opts;
+    opts.map_flags |= flag;
+    MapHandle::create(map_type, name, key_size, value_size, max_entries, &opts)
+}
+
+/// Open the map pinned at `path` with the given access flag.
+fn open_pinned_path(flag: u32, path: &Path) -> Result<MapHandle> {
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        Error::with_invalid_data(format!("invalid path `{}`: has NUL bytes", path.display()))
+    })?;
+    let opts = libbpf_sys::bpf_obj_get_opts {
+        sz: size_of::<libbpf_sys::bpf_obj_get_opts>() as _,
+        file_flags: flag,
+        ..Default::default()
+    };
+    // SAFETY: The path is a valid C string and the options are valid.
+    let fd = parse_ret_i32(unsafe { libbpf_sys::bpf_obj_get_opts(path.as_ptr(), &opts) })?;
+    // SAFETY: A file descriptor coming from `bpf_obj_get_opts` is always
+    //         suitable for ownership.
+    MapHandle::from_fd(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Open the map with the given ID with the given access flag.
+fn open_map_id(flag: u32, id: u32) -> Result<MapHandle> {
+    let opts = libbpf_sys::bpf_get_fd_by_id_opts {
+        sz: size_of::<libbpf_sys::bpf_get_fd_by_id_opts>() as _,
+        open_flags: flag,
+        ..Default::default()
+    };
+    // SAFETY: The options are valid.
+    let fd = parse_ret_i32(unsafe { libbpf_sys::bpf_map_get_fd_by_id_opts(id, &opts) })?;
+    // SAFETY: A file descriptor coming from `bpf_map_get_fd_by_id_opts` is
+    //         always suitable for ownership.
+    MapHandle::from_fd(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+
+/// A handle to a map that userspace may only read from.
+#[derive(Debug)]
+pub struct ReadOnlyMap {
+    map: MapHandle,
+}
+
+impl ReadOnlyMap {
+    /// Create a map that userspace can only read from (i.e., with
+    /// `BPF_F_RDONLY` set), while BPF programs can write to it.
+    ///
+    /// See [`MapHandle::create`] for details on the arguments.
+    pub fn create<T>(
+        map_type: MapType,
+        name: Option<T>,
+        key_size: u32,
+        value_size: u32,
+        max_entries: u32,
+        opts: &libbpf_sys::bpf_map_create_opts,
+    ) -> Result<Self>
+    where
+        T: AsRef<OsStr>,
+    {
+        let map = create(
+            libbpf_sys::BPF_F_RDONLY,
+            map_type,
+            name,
+            key_size,
+            value_size,
+            max_entries,
+            opts,
+        )?;
+        Ok(Self { map })
+    }
+
+    /// Open a pinned map for reading only.
+    ///
+    /// This only requires read permission on the pinned file.
+    pub fn from_pinned_path<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let map = open_pinned_path(libbpf_sys::BPF_F_RDONLY, path.as_ref())?;
+        Ok(Self { map })
+    }
+
+    /// Open a loaded map by its ID for reading only.
+    pub fn from_map_id(id: u32) -> Result<Self> {
+        let map = open_map_id(libbpf_sys::BPF_F_RDONLY, id)?;
+        Ok(Self { map })
+    }
+
+    /// Retrieve the map's name.
+    pub fn name(&self) -> &OsStr {
+        self.map.name()
+    }
+
+    /// Retrieve the map's type.
+    pub fn map_type(&self) -> MapType {
+        self.map.map_type()
+    }
+
+    /// Retrieve the size of the map's keys.
+    pub fn key_size(&self) -> u32 {
+        self.map.key_size()
+    }
+
+    /// Retrieve the size of the map's values.
+    pub fn value_size(&self) -> u32 {
+        self.map.value_size()
+    }
+
+    /// Fetch extra map information.
+    pub fn info(&self) -> Result<MapInfo> {
+        self.map.info()
+    }
+
+    /// See [`MapCore::keys`].
+    pub fn keys(&self) -> MapKeyIter<'_> {
+        self.map.keys()
+    }
+
+    /// See [`MapCore::iter`].
+    pub fn iter(&self) -> Result<MapEntryIter<'_>> {
+        self.map.iter()
+    }
+
+    /// See [`MapCore::lookup`].
+    pub fn lookup(&self, key: &[u8], flags: MapFlags) -> Result<Option<Vec<u8>>> {
+        self.map.lookup(key, flags)
+    }
+
+    /// See [`MapCore::lookup_into`].
+    pub fn lookup_into(&self, key: &[u8], value: &mut [u8], flags: MapFlags) -> Result<bool> {
+        self.map.lookup_into(key, value, flags)
+    }
+
+    /// See [`MapCore::lookup_percpu`].
+    pub fn lookup_percpu(&self, key: &[u8], flags: MapFlags) -> Result<Option<Vec<Vec<u8>>>> {
+        self.map.lookup_percpu(key, flags)
+    }
+
+    /// See [`MapCore::lookup_bloom_filter`].
+    pub fn lookup_bloom_filter(&self, value: &[u8]) -> Result<bool> {
+        self.map.lookup_bloom_filter(value)
+    }
+
+    /// See [`MapCore::snapshot`].
+    pub fn snapshot(&self) -> Result<MapSnapshot> {
+        self.map.snapshot()
+    }
+}
+
+impl AsFd for ReadOnlyMap {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.map.as_fd()
+    }
+}
+
+impl From<MapHandle> for ReadOnlyMap {
+    /// Restrict a [`MapHandle`] to read operations.
+    fn from(map: MapHandle) -> Self {
+        Self { map }
+    }
+}
+
+
+/// A handle to a map that userspace may only write to.
+#[derive(Debug)]
+pub struct WriteOnlyMap {
+    map: MapHandle,
+}
+
+impl WriteOnlyMap {
+    /// Create a map that userspace can only write to (i.e., with
+    /// `BPF_F_WRONLY` set), while BPF programs can read from it.
+    ///
+    /// See [`MapHandle::create`] for details on the arguments.
+    pub fn create<T>(
+        map_type: MapType,
+        name: Option<T>,
+        key_size: u32,
+        value_size: u32,
+        max_entries: u32,
+        opts: &libbpf_sys::bpf_map_create_opts,
+    ) -> Result<Self>
+    where
+        T: AsRef<OsStr>,
+    {
+        let map = create(
+            libbpf_sys::BPF_F_WRONLY,
+            map_type,
+            name,
+            key_size,
+            value_size,
+            max_entries,
+            opts,
+        )?;
+        Ok(Self { map })
+    }
+
+    /// Open a pinned map for writing only.
+    ///
+    /// This only requires write permission on the pinned file.
+    pub fn from_pinned_path<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let map = open_pinned_path(libbpf_sys::BPF_F_WRONLY, path.as_ref())?;
+        Ok(Self { map })
+    }
+
+    /// Open a loaded map by its ID for writing only.
+    pub fn from_map_id(id: u32) -> Result<Self> {
+        let map = open_map_id(libbpf_sys::BPF_F_WRONLY, id)?;
+        Ok(Self { map })
+    }
+
+    /// Retrieve the map's name.
+    pub fn name(&self) -> &OsStr {
+        self.map.name()
+    }
+
+    /// Retrieve the map's type.
+    pub fn map_type(&self) -> MapType {
+        self.map.map_type()
+    }
+
+    /// Retrieve the size of the map's keys.
+    pub fn key_size(&self) -> u32 {
+        self.map.key_size()
+    }
+
+    /// Retrieve the size of the map's values.
+    pub fn value_size(&self) -> u32 {
+        self.map.value_size()
+    }
+
+    /// Fetch extra map information.
+    pub fn info(&self) -> Result<MapInfo> {
+        self.map.info()
+    }
+
+    /// See [`MapCore::update`].
+    pub fn update(&self, key: &[u8], value: &[u8], flags: MapFlags) -> Result<()> {
+        self.map.update(key, value, flags)
+    }
+
+    /// See [`MapCore::update_batch`].
+    pub fn update_batch(
+        &self,
+        keys: &[u8],
+        values: &[u8],
+        count: u32,
+        elem_flags: MapFlags,
+        flags: MapFlags,
+    ) -> Result<()> {
+        self.map
+            .update_batch(keys, values, count, elem_flags, flags)
+    }
+
+    /// See [`MapCore::update_percpu`].
+    pub fn update_percpu(&self, key: &[u8], values: &[Vec<u8>], flags: MapFlags) -> Result<()> {
+        self.map.update_percpu(key, values, flags)
+    }
+
+    /// See [`MapCore::delete`].
+    pub fn delete(&self, key: &[u8]) -> Result<()> {
+        self.map.delete(key)
+    }
+
+    /// See [`MapCore::delete_batch`].
+    pub fn delete_batch(
+        &self,
+        keys: &[u8],
+        count: u32,
+        elem_flags: MapFlags,
+        flags: MapFlags,
+    ) -> Result<()> {
+        self.map.delete_batch(keys, count, elem_flags, flags)
+    }
+
+    /// See [`MapCore::restore`].
+    pub fn restore(&self, snapshot: &MapSnapshot, flags: MapFlags) -> Result<()> {
+        self.map.restore(snapshot, flags)
+    }
+}
+
+impl AsFd for WriteOnlyMap {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.map.as_fd()
+    }
+}
+
+impl From<MapHandle> for WriteOnlyMap {
+    /// Restrict a [`MapHandle`] to write operations.
+    fn from(map: MapHandle) -> Self {
+        Self { map }
+    }
+}
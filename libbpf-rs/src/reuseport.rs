@@ -0,0 +1,112 @@
+//! Support for BPF based socket selection within `SO_REUSEPORT` groups.
+//!
+//! Sockets bound to the same address and port with `SO_REUSEPORT` set form
+//! a group, among which the kernel by default distributes incoming
+//! connections or datagrams based on a hash. A `SEC("sk_reuseport")`
+//! program attached with
+//! [`ProgramMut::attach_reuseport`][crate::ProgramMut::attach_reuseport]
+//! can take over this decision, picking the socket from a
+//! `BPF_MAP_TYPE_REUSEPORT_SOCKARRAY` map, which [`ReuseportSockArray`]
+//! helps populating.
+
+use std::os::fd::AsRawFd as _;
+use std::os::fd::BorrowedFd;
+
+use crate::util;
+use crate::Error;
+use crate::MapCore;
+use crate::MapFlags;
+use crate::MapType;
+use crate::Result;
+
+
+/// A typed wrapper around a `BPF_MAP_TYPE_REUSEPORT_SOCKARRAY` map, mapping
+/// indices to the sockets of a `SO_REUSEPORT` group.
+#[derive(Debug)]
+pub struct ReuseportSockArray<M> {
+    map: M,
+}
+
+impl<M> ReuseportSockArray<M>
+where
+    M: MapCore,
+{
+    /// Wrap `map`, which has to be of type
+    /// [`MapType::ReuseportSockarray`].
+    pub fn new(map: M) -> Result<Self> {
+        if map.map_type() != MapType::ReuseportSockarray {
+            return Err(Error::with_invalid_data(format!(
+                "map `{}` is of type {:?}; expected {:?}",
+                map.name().to_string_lossy(),
+                map.map_type(),
+                MapType::ReuseportSockarray,
+            )));
+        }
+        Ok(Self { map })
+    }
+
+    /// Store `socket` at `index`, replacing any socket previously stored
+    /// there.
+    ///
+    /// The socket has to be bound with `SO_REUSEPORT` set and, for TCP,
+    /// be listening. All sockets in the map have to belong to the same
+    /// group.
+    pub fn insert(&self, index: u32, socket: BorrowedFd<'_>) -> Result<()> {
+        let fd = socket.as_raw_fd();
+        // The kernel accepts the file descriptor as 32 or 64 bit value,
+        // depending on the map's value size.
+        let value = match self.map.value_size() {
+            4 => (fd as u32).to_ne_bytes().to_vec(),
+            _ => (fd as u64).to_ne_bytes().to_vec(),
+        };
+        self.map.update(&index.to_ne_bytes(), &value, MapFlags::ANY)
+    }
+
+    /// Remove the socket stored at `index`.
+    pub fn remove(&self, index: u32) -> Result<()> {
+        self.map.delete(&index.to_ne_bytes())
+    }
+
+    /// Retrieve the cookie of the socket stored at `index`, if any.
+    ///
+    /// This requires the map to have a value size of eight bytes.
+    pub fn socket_cookie(&self, index: u32) -> Result<Option<u64>> {
+        let value = self.map.lookup(&index.to_ne_bytes(), MapFlags::ANY)?;
+        value
+            .map(|value| {
+                let cookie = <[u8; 8]>::try_from(value.as_slice()).map_err(|_| {
+                    Error::with_invalid_data(format!(
+                        "socket cookie has unexpected size {}",
+                        value.len()
+                    ))
+                })?;
+                Ok(u64::from_ne_bytes(cookie))
+            })
+            .transpose()
+    }
+
+    /// Retrieve the wrapped map.
+    #[inline]
+    pub fn map(&self) -> &M {
+        &self.map
+    }
+
+    /// Unwrap the map.
+    #[inline]
+    pub fn into_inner(self) -> M {
+        self.map
+    }
+}
+
+
+/// Detach the BPF program used for socket selection from the `SO_REUSEPORT`
+/// group `socket` belongs to, reverting to hash based selection.
+pub fn detach(socket: BorrowedFd<'_>) -> Result<()> {
+    let unused: libc::c_int = 0;
+    util::setsockopt(
+        socket,
+        libc::SOL_SOCKET,
+        libc::SO_DETACH_REUSEPORT_BPF,
+        &unused,
+    )
+}
@@ -2,8 +2,7 @@ use core::ffi::c_void;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
-use std::ops::Deref as _;
-use std::ops::DerefMut as _;
+use std::marker::PhantomData;
 use std::os::raw::c_ulong;
 use std::os::unix::prelude::AsRawFd;
 use std::os::unix::prelude::BorrowedFd;
@@ -21,30 +20,80 @@ use crate::MapCore;
 use crate::MapType;
 use crate::Result;
 
-type Cb<'a> = Box<dyn FnMut(&[u8]) -> i32 + 'a>;
-
+/// A callback invoked for each ring buffer sample.
+///
+/// The callback is stored type erased, along with a trampoline that is
+/// monomorphized for its type, so that invoking it from `libbpf` does
+/// not involve dynamic dispatch.
 struct RingBufferCallback<'a> {
-    cb: Cb<'a>,
+    /// The callback, as allocated by the `Box` it was moved into.
+    ctx: NonNull<c_void>,
+    /// The function passed to `libbpf` for invoking the callback.
+    sample_cb: unsafe extern "C" fn(*mut c_void, *mut c_void, c_ulong) -> i32,
+    /// The function releasing the callback.
+    drop_cb: unsafe fn(NonNull<c_void>),
+    // Callbacks are required to be `Send`, because they are owned by the
+    // `RingBuffer`, which itself may be sent to a different thread.
+    _callback: PhantomData<Box<dyn FnMut(&[u8]) -> i32 + Send + 'a>>,
 }
 
 impl<'a> RingBufferCallback<'a> {
     fn new<F>(cb: F) -> Self
     where
-        F: FnMut(&[u8]) -> i32 + 'a,
+        F: FnMut(&[u8]) -> i32 + Send + 'a,
     {
-        RingBufferCallback { cb: Box::new(cb) }
+        unsafe extern "C" fn call_sample_cb<F>(
+            ctx: *mut c_void,
+            data: *mut c_void,
+            size: c_ulong,
+        ) -> i32
+        where
+            F: FnMut(&[u8]) -> i32,
+        {
+            // SAFETY: `ctx` references the callback of type `F` that we
+            //         registered it with and `libbpf` does not invoke
+            //         callbacks concurrently.
+            let callback = unsafe { &mut *ctx.cast::<F>() };
+            let slice = unsafe { slice::from_raw_parts(data as *const u8, size as usize) };
+
+            callback(slice)
+        }
+
+        unsafe fn drop_cb<F>(ctx: NonNull<c_void>) {
+            // SAFETY: The caller guarantees that `ctx` was derived from
+            //         a `Box<F>` and is no longer used afterwards.
+            let _cb = unsafe { Box::from_raw(ctx.cast::<F>().as_ptr()) };
+        }
+
+        RingBufferCallback {
+            ctx: NonNull::from(Box::leak(Box::new(cb))).cast(),
+            sample_cb: call_sample_cb::<F>,
+            drop_cb: drop_cb::<F>,
+            _callback: PhantomData,
+        }
     }
 }
 
 impl Debug for RingBufferCallback<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let Self { cb } = self;
+        let Self { ctx, .. } = self;
         f.debug_struct("RingBufferCallback")
-            .field("cb", &(cb.deref() as *const _))
+            .field("cb", ctx)
             .finish()
     }
 }
 
+impl Drop for RingBufferCallback<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `drop_cb` was instantiated for the type of the callback
+        //         `ctx` refers to and we never use `ctx` again.
+        let () = unsafe { (self.drop_cb)(self.ctx) };
+    }
+}
+
+// SAFETY: The callback is required to be `Send` upon construction.
+unsafe impl Send for RingBufferCallback<'_> {}
+
 /// Builds [`RingBuffer`] instances.
 ///
 /// `ringbuf`s are a special kind of [`Map`][crate::Map], used to transfer data
@@ -73,7 +122,7 @@ impl<'slf, 'cb: 'slf> RingBufferBuilder<'slf, 'cb> {
     /// [`plain`](https://crates.io/crates/plain) helpful.
     pub fn add<NewF>(&mut self, map: &'slf dyn MapCore, callback: NewF) -> Result<&mut Self>
     where
-        NewF: FnMut(&[u8]) -> i32 + 'cb,
+        NewF: FnMut(&[u8]) -> i32 + Send + 'cb,
     {
         if map.map_type() != MapType::RingBuf {
             return Err(Error::with_invalid_data("Must use a RingBuf map"));
@@ -87,23 +136,19 @@ impl<'slf, 'cb: 'slf> RingBufferBuilder<'slf, 'cb> {
     pub fn build(self) -> Result<RingBuffer<'cb>> {
         let mut cbs = vec![];
         let mut rb_ptr: Option<NonNull<libbpf_sys::ring_buffer>> = None;
-        let c_sample_cb: libbpf_sys::ring_buffer_sample_fn = Some(Self::call_sample_cb);
 
         for (fd, callback) in self.fd_callbacks {
-            let mut sample_cb = Box::new(callback);
+            let sample_cb = Some(callback.sample_cb);
+            let ctx = callback.ctx.as_ptr();
             match rb_ptr {
                 None => {
                     // Allocate a new ringbuf manager and add a ringbuf to it
                     // SAFETY: All pointers are valid or rightly NULL.
-                    //         The object referenced by `sample_cb` is
-                    //         not modified by `libbpf`
+                    //         The callback referenced by `ctx` is not
+                    //         modified by `libbpf` and outlives the
+                    //         ring buffer.
                     let ptr = unsafe {
-                        libbpf_sys::ring_buffer__new(
-                            fd.as_raw_fd(),
-                            c_sample_cb,
-                            sample_cb.deref_mut() as *mut _ as *mut _,
-                            null_mut(),
-                        )
+                        libbpf_sys::ring_buffer__new(fd.as_raw_fd(), sample_cb, ctx, null_mut())
                     };
                     let ptr = validate_bpf_ret(ptr).context("failed to create new ring buffer")?;
                     rb_ptr = Some(ptr)
@@ -111,15 +156,11 @@ impl<'slf, 'cb: 'slf> RingBufferBuilder<'slf, 'cb> {
                 Some(mut ptr) => {
                     // Add a ringbuf to the existing ringbuf manager
                     // SAFETY: All pointers are valid or rightly NULL.
-                    //         The object referenced by `sample_cb` is
-                    //         not modified by `libbpf`
+                    //         The callback referenced by `ctx` is not
+                    //         modified by `libbpf` and outlives the
+                    //         ring buffer.
                     let err = unsafe {
-                        libbpf_sys::ring_buffer__add(
-                            ptr.as_ptr(),
-                            fd.as_raw_fd(),
-                            c_sample_cb,
-                            sample_cb.deref_mut() as *mut _ as *mut _,
-                        )
+                        libbpf_sys::ring_buffer__add(ptr.as_ptr(), fd.as_raw_fd(), sample_cb, ctx)
                     };
 
                     // Handle errors
@@ -131,7 +172,7 @@ impl<'slf, 'cb: 'slf> RingBufferBuilder<'slf, 'cb> {
                 }
             }
 
-            let () = cbs.push(sample_cb);
+            let () = cbs.push(callback);
         }
 
         match rb_ptr {
@@ -141,14 +182,6 @@ impl<'slf, 'cb: 'slf> RingBufferBuilder<'slf, 'cb> {
             )),
         }
     }
-
-    unsafe extern "C" fn call_sample_cb(ctx: *mut c_void, data: *mut c_void, size: c_ulong) -> i32 {
-        let callback_struct = ctx as *mut RingBufferCallback<'_>;
-        let callback = unsafe { (*callback_struct).cb.as_mut() };
-        let slice = unsafe { slice::from_raw_parts(data as *const u8, size as usize) };
-
-        callback(slice)
-    }
 }
 
 /// The canonical interface for managing a collection of `ringbuf` maps.
@@ -156,11 +189,17 @@ impl<'slf, 'cb: 'slf> RingBufferBuilder<'slf, 'cb> {
 /// `ringbuf`s are a special kind of [`Map`][crate::Map], used to transfer data
 /// between [`Program`][crate::Program]s and userspace. As of Linux 5.8, the
 /// `ringbuf` map is now preferred over the `perf buffer`.
+///
+/// A `RingBuffer` is [`Send`], but not [`Sync`], as it must not be polled
+/// or consumed from multiple threads concurrently:
+/// ```compile_fail
+/// fn test<T: Sync>() {}
+/// test::<libbpf_rs::RingBuffer<'_>>();
+/// ```
 #[derive(Debug)]
 pub struct RingBuffer<'cb> {
     ptr: NonNull<libbpf_sys::ring_buffer>,
-    #[allow(clippy::vec_box)]
-    _cbs: Vec<Box<RingBufferCallback<'cb>>>,
+    _cbs: Vec<RingBufferCallback<'cb>>,
 }
 
 impl RingBuffer<'_> {
@@ -208,8 +247,11 @@ impl RingBuffer<'_> {
     }
 
     /// Get an fd that can be used to sleep until data is available
-    pub fn epoll_fd(&self) -> i32 {
-        unsafe { libbpf_sys::ring_buffer__epoll_fd(self.ptr.as_ptr()) }
+    pub fn epoll_fd(&self) -> BorrowedFd<'_> {
+        let fd = unsafe { libbpf_sys::ring_buffer__epoll_fd(self.ptr.as_ptr()) };
+        // SAFETY: The epoll file descriptor is owned by the `ring_buffer`
+        //         and stays valid for as long as it is alive.
+        unsafe { BorrowedFd::borrow_raw(fd) }
     }
 }
 
@@ -222,7 +264,10 @@ impl AsRawLibbpf for RingBuffer<'_> {
     }
 }
 
-// SAFETY: `ring_buffer` objects can safely be polled from any thread.
+// SAFETY: `ring_buffer` objects can safely be polled from any thread
+//         and all callbacks are `Send`. Note that we intentionally do
+//         not implement `Sync`: concurrent polling of the same
+//         `ring_buffer` is not supported by libbpf.
 unsafe impl Send for RingBuffer<'_> {}
 
 impl Drop for RingBuffer<'_> {
@@ -237,6 +282,8 @@ impl Drop for RingBuffer<'_> {
 mod test {
     use super::*;
 
+    use std::sync::Arc;
+
     /// Check that `RingBuffer` is `Send`.
     #[test]
     fn ringbuffer_is_send() {
@@ -247,5 +294,39 @@ mod test {
         }
 
         test::<RingBuffer<'_>>();
+        test::<RingBufferBuilder<'_, '_>>();
+    }
+
+    /// Check that a `RingBufferCallback` invokes and eventually releases
+    /// the callback it wraps.
+    #[test]
+    fn ringbuffer_callback() {
+        let data = Arc::new(());
+        let mut samples = Vec::new();
+        let callback = {
+            let data = Arc::clone(&data);
+            RingBufferCallback::new(move |sample: &[u8]| {
+                let _data = &data;
+                let () = samples.push(sample.to_vec());
+                samples.len() as i32
+            })
+        };
+        assert_eq!(Arc::strong_count(&data), 2);
+
+        let mut sample = [1u8, 2, 3];
+        let ret = unsafe {
+            (callback.sample_cb)(
+                callback.ctx.as_ptr(),
+                sample.as_mut_ptr().cast(),
+                sample.len() as c_ulong,
+            )
+        };
+        assert_eq!(ret, 1);
+        let ret =
+            unsafe { (callback.sample_cb)(callback.ctx.as_ptr(), sample.as_mut_ptr().cast(), 0) };
+        assert_eq!(ret, 2);
+
+        let () = drop(callback);
+        assert_eq!(Arc::strong_count(&data), 1);
     }
 }
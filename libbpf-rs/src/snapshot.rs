@@ -0,0 +1,333 @@
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::ffi::OsStringExt as _;
+
+use crate::map::check_percpu_buffer_size;
+use crate::map::update_raw;
+use crate::util;
+use crate::Error;
+use crate::MapCore;
+use crate::MapFlags;
+use crate::MapType;
+use crate::Result;
+
+
+/// The magic number identifying serialized snapshots.
+const MAGIC: &[u8; 8] = b"BPFSNAP\0";
+/// The version of the serialization format.
+const VERSION: u32 = 1;
+
+
+/// A snapshot of the entries of a BPF map, along with metadata describing
+/// the map, as created by [`MapCore::snapshot`].
+///
+/// Snapshots can be serialized via [`MapSnapshot::to_bytes`], e.g., to
+/// persist the contents of a map across reboots or to migrate them to a
+/// different host, and restored via [`MapCore::restore`]. The metadata is
+/// stored in a fixed byte order, but keys and values are stored as is:
+/// if they contain multi-byte integers, restoring them on a host with a
+/// different byte order will not yield the expected result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapSnapshot {
+    name: OsString,
+    map_type: MapType,
+    key_size: u32,
+    value_size: u32,
+    /// The number of possible CPUs values of a per-cpu map were captured
+    /// for, or zero for other maps.
+    num_cpus: u32,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl MapSnapshot {
+    /// Capture the entries of `map`.
+    pub(crate) fn capture<M>(map: &M) -> Result<Self>
+    where
+        M: MapCore + ?Sized,
+    {
+        let map_type = map.map_type();
+        if !is_snapshottable(map_type) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                format!("maps of type {map_type:?} cannot be snapshotted"),
+            ))
+        }
+
+        let num_cpus = if map_type.is_percpu() {
+            crate::num_possible_cpus()? as u32
+        } else {
+            0
+        };
+
+        let mut iter = map.iter()?;
+        let mut entries = Vec::new();
+        while let Some(entry) = iter.try_next()? {
+            let () = entries.push(entry);
+        }
+
+        let snapshot = Self {
+            name: map.name().to_os_string(),
+            map_type,
+            key_size: map.key_size(),
+            value_size: map.value_size(),
+            num_cpus,
+            entries,
+        };
+        Ok(snapshot)
+    }
+
+    /// Write the entries of the snapshot into `map`.
+    pub(crate) fn restore_into<M>(&self, map: &M, flags: MapFlags) -> Result<()>
+    where
+        M: MapCore + ?Sized,
+    {
+        let map_type = map.map_type();
+        if map_type != self.map_type
+            || map.key_size() != self.key_size
+            || map.value_size() != self.value_size
+        {
+            return Err(Error::with_invalid_data(format!(
+                "snapshot of map `{}` ({:?}, key size {}, value size {}) is incompatible with map `{}` ({map_type:?}, key size {}, value size {})",
+                self.name.to_string_lossy(),
+                self.map_type,
+                self.key_size,
+                self.value_size,
+                map.name().to_string_lossy(),
+                map.key_size(),
+                map.value_size(),
+            )))
+        }
+
+        if map_type.is_percpu() {
+            let num_cpus = crate::num_possible_cpus()? as u32;
+            if num_cpus != self.num_cpus {
+                return Err(Error::with_invalid_data(format!(
+                    "snapshot of per-cpu map `{}` was taken on a system with {} possible CPUs, but this system has {num_cpus}",
+                    self.name.to_string_lossy(),
+                    self.num_cpus,
+                )))
+            }
+            // Values were captured in the format per-cpu updates expect, so
+            // they can be passed on as is.
+            for (key, value) in &self.entries {
+                let () = check_percpu_buffer_size(map, value)?;
+                let () = update_raw(map, key, value, flags)?;
+            }
+        } else {
+            for (key, value) in &self.entries {
+                let () = map.update(key, value, flags)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Retrieve the name of the map the snapshot was taken of.
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// Retrieve the type of the map the snapshot was taken of.
+    pub fn map_type(&self) -> MapType {
+        self.map_type
+    }
+
+    /// Retrieve the size of the map's keys in bytes.
+    pub fn key_size(&self) -> u32 {
+        self.key_size
+    }
+
+    /// Retrieve the size of the map's values in bytes.
+    pub fn value_size(&self) -> u32 {
+        self.value_size
+    }
+
+    /// Retrieve the captured entries, as pairs of key and value.
+    ///
+    /// Values of per-cpu maps are formatted as for [`MapCore::iter`].
+    pub fn entries(&self) -> &[(Vec<u8>, Vec<u8>)] {
+        &self.entries
+    }
+
+    /// Serialize the snapshot.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.name.as_bytes();
+        let mut bytes = Vec::new();
+        let () = bytes.extend_from_slice(MAGIC);
+        let () = bytes.extend_from_slice(&VERSION.to_le_bytes());
+        let () = bytes.extend_from_slice(&u32::from(self.map_type).to_le_bytes());
+        let () = bytes.extend_from_slice(&self.key_size.to_le_bytes());
+        let () = bytes.extend_from_slice(&self.value_size.to_le_bytes());
+        let () = bytes.extend_from_slice(&self.num_cpus.to_le_bytes());
+        let () = bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        let () = bytes.extend_from_slice(name);
+        let () = bytes.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+        for (key, value) in &self.entries {
+            let () = bytes.extend_from_slice(key);
+            let () = bytes.extend_from_slice(value);
+        }
+        bytes
+    }
+
+    /// Deserialize a snapshot previously serialized with
+    /// [`MapSnapshot::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        if reader.read(MAGIC.len())? != MAGIC {
+            return Err(Error::with_invalid_data("data is not a map snapshot"))
+        }
+        let version = reader.read_u32()?;
+        if version != VERSION {
+            return Err(Error::with_invalid_data(format!(
+                "unsupported map snapshot version {version}"
+            )))
+        }
+
+        let map_type = MapType::from(reader.read_u32()?);
+        let key_size = reader.read_u32()?;
+        let value_size = reader.read_u32()?;
+        let num_cpus = reader.read_u32()?;
+        let name_len = reader.read_u32()? as usize;
+        let name = OsString::from_vec(reader.read(name_len)?.to_vec());
+        let count = reader.read_u64()?;
+
+        let value_len = if map_type.is_percpu() {
+            (num_cpus as usize).checked_mul(util::roundup(value_size as usize, 8))
+        } else {
+            Some(value_size as usize)
+        };
+        let entry_len = value_len
+            .and_then(|value_len| value_len.checked_add(key_size as usize))
+            .filter(|entry_len| *entry_len > 0)
+            .ok_or_else(|| Error::with_invalid_data("map snapshot has invalid entry size"))?;
+        // Don't trust the count, but check it against the data present,
+        // before looping over entries.
+        let len = usize::try_from(count)
+            .ok()
+            .and_then(|count| count.checked_mul(entry_len))
+            .ok_or_else(|| Error::with_invalid_data("map snapshot is truncated"))?;
+        if len > reader.0.len() {
+            return Err(Error::with_invalid_data("map snapshot is truncated"))
+        }
+        if len < reader.0.len() {
+            return Err(Error::with_invalid_data(
+                "map snapshot contains trailing data",
+            ))
+        }
+
+        let mut entries = Vec::with_capacity(len / entry_len);
+        for _ in 0..count {
+            let key = reader.read(key_size as usize)?.to_vec();
+            let value = reader.read(entry_len - key_size as usize)?.to_vec();
+            let () = entries.push((key, value));
+        }
+
+        let snapshot = Self {
+            name,
+            map_type,
+            key_size,
+            value_size,
+            num_cpus,
+            entries,
+        };
+        Ok(snapshot)
+    }
+}
+
+
+/// Check whether the contents of maps of the given type can be
+/// snapshotted.
+///
+/// Besides maps that don't support iteration, maps referencing kernel
+/// objects such as programs, sockets, or other maps are excluded, as the
+/// file descriptors they were populated with are meaningless outside of
+/// the process that inserted them.
+fn is_snapshottable(map_type: MapType) -> bool {
+    map_type.supports_key_iteration()
+        && !matches!(
+            map_type,
+            MapType::ProgArray
+                | MapType::PerfEventArray
+                | MapType::CgroupArray
+                | MapType::ArrayOfMaps
+                | MapType::HashOfMaps
+                | MapType::Devmap
+                | MapType::DevmapHash
+                | MapType::Sockmap
+                | MapType::Sockhash
+                | MapType::Cpumap
+                | MapType::Xskmap
+                | MapType::ReuseportSockarray
+                | MapType::StackTrace
+        )
+}
+
+
+/// A cursor over serialized snapshot data.
+struct Reader<'dat>(&'dat [u8]);
+
+impl<'dat> Reader<'dat> {
+    fn read(&mut self, len: usize) -> Result<&'dat [u8]> {
+        if self.0.len() < len {
+            return Err(Error::with_invalid_data("map snapshot is truncated"))
+        }
+        let (data, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(data)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let data = self.read(4)?;
+        Ok(u32::from_le_bytes(data.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let data = self.read(8)?;
+        Ok(u64::from_le_bytes(data.try_into().unwrap()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that snapshots survive a serialization round trip.
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = MapSnapshot {
+            name: OsString::from("allowlist"),
+            map_type: MapType::Hash,
+            key_size: 4,
+            value_size: 2,
+            num_cpus: 0,
+            entries: vec![
+                (vec![1, 2, 3, 4], vec![5, 6]),
+                (vec![7, 8, 9, 10], vec![11, 12]),
+            ],
+        };
+        let bytes = snapshot.to_bytes();
+        assert_eq!(MapSnapshot::from_bytes(&bytes).unwrap(), snapshot);
+
+        let err = MapSnapshot::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{err}");
+
+        // Entries without any data are rejected, irrespective of the
+        // count claimed.
+        let empty = MapSnapshot {
+            key_size: 0,
+            value_size: 0,
+            entries: Vec::new(),
+            ..snapshot.clone()
+        };
+        let mut bytes = empty.to_bytes();
+        let () = bytes.truncate(bytes.len() - 8);
+        let () = bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        let err = MapSnapshot::from_bytes(&bytes).unwrap_err();
+        assert!(err.to_string().contains("invalid entry size"), "{err}");
+
+        let err = MapSnapshot::from_bytes(b"not a snapshot").unwrap_err();
+        assert!(err.to_string().contains("not a map snapshot"), "{err}");
+    }
+}
@@ -0,0 +1,397 @@
+//! Runtime statistics about BPF programs and maps owned by the current
+//! process.
+//!
+//! Long running agents typically want to keep an eye on the overhead
+//! their BPF programs incur and the memory their maps consume. This
+//! module provides a pull based API in the form of [`Collector::snapshot`]
+//! as well as a push based one, [`Collector::spawn`], which periodically
+//! invokes a user provided callback with a fresh [`Snapshot`].
+//!
+//! Note that the kernel only accounts program run times while run time
+//! statistics are enabled, e.g., by means of [`enable_run_time_stats`] or
+//! the `kernel.bpf_stats_enabled` sysctl.
+
+use std::collections::HashSet;
+use std::ffi::c_void;
+use std::fs;
+use std::mem;
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::map::MapKeyIter;
+use crate::util;
+use crate::util::BpfObjectType;
+use crate::ErrorExt as _;
+use crate::MapType;
+use crate::ProgramType;
+use crate::Result;
+
+
+/// Statistics about a single BPF program.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProgramStats {
+    /// The program's ID.
+    pub id: u32,
+    /// The program's name.
+    pub name: String,
+    /// The program's type.
+    pub ty: ProgramType,
+    /// The accumulated time spent running the program.
+    ///
+    /// Only accounted while run time statistics are enabled.
+    pub run_time: Duration,
+    /// The number of times the program ran.
+    ///
+    /// Only accounted while run time statistics are enabled.
+    pub run_count: u64,
+    /// The number of times the program was skipped due to recursion.
+    pub recursion_misses: u64,
+    /// The amount of memory charged for the program, if known.
+    pub memlock: Option<u64>,
+}
+
+/// Statistics about a single BPF map.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MapStats {
+    /// The map's ID.
+    pub id: u32,
+    /// The map's name.
+    pub name: String,
+    /// The map's type.
+    pub ty: MapType,
+    /// The maximum number of entries the map can hold.
+    pub max_entries: u32,
+    /// The number of entries currently stored in the map.
+    ///
+    /// Only reported if requested via [`Collector::count_entries`] and
+    /// the map type supports key iteration.
+    pub entries: Option<u64>,
+    /// The amount of memory charged for the map, if known.
+    pub memlock: Option<u64>,
+}
+
+/// A snapshot of statistics about all BPF programs and maps the
+/// process has file descriptors to.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Snapshot {
+    /// Statistics about programs.
+    pub programs: Vec<ProgramStats>,
+    /// Statistics about maps.
+    pub maps: Vec<MapStats>,
+}
+
+
+/// Enable collection of program run time statistics system wide.
+///
+/// Statistics stay enabled for as long as the returned file descriptor is
+/// kept open.
+pub fn enable_run_time_stats() -> Result<OwnedFd> {
+    // SAFETY: `bpf_enable_stats` is always safe to call.
+    let fd = unsafe { libbpf_sys::bpf_enable_stats(libbpf_sys::BPF_STATS_RUN_TIME) };
+    let fd = util::parse_ret_i32(fd).context("failed to enable BPF run time statistics")?;
+    // SAFETY: A file descriptor coming from the `bpf_enable_stats`
+    //         function is always suitable for ownership and can be
+    //         cleaned up with close.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Read the `memlock` value reported in `/proc/self/fdinfo/<fd>`.
+fn read_memlock(fd: RawFd) -> Option<u64> {
+    let fdinfo = fs::read_to_string(format!("/proc/self/fdinfo/{fd}")).ok()?;
+    fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("memlock:"))
+        .and_then(|memlock| memlock.trim().parse().ok())
+}
+
+/// Convert a NUL terminated `c_char` name as found in BPF info structs
+/// into a `String`.
+fn info_name(name: &[libc::c_char]) -> String {
+    util::c_char_slice_to_cstr(name)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn obj_info<T: Default>(fd: BorrowedFd<'_>) -> Result<T> {
+    let mut info = T::default();
+    let mut len = mem::size_of::<T>() as u32;
+    // SAFETY: All pointers are derived from references and hence valid.
+    let ret = unsafe {
+        libbpf_sys::bpf_obj_get_info_by_fd(
+            fd.as_raw_fd(),
+            &mut info as *mut T as *mut c_void,
+            &mut len,
+        )
+    };
+    let () = util::parse_ret(ret)?;
+    Ok(info)
+}
+
+fn program_stats(fd: BorrowedFd<'_>) -> Result<ProgramStats> {
+    let info = obj_info::<libbpf_sys::bpf_prog_info>(fd)?;
+    let stats = ProgramStats {
+        id: info.id,
+        name: info_name(&info.name),
+        ty: ProgramType::from(info.type_),
+        run_time: Duration::from_nanos(info.run_time_ns),
+        run_count: info.run_cnt,
+        recursion_misses: info.recursion_misses,
+        memlock: read_memlock(fd.as_raw_fd()),
+    };
+    Ok(stats)
+}
+
+fn map_stats(fd: BorrowedFd<'_>, count_entries: bool) -> Result<MapStats> {
+    let info = obj_info::<libbpf_sys::bpf_map_info>(fd)?;
+    let ty = MapType::from(info.type_);
+    let entries = if count_entries && ty.supports_key_iteration() {
+        let mut keys = MapKeyIter::new(fd, ty, info.key_size, info.value_size);
+        let mut count = 0;
+        while keys.try_next()?.is_some() {
+            count += 1;
+        }
+        Some(count)
+    } else {
+        None
+    };
+
+    let stats = MapStats {
+        id: info.id,
+        name: info_name(&info.name),
+        ty,
+        max_entries: info.max_entries,
+        entries,
+        memlock: read_memlock(fd.as_raw_fd()),
+    };
+    Ok(stats)
+}
+
+/// Check whether the target of a `/proc/self/fd` link refers to a BPF
+/// program or map.
+fn is_bpf_object_link(target: &Path) -> bool {
+    matches!(
+        target.as_os_str().as_bytes(),
+        b"anon_inode:bpf-prog" | b"anon_inode:bpf-map"
+    )
+}
+
+/// Duplicate the file descriptor `fd` of the current process.
+///
+/// We work with a duplicate so that other threads closing the original
+/// file descriptor concurrently cannot cause us to operate on a stale
+/// or reused descriptor.
+fn dup_fd(fd: RawFd) -> Option<OwnedFd> {
+    // SAFETY: `fcntl` is always safe to call; it merely fails for
+    //         invalid file descriptors.
+    let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if dup < 0 {
+        return None
+    }
+    // SAFETY: We just created the file descriptor and nobody else owns
+    //         it.
+    Some(unsafe { OwnedFd::from_raw_fd(dup) })
+}
+
+/// Invoke `f` with a duplicate of each file descriptor of the current
+/// process that refers to a BPF program or map, one at a time.
+fn for_each_bpf_fd<F>(mut f: F) -> Result<()>
+where
+    F: FnMut(BorrowedFd<'_>) -> Result<()>,
+{
+    let entries = fs::read_dir("/proc/self/fd").context("failed to read `/proc/self/fd`")?;
+    for entry in entries {
+        let entry = entry.context("failed to read `/proc/self/fd` entry")?;
+        let name = entry.file_name();
+        let Some(fd) = name.to_str().and_then(|name| name.parse::<RawFd>().ok()) else {
+            continue
+        };
+        // The file descriptor may be gone already, in which case there
+        // is nothing to report.
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue
+        };
+        if !is_bpf_object_link(&target) {
+            continue
+        }
+        if let Some(dup) = dup_fd(fd) {
+            let () = f(dup.as_fd())?;
+        }
+    }
+    Ok(())
+}
+
+/// A collector of BPF statistics.
+#[derive(Clone, Debug, Default)]
+pub struct Collector {
+    count_entries: bool,
+}
+
+impl Collector {
+    /// Create a new `Collector` with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to count the number of entries stored in each map.
+    ///
+    /// Counting requires iterating over all keys of a map and can be
+    /// costly for large maps. It is disabled by default.
+    pub fn count_entries(mut self, count_entries: bool) -> Self {
+        self.count_entries = count_entries;
+        self
+    }
+
+    /// Take a snapshot of statistics for all BPF programs and maps the
+    /// process has open file descriptors to.
+    ///
+    /// Objects referenced by more than one file descriptor are reported
+    /// only once.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        let mut snapshot = Snapshot::default();
+        let mut prog_ids = HashSet::new();
+        let mut map_ids = HashSet::new();
+
+        let () = for_each_bpf_fd(|fd| {
+            // The file descriptor may have been reused for an object of
+            // a different kind since we looked at it, so we check again.
+            match util::object_type_from_fd(fd) {
+                Ok(BpfObjectType::Program) => {
+                    let stats = program_stats(fd)?;
+                    if prog_ids.insert(stats.id) {
+                        let () = snapshot.programs.push(stats);
+                    }
+                }
+                Ok(BpfObjectType::Map) => {
+                    let stats = map_stats(fd, self.count_entries)?;
+                    if map_ids.insert(stats.id) {
+                        let () = snapshot.maps.push(stats);
+                    }
+                }
+                Ok(BpfObjectType::Link) | Err(..) => (),
+            }
+            Ok(())
+        })?;
+        Ok(snapshot)
+    }
+
+    /// Periodically take snapshots on a background thread, reporting
+    /// each one to the provided callback.
+    ///
+    /// The first snapshot is taken immediately. Collection stops once
+    /// the returned [`Monitor`] is dropped.
+    pub fn spawn<F>(self, interval: Duration, mut callback: F) -> Result<Monitor>
+    where
+        F: FnMut(Result<Snapshot>) + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name("bpf-stats".to_string())
+            .spawn(move || loop {
+                let () = callback(self.snapshot());
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            })
+            .context("failed to spawn BPF statistics thread")?;
+
+        let monitor = Monitor {
+            stop: Some(stop),
+            thread: Some(thread),
+        };
+        Ok(monitor)
+    }
+}
+
+
+/// A handle to a background thread periodically collecting statistics,
+/// as created by [`Collector::spawn`].
+///
+/// Dropping the handle stops collection and waits for the thread to
+/// exit.
+#[derive(Debug)]
+pub struct Monitor {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the thread.
+        let () = drop(self.stop.take());
+
+        if let Some(thread) = self.thread.take() {
+            let _result = thread.join();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that we recognize BPF programs and maps by their file
+    /// descriptor's link target.
+    #[test]
+    fn bpf_object_links() {
+        assert!(is_bpf_object_link(Path::new("anon_inode:bpf-prog")));
+        assert!(is_bpf_object_link(Path::new("anon_inode:bpf-map")));
+        assert!(!is_bpf_object_link(Path::new("anon_inode:bpf_link")));
+        assert!(!is_bpf_object_link(Path::new("anon_inode:[eventfd]")));
+        assert!(!is_bpf_object_link(Path::new("/dev/null")));
+        assert!(!is_bpf_object_link(Path::new("socket:[1234]")));
+    }
+
+    /// Check that a snapshot reports each object only once, irrespective
+    /// of which other BPF objects the process has open.
+    #[test]
+    fn snapshot_unique_objects() {
+        let snapshot = Collector::new().snapshot().unwrap();
+        let prog_ids = snapshot
+            .programs
+            .iter()
+            .map(|prog| prog.id)
+            .collect::<HashSet<_>>();
+        assert_eq!(prog_ids.len(), snapshot.programs.len());
+        let map_ids = snapshot
+            .maps
+            .iter()
+            .map(|map| map.id)
+            .collect::<HashSet<_>>();
+        assert_eq!(map_ids.len(), snapshot.maps.len());
+    }
+
+    /// Check that a spawned monitor reports snapshots and shuts down when
+    /// dropped.
+    #[test]
+    fn monitor_shutdown() {
+        let (tx, rx) = mpsc::channel();
+        let monitor = Collector::new()
+            .spawn(Duration::from_millis(10), move |snapshot| {
+                let _result = tx.send(snapshot.is_ok());
+            })
+            .unwrap();
+
+        assert!(rx.recv().unwrap());
+        let () = drop(monitor);
+        // After the monitor is gone the sending end got dropped as well.
+        assert!(rx.iter().all(|ok| ok));
+    }
+}
@@ -0,0 +1,462 @@
+//! A minimal parser for the parts of 64 bit ELF files relevant to
+//! symbolization.
+
+use std::borrow::Cow;
+use std::fs;
+use std::mem::size_of;
+use std::ops::Range;
+use std::path::Path;
+use std::ptr;
+use std::str;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+use super::inflate::inflate_zlib;
+
+pub(crate) const ELFMAG: &[u8] = b"\x7fELF";
+pub(crate) const ELFCLASS64: u8 = 2;
+pub(crate) const ELFDATA2LSB: u8 = 1;
+pub(crate) const ELFDATA2MSB: u8 = 2;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+
+pub(crate) const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+
+const SHF_COMPRESSED: u64 = 0x800;
+
+const SHN_UNDEF: u16 = 0;
+
+const STT_FUNC: u8 = 2;
+const STT_GNU_IFUNC: u8 = 10;
+
+const NT_GNU_BUILD_ID: u32 = 3;
+
+const ELFCOMPRESS_ZLIB: u32 = 1;
+
+
+// Mirrors of the structures defined in `elf.h`, not all of whose members
+// we make use of.
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub(crate) struct Elf64Ehdr {
+    pub e_ident: [u8; 16],
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_version: u32,
+    pub e_entry: u64,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_flags: u32,
+    pub e_ehsize: u16,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+struct Elf64Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub(crate) struct Elf64Shdr {
+    pub sh_name: u32,
+    pub sh_type: u32,
+    pub sh_flags: u64,
+    pub sh_addr: u64,
+    pub sh_offset: u64,
+    pub sh_size: u64,
+    pub sh_link: u32,
+    pub sh_info: u32,
+    pub sh_addralign: u64,
+    pub sh_entsize: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub(crate) struct Elf64Sym {
+    pub st_name: u32,
+    pub st_info: u8,
+    pub st_other: u8,
+    pub st_shndx: u16,
+    pub st_value: u64,
+    pub st_size: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub(crate) struct Elf64Rel {
+    pub r_offset: u64,
+    pub r_info: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+struct Elf64Nhdr {
+    n_namesz: u32,
+    n_descsz: u32,
+    n_type: u32,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+struct Elf64Chdr {
+    ch_type: u32,
+    ch_reserved: u32,
+    ch_size: u64,
+    ch_addralign: u64,
+}
+
+/// A marker for types that are valid for any bit pattern.
+///
+/// # Safety
+/// Implementors must be `repr(C)` structs made up only of integers.
+pub(crate) unsafe trait Pod: Copy {}
+
+// SAFETY: All of these are `repr(C)` structs of integers.
+unsafe impl Pod for Elf64Ehdr {}
+// SAFETY: See above.
+unsafe impl Pod for Elf64Phdr {}
+// SAFETY: See above.
+unsafe impl Pod for Elf64Shdr {}
+// SAFETY: See above.
+unsafe impl Pod for Elf64Sym {}
+// SAFETY: See above.
+unsafe impl Pod for Elf64Rel {}
+// SAFETY: See above.
+unsafe impl Pod for Elf64Nhdr {}
+// SAFETY: See above.
+unsafe impl Pod for Elf64Chdr {}
+
+
+/// Convert a file offset and size into a range, checking it against
+/// the size of the data.
+pub(crate) fn range(data: &[u8], offset: u64, size: u64) -> Result<Range<usize>> {
+    let start = usize::try_from(offset).ok();
+    let end = start.and_then(|start| start.checked_add(usize::try_from(size).ok()?));
+    match (start, end) {
+        (Some(start), Some(end)) if end <= data.len() => Ok(start..end),
+        _ => Err(Error::with_invalid_data(format!(
+            "ELF data range {offset:#x}+{size:#x} is out of bounds"
+        ))),
+    }
+}
+
+/// Calculate the offset of entry `idx` of a table of `entsize` byte
+/// entries starting at `offset`.
+fn table_offset(offset: u64, idx: u64, entsize: u64) -> Result<u64> {
+    idx.checked_mul(entsize)
+        .and_then(|rel| offset.checked_add(rel))
+        .ok_or_else(|| {
+            Error::with_invalid_data(format!(
+                "ELF table entry {idx} at {offset:#x} is out of bounds"
+            ))
+        })
+}
+
+pub(crate) fn read<T: Pod>(data: &[u8], offset: u64) -> Result<T> {
+    let range = range(data, offset, size_of::<T>() as u64)?;
+    // SAFETY: The range is in bounds and `T` is valid for any bit
+    //         pattern.
+    let value = unsafe { ptr::read_unaligned(data[range].as_ptr().cast::<T>()) };
+    Ok(value)
+}
+
+/// Read the NUL terminated string at `offset` of the provided string
+/// table.
+pub(crate) fn read_str(strtab: &[u8], offset: u32) -> Option<&str> {
+    let bytes = strtab.get(offset as usize..)?;
+    let end = bytes.iter().position(|b| *b == 0)?;
+    str::from_utf8(&bytes[..end]).ok()
+}
+
+/// Retrieve the contents of a section, decompressing them if necessary.
+fn section_data<'data>(data: &'data [u8], shdr: &Elf64Shdr) -> Result<Cow<'data, [u8]>> {
+    let section = &data[range(data, shdr.sh_offset, shdr.sh_size)?];
+    if shdr.sh_flags & SHF_COMPRESSED == 0 {
+        return Ok(Cow::Borrowed(section))
+    }
+
+    let chdr = read::<Elf64Chdr>(section, 0)?;
+    if chdr.ch_type != ELFCOMPRESS_ZLIB {
+        return Err(Error::with_invalid_data(format!(
+            "unsupported ELF section compression type {}",
+            chdr.ch_type
+        )))
+    }
+    let size = usize::try_from(chdr.ch_size).map_err(|_| {
+        Error::with_invalid_data(format!(
+            "compressed ELF section size {} is too large",
+            chdr.ch_size
+        ))
+    })?;
+    let decompressed = inflate_zlib(&section[size_of::<Elf64Chdr>()..], size)?;
+    Ok(Cow::Owned(decompressed))
+}
+
+
+/// A function symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Symbol {
+    /// The symbol's name.
+    pub name: String,
+    /// The symbol's virtual address.
+    pub addr: u64,
+    /// The symbol's size, which may be zero if unknown.
+    pub size: u64,
+}
+
+/// A loadable segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Segment {
+    /// The segment's offset in the file.
+    pub offset: u64,
+    /// The segment's virtual address.
+    pub vaddr: u64,
+    /// The segment's size in the file.
+    pub size: u64,
+}
+
+/// The symbolization relevant contents of an ELF file.
+#[derive(Clone, Debug)]
+pub(crate) struct ElfFile {
+    /// Function symbols, sorted by address.
+    symbols: Vec<Symbol>,
+    /// Loadable segments.
+    segments: Vec<Segment>,
+    /// The GNU build ID, if present.
+    build_id: Option<Vec<u8>>,
+}
+
+impl ElfFile {
+    /// Parse the ELF file at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = fs::read(path)
+            .with_context(|| format!("failed to read ELF file `{}`", path.display()))?;
+        Self::parse(&data).with_context(|| format!("failed to parse ELF file `{}`", path.display()))
+    }
+
+    /// Parse an ELF file from its contents.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let ehdr = read::<Elf64Ehdr>(data, 0)?;
+        if !ehdr.e_ident.starts_with(ELFMAG) {
+            return Err(Error::with_invalid_data("not an ELF file"))
+        }
+        if ehdr.e_ident[4] != ELFCLASS64 {
+            return Err(Error::with_invalid_data(
+                "only 64 bit ELF files are supported",
+            ))
+        }
+        let native = if cfg!(target_endian = "little") {
+            ELFDATA2LSB
+        } else {
+            ELFDATA2MSB
+        };
+        if ehdr.e_ident[5] != native {
+            return Err(Error::with_invalid_data(
+                "ELF files of foreign endianness are not supported",
+            ))
+        }
+
+        let phdrs = (0..u64::from(ehdr.e_phnum))
+            .map(|idx| {
+                let offset = table_offset(ehdr.e_phoff, idx, u64::from(ehdr.e_phentsize))?;
+                read::<Elf64Phdr>(data, offset)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let shdrs = (0..u64::from(ehdr.e_shnum))
+            .map(|idx| {
+                let offset = table_offset(ehdr.e_shoff, idx, u64::from(ehdr.e_shentsize))?;
+                read::<Elf64Shdr>(data, offset)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let segments = phdrs
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_LOAD)
+            .map(|phdr| {
+                let in_bounds = phdr.p_offset.checked_add(phdr.p_filesz).is_some()
+                    && phdr.p_vaddr.checked_add(phdr.p_filesz).is_some();
+                if !in_bounds {
+                    return Err(Error::with_invalid_data(format!(
+                        "ELF segment {:#x}+{:#x} is out of bounds",
+                        phdr.p_offset, phdr.p_filesz
+                    )))
+                }
+                Ok(Segment {
+                    offset: phdr.p_offset,
+                    vaddr: phdr.p_vaddr,
+                    size: phdr.p_filesz,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let mut symbols = Vec::new();
+        for shdr in &shdrs {
+            if shdr.sh_type != SHT_SYMTAB && shdr.sh_type != SHT_DYNSYM {
+                continue
+            }
+            let strtab = shdrs.get(shdr.sh_link as usize).ok_or_else(|| {
+                Error::with_invalid_data(format!("invalid string table index {}", shdr.sh_link))
+            })?;
+            let strtab = section_data(data, strtab)?;
+            let symtab = section_data(data, shdr)?;
+            let sym_size = size_of::<Elf64Sym>() as u64;
+            for idx in 0..symtab.len() as u64 / sym_size {
+                let sym = read::<Elf64Sym>(&symtab, table_offset(0, idx, sym_size)?)?;
+                let ty = sym.st_info & 0xf;
+                if (ty != STT_FUNC && ty != STT_GNU_IFUNC)
+                    || sym.st_shndx == SHN_UNDEF
+                    || sym.st_value == 0
+                {
+                    continue
+                }
+                if let Some(name) = read_str(&strtab, sym.st_name).filter(|name| !name.is_empty()) {
+                    let () = symbols.push(Symbol {
+                        name: name.to_string(),
+                        addr: sym.st_value,
+                        size: sym.st_size,
+                    });
+                }
+            }
+        }
+        // `.symtab` and `.dynsym` commonly overlap.
+        let () = symbols.sort_by(|a, b| a.addr.cmp(&b.addr).then_with(|| a.name.cmp(&b.name)));
+        let () = symbols.dedup();
+
+        let build_id = phdrs
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_NOTE)
+            .find_map(|phdr| {
+                let notes = &data[range(data, phdr.p_offset, phdr.p_filesz).ok()?];
+                find_build_id(notes)
+            });
+
+        let slf = Self {
+            symbols,
+            segments,
+            build_id,
+        };
+        Ok(slf)
+    }
+
+    /// Translate a file offset into a virtual address, based on the
+    /// loadable segment containing it.
+    pub fn offset_to_vaddr(&self, offset: u64) -> Option<u64> {
+        self.segments.iter().find_map(|seg| {
+            let rel = offset
+                .checked_sub(seg.offset)
+                .filter(|rel| *rel < seg.size)?;
+            seg.vaddr.checked_add(rel)
+        })
+    }
+
+    /// Translate a virtual address into a file offset, based on the
+    /// loadable segment containing it.
+    pub fn vaddr_to_offset(&self, vaddr: u64) -> Option<u64> {
+        self.segments.iter().find_map(|seg| {
+            let rel = vaddr.checked_sub(seg.vaddr).filter(|rel| *rel < seg.size)?;
+            seg.offset.checked_add(rel)
+        })
+    }
+
+    /// Find the function symbol covering the virtual address `addr`.
+    pub fn find_symbol(&self, addr: u64) -> Option<&Symbol> {
+        let idx = self.symbols.partition_point(|sym| sym.addr <= addr);
+        let sym = self.symbols.get(idx.checked_sub(1)?)?;
+        // `addr` is at least `sym.addr`, by virtue of the lookup.
+        if sym.size != 0 && addr - sym.addr >= sym.size {
+            return None
+        }
+        Some(sym)
+    }
+
+    /// Find the function symbol with the given name.
+    ///
+    /// If multiple symbols share the name, the one with the lowest
+    /// address is reported.
+    pub fn find_symbol_by_name(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|sym| sym.name == name)
+    }
+
+    /// Retrieve the file's GNU build ID, if any.
+    pub fn build_id(&self) -> Option<&[u8]> {
+        self.build_id.as_deref()
+    }
+}
+
+/// Find the GNU build ID in a sequence of ELF notes.
+fn find_build_id(mut notes: &[u8]) -> Option<Vec<u8>> {
+    let align = |n: u32| Some((usize::try_from(n).ok()?.checked_add(3)?) & !3);
+
+    while !notes.is_empty() {
+        let nhdr = read::<Elf64Nhdr>(notes, 0).ok()?;
+        let name_start = size_of::<Elf64Nhdr>();
+        let desc_start = name_start.checked_add(align(nhdr.n_namesz)?)?;
+        let end = desc_start.checked_add(align(nhdr.n_descsz)?)?;
+        let name = notes.get(name_start..)?.get(..nhdr.n_namesz as usize)?;
+        let desc = notes.get(desc_start..)?.get(..nhdr.n_descsz as usize)?;
+        if nhdr.n_type == NT_GNU_BUILD_ID && name == b"GNU\0" {
+            return Some(desc.to_vec())
+        }
+        notes = notes.get(end..)?;
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that we can parse the running executable and find
+    /// ourselves in it.
+    #[test]
+    fn self_parsing() {
+        let elf = ElfFile::open(Path::new("/proc/self/exe")).unwrap();
+        assert!(!elf.segments.is_empty());
+        assert!(elf.symbols.windows(2).all(|w| w[0].addr <= w[1].addr));
+
+        let sym = elf
+            .symbols
+            .iter()
+            .find(|sym| sym.name.contains("self_parsing"))
+            .unwrap();
+        assert_eq!(elf.find_symbol(sym.addr), Some(sym));
+        assert_eq!(elf.find_symbol_by_name(&sym.name), Some(sym));
+
+        let offset = elf.vaddr_to_offset(sym.addr).unwrap();
+        assert_eq!(elf.offset_to_vaddr(offset), Some(sym.addr));
+    }
+
+    /// Check that we reject data that is not a valid ELF file.
+    #[test]
+    fn invalid_data() {
+        assert!(ElfFile::parse(&[]).is_err());
+        assert!(ElfFile::parse(&[0; 64]).is_err());
+    }
+}
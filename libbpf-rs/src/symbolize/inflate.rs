@@ -0,0 +1,385 @@
+//! A minimal decoder for zlib (RFC 1950) wrapped DEFLATE (RFC 1951)
+//! streams, as used by compressed ELF sections.
+
+use crate::Error;
+use crate::Result;
+
+
+/// The maximum length of a Huffman code.
+const MAX_BITS: usize = 15;
+/// The maximum size of decompressed data we produce.
+const MAX_OUTPUT: usize = 1 << 30;
+/// The maximum capacity we reserve for decompressed data upfront.
+const MAX_CAPACITY_HINT: usize = 16 << 20;
+
+/// Base lengths for length symbols 257..=285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+/// Extra bits for length symbols 257..=285.
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances for distance symbols 0..=29.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+/// Extra bits for distance symbols 0..=29.
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order in which code length code lengths are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+
+fn invalid(msg: &str) -> Error {
+    Error::with_invalid_data(format!("invalid zlib stream: {msg}"))
+}
+
+
+/// A reader of a DEFLATE stream's bits, least significant first.
+struct BitReader<'data> {
+    data: &'data [u8],
+    pos: usize,
+    buf: u32,
+    nbits: u32,
+}
+
+impl<'data> BitReader<'data> {
+    fn new(data: &'data [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buf: 0,
+            nbits: 0,
+        }
+    }
+
+    /// Read `n` bits, with `n` being at most 16.
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        while self.nbits < n {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid("unexpected end of data"))?;
+            self.pos += 1;
+            self.buf |= u32::from(byte) << self.nbits;
+            self.nbits += 8;
+        }
+        let value = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.nbits -= n;
+        Ok(value)
+    }
+
+    /// Discard the remaining bits of the current byte and read `n`
+    /// bytes.
+    fn bytes(&mut self, n: usize) -> Result<&'data [u8]> {
+        self.buf = 0;
+        self.nbits = 0;
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+}
+
+
+/// A canonical Huffman code.
+struct Huffman {
+    /// The number of codes of each length.
+    counts: [u16; MAX_BITS + 1],
+    /// Symbols ordered by code.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for len in lengths {
+            let count = counts
+                .get_mut(usize::from(*len))
+                .ok_or_else(|| invalid("code length out of range"))?;
+            *count += 1;
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0; usize::from(offsets[MAX_BITS + 1])];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                let offset = &mut offsets[usize::from(*len)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader<'_>) -> Result<u16> {
+        // The first code of the current length, the index of its symbol,
+        // and the code read so far.
+        let (mut first, mut index, mut code) = (0u32, 0u32, 0u32);
+        for len in 1..=MAX_BITS {
+            code |= reader.bits(1)?;
+            let count = u32::from(self.counts[len]);
+            if code < first + count {
+                return Ok(self.symbols[(index + code - first) as usize])
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("undecodable Huffman code"))
+    }
+}
+
+
+/// Check that `len` more bytes of output fit into `limit` bytes.
+fn check_limit(out: &[u8], len: usize, limit: usize) -> Result<()> {
+    if len > limit - out.len() {
+        return Err(invalid("decompressed data exceeds expected size"))
+    }
+    Ok(())
+}
+
+/// Decode the symbols of a Huffman compressed block, producing at most
+/// `limit` bytes of output in total.
+fn inflate_block(
+    reader: &mut BitReader<'_>,
+    out: &mut Vec<u8>,
+    limit: usize,
+    lengths: &Huffman,
+    dists: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = usize::from(lengths.decode(reader)?);
+        match symbol {
+            0..=255 => {
+                let () = check_limit(out, 1, limit)?;
+                let () = out.push(symbol as u8);
+            }
+            256 => break Ok(()),
+            _ => {
+                let idx = symbol - 257;
+                let (Some(base), Some(extra)) = (LENGTH_BASE.get(idx), LENGTH_EXTRA.get(idx))
+                else {
+                    return Err(invalid("invalid length symbol"))
+                };
+                let len = usize::from(*base) + reader.bits(u32::from(*extra))? as usize;
+
+                let idx = usize::from(dists.decode(reader)?);
+                let (Some(base), Some(extra)) = (DIST_BASE.get(idx), DIST_EXTRA.get(idx)) else {
+                    return Err(invalid("invalid distance symbol"))
+                };
+                let dist = usize::from(*base) + reader.bits(u32::from(*extra))? as usize;
+                if dist > out.len() {
+                    return Err(invalid("distance too far back"))
+                }
+                let () = check_limit(out, len, limit)?;
+
+                // The source and destination may overlap, so copy byte
+                // by byte.
+                let start = out.len() - dist;
+                for idx in start..start + len {
+                    let () = out.push(out[idx]);
+                }
+            }
+        }
+    }
+}
+
+/// Read the code length and distance codes of a dynamic block.
+fn dynamic_codes(reader: &mut BitReader<'_>) -> Result<(Huffman, Huffman)> {
+    let nlen = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for idx in CODE_LENGTH_ORDER.iter().take(ncode) {
+        lengths[*idx] = reader.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut idx = 0;
+    while idx < lengths.len() {
+        let symbol = code.decode(reader)?;
+        let (len, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let prev = *idx
+                    .checked_sub(1)
+                    .and_then(|prev| lengths.get(prev))
+                    .ok_or_else(|| invalid("repeated length without predecessor"))?;
+                (prev, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        let fill = lengths
+            .get_mut(idx..idx + repeat)
+            .ok_or_else(|| invalid("too many code lengths"))?;
+        let () = fill.fill(len);
+        idx += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err(invalid("missing end-of-block code"))
+    }
+    let lengths_code = Huffman::new(&lengths[..nlen])?;
+    let dists_code = Huffman::new(&lengths[nlen..])?;
+    Ok((lengths_code, dists_code))
+}
+
+/// Compute the Adler-32 checksum of `data`.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += u32::from(*byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Decompress a zlib stream.
+///
+/// `size` is the expected size of the decompressed data, as recorded
+/// alongside the (untrusted) stream. Decompression fails if the data do
+/// not match it or if it exceeds 1 GiB.
+pub(crate) fn inflate_zlib(data: &[u8], size: usize) -> Result<Vec<u8>> {
+    if size > MAX_OUTPUT {
+        return Err(invalid("decompressed size too large"))
+    }
+
+    let [cmf, flg, ..] = *data else {
+        return Err(invalid("missing header"))
+    };
+    if cmf & 0x0f != 8 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(invalid("unsupported header"))
+    }
+    if flg & 0x20 != 0 {
+        return Err(invalid("preset dictionaries are unsupported"))
+    }
+
+    let mut reader = BitReader::new(&data[2..]);
+    let mut out = Vec::with_capacity(size.min(MAX_CAPACITY_HINT));
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                let header = reader.bytes(4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err(invalid("stored block length mismatch"))
+                }
+                let () = check_limit(&out, usize::from(len), size)?;
+                let () = out.extend_from_slice(reader.bytes(usize::from(len))?);
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                let () = lengths[144..256].fill(9);
+                let () = lengths[256..280].fill(7);
+                let lengths = Huffman::new(&lengths)?;
+                let dists = Huffman::new(&[5; 30])?;
+                let () = inflate_block(&mut reader, &mut out, size, &lengths, &dists)?;
+            }
+            2 => {
+                let (lengths, dists) = dynamic_codes(&mut reader)?;
+                let () = inflate_block(&mut reader, &mut out, size, &lengths, &dists)?;
+            }
+            _ => return Err(invalid("reserved block type")),
+        }
+        if last {
+            break
+        }
+    }
+
+    let checksum = reader.bytes(4)?;
+    if u32::from_be_bytes(checksum.try_into().unwrap()) != adler32(&out) {
+        return Err(invalid("checksum mismatch"))
+    }
+    if out.len() != size {
+        return Err(invalid("decompressed data fall short of expected size"))
+    }
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Check that we can decompress streams using stored, fixed, and
+    /// dynamic Huffman blocks.
+    #[test]
+    fn zlib_inflation() {
+        let text = b"hello hello hello, compressed world! ".repeat(4);
+        // Level 0, i.e., stored.
+        let stored = unhex(concat!(
+            "78010194006bff",
+            "68656c6c6f2068656c6c6f2068656c6c6f2c20636f6d7072657373656420776f726c642120",
+            "68656c6c6f2068656c6c6f2068656c6c6f2c20636f6d7072657373656420776f726c642120",
+            "68656c6c6f2068656c6c6f2068656c6c6f2c20636f6d7072657373656420776f726c642120",
+            "68656c6c6f2068656c6c6f2068656c6c6f2c20636f6d7072657373656420776f726c642120",
+            "d6dd3619",
+        ));
+        assert_eq!(inflate_zlib(&stored, text.len()).unwrap(), text);
+        // Level 9, i.e., fixed Huffman codes.
+        let fixed = unhex(concat!(
+            "78dacb48cdc9c957c840903a0ac9f9b90545a9c5c5a9290ae5f94539298ac8d2f450",
+            "0400d6dd3619",
+        ));
+        assert_eq!(inflate_zlib(&fixed, text.len()).unwrap(), text);
+
+        let mut text = [
+            &b"pack my box with five dozen liquor jugs. "[..],
+            &b"sphinx of black quartz, judge my vow. "[..],
+        ]
+        .concat()
+        .repeat(2);
+        let () = text.extend(48..123);
+        let dynamic = unhex(concat!(
+            "78daadcc551283301440d1adbc057498babb0b75f72201822548b0d5b7eca1df77eea182",
+            "64801583482208b1af818203043249900d267618714167aac78147356c47401410cdf471",
+            "98e0fa49e6576515a54240420ee87fb96c2e5f2896ca956aadde68b6da9d6eaf3f188ec6",
+            "93e96cbee097abf566bbdb1f8ea7f3e57abb3f9eaff74710251929aa8675c3b46c421dd7",
+            "f359104671f205a49b51d2",
+        ));
+        assert_eq!(inflate_zlib(&dynamic, text.len()).unwrap(), text);
+        // The recorded size has to match the decompressed data.
+        assert!(inflate_zlib(&dynamic, text.len() - 1).is_err());
+        assert!(inflate_zlib(&dynamic, text.len() + 1).is_err());
+        assert!(inflate_zlib(&dynamic, usize::MAX).is_err());
+
+        let mut corrupted = dynamic.clone();
+        let () = corrupted.truncate(dynamic.len() - 1);
+        assert!(inflate_zlib(&corrupted, text.len()).is_err());
+        assert!(inflate_zlib(&[], 0).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Resolution of kernel symbols based on `/proc/kallsyms`.
+
+use std::fs;
+use std::io::BufRead;
+
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// A kernel symbol, as listed in `/proc/kallsyms`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ksym {
+    /// The symbol's name.
+    pub name: String,
+    /// The symbol's address.
+    pub addr: u64,
+    /// The symbol's type, as reported by `nm` (e.g., `T` for a global
+    /// text symbol, `d` for a local data symbol).
+    pub kind: char,
+    /// The module the symbol belongs to, if any.
+    pub module: Option<String>,
+}
+
+impl Ksym {
+    /// Check whether the symbol refers to code.
+    pub fn is_text(&self) -> bool {
+        matches!(self.kind, 't' | 'T' | 'w' | 'W')
+    }
+}
+
+
+/// A cache of the kernel's symbols, supporting lookups by address as
+/// well as by name.
+///
+/// Symbols of kernel modules come and go as modules are loaded and
+/// unloaded. Use [`Ksyms::refresh`] to bring the cache up to date.
+///
+/// If kernel addresses are hidden from the current process (see
+/// `kernel.kptr_restrict`), all addresses are reported as zero by the
+/// kernel and no symbols are available.
+#[derive(Clone, Debug, Default)]
+pub struct Ksyms {
+    /// All symbols, sorted by address.
+    symbols: Vec<Ksym>,
+    /// Indices into `symbols`, sorted by symbol name.
+    by_name: Vec<usize>,
+    /// The contents of `/proc/modules` at the time symbols were loaded.
+    modules: Option<Vec<u8>>,
+}
+
+impl Ksyms {
+    /// Load kernel symbols from `/proc/kallsyms`.
+    pub fn load() -> Result<Self> {
+        // Read modules first: if they change while we read symbols, the
+        // next refresh will notice.
+        let modules = read_modules();
+        let kallsyms = fs::read("/proc/kallsyms").context("failed to read `/proc/kallsyms`")?;
+        let mut slf = Self::parse(&kallsyms[..])?;
+        slf.modules = modules;
+        Ok(slf)
+    }
+
+    /// Parse kernel symbols in the `/proc/kallsyms` format.
+    ///
+    /// Symbols with a zero address are skipped.
+    fn parse<R: BufRead>(reader: R) -> Result<Self> {
+        let mut symbols = Vec::new();
+        for line in reader.lines() {
+            let line = line.context("failed to read kallsyms line")?;
+            let mut fields = line.split_ascii_whitespace();
+            let (Some(addr), Some(kind), Some(name)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue
+            };
+            let (Ok(addr), Some(kind)) = (u64::from_str_radix(addr, 16), kind.chars().next())
+            else {
+                continue
+            };
+            if addr == 0 {
+                continue
+            }
+            let module = fields
+                .next()
+                .and_then(|module| module.strip_prefix('['))
+                .and_then(|module| module.strip_suffix(']'))
+                .map(str::to_string);
+
+            let () = symbols.push(Ksym {
+                name: name.to_string(),
+                addr,
+                kind,
+                module,
+            });
+        }
+        let () = symbols.sort_by_key(|sym| sym.addr);
+
+        let mut by_name = (0..symbols.len()).collect::<Vec<_>>();
+        // Sort core kernel symbols before those of modules with the same
+        // name.
+        let () = by_name.sort_by(|a, b| {
+            let (a, b) = (&symbols[*a], &symbols[*b]);
+            a.name
+                .cmp(&b.name)
+                .then_with(|| a.module.is_some().cmp(&b.module.is_some()))
+        });
+
+        let slf = Self {
+            symbols,
+            by_name,
+            modules: None,
+        };
+        Ok(slf)
+    }
+
+    /// Reload symbols if the set of loaded kernel modules changed since
+    /// they were last loaded.
+    ///
+    /// Changes are detected based on the contents of `/proc/modules`.
+    ///
+    /// Returns `true` if symbols were reloaded.
+    pub fn refresh(&mut self) -> Result<bool> {
+        let modules = read_modules();
+        if modules == self.modules {
+            return Ok(false)
+        }
+        *self = Self::load()?;
+        Ok(true)
+    }
+
+    /// Find the symbol covering `addr`, i.e., the one with the highest
+    /// address not exceeding it.
+    pub fn find(&self, addr: u64) -> Option<&Ksym> {
+        let idx = self.symbols.partition_point(|sym| sym.addr <= addr);
+        self.symbols.get(idx.checked_sub(1)?)
+    }
+
+    /// Find the symbol covering `addr` like [`Ksyms::find`], but only if
+    /// it is followed by another symbol of the same module (or of the
+    /// core kernel).
+    ///
+    /// Addresses beyond the last symbol of a module or the core kernel
+    /// may belong to a module loaded after symbols were loaded.
+    pub(crate) fn find_enclosed(&self, addr: u64) -> Option<&Ksym> {
+        let idx = self
+            .symbols
+            .partition_point(|sym| sym.addr <= addr)
+            .checked_sub(1)?;
+        let sym = &self.symbols[idx];
+        let next = self.symbols.get(idx + 1)?;
+        (next.module == sym.module).then_some(sym)
+    }
+
+    /// Retrieve all symbols with the given name.
+    ///
+    /// Symbols of the core kernel are reported before those of modules.
+    pub fn find_by_name(&self, name: &str) -> impl Iterator<Item = &Ksym> {
+        let name_of = |idx: &usize| self.symbols[*idx].name.as_str();
+        let start = self.by_name.partition_point(|idx| name_of(idx) < name);
+        let end = self.by_name.partition_point(|idx| name_of(idx) <= name);
+        self.by_name[start..end]
+            .iter()
+            .map(|idx| &self.symbols[*idx])
+    }
+
+    /// Retrieve the address of the symbol with the given name.
+    ///
+    /// If multiple symbols share the name, the address of the core
+    /// kernel's one is reported, if any.
+    pub fn addr_of(&self, name: &str) -> Option<u64> {
+        self.find_by_name(name).next().map(|sym| sym.addr)
+    }
+
+    /// Retrieve an iterator over all symbols, sorted by address.
+    pub fn iter(&self) -> impl Iterator<Item = &Ksym> {
+        self.symbols.iter()
+    }
+}
+
+/// Read `/proc/modules`, which may not exist on kernels built without
+/// module support.
+fn read_modules() -> Option<Vec<u8>> {
+    fs::read("/proc/modules").ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that we can parse and query data in the kallsyms format.
+    #[test]
+    fn kallsyms_parsing() {
+        let kallsyms = b"\
+ffffffff81000000 T _stext
+ffffffff81000100 t do_one_initcall
+ffffffff82000000 D some_data
+ffffffffc0000000 t nf_hook [nf_tables]
+ffffffffc0001000 t do_one_initcall [dummy]
+0000000000000000 T hidden
+";
+        let syms = Ksyms::parse(&kallsyms[..]).unwrap();
+        assert_eq!(syms.iter().count(), 5);
+
+        assert_eq!(syms.find(0xffffffff81000000).unwrap().name, "_stext");
+        assert_eq!(
+            syms.find(0xffffffff81000142).unwrap().name,
+            "do_one_initcall"
+        );
+        let sym = syms.find(0xffffffffc0000010).unwrap();
+        assert_eq!(sym.name, "nf_hook");
+        assert!(sym.is_text());
+        assert_eq!(sym.module.as_deref(), Some("nf_tables"));
+        assert_eq!(syms.find(0x1000), None);
+
+        assert_eq!(
+            syms.find_enclosed(0xffffffff81000142).unwrap().name,
+            "do_one_initcall"
+        );
+        // Addresses past the last symbol of the core kernel or a module
+        // may belong to a module loaded since.
+        assert_eq!(syms.find_enclosed(0xffffffff82000010), None);
+        assert_eq!(syms.find_enclosed(0xffffffffc0000010), None);
+        assert_eq!(syms.find_enclosed(0xffffffffc0001010), None);
+        assert_eq!(syms.find_enclosed(0x1000), None);
+
+        assert_eq!(syms.addr_of("some_data"), Some(0xffffffff82000000));
+        assert_eq!(syms.addr_of("do_one_initcall"), Some(0xffffffff81000100));
+        assert_eq!(syms.find_by_name("do_one_initcall").count(), 2);
+        assert_eq!(syms.addr_of("hidden"), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! Symbolization of kernel and user space addresses.
+//!
+//! Profilers and tracers commonly capture stack traces in BPF programs,
+//! e.g., by means of `bpf_get_stackid` and a
+//! [`StackTrace`][crate::MapType::StackTrace] map. This module turns the
+//! raw instruction addresses contained in such traces into
+//! human-readable [`Frame`]s:
+//! - kernel addresses are resolved using `/proc/kallsyms`, which is exposed directly for name based
+//!   lookups by means of [`Ksyms`]
+//! - user space addresses are resolved using the symbol tables (`.symtab` and `.dynsym`) of the ELF
+//!   files mapped into the process in question
+//!
+//! Only function symbols are considered and no debug information is
+//! evaluated, i.e., frames do not carry source code locations and
+//! inlined functions are attributed to their caller. Stripped binaries
+//! are symbolized based on their dynamic symbols only, but frames still
+//! report the binary's GNU build ID, if present, allowing for offline
+//! symbolization. Evaluating DWARF debug information is out of scope for
+//! this module; tools requiring source locations can hand the reported
+//! build IDs and addresses to a dedicated symbolizer.
+//!
+//! In the opposite direction, [`find_symbol_offset`] and
+//! [`find_process_symbol_offset`] resolve function symbols to the file
+//! offsets expected for attaching uprobes, e.g., to `SSL_write` in
+//! `libssl`. [`process_modules`] enumerates the executable and shared
+//! libraries a process has loaded, to find out what to attach to.
+//!
+//! ```no_run
+//! # fn example(pid: u32, kstack: &[u64], ustack: &[u64]) -> libbpf_rs::Result<()> {
+//! use libbpf_rs::symbolize::Symbolizer;
+//!
+//! let mut symbolizer = Symbolizer::new();
+//! for frame in symbolizer.symbolize_kernel(kstack)? {
+//!     println!("{frame}");
+//! }
+//! for frame in symbolizer.symbolize_user(pid, ustack)? {
+//!     println!("{frame}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub(crate) mod elf;
+mod inflate;
+mod ksyms;
+mod process;
+mod resolve;
+mod stack;
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::Result;
+
+use self::elf::ElfFile;
+use self::process::Mapping;
+
+pub use self::ksyms::Ksym;
+pub use self::ksyms::Ksyms;
+pub use self::process::find_process_module;
+pub use self::process::process_modules;
+pub use self::process::ProcessModule;
+pub use self::resolve::find_process_symbol_offset;
+pub use self::resolve::find_symbol_offset;
+pub use self::resolve::SymbolOffset;
+pub use self::stack::StackOrigin;
+pub use self::stack::StackTraceMap;
+
+
+/// A symbolized instruction address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Frame {
+    /// The address that was symbolized.
+    pub addr: u64,
+    /// The name of the function containing the address, if it could be
+    /// determined.
+    pub symbol: Option<String>,
+    /// The offset of the address from the start of `symbol`.
+    pub offset: u64,
+    /// The kernel module or ELF file containing the address, if known.
+    ///
+    /// Addresses in the core kernel are reported without a module.
+    pub module: Option<String>,
+    /// The GNU build ID of the ELF file containing the address, if any.
+    pub build_id: Option<Vec<u8>>,
+}
+
+impl Frame {
+    pub(crate) fn unknown(addr: u64) -> Self {
+        Self {
+            addr,
+            symbol: None,
+            offset: 0,
+            module: None,
+            build_id: None,
+        }
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let Self {
+            addr,
+            symbol,
+            offset,
+            module,
+            build_id: _,
+        } = self;
+
+        match symbol {
+            Some(symbol) => write!(f, "{symbol}+{offset:#x}")?,
+            None => write!(f, "{addr:#x}")?,
+        }
+        if let Some(module) = module {
+            let () = write!(f, " [{module}]")?;
+        }
+        Ok(())
+    }
+}
+
+
+/// A symbolizer of kernel and user space addresses.
+///
+/// A `Symbolizer` caches all data it loads, i.e., kernel symbols, the
+/// memory mappings of processes, and parsed ELF files. Use
+/// [`Symbolizer::forget_process`] to evict the data for a process that
+/// exited or changed its mappings. Kernel symbols are refreshed when
+/// an address may belong to a module loaded since they were loaded,
+/// provided the set of loaded modules changed; use
+/// [`Symbolizer::forget_kernel`] to force reloading them.
+#[derive(Debug, Default)]
+pub struct Symbolizer {
+    kernel: Option<Ksyms>,
+    processes: HashMap<u32, Vec<Mapping>>,
+    /// Parsed ELF files; `None` for files that could not be parsed.
+    elfs: HashMap<PathBuf, Option<Arc<ElfFile>>>,
+}
+
+impl Symbolizer {
+    /// Create a new `Symbolizer` with empty caches.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Symbolize kernel addresses.
+    ///
+    /// Symbolizing requires kernel addresses to be visible in
+    /// `/proc/kallsyms`; if they are hidden (see `kernel.kptr_restrict`)
+    /// all frames are reported without symbol information.
+    pub fn symbolize_kernel(&mut self, addrs: &[u64]) -> Result<Vec<Frame>> {
+        let kernel = match &mut self.kernel {
+            Some(kernel) => kernel,
+            None => self.kernel.insert(Ksyms::load()?),
+        };
+        // Only check for changed modules if an address may not be
+        // covered by the symbols we know of, to not read `/proc/modules`
+        // on every call.
+        if addrs
+            .iter()
+            .any(|addr| kernel.find_enclosed(*addr).is_none())
+        {
+            let _refreshed = kernel.refresh()?;
+        }
+
+        let frames = addrs
+            .iter()
+            .map(|addr| match kernel.find(*addr) {
+                Some(sym) => Frame {
+                    addr: *addr,
+                    symbol: Some(sym.name.clone()),
+                    offset: addr - sym.addr,
+                    module: sym.module.clone(),
+                    build_id: None,
+                },
+                None => Frame::unknown(*addr),
+            })
+            .collect();
+        Ok(frames)
+    }
+
+    fn elf(&mut self, pid: u32, path: &Path) -> Option<Arc<ElfFile>> {
+        self.elfs
+            .entry(process::root_path(pid, path))
+            .or_insert_with_key(|path| ElfFile::open(path).ok().map(Arc::new))
+            .clone()
+    }
+
+    fn symbolize_user_addr(&mut self, pid: u32, addr: u64) -> Frame {
+        let Some(mapping) = self
+            .processes
+            .get(&pid)
+            .and_then(|mappings| mappings.iter().find(|m| m.range.contains(&addr)))
+            .cloned()
+        else {
+            return Frame::unknown(addr)
+        };
+
+        let module = Some(mapping.path.to_string_lossy().into_owned());
+        let Some(elf) = self.elf(pid, &mapping.path) else {
+            return Frame {
+                module,
+                ..Frame::unknown(addr)
+            }
+        };
+        let build_id = elf.build_id().map(<[u8]>::to_vec);
+        let sym = elf
+            .offset_to_vaddr(mapping.file_offset(addr))
+            .and_then(|vaddr| Some((vaddr, elf.find_symbol(vaddr)?)));
+
+        match sym {
+            Some((vaddr, sym)) => Frame {
+                addr,
+                symbol: Some(sym.name.clone()),
+                offset: vaddr - sym.addr,
+                module,
+                build_id,
+            },
+            None => Frame {
+                module,
+                build_id,
+                ..Frame::unknown(addr)
+            },
+        }
+    }
+
+    /// Symbolize user space addresses of process `pid`.
+    ///
+    /// The process has to be alive at the time its addresses are
+    /// symbolized for the first time.
+    pub fn symbolize_user(&mut self, pid: u32, addrs: &[u64]) -> Result<Vec<Frame>> {
+        if !self.processes.contains_key(&pid) {
+            let mappings = process::load_mappings(pid)?;
+            let _prev = self.processes.insert(pid, mappings);
+        }
+
+        let frames = addrs
+            .iter()
+            .map(|addr| self.symbolize_user_addr(pid, *addr))
+            .collect();
+        Ok(frames)
+    }
+
+    /// Evict cached data about process `pid`.
+    pub fn forget_process(&mut self, pid: u32) {
+        let _mappings = self.processes.remove(&pid);
+        let prefix = PathBuf::from(format!("/proc/{pid}/root"));
+        let () = self.elfs.retain(|path, _elf| !path.starts_with(&prefix));
+    }
+
+    /// Evict cached kernel symbols, causing them to be reloaded on the
+    /// next use.
+    pub fn forget_kernel(&mut self) {
+        self.kernel = None;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process;
+
+
+    #[inline(never)]
+    fn symbolize_me() -> u64 {
+        symbolize_me as *const () as u64
+    }
+
+    /// Check that we can symbolize an address of our own process.
+    #[test]
+    fn own_symbolization() {
+        let addr = symbolize_me();
+        let mut symbolizer = Symbolizer::new();
+        let frames = symbolizer
+            .symbolize_user(process::id(), &[addr, addr + 1, 0])
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+
+        let symbol = frames[0].symbol.as_deref().unwrap();
+        assert!(symbol.contains("symbolize_me"), "{symbol}");
+        assert_eq!(frames[0].offset, 0);
+        assert_eq!(frames[1].symbol, frames[0].symbol);
+        assert_eq!(frames[1].offset, 1);
+        assert_eq!(frames[2], Frame::unknown(0));
+    }
+
+    /// Check that frames are displayed as expected.
+    #[test]
+    fn frame_display() {
+        let frame = Frame {
+            addr: 0xffffffffc0000010,
+            symbol: Some("nf_hook".to_string()),
+            offset: 0x10,
+            module: Some("nf_tables".to_string()),
+            build_id: None,
+        };
+        assert_eq!(frame.to_string(), "nf_hook+0x10 [nf_tables]");
+        assert_eq!(Frame::unknown(0x1234).to_string(), "0x1234");
+    }
+}
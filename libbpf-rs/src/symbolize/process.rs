@@ -0,0 +1,262 @@
+//! Parsing of process memory maps as reported by `/proc/<pid>/maps`.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// A file backed, executable memory mapping of a process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Mapping {
+    /// The range of virtual addresses covered by the mapping.
+    pub range: Range<u64>,
+    /// The offset into the file at which the mapping starts.
+    pub offset: u64,
+    /// The path of the mapped file, as seen by the process.
+    pub path: PathBuf,
+}
+
+impl Mapping {
+    /// Translate a virtual address inside this mapping into an offset
+    /// into the mapped file.
+    pub fn file_offset(&self, addr: u64) -> u64 {
+        addr - self.range.start + self.offset
+    }
+}
+
+
+/// Load the executable, file backed memory mappings of process `pid`.
+pub(crate) fn load_mappings(pid: u32) -> Result<Vec<Mapping>> {
+    let path = format!("/proc/{pid}/maps");
+    let maps = fs::read(&path).with_context(|| format!("failed to read `{path}`"))?;
+    parse_mappings(&maps[..])
+}
+
+/// Parse memory mappings in the `/proc/<pid>/maps` format, retaining
+/// only executable, file backed ones.
+pub(crate) fn parse_mappings<R: BufRead>(reader: R) -> Result<Vec<Mapping>> {
+    let mut mappings = Vec::new();
+    for line in reader.lines() {
+        let line = line.context("failed to read memory mapping")?;
+        // <start>-<end> <perms> <offset> <dev> <inode> <path>
+        let mut fields = line.splitn(6, ' ');
+        let (Some(range), Some(perms), Some(offset), Some(_dev), Some(_inode), Some(path)) = (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) else {
+            continue
+        };
+
+        let path = path.trim_start();
+        if !perms.contains('x') || !path.starts_with('/') {
+            continue
+        }
+        let Some((start, end)) = range.split_once('-') else {
+            continue
+        };
+        let (Ok(start), Ok(end), Ok(offset)) = (
+            u64::from_str_radix(start, 16),
+            u64::from_str_radix(end, 16),
+            u64::from_str_radix(offset, 16),
+        ) else {
+            continue
+        };
+
+        let () = mappings.push(Mapping {
+            range: start..end,
+            offset,
+            // Files that got replaced since being mapped are reported
+            // with a suffix; we will fail to open them later on.
+            path: PathBuf::from(path),
+        });
+    }
+    Ok(mappings)
+}
+
+/// Translate a path as seen by process `pid` into one accessible from
+/// the current process, even if the process lives in a different mount
+/// namespace.
+pub(crate) fn root_path(pid: u32, path: &Path) -> PathBuf {
+    let mut root = PathBuf::from(format!("/proc/{pid}/root"));
+    let () = root.push(path.strip_prefix("/").unwrap_or(path));
+    root
+}
+
+
+/// An ELF file, i.e., the executable or a shared library, loaded into a
+/// process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProcessModule {
+    /// The module's path, as seen by the process.
+    pub path: PathBuf,
+    /// The module's path, accessible from the current process' mount
+    /// namespace, e.g., for attaching uprobes.
+    pub root_path: PathBuf,
+    /// The range of virtual addresses covered by the module's
+    /// executable mappings.
+    pub range: Range<u64>,
+    /// Whether the module is the process' executable.
+    pub is_exe: bool,
+}
+
+impl ProcessModule {
+    /// Check whether the module matches `module`.
+    ///
+    /// `module` is either an absolute path, as seen by the process, or a
+    /// file name, with `lib` prefix and `.so` suffix (plus version) being
+    /// optional, i.e., `libssl.so.3`, `libssl`, and `ssl` all match
+    /// `/usr/lib/libssl.so.3`.
+    pub fn matches(&self, module: &str) -> bool {
+        if module.starts_with('/') {
+            return self.path == Path::new(module)
+        }
+
+        let Some(file_name) = self.path.file_name().map(OsStr::as_bytes) else {
+            return false
+        };
+        let module = module.as_bytes();
+        file_name == module
+            || file_name.starts_with(&[module, b".so"].concat())
+            || file_name.starts_with(&[b"lib", module, b".so"].concat())
+    }
+}
+
+/// Enumerate the modules, i.e., the executable and shared libraries,
+/// loaded into process `pid`.
+///
+/// Modules are reported in the order of their lowest address.
+pub fn process_modules(pid: u32) -> Result<Vec<ProcessModule>> {
+    let mappings = load_mappings(pid)?;
+    // The executable is identified by its inode, because the path
+    // reported for `/proc/<pid>/exe` depends on the reader's mount
+    // namespace.
+    let exe = fs::metadata(format!("/proc/{pid}/exe"))
+        .ok()
+        .map(|meta| (meta.dev(), meta.ino()));
+
+    let mut modules = Vec::<ProcessModule>::new();
+    for mapping in mappings {
+        if let Some(module) = modules
+            .iter_mut()
+            .find(|module| module.path == mapping.path)
+        {
+            module.range.start = module.range.start.min(mapping.range.start);
+            module.range.end = module.range.end.max(mapping.range.end);
+            continue
+        }
+
+        let root_path = root_path(pid, &mapping.path);
+        let is_exe = exe.is_some()
+            && fs::metadata(&root_path)
+                .map(|meta| Some((meta.dev(), meta.ino())) == exe)
+                .unwrap_or(false);
+        let () = modules.push(ProcessModule {
+            path: mapping.path,
+            root_path,
+            range: mapping.range,
+            is_exe,
+        });
+    }
+    let () = modules.sort_by_key(|module| module.range.start);
+    Ok(modules)
+}
+
+/// Find the module `module` loaded into process `pid`.
+///
+/// See [`ProcessModule::matches`] for how `module` is interpreted. If
+/// multiple modules match, the first one is reported.
+pub fn find_process_module(pid: u32, module: &str) -> Result<ProcessModule> {
+    process_modules(pid)?
+        .into_iter()
+        .find(|loaded| loaded.matches(module))
+        .ok_or_else(|| {
+            Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!("module `{module}` is not loaded into process {pid}"),
+            )
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process;
+
+
+    /// Check that we can parse memory mappings.
+    #[test]
+    fn mapping_parsing() {
+        let maps = b"\
+55d0c2a00000-55d0c2a28000 r--p 00000000 fd:01 1234 /usr/bin/cat
+55d0c2a28000-55d0c2a3c000 r-xp 00028000 fd:01 1234 /usr/bin/cat
+7f2d4c000000-7f2d4c021000 rw-p 00000000 00:00 0
+7ffd1a5f0000-7ffd1a5f2000 r-xp 00000000 00:00 0                          [vdso]
+7f2d4c600000-7f2d4c795000 r-xp 00028000 fd:01 5678                       /usr/lib/libc.so.6
+";
+        let mappings = parse_mappings(&maps[..]).unwrap();
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[0].path, PathBuf::from("/usr/bin/cat"));
+        assert_eq!(mappings[0].file_offset(0x55d0c2a28010), 0x28010);
+        assert_eq!(mappings[1].path, PathBuf::from("/usr/lib/libc.so.6"));
+        assert_eq!(mappings[1].range.start, 0x7f2d4c600000);
+    }
+
+    /// Check that our own memory mappings include the test executable.
+    #[test]
+    fn own_mappings() {
+        let mappings = load_mappings(process::id()).unwrap();
+        let exe = fs::read_link("/proc/self/exe").unwrap();
+        assert!(mappings.iter().any(|mapping| mapping.path == exe));
+    }
+
+    /// Check that module names are matched as expected.
+    #[test]
+    fn module_matching() {
+        let module = |path: &str| ProcessModule {
+            path: PathBuf::from(path),
+            root_path: PathBuf::from(path),
+            range: 0..0,
+            is_exe: false,
+        };
+        let libssl = module("/usr/lib/libssl.so.3");
+        assert!(libssl.matches("/usr/lib/libssl.so.3"));
+        assert!(libssl.matches("libssl.so.3"));
+        assert!(libssl.matches("libssl"));
+        assert!(libssl.matches("ssl"));
+        assert!(!libssl.matches("libcrypto"));
+        assert!(!libssl.matches("/lib/libssl.so.3"));
+        assert!(!module("/usr/bin/cat").matches("ca"));
+    }
+
+    /// Check that we can enumerate our own modules and identify the
+    /// executable among them.
+    #[test]
+    fn own_modules() {
+        let pid = process::id();
+        let modules = process_modules(pid).unwrap();
+        let exe = modules.iter().find(|module| module.is_exe).unwrap();
+        assert_eq!(exe.path, fs::read_link("/proc/self/exe").unwrap());
+        assert_eq!(modules.iter().filter(|module| module.is_exe).count(), 1);
+
+        let name = exe.path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(&find_process_module(pid, name).unwrap(), exe);
+        assert!(find_process_module(pid, "libbpf_rs_does_not_exist").is_err());
+    }
+}
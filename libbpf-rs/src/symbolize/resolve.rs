@@ -0,0 +1,127 @@
+//! Resolution of function symbols to file offsets and addresses.
+
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::elf::ElfFile;
+use super::process;
+use crate::Error;
+use crate::Result;
+
+
+/// The location of a function symbol inside an ELF file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SymbolOffset {
+    /// The path of the ELF file containing the symbol.
+    ///
+    /// For symbols resolved in the context of a process, this path is
+    /// accessible from the current process' mount namespace, i.e., it
+    /// may be used for attaching uprobes.
+    pub path: PathBuf,
+    /// The symbol's virtual address, as recorded in the ELF file.
+    pub vaddr: u64,
+    /// The symbol's offset into the ELF file, as expected by
+    /// [`ProgramMut::attach_uprobe`][crate::ProgramMut::attach_uprobe].
+    pub offset: u64,
+    /// The symbol's size, which may be zero if unknown.
+    pub size: u64,
+    /// The symbol's address in the process it was resolved for, taking
+    /// into account the load bias of position independent code.
+    ///
+    /// `None` for symbols not resolved in the context of a process.
+    pub addr: Option<u64>,
+}
+
+/// Find the function symbol `name` in the ELF file at `path`.
+///
+/// Symbols are looked up in both the `.symtab` and the `.dynsym`
+/// section, either of which may be compressed.
+pub fn find_symbol_offset<P>(path: P, name: &str) -> Result<SymbolOffset>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let elf = ElfFile::open(path)?;
+    let sym = elf.find_symbol_by_name(name).ok_or_else(|| {
+        Error::with_io_error(
+            io::ErrorKind::NotFound,
+            format!("symbol `{name}` not found in `{}`", path.display()),
+        )
+    })?;
+    let offset = elf.vaddr_to_offset(sym.addr).ok_or_else(|| {
+        Error::with_invalid_data(format!(
+            "symbol `{name}` in `{}` is not part of a loadable segment",
+            path.display()
+        ))
+    })?;
+
+    let offset = SymbolOffset {
+        path: path.to_path_buf(),
+        vaddr: sym.addr,
+        offset,
+        size: sym.size,
+        addr: None,
+    };
+    Ok(offset)
+}
+
+/// Find the function symbol `name` in `module` as mapped into the
+/// process `pid`.
+///
+/// `module` is either the absolute path of an ELF file mapped into the
+/// process (as seen by it) or a library name, such as `libssl.so.3`,
+/// `libssl`, or `ssl`; see
+/// [`ProcessModule::matches`][super::ProcessModule::matches]. The
+/// reported [`SymbolOffset::addr`] reflects where the symbol got loaded
+/// in the process.
+pub fn find_process_symbol_offset(pid: u32, module: &str, name: &str) -> Result<SymbolOffset> {
+    let module = process::find_process_module(pid, module)?;
+    let mut offset = find_symbol_offset(&module.root_path, name)?;
+
+    let mappings = process::load_mappings(pid)?;
+    offset.addr = mappings
+        .iter()
+        .filter(|mapping| mapping.path == module.path)
+        .find(|mapping| {
+            let len = mapping.range.end - mapping.range.start;
+            (mapping.offset..mapping.offset + len).contains(&offset.offset)
+        })
+        .map(|mapping| mapping.range.start + offset.offset - mapping.offset);
+    Ok(offset)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::process;
+
+
+    #[inline(never)]
+    #[no_mangle]
+    extern "C" fn libbpf_rs_resolve_me() -> u64 {
+        libbpf_rs_resolve_me as *const () as u64
+    }
+
+    /// Check that we can resolve a symbol of our own process to its
+    /// run time address.
+    #[test]
+    fn own_symbol_resolution() {
+        let exe = fs::read_link("/proc/self/exe").unwrap();
+        let name = "libbpf_rs_resolve_me";
+        let offset = find_symbol_offset(&exe, name).unwrap();
+        assert_eq!(offset.addr, None);
+
+        let module = exe.to_str().unwrap();
+        let process_offset = find_process_symbol_offset(process::id(), module, name).unwrap();
+        assert_eq!(process_offset.offset, offset.offset);
+        assert_eq!(process_offset.addr, Some(libbpf_rs_resolve_me()));
+
+        let err = find_symbol_offset(&exe, "libbpf_rs_does_not_exist").unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::io;
+use std::mem::size_of;
+use std::os::fd::AsFd as _;
+
+use super::Frame;
+use super::Symbolizer;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapCore;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapInfo;
+use crate::MapType;
+use crate::Result;
+
+
+/// The size of a build ID as reported in build ID based stack traces.
+const BUILD_ID_SIZE: usize = 20;
+
+/// Mirror of the kernel's `struct bpf_stack_build_id`.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+struct StackBuildId {
+    status: i32,
+    build_id: [u8; BUILD_ID_SIZE],
+    /// The file offset (`BPF_STACK_BUILD_ID_VALID`) or instruction
+    /// pointer (`BPF_STACK_BUILD_ID_IP`).
+    offset_or_ip: u64,
+}
+
+const BPF_STACK_BUILD_ID_VALID: i32 = 1;
+const BPF_STACK_BUILD_ID_IP: i32 = 2;
+
+
+/// Whose addresses a stack trace contains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StackOrigin {
+    /// The stack trace contains kernel addresses.
+    Kernel,
+    /// The stack trace contains user space addresses of the process with
+    /// the given PID.
+    User(u32),
+}
+
+
+/// A typed wrapper around a [`StackTrace`][MapType::StackTrace] map.
+///
+/// Stack traces are looked up by the ID returned by `bpf_get_stackid` on
+/// the BPF side and reported as symbolized [`Frame`]s. Because the
+/// kernel already deduplicates identical stacks, assigning them the same
+/// ID, symbolized stacks are cached by ID and origin, so that each
+/// distinct stack is only symbolized once.
+///
+/// Maps created with `BPF_F_STACK_BUILD_ID` are supported as well. Their
+/// entries that the kernel managed to resolve are reported with the
+/// build ID of the respective ELF file and with [`Frame::addr`]
+/// containing the offset into said file, leaving symbolization to a
+/// build ID aware (and typically offline) component.
+#[derive(Debug)]
+pub struct StackTraceMap {
+    map: MapHandle,
+    build_id: bool,
+    cache: HashMap<(u32, StackOrigin), Vec<Frame>>,
+}
+
+impl StackTraceMap {
+    /// Create a new `StackTraceMap` for the provided map.
+    pub fn new<M>(map: &M) -> Result<Self>
+    where
+        M: MapCore,
+    {
+        if map.map_type() != MapType::StackTrace {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "map `{}` is not a stack trace map",
+                    map.name().to_string_lossy()
+                ),
+            ))
+        }
+
+        let info = MapInfo::new(map.as_fd())?;
+        let build_id = info.info.map_flags & libbpf_sys::BPF_F_STACK_BUILD_ID != 0;
+        let fd = map
+            .as_fd()
+            .try_clone_to_owned()
+            .context("failed to duplicate map file descriptor")?;
+
+        let slf = Self {
+            map: MapHandle::from_fd(fd)?,
+            build_id,
+            cache: HashMap::new(),
+        };
+        Ok(slf)
+    }
+
+    /// Check whether the map stores build ID based stack traces.
+    pub fn is_build_id(&self) -> bool {
+        self.build_id
+    }
+
+    fn lookup(&self, id: u32) -> Result<Option<Vec<u8>>> {
+        self.map
+            .lookup(&id.to_ne_bytes(), MapFlags::ANY)
+            .with_context(|| format!("failed to look up stack trace {id}"))
+    }
+
+    /// Retrieve the raw instruction addresses of the stack trace with
+    /// the given ID, innermost frame first.
+    ///
+    /// For build ID based maps, entries not resolved to a build ID are
+    /// reported by their instruction address, resolved ones by their
+    /// file offset.
+    pub fn addrs(&self, id: u32) -> Result<Option<Vec<u64>>> {
+        let addrs = if self.build_id {
+            self.build_id_frames(id)?
+                .map(|frames| frames.into_iter().map(|frame| frame.addr).collect())
+        } else {
+            self.lookup(id)?.map(|value| {
+                value
+                    .chunks_exact(size_of::<u64>())
+                    .map(|chunk| u64::from_ne_bytes(chunk.try_into().unwrap()))
+                    .take_while(|addr| *addr != 0)
+                    .collect()
+            })
+        };
+        Ok(addrs)
+    }
+
+    /// Retrieve the stack trace with the given ID from a build ID based
+    /// map, as unsymbolized frames.
+    fn build_id_frames(&self, id: u32) -> Result<Option<Vec<Frame>>> {
+        let Some(value) = self.lookup(id)? else {
+            return Ok(None)
+        };
+
+        let frames = value
+            .chunks_exact(size_of::<StackBuildId>())
+            .map(|chunk| {
+                // SAFETY: The chunk has the size of a `StackBuildId`, which
+                //         is valid for any bit pattern.
+                unsafe { chunk.as_ptr().cast::<StackBuildId>().read_unaligned() }
+            })
+            .map_while(|entry| match entry.status {
+                BPF_STACK_BUILD_ID_VALID => Some(Frame {
+                    build_id: Some(entry.build_id.to_vec()),
+                    ..Frame::unknown(entry.offset_or_ip)
+                }),
+                BPF_STACK_BUILD_ID_IP => Some(Frame::unknown(entry.offset_or_ip)),
+                // `BPF_STACK_BUILD_ID_EMPTY` terminates the stack.
+                _ => None,
+            })
+            .collect();
+        Ok(Some(frames))
+    }
+
+    /// Retrieve the stack trace with the given ID as unsymbolized frames,
+    /// innermost frame first.
+    ///
+    /// Frames of build ID based maps carry the build ID, if the kernel
+    /// could resolve it.
+    pub(crate) fn raw_frames(&self, id: u32) -> Result<Option<Vec<Frame>>> {
+        if self.build_id {
+            self.build_id_frames(id)
+        } else {
+            let frames = self
+                .addrs(id)?
+                .map(|addrs| addrs.into_iter().map(Frame::unknown).collect());
+            Ok(frames)
+        }
+    }
+
+    /// Retrieve the stack trace with the given ID as symbolized frames,
+    /// innermost frame first.
+    ///
+    /// `None` is returned if no stack trace with the given ID exists.
+    pub fn frames(
+        &mut self,
+        id: u32,
+        origin: StackOrigin,
+        symbolizer: &mut Symbolizer,
+    ) -> Result<Option<Vec<Frame>>> {
+        if let Some(frames) = self.cache.get(&(id, origin)) {
+            return Ok(Some(frames.clone()))
+        }
+
+        let frames = if self.build_id {
+            let Some(mut frames) = self.build_id_frames(id)? else {
+                return Ok(None)
+            };
+            // Entries the kernel could not resolve to a build ID carry
+            // an instruction address that we can symbolize ourselves.
+            if let StackOrigin::User(pid) = origin {
+                for frame in frames.iter_mut().filter(|frame| frame.build_id.is_none()) {
+                    let symbolized = symbolizer.symbolize_user(pid, &[frame.addr])?;
+                    if let Some(symbolized) = symbolized.into_iter().next() {
+                        *frame = symbolized;
+                    }
+                }
+            }
+            frames
+        } else {
+            let Some(addrs) = self.addrs(id)? else {
+                return Ok(None)
+            };
+            match origin {
+                StackOrigin::Kernel => symbolizer.symbolize_kernel(&addrs)?,
+                StackOrigin::User(pid) => symbolizer.symbolize_user(pid, &addrs)?,
+            }
+        };
+
+        let _prev = self.cache.insert((id, origin), frames.clone());
+        Ok(Some(frames))
+    }
+
+    /// Remove the stack trace with the given ID from the map, allowing
+    /// the kernel to reuse its slot.
+    ///
+    /// Cached symbolization results for the ID are discarded as well.
+    pub fn remove(&mut self, id: u32) -> Result<()> {
+        let () = self.cache.retain(|(cached, _), _| *cached != id);
+        self.map
+            .delete(&id.to_ne_bytes())
+            .with_context(|| format!("failed to delete stack trace {id}"))
+    }
+
+    /// Discard all cached symbolization results.
+    pub fn clear_cache(&mut self) {
+        let () = self.cache.clear();
+    }
+}
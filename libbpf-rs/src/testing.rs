@@ -0,0 +1,1475 @@
+//! Helpers for testing packet processing programs.
+//!
+//! `XDP` and `tc` programs can be exercised with
+//! [`ProgramMut::test_run`][crate::ProgramMut::test_run] without setting
+//! up interfaces or network namespaces. This module provides the pieces
+//! to make doing so convenient: a [`PacketBuilder`] assembling Ethernet
+//! frames carrying IPv4 or IPv6 and TCP or UDP, with correct lengths and
+//! checksums, [`run_packet`] to run a program on such a frame, and
+//! assertions over the [`PacketRun`] result. A [`MapFixture`]
+//! pre-populates maps and checks their state after a program ran,
+//! [`requires`] skips tests the environment is unable to run, and [`Vm`]
+//! runs tests against kernels other than the host's.
+//!
+//! The module is only available with the `testing` feature enabled,
+//! which is meant to be requested for `libbpf-rs` as dev-dependency.
+//!
+//! ```no_run
+//! # fn example(prog: &libbpf_rs::ProgramMut<'_>) -> libbpf_rs::Result<()> {
+//! use std::net::Ipv4Addr;
+//!
+//! use libbpf_rs::libbpf_sys;
+//! use libbpf_rs::testing::run_packet;
+//! use libbpf_rs::testing::PacketBuilder;
+//!
+//! let packet = PacketBuilder::new()
+//!     .ipv4(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2))
+//!     .udp(1234, 53)
+//!     .payload(b"query")
+//!     .build();
+//! let run = run_packet(prog, &packet)?;
+//! run.assert_xdp_action(libbpf_sys::XDP_PASS)
+//!     .assert_packet(&packet);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::CStr;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::io;
+use std::mem;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::ops::BitOr;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::ffi::OsStringExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+
+use bitflags::bitflags;
+
+use crate::error::IntoError as _;
+use crate::privilege;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapCore;
+use crate::MapFlags;
+use crate::ProgramInput;
+use crate::ProgramMut;
+use crate::Result;
+
+pub use crate::privilege::Capability;
+
+
+/// The size of an Ethernet header, without VLAN tags.
+const ETH_HLEN: usize = 14;
+/// The size of an IPv4 header without options.
+const IPV4_HLEN: usize = 20;
+/// The size of an IPv6 header, without extension headers.
+const IPV6_HLEN: usize = 40;
+/// The size of a TCP header without options.
+const TCP_HLEN: usize = 20;
+/// The size of a UDP header.
+const UDP_HLEN: usize = 8;
+
+/// The Ethernet type of IPv4.
+pub const ETH_P_IP: u16 = 0x0800;
+/// The Ethernet type of IPv6.
+pub const ETH_P_IPV6: u16 = 0x86dd;
+/// The IP protocol number of TCP.
+pub const IPPROTO_TCP: u8 = 6;
+/// The IP protocol number of UDP.
+pub const IPPROTO_UDP: u8 = 17;
+
+/// The number of bytes a program may grow a packet by before
+/// [`run_packet`] fails to capture the output.
+const OUTPUT_SLACK: usize = 4096;
+
+
+bitflags! {
+    /// The flags of a TCP header.
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+    pub struct TcpFlags: u8 {
+        /// No more data from the sender.
+        const FIN = 1 << 0;
+        /// Synchronize sequence numbers.
+        const SYN = 1 << 1;
+        /// Reset the connection.
+        const RST = 1 << 2;
+        /// Push buffered data to the application.
+        const PSH = 1 << 3;
+        /// The acknowledgment field is significant.
+        const ACK = 1 << 4;
+        /// The urgent pointer field is significant.
+        const URG = 1 << 5;
+        /// ECN echo.
+        const ECE = 1 << 6;
+        /// Congestion window reduced.
+        const CWR = 1 << 7;
+    }
+}
+
+
+/// Compute the Internet checksum (RFC 1071) over `data`, starting out
+/// with the partial sum `sum`.
+fn checksum(data: &[u8], mut sum: u32) -> u16 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u32::from(u16::from_be_bytes([chunk[0], chunk[1]]));
+    }
+    if let [last] = chunks.remainder() {
+        sum += u32::from(u16::from_be_bytes([*last, 0]));
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Compute the partial checksum of the pseudo header covering a
+/// transport header of `len` bytes.
+fn pseudo_header_sum(src: &IpAddr, dst: &IpAddr, protocol: u8, len: usize) -> u32 {
+    let mut header = Vec::with_capacity(40);
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            header.extend_from_slice(&src.octets());
+            header.extend_from_slice(&dst.octets());
+            header.extend_from_slice(&[0, protocol]);
+            header.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        (src, dst) => {
+            let octets = |addr: &IpAddr| match addr {
+                IpAddr::V4(addr) => addr.to_ipv6_mapped().octets(),
+                IpAddr::V6(addr) => addr.octets(),
+            };
+            header.extend_from_slice(&octets(src));
+            header.extend_from_slice(&octets(dst));
+            header.extend_from_slice(&(len as u32).to_be_bytes());
+            header.extend_from_slice(&[0, 0, 0, protocol]);
+        }
+    }
+    // The complement of the checksum of the header is its folded sum.
+    u32::from(!checksum(&header, 0))
+}
+
+
+#[derive(Clone, Debug)]
+enum Transport {
+    Tcp {
+        src: u16,
+        dst: u16,
+        seq: u32,
+        ack: u32,
+        flags: TcpFlags,
+    },
+    Udp {
+        src: u16,
+        dst: u16,
+    },
+}
+
+
+/// A builder for Ethernet frames.
+///
+/// Lengths, protocol fields, and checksums are filled in based on the
+/// configured layers when the frame is built.
+#[derive(Clone, Debug)]
+pub struct PacketBuilder {
+    eth_src: [u8; 6],
+    eth_dst: [u8; 6],
+    ether_type: Option<u16>,
+    addrs: Option<(IpAddr, IpAddr)>,
+    ttl: u8,
+    transport: Option<Transport>,
+    payload: Vec<u8>,
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self {
+            eth_src: [0x02, 0, 0, 0, 0, 0x01],
+            eth_dst: [0x02, 0, 0, 0, 0, 0x02],
+            ether_type: None,
+            addrs: None,
+            ttl: 64,
+            transport: None,
+            payload: Vec::new(),
+        }
+    }
+}
+
+impl PacketBuilder {
+    /// Create a new builder for an Ethernet frame between two locally
+    /// administered addresses, without any further layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the source MAC address.
+    pub fn eth_src(mut self, addr: [u8; 6]) -> Self {
+        self.eth_src = addr;
+        self
+    }
+
+    /// Set the destination MAC address.
+    pub fn eth_dst(mut self, addr: [u8; 6]) -> Self {
+        self.eth_dst = addr;
+        self
+    }
+
+    /// Set the Ethernet type explicitly, instead of deriving it from the
+    /// network layer.
+    pub fn ether_type(mut self, ether_type: u16) -> Self {
+        self.ether_type = Some(ether_type);
+        self
+    }
+
+    /// Add an IPv4 header.
+    pub fn ipv4(mut self, src: Ipv4Addr, dst: Ipv4Addr) -> Self {
+        self.addrs = Some((IpAddr::V4(src), IpAddr::V4(dst)));
+        self
+    }
+
+    /// Add an IPv6 header.
+    pub fn ipv6(mut self, src: Ipv6Addr, dst: Ipv6Addr) -> Self {
+        self.addrs = Some((IpAddr::V6(src), IpAddr::V6(dst)));
+        self
+    }
+
+    /// Set the IPv4 TTL or IPv6 hop limit, which defaults to 64.
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Add a TCP header, with only the `ACK` flag set.
+    pub fn tcp(mut self, src_port: u16, dst_port: u16) -> Self {
+        self.transport = Some(Transport::Tcp {
+            src: src_port,
+            dst: dst_port,
+            seq: 0,
+            ack: 0,
+            flags: TcpFlags::ACK,
+        });
+        self
+    }
+
+    /// Set the flags of the TCP header.
+    ///
+    /// This has no effect if no TCP header was added.
+    pub fn tcp_flags(mut self, tcp_flags: TcpFlags) -> Self {
+        if let Some(Transport::Tcp { flags, .. }) = &mut self.transport {
+            *flags = tcp_flags;
+        }
+        self
+    }
+
+    /// Set the sequence and acknowledgment numbers of the TCP header.
+    ///
+    /// This has no effect if no TCP header was added.
+    pub fn tcp_seq(mut self, seq_num: u32, ack_num: u32) -> Self {
+        if let Some(Transport::Tcp { seq, ack, .. }) = &mut self.transport {
+            *seq = seq_num;
+            *ack = ack_num;
+        }
+        self
+    }
+
+    /// Add a UDP header.
+    pub fn udp(mut self, src_port: u16, dst_port: u16) -> Self {
+        self.transport = Some(Transport::Udp {
+            src: src_port,
+            dst: dst_port,
+        });
+        self
+    }
+
+    /// Set the payload following the innermost header.
+    pub fn payload(mut self, payload: &[u8]) -> Self {
+        self.payload = payload.to_vec();
+        self
+    }
+
+    fn build_transport(&self, transport: &Transport) -> Vec<u8> {
+        let mut segment = Vec::new();
+        let (protocol, csum_offset) = match transport {
+            Transport::Tcp {
+                src,
+                dst,
+                seq,
+                ack,
+                flags,
+            } => {
+                segment.extend_from_slice(&src.to_be_bytes());
+                segment.extend_from_slice(&dst.to_be_bytes());
+                segment.extend_from_slice(&seq.to_be_bytes());
+                segment.extend_from_slice(&ack.to_be_bytes());
+                segment.extend_from_slice(&[((TCP_HLEN / 4) << 4) as u8, flags.bits()]);
+                // Window, checksum, and urgent pointer.
+                segment.extend_from_slice(&[0xff, 0xff, 0, 0, 0, 0]);
+                (IPPROTO_TCP, 16)
+            }
+            Transport::Udp { src, dst } => {
+                let len = (UDP_HLEN + self.payload.len()) as u16;
+                segment.extend_from_slice(&src.to_be_bytes());
+                segment.extend_from_slice(&dst.to_be_bytes());
+                segment.extend_from_slice(&len.to_be_bytes());
+                segment.extend_from_slice(&[0, 0]);
+                (IPPROTO_UDP, 6)
+            }
+        };
+        segment.extend_from_slice(&self.payload);
+
+        if let Some((src, dst)) = &self.addrs {
+            let sum = pseudo_header_sum(src, dst, protocol, segment.len());
+            let mut csum = checksum(&segment, sum);
+            // A UDP checksum of zero denotes the absence of a checksum.
+            if protocol == IPPROTO_UDP && csum == 0 {
+                csum = 0xffff;
+            }
+            segment[csum_offset..csum_offset + 2].copy_from_slice(&csum.to_be_bytes());
+        }
+        segment
+    }
+
+    /// Build the frame.
+    pub fn build(&self) -> Vec<u8> {
+        let (protocol, segment) = match &self.transport {
+            Some(transport @ Transport::Tcp { .. }) => {
+                (IPPROTO_TCP, self.build_transport(transport))
+            }
+            Some(transport @ Transport::Udp { .. }) => {
+                (IPPROTO_UDP, self.build_transport(transport))
+            }
+            None => (0, self.payload.clone()),
+        };
+
+        let mut frame = Vec::with_capacity(ETH_HLEN + IPV6_HLEN + segment.len());
+        frame.extend_from_slice(&self.eth_dst);
+        frame.extend_from_slice(&self.eth_src);
+        let ether_type = self.ether_type.unwrap_or(match self.addrs {
+            Some((IpAddr::V4(_), _)) => ETH_P_IP,
+            Some((IpAddr::V6(_), _)) => ETH_P_IPV6,
+            None => 0,
+        });
+        frame.extend_from_slice(&ether_type.to_be_bytes());
+
+        match self.addrs {
+            Some((IpAddr::V4(src), IpAddr::V4(dst))) => {
+                let len = (IPV4_HLEN + segment.len()) as u16;
+                let mut header = Vec::with_capacity(IPV4_HLEN);
+                header.extend_from_slice(&[0x45, 0]);
+                header.extend_from_slice(&len.to_be_bytes());
+                // Identification and flags, with "don't fragment" set.
+                header.extend_from_slice(&[0, 0, 0x40, 0]);
+                header.extend_from_slice(&[self.ttl, protocol, 0, 0]);
+                header.extend_from_slice(&src.octets());
+                header.extend_from_slice(&dst.octets());
+                let csum = checksum(&header, 0);
+                header[10..12].copy_from_slice(&csum.to_be_bytes());
+                frame.extend_from_slice(&header);
+            }
+            Some((IpAddr::V6(src), IpAddr::V6(dst))) => {
+                frame.extend_from_slice(&[0x60, 0, 0, 0]);
+                frame.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+                frame.extend_from_slice(&[protocol, self.ttl]);
+                frame.extend_from_slice(&src.octets());
+                frame.extend_from_slice(&dst.octets());
+            }
+            // Both addresses are always of the same family.
+            Some(_) | None => (),
+        }
+
+        frame.extend_from_slice(&segment);
+        frame
+    }
+}
+
+
+/// A parsed view of an Ethernet frame as produced by [`PacketBuilder`],
+/// for asserting on the fields of packets modified by a program.
+///
+/// Network and transport layer fields are only populated for IPv4 (without
+/// options) and IPv6 (without extension headers) and TCP and UDP,
+/// respectively.
+#[derive(Clone, Debug)]
+pub struct ParsedPacket<'data> {
+    /// The destination MAC address.
+    pub eth_dst: [u8; 6],
+    /// The source MAC address.
+    pub eth_src: [u8; 6],
+    /// The Ethernet type.
+    pub ether_type: u16,
+    /// The source IP address.
+    pub src_addr: Option<IpAddr>,
+    /// The destination IP address.
+    pub dst_addr: Option<IpAddr>,
+    /// The IPv4 TTL or IPv6 hop limit.
+    pub ttl: Option<u8>,
+    /// The transport protocol number.
+    pub protocol: Option<u8>,
+    /// The transport layer source port.
+    pub src_port: Option<u16>,
+    /// The transport layer destination port.
+    pub dst_port: Option<u16>,
+    /// The TCP flags.
+    pub tcp_flags: Option<TcpFlags>,
+    /// The payload following the innermost parsed header.
+    pub payload: &'data [u8],
+    /// The raw frame.
+    data: &'data [u8],
+    /// The offset of the transport header, if any.
+    l4_offset: Option<usize>,
+}
+
+impl<'data> ParsedPacket<'data> {
+    /// Parse the given Ethernet frame.
+    pub fn parse(data: &'data [u8]) -> Result<Self> {
+        fn header<'data>(data: &'data [u8], len: usize, layer: &str) -> Result<&'data [u8]> {
+            data.get(..len)
+                .ok_or_else(|| Error::with_invalid_data(format!("truncated {layer} header")))
+        }
+
+        let eth = header(data, ETH_HLEN, "Ethernet")?;
+        let mut packet = Self {
+            eth_dst: eth[0..6].try_into().unwrap(),
+            eth_src: eth[6..12].try_into().unwrap(),
+            ether_type: u16::from_be_bytes([eth[12], eth[13]]),
+            src_addr: None,
+            dst_addr: None,
+            ttl: None,
+            protocol: None,
+            src_port: None,
+            dst_port: None,
+            tcp_flags: None,
+            payload: &data[ETH_HLEN..],
+            data,
+            l4_offset: None,
+        };
+
+        let l4_offset = match packet.ether_type {
+            ETH_P_IP => {
+                let ip = header(packet.payload, IPV4_HLEN, "IPv4")?;
+                if ip[0] != 0x45 {
+                    return Err(Error::with_invalid_data(
+                        "IPv4 headers with options are not supported",
+                    ))
+                }
+                let octets = |offset: usize| <[u8; 4]>::try_from(&ip[offset..offset + 4]).unwrap();
+                packet.src_addr = Some(IpAddr::from(octets(12)));
+                packet.dst_addr = Some(IpAddr::from(octets(16)));
+                packet.ttl = Some(ip[8]);
+                packet.protocol = Some(ip[9]);
+                ETH_HLEN + IPV4_HLEN
+            }
+            ETH_P_IPV6 => {
+                let ip = header(packet.payload, IPV6_HLEN, "IPv6")?;
+                let octets =
+                    |offset: usize| <[u8; 16]>::try_from(&ip[offset..offset + 16]).unwrap();
+                packet.src_addr = Some(IpAddr::from(octets(8)));
+                packet.dst_addr = Some(IpAddr::from(octets(24)));
+                packet.ttl = Some(ip[7]);
+                packet.protocol = Some(ip[6]);
+                ETH_HLEN + IPV6_HLEN
+            }
+            _ => return Ok(packet),
+        };
+        packet.payload = &data[l4_offset..];
+
+        let hlen = match packet.protocol {
+            Some(IPPROTO_TCP) => {
+                let tcp = header(packet.payload, TCP_HLEN, "TCP")?;
+                packet.tcp_flags = Some(TcpFlags::from_bits_retain(tcp[13]));
+                usize::from(tcp[12] >> 4) * 4
+            }
+            Some(IPPROTO_UDP) => UDP_HLEN,
+            _ => return Ok(packet),
+        };
+        let l4 = header(packet.payload, hlen, "transport")?;
+        packet.src_port = Some(u16::from_be_bytes([l4[0], l4[1]]));
+        packet.dst_port = Some(u16::from_be_bytes([l4[2], l4[3]]));
+        packet.payload = &data[l4_offset + hlen..];
+        packet.l4_offset = Some(l4_offset);
+        Ok(packet)
+    }
+
+    /// Check whether the IPv4 header checksum and the TCP or UDP checksum
+    /// of the packet are valid.
+    ///
+    /// Layers that were not parsed are not checked.
+    pub fn checksums_valid(&self) -> bool {
+        if self.ether_type == ETH_P_IP
+            && checksum(&self.data[ETH_HLEN..ETH_HLEN + IPV4_HLEN], 0) != 0
+        {
+            return false
+        }
+
+        match (self.l4_offset, self.src_addr, self.dst_addr, self.protocol) {
+            (Some(offset), Some(src), Some(dst), Some(protocol)) => {
+                let segment = &self.data[offset..];
+                // A UDP checksum of zero denotes the absence of a checksum.
+                if protocol == IPPROTO_UDP && segment[6..8] == [0, 0] {
+                    return true
+                }
+                let sum = pseudo_header_sum(&src, &dst, protocol, segment.len());
+                checksum(segment, sum) == 0
+            }
+            _ => true,
+        }
+    }
+}
+
+
+/// Retrieve the name of an `XDP` action.
+fn xdp_action_name(action: u32) -> String {
+    let name = match action {
+        libbpf_sys::XDP_ABORTED => "XDP_ABORTED",
+        libbpf_sys::XDP_DROP => "XDP_DROP",
+        libbpf_sys::XDP_PASS => "XDP_PASS",
+        libbpf_sys::XDP_TX => "XDP_TX",
+        libbpf_sys::XDP_REDIRECT => "XDP_REDIRECT",
+        _ => return action.to_string(),
+    };
+    name.to_string()
+}
+
+/// Retrieve the name of a `tc` action.
+fn tc_action_name(action: i32) -> String {
+    let name = match action {
+        -1 => "TC_ACT_UNSPEC",
+        0 => "TC_ACT_OK",
+        1 => "TC_ACT_RECLASSIFY",
+        2 => "TC_ACT_SHOT",
+        3 => "TC_ACT_PIPE",
+        4 => "TC_ACT_STOLEN",
+        5 => "TC_ACT_QUEUED",
+        6 => "TC_ACT_REPEAT",
+        7 => "TC_ACT_REDIRECT",
+        8 => "TC_ACT_TRAP",
+        _ => return action.to_string(),
+    };
+    name.to_string()
+}
+
+/// Format a hex dump of `data`, sixteen bytes per line.
+fn hexdump(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (idx, line) in data.chunks(16).enumerate() {
+        // Writing to a `String` never fails.
+        let _result = write!(dump, "\n  {:04x}:", idx * 16);
+        for byte in line {
+            let _result = write!(dump, " {byte:02x}");
+        }
+    }
+    dump
+}
+
+
+/// The result of running a program on a packet via [`run_packet`].
+#[derive(Clone, Debug)]
+pub struct PacketRun {
+    /// The value returned by the program, i.e., its action.
+    pub return_value: u32,
+    /// The packet as modified by the program.
+    pub packet: Vec<u8>,
+    /// The struct is non-exhaustive and open to extension.
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl PacketRun {
+    /// Parse the packet as modified by the program.
+    pub fn parse(&self) -> Result<ParsedPacket<'_>> {
+        ParsedPacket::parse(&self.packet)
+    }
+
+    /// Assert that an `XDP` program returned `action`, e.g.,
+    /// [`libbpf_sys::XDP_PASS`].
+    #[track_caller]
+    pub fn assert_xdp_action(&self, action: u32) -> &Self {
+        assert!(
+            self.return_value == action,
+            "expected XDP action {}, but program returned {}",
+            xdp_action_name(action),
+            xdp_action_name(self.return_value),
+        );
+        self
+    }
+
+    /// Assert that a `tc` program returned `action`, e.g., `0` for
+    /// `TC_ACT_OK` or `2` for `TC_ACT_SHOT`.
+    #[track_caller]
+    pub fn assert_tc_action(&self, action: i32) -> &Self {
+        assert!(
+            self.return_value as i32 == action,
+            "expected tc action {}, but program returned {}",
+            tc_action_name(action),
+            tc_action_name(self.return_value as i32),
+        );
+        self
+    }
+
+    /// Assert that the packet as modified by the program equals
+    /// `expected`.
+    #[track_caller]
+    pub fn assert_packet(&self, expected: &[u8]) -> &Self {
+        if self.packet != expected {
+            let offset = self
+                .packet
+                .iter()
+                .zip(expected)
+                .position(|(actual, expected)| actual != expected)
+                .unwrap_or_else(|| self.packet.len().min(expected.len()));
+            panic!(
+                "packet differs from expectation at offset {offset}\nexpected:{}\nactual:{}",
+                hexdump(expected),
+                hexdump(&self.packet),
+            );
+        }
+        self
+    }
+
+    /// Assert that the packet as modified by the program carries valid
+    /// checksums (see [`ParsedPacket::checksums_valid`]).
+    #[track_caller]
+    pub fn assert_checksums_valid(&self) -> &Self {
+        let packet = self
+            .parse()
+            .unwrap_or_else(|err| panic!("failed to parse packet: {err}"));
+        assert!(
+            packet.checksums_valid(),
+            "packet carries invalid checksums:{}",
+            hexdump(&self.packet)
+        );
+        self
+    }
+}
+
+
+/// Run a packet processing program, such as an `XDP` or `tc` program, on
+/// `packet` via [`ProgramMut::test_run`].
+///
+/// The program may grow the packet by up to a page.
+pub fn run_packet(prog: &ProgramMut<'_>, packet: &[u8]) -> Result<PacketRun> {
+    let mut data_out = vec![0; packet.len() + OUTPUT_SLACK];
+    let input = ProgramInput {
+        data_in: Some(packet),
+        data_out: Some(&mut data_out),
+        ..Default::default()
+    };
+    let output = prog.test_run(input)?;
+    let return_value = output.return_value;
+    let len = output.data.map(|data| data.len()).unwrap_or(0);
+    let () = data_out.truncate(len);
+
+    let run = PacketRun {
+        return_value,
+        packet: data_out,
+        _non_exhaustive: (),
+    };
+    Ok(run)
+}
+
+
+/// The entries of a map, ordered by key.
+type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Collect key-value pairs into [`Entries`].
+fn collect_entries<I, K, V>(entries: I) -> Entries
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    entries
+        .into_iter()
+        .map(|(key, value)| (key.as_ref().to_vec(), value.as_ref().to_vec()))
+        .collect()
+}
+
+/// Format `data` as space separated hexadecimal bytes.
+fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Describe the differences between `expected` and `actual` entries, if
+/// any, one line per differing key.
+fn diff_entries(expected: &Entries, actual: &Entries) -> Option<String> {
+    let mut diff = String::new();
+    for (key, value) in expected {
+        // Writing to a `String` never fails.
+        let _result = match actual.get(key) {
+            None => write!(diff, "\n  missing    [{}]: {}", hex(key), hex(value)),
+            Some(actual) if actual != value => write!(
+                diff,
+                "\n  mismatch   [{}]: expected {}, actual {}",
+                hex(key),
+                hex(value),
+                hex(actual)
+            ),
+            Some(_) => Ok(()),
+        };
+    }
+    for (key, value) in actual {
+        if !expected.contains_key(key) {
+            let _result = write!(diff, "\n  unexpected [{}]: {}", hex(key), hex(value));
+        }
+    }
+    (!diff.is_empty()).then_some(diff)
+}
+
+
+/// A fixture for setting up the state of a map before running a program
+/// and asserting on the state the program left it in.
+///
+/// Keys and values are provided as byte slices, e.g., as created by
+/// [`u32::to_ne_bytes`], and have to match the map's key and value sizes.
+/// Per-CPU maps are not supported.
+///
+/// ```no_run
+/// # fn example(map: &libbpf_rs::Map<'_>) -> libbpf_rs::Result<()> {
+/// use libbpf_rs::testing::MapFixture;
+///
+/// let mut fixture = MapFixture::new(map)?;
+/// fixture.populate([(9u32.to_ne_bytes(), 41u64.to_ne_bytes())])?;
+/// // Run the program, e.g., via `run_packet` or by generating traffic.
+/// fixture.assert_changes([(9u32.to_ne_bytes(), 42u64.to_ne_bytes())]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MapFixture<'map, M: ?Sized> {
+    map: &'map M,
+    /// The entries of the map after it was last populated.
+    baseline: Entries,
+}
+
+impl<'map, M> MapFixture<'map, M>
+where
+    M: MapCore + ?Sized,
+{
+    /// Create a fixture for `map`, capturing its current entries as the
+    /// baseline for [`MapFixture::assert_changes`].
+    pub fn new(map: &'map M) -> Result<Self> {
+        let map_type = map.map_type();
+        if map_type.is_percpu() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                format!("map fixtures do not support per-CPU maps (type {map_type:?})"),
+            ))
+        }
+
+        let mut fixture = Self {
+            map,
+            baseline: Entries::new(),
+        };
+        fixture.baseline = fixture.entries()?;
+        Ok(fixture)
+    }
+
+    /// Write `entries` into the map, replacing existing values for the
+    /// same keys, and capture the resulting entries as the new baseline.
+    pub fn populate<I, K, V>(&mut self, entries: I) -> Result<&mut Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        for (key, value) in entries {
+            let () = self
+                .map
+                .update(key.as_ref(), value.as_ref(), MapFlags::ANY)?;
+        }
+        self.baseline = self.entries()?;
+        Ok(self)
+    }
+
+    /// Read all entries of the map, ordered by key.
+    pub fn entries(&self) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+        let mut iter = self.map.iter()?;
+        let mut entries = BTreeMap::new();
+        while let Some((key, value)) = iter.try_next()? {
+            let _prev = entries.insert(key, value);
+        }
+        Ok(entries)
+    }
+
+    #[track_caller]
+    fn assert_diff(&self, what: &str, expected: &Entries, actual: &Entries) {
+        if let Some(diff) = diff_entries(expected, actual) {
+            panic!(
+                "{what} of map `{}` differ from expectation:{diff}",
+                self.map.name().to_string_lossy()
+            );
+        }
+    }
+
+    #[track_caller]
+    fn current(&self) -> Entries {
+        self.entries().unwrap_or_else(|err| {
+            panic!(
+                "failed to read entries of map `{}`: {err}",
+                self.map.name().to_string_lossy()
+            )
+        })
+    }
+
+    /// Assert that the map contains exactly the `expected` entries.
+    #[track_caller]
+    pub fn assert_entries<I, K, V>(&self, expected: I) -> &Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let () = self.assert_diff("entries", &collect_entries(expected), &self.current());
+        self
+    }
+
+    /// Assert that the entries added or modified since the map was last
+    /// populated (or the fixture was created) are exactly the `expected`
+    /// ones.
+    ///
+    /// Entries removed since then are reported as missing.
+    #[track_caller]
+    pub fn assert_changes<I, K, V>(&self, expected: I) -> &Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let current = self.current();
+        let mut changes = current
+            .iter()
+            .filter(|(key, value)| self.baseline.get(*key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Entries>();
+        let mut expected = collect_entries(expected);
+        // Keys that vanished are expected to still carry their baseline
+        // value, so that their removal shows up as a difference.
+        for (key, value) in &self.baseline {
+            if !current.contains_key(key) {
+                let _prev = changes.remove(key);
+                let _prev = expected.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        let () = self.assert_diff("changed entries", &expected, &changes);
+        self
+    }
+}
+
+
+/// The environment variable turning unmet test requirements into
+/// failures, for CI runners which are expected to run all tests.
+const REQUIRE_ALL_VAR: &str = "LIBBPF_RS_TEST_REQUIRE_ALL";
+
+
+/// A requirement for the running kernel to be of at least the given
+/// major and minor version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KernelAtLeast(pub u32, pub u32);
+
+
+/// A set of requirements a test has on its environment, as checked by
+/// [`requires`].
+///
+/// Requirements are combined with `|`, e.g.,
+/// `Capability::Bpf | KernelAtLeast(5, 8)`. `CAP_SYS_ADMIN` satisfies a
+/// requirement for `CAP_BPF` or `CAP_PERFMON`, as it does in the kernel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Requirements {
+    /// The masks of the required capabilities.
+    caps: u64,
+    kernel: Option<(u32, u32)>,
+}
+
+impl Requirements {
+    /// Describe the requirements not met by an environment with the
+    /// given effective capabilities and kernel version.
+    fn unmet(&self, effective: Option<u64>, kernel: Option<(u32, u32)>) -> Vec<String> {
+        let mut unmet = Vec::new();
+
+        if self.caps != 0 {
+            match effective {
+                Some(effective) => {
+                    let names = Capability::ALL
+                        .iter()
+                        .filter(|cap| self.caps & cap.mask() != 0 && !cap.is_granted(effective))
+                        .map(Capability::name)
+                        .collect::<Vec<_>>();
+                    if !names.is_empty() {
+                        unmet.push(format!("missing {}", names.join(", ")));
+                    }
+                }
+                None => unmet.push("failed to determine effective capabilities".to_string()),
+            }
+        }
+
+        if let Some((major, minor)) = self.kernel {
+            match kernel {
+                Some(version) if version >= (major, minor) => (),
+                Some((actual_major, actual_minor)) => unmet.push(format!(
+                    "kernel {actual_major}.{actual_minor} is older than {major}.{minor}"
+                )),
+                None => unmet.push("failed to determine kernel version".to_string()),
+            }
+        }
+        unmet
+    }
+}
+
+impl From<Capability> for Requirements {
+    fn from(cap: Capability) -> Self {
+        Self {
+            caps: cap.mask(),
+            kernel: None,
+        }
+    }
+}
+
+impl From<KernelAtLeast> for Requirements {
+    fn from(KernelAtLeast(major, minor): KernelAtLeast) -> Self {
+        Self {
+            caps: 0,
+            kernel: Some((major, minor)),
+        }
+    }
+}
+
+impl<R> BitOr<R> for Requirements
+where
+    R: Into<Requirements>,
+{
+    type Output = Requirements;
+
+    fn bitor(self, other: R) -> Self::Output {
+        let other = other.into();
+        Self {
+            caps: self.caps | other.caps,
+            kernel: self.kernel.max(other.kernel),
+        }
+    }
+}
+
+impl<R> BitOr<R> for Capability
+where
+    R: Into<Requirements>,
+{
+    type Output = Requirements;
+
+    fn bitor(self, other: R) -> Self::Output {
+        Requirements::from(self) | other
+    }
+}
+
+impl<R> BitOr<R> for KernelAtLeast
+where
+    R: Into<Requirements>,
+{
+    type Output = Requirements;
+
+    fn bitor(self, other: R) -> Self::Output {
+        Requirements::from(self) | other
+    }
+}
+
+
+/// Parse the major and minor version from a kernel release string, such
+/// as `6.8.0-45-generic`.
+fn parse_kernel_release(release: &str) -> Option<(u32, u32)> {
+    let mut parts = release.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Retrieve the major and minor version of the running kernel.
+fn kernel_version() -> Option<(u32, u32)> {
+    // SAFETY: `utsname` is valid for any bit pattern.
+    let mut uts = unsafe { mem::zeroed::<libc::utsname>() };
+    // SAFETY: `uts` is a valid `utsname` object.
+    let rc = unsafe { libc::uname(&mut uts) };
+    if rc != 0 {
+        return None
+    }
+    // SAFETY: The kernel NUL terminates the release string.
+    let release = unsafe { CStr::from_ptr(uts.release.as_ptr()) };
+    parse_kernel_release(release.to_str().ok()?)
+}
+
+/// Check whether the environment satisfies the `requirements` of a
+/// test, returning `false` after printing the reason to stderr if it
+/// does not.
+///
+/// Tests use this function as a guard, returning early when it fails:
+///
+/// ```no_run
+/// use libbpf_rs::testing::requires;
+/// use libbpf_rs::testing::Capability;
+/// use libbpf_rs::testing::KernelAtLeast;
+///
+/// #[test]
+/// fn test_ringbuf() {
+///     if !requires(Capability::Bpf | KernelAtLeast(5, 8)) {
+///         return
+///     }
+///     // ...
+/// }
+/// ```
+///
+/// The Rust test harness has no notion of skipped tests, so such tests
+/// are reported as passed. On CI runners expected to run every test,
+/// setting the `LIBBPF_RS_TEST_REQUIRE_ALL` environment variable makes
+/// unmet requirements fail the test instead.
+#[track_caller]
+pub fn requires<R>(requirements: R) -> bool
+where
+    R: Into<Requirements>,
+{
+    let unmet = requirements
+        .into()
+        .unmet(privilege::effective_caps(), kernel_version());
+    if unmet.is_empty() {
+        return true
+    }
+
+    let reason = unmet.join("; ");
+    if env::var_os(REQUIRE_ALL_VAR).is_some() {
+        panic!("test requirements not met ({REQUIRE_ALL_VAR} is set): {reason}");
+    }
+    eprintln!("skipping test: {reason}");
+    false
+}
+
+
+/// The environment variable carrying the name of the kernel a test
+/// binary runs under, as set by [`Vm`] when running it inside a VM.
+const VM_KERNEL_VAR: &str = "LIBBPF_RS_TEST_VM_KERNEL";
+/// The environment variable naming the directory containing kernel
+/// images, for looking them up by name.
+const KERNEL_DIR_VAR: &str = "LIBBPF_RS_TEST_KERNEL_DIR";
+/// The environment variable overriding the `vmtest` binary to use.
+const VMTEST_VAR: &str = "LIBBPF_RS_VMTEST";
+/// The file name prefixes of kernel images, in order of preference.
+const KERNEL_IMAGE_PREFIXES: [&str; 2] = ["bzImage-", "vmlinuz-"];
+
+
+/// A kernel image to boot VMs with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Kernel {
+    name: String,
+    image: PathBuf,
+}
+
+impl Kernel {
+    /// Create a [`Kernel`] called `name`, booted from `image`.
+    pub fn new<N, P>(name: N, image: P) -> Self
+    where
+        N: Into<String>,
+        P: Into<PathBuf>,
+    {
+        Self {
+            name: name.into(),
+            image: image.into(),
+        }
+    }
+
+    /// Look up the kernel called `name` (e.g., `"5.10"`).
+    ///
+    /// The image is expected to reside in the directory named by the
+    /// `LIBBPF_RS_TEST_KERNEL_DIR` environment variable, as
+    /// `bzImage-<name>` or `vmlinuz-<name>`.
+    pub fn named(name: &str) -> Result<Self> {
+        let dir = env::var_os(KERNEL_DIR_VAR).ok_or_error(io::ErrorKind::NotFound, || {
+            format!("unable to look up kernel `{name}`: {KERNEL_DIR_VAR} is not set")
+        })?;
+        let dir = Path::new(&dir);
+        let image = find_kernel_image(dir, name).ok_or_error(io::ErrorKind::NotFound, || {
+            format!("no image for kernel `{name}` found in `{}`", dir.display())
+        })?;
+        Ok(Self::new(name, image))
+    }
+
+    /// Retrieve the kernel's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve the path to the kernel's image.
+    pub fn image(&self) -> &Path {
+        &self.image
+    }
+}
+
+fn find_kernel_image(dir: &Path, name: &str) -> Option<PathBuf> {
+    KERNEL_IMAGE_PREFIXES
+        .iter()
+        .map(|prefix| dir.join(format!("{prefix}{name}")))
+        .find(|path| path.is_file())
+}
+
+/// Retrieve the name of the kernel the current process runs under, if
+/// it was started inside of a VM by [`Vm`].
+pub fn vm_kernel() -> Option<String> {
+    env::var(VM_KERNEL_VAR).ok()
+}
+
+/// Append `arg` to a shell command line, quoted so that the shell
+/// passes it through verbatim.
+fn push_shell_arg(cmdline: &mut Vec<u8>, arg: &OsStr) {
+    if !cmdline.is_empty() {
+        let () = cmdline.push(b' ');
+    }
+    let () = cmdline.push(b'\'');
+    for &b in arg.as_bytes() {
+        if b == b'\'' {
+            let () = cmdline.extend_from_slice(b"'\\''");
+        } else {
+            let () = cmdline.push(b);
+        }
+    }
+    let () = cmdline.push(b'\'');
+}
+
+/// A virtual machine booting a given kernel, for running tests against
+/// kernels other than the host's.
+///
+/// VMs are run by [`vmtest`](https://github.com/danobi/vmtest), which
+/// needs to be installed along with `qemu`. The binary is looked up in
+/// `PATH`, unless overridden by the `LIBBPF_RS_VMTEST` environment
+/// variable. The VM shares the host's file system, so that programs on
+/// the host can be run inside of it as is.
+#[derive(Clone, Debug)]
+pub struct Vm {
+    kernel: Kernel,
+    vmtest: PathBuf,
+}
+
+impl Vm {
+    /// Create a [`Vm`] booting `kernel`.
+    pub fn new(kernel: Kernel) -> Self {
+        let vmtest = env::var_os(VMTEST_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("vmtest"));
+        Self { kernel, vmtest }
+    }
+
+    /// Set the path to the `vmtest` binary to use.
+    pub fn vmtest<P>(mut self, vmtest: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.vmtest = vmtest.into();
+        self
+    }
+
+    /// Retrieve the kernel the VM boots.
+    pub fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+
+    /// Boot the VM and run `program` with `args` inside of it, e.g., a
+    /// compiled test binary.
+    ///
+    /// Inside the VM, [`vm_kernel`] reports the name of the kernel. The
+    /// exit status of `program` is reported as part of the output and
+    /// does not constitute an error.
+    pub fn run_command<P, A, S>(&self, program: P, args: A) -> Result<Output>
+    where
+        P: AsRef<OsStr>,
+        A: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let var = format!("{VM_KERNEL_VAR}={}", self.kernel.name);
+        let mut cmdline = Vec::new();
+        let () = push_shell_arg(&mut cmdline, OsStr::new("env"));
+        let () = push_shell_arg(&mut cmdline, OsStr::new(&var));
+        let () = push_shell_arg(&mut cmdline, program.as_ref());
+        for arg in args {
+            let () = push_shell_arg(&mut cmdline, arg.as_ref());
+        }
+
+        Command::new(&self.vmtest)
+            .arg("--kernel")
+            .arg(&self.kernel.image)
+            .arg(OsString::from_vec(cmdline))
+            .output()
+            .with_context(|| format!("failed to run `{}`", self.vmtest.display()))
+    }
+
+    /// Run the test `name` of the current test binary with `test` as its
+    /// body inside of the VM.
+    ///
+    /// On the host, this function runs the test binary inside the VM,
+    /// restricted to the test `name`, and fails if the test fails there.
+    /// Inside the VM, `test` is invoked if the VM boots this [`Vm`]'s
+    /// kernel and skipped otherwise, so that a test can cover multiple
+    /// kernels:
+    ///
+    /// ```no_run
+    /// use libbpf_rs::testing::Kernel;
+    /// use libbpf_rs::testing::Vm;
+    ///
+    /// #[test]
+    /// fn test_ringbuf() {
+    ///     for kernel in ["5.10", "6.1"] {
+    ///         let vm = Vm::new(Kernel::new(kernel, format!("/boot/bzImage-{kernel}")));
+    ///         let () = vm
+    ///             .run_test("test_ringbuf", || {
+    ///                 // ...
+    ///             })
+    ///             .unwrap();
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// `name` has to be the full name of the test, as listed by the test
+    /// binary's `--list` option.
+    pub fn run_test<F>(&self, name: &str, test: F) -> Result<()>
+    where
+        F: FnOnce(),
+    {
+        if let Some(kernel) = vm_kernel() {
+            if kernel == self.kernel.name {
+                let () = test();
+            }
+            return Ok(())
+        }
+
+        let exe = env::current_exe().context("failed to determine test binary")?;
+        let output = self.run_command(
+            &exe,
+            [name, "--exact", "--include-ignored", "--test-threads=1"],
+        )?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Other,
+                format!(
+                    "test `{name}` failed on kernel `{}` ({}):\n{stdout}{stderr}",
+                    self.kernel.name, output.status
+                ),
+            ))
+        }
+        // With a filter matching no test the binary still succeeds,
+        // which must not be mistaken for a passed test.
+        if !stdout.contains("running 1 test") {
+            return Err(Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!("test `{name}` not found in `{}`", exe.display()),
+            ))
+        }
+        Ok(())
+    }
+}
+
+/// Run the test `name` with `test` as its body against each of the
+/// named `kernels`, as looked up by [`Kernel::named`].
+///
+/// See [`Vm::run_test`] for details.
+pub fn run_in_kernels<F>(kernels: &[&str], name: &str, test: F) -> Result<()>
+where
+    F: Fn(),
+{
+    if let Some(kernel) = vm_kernel() {
+        if kernels.contains(&kernel.as_str()) {
+            let () = test();
+        }
+        return Ok(())
+    }
+
+    for kernel in kernels {
+        let () = Vm::new(Kernel::named(kernel)?).run_test(name, &test)?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::panic::catch_unwind;
+
+
+    /// Check that built frames carry the expected fields and checksums.
+    #[test]
+    fn packet_building() {
+        let packet = PacketBuilder::new()
+            .ipv4(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2))
+            .ttl(3)
+            .tcp(1234, 80)
+            .tcp_flags(TcpFlags::SYN)
+            .payload(b"hello")
+            .build();
+        assert_eq!(packet.len(), ETH_HLEN + IPV4_HLEN + TCP_HLEN + 5);
+
+        let parsed = ParsedPacket::parse(&packet).unwrap();
+        assert_eq!(parsed.ether_type, ETH_P_IP);
+        assert_eq!(parsed.src_addr, Some(IpAddr::from([10, 0, 0, 1])));
+        assert_eq!(parsed.dst_addr, Some(IpAddr::from([10, 0, 0, 2])));
+        assert_eq!(parsed.ttl, Some(3));
+        assert_eq!(parsed.protocol, Some(IPPROTO_TCP));
+        assert_eq!(parsed.src_port, Some(1234));
+        assert_eq!(parsed.dst_port, Some(80));
+        assert_eq!(parsed.tcp_flags, Some(TcpFlags::SYN));
+        assert_eq!(parsed.payload, b"hello");
+        assert!(parsed.checksums_valid());
+
+        let packet = PacketBuilder::new()
+            .ipv6(Ipv6Addr::LOCALHOST, Ipv6Addr::LOCALHOST)
+            .udp(53, 5353)
+            .payload(b"odd")
+            .build();
+        let parsed = ParsedPacket::parse(&packet).unwrap();
+        assert_eq!(parsed.ether_type, ETH_P_IPV6);
+        assert_eq!(parsed.protocol, Some(IPPROTO_UDP));
+        assert_eq!(parsed.dst_port, Some(5353));
+        assert_eq!(parsed.payload, b"odd");
+        assert!(parsed.checksums_valid());
+
+        let mut corrupted = packet.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        let parsed = ParsedPacket::parse(&corrupted).unwrap();
+        assert!(!parsed.checksums_valid());
+
+        let err = ParsedPacket::parse(&packet[..ETH_HLEN + 10]).unwrap_err();
+        assert!(err.to_string().contains("IPv6"), "{err}");
+    }
+
+    /// Check that the checksum matches a well known reference value.
+    #[test]
+    fn internet_checksum() {
+        // Example header from the Wikipedia article on the IPv4 header
+        // checksum.
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        assert_eq!(checksum(&header, 0), 0xb861);
+    }
+
+    /// Check that assertions report mismatching actions and packets.
+    #[test]
+    fn run_assertions() {
+        let packet = PacketBuilder::new().payload(&[1, 2, 3]).build();
+        let run = PacketRun {
+            return_value: libbpf_sys::XDP_DROP,
+            packet: packet.clone(),
+            _non_exhaustive: (),
+        };
+        let _run = run
+            .assert_xdp_action(libbpf_sys::XDP_DROP)
+            .assert_packet(&packet);
+
+        let result = catch_unwind(|| run.assert_xdp_action(libbpf_sys::XDP_PASS));
+        let err = result.unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("XDP_PASS"), "{msg}");
+        assert!(msg.contains("XDP_DROP"), "{msg}");
+    }
+
+    /// Check that differences between map entries are reported.
+    #[test]
+    fn entry_diffing() {
+        let expected = collect_entries([([1u8], [1u8]), ([2], [2]), ([3], [3])]);
+        let actual = collect_entries([([1u8], [1u8]), ([2], [4]), ([5], [5])]);
+        assert_eq!(diff_entries(&expected, &expected), None);
+
+        let diff = diff_entries(&expected, &actual).unwrap();
+        let lines = diff.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "  mismatch   [02]: expected 02, actual 04",
+                "  missing    [03]: 03",
+                "  unexpected [05]: 05",
+            ]
+        );
+    }
+
+    /// Check that kernel release strings are parsed as expected.
+    #[test]
+    fn kernel_release_parsing() {
+        assert_eq!(parse_kernel_release("6.8.0-45-generic"), Some((6, 8)));
+        assert_eq!(parse_kernel_release("5.15.153.1-microsoft"), Some((5, 15)));
+        assert_eq!(parse_kernel_release("6.10"), Some((6, 10)));
+        assert_eq!(parse_kernel_release("6"), None);
+        assert_eq!(parse_kernel_release("foo"), None);
+    }
+
+    /// Check that requirements are evaluated against an environment.
+    #[test]
+    fn requirement_evaluation() {
+        let reqs = Capability::Bpf | Capability::Perfmon | KernelAtLeast(5, 8);
+        let bpf = Capability::Bpf.mask();
+        let all = Capability::Bpf.mask() | Capability::Perfmon.mask();
+        let admin = Capability::SysAdmin.mask();
+
+        assert!(reqs.unmet(Some(all), Some((5, 8))).is_empty());
+        assert!(reqs.unmet(Some(admin), Some((6, 1))).is_empty());
+        assert_eq!(
+            reqs.unmet(Some(bpf), Some((5, 4))),
+            ["missing CAP_PERFMON", "kernel 5.4 is older than 5.8"]
+        );
+        assert_eq!(
+            reqs.unmet(None, None),
+            [
+                "failed to determine effective capabilities",
+                "failed to determine kernel version"
+            ]
+        );
+
+        // CAP_SYS_ADMIN does not stand in for CAP_NET_ADMIN.
+        let reqs = Capability::NetAdmin | Capability::Bpf;
+        assert_eq!(reqs.unmet(Some(admin), None), ["missing CAP_NET_ADMIN"]);
+        assert!(reqs
+            .unmet(Some(admin | Capability::NetAdmin.mask()), None)
+            .is_empty());
+
+        let reqs = KernelAtLeast(5, 8) | KernelAtLeast(6, 1);
+        assert_eq!(reqs.unmet(None, Some((5, 10))).len(), 1);
+        assert!(Requirements::default().unmet(None, None).is_empty());
+    }
+
+    /// Check that arguments are quoted for the shell as expected.
+    #[test]
+    fn shell_arg_quoting() {
+        let mut cmdline = Vec::new();
+        let () = push_shell_arg(&mut cmdline, OsStr::new("/bin/test binary"));
+        let () = push_shell_arg(&mut cmdline, OsStr::new("it's"));
+        let () = push_shell_arg(&mut cmdline, OsStr::new(""));
+        assert_eq!(cmdline, b"'/bin/test binary' 'it'\\''s' ''");
+    }
+
+    /// Check that kernel images are found by name.
+    #[test]
+    fn kernel_image_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let () = fs::write(dir.path().join("vmlinuz-5.10"), b"").unwrap();
+        let () = fs::write(dir.path().join("bzImage-6.1"), b"").unwrap();
+        let () = fs::write(dir.path().join("vmlinuz-6.1"), b"").unwrap();
+
+        assert_eq!(
+            find_kernel_image(dir.path(), "5.10"),
+            Some(dir.path().join("vmlinuz-5.10"))
+        );
+        assert_eq!(
+            find_kernel_image(dir.path(), "6.1"),
+            Some(dir.path().join("bzImage-6.1"))
+        );
+        assert_eq!(find_kernel_image(dir.path(), "5.4"), None);
+    }
+}
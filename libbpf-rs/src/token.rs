@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::path::Path;
+use std::ptr;
+
+use crate::util;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// A BPF token, granting delegated BPF permissions.
+///
+/// A token is derived from a BPF file system instance that was mounted
+/// with delegation options (`delegate_cmds`, `delegate_maps`,
+/// `delegate_progs`, `delegate_attachs`). It allows a process inside a
+/// user namespace to perform the delegated subset of BPF operations
+/// without having `CAP_BPF` in the init namespace.
+///
+/// The token can be passed along to map creation via the `token_fd`
+/// member of [`libbpf_sys::bpf_map_create_opts`]. When opening BPF
+/// objects, prefer [`ObjectBuilder::bpf_token_path`][crate::ObjectBuilder::bpf_token_path],
+/// which lets `libbpf` create and manage the token.
+#[derive(Debug)]
+pub struct BpfToken {
+    fd: OwnedFd,
+}
+
+impl BpfToken {
+    /// Create a BPF token from a file descriptor referring to the root of
+    /// a BPF file system mount.
+    pub fn from_bpffs_fd(bpffs: BorrowedFd<'_>) -> Result<Self> {
+        // SAFETY: `bpf_token_create` is always safe to call with a valid
+        //         file descriptor and a NULL `opts` pointer.
+        let fd = unsafe { libbpf_sys::bpf_token_create(bpffs.as_raw_fd(), ptr::null_mut()) };
+        let fd = util::parse_ret_i32(fd).context("failed to create BPF token")?;
+        // SAFETY: A file descriptor coming from the `bpf_token_create`
+        //         function is always suitable for ownership and can be
+        //         cleaned up with close.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok(Self { fd })
+    }
+
+    /// Create a BPF token from the BPF file system mounted at `path`.
+    pub fn from_bpffs_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bpffs = File::options()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY)
+            .open(path)
+            .with_context(|| format!("failed to open BPF file system at `{}`", path.display()))?;
+        Self::from_bpffs_fd(bpffs.as_fd())
+    }
+}
+
+impl AsFd for BpfToken {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
@@ -0,0 +1,158 @@
+//! Instrumentation of the object life cycle by means of the `tracing`
+//! crate.
+//!
+//! With the `tracing` feature enabled, opening and loading objects,
+//! creating maps, and attaching programs happens inside a span (at
+//! `DEBUG` level, with target `libbpf_rs`), with an event reporting
+//! the operation's duration and outcome, including the file descriptors
+//! and IDs of the created kernel objects. Without the feature all of
+//! this compiles down to nothing.
+
+use std::fmt::Display;
+
+use crate::Result;
+
+#[cfg(feature = "tracing")]
+pub(crate) use imp::*;
+
+/// Run `f`, representing the operation `op` on `name`.
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn instrument<T, F>(op: &'static str, name: &dyn Display, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let _ = (op, name);
+    f()
+}
+
+
+#[cfg(feature = "tracing")]
+mod imp {
+    use super::*;
+
+    use std::ffi::c_void;
+    use std::mem::size_of_val;
+    use std::os::fd::AsFd;
+    use std::os::fd::AsRawFd as _;
+    use std::os::fd::RawFd;
+    use std::time::Instant;
+
+    use tracing::debug;
+    use tracing::debug_span;
+
+    use crate::Link;
+    use crate::MapCore as _;
+    use crate::MapHandle;
+    use crate::Object;
+    use crate::OpenObject;
+
+
+    /// Retrieve the ID of the program, map, or link referenced by `fd`.
+    fn object_id(fd: RawFd) -> Option<u32> {
+        // `bpf_prog_info`, `bpf_map_info`, and `bpf_link_info` all
+        // start out with the object's type followed by its ID. The kernel
+        // accepts truncated info structs, so just ask for these two.
+        let mut info = [0u32; 2];
+        let mut len = size_of_val(&info) as u32;
+        // SAFETY: All pointers are derived from references and hence
+        //         valid.
+        let ret = unsafe {
+            libbpf_sys::bpf_obj_get_info_by_fd(fd, info.as_mut_ptr().cast::<c_void>(), &mut len)
+        };
+        (ret == 0).then_some(info[1])
+    }
+
+    /// A type that is the result of an instrumented operation.
+    pub(crate) trait Outcome {
+        /// Emit events describing the result.
+        fn record(&self);
+    }
+
+    impl Outcome for () {
+        fn record(&self) {}
+    }
+
+    impl Outcome for Link {
+        fn record(&self) {
+            let fd = self.as_fd().as_raw_fd();
+            debug!(target: "libbpf_rs", fd, id = object_id(fd), "created link");
+        }
+    }
+
+    impl Outcome for MapHandle {
+        fn record(&self) {
+            let fd = self.as_fd().as_raw_fd();
+            debug!(
+                target: "libbpf_rs",
+                map = %self.name().to_string_lossy(),
+                fd,
+                id = object_id(fd),
+                "created map"
+            );
+        }
+    }
+
+    impl Outcome for OpenObject {
+        fn record(&self) {
+            debug!(
+                target: "libbpf_rs",
+                maps = self.maps().count(),
+                progs = self.progs().count(),
+                "opened object"
+            );
+        }
+    }
+
+    impl Outcome for Object {
+        fn record(&self) {
+            for map in self.maps() {
+                let fd = map.as_fd().as_raw_fd();
+                debug!(
+                    target: "libbpf_rs",
+                    map = %map.name().to_string_lossy(),
+                    fd,
+                    id = object_id(fd),
+                    "created map"
+                );
+            }
+
+            for prog in self.progs().filter(|prog| prog.autoload()) {
+                let fd = prog.as_fd().as_raw_fd();
+                debug!(
+                    target: "libbpf_rs",
+                    prog = %prog.name().to_string_lossy(),
+                    fd,
+                    id = object_id(fd),
+                    "loaded program"
+                );
+            }
+        }
+    }
+
+    /// Run `f` inside a span representing the operation `op` on `name`,
+    /// reporting its duration and outcome.
+    pub(crate) fn instrument<T, F>(op: &'static str, name: &dyn Display, f: F) -> Result<T>
+    where
+        T: Outcome,
+        F: FnOnce() -> Result<T>,
+    {
+        let span = debug_span!(target: "libbpf_rs", "bpf", op, name = %name);
+        let _guard = span.enter();
+
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+
+        match &result {
+            Ok(value) => {
+                let () = value.record();
+                debug!(target: "libbpf_rs", ?elapsed, "{op} succeeded");
+            }
+            Err(err) => {
+                debug!(target: "libbpf_rs", ?elapsed, error = %err, "{op} failed");
+            }
+        }
+        result
+    }
+}
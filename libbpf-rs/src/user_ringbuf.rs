@@ -160,6 +160,11 @@ impl AsRawLibbpf for UserRingBuffer {
     }
 }
 
+// SAFETY: `user_ring_buffer` objects can safely be sent to a different
+//         thread. We intentionally do not implement `Sync`, because
+//         reservation of samples requires external synchronization.
+unsafe impl Send for UserRingBuffer {}
+
 impl Drop for UserRingBuffer {
     fn drop(&mut self) {
         unsafe {
@@ -167,3 +172,20 @@ impl Drop for UserRingBuffer {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Check that `UserRingBuffer` is `Send`.
+    #[test]
+    fn user_ringbuffer_is_send() {
+        fn test<T>()
+        where
+            T: Send,
+        {
+        }
+
+        test::<UserRingBuffer>();
+    }
+}
@@ -1,6 +1,8 @@
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::fs;
+use std::io;
+use std::mem::size_of_val;
 use std::mem::transmute;
 use std::ops::Deref;
 use std::os::fd::AsRawFd;
@@ -12,6 +14,7 @@ use std::sync::OnceLock;
 
 use crate::error::IntoError;
 use crate::Error;
+use crate::ErrorExt as _;
 use crate::Result;
 
 pub fn str_to_cstring(s: &str) -> Result<CString> {
@@ -65,6 +68,74 @@ pub fn num_possible_cpus() -> Result<usize> {
     parse_ret(ret).map(|()| ret as usize)
 }
 
+/// Get the IDs of the CPUs that are currently online, in ascending
+/// order.
+pub fn online_cpus() -> Result<Vec<usize>> {
+    let path = "/sys/devices/system/cpu/online";
+    let online = fs::read_to_string(path).with_context(|| format!("failed to read `{path}`"))?;
+    parse_cpu_list(&online).with_context(|| format!("failed to parse `{path}`"))
+}
+
+/// Get the ID of the NUMA node the CPU with the given ID belongs to.
+///
+/// Systems without NUMA support report all CPUs as belonging to node
+/// `0`. The result can be used for allocating maps close to the CPUs
+/// accessing them (see
+/// [`OpenMapMut::set_numa_node`][crate::OpenMapMut::set_numa_node]).
+pub fn cpu_numa_node(cpu: usize) -> Result<u32> {
+    let path = format!("/sys/devices/system/cpu/cpu{cpu}");
+    let entries = fs::read_dir(&path).with_context(|| format!("failed to read `{path}`"))?;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read `{path}`"))?;
+        let name = entry.file_name();
+        let node = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("node"))
+            .and_then(|node| node.parse::<u32>().ok());
+        if let Some(node) = node {
+            return Ok(node)
+        }
+    }
+    Ok(0)
+}
+
+/// Parse a CPU list such as `0-3,5,7-8`.
+fn parse_cpu_list(list: &str) -> Result<Vec<usize>> {
+    let parse = |cpu: &str| {
+        cpu.parse::<usize>()
+            .map_err(|_| Error::with_invalid_data(format!("invalid CPU ID `{cpu}`")))
+    };
+
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => cpus.extend(parse(start)?..=parse(end)?),
+            None => cpus.push(parse(range)?),
+        }
+    }
+    Ok(cpus)
+}
+
+/// Split a `<module>:<symbol>` kernel symbol reference into its module
+/// and symbol name parts. Plain symbol names yield `None`.
+pub fn split_module_symbol(target: &str) -> Option<(&str, &str)> {
+    target
+        .split_once(':')
+        .filter(|(module, symbol)| !module.is_empty() && !symbol.is_empty())
+}
+
+/// Check whether the loadable kernel module with the given name is
+/// currently loaded (and done initializing).
+///
+/// Dashes and underscores in module names are interchangeable, as with
+/// `modprobe`. Modules built into the kernel are not reported as loaded,
+/// as their symbols are not module scoped.
+pub fn kernel_module_loaded(module: &str) -> bool {
+    let module = module.replace('-', "_");
+    fs::read_to_string(format!("/sys/module/{module}/initstate"))
+        .is_ok_and(|state| state.trim() == "live")
+}
+
 pub fn parse_ret(ret: i32) -> Result<()> {
     if ret < 0 {
         // Error code is returned negative, flip to positive to match errno
@@ -78,6 +149,29 @@ pub fn parse_ret_i32(ret: i32) -> Result<i32> {
     parse_ret(ret).map(|()| ret)
 }
 
+/// Set the socket option `option` at `level` of `socket` to `value`.
+pub fn setsockopt<T>(
+    socket: BorrowedFd<'_>,
+    level: libc::c_int,
+    option: libc::c_int,
+    value: &T,
+) -> Result<()> {
+    // SAFETY: `value` is a valid reference and we pass along its size.
+    let rc = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            option,
+            value as *const T as *const libc::c_void,
+            size_of_val(value) as libc::socklen_t,
+        )
+    };
+    if rc != 0 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
 
 /// Check the returned pointer of a `libbpf` call, extracting any
 /// reported errors and converting them.
@@ -187,6 +281,43 @@ mod tests {
         assert!(num > 0);
     }
 
+    /// Check that we can parse CPU lists and retrieve the online CPUs.
+    #[test]
+    fn cpu_list_parsing() {
+        assert_eq!(parse_cpu_list("0\n").unwrap(), vec![0]);
+        assert_eq!(
+            parse_cpu_list("0-3,5,7-8\n").unwrap(),
+            vec![0, 1, 2, 3, 5, 7, 8]
+        );
+        assert_eq!(parse_cpu_list("").unwrap(), Vec::<usize>::new());
+        assert!(parse_cpu_list("0-x").is_err());
+
+        let online = online_cpus().unwrap();
+        assert!(!online.is_empty());
+        assert!(online.len() <= num_possible_cpus().unwrap());
+    }
+
+    /// Check that we can retrieve the NUMA node of online CPUs.
+    #[test]
+    fn cpu_numa_node_retrieval() {
+        let cpu = online_cpus().unwrap()[0];
+        let _node = cpu_numa_node(cpu).unwrap();
+        assert!(cpu_numa_node(usize::MAX).is_err());
+    }
+
+    /// Check that module scoped symbol references are split correctly.
+    #[test]
+    fn module_symbol_splitting() {
+        assert_eq!(
+            split_module_symbol("nf_conntrack:nf_ct_delete"),
+            Some(("nf_conntrack", "nf_ct_delete"))
+        );
+        assert_eq!(split_module_symbol("do_unlinkat"), None);
+        assert_eq!(split_module_symbol(":do_unlinkat"), None);
+        assert_eq!(split_module_symbol("nf_conntrack:"), None);
+        assert!(!kernel_module_loaded("this_module_does_not_exist"));
+    }
+
     /// Check that we can convert a `[c_char]` into a `CStr`.
     #[test]
     fn c_char_slice_conversion() {
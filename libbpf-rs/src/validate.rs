@@ -0,0 +1,537 @@
+//! Validation of untrusted BPF object files.
+//!
+//! libbpf parses object files under the assumption that they were produced
+//! by a well behaved compiler. Services accepting objects from untrusted
+//! sources can use [`validate_object`] (or
+//! [`ObjectBuilder::validate`][crate::ObjectBuilder::validate]) to reject
+//! malformed data with a precise error before it ever reaches libbpf.
+
+use std::mem::size_of;
+
+use crate::symbolize::elf::range;
+use crate::symbolize::elf::read;
+use crate::symbolize::elf::read_str;
+use crate::symbolize::elf::Elf64Ehdr;
+use crate::symbolize::elf::Elf64Rel;
+use crate::symbolize::elf::Elf64Shdr;
+use crate::symbolize::elf::Elf64Sym;
+use crate::symbolize::elf::ELFCLASS64;
+use crate::symbolize::elf::ELFDATA2LSB;
+use crate::symbolize::elf::ELFDATA2MSB;
+use crate::symbolize::elf::ELFMAG;
+use crate::symbolize::elf::SHT_SYMTAB;
+use crate::Error;
+use crate::Result;
+
+
+const EV_CURRENT: u32 = 1;
+const ET_REL: u16 = 1;
+const EM_NONE: u16 = 0;
+const EM_BPF: u16 = 247;
+
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+
+const SHN_LORESERVE: u16 = 0xff00;
+const SHN_XINDEX: u16 = 0xffff;
+
+
+/// Limits imposed on object files by [`validate_object`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectLimits {
+    /// The maximum size of the object file, in bytes.
+    pub max_size: usize,
+    /// The maximum number of sections.
+    pub max_sections: usize,
+    /// The maximum number of symbols in the symbol table.
+    pub max_symbols: usize,
+    /// The maximum number of relocations, across all relocation sections.
+    pub max_relocations: usize,
+    /// The struct is non-exhaustive and open to extension.
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl Default for ObjectLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 64 << 20,
+            max_sections: 4096,
+            max_symbols: 1 << 16,
+            max_relocations: 1 << 20,
+            _non_exhaustive: (),
+        }
+    }
+}
+
+
+fn invalid<T>(msg: impl ToString) -> Result<T> {
+    Err(Error::with_invalid_data(msg))
+}
+
+/// Retrieve the section header at `idx`.
+fn shdr(data: &[u8], ehdr: &Elf64Ehdr, idx: u64) -> Result<Elf64Shdr> {
+    let offset = idx
+        .checked_mul(u64::from(ehdr.e_shentsize))
+        .and_then(|offset| offset.checked_add(ehdr.e_shoff));
+    match offset {
+        Some(offset) => read::<Elf64Shdr>(data, offset),
+        None => invalid(format!("section header {idx} is out of bounds")),
+    }
+}
+
+/// Validate the entries of the symbol table in `symtab`, returning the
+/// number of symbols.
+fn validate_symbols(
+    data: &[u8],
+    shdrs: &[Elf64Shdr],
+    symtab: &Elf64Shdr,
+    limits: &ObjectLimits,
+) -> Result<usize> {
+    if symtab.sh_entsize != size_of::<Elf64Sym>() as u64 {
+        return invalid(format!(
+            "symbol table entry size {} is invalid",
+            symtab.sh_entsize
+        ))
+    }
+    let syms = &data[range(data, symtab.sh_offset, symtab.sh_size)?];
+    if syms.len() % size_of::<Elf64Sym>() != 0 {
+        return invalid("symbol table size is not a multiple of the entry size")
+    }
+    let count = syms.len() / size_of::<Elf64Sym>();
+    if count > limits.max_symbols {
+        return invalid(format!(
+            "object has {count} symbols, exceeding the limit of {}",
+            limits.max_symbols
+        ))
+    }
+
+    let strtab = shdrs
+        .get(symtab.sh_link as usize)
+        .filter(|shdr| shdr.sh_type == SHT_STRTAB)
+        .ok_or_else(|| Error::with_invalid_data("symbol table does not link a string table"))?;
+    let strtab = &data[range(data, strtab.sh_offset, strtab.sh_size)?];
+
+    for idx in 0..count {
+        let sym = read::<Elf64Sym>(syms, (idx * size_of::<Elf64Sym>()) as u64)?;
+        if sym.st_name != 0 && read_str(strtab, sym.st_name).is_none() {
+            return invalid(format!("symbol {idx} has an invalid name"))
+        }
+        let shndx = sym.st_shndx;
+        if shndx < SHN_LORESERVE && usize::from(shndx) >= shdrs.len() {
+            return invalid(format!(
+                "symbol {idx} refers to non-existent section {shndx}"
+            ))
+        }
+    }
+    Ok(count)
+}
+
+/// Validate the relocation section `rel`, returning the number of
+/// relocations in it.
+fn validate_relocations(
+    data: &[u8],
+    shdrs: &[Elf64Shdr],
+    symtab_idx: usize,
+    num_syms: usize,
+    (idx, rel): (usize, &Elf64Shdr),
+) -> Result<usize> {
+    if rel.sh_link as usize != symtab_idx {
+        return invalid(format!(
+            "relocation section {idx} does not refer to the symbol table"
+        ))
+    }
+    if rel.sh_info as usize >= shdrs.len() {
+        return invalid(format!(
+            "relocation section {idx} applies to non-existent section {}",
+            rel.sh_info
+        ))
+    }
+    if rel.sh_entsize != size_of::<Elf64Rel>() as u64 {
+        return invalid(format!(
+            "relocation section {idx} has invalid entry size {}",
+            rel.sh_entsize
+        ))
+    }
+    let rels = &data[range(data, rel.sh_offset, rel.sh_size)?];
+    if rels.len() % size_of::<Elf64Rel>() != 0 {
+        return invalid(format!(
+            "relocation section {idx} size is not a multiple of the entry size"
+        ))
+    }
+
+    let count = rels.len() / size_of::<Elf64Rel>();
+    for entry in 0..count {
+        let reloc = read::<Elf64Rel>(rels, (entry * size_of::<Elf64Rel>()) as u64)?;
+        let sym = reloc.r_info >> 32;
+        if sym >= num_syms as u64 {
+            return invalid(format!(
+                "relocation {entry} of section {idx} refers to non-existent symbol {sym}"
+            ))
+        }
+    }
+    Ok(count)
+}
+
+/// Check that `data` is a well-formed BPF object file within the
+/// provided `limits`.
+///
+/// The check covers the ELF header, the bounds and links of all sections,
+/// the section names, the symbol table, and relocations. It is meant to
+/// reject malformed input before handing it to libbpf; passing it does
+/// not imply that libbpf will be able to open the object, let alone that
+/// the kernel will accept its programs.
+pub fn validate_object(data: &[u8], limits: &ObjectLimits) -> Result<()> {
+    if data.len() > limits.max_size {
+        return invalid(format!(
+            "object of {} bytes exceeds the size limit of {} bytes",
+            data.len(),
+            limits.max_size
+        ))
+    }
+
+    let ehdr =
+        read::<Elf64Ehdr>(data, 0).map_err(|_| Error::with_invalid_data("truncated ELF header"))?;
+    if !ehdr.e_ident.starts_with(ELFMAG) {
+        return invalid("not an ELF file")
+    }
+    if ehdr.e_ident[4] != ELFCLASS64 {
+        return invalid("only 64 bit ELF files are supported")
+    }
+    let native = if cfg!(target_endian = "little") {
+        ELFDATA2LSB
+    } else {
+        ELFDATA2MSB
+    };
+    if ehdr.e_ident[5] != native {
+        return invalid("ELF files of foreign endianness are not supported")
+    }
+    if ehdr.e_version != EV_CURRENT {
+        return invalid(format!("unsupported ELF version {}", ehdr.e_version))
+    }
+    if ehdr.e_type != ET_REL {
+        return invalid(format!(
+            "ELF file is of type {}, not a relocatable object",
+            ehdr.e_type
+        ))
+    }
+    if ehdr.e_machine != EM_BPF && ehdr.e_machine != EM_NONE {
+        return invalid(format!(
+            "ELF file is for machine {}, not BPF",
+            ehdr.e_machine
+        ))
+    }
+    if usize::from(ehdr.e_shentsize) != size_of::<Elf64Shdr>() {
+        return invalid(format!(
+            "section header size {} is invalid",
+            ehdr.e_shentsize
+        ))
+    }
+    if ehdr.e_shoff == 0 {
+        return invalid("ELF file has no section headers")
+    }
+
+    // With more than `SHN_LORESERVE` sections, the section count and the
+    // index of the section name table are stored in the first section
+    // header instead.
+    let first = shdr(data, &ehdr, 0)?;
+    let num_sections = match ehdr.e_shnum {
+        0 => first.sh_size,
+        num => u64::from(num),
+    };
+    if num_sections > limits.max_sections as u64 {
+        return invalid(format!(
+            "object has {num_sections} sections, exceeding the limit of {}",
+            limits.max_sections
+        ))
+    }
+    let shdrs = (0..num_sections)
+        .map(|idx| shdr(data, &ehdr, idx))
+        .collect::<Result<Vec<_>>>()?;
+
+    let shstrndx = match ehdr.e_shstrndx {
+        SHN_XINDEX => first.sh_link as usize,
+        idx => usize::from(idx),
+    };
+    let shstrtab = shdrs
+        .get(shstrndx)
+        .filter(|shdr| shdr.sh_type == SHT_STRTAB)
+        .ok_or_else(|| Error::with_invalid_data("section name table is missing"))?;
+    let shstrtab = &data[range(data, shstrtab.sh_offset, shstrtab.sh_size)?];
+
+    let mut symtab = None;
+    for (idx, shdr) in shdrs.iter().enumerate().skip(1) {
+        let name = read_str(shstrtab, shdr.sh_name).ok_or_else(|| {
+            Error::with_invalid_data(format!("section {idx} has an invalid name"))
+        })?;
+        if shdr.sh_type != SHT_NOBITS {
+            let _range = range(data, shdr.sh_offset, shdr.sh_size).map_err(|_| {
+                Error::with_invalid_data(format!(
+                    "data of section {idx} (`{name}`) is out of bounds"
+                ))
+            })?;
+        }
+        if shdr.sh_type == SHT_SYMTAB {
+            if symtab.is_some() {
+                return invalid("ELF file has multiple symbol tables")
+            }
+            symtab = Some(idx);
+        }
+    }
+
+    let symtab_idx =
+        symtab.ok_or_else(|| Error::with_invalid_data("ELF file has no symbol table"))?;
+    let num_syms = validate_symbols(data, &shdrs, &shdrs[symtab_idx], limits)?;
+
+    let mut num_relocs = 0usize;
+    for (idx, shdr) in shdrs.iter().enumerate() {
+        if shdr.sh_type == SHT_REL {
+            num_relocs += validate_relocations(data, &shdrs, symtab_idx, num_syms, (idx, shdr))?;
+            if num_relocs > limits.max_relocations {
+                return invalid(format!(
+                    "object has more than {} relocations",
+                    limits.max_relocations
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::slice;
+
+
+    /// Append the raw bytes of `value` to `data`.
+    fn push<T: Copy>(data: &mut Vec<u8>, value: T) {
+        // SAFETY: `T` is one of the plain `repr(C)` ELF structures.
+        let bytes =
+            unsafe { slice::from_raw_parts((&value as *const T).cast::<u8>(), size_of::<T>()) };
+        data.extend_from_slice(bytes)
+    }
+
+    fn section(
+        name: u32,
+        ty: u32,
+        offset: usize,
+        size: usize,
+        link: u32,
+        entsize: usize,
+    ) -> Elf64Shdr {
+        Elf64Shdr {
+            sh_name: name,
+            sh_type: ty,
+            sh_flags: 0,
+            sh_addr: 0,
+            sh_offset: offset as u64,
+            sh_size: size as u64,
+            sh_link: link,
+            sh_info: 0,
+            sh_addralign: 1,
+            sh_entsize: entsize as u64,
+        }
+    }
+
+    /// Build a minimal BPF object file with a program section, a symbol
+    /// table, and a relocation section.
+    fn minimal_object() -> Vec<u8> {
+        let shstrtab = b"\0.shstrtab\0.strtab\0.symtab\0xdp\0.relxdp\0";
+        let strtab = b"\0prog\0";
+        let prog = [0u8; 16];
+
+        let mut body = Vec::new();
+        let shstrtab_off = size_of::<Elf64Ehdr>();
+        body.extend_from_slice(shstrtab);
+        let strtab_off = shstrtab_off + body.len();
+        body.extend_from_slice(strtab);
+        let prog_off = shstrtab_off + body.len();
+        body.extend_from_slice(&prog);
+        let symtab_off = shstrtab_off + body.len();
+        for sym in [
+            Elf64Sym {
+                st_name: 0,
+                st_info: 0,
+                st_other: 0,
+                st_shndx: 0,
+                st_value: 0,
+                st_size: 0,
+            },
+            Elf64Sym {
+                st_name: 1,
+                st_info: 0x12,
+                st_other: 0,
+                st_shndx: 4,
+                st_value: 0,
+                st_size: prog.len() as u64,
+            },
+        ] {
+            push(&mut body, sym);
+        }
+        let rel_off = shstrtab_off + body.len();
+        push(
+            &mut body,
+            Elf64Rel {
+                r_offset: 0,
+                r_info: (1 << 32) | 1,
+            },
+        );
+        let shoff = shstrtab_off + body.len();
+
+        let mut rel = section(
+            31,
+            SHT_REL,
+            rel_off,
+            size_of::<Elf64Rel>(),
+            3,
+            size_of::<Elf64Rel>(),
+        );
+        rel.sh_info = 4;
+        let shdrs = [
+            section(0, 0, 0, 0, 0, 0),
+            section(1, SHT_STRTAB, shstrtab_off, shstrtab.len(), 0, 0),
+            section(11, SHT_STRTAB, strtab_off, strtab.len(), 0, 0),
+            section(
+                19,
+                SHT_SYMTAB,
+                symtab_off,
+                2 * size_of::<Elf64Sym>(),
+                2,
+                size_of::<Elf64Sym>(),
+            ),
+            section(27, 1, prog_off, prog.len(), 0, 0),
+            rel,
+        ];
+
+        let mut e_ident = [0; 16];
+        e_ident[..4].copy_from_slice(ELFMAG);
+        e_ident[4] = ELFCLASS64;
+        e_ident[5] = if cfg!(target_endian = "little") {
+            ELFDATA2LSB
+        } else {
+            ELFDATA2MSB
+        };
+        e_ident[6] = EV_CURRENT as u8;
+        let ehdr = Elf64Ehdr {
+            e_ident,
+            e_type: ET_REL,
+            e_machine: EM_BPF,
+            e_version: EV_CURRENT,
+            e_entry: 0,
+            e_phoff: 0,
+            e_shoff: shoff as u64,
+            e_flags: 0,
+            e_ehsize: size_of::<Elf64Ehdr>() as u16,
+            e_phentsize: 0,
+            e_phnum: 0,
+            e_shentsize: size_of::<Elf64Shdr>() as u16,
+            e_shnum: shdrs.len() as u16,
+            e_shstrndx: 1,
+        };
+
+        let mut data = Vec::new();
+        push(&mut data, ehdr);
+        data.extend_from_slice(&body);
+        for shdr in shdrs {
+            push(&mut data, shdr);
+        }
+        data
+    }
+
+    fn validate(data: &[u8]) -> Result<()> {
+        validate_object(data, &ObjectLimits::default())
+    }
+
+    /// Check that a well-formed object passes validation.
+    #[test]
+    fn valid_object() {
+        let () = validate(&minimal_object()).unwrap();
+    }
+
+    /// Check that malformed objects are rejected with a precise error.
+    #[test]
+    fn invalid_objects() {
+        let data = minimal_object();
+        let shoff = data.len() - 6 * size_of::<Elf64Shdr>();
+        let shdr_field = |idx: usize, offset: usize| shoff + idx * size_of::<Elf64Shdr>() + offset;
+
+        let check = |data: &[u8], expected: &str| {
+            let err = validate(data).unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        };
+
+        check(&[], "truncated ELF header");
+        check(&data[..shoff], "out of bounds");
+
+        let mut corrupted = data.clone();
+        corrupted[0] = 0;
+        check(&corrupted, "not an ELF file");
+
+        // Point the program section's data past the end of the file.
+        let mut corrupted = data.clone();
+        corrupted[shdr_field(4, 24)..shdr_field(4, 32)]
+            .copy_from_slice(&(data.len() as u64).to_ne_bytes());
+        check(&corrupted, "data of section 4 (`xdp`) is out of bounds");
+
+        // Point the program symbol at a non-existent section.
+        let mut corrupted = data.clone();
+        let sym_off = u64::from_ne_bytes(
+            data[shdr_field(3, 24)..shdr_field(3, 32)]
+                .try_into()
+                .unwrap(),
+        );
+        let shndx = sym_off as usize + size_of::<Elf64Sym>() + 6;
+        corrupted[shndx..shndx + 2].copy_from_slice(&42u16.to_ne_bytes());
+        check(&corrupted, "symbol 1 refers to non-existent section 42");
+
+        // Make the relocation refer to a non-existent symbol.
+        let mut corrupted = data.clone();
+        let rel_off = u64::from_ne_bytes(
+            data[shdr_field(5, 24)..shdr_field(5, 32)]
+                .try_into()
+                .unwrap(),
+        );
+        let info = rel_off as usize + 8;
+        corrupted[info..info + 8].copy_from_slice(&(7u64 << 32).to_ne_bytes());
+        check(&corrupted, "refers to non-existent symbol 7");
+
+        let limits = ObjectLimits {
+            max_sections: 4,
+            ..Default::default()
+        };
+        let err = validate_object(&data, &limits).unwrap_err();
+        assert!(
+            err.to_string().contains("exceeding the limit of 4"),
+            "{err}"
+        );
+    }
+
+    /// Check that arbitrarily corrupted objects never cause a panic.
+    #[test]
+    fn corruption_robustness() {
+        let data = minimal_object();
+        // A simple xorshift generator, to keep the test deterministic.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..10_000 {
+            let mut corrupted = data.clone();
+            for _ in 0..1 + next() % 4 {
+                let idx = next() as usize % corrupted.len();
+                corrupted[idx] = next() as u8;
+            }
+            let len =
+                corrupted.len() - (next() % 8 == 0) as usize * (next() as usize % corrupted.len());
+            let _result = validate(&corrupted[..len]);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::io;
+use std::os::fd::AsFd as _;
+use std::os::fd::BorrowedFd;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapCore;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapType;
+use crate::Result;
+use crate::RingBuffer;
+use crate::RingBufferBuilder;
+
+
+/// An update of a watched map's entry, as reported by [`MapWatcher`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapUpdate {
+    /// The key of the entry that changed.
+    pub key: Vec<u8>,
+    /// The entry's new value, or `None` if it was deleted.
+    pub value: Option<Vec<u8>>,
+}
+
+
+/// Watch a BPF map for changes made by the BPF side.
+///
+/// The kernel does not provide notifications about map updates. Instead,
+/// a `MapWatcher` pairs a data map with a companion
+/// [`RingBuf`][MapType::RingBuf] map acting as a change log: whenever the
+/// BPF program changes an entry of the data map, it submits a record to
+/// the change log. A record contains either just the key of the changed
+/// entry, in which case the current value is looked up in the data map,
+/// or the key directly followed by the new value.
+///
+/// ```c
+/// if (!bpf_map_update_elem(&data, &key, &value, BPF_ANY))
+///     bpf_ringbuf_output(&changes, &key, sizeof(key), 0);
+/// ```
+///
+/// Looking up the value lazily means that the reported value may be
+/// more recent than the change that triggered the record. An entry not
+/// found during lookup is reported as deleted.
+///
+/// Per-CPU data maps are not supported.
+#[derive(Debug)]
+pub struct MapWatcher {
+    data: MapHandle,
+    ringbuf: RingBuffer<'static>,
+    records: Arc<Mutex<VecDeque<Vec<u8>>>>,
+}
+
+impl MapWatcher {
+    /// Create a new `MapWatcher` watching `data` for changes reported via
+    /// the `change_log` ring buffer map.
+    pub fn new<D, L>(data: &D, change_log: &L) -> Result<Self>
+    where
+        D: MapCore,
+        L: MapCore,
+    {
+        if change_log.map_type() != MapType::RingBuf {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "change log map `{}` is not a ring buffer",
+                    change_log.name().to_string_lossy()
+                ),
+            ))
+        }
+        if data.map_type().is_percpu() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                "per-CPU maps cannot be watched",
+            ))
+        }
+
+        let fd = data
+            .as_fd()
+            .try_clone_to_owned()
+            .context("failed to duplicate map file descriptor")?;
+        let data = MapHandle::from_fd(fd)?;
+
+        // The ring buffer keeps its own reference to the change log map,
+        // so there is no need for us to hold on to it.
+        let records = Arc::new(Mutex::new(VecDeque::new()));
+        let mut builder = RingBufferBuilder::new();
+        let _builder = builder.add(change_log, {
+            let records = Arc::clone(&records);
+            move |record: &[u8]| {
+                let () = records.lock().unwrap().push_back(record.to_vec());
+                0
+            }
+        })?;
+        let ringbuf = builder.build()?;
+
+        let slf = Self {
+            data,
+            ringbuf,
+            records,
+        };
+        Ok(slf)
+    }
+
+    /// Convert a change log record into a [`MapUpdate`].
+    fn update(&self, record: Vec<u8>) -> Result<MapUpdate> {
+        let key_size = self.data.key_size() as usize;
+        let value_size = self.data.value_size() as usize;
+
+        let update = if record.len() == key_size {
+            let value = self
+                .data
+                .lookup(&record, MapFlags::ANY)
+                .context("failed to look up changed map entry")?;
+            MapUpdate { key: record, value }
+        } else if record.len() == key_size + value_size {
+            let mut key = record;
+            let value = key.split_off(key_size);
+            MapUpdate {
+                key,
+                value: Some(value),
+            }
+        } else {
+            return Err(Error::with_invalid_data(format!(
+                "change log record has unexpected size of {} bytes",
+                record.len()
+            )))
+        };
+        Ok(update)
+    }
+
+    /// Wait up to `timeout` for changes and return all updates that
+    /// have been reported.
+    ///
+    /// If `timeout` is [`Duration::MAX`], this blocks until at least one
+    /// change was reported. The returned updates are in the order in
+    /// which the BPF side submitted the corresponding records.
+    pub fn poll(&mut self, timeout: Duration) -> Result<Vec<MapUpdate>> {
+        let () = self
+            .ringbuf
+            .poll(timeout)
+            .context("failed to poll map change log")?;
+        let records = self.records.lock().unwrap().drain(..).collect::<Vec<_>>();
+        records
+            .into_iter()
+            .map(|record| self.update(record))
+            .collect()
+    }
+
+    /// Retrieve a file descriptor that becomes readable once changes
+    /// are available, for integration with an event loop.
+    pub fn epoll_fd(&self) -> BorrowedFd<'_> {
+        self.ringbuf.epoll_fd()
+    }
+}
+
+/// Iterating over a `MapWatcher` blocks until the next update is
+/// available.
+impl Iterator for MapWatcher {
+    type Item = Result<MapUpdate>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = self.records.lock().unwrap().pop_front();
+            if let Some(record) = record {
+                return Some(self.update(record))
+            }
+
+            if let Err(err) = self.ringbuf.poll(Duration::MAX) {
+                return Some(Err(err.context("failed to poll map change log")))
+            }
+        }
+    }
+}
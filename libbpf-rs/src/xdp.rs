@@ -30,6 +30,44 @@ bitflags! {
 
 }
 
+bitflags! {
+    /// The XDP features supported by a network device, as reported by
+    /// the kernel's `netdev` netlink family (`NETDEV_XDP_ACT_*`).
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+    pub struct XdpFeatures: u64 {
+        /// The basic `XDP_ABORTED`, `XDP_DROP`, `XDP_PASS`, and `XDP_TX`
+        /// actions are supported.
+        const BASIC        = 1 << 0;
+        /// `XDP_REDIRECT` is supported.
+        const REDIRECT     = 1 << 1;
+        /// The device can be the target of `XDP_REDIRECT`.
+        const NDO_XMIT     = 1 << 2;
+        /// AF_XDP sockets can be bound to the device in zero-copy mode.
+        const XSK_ZEROCOPY = 1 << 3;
+        /// Programs can be offloaded to the device (`XDP_FLAGS_HW_MODE`).
+        const HW_OFFLOAD   = 1 << 4;
+        /// Multi-buffer ("frags") programs are supported in driver mode.
+        const RX_SG        = 1 << 5;
+        /// Multi-buffer frames can be redirected to the device.
+        const NDO_XMIT_SG  = 1 << 6;
+    }
+}
+
+/// The XDP capabilities of a network device, as reported by
+/// [`Xdp::features`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XdpDeviceFeatures {
+    /// The supported features.
+    pub features: XdpFeatures,
+    /// The maximum number of fragments supported for AF_XDP zero-copy
+    /// transmission. Only meaningful if [`XdpFeatures::XSK_ZEROCOPY`] is
+    /// supported.
+    pub zc_max_segs: u32,
+    /// The struct is non-exhaustive and open to extension.
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
 /// Represents a XDP program.
 ///
 /// This struct exposes operations to attach, detach and query a XDP program
@@ -93,6 +131,29 @@ impl<'fd> Xdp<'fd> {
         util::parse_ret(err).map(|()| prog_id)
     }
 
+    /// Query the XDP features supported by the network device with index
+    /// `ifindex`.
+    ///
+    /// This allows for picking an attach mode up front, instead of
+    /// attempting attachment and checking for failure. Kernels without
+    /// support for reporting features (i.e., before 6.3) report an empty
+    /// feature set.
+    pub fn features(ifindex: i32) -> Result<XdpDeviceFeatures> {
+        let mut opts = libbpf_sys::bpf_xdp_query_opts {
+            sz: size_of::<libbpf_sys::bpf_xdp_query_opts>() as libbpf_sys::size_t,
+            ..Default::default()
+        };
+        let err = unsafe { libbpf_sys::bpf_xdp_query(ifindex, 0, &mut opts) };
+        let () = util::parse_ret(err)?;
+
+        let features = XdpDeviceFeatures {
+            features: XdpFeatures::from_bits_retain(opts.feature_flags),
+            zc_max_segs: opts.xdp_zc_max_segs,
+            _non_exhaustive: (),
+        };
+        Ok(features)
+    }
+
     /// Replace an existing xdp program (identified by old_prog_fd) with this xdp program
     pub fn replace(&self, ifindex: i32, old_prog_fd: BorrowedFd<'_>) -> Result<()> {
         let mut opts = self.attach_opts;
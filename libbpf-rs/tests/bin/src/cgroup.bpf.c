@@ -0,0 +1,9 @@
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+SEC("cgroup_skb/egress")
+int cgroup_skb_egress(struct __sk_buff *skb) {
+    return 1;
+}
+
+char _license[] SEC("license") = "GPL";
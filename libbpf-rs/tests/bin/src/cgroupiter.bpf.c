@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+SEC("iter/cgroup")
+int dump_cgroup_id(struct bpf_iter__cgroup *ctx)
+{
+    struct seq_file *seq = ctx->meta->seq;
+    struct cgroup *cgrp = ctx->cgroup;
+    u64 id;
+
+    if (!cgrp)
+        return 0;
+
+    id = cgrp->kn->id;
+    bpf_seq_write(seq, &id, sizeof(id));
+    return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
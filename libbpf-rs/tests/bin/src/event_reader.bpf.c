@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_core_read.h>
+
+// Set by user space to exercise the perf buffer fallback on kernels that
+// support ring buffers.
+const volatile bool use_perfbuf = false;
+
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    __uint(max_entries, 4096 /* one page */);
+} events SEC(".maps");
+
+SEC("tp/syscalls/sys_enter_getpid")
+int handle__sys_enter_getpid(void *ctx)
+{
+    int value = 42;
+
+    if (bpf_core_enum_value_exists(enum bpf_func_id, BPF_FUNC_ringbuf_output) && !use_perfbuf)
+        bpf_ringbuf_output(&events, &value, sizeof(value), 0);
+    else
+        bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &value, sizeof(value));
+
+    return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+/* A global function, verified independently of its callers and, hence,
+ * replaceable by an extension program.
+ */
+__noinline int xdp_verdict(struct xdp_md *ctx)
+{
+    return XDP_PASS;
+}
+
+SEC("xdp")
+int xdp_target(struct xdp_md *ctx)
+{
+    return xdp_verdict(ctx);
+}
+
+SEC("freplace")
+int xdp_drop_all(struct xdp_md *ctx)
+{
+    return XDP_DROP;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_endian.h>
+
+#define ETH_P_IP 0x0800
+#define TC_ACT_OK 0
+#define TC_ACT_SHOT 2
+#define DISCARD_PORT 9
+
+/* The number of dropped datagrams, keyed by destination port. */
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 16);
+    __type(key, __u32);
+    __type(value, __u64);
+} drops SEC(".maps");
+
+/* Account a dropped datagram to a destination port. */
+static __always_inline void count_drop(__u32 port)
+{
+    __u64 one = 1, *count;
+
+    count = bpf_map_lookup_elem(&drops, &port);
+    if (count)
+        __sync_fetch_and_add(count, 1);
+    else
+        bpf_map_update_elem(&drops, &port, &one, BPF_NOEXIST);
+}
+
+/* Parse an IPv4 packet, returning its IP header, if any. */
+static __always_inline struct iphdr *parse_ipv4(void *data, void *data_end)
+{
+    struct ethhdr *eth = data;
+    struct iphdr *ip = (void *)(eth + 1);
+
+    if ((void *)(ip + 1) > data_end)
+        return NULL;
+    if (eth->h_proto != bpf_htons(ETH_P_IP) || ip->ihl != 5)
+        return NULL;
+    return ip;
+}
+
+/* Check whether a packet is a UDP datagram to the discard port. */
+static __always_inline bool is_discard(struct iphdr *ip, void *data_end)
+{
+    struct udphdr *udp = (void *)(ip + 1);
+
+    if (ip->protocol != IPPROTO_UDP || (void *)(udp + 1) > data_end)
+        return false;
+    return udp->dest == bpf_htons(DISCARD_PORT);
+}
+
+/* Drop datagrams to the discard port and bounce TCP segments back to the
+ * sender, decrementing their TTL.
+ */
+SEC("xdp")
+int xdp_bounce(struct xdp_md *ctx)
+{
+    void *data_end = (void *)(long)ctx->data_end;
+    void *data = (void *)(long)ctx->data;
+    struct ethhdr *eth = data;
+    struct iphdr *ip = parse_ipv4(data, data_end);
+    unsigned char mac[ETH_ALEN];
+    __u32 csum;
+
+    if (!ip)
+        return XDP_PASS;
+    if (is_discard(ip, data_end)) {
+        count_drop(DISCARD_PORT);
+        return XDP_DROP;
+    }
+    if (ip->protocol != IPPROTO_TCP || ip->ttl <= 1)
+        return XDP_PASS;
+
+    /* Incrementally update the checksum (RFC 1624). */
+    csum = ip->check + bpf_htons(0x0100);
+    ip->check = csum + (csum >> 16);
+    ip->ttl--;
+
+    __builtin_memcpy(mac, eth->h_source, ETH_ALEN);
+    __builtin_memcpy(eth->h_source, eth->h_dest, ETH_ALEN);
+    __builtin_memcpy(eth->h_dest, mac, ETH_ALEN);
+    return XDP_TX;
+}
+
+/* Drop datagrams to the discard port. */
+SEC("tc")
+int tc_discard(struct __sk_buff *skb)
+{
+    void *data_end = (void *)(long)skb->data_end;
+    void *data = (void *)(long)skb->data;
+    struct iphdr *ip = parse_ipv4(data, data_end);
+
+    if (ip && is_discard(ip, data_end))
+        return TC_ACT_SHOT;
+    return TC_ACT_OK;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,25 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, u32);
+    __type(value, u64);
+} samples SEC(".maps");
+
+SEC("perf_event")
+int handle__perf_event(void *ctx)
+{
+    u32 key = 0;
+    u64 *count;
+
+    count = bpf_map_lookup_elem(&samples, &key);
+    if (count)
+        __sync_fetch_and_add(count, 1);
+    return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
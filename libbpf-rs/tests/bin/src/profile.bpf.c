@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+struct profile_key {
+    u32 pid;
+    s32 kernel_stack_id;
+    s32 user_stack_id;
+    char comm[16];
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_STACK_TRACE);
+    __uint(max_entries, 1024);
+    __uint(key_size, sizeof(u32));
+    __uint(value_size, 127 * sizeof(u64));
+} stacks SEC(".maps");
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, 1024);
+    __type(key, struct profile_key);
+    __type(value, u64);
+} counts SEC(".maps");
+
+SEC("perf_event")
+int profile(void *ctx)
+{
+    struct profile_key key = {};
+    u64 one = 1, *count;
+
+    key.pid = bpf_get_current_pid_tgid() >> 32;
+    key.kernel_stack_id = bpf_get_stackid(ctx, &stacks, 0);
+    key.user_stack_id = bpf_get_stackid(ctx, &stacks, BPF_F_USER_STACK);
+    bpf_get_current_comm(&key.comm, sizeof(key.comm));
+
+    count = bpf_map_lookup_elem(&counts, &key);
+    if (count)
+        __sync_fetch_and_add(count, 1);
+    else
+        bpf_map_update_elem(&counts, &key, &one, BPF_NOEXIST);
+    return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
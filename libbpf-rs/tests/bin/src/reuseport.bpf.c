@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+struct {
+    __uint(type, BPF_MAP_TYPE_REUSEPORT_SOCKARRAY);
+    __uint(max_entries, 2);
+    __type(key, u32);
+    __type(value, u64);
+} sockets SEC(".maps");
+
+/* Steer all traffic to the socket at index 1. */
+SEC("sk_reuseport")
+int select_sock(struct sk_reuseport_md *md)
+{
+    u32 key = 1;
+
+    if (bpf_sk_select_reuseport(md, &sockets, &key, 0))
+        return SK_DROP;
+    return SK_PASS;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+/* Drop all packets received on the socket. */
+SEC("socket")
+int drop_all(struct __sk_buff *skb)
+{
+    return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+static inline struct tcp_sock *tcp_sk(const struct sock *sk)
+{
+    return (struct tcp_sock *)sk;
+}
+
+SEC("struct_ops/ca_cong_control")
+void BPF_PROG(ca_cong_control, struct sock *sk, const struct rate_sample *rs)
+{
+}
+
+SEC("struct_ops/ca_ssthresh")
+__u32 BPF_PROG(ca_ssthresh, struct sock *sk)
+{
+    return tcp_sk(sk)->snd_ssthresh;
+}
+
+SEC("struct_ops/ca_undo_cwnd")
+__u32 BPF_PROG(ca_undo_cwnd, struct sock *sk)
+{
+    return tcp_sk(sk)->snd_cwnd;
+}
+
+/* Two implementations registered under the same name, so that one can
+ * replace the other. */
+SEC(".struct_ops.link")
+struct tcp_congestion_ops ca_v1 = {
+    .cong_control = (void *)ca_cong_control,
+    .ssthresh = (void *)ca_ssthresh,
+    .undo_cwnd = (void *)ca_undo_cwnd,
+    .name = "libbpf_rs_ca",
+};
+
+SEC(".struct_ops.link")
+struct tcp_congestion_ops ca_v2 = {
+    .cong_control = (void *)ca_cong_control,
+    .ssthresh = (void *)ca_ssthresh,
+    .undo_cwnd = (void *)ca_undo_cwnd,
+    .name = "libbpf_rs_ca",
+};
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+#define TC_ACT_OK 0
+
+SEC("tc")
+int mark_skb(struct __sk_buff *skb)
+{
+    skb->mark = skb->priority + 1;
+    return TC_ACT_OK;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,19 @@
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, u32);
+    __type(value, u64);
+} values SEC(".maps");
+
+SEC("xdp")
+int xdp_reject(struct xdp_md *ctx) {
+    u32 key = 0;
+    u64 *value = bpf_map_lookup_elem(&values, &key);
+    /* Missing NULL check; rejected by the verifier. */
+    return *value;
+}
+
+char _license[] SEC("license") = "GPL";
@@ -11,40 +11,100 @@ use std::ffi::OsStr;
 use std::fs;
 use std::hint;
 use std::io;
+use std::io::BufRead;
 use std::io::Read;
+use std::mem;
 use std::mem::size_of;
 use std::mem::size_of_val;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::net::SocketAddrV4;
+use std::net::UdpSocket;
+use std::os::unix::fs::MetadataExt as _;
 use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::panic::catch_unwind;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process;
 use std::ptr;
 use std::ptr::addr_of;
 use std::slice;
+use std::sync::atomic::Ordering;
 use std::sync::mpsc::channel;
 use std::time::Duration;
-
+use std::time::Instant;
+
+use libbpf_rs::arena;
+use libbpf_rs::arena::Arena;
+use libbpf_rs::arena::ArenaOpts;
+use libbpf_rs::bump_memlock_rlimit;
+use libbpf_rs::cpu_numa_node;
+use libbpf_rs::insn::InsnBuilder;
+use libbpf_rs::insn::Reg;
+use libbpf_rs::local_storage::pidfd_open;
+use libbpf_rs::local_storage::CgroupStorage;
+use libbpf_rs::local_storage::LocalStorage;
+use libbpf_rs::memlock_accounting;
 use libbpf_rs::num_possible_cpus;
+use libbpf_rs::offload;
+use libbpf_rs::online_cpus;
+use libbpf_rs::perf::PerfEventBuilder;
+use libbpf_rs::perf::PerfEventGroup;
+use libbpf_rs::perf::SoftwareEvent;
+use libbpf_rs::profiler::Profiler;
+use libbpf_rs::reuseport;
+use libbpf_rs::reuseport::ReuseportSockArray;
+use libbpf_rs::symbolize::StackOrigin;
+use libbpf_rs::symbolize::StackTraceMap;
+use libbpf_rs::symbolize::Symbolizer;
+use libbpf_rs::testing::run_packet;
+use libbpf_rs::testing::MapFixture;
+use libbpf_rs::testing::PacketBuilder;
+use libbpf_rs::validate_object;
 use libbpf_rs::AsRawLibbpf;
+use libbpf_rs::AttachTarget;
+use libbpf_rs::BpfToken;
+use libbpf_rs::CgroupIterOrder;
+use libbpf_rs::ErrorKind;
+use libbpf_rs::EventBackend;
+use libbpf_rs::EventReader;
+use libbpf_rs::EventReaderBuilder;
 use libbpf_rs::Iter;
+use libbpf_rs::IterTarget;
+use libbpf_rs::Link;
+use libbpf_rs::LinkDropPolicy;
 use libbpf_rs::Linker;
+use libbpf_rs::LoadItem;
+use libbpf_rs::LoadOutcome;
+use libbpf_rs::LoadProgress;
 use libbpf_rs::Map;
 use libbpf_rs::MapCore;
 use libbpf_rs::MapFlags;
 use libbpf_rs::MapHandle;
 use libbpf_rs::MapInfo;
+use libbpf_rs::MapSnapshot;
 use libbpf_rs::MapType;
+use libbpf_rs::MapWatcher;
+use libbpf_rs::MemlockAccounting;
 use libbpf_rs::Object;
 use libbpf_rs::ObjectBuilder;
+use libbpf_rs::ObjectLimits;
 use libbpf_rs::Program;
 use libbpf_rs::ProgramInput;
 use libbpf_rs::ProgramType;
+use libbpf_rs::ReadOnlyMap;
 use libbpf_rs::TracepointOpts;
 use libbpf_rs::UprobeOpts;
 use libbpf_rs::UsdtOpts;
 use libbpf_rs::UserRingBuffer;
+use libbpf_rs::WriteOnlyMap;
 use plain::Plain;
 use probe::probe;
 use scopeguard::defer;
+use tempfile::tempdir;
 use tempfile::NamedTempFile;
 use test_tag::tag;
 
@@ -127,6 +187,140 @@ fn test_object_build_from_memory_empty_name() {
     assert!(name.is_empty());
 }
 
+/// Check that validation accepts the objects compiled for the tests and
+/// rejects malformed ones before they reach libbpf.
+#[test]
+fn test_object_validation() {
+    let dir = get_test_object_path("");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).expect("failed to read test object directory") {
+        let path = entry.unwrap().path();
+        if !path.to_string_lossy().ends_with(".bpf.o") {
+            continue
+        }
+        let contents = fs::read(&path).expect("failed to read object file");
+        let () = validate_object(&contents, &ObjectLimits::default())
+            .unwrap_or_else(|err| panic!("`{}` failed validation: {err:#}", path.display()));
+        count += 1;
+    }
+    assert_ne!(count, 0);
+
+    let obj_path = get_test_object_path("runqslower.bpf.o");
+    let mut contents = fs::read(obj_path).expect("failed to read object file");
+    let mut builder = ObjectBuilder::default();
+    let _obj = builder
+        .validate(ObjectLimits::default())
+        .open_memory(&contents)
+        .expect("failed to build object");
+
+    // Point the section headers past the end of the file.
+    let () = contents[0x28..0x30].copy_from_slice(&u64::MAX.to_ne_bytes());
+    let err = builder.open_memory(&contents).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(format!("{err:#}").contains("out of bounds"), "{err:#}");
+
+    let limits = ObjectLimits {
+        max_size: 16,
+        ..Default::default()
+    };
+    let err = builder.validate(limits).open_memory(&contents).unwrap_err();
+    assert!(format!("{err:#}").contains("size limit"), "{err:#}");
+}
+
+/// Check that we can capture the kernel log while loading an object.
+#[tag(root)]
+#[test]
+fn test_object_kernel_log() {
+    bump_rlimit_mlock();
+
+    let obj_path = get_test_object_path("runqslower.bpf.o");
+    let obj = ObjectBuilder::default()
+        .open_file(&obj_path)
+        .expect("failed to open object")
+        .load()
+        .expect("failed to load object");
+    assert!(obj.kernel_log().is_none());
+
+    let obj = ObjectBuilder::default()
+        .kernel_log_level(1)
+        .kernel_log_size(1 << 20)
+        .unwrap()
+        .open_file(&obj_path)
+        .expect("failed to open object")
+        .load()
+        .expect("failed to load object");
+    let log = obj.kernel_log().unwrap();
+    assert!(log.contains("processed"), "{log}");
+}
+
+/// Check that progress is reported for every map and program while
+/// loading an object.
+#[tag(root)]
+#[test]
+fn test_object_load_progress() {
+    bump_rlimit_mlock();
+
+    let open_obj = open_test_object("runqslower.bpf.o");
+    let mut expected = open_obj
+        .maps()
+        .map(|map| (LoadItem::Map, map.name().to_os_string()))
+        .collect::<Vec<_>>();
+    let () = expected.extend(
+        open_obj
+            .progs()
+            .map(|prog| (LoadItem::Program, prog.name().to_os_string())),
+    );
+    assert!(!expected.is_empty());
+
+    let mut started = Vec::new();
+    let mut finished = Vec::new();
+    let _obj = open_obj
+        .load_with_progress(|event| match *event {
+            LoadProgress::Started { item, name } => started.push((item, name.to_os_string())),
+            LoadProgress::Finished {
+                item,
+                name,
+                outcome,
+                ..
+            } => {
+                assert_eq!(outcome, LoadOutcome::Loaded);
+                assert_eq!(started.last(), Some(&(item, name.to_os_string())));
+                let () = finished.push((item, name.to_os_string()));
+            }
+        })
+        .expect("failed to load object");
+
+    assert_eq!(started, expected);
+    assert_eq!(finished, expected);
+}
+
+/// Check that we can load an object with implicit BPF token creation
+/// disabled.
+#[tag(root)]
+#[test]
+fn test_object_load_without_bpf_token() {
+    bump_rlimit_mlock();
+
+    let obj_path = get_test_object_path("runqslower.bpf.o");
+    let mut builder = ObjectBuilder::default();
+    let _obj = builder
+        .bpf_token_path("")
+        .unwrap()
+        .open_file(obj_path)
+        .expect("failed to open object")
+        .load()
+        .expect("failed to load object");
+}
+
+/// Check that creating a BPF token from a path that is not a BPF file
+/// system fails.
+#[test]
+fn test_bpf_token_invalid_path() {
+    let dir = tempdir().unwrap();
+    let _err = BpfToken::from_bpffs_path(dir.path()).unwrap_err();
+    let _err = BpfToken::from_bpffs_path(dir.path().join("does-not-exist")).unwrap_err();
+}
+
 /// Check that loading an object from an empty file fails as expected.
 #[tag(root)]
 #[test]
@@ -159,6 +353,34 @@ fn test_object_maps() {
     assert!(!obj.maps().any(|map| map.name() == OsStr::new("asdf")));
 }
 
+/// Check that looking up maps and programs by name reports the available
+/// names on failure.
+#[tag(root)]
+#[test]
+fn test_object_lookup_by_name() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("runqslower.bpf.o");
+    let map = obj.map("start").expect("failed to find map");
+    assert_eq!(map.name(), "start");
+    let _map = obj.map_mut("events").expect("failed to find map");
+    let prog = obj
+        .prog("handle__sched_wakeup")
+        .expect("failed to find program");
+    assert_eq!(prog.name(), "handle__sched_wakeup");
+
+    let err = obj.map("asdf").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    let msg = err.to_string();
+    assert!(msg.starts_with("map `asdf` not found"), "{msg}");
+    assert!(msg.contains("`start`"), "{msg}");
+    assert!(msg.contains("`events`"), "{msg}");
+
+    let err = obj.prog_mut("asdf").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("`handle__sched_wakeup`"), "{err}");
+}
+
 #[tag(root)]
 #[test]
 fn test_object_maps_iter() {
@@ -186,6 +408,267 @@ fn test_object_map_key_value_size() {
     assert!(start
         .update(&[1, 2, 3, 4, 5], &[1], MapFlags::empty())
         .is_err());
+
+    let err = start.delete(&[1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        err.to_string(),
+        "key for map `start` has size 1, expected 4"
+    );
+    let err = start
+        .update(&[1, 2, 3, 4], &[1], MapFlags::empty())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "value for map `start` has size 1, expected 8"
+    );
+}
+
+/// Check that maps can be opened and created with restricted userspace
+/// access.
+#[tag(root)]
+#[test]
+fn test_map_restricted_access() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Hash, Some("restricted"), 4, 8, 8, &opts)
+        .expect("failed to create map");
+    let id = map.info().unwrap().info.id;
+
+    let writer = WriteOnlyMap::from_map_id(id).expect("failed to open map write-only");
+    let reader = ReadOnlyMap::from_map_id(id).expect("failed to open map read-only");
+    let key = 1u32.to_ne_bytes();
+    let () = writer
+        .update(&key, &42u64.to_ne_bytes(), MapFlags::ANY)
+        .unwrap();
+    assert_eq!(
+        reader.lookup(&key, MapFlags::ANY).unwrap(),
+        Some(42u64.to_ne_bytes().to_vec())
+    );
+    assert_eq!(reader.keys().count(), 1);
+    let () = writer.delete(&key).unwrap();
+    assert_eq!(reader.lookup(&key, MapFlags::ANY).unwrap(), None);
+
+    // The kernel rejects writes through the read-only file descriptor,
+    // which the type system prevents us from attempting.
+    let value = 42u64.to_ne_bytes();
+    let rc = unsafe {
+        libbpf_sys::bpf_map_update_elem(
+            reader.as_fd().as_raw_fd(),
+            key.as_ptr().cast(),
+            value.as_ptr().cast(),
+            libbpf_sys::BPF_ANY.into(),
+        )
+    };
+    assert_eq!(rc, -libc::EPERM);
+
+    let map = ReadOnlyMap::create(MapType::Hash, Some("rdonly"), 4, 8, 8, &opts)
+        .expect("failed to create read-only map");
+    let flags = map.info().unwrap().info.map_flags;
+    assert_ne!(flags & libbpf_sys::BPF_F_RDONLY, 0);
+}
+
+/// Check that we can look up values into caller provided buffers.
+#[tag(root)]
+#[test]
+fn test_map_lookup_into() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Hash, Some("lookup_into"), 4, 8, 8, &opts)
+        .expect("failed to create map");
+    let key = 1u32.to_ne_bytes();
+    let () = map
+        .update(&key, &42u64.to_ne_bytes(), MapFlags::ANY)
+        .unwrap();
+
+    let mut value = [0; 8];
+    assert!(map.lookup_into(&key, &mut value, MapFlags::ANY).unwrap());
+    assert_eq!(u64::from_ne_bytes(value), 42);
+    assert!(!map
+        .lookup_into(&2u32.to_ne_bytes(), &mut value, MapFlags::ANY)
+        .unwrap());
+    assert!(map.lookup_into(&key, &mut [0; 4], MapFlags::ANY).is_err());
+
+    let map = MapHandle::create(MapType::PercpuArray, Some("lookup_pcpu"), 4, 4, 1, &opts)
+        .expect("failed to create map");
+    let ncpu = num_possible_cpus().unwrap();
+    let mut values = vec![0; ncpu * 8];
+    assert!(map
+        .lookup_into(&0u32.to_ne_bytes(), &mut values, MapFlags::ANY)
+        .unwrap());
+    assert!(values.iter().all(|byte| *byte == 0));
+
+    // Forgetting to pad each value to eight bytes is called out.
+    let mut values = vec![0; ncpu * 4];
+    let err = map
+        .lookup_into(&0u32.to_ne_bytes(), &mut values, MapFlags::ANY)
+        .unwrap_err();
+    assert!(err.to_string().contains("padded to 8 bytes"), "{err}");
+}
+
+/// Check that we can iterate over the keys and entries of a large map.
+#[tag(root)]
+#[test]
+fn test_map_iter_large() {
+    bump_rlimit_mlock();
+
+    const COUNT: u32 = 10_000;
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Hash, Some("large_map"), 4, 8, 2 * COUNT, &opts)
+        .expect("failed to create map");
+    let keys = (0..COUNT).flat_map(u32::to_ne_bytes).collect::<Vec<_>>();
+    let values = (0..COUNT)
+        .flat_map(|key| (u64::from(key) * 2).to_ne_bytes())
+        .collect::<Vec<_>>();
+    let () = map
+        .update_batch(&keys, &values, COUNT, MapFlags::ANY, MapFlags::ANY)
+        .expect("failed to populate map");
+
+    let keys = map
+        .keys()
+        .map(|key| u32::from_ne_bytes(key.try_into().unwrap()))
+        .collect::<HashSet<_>>();
+    assert_eq!(keys, (0..COUNT).collect::<HashSet<_>>());
+
+    let mut count = 0;
+    for (key, value) in map.iter().expect("failed to create iterator") {
+        let key = u32::from_ne_bytes(key.try_into().unwrap());
+        let value = u64::from_ne_bytes(value.try_into().unwrap());
+        assert_eq!(value, u64::from(key) * 2);
+        count += 1;
+    }
+    assert_eq!(count, COUNT);
+}
+
+/// Check that values of memory mappable arrays are accessible via the
+/// mapping.
+#[tag(root)]
+#[test]
+fn test_map_direct_ptr() {
+    bump_rlimit_mlock();
+
+    let mut opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        map_flags: libbpf_sys::BPF_F_MMAPABLE,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Array, Some("mmap_array"), 4, 4, 8, &opts)
+        .expect("failed to create map");
+    let key = 3u32.to_ne_bytes();
+    let () = map
+        .update(&key, &42u32.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to update map");
+
+    let values = map.direct_ptr().expect("map is not memory mapped");
+    // Values are padded to eight bytes each.
+    assert_eq!(values.len(), 8 * 8);
+    // SAFETY: The mapping covers all values and stays valid for as long
+    //         as the map handle exists.
+    let value = unsafe { values.as_ptr().cast::<u32>().add(3).read_volatile() };
+    assert_eq!(value, 42);
+    // The mapping is established only once.
+    assert_eq!(map.direct_ptr(), Some(values));
+
+    let value = map
+        .lookup(&key, MapFlags::ANY)
+        .expect("failed to look up value")
+        .expect("no value found");
+    assert_eq!(value, 42u32.to_ne_bytes());
+
+    let () = map
+        .update(&key, &1337u32.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to update map");
+    let value = map
+        .lookup(&key, MapFlags::ANY)
+        .expect("failed to look up value")
+        .expect("no value found");
+    assert_eq!(value, 1337u32.to_ne_bytes());
+
+    let value = map
+        .lookup(&8u32.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to look up value");
+    assert_eq!(value, None);
+
+    opts.map_flags = 0;
+    let map = MapHandle::create(MapType::Array, Some("array"), 4, 4, 8, &opts)
+        .expect("failed to create map");
+    assert_eq!(map.direct_ptr(), None);
+}
+
+/// Check that the values of maps backing global variables are accessed
+/// via the mapping libbpf established when loading the object.
+#[tag(root)]
+#[test]
+fn test_object_map_direct_ptr() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("runqslower.bpf.o");
+    let rodata = || {
+        obj.maps()
+            .find(|map| map.name().to_string_lossy().ends_with(".rodata"))
+            .expect("failed to find .rodata map")
+    };
+    let values = rodata().direct_ptr().expect("map is not memory mapped");
+    // All `Map` objects of the map share libbpf's mapping.
+    assert_eq!(rodata().direct_ptr(), Some(values));
+
+    let map = rodata();
+    let value = map
+        .lookup(&0u32.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to look up value")
+        .expect("no value found");
+    assert_eq!(value.len(), map.value_size() as usize);
+}
+
+/// Check that `MapCore::par_dump` reports each entry of a map exactly once.
+#[tag(root)]
+#[test]
+fn test_map_par_dump() {
+    bump_rlimit_mlock();
+
+    const COUNT: u32 = 10_000;
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+
+    for map_type in [MapType::Hash, MapType::Array] {
+        let map = MapHandle::create(map_type, Some("dump_map"), 4, 8, COUNT, &opts)
+            .expect("failed to create map");
+        let keys = (0..COUNT).flat_map(u32::to_ne_bytes).collect::<Vec<_>>();
+        let values = (0..COUNT)
+            .flat_map(|key| (u64::from(key) * 3).to_ne_bytes())
+            .collect::<Vec<_>>();
+        let () = map
+            .update_batch(&keys, &values, COUNT, MapFlags::ANY, MapFlags::ANY)
+            .expect("failed to populate map");
+
+        let mut keys = HashSet::new();
+        for entry in map.par_dump(4).expect("failed to dump map") {
+            let (key, value) = entry.expect("failed to retrieve map entry");
+            let key = u32::from_ne_bytes(key.try_into().unwrap());
+            let value = u64::from_ne_bytes(value.try_into().unwrap());
+            assert_eq!(value, u64::from(key) * 3);
+            assert!(keys.insert(key), "key {key} was reported twice");
+        }
+        assert_eq!(keys, (0..COUNT).collect::<HashSet<_>>());
+
+        let err = map.par_dump(0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
 }
 
 #[tag(root)]
@@ -323,6 +806,196 @@ fn test_object_map_delete_batch() {
     assert!(start.keys().collect::<Vec<_>>().is_empty())
 }
 
+/// Check that we can drain a map via batched lookup and delete.
+#[tag(root)]
+#[test]
+fn test_map_lookup_and_delete_batch() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Hash, Some("drain"), 4, 8, 1024, &opts)
+        .expect("failed to create map");
+    for i in 0..1000u32 {
+        let () = map
+            .update(
+                &i.to_ne_bytes(),
+                &u64::from(i * 2).to_ne_bytes(),
+                MapFlags::ANY,
+            )
+            .unwrap();
+    }
+
+    let mut entries = map
+        .lookup_and_delete_batch(64, MapFlags::ANY, MapFlags::ANY)
+        .expect("failed to drain map")
+        .map(|entry| {
+            let (key, value) = entry.expect("failed to retrieve entry");
+            let key = u32::from_ne_bytes(key.try_into().unwrap());
+            let value = u64::from_ne_bytes(value.try_into().unwrap());
+            (key, value)
+        })
+        .collect::<Vec<_>>();
+    let () = entries.sort();
+    let expected = (0..1000u32)
+        .map(|i| (i, u64::from(i * 2)))
+        .collect::<Vec<_>>();
+    assert_eq!(entries, expected);
+    assert_eq!(map.keys().count(), 0);
+
+    let map = MapHandle::create(MapType::Array, Some("drain_array"), 4, 8, 16, &opts)
+        .expect("failed to create map");
+    let err = map
+        .lookup_and_delete_batch(16, MapFlags::ANY, MapFlags::ANY)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+}
+
+/// Check that we can create an arena and access it from userspace.
+#[tag(root)]
+#[test]
+fn test_arena() {
+    bump_rlimit_mlock();
+
+    let map = arena::create(Some("arena"), 4, &ArenaOpts::default()).unwrap();
+    assert_eq!(map.map_type(), MapType::Arena);
+    let arena = Arena::from_handle(&map).unwrap();
+    assert!(arena.len() >= 4 * 4096, "{}", arena.len());
+
+    let () = arena.write(8, [1u32, 2, 3]).unwrap();
+    assert_eq!(arena.read::<[u32; 3]>(8).unwrap(), [1, 2, 3]);
+    assert_eq!(arena.read::<u32>(12).unwrap(), 2);
+
+    let counter = arena.atomic_u64(64).unwrap();
+    let _prev = counter.fetch_add(5, Ordering::Relaxed);
+    assert_eq!(arena.read::<u64>(64).unwrap(), 5);
+
+    let err = arena.read::<u64>(arena.len() - 4).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let err = arena.atomic_u64(65).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let addr = arena.as_ptr() as u64 + 100;
+    assert_eq!(arena.offset_of(addr), Some(100));
+    assert_eq!(arena.offset_of(arena.as_ptr() as u64 - 1), None);
+
+    // Accesses to arenas that may fault are rejected.
+    let opts = ArenaOpts {
+        segv_on_fault: true,
+        ..Default::default()
+    };
+    let map = arena::create(Some("segv_arena"), 4, &opts).unwrap();
+    let arena = Arena::from_handle(&map).unwrap();
+    let err = arena.read::<u64>(0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    let err = arena.write(0, 1u64).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+}
+
+/// Check that we can access task, socket, and cgroup local storage from
+/// userspace.
+#[tag(root)]
+#[test]
+fn test_map_local_storage() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("local_storage.bpf.o");
+
+    let tasks = LocalStorage::new(get_map(&obj, "task_storage")).unwrap();
+    let pidfd = pidfd_open(process::id()).unwrap();
+    assert_eq!(tasks.get(pidfd.as_fd()).unwrap(), None);
+    let () = tasks
+        .insert(pidfd.as_fd(), &42u64.to_ne_bytes(), MapFlags::NO_EXIST)
+        .unwrap();
+    assert_eq!(
+        tasks.get(pidfd.as_fd()).unwrap(),
+        Some(42u64.to_ne_bytes().to_vec())
+    );
+    let () = tasks.remove(pidfd.as_fd()).unwrap();
+    assert_eq!(tasks.get(pidfd.as_fd()).unwrap(), None);
+
+    let sockets = LocalStorage::new(get_map(&obj, "sk_storage")).unwrap();
+    let socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let () = sockets
+        .insert(socket.as_fd(), &1337u64.to_ne_bytes(), MapFlags::ANY)
+        .unwrap();
+    assert_eq!(
+        sockets.get(socket.as_fd()).unwrap(),
+        Some(1337u64.to_ne_bytes().to_vec())
+    );
+
+    let cgroups = LocalStorage::new(get_map(&obj, "cgrp_storage")).unwrap();
+    let cgroup = fs::File::open("/sys/fs/cgroup").unwrap();
+    let () = cgroups
+        .insert(cgroup.as_fd(), &7u64.to_ne_bytes(), MapFlags::ANY)
+        .unwrap();
+    assert_eq!(
+        cgroups.get(cgroup.as_fd()).unwrap(),
+        Some(7u64.to_ne_bytes().to_vec())
+    );
+    let () = cgroups.remove(cgroup.as_fd()).unwrap();
+
+    let err = CgroupStorage::new(get_map(&obj, "cgrp_storage")).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+/// Check that map contents survive being snapshotted, serialized, and
+/// restored into a different map.
+#[tag(root)]
+#[test]
+fn test_map_snapshot_restore() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Hash, Some("snapshot"), 4, 8, 64, &opts)
+        .expect("failed to create map");
+    for i in 0..32u32 {
+        let () = map
+            .update(
+                &i.to_ne_bytes(),
+                &u64::from(i * 3).to_ne_bytes(),
+                MapFlags::ANY,
+            )
+            .unwrap();
+    }
+
+    let snapshot = map.snapshot().expect("failed to snapshot map");
+    assert_eq!(snapshot.name(), "snapshot");
+    assert_eq!(snapshot.map_type(), MapType::Hash);
+    assert_eq!(snapshot.entries().len(), 32);
+    let bytes = snapshot.to_bytes();
+    let snapshot = MapSnapshot::from_bytes(&bytes).expect("failed to deserialize snapshot");
+
+    let restored = MapHandle::create(MapType::Hash, Some("restored"), 4, 8, 64, &opts)
+        .expect("failed to create map");
+    let () = restored
+        .restore(&snapshot, MapFlags::ANY)
+        .expect("failed to restore snapshot");
+    for i in 0..32u32 {
+        let value = restored
+            .lookup(&i.to_ne_bytes(), MapFlags::ANY)
+            .unwrap()
+            .expect("entry not restored");
+        assert_eq!(value, u64::from(i * 3).to_ne_bytes());
+    }
+
+    // Snapshots can only be restored into compatible maps.
+    let other = MapHandle::create(MapType::Hash, Some("other"), 4, 4, 64, &opts)
+        .expect("failed to create map");
+    let err = other.restore(&snapshot, MapFlags::ANY).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let map = MapHandle::create(MapType::Queue, Some("queue"), 0, 8, 16, &opts)
+        .expect("failed to create map");
+    let err = map.snapshot().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+}
+
 /// Test whether `MapInfo` works properly
 #[tag(root)]
 #[test]
@@ -362,6 +1035,25 @@ pub fn test_map_info() {
     assert_eq!(map_info.ifindex, 0);
 }
 
+/// Check that a `MapHandle` can be converted into the `OwnedFd` it wraps.
+#[tag(root)]
+#[test]
+fn test_map_handle_into_fd() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Hash, Some("simple_map"), 8, 64, 1024, &opts).unwrap();
+    let raw_fd = map.as_fd().as_raw_fd();
+
+    let fd = OwnedFd::from(map);
+    assert_eq!(fd.as_raw_fd(), raw_fd);
+    let map_info = MapInfo::new(fd.as_fd()).unwrap();
+    assert_eq!(map_info.name().unwrap(), "simple_map");
+}
+
 #[tag(root)]
 #[test]
 fn test_object_percpu_lookup() {
@@ -417,6 +1109,39 @@ fn test_object_percpu_update() {
     assert_eq!(vals, res);
 }
 
+/// Check that we can sum up the per-cpu values of a map.
+#[tag(root)]
+#[test]
+fn test_object_percpu_sum() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("percpu_map.bpf.o");
+    let map = get_map_mut(&mut obj, "percpu_map");
+    let key = (0_u32).to_ne_bytes();
+    let ncpu = num_possible_cpus().unwrap();
+
+    let vals = (0..ncpu)
+        .map(|i| (i as u32).to_ne_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let () = map
+        .update_percpu(&key, &vals, MapFlags::ANY)
+        .expect("failed to update map");
+
+    let sum = map
+        .lookup_percpu_sum::<u32>(&key, MapFlags::ANY)
+        .expect("failed to lookup")
+        .expect("failed to find value for key");
+    assert_eq!(sum as usize, (0..ncpu).sum::<usize>());
+
+    // The counter type has to match the value size.
+    assert!(map.lookup_percpu_sum::<u64>(&key, MapFlags::ANY).is_err());
+    // Keys out of bounds have no value.
+    let sum = map
+        .lookup_percpu_sum::<u32>(&(1_u32).to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to lookup");
+    assert_eq!(sum, None);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_percpu_invalid_update_fn() {
@@ -646,6 +1371,40 @@ fn test_object_map_mutation() {
         .is_none());
 }
 
+/// Check that we can dump a map's contents as JSON.
+#[tag(root)]
+#[test]
+fn test_object_map_dump_json() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("runqslower.bpf.o");
+    let start = get_map_mut(&mut obj, "start");
+    let key = 42u32.to_ne_bytes();
+    let value = 1337u64.to_ne_bytes();
+    start
+        .update(&key, &value, MapFlags::empty())
+        .expect("failed to write");
+
+    let dump = start.dump_json().expect("failed to dump map");
+    assert_eq!(dump.as_array().unwrap().len(), 1);
+    assert_eq!(dump[0]["key"], 42);
+    assert_eq!(dump[0]["value"], 1337);
+
+    // Global data is rendered based on the section's variables.
+    let rodata = obj
+        .maps()
+        .find(|map| map.name().to_string_lossy().ends_with(".rodata"))
+        .expect("failed to find .rodata map");
+    let dump = rodata.dump_json().expect("failed to dump map");
+    assert_eq!(dump[0]["key"], "00000000");
+    assert_eq!(dump[0]["value"]["min_us"], 0);
+    assert_eq!(dump[0]["value"]["targ_pid"], 0);
+
+    let events = get_map(&obj, "events");
+    let err = events.dump_json().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_map_lookup_flags() {
@@ -809,14 +1568,43 @@ fn test_object_loading_loaded_map_from_id() {
 
 #[tag(root)]
 #[test]
-fn test_object_programs() {
+fn test_object_programs() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("runqslower.bpf.o");
+    let _prog = get_prog_mut(&mut obj, "handle__sched_wakeup");
+    let _prog = get_prog_mut(&mut obj, "handle__sched_wakeup_new");
+    let _prog = get_prog_mut(&mut obj, "handle__sched_switch");
+    assert!(!obj.progs().any(|prog| prog.name() == OsStr::new("asdf")));
+}
+
+/// Check that we can attach a program to a cgroup by path.
+#[tag(root)]
+#[test]
+fn test_object_program_attach_cgroup_path() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("cgroup.bpf.o");
+    let prog = get_prog_mut(&mut obj, "cgroup_skb_egress");
+    let _link = prog
+        .attach_cgroup_path("/sys/fs/cgroup")
+        .expect("failed to attach to root cgroup");
+
+    let err = prog.attach_cgroup_path("/does-not-exist").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+/// Check that the verifier log is reported when a program fails to load.
+#[tag(root)]
+#[test]
+fn test_object_load_verifier_log() {
     bump_rlimit_mlock();
 
-    let mut obj = get_test_object("runqslower.bpf.o");
-    let _prog = get_prog_mut(&mut obj, "handle__sched_wakeup");
-    let _prog = get_prog_mut(&mut obj, "handle__sched_wakeup_new");
-    let _prog = get_prog_mut(&mut obj, "handle__sched_switch");
-    assert!(!obj.progs().any(|prog| prog.name() == OsStr::new("asdf")));
+    let obj = open_test_object("verifier_reject.bpf.o");
+    let err = obj.load().unwrap_err();
+    let msg = format!("{err:#}");
+    assert!(msg.contains("verifier log:"), "{msg}");
+    assert!(msg.contains("invalid mem access"), "{msg}");
 }
 
 #[tag(root)]
@@ -855,6 +1643,164 @@ fn test_object_program_pin() {
     assert!(!Path::new(path).exists());
 }
 
+/// Check that the inferred program and attach types as well as the log
+/// level can be overridden before load.
+#[tag(root)]
+#[test]
+fn test_open_program_setters() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("tc-unit.bpf.o");
+    let mut prog = open_obj
+        .prog_mut("handle_tc")
+        .expect("failed to find program");
+    assert_eq!(prog.prog_type(), ProgramType::SchedCls);
+    assert_eq!(prog.log_level(), 0);
+
+    let () = prog
+        .set_prog_type(ProgramType::SchedAct)
+        .expect("failed to set program type");
+    let () = prog.set_log_level(1).expect("failed to set log level");
+    assert_eq!(prog.prog_type(), ProgramType::SchedAct);
+    assert_eq!(prog.log_level(), 1);
+
+    let obj = open_obj.load().expect("failed to load object");
+    let prog = obj.prog("handle_tc").expect("failed to find program");
+    assert_eq!(prog.prog_type(), ProgramType::SchedAct);
+}
+
+/// Check that verifier statistics are reported for loaded programs.
+#[tag(root)]
+#[test]
+fn test_object_verifier_stats() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("runqslower.bpf.o");
+    let mut prog = open_obj
+        .prog_mut("handle__sched_wakeup")
+        .expect("failed to find program");
+    let () = prog.set_log_level(4).expect("failed to set log level");
+    let obj = open_obj.load().expect("failed to load object");
+
+    let stats = obj
+        .verifier_stats("handle__sched_wakeup")
+        .expect("failed to retrieve verifier stats");
+    assert!(stats.processed_insns.unwrap() > 0, "{stats:?}");
+    assert!(stats.verification_time.is_some(), "{stats:?}");
+    assert!(stats.peak_states.is_some(), "{stats:?}");
+
+    // Without a log we still learn the number of processed instructions
+    // from the kernel.
+    let stats = obj
+        .verifier_stats("handle__sched_switch")
+        .expect("failed to retrieve verifier stats");
+    assert!(stats.processed_insns.unwrap() > 0, "{stats:?}");
+    assert_eq!(stats.verification_time, None);
+
+    let err = obj.verifier_stats("asdf").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+/// Check that duplicated map, program, and link file descriptors refer to
+/// the original kernel objects.
+#[tag(root)]
+#[test]
+fn test_object_try_clone() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("runqslower.bpf.o");
+    let start = get_map(&obj, "start");
+    let handle = MapHandle::try_from(&start).expect("failed to create map handle");
+    let clone = handle.try_clone().expect("failed to clone map handle");
+    assert_eq!(clone.name(), handle.name());
+
+    let key = 1u32.to_ne_bytes();
+    let () = clone
+        .update(&key, &42u64.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to update map");
+    let () = drop(clone);
+    let value = handle
+        .lookup(&key, MapFlags::ANY)
+        .expect("failed to look up value")
+        .expect("no value found");
+    assert_eq!(value, 42u64.to_ne_bytes());
+
+    let prog = get_prog_mut(&mut obj, "handle__sched_wakeup");
+    let prog_fd = prog.try_clone_fd().expect("failed to duplicate program fd");
+    let prog_id = Program::id_from_fd(prog.as_fd()).expect("failed to get program id");
+    assert_eq!(Program::id_from_fd(prog_fd.as_fd()).unwrap(), prog_id);
+
+    let link = prog.attach().expect("failed to attach prog");
+    let link_fd = link.try_clone_fd().expect("failed to duplicate link fd");
+    let () = drop(link);
+
+    let mut info = libbpf_sys::bpf_link_info::default();
+    let mut len = size_of_val(&info) as u32;
+    let ret =
+        unsafe { libbpf_sys::bpf_link_get_info_by_fd(link_fd.as_raw_fd(), &mut info, &mut len) };
+    assert_eq!(ret, 0);
+    assert_eq!(info.prog_id, prog_id);
+}
+
+/// Check that `Object::attach_all` attaches all auto-attachable programs.
+#[tag(root)]
+#[test]
+fn test_object_attach_all() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("tracepoint.bpf.o");
+    let mut prog = open_obj
+        .prog_mut("handle__tracepoint_with_cookie_pb")
+        .expect("failed to find program");
+    assert!(prog.autoattach());
+    let () = prog.set_autoattach(false);
+    assert!(!prog.autoattach());
+    let obj = open_obj.load().expect("failed to load object");
+
+    let mut links = obj.attach_all().expect("failed to attach programs");
+    assert_eq!(links.len(), 2);
+    assert!(links.get("handle__tracepoint").is_some());
+    assert!(links.get("handle__tracepoint_with_cookie").is_some());
+    assert!(links.get("handle__tracepoint_with_cookie_pb").is_none());
+
+    let link = links
+        .take("handle__tracepoint")
+        .expect("failed to find link");
+    assert_eq!(links.len(), 1);
+    let () = drop(link);
+    let names = links
+        .into_iter()
+        .map(|(name, _link)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["handle__tracepoint_with_cookie"]);
+}
+
+/// Check that `ProgramMut::attach_to` dispatches based on the program
+/// type and that `ProgramMut::attach` reports missing targets.
+#[tag(root)]
+#[test]
+fn test_program_attach_to() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("xdp.bpf.o");
+    let prog = get_prog_mut(&mut obj, "xdp_filter");
+    let err = prog.attach().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    assert!(format!("{err:#}").contains("network interface"), "{err:#}");
+
+    let cgroup = fs::File::open("/sys/fs/cgroup").expect("failed to open cgroup");
+    let err = prog
+        .attach_to(AttachTarget::Cgroup(cgroup.as_fd()))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    // Index 1 is the loopback interface.
+    let link = prog
+        .attach_to(AttachTarget::Interface(1))
+        .expect("failed to attach XDP program");
+    let () = drop(link);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_link_pin() {
@@ -884,6 +1830,36 @@ fn test_object_link_pin() {
     assert!(!Path::new(path).exists());
 }
 
+/// Check that a link configured to pin itself on drop ends up pinned and
+/// stays attached.
+#[tag(root)]
+#[test]
+fn test_object_link_pin_on_drop() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("runqslower.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__sched_wakeup");
+    let mut link = prog.attach().expect("failed to attach prog");
+
+    let path = "/sys/fs/bpf/mylink_pin_on_drop";
+    assert!(!Path::new(path).exists());
+
+    defer! {
+        let _ = fs::remove_file(path);
+    }
+
+    let () = link.set_drop_policy(LinkDropPolicy::PinAt(path.into()));
+    assert_eq!(
+        link.drop_policy(),
+        &LinkDropPolicy::PinAt(PathBuf::from(path))
+    );
+    let () = drop(link);
+    assert!(Path::new(path).exists());
+
+    let link = Link::open(path).expect("failed to open pinned link");
+    assert_eq!(link.pin_path(), None);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_reuse_pined_map() {
@@ -1279,6 +2255,133 @@ fn test_object_ringbuf_with_closed_map() {
     });
 }
 
+/// Check that an `EventReader` receives events through both of its
+/// backends.
+#[tag(root)]
+#[test]
+fn test_object_event_reader() {
+    bump_rlimit_mlock();
+
+    for backend in [EventBackend::RingBuf, EventBackend::PerfBuf] {
+        let mut open_obj = open_test_object("event_reader.bpf.o");
+        let mut map = open_obj.map_mut("events").expect("failed to find map");
+        let () = EventReader::prepare_for(&mut map, backend).expect("failed to prepare map");
+
+        if backend == EventBackend::PerfBuf {
+            let mut rodata = open_obj
+                .maps_mut()
+                .find(|map| map.name().to_string_lossy().ends_with(".rodata"))
+                .expect("failed to find .rodata map");
+            // `use_perfbuf` is the only read-only variable.
+            rodata.initial_value_mut().unwrap()[0] = 1;
+        }
+
+        let mut obj = open_obj.load().expect("failed to load object");
+        let prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+        let _link = prog.attach().expect("failed to attach prog");
+
+        let map = get_map(&obj, "events");
+        let (sender, receiver) = channel();
+        let reader = EventReaderBuilder::new(&map)
+            .sample_cb(move |data: &[u8]| {
+                let mut value = 0i32;
+                plain::copy_from_bytes(&mut value, data).expect("Wrong size");
+                let () = sender.send(value).unwrap();
+            })
+            .build()
+            .expect("failed to build event reader");
+        assert_eq!(reader.backend(), backend);
+
+        let _pid = unsafe { libc::getpid() };
+        let () = reader
+            .poll(Duration::from_secs(5))
+            .expect("failed to poll event reader");
+        assert_eq!(receiver.try_recv().unwrap(), 42);
+    }
+}
+
+/// Check that we can retrieve and symbolize stack traces captured by a
+/// BPF program.
+#[tag(root)]
+#[test]
+fn test_object_stack_trace_symbolization() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("stacktrace.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let pid = unsafe { libc::getpid() } as u32;
+
+    let stack_ids = get_map(&obj, "stack_ids");
+    let id = stack_ids
+        .lookup(&pid.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to look up stack ID")
+        .expect("no stack ID recorded");
+    let id = i64::from_ne_bytes(id.try_into().unwrap());
+    assert!(id >= 0, "{id}");
+
+    let err = StackTraceMap::new(&stack_ids).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let stacks = get_map(&obj, "stacks");
+    let mut stacks = StackTraceMap::new(&stacks).expect("failed to create stack trace map");
+    assert!(!stacks.is_build_id());
+
+    let mut symbolizer = Symbolizer::new();
+    let frames = stacks
+        .frames(id as u32, StackOrigin::User(pid), &mut symbolizer)
+        .expect("failed to symbolize stack trace")
+        .expect("stack trace not found");
+    assert!(!frames.is_empty());
+    assert!(
+        frames.iter().any(|frame| frame.symbol.is_some()),
+        "{frames:?}"
+    );
+
+    let () = stacks
+        .remove(id as u32)
+        .expect("failed to remove stack trace");
+    assert_eq!(stacks.addrs(id as u32).unwrap(), None);
+}
+
+/// Check that `MapWatcher` reports updates announced via a change log.
+#[tag(root)]
+#[test]
+fn test_object_map_watch() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("map_watch.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let counts = get_map(&obj, "counts");
+    let changes = get_map(&obj, "changes");
+    let mut watcher = MapWatcher::new(&counts, &changes).expect("failed to create watcher");
+
+    let err = MapWatcher::new(&counts, &counts).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let pid = unsafe { libc::getpid() } as u32;
+    let _pid = unsafe { libc::getpid() };
+
+    // Other processes may call `getpid` as well; only look at our own
+    // updates.
+    let mut values = Vec::new();
+    while values.len() < 2 {
+        let update = watcher
+            .next()
+            .expect("watcher unexpectedly ended")
+            .expect("failed to retrieve update");
+        if update.key == pid.to_ne_bytes() {
+            let value = update.value.expect("entry unexpectedly deleted");
+            let () = values.push(u64::from_ne_bytes(value.try_into().unwrap()));
+        }
+    }
+    assert!(values[0] >= 1, "{values:?}");
+    assert!(values[1] >= values[0], "{values:?}");
+}
+
 #[tag(root)]
 #[test]
 fn test_object_user_ringbuf() {
@@ -1311,95 +2414,361 @@ fn test_object_user_ringbuf() {
     // Trigger BPF program.
     let _pid = unsafe { libc::getpid() };
 
-    // At this point, the BPF program should have run and consumed the sample in
-    // the user ring buffer, and stored the key/value in the samples map.
-    let samples_map = get_map_mut(&mut obj, "samples");
-    let key: u32 = 42;
-    let value: u32 = 1337;
-    let res = samples_map
-        .lookup(&key.to_ne_bytes(), MapFlags::ANY)
-        .expect("failed to lookup")
-        .expect("failed to find value for key");
+    // At this point, the BPF program should have run and consumed the sample in
+    // the user ring buffer, and stored the key/value in the samples map.
+    let samples_map = get_map_mut(&mut obj, "samples");
+    let key: u32 = 42;
+    let value: u32 = 1337;
+    let res = samples_map
+        .lookup(&key.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to lookup")
+        .expect("failed to find value for key");
+
+    // The value in the samples map should be the same as the value we submitted
+    assert_eq!(res.len(), size_of::<u32>());
+    let mut array = [0; size_of::<u32>()];
+    array.copy_from_slice(&res[..]);
+    assert_eq!(u32::from_ne_bytes(array), value);
+}
+
+#[tag(root)]
+#[test]
+fn test_object_user_ringbuf_reservation_too_big() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("user_ringbuf.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let _link = prog.attach().expect("failed to attach prog");
+    let urb_map = get_map_mut(&mut obj, "user_ringbuf");
+    let user_ringbuf = UserRingBuffer::new(&urb_map).expect("failed to create user ringbuf");
+    let err = user_ringbuf.reserve(1024 * 1024).unwrap_err();
+    assert!(
+        err.to_string().contains("requested size is too large"),
+        "{err:#}"
+    );
+}
+
+#[tag(root)]
+#[test]
+fn test_object_user_ringbuf_not_enough_space() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("user_ringbuf.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let _link = prog.attach().expect("failed to attach prog");
+    let urb_map = get_map_mut(&mut obj, "user_ringbuf");
+    let user_ringbuf = UserRingBuffer::new(&urb_map).expect("failed to create user ringbuf");
+    let _ = user_ringbuf
+        .reserve(1024 * 3)
+        .expect("failed to reserve space");
+    let err = user_ringbuf.reserve(1024 * 3).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("not enough space in the ring buffer"),
+        "{err:#}"
+    );
+}
+
+#[tag(root)]
+#[test]
+fn test_object_task_iter() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("taskiter.bpf.o");
+    let prog = get_prog_mut(&mut obj, "dump_pid");
+    let link = prog.attach().expect("failed to attach prog");
+    let mut iter = Iter::new(&link).expect("failed to create iterator");
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct IndexPidPair {
+        i: u32,
+        pid: i32,
+    }
+
+    unsafe impl Plain for IndexPidPair {}
+
+    let mut buf = Vec::new();
+    let bytes_read = iter
+        .read_to_end(&mut buf)
+        .expect("failed to read from iterator");
+
+    assert!(bytes_read > 0);
+    assert_eq!(bytes_read % size_of::<IndexPidPair>(), 0);
+    let items: &[IndexPidPair] =
+        plain::slice_from_bytes(buf.as_slice()).expect("Input slice cannot satisfy length");
+
+    assert!(!items.is_empty());
+    assert_eq!(items[0].i, 0);
+    assert!(items.windows(2).all(|w| w[0].i + 1 == w[1].i));
+    // Check for init
+    assert!(items.iter().any(|&item| item.pid == 1));
+}
+
+/// Check that we can restrict a task iterator to a single process and
+/// read its output in a buffered manner.
+#[tag(root)]
+#[test]
+fn test_object_task_iter_target() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("taskiter.bpf.o");
+    let prog = get_prog_mut(&mut obj, "dump_pid");
+    let pid = unsafe { libc::getpid() };
+    let link = prog
+        .attach_iter_target(IterTarget::Process(pid as u32))
+        .expect("failed to attach prog");
+    let mut iter = link.iter_create().expect("failed to create iterator");
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct IndexPidPair {
+        i: u32,
+        pid: i32,
+    }
+
+    unsafe impl Plain for IndexPidPair {}
+
+    let buffered = iter.fill_buf().expect("failed to read from iterator");
+    assert!(!buffered.is_empty());
+
+    let mut buf = Vec::new();
+    let _bytes_read = iter
+        .read_to_end(&mut buf)
+        .expect("failed to read from iterator");
+    let items: &[IndexPidPair] =
+        plain::slice_from_bytes(buf.as_slice()).expect("Input slice cannot satisfy length");
+    assert!(!items.is_empty());
+    assert!(items.iter().all(|item| item.pid == pid));
+}
+
+/// Check that we can iterate over cgroups in different orders.
+#[tag(root)]
+#[test]
+fn test_object_cgroup_iter() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("cgroupiter.bpf.o");
+    let prog = get_prog_mut(&mut obj, "dump_cgroup_id");
+    let root = fs::File::open("/sys/fs/cgroup").expect("failed to open root cgroup");
+    // The ID of a cgroup is the inode number of its directory.
+    let root_id = root.metadata().unwrap().ino();
+
+    let cgroup_ids = |target| {
+        let link = prog
+            .attach_iter_target(target)
+            .expect("failed to attach prog");
+        let mut iter = link.iter_create().expect("failed to create iterator");
+        let mut buf = Vec::new();
+        let _bytes_read = iter
+            .read_to_end(&mut buf)
+            .expect("failed to read from iterator");
+        buf.chunks_exact(size_of::<u64>())
+            .map(|id| u64::from_ne_bytes(id.try_into().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    let ids = cgroup_ids(IterTarget::CgroupFd {
+        fd: root.as_fd(),
+        order: CgroupIterOrder::SelfOnly,
+    });
+    assert_eq!(ids, vec![root_id]);
+
+    let ids = cgroup_ids(IterTarget::CgroupId {
+        id: root_id,
+        order: CgroupIterOrder::DescendantsPre,
+    });
+    assert_eq!(ids.first(), Some(&root_id));
+    let post_ids = cgroup_ids(IterTarget::CgroupId {
+        id: root_id,
+        order: CgroupIterOrder::DescendantsPost,
+    });
+    assert_eq!(post_ids.last(), Some(&root_id));
+    assert_eq!(ids.len(), post_ids.len());
+}
+
+/// Check that we can load a program assembled from raw instructions.
+#[tag(root)]
+#[test]
+fn test_program_load_from_insns() {
+    bump_rlimit_mlock();
+
+    let insns = InsnBuilder::new().mov64_imm(Reg::R0, 0).exit().build();
+    let fd = Program::load_from_insns(&insns, ProgramType::SocketFilter, "GPL")
+        .expect("failed to load program");
+    let id = Program::id_from_fd(fd.as_fd()).expect("failed to get program ID");
+    assert!(id > 0);
 
-    // The value in the samples map should be the same as the value we submitted
-    assert_eq!(res.len(), size_of::<u32>());
-    let mut array = [0; size_of::<u32>()];
-    array.copy_from_slice(&res[..]);
-    assert_eq!(u32::from_ne_bytes(array), value);
+    // A program without an exit instruction is rejected by the verifier.
+    let insns = InsnBuilder::new().mov64_imm(Reg::R0, 0).build();
+    let err = Program::load_from_insns(&insns, ProgramType::SocketFilter, "GPL").unwrap_err();
+    assert!(format!("{err:#}").contains("verifier log"), "{err:#}");
 }
 
+/// Check that we can attach a socket filter program to and detach it from
+/// a socket.
 #[tag(root)]
 #[test]
-fn test_object_user_ringbuf_reservation_too_big() {
+fn test_program_attach_socket() {
     bump_rlimit_mlock();
 
-    let mut obj = get_test_object("user_ringbuf.bpf.o");
-    let prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
-    let _link = prog.attach().expect("failed to attach prog");
-    let urb_map = get_map_mut(&mut obj, "user_ringbuf");
-    let user_ringbuf = UserRingBuffer::new(&urb_map).expect("failed to create user ringbuf");
-    let err = user_ringbuf.reserve(1024 * 1024).unwrap_err();
-    assert!(
-        err.to_string().contains("requested size is too large"),
-        "{err:#}"
-    );
+    let mut obj = get_test_object("socket_filter.bpf.o");
+    let prog = get_prog_mut(&mut obj, "drop_all");
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let () = socket
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let addr = socket.local_addr().unwrap();
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let mut buf = [0; 16];
+
+    let () = prog
+        .attach_socket(socket.as_fd())
+        .expect("failed to attach socket filter");
+    let _cnt = sender.send_to(b"dropped", addr).unwrap();
+    let _err = socket.recv(&mut buf).unwrap_err();
+
+    let () = prog
+        .detach_socket(socket.as_fd())
+        .expect("failed to detach socket filter");
+    let _cnt = sender.send_to(b"accepted", addr).unwrap();
+    let cnt = socket.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..cnt], b"accepted");
+}
+
+/// Create a UDP socket bound to `addr` with `SO_REUSEPORT` set.
+fn reuseport_udp_socket(addr: SocketAddrV4) -> UdpSocket {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    assert!(fd >= 0, "{}", io::Error::last_os_error());
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let enable: c_int = 1;
+    let rc = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            &enable as *const c_int as *const c_void,
+            size_of_val(&enable) as _,
+        )
+    };
+    assert_eq!(rc, 0, "{}", io::Error::last_os_error());
+
+    let sockaddr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as _,
+        sin_port: addr.port().to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(*addr.ip()).to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    let rc = unsafe {
+        libc::bind(
+            fd,
+            &sockaddr as *const libc::sockaddr_in as *const libc::sockaddr,
+            size_of_val(&sockaddr) as _,
+        )
+    };
+    assert_eq!(rc, 0, "{}", io::Error::last_os_error());
+    UdpSocket::from(socket)
 }
 
+/// Check that we can steer datagrams within a `SO_REUSEPORT` group.
 #[tag(root)]
 #[test]
-fn test_object_user_ringbuf_not_enough_space() {
+fn test_program_attach_reuseport() {
     bump_rlimit_mlock();
 
-    let mut obj = get_test_object("user_ringbuf.bpf.o");
-    let prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
-    let _link = prog.attach().expect("failed to attach prog");
-    let urb_map = get_map_mut(&mut obj, "user_ringbuf");
-    let user_ringbuf = UserRingBuffer::new(&urb_map).expect("failed to create user ringbuf");
-    let _ = user_ringbuf
-        .reserve(1024 * 3)
-        .expect("failed to reserve space");
-    let err = user_ringbuf.reserve(1024 * 3).unwrap_err();
-    assert!(
-        err.to_string()
-            .contains("not enough space in the ring buffer"),
-        "{err:#}"
-    );
+    let mut obj = get_test_object("reuseport.bpf.o");
+    let first = reuseport_udp_socket(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+    let addr = match first.local_addr().unwrap() {
+        SocketAddr::V4(addr) => addr,
+        SocketAddr::V6(..) => unreachable!(),
+    };
+    let second = reuseport_udp_socket(addr);
+    let () = first
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let () = second
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+
+    let sockets = ReuseportSockArray::new(get_map(&obj, "sockets")).unwrap();
+    let () = sockets.insert(0, first.as_fd()).unwrap();
+    let () = sockets.insert(1, second.as_fd()).unwrap();
+    assert!(sockets.socket_cookie(1).unwrap().is_some());
+
+    let prog = get_prog_mut(&mut obj, "select_sock");
+    let () = prog
+        .attach_reuseport(first.as_fd())
+        .expect("failed to attach reuseport program");
+
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let mut buf = [0; 16];
+    for _ in 0..8 {
+        let _cnt = sender.send_to(b"hello", addr).unwrap();
+        let cnt = second.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..cnt], b"hello");
+    }
+    let _err = first.recv(&mut buf).unwrap_err();
+
+    let () = reuseport::detach(first.as_fd()).unwrap();
 }
 
+/// Check that a map bound to a program stays alive after its last file
+/// descriptor got closed.
 #[tag(root)]
 #[test]
-fn test_object_task_iter() {
+fn test_program_bind_map() {
     bump_rlimit_mlock();
 
-    let mut obj = get_test_object("taskiter.bpf.o");
-    let prog = get_prog_mut(&mut obj, "dump_pid");
-    let link = prog.attach().expect("failed to attach prog");
-    let mut iter = Iter::new(&link).expect("failed to create iterator");
+    let insns = InsnBuilder::new().mov64_imm(Reg::R0, 0).exit().build();
+    let prog_fd = Program::load_from_insns(&insns, ProgramType::SocketFilter, "GPL")
+        .expect("failed to load program");
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Array, Some("bound_map"), 4, 8, 1, &opts)
+        .expect("failed to create map");
+    let map_id = map.info().unwrap().info.id;
 
-    #[repr(C)]
-    #[derive(Clone, Copy)]
-    struct IndexPidPair {
-        i: u32,
-        pid: i32,
-    }
+    let () = Program::bind_map_to_fd(prog_fd.as_fd(), map.as_fd()).expect("failed to bind map");
+    drop(map);
 
-    unsafe impl Plain for IndexPidPair {}
+    let map = MapHandle::from_map_id(map_id).expect("bound map should still exist");
+    assert_eq!(map.name(), "bound_map");
+}
 
-    let mut buf = Vec::new();
-    let bytes_read = iter
-        .read_to_end(&mut buf)
-        .expect("failed to read from iterator");
+/// Check that we can register, update, and unregister a struct_ops map.
+#[tag(root)]
+#[test]
+fn test_object_struct_ops() {
+    bump_rlimit_mlock();
 
-    assert!(bytes_read > 0);
-    assert_eq!(bytes_read % size_of::<IndexPidPair>(), 0);
-    let items: &[IndexPidPair] =
-        plain::slice_from_bytes(buf.as_slice()).expect("Input slice cannot satisfy length");
+    let available = || {
+        fs::read_to_string("/proc/sys/net/ipv4/tcp_available_congestion_control")
+            .expect("failed to read available congestion control algorithms")
+            .split_whitespace()
+            .any(|name| name == "libbpf_rs_ca")
+    };
+    assert!(!available());
 
-    assert!(!items.is_empty());
-    assert_eq!(items[0].i, 0);
-    assert!(items.windows(2).all(|w| w[0].i + 1 == w[1].i));
-    // Check for init
-    assert!(items.iter().any(|&item| item.pid == 1));
+    let mut obj = get_test_object("struct_ops.bpf.o");
+    let mut map = get_map_mut(&mut obj, "ca_v1");
+    let mut link = map
+        .attach_struct_ops()
+        .expect("failed to attach struct_ops");
+    assert!(available());
+
+    let map = get_map(&obj, "ca_v2");
+    let () = link
+        .update_map(&map)
+        .expect("failed to update struct_ops map");
+    assert!(available());
+
+    let () = link.detach().expect("failed to detach struct_ops link");
+    assert!(!available());
 }
 
 #[tag(root)]
@@ -1670,6 +3039,26 @@ fn test_program_helper_probes() {
     assert!(supported_res.is_err());
 }
 
+/// Check that we can detect the kernel's BPF memory accounting mode and
+/// bump `RLIMIT_MEMLOCK` accordingly.
+#[tag(root)]
+#[test]
+fn test_bump_memlock_rlimit() {
+    let accounting = memlock_accounting().expect("failed to detect memlock accounting");
+    let bumped = bump_memlock_rlimit().expect("failed to bump RLIMIT_MEMLOCK");
+    assert_eq!(accounting, bumped);
+
+    if accounting == MemlockAccounting::Rlimit {
+        let mut rlimit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        let rc = unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut rlimit) };
+        assert_eq!(rc, 0);
+        assert_eq!(rlimit.rlim_cur, libc::RLIM_INFINITY);
+    }
+}
+
 #[tag(root)]
 #[test]
 fn test_object_open_program_insns() {
@@ -1942,6 +3331,57 @@ fn test_map_pinned_status_with_pin_root_path() {
     let _ = fs::remove_dir("/sys/fs/bpf/test_namespace");
 }
 
+/// Check that maps pinned by name report the path they will be pinned at
+/// before load, reuse existing pins, and can be opted out of pinning.
+#[tag(root)]
+#[test]
+fn test_map_pin_by_name_reuse() {
+    bump_rlimit_mlock();
+
+    let pin_root = "/sys/fs/bpf/test_pin_reuse";
+    let expected_path = format!("{pin_root}/auto_pin_map");
+    defer! {
+        let _ = fs::remove_file(&expected_path);
+        let _ = fs::remove_dir(pin_root);
+    }
+
+    let obj_path = get_test_object_path("map_auto_pin.bpf.o");
+    let open = || {
+        ObjectBuilder::default()
+            .pin_root_path(pin_root)
+            .expect("root_pin_path failed")
+            .open_file(&obj_path)
+            .expect("failed to open object")
+    };
+
+    let open_obj = open();
+    let map = open_obj.map("auto_pin_map").unwrap();
+    assert_eq!(map.pin_path(), Some(OsStr::new(&expected_path)));
+    let obj = open_obj.load().expect("failed to load object");
+    assert!(!obj.map_pin_reused("auto_pin_map").unwrap());
+    assert!(Path::new(&expected_path).exists());
+
+    let obj2 = open().load().expect("failed to load object");
+    assert!(obj2.map_pin_reused("auto_pin_map").unwrap());
+    let id = |obj: &Object| {
+        let map = obj.map("auto_pin_map").unwrap();
+        MapInfo::new(map.as_fd()).unwrap().info.id
+    };
+    assert_eq!(id(&obj), id(&obj2));
+
+    let mut open_obj = open();
+    let () = open_obj
+        .map_mut("auto_pin_map")
+        .unwrap()
+        .clear_pin_path()
+        .expect("failed to clear pin path");
+    let obj3 = open_obj.load().expect("failed to load object");
+    let map = obj3.map("auto_pin_map").unwrap();
+    assert!(!map.is_pinned());
+    assert!(!obj3.map_pin_reused("auto_pin_map").unwrap());
+    assert_ne!(id(&obj), id(&obj3));
+}
+
 /// Check that we can get program fd by id and vice versa.
 #[tag(root)]
 #[test]
@@ -1995,6 +3435,94 @@ fn test_map_resize() {
     assert_eq!(new_len, len * 2);
 }
 
+/// Check that map attributes can be adjusted before load.
+#[tag(root)]
+#[test]
+fn test_open_map_setters() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("runqslower.bpf.o");
+    let mut start = open_obj
+        .maps_mut()
+        .find(|map| map.name() == OsStr::new("start"))
+        .expect("failed to find `start` map");
+    assert_eq!(start.max_entries(), 10240);
+    assert_eq!(start.key_size(), size_of::<u32>() as u32);
+    assert_eq!(start.value_size(), size_of::<u64>() as u32);
+
+    let max_entries = num_possible_cpus().unwrap() as u32 * 64;
+    let () = start
+        .set_max_entries(max_entries)
+        .expect("failed to set max entries");
+    let () = start.set_numa_node(0).expect("failed to set NUMA node");
+    assert_eq!(start.max_entries(), max_entries);
+    assert_eq!(start.numa_node(), 0);
+    assert_ne!(start.map_flags() & libbpf_sys::BPF_F_NUMA_NODE, 0);
+
+    let obj = open_obj.load().expect("failed to load object");
+    let start = get_map(&obj, "start");
+    let info = MapInfo::new(start.as_fd()).unwrap();
+    assert_eq!(info.info.max_entries, max_entries);
+    assert_ne!(info.info.map_flags & libbpf_sys::BPF_F_NUMA_NODE, 0);
+    assert_eq!(start.numa_node(), Some(0));
+    assert_eq!(MapHandle::try_from(&start).unwrap().numa_node(), Some(0));
+}
+
+/// Check that maps created outside of objects can be placed on the NUMA
+/// node of a CPU.
+#[tag(root)]
+#[test]
+fn test_map_create_numa_node() {
+    bump_rlimit_mlock();
+
+    let node = cpu_numa_node(online_cpus().unwrap()[0]).unwrap();
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        map_flags: libbpf_sys::BPF_F_NUMA_NODE,
+        numa_node: node,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::PercpuHash, Some("numa"), 4, 8, 8, &opts)
+        .expect("failed to create map");
+    assert_eq!(map.numa_node(), Some(node));
+    let info = map.info().unwrap();
+    assert_ne!(info.info.map_flags & libbpf_sys::BPF_F_NUMA_NODE, 0);
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Hash, Some("no_numa"), 4, 8, 8, &opts)
+        .expect("failed to create map");
+    assert_eq!(map.numa_node(), None);
+}
+
+/// Check that offload probes report the loopback device as lacking
+/// offload support and that loading maps bound to it reports the device.
+#[tag(root)]
+#[test]
+fn test_offload_loopback() {
+    bump_rlimit_mlock();
+
+    let lo = unsafe { libc::if_nametoindex(b"lo\0".as_ptr().cast()) };
+    assert_ne!(lo, 0);
+
+    assert!(!offload::is_prog_type_supported(lo, ProgramType::Xdp).unwrap());
+    assert!(!offload::is_prog_type_supported(lo, ProgramType::Tracepoint).unwrap());
+    assert!(!offload::is_map_type_supported(lo, MapType::Hash).unwrap());
+    assert!(!offload::is_map_type_supported(lo, MapType::RingBuf).unwrap());
+
+    let mut open_obj = open_test_object("runqslower.bpf.o");
+    let mut map = open_obj.map_mut("start").expect("failed to find map");
+    let () = map.set_map_ifindex(lo).expect("failed to set ifindex");
+    assert_eq!(map.ifindex(), lo);
+
+    let err = open_obj.load().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let msg = err.to_string();
+    assert!(msg.contains("`start` on device `lo`"), "{msg}");
+}
+
 /// Check that we are able to attach using ksyscall
 #[tag(root)]
 #[test]
@@ -2020,6 +3548,31 @@ fn test_attach_ksyscall() {
     assert_eq!(result, 1);
 }
 
+/// Check that attaching kprobes to functions of modules that are not
+/// loaded fails with a descriptive error.
+#[tag(root)]
+#[test]
+fn test_attach_kprobe_missing_module() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ksyscall.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__ksyscall");
+    let err = prog
+        .attach_kprobe(false, "no_such_module:no_such_function")
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(format!("{err:#}").contains("no_such_module"), "{err:#}");
+
+    let err = prog
+        .attach_kprobe_wait(
+            false,
+            "no_such_module:no_such_function",
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+}
+
 /// Check that we can invoke a program directly.
 #[tag(root)]
 #[test]
@@ -2047,6 +3600,53 @@ fn test_run_prog_success() {
     assert_eq!(output.return_value, value as _);
 }
 
+/// Check that we can replace a function of a loaded program with an
+/// extension program.
+#[tag(root)]
+#[test]
+fn test_attach_freplace() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("freplace.bpf.o");
+    let () = open_obj
+        .prog_mut("xdp_drop_all")
+        .expect("failed to find program")
+        .set_autoload(false);
+    let target_obj = open_obj.load().expect("failed to load object");
+    let target = target_obj
+        .prog_mut("xdp_target")
+        .expect("failed to find program");
+
+    let mut open_obj = open_test_object("freplace.bpf.o");
+    let () = open_obj
+        .prog_mut("xdp_target")
+        .expect("failed to find program")
+        .set_autoload(false);
+    let () = open_obj
+        .prog_mut("xdp_drop_all")
+        .expect("failed to find program")
+        .set_attach_target(Some(target.as_fd()), Some("xdp_verdict".to_string()))
+        .expect("failed to set attach target");
+    let mut obj = open_obj.load().expect("failed to load object");
+    let ext = get_prog_mut(&mut obj, "xdp_drop_all");
+
+    let run = || {
+        let input = ProgramInput {
+            data_in: Some(&[0; 64]),
+            ..Default::default()
+        };
+        target.test_run(input).unwrap().return_value
+    };
+
+    assert_eq!(run(), libbpf_sys::XDP_PASS);
+    let link = ext
+        .attach_freplace(target.as_fd(), "xdp_verdict")
+        .expect("failed to attach extension program");
+    assert_eq!(run(), libbpf_sys::XDP_DROP);
+    let () = drop(link);
+    assert_eq!(run(), libbpf_sys::XDP_PASS);
+}
+
 /// Check that we fail program invocation when providing insufficient arguments.
 #[tag(root)]
 #[test]
@@ -2059,3 +3659,295 @@ fn test_run_prog_fail() {
     let input = ProgramInput::default();
     let _err = prog.test_run(input).unwrap_err();
 }
+
+/// Check that we can pass a context structure to a program and retrieve
+/// the context as modified by it.
+#[tag(root)]
+#[test]
+fn test_run_prog_context() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("test_run_ctx.bpf.o");
+    let prog = get_prog_mut(&mut obj, "mark_skb");
+
+    // SAFETY: `__sk_buff` is valid for any bit pattern.
+    let mut ctx_in = unsafe { mem::zeroed::<libbpf_sys::__sk_buff>() };
+    ctx_in.priority = 41;
+    let mut ctx_out = ctx_in;
+    let input = ProgramInput {
+        data_in: Some(&[0; 64]),
+        repeat: 3,
+        ..Default::default()
+    }
+    .with_context_in(&mut ctx_in)
+    .with_context_out(&mut ctx_out);
+    let output = prog.test_run(input).unwrap();
+    assert_eq!(output.return_value, 0);
+    assert!(output.context.is_some());
+    assert_eq!(ctx_out.mark, 42);
+}
+
+/// Check that we can run an `XDP` program in live frame mode.
+#[tag(root)]
+#[test]
+fn test_run_prog_xdp_live_frames() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("xdp.bpf.o");
+    let prog = get_prog_mut(&mut obj, "xdp_filter");
+
+    let input = ProgramInput {
+        data_in: Some(&[0; 64]),
+        flags: libbpf_sys::BPF_F_TEST_XDP_LIVE_FRAMES,
+        repeat: 16,
+        batch_size: 4,
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).unwrap();
+
+    let mut data_out = [0; 64];
+    let input = ProgramInput {
+        data_in: Some(&[0; 64]),
+        data_out: Some(&mut data_out),
+        flags: libbpf_sys::BPF_F_TEST_XDP_LIVE_FRAMES,
+        ..Default::default()
+    };
+    let err = prog.test_run(input).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+/// Check that we can exercise packet processing programs with frames
+/// assembled by the `testing` module.
+#[tag(root)]
+#[test]
+fn test_run_packet() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("packet.bpf.o");
+    let src = Ipv4Addr::new(192, 0, 2, 1);
+    let dst = Ipv4Addr::new(192, 0, 2, 2);
+    let discard = PacketBuilder::new().ipv4(src, dst).udp(1024, 9).build();
+    let echo = PacketBuilder::new().ipv4(src, dst).udp(1024, 7).build();
+
+    let prog = get_prog_mut(&mut obj, "xdp_bounce");
+    run_packet(&prog, &discard)
+        .unwrap()
+        .assert_xdp_action(libbpf_sys::XDP_DROP);
+    run_packet(&prog, &echo)
+        .unwrap()
+        .assert_xdp_action(libbpf_sys::XDP_PASS)
+        .assert_packet(&echo);
+
+    let tcp = PacketBuilder::new()
+        .eth_src([0x02, 0, 0, 0, 0, 0xaa])
+        .eth_dst([0x02, 0, 0, 0, 0, 0xbb])
+        .ipv4(src, dst)
+        .ttl(8)
+        .tcp(1024, 80)
+        .build();
+    let run = run_packet(&prog, &tcp).unwrap();
+    run.assert_xdp_action(libbpf_sys::XDP_TX)
+        .assert_checksums_valid();
+    let packet = run.parse().unwrap();
+    assert_eq!(packet.ttl, Some(7));
+    assert_eq!(packet.eth_src, [0x02, 0, 0, 0, 0, 0xbb]);
+    assert_eq!(packet.eth_dst, [0x02, 0, 0, 0, 0, 0xaa]);
+
+    let prog = get_prog_mut(&mut obj, "tc_discard");
+    run_packet(&prog, &discard).unwrap().assert_tc_action(2);
+    run_packet(&prog, &echo).unwrap().assert_tc_action(0);
+}
+
+/// Check that map fixtures pre-populate maps and report the changes a
+/// program made to them.
+#[tag(root)]
+#[test]
+fn test_map_fixture() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("packet.bpf.o");
+    let src = Ipv4Addr::new(192, 0, 2, 1);
+    let dst = Ipv4Addr::new(192, 0, 2, 2);
+    let discard = PacketBuilder::new().ipv4(src, dst).udp(1024, 9).build();
+
+    // Use a handle, so that the fixture does not borrow the object.
+    let drops = MapHandle::try_from(&get_map(&obj, "drops")).unwrap();
+    let mut fixture = MapFixture::new(&drops).unwrap();
+    fixture
+        .populate([
+            (7u32.to_ne_bytes(), 1u64.to_ne_bytes()),
+            (9u32.to_ne_bytes(), 41u64.to_ne_bytes()),
+        ])
+        .unwrap();
+
+    let prog = get_prog_mut(&mut obj, "xdp_bounce");
+    run_packet(&prog, &discard)
+        .unwrap()
+        .assert_xdp_action(libbpf_sys::XDP_DROP);
+
+    fixture
+        .assert_changes([(9u32.to_ne_bytes(), 42u64.to_ne_bytes())])
+        .assert_entries([
+            (7u32.to_ne_bytes(), 1u64.to_ne_bytes()),
+            (9u32.to_ne_bytes(), 42u64.to_ne_bytes()),
+        ]);
+
+    let result = catch_unwind(|| {
+        fixture.assert_changes([(9u32.to_ne_bytes(), 43u64.to_ne_bytes())]);
+    });
+    let err = result.unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("map `drops`"), "{msg}");
+    assert!(msg.contains("mismatch"), "{msg}");
+}
+
+/// Check that we can benchmark a program.
+#[tag(root)]
+#[test]
+fn test_run_prog_benchmark() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("xdp.bpf.o");
+    let prog = get_prog_mut(&mut obj, "xdp_filter");
+
+    let input = ProgramInput {
+        data_in: Some(&[0; 64]),
+        ..Default::default()
+    };
+    let stats = prog.benchmark(input, 1000).unwrap();
+    assert!(stats.runs >= 1000, "{stats:?}");
+    assert!(stats.min <= stats.avg, "{stats:?}");
+    assert!(stats.avg <= stats.max, "{stats:?}");
+    assert!(stats.p99 <= stats.max, "{stats:?}");
+}
+
+/// Check that we can count and sample perf events, attaching a BPF
+/// program to the latter.
+#[tag(root)]
+#[test]
+fn test_object_perf_event() {
+    bump_rlimit_mlock();
+
+    let counter = PerfEventBuilder::new(SoftwareEvent::TaskClock)
+        .disabled(true)
+        .open()
+        .expect("failed to open perf event");
+    let () = counter.enable().expect("failed to enable perf event");
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(10) {
+        let () = hint::spin_loop();
+    }
+    let () = counter.disable().expect("failed to disable perf event");
+    assert!(counter.count().unwrap() > 0);
+    let () = counter.reset().expect("failed to reset perf event");
+    assert_eq!(counter.count().unwrap(), 0);
+
+    let mut obj = get_test_object("perf_event.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__perf_event");
+    let sampler = PerfEventBuilder::new(SoftwareEvent::TaskClock)
+        .sample_period(100_000)
+        .open()
+        .expect("failed to open perf event");
+    let _link = prog
+        .attach_perf_event(sampler.into())
+        .expect("failed to attach prog");
+
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(10) {
+        let () = hint::spin_loop();
+    }
+
+    let samples = get_map(&obj, "samples");
+    let count = samples
+        .lookup(&0u32.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to look up sample count")
+        .expect("no sample count found");
+    let count = u64::from_ne_bytes(count.try_into().unwrap());
+    assert!(count > 0, "{count}");
+}
+
+/// Check that we can read the counts of a group of perf events.
+#[tag(root)]
+#[test]
+fn test_perf_event_group() {
+    let mut group =
+        PerfEventGroup::new(PerfEventBuilder::new(SoftwareEvent::TaskClock).disabled(true))
+            .expect("failed to create perf event group");
+    let idx = group
+        .add(&PerfEventBuilder::new(SoftwareEvent::CpuClock))
+        .expect("failed to add perf event to group");
+    assert_eq!(idx, 1);
+    assert!(group.sibling(idx).is_some());
+    assert!(group.sibling(idx + 1).is_none());
+
+    let () = group.enable().expect("failed to enable perf event group");
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(10) {
+        let () = hint::spin_loop();
+    }
+    let () = group.disable().expect("failed to disable perf event group");
+    let counts = group.counts().expect("failed to read perf event group");
+    assert_eq!(counts.len(), 2);
+    assert!(counts.iter().all(|count| *count > 0), "{counts:?}");
+
+    let () = group.reset().expect("failed to reset perf event group");
+    assert_eq!(group.counts().unwrap(), [0, 0]);
+}
+
+/// Check that we can attach a program to perf events on all online
+/// CPUs.
+#[tag(root)]
+#[test]
+fn test_object_perf_event_all_cpus() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("perf_event.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__perf_event");
+    let mut event = PerfEventBuilder::new(SoftwareEvent::CpuClock);
+    event.pid(-1).sample_freq(1000);
+    let links = prog
+        .attach_perf_event_all_cpus(&event)
+        .expect("failed to attach prog");
+    assert_eq!(links.len(), online_cpus().unwrap().len());
+
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(50) {
+        let () = hint::spin_loop();
+    }
+
+    let samples = get_map(&obj, "samples");
+    let count = samples
+        .lookup(&0u32.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to look up sample count")
+        .expect("no sample count found");
+    let count = u64::from_ne_bytes(count.try_into().unwrap());
+    assert!(count > 0, "{count}");
+}
+
+/// Check that the `Profiler` reports samples of the current process.
+#[tag(root)]
+#[test]
+fn test_profiler() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("profile.bpf.o");
+    let prog = obj.prog_mut("profile").unwrap();
+    let counts = get_map(&obj, "counts");
+    let stacks = get_map(&obj, "stacks");
+    let mut profiler =
+        Profiler::new(&prog, &counts, &stacks, 1000).expect("failed to create profiler");
+
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(100) {
+        let () = hint::spin_loop();
+    }
+
+    let samples = profiler.drain().expect("failed to drain samples");
+    let pid = process::id();
+    assert!(
+        samples
+            .iter()
+            .any(|sample| sample.pid == pid && sample.count > 0 && sample.user_stack.is_some()),
+        "{samples:?}"
+    );
+}
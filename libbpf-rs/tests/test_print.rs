@@ -8,6 +8,7 @@
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 
+use libbpf_rs::capture_print;
 use libbpf_rs::get_print;
 use libbpf_rs::set_print;
 use libbpf_rs::ObjectBuilder;
@@ -78,3 +79,32 @@ fn test_set_and_save_print() {
     let prev = set_print(None);
     assert_eq!(prev, Some((PrintLevel::Debug, callback2 as PrintCallback)));
 }
+
+#[test]
+#[serial]
+fn test_capture_print() {
+    static FORWARDED: AtomicBool = AtomicBool::new(false);
+
+    fn callback(_: PrintLevel, _: String) {
+        FORWARDED.store(true, Ordering::Relaxed);
+    }
+
+    set_print(Some((PrintLevel::Warn, callback)));
+    let (result, messages) = capture_print(|| ObjectBuilder::default().open_file("/dev/null"));
+    assert!(result.is_err(), "Successfully loaded /dev/null?");
+    assert!(!messages.is_empty());
+    assert!(messages.iter().all(|(_, msg)| msg.starts_with("libbpf: ")));
+    assert!(messages.iter().any(|(level, _)| *level == PrintLevel::Warn));
+    // Captured messages are still forwarded to the installed callback.
+    assert!(FORWARDED.load(Ordering::Relaxed));
+
+    // Messages are captured even if printing is disabled.
+    set_print(None);
+    let (result, messages) = capture_print(|| ObjectBuilder::default().open_file("/dev/null"));
+    assert!(result.is_err(), "Successfully loaded /dev/null?");
+    assert!(!messages.is_empty());
+
+    // Nothing is captured outside of `capture_print`.
+    let ((), messages) = capture_print(|| ());
+    assert!(messages.is_empty());
+}
@@ -8,6 +8,7 @@ use scopeguard::defer;
 use test_tag::tag;
 
 use libbpf_rs::Xdp;
+use libbpf_rs::XdpFeatures;
 use libbpf_rs::XdpFlags;
 
 use crate::common::bump_rlimit_mlock;
@@ -69,3 +70,14 @@ fn test_xdp() {
         .detach(LO_IFINDEX, XdpFlags::UPDATE_IF_NOEXIST)
         .is_ok());
 }
+
+
+/// Check that we can query the XDP features of the loopback device,
+/// which lacks native XDP support.
+#[tag(root)]
+#[test]
+fn test_xdp_features() {
+    let features = Xdp::features(LO_IFINDEX).unwrap();
+    assert!(!features.features.contains(XdpFeatures::HW_OFFLOAD));
+    assert!(!features.features.contains(XdpFeatures::XSK_ZEROCOPY));
+}